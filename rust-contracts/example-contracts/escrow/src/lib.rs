@@ -60,6 +60,9 @@ enum InitError {
     ParseParams,
     /// Buyer and seller must have different accounts.
     SameBuyerSeller,
+    /// The required deposit and the arbiter fee must not add up to more than
+    /// the largest amount.
+    AmountOverflow,
 }
 
 impl From<ParseError> for InitError {
@@ -73,6 +76,15 @@ impl From<ParseError> for InitError {
 fn contract_init(ctx: &impl HasInitContext<()>) -> Result<State, InitError> {
     let init_params: InitParams = ctx.parameter_cursor().get()?;
     ensure!(init_params.buyer != init_params.seller, InitError::SameBuyerSeller);
+    // The buyer deposits the sum of the two, which must not overflow.
+    ensure!(
+        init_params
+            .required_deposit
+            .micro_ccd
+            .checked_add(init_params.arbiter_fee.micro_ccd)
+            .is_some(),
+        InitError::AmountOverflow
+    );
     let state = State {
         mode: Mode::AwaitingDeposit,
         init_params,
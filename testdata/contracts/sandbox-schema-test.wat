@@ -0,0 +1,20 @@
+(module
+
+  ;; This module tests the resource limits of schema generation in the
+  ;; sandbox. The schema function grows the memory to 4 pages and loops
+  ;; 10000 times before returning the schema of the init function of the
+  ;; contract `test`, which is the type u8.
+
+  (memory 1)
+
+  ;; The length of the schema in little endian, followed by the schema.
+  (data (i32.const 0) "\01\00\00\00\02")
+
+  (func (export "concordium_schema_function_init_test") (result i32)
+    (local $i i32)
+    (drop (memory.grow (i32.const 3)))
+    (loop $loop
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br_if $loop (i32.lt_u (local.get $i) (i32.const 10000))))
+    (i32.const 0))
+)
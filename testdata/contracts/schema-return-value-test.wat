@@ -0,0 +1,31 @@
+(module
+
+  ;; This module tests the generation of return value schemas from separate
+  ;; exports. The init function of the contract `test` has a parameter schema
+  ;; u8 and a separate return value schema u16, and the receive function
+  ;; `test.get` only has a separate return value schema u32.
+
+  (memory 1)
+
+  ;; Schemas prefixed with their length in little endian. The function schema
+  ;; at offset 0 is a parameter schema of type u8, and the types at offsets 8
+  ;; and 16 are u16 and u32.
+  (data (i32.const 0) "\02\00\00\00\00\02")
+  (data (i32.const 8) "\01\00\00\00\03")
+  (data (i32.const 16) "\01\00\00\00\04")
+
+  (func (export "init_test") (param i64) (result i32)
+    (i32.const 0))
+
+  (func (export "test.get") (param i64) (result i32)
+    (i32.const 0))
+
+  (func (export "concordium_schema_function_init_test") (result i32)
+    (i32.const 0))
+
+  (func (export "concordium_schema_function_return_init_test") (result i32)
+    (i32.const 8))
+
+  (func (export "concordium_schema_function_return_test.get") (result i32)
+    (i32.const 16))
+)
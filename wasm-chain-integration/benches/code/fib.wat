@@ -0,0 +1,18 @@
+(module
+ ;; This module computes Fibonacci numbers naively, like the `fib` example
+ ;; contract, so that execution is dominated by deeply nested function calls.
+ ;; Its purpose is to compare the ways of limiting the call depth that are
+ ;; supported by the metering transformation. This is done in the
+ ;; `benches/wasm.rs` file.
+
+  ;; compute the n-th Fibonacci number, where n is the given parameter.
+  (func $fib (export "fib") (param i64) (result i64)
+    (if (result i64) (i64.le_u (local.get 0) (i64.const 1))
+      (then (i64.const 1))
+      (else
+        (i64.add
+          (call $fib (i64.sub (local.get 0) (i64.const 1)))
+          (call $fib (i64.sub (local.get 0) (i64.const 2)))))
+    )
+  )
+)
@@ -273,6 +273,51 @@ fn mut_trie_freeze_get(b: &mut Criterion) {
     });
 }
 
+/// Benchmark saving and loading a state file, with and without compression.
+/// The throughput is reported in bytes of the saved file, and the compressed
+/// file is checked to be smaller than the plain one.
+fn state_file_save_load(b: &mut Criterion) {
+    let words = get_data();
+    let (trie, mut loader) = make_trie(&words);
+    let state: PersistentState = trie.expect("The trie is not empty.").into();
+    let mut plain = Vec::new();
+    state.save(&mut loader, &mut plain).expect("Saving succeeds.");
+    {
+        let mut group = b.benchmark_group("state file");
+        group.throughput(Throughput::Bytes(plain.len() as u64));
+        group.bench_function("save", |b| {
+            b.iter(|| {
+                let mut out = Vec::new();
+                state.save(&mut loader, &mut out).expect("Saving succeeds.");
+                out
+            })
+        });
+        group.bench_function("load", |b| {
+            b.iter(|| PersistentState::load_saved(&mut &plain[..]).expect("Loading succeeds."))
+        });
+        group.finish();
+    }
+    #[cfg(feature = "compression")]
+    {
+        let mut compressed = Vec::new();
+        state.save_compressed(&mut loader, &mut compressed).expect("Saving succeeds.");
+        assert!(compressed.len() < plain.len(), "Compression should reduce the size of the file.");
+        let mut group = b.benchmark_group("state file compressed");
+        group.throughput(Throughput::Bytes(compressed.len() as u64));
+        group.bench_function("save", |b| {
+            b.iter(|| {
+                let mut out = Vec::new();
+                state.save_compressed(&mut loader, &mut out).expect("Saving succeeds.");
+                out
+            })
+        });
+        group.bench_function("load", |b| {
+            b.iter(|| PersistentState::load_saved(&mut &compressed[..]).expect("Loading succeeds."))
+        });
+        group.finish();
+    }
+}
+
 criterion_group!(
     benches,
     btree_insert,
@@ -289,7 +334,8 @@ criterion_group!(
     mut_trie_delete,
     trie_thaw_delete,
     mut_trie_freeze,
-    mut_trie_freeze_get
+    mut_trie_freeze_get,
+    state_file_save_load
 );
 
 criterion_main!(benches);
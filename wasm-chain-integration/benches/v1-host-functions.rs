@@ -5,21 +5,30 @@
 //! relevant operations. Note that often there are other concerns than just
 //! execution time when assigning costs, so benchmarks here should generally
 //! only ensure that a sufficiently low upper bound is there.
+//!
+//! If the environment variable `EMIT_COST_TABLE` is set to a path, a candidate
+//! cost table computed from the state host functions is written to it, see
+//! `candidate_cost_table`.
 use concordium_contracts_common::{
-    Address, Amount, ChainMetadata, ContractAddress, OwnedEntrypointName, Timestamp,
+    Address, Amount, ChainMetadata, ContractAddress, OwnedEntrypointName, ReceiveName, Timestamp,
 };
 use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
 use sha2::Digest;
-use std::time::Duration;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use wasm_chain_integration::{
-    constants::MAX_ACTIVATION_FRAMES,
+    constants::{CostTable, LinearCost, MAX_ACTIVATION_FRAMES},
     v0,
     v1::{
+        self,
+        completion::{run_to_completion, HandlerResponse},
         trie::{
             self, low_level::MutableTrie, EmptyCollector, Loader, MutableState, PersistentState,
         },
-        ConcordiumAllowedImports, InstanceState, ProcessedImports, ReceiveContext, ReceiveHost,
-        StateLessReceiveHost,
+        ConcordiumAllowedImports, InstanceState, InvokeResponse, ParameterCursors,
+        ProcessedImports, ReceiveContext, ReceiveHost, ReceiveResult, StateLessReceiveHost,
     },
     InterpreterEnergy,
 };
@@ -66,12 +75,17 @@ pub fn criterion_benchmark(c: &mut Criterion) {
 
     let skeleton = parse::parse_skeleton(black_box(CONTRACT_BYTES_HOST_FUNCTIONS)).unwrap();
     let module = {
-        let mut module = validate::validate_module(&ConcordiumAllowedImports, &skeleton).unwrap();
+        let mut module = validate::validate_module(
+            &validate::ValidationConfig::LEGACY,
+            &ConcordiumAllowedImports::LATEST,
+            &skeleton,
+        )
+        .unwrap();
         module.inject_metering().expect("Metering injection should succeed.");
         module
     };
 
-    let artifact = module.compile::<ProcessedImports>().unwrap();
+    let artifact = Arc::new(module.compile::<ProcessedImports>().unwrap());
 
     let owner = concordium_contracts_common::AccountAddress([0u8; 32]);
 
@@ -93,63 +107,77 @@ pub fn criterion_benchmark(c: &mut Criterion) {
         entrypoint: OwnedEntrypointName::new_unchecked("entrypoint".into()),
     };
 
-    let mut add_benchmark = |name: &str, args: [_; 1], n, empty_state: bool| {
+    // Run the entrypoint until it runs out of energy, and return the state and
+    // the parameters so that dropping them is not counted in the benchmark.
+    let run = |name: &str,
+               args: &[machine::Value],
+               mut mutable_state: MutableState,
+               parameters: Vec<Vec<u8>>| {
+        let mut backing_store = Loader {
+            inner: Vec::new(),
+        };
+        let inner = mutable_state.get_inner(&mut backing_store);
+        let state = InstanceState::new(0, backing_store, inner);
+        let mut host = ReceiveHost::<_, Vec<u8>, _> {
+            energy: start_energy,
+            stateless: StateLessReceiveHost {
+                activation_frames: MAX_ACTIVATION_FRAMES,
+                logs: v0::Logs::new(),
+                receive_ctx: &receive_ctx,
+                return_value: Vec::new(),
+                parameters,
+                parameter_cursors: ParameterCursors::default(),
+                random_counter: 0,
+            },
+            state,
+        };
+        let r = artifact
+            .run(&mut host, name, args)
+            .expect_err("Execution should fail due to out of energy.");
+        // Should fail due to out of energy.
+        assert!(
+            matches!(r, machine::RuntimeError::OutOfEnergy),
+            "Execution did not fail due to out of energy: {}.",
+            r
+        );
+        let params = std::mem::take(&mut host.stateless.parameters);
+        // it is not ideal to drop the host here since it might contain iterators and
+        // entries which do take a bit of time to drop.
+        drop(host);
+        // return the state so that its drop is not counted in the benchmark.
+        (mutable_state, params)
+    };
+    let run = &run;
+
+    // Construct the inputs of a benchmark of a state host function. If the
+    // state is not empty the trie has the most nodes on the path to the key
+    // that is looked up.
+    let mk_inputs = |n: usize, empty_state: bool| {
         let params = vec![17u8; n];
         let inputs = if empty_state {
             Vec::new()
         } else {
             let mut inputs = Vec::with_capacity(n + 1);
-            // construct the trie with the most nodes on the path to the
-            // key we will look up.
             for i in 0..=n {
                 inputs.push((params[0..i].to_vec(), i.to_be_bytes()));
             }
             inputs
         };
-        let artifact = &artifact;
+        (params, inputs)
+    };
+
+    let mut add_benchmark = |name: &str, args: [_; 1], n, empty_state: bool| {
+        let (params, inputs) = mk_inputs(n, empty_state);
         let params = &params;
         let mk_data = || {
             let (a, b) = mk_state(&inputs);
             (a, b, vec![params.clone()])
         };
-        let receive_ctx = &receive_ctx;
         let args = &args[..];
         group.bench_function(format!("{} n = {}", name, n), move |b: &mut criterion::Bencher| {
             b.iter_batched(
                 mk_data,
-                |(mut mutable_state, _, parameters)| {
-                    let mut backing_store = Loader {
-                        inner: Vec::new(),
-                    };
-                    let inner = mutable_state.get_inner(&mut backing_store);
-                    let state = InstanceState::new(0, backing_store, inner);
-                    let mut host = ReceiveHost::<_, Vec<u8>, _> {
-                        energy: start_energy,
-                        stateless: StateLessReceiveHost {
-                            activation_frames: MAX_ACTIVATION_FRAMES,
-                            logs: v0::Logs::new(),
-                            receive_ctx,
-                            return_value: Vec::new(),
-                            parameters,
-                        },
-                        state,
-                    };
-                    let r = artifact
-                        .run(&mut host, name, args)
-                        .expect_err("Execution should fail due to out of energy.");
-                    // Should fail due to out of energy.
-                    assert!(
-                        r.downcast_ref::<wasm_chain_integration::OutOfEnergy>().is_some(),
-                        "Execution did not fail due to out of energy: {}.",
-                        r
-                    );
-                    let params = std::mem::take(&mut host.stateless.parameters);
-                    // it is not ideal to drop the host here since it might contain iterators and
-                    // entries which do take a bit of time to drop.
-                    drop(host);
-                    // return the state so that its drop is not counted in the benchmark.
-                    (mutable_state, params)
-                },
+                |(mutable_state, _, parameters)| run(name, args, mutable_state, parameters),
                 if n <= 10 {
                     BatchSize::SmallInput
                 } else {
@@ -276,6 +304,8 @@ pub fn criterion_benchmark(c: &mut Criterion) {
                             receive_ctx,
                             return_value: Vec::new(),
                             parameters,
+                            parameter_cursors: ParameterCursors::default(),
+                            random_counter: 0,
                         },
                         state,
                     };
@@ -292,18 +322,23 @@ pub fn criterion_benchmark(c: &mut Criterion) {
                                 loop {
                                     current_config.push_value(0u64); // push the response to the stack, the value is not inspected.
                                     match artifact.run_config(&mut host, current_config) {
-                                        Ok(r) => {
-                                            match r {
-                                                machine::ExecutionOutcome::Success { .. } => panic!("Execution terminated, but it was not expected to."),
-                                                machine::ExecutionOutcome::Interrupted { config,.. } => {
-                                                    current_config = config;
-                                                }
+                                        Ok(r) => match r {
+                                            machine::ExecutionOutcome::Success {
+                                                ..
+                                            } => panic!(
+                                                "Execution terminated, but it was not expected to."
+                                            ),
+                                            machine::ExecutionOutcome::Interrupted {
+                                                config,
+                                                ..
+                                            } => {
+                                                current_config = config;
                                             }
-                                        }
+                                        },
                                         Err(r) => {
                                             // Should fail due to out of energy.
                                             assert!(
-                                                r.downcast_ref::<wasm_chain_integration::OutOfEnergy>().is_some(),
+                                                matches!(r, machine::RuntimeError::OutOfEnergy),
                                                 "Execution did not fail due to out of energy: {}.",
                                                 r
                                             );
@@ -323,7 +358,7 @@ pub fn criterion_benchmark(c: &mut Criterion) {
                     // return the state so that its drop is not counted in the benchmark.
                     (mutable_state, params)
                 },
-                BatchSize::SmallInput
+                BatchSize::SmallInput,
             )
         });
     };
@@ -344,6 +379,48 @@ pub fn criterion_benchmark(c: &mut Criterion) {
         }
     }
 
+    {
+        // n is the length of the return value of the called contract. The
+        // contract is resumed with a return value of this length after each
+        // invoke until it runs out of energy. This measures receiving return
+        // values, which is charged when execution resumes.
+        for n in [0, 10, 100, 1000, 10000] {
+            let name = "hostfn.invoke_contract";
+            let params = vec![0u8; 16 + 2 + 2 + 8]; // address, empty parameter, name, amount
+            let artifact = &artifact;
+            let receive_ctx = &receive_ctx;
+            let bench_name = format!("{} return value n = {}", name, n);
+            group.bench_function(bench_name, move |b: &mut criterion::Bencher| {
+                b.iter(|| {
+                    let (mut mutable_state, backing_store) = mk_state::<Vec<u8>, [u8; 1]>(&[]);
+                    let result = run_to_completion(
+                        artifact.clone(),
+                        0,
+                        receive_ctx.clone(),
+                        ReceiveName::new_unchecked(name),
+                        &params,
+                        start_energy,
+                        &mut mutable_state,
+                        backing_store,
+                        |_| HandlerResponse::Resume {
+                            response:    InvokeResponse::Success {
+                                state_updated: false,
+                                new_balance:   Amount::from_ccd(1000),
+                                data:          Some(vec![0u8; n]),
+                            },
+                            energy_used: 0,
+                        },
+                    )
+                    .expect("Execution should not fail.");
+                    assert!(
+                        matches!(result, ReceiveResult::OutOfEnergy),
+                        "Execution should fail due to out of energy."
+                    );
+                })
+            });
+        }
+    }
+
     let mut add_crypto_primitive_benchmark = |name: &'static str, params: Vec<u8>, name_ext| {
         let args = [machine::Value::I64(0)];
         let inputs: Vec<(Vec<u8>, [u8; 1])> = Vec::new();
@@ -377,6 +454,8 @@ pub fn criterion_benchmark(c: &mut Criterion) {
                             receive_ctx,
                             return_value: Vec::new(),
                             parameters,
+                            parameter_cursors: ParameterCursors::default(),
+                            random_counter: 0,
                         },
                         state,
                     };
@@ -385,7 +464,7 @@ pub fn criterion_benchmark(c: &mut Criterion) {
                         .expect_err("Execution should fail due to out of energy.");
                     // Should fail due to out of energy.
                     assert!(
-                        r.downcast_ref::<wasm_chain_integration::OutOfEnergy>().is_some(),
+                        matches!(r, machine::RuntimeError::OutOfEnergy),
                         "Execution did not fail due to out of energy: {}.",
                         r
                     );
@@ -446,6 +525,88 @@ pub fn criterion_benchmark(c: &mut Criterion) {
         add_crypto_primitive_benchmark(name, params, None);
     }
 
+    {
+        // ecdsa verification has a fixed message length
+        use p256::ecdsa::{signature::hazmat::PrehashSigner, Signature, SigningKey, VerifyingKey};
+        let name = "hostfn.verify_ecdsa_secp256r1_signature";
+        let sk = SigningKey::from_bytes(&[7u8; 32]).expect("Valid secret key.");
+        let message = sha2::Sha256::digest(&[]);
+        let sig: Signature = sk.sign_prehash(&message).expect("Signing succeeds.");
+        let mut params = Vec::with_capacity(129);
+        params.extend_from_slice(VerifyingKey::from(&sk).to_encoded_point(true).as_bytes());
+        params.extend_from_slice(&sig.to_vec());
+        params.extend_from_slice(&message);
+        add_crypto_primitive_benchmark(name, params, None);
+    }
+
+    {
+        // BLS signatures are in G1 and public keys in G2. This must match the
+        // scheme used by the host functions.
+        let bls_sign = |sk: bls12_381::Scalar, message: &[u8]| {
+            use bls12_381::hash_to_curve::{ExpandMsgXmd, HashToCurve};
+            let hashed = <bls12_381::G1Projective as HashToCurve<
+                ExpandMsgXmd<sha2_09::Sha256>,
+            >>::hash_to_curve(message, b"BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_POP_");
+            hashed * sk
+        };
+        let bls_public_key = |sk: bls12_381::Scalar| {
+            bls12_381::G2Affine::from(bls12_381::G2Affine::generator() * sk).to_compressed()
+        };
+
+        // n is the length of the message
+        for n in [0u32, 10, 20, 50, 100, 1000, 10_000, 60_000] {
+            let name = "hostfn.bls_verify";
+            let sk = bls12_381::Scalar::from(123_456_789u64);
+            let sig = bls_sign(sk, &vec![0u8; n as usize]);
+            let mut params = Vec::with_capacity(148);
+            params.extend_from_slice(&bls_public_key(sk));
+            params.extend_from_slice(&bls12_381::G1Affine::from(sig).to_compressed());
+            params.extend_from_slice(&n.to_le_bytes());
+            add_crypto_primitive_benchmark(name, params, Some(n));
+        }
+
+        // n is the number of keys
+        for n in [1u32, 2, 5, 10, 50, 100] {
+            let name = "hostfn.bls_aggregate_verify";
+            let message = [0u8; 100];
+            let mut sig = bls12_381::G1Projective::identity();
+            let mut keys = Vec::with_capacity(96 * n as usize);
+            for i in 1..=u64::from(n) {
+                let sk = bls12_381::Scalar::from(i);
+                sig += bls_sign(sk, &message);
+                keys.extend_from_slice(&bls_public_key(sk));
+            }
+            let mut params = Vec::with_capacity(56 + keys.len());
+            params.extend_from_slice(&bls12_381::G1Affine::from(sig).to_compressed());
+            params.extend_from_slice(&n.to_le_bytes());
+            params.extend_from_slice(&(message.len() as u32).to_le_bytes());
+            params.extend_from_slice(&keys);
+            add_crypto_primitive_benchmark(name, params, Some(n));
+        }
+
+        let a = bls12_381::Scalar::from(17u64);
+        // The largest scalar, so that multiplication does the most work.
+        let b = -bls12_381::Scalar::one();
+        let g1 = |x: bls12_381::Scalar| {
+            bls12_381::G1Affine::from(bls12_381::G1Affine::generator() * x).to_compressed()
+        };
+        let g2 = |x: bls12_381::Scalar| {
+            bls12_381::G2Affine::from(bls12_381::G2Affine::generator() * x).to_compressed()
+        };
+        add_crypto_primitive_benchmark("hostfn.bls_g1_add", [g1(a), g1(b)].concat(), None);
+        add_crypto_primitive_benchmark(
+            "hostfn.bls_g1_mul",
+            [&g1(a)[..], &b.to_bytes()].concat(),
+            None,
+        );
+        add_crypto_primitive_benchmark("hostfn.bls_g2_add", [g2(a), g2(b)].concat(), None);
+        add_crypto_primitive_benchmark(
+            "hostfn.bls_g2_mul",
+            [&g2(a)[..], &b.to_bytes()].concat(),
+            None,
+        );
+    }
+
     {
         // n is the length of the data to be hashed
         for n in [0u32, 10, 20, 50, 100, 1000, 10_000, 100_000] {
@@ -474,6 +635,91 @@ pub fn criterion_benchmark(c: &mut Criterion) {
     }
 
     group.finish();
+
+    if let Ok(path) = std::env::var(COST_TABLE_ENV) {
+        let table = candidate_cost_table(start_energy, |name, args, n, empty_state| {
+            let (params, inputs) = mk_inputs(n, empty_state);
+            let args = [args];
+            let mut total = Duration::default();
+            for _ in 0..CALIBRATION_RUNS {
+                let (mutable_state, _) = mk_state(&inputs);
+                let start = Instant::now();
+                let result = run(name, &args, mutable_state, vec![params.clone()]);
+                total += start.elapsed();
+                drop(result);
+            }
+            total / CALIBRATION_RUNS
+        });
+        let json = serde_json::to_string_pretty(&table).expect("The table can be serialized.");
+        std::fs::write(&path, json).expect("Could not write the cost table.");
+    }
+}
+
+/// If this environment variable is set to a path, a candidate [CostTable] is
+/// written to that path, as JSON, after the benchmarks have run.
+const COST_TABLE_ENV: &str = "EMIT_COST_TABLE";
+
+/// Number of runs the time of each host function is averaged over when
+/// computing the candidate cost table.
+const CALIBRATION_RUNS: u32 = 20;
+
+/// Compute a candidate cost table from the time it takes to run each state
+/// host function until the given energy is used up. The benchmarks should take
+/// 1ns per unit of interpreter energy, i.e., 1ms per 1000NRG, so each cost of
+/// [CostTable::V1] is scaled by the ratio of the measured time to that. The
+/// ratio is measured once per host function, with keys or data of the size
+/// given below, so the result is a starting point to be refined by the other
+/// benchmarks. The `measure` function runs the named entrypoint with the given
+/// argument and size of data, on an empty state or not, and returns the
+/// average time it took.
+fn candidate_cost_table(
+    energy: InterpreterEnergy,
+    mut measure: impl FnMut(&str, machine::Value, usize, bool) -> Duration,
+) -> CostTable {
+    let mut factor = |name: &str, n: usize, empty_state: bool| {
+        let arg = if name == "hostfn.state_entry_read"
+            || name == "hostfn.state_entry_write"
+            || name == "hostfn.state_delete_entry"
+        {
+            machine::Value::I64(n as i64)
+        } else {
+            machine::Value::I64(0)
+        };
+        measure(name, arg, n, empty_state).as_nanos() as f64 / energy.energy as f64
+    };
+    let scale = |cost: u64, factor: f64| (cost as f64 * factor).round() as u64;
+    let scale_linear = |cost: LinearCost, factor: f64| LinearCost {
+        base:      scale(cost.base, factor),
+        per_unit:  scale(cost.per_unit, factor),
+        unit_size: cost.unit_size,
+    };
+    let mut table = CostTable::V1;
+    table.create_entry =
+        scale_linear(table.create_entry, factor("hostfn.state_create_entry", 50, false));
+    table.lookup_entry =
+        scale_linear(table.lookup_entry, factor("hostfn.state_lookup_entry", 1000, false));
+    table.entry_size = scale(table.entry_size, factor("hostfn.state_entry_size", 0, false));
+    table.read_entry =
+        scale_linear(table.read_entry, factor("hostfn.state_entry_read", 1000, false));
+    table.write_entry =
+        scale_linear(table.write_entry, factor("hostfn.state_entry_write", 1000, false));
+    table.delete_entry =
+        scale_linear(table.delete_entry, factor("hostfn.state_delete_entry", 1000, false));
+    table.new_iterator =
+        scale_linear(table.new_iterator, factor("hostfn.state_iterate_prefix", 1000, false));
+    table.delete_prefix_find =
+        scale_linear(table.delete_prefix_find, factor("hostfn.state_delete_prefix", 1000, false));
+    table.iterator_key_size =
+        scale(table.iterator_key_size, factor("hostfn.state_iterator_key_size", 0, false));
+    table.copy_from_host =
+        scale_linear(table.copy_from_host, factor("hostfn.state_iterator_key_read", 1000, false));
+    table.delete_iterator =
+        scale_linear(table.delete_iterator, factor("hostfn.state_iterator_delete", 1000, false));
+    table.iterator_next =
+        scale(table.iterator_next, factor("hostfn.state_iterator_next", 0, false));
+    table.write_output =
+        scale_linear(table.write_output, factor("hostfn.write_output", 1000, true));
+    table
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -3,7 +3,7 @@ use concordium_contracts_common::{
     Address, Amount, ChainMetadata, ContractAddress, Parameter, Timestamp,
 };
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 use wasm_chain_integration::{
     constants::MAX_ACTIVATION_FRAMES,
     utils::TestHost,
@@ -16,6 +16,7 @@ use wasm_chain_integration::{
 use wasm_transform::{
     artifact::{ArtifactNamedImport, TryFromImport},
     machine::{Host, NoInterrupt, Value},
+    metering_transformation::MeteringConfig,
     types::{FunctionType, ValueType},
     *,
 };
@@ -27,6 +28,7 @@ static CONTRACT_BYTES_INSTRUCTIONS: &[u8] = include_bytes!("./code/instruction.w
 static CONTRACT_BYTES_MEMORY_INSTRUCTIONS: &[u8] = include_bytes!("./code/memory-instruction.wasm");
 static CONTRACT_BYTES_LOOP: &[u8] = include_bytes!("./code/loop-energy.wasm");
 static CONTRACT_BYTES_HOST_FUNCTIONS: &[u8] = include_bytes!("./code/host-functions.wasm");
+static CONTRACT_BYTES_FIB: &[u8] = include_bytes!("./code/fib.wasm");
 
 struct MeteringHost {
     energy:            InterpreterEnergy,
@@ -157,7 +159,12 @@ pub fn criterion_benchmark(c: &mut Criterion) {
                 let skeleton =
                     parse::parse_skeleton(black_box(CONTRACT_BYTES_SIMPLE_GAME)).unwrap();
                 assert!(
-                    validate::validate_module(&ConcordiumAllowedImports, &skeleton).is_ok(),
+                    validate::validate_module(
+                        &validate::ValidationConfig::LEGACY,
+                        &ConcordiumAllowedImports,
+                        &skeleton
+                    )
+                    .is_ok(),
                     "Cannot validate module."
                 )
             })
@@ -167,8 +174,12 @@ pub fn criterion_benchmark(c: &mut Criterion) {
             b.iter(move || {
                 let skeleton =
                     parse::parse_skeleton(black_box(CONTRACT_BYTES_SIMPLE_GAME)).unwrap();
-                let mut module =
-                    validate::validate_module(&ConcordiumAllowedImports, &skeleton).unwrap();
+                let mut module = validate::validate_module(
+                    &validate::ValidationConfig::LEGACY,
+                    &ConcordiumAllowedImports,
+                    &skeleton,
+                )
+                .unwrap();
                 assert!(module.inject_metering().is_ok(), "Metering injection failed.")
             })
         });
@@ -177,8 +188,12 @@ pub fn criterion_benchmark(c: &mut Criterion) {
             b.iter(move || {
                 let skeleton =
                     parse::parse_skeleton(black_box(CONTRACT_BYTES_SIMPLE_GAME)).unwrap();
-                let mut module =
-                    validate::validate_module(&ConcordiumAllowedImports, &skeleton).unwrap();
+                let mut module = validate::validate_module(
+                    &validate::ValidationConfig::LEGACY,
+                    &ConcordiumAllowedImports,
+                    &skeleton,
+                )
+                .unwrap();
                 module.inject_metering().unwrap();
                 assert!(module.compile::<ProcessedImports>().is_ok(), "Compilation failed.")
             })
@@ -194,7 +209,11 @@ pub fn criterion_benchmark(c: &mut Criterion) {
         group.bench_function("validate", |b| {
             b.iter(|| {
                 let skeleton = parse::parse_skeleton(black_box(CONTRACT_BYTES_MINIMAL)).unwrap();
-                if let Err(e) = validate::validate_module(&ConcordiumAllowedImports, &skeleton) {
+                if let Err(e) = validate::validate_module(
+                    &validate::ValidationConfig::LEGACY,
+                    &ConcordiumAllowedImports,
+                    &skeleton,
+                ) {
                     panic!("{}", e)
                 }
             })
@@ -203,8 +222,12 @@ pub fn criterion_benchmark(c: &mut Criterion) {
         group.bench_function("validate + inject metering", |b| {
             b.iter(move || {
                 let skeleton = parse::parse_skeleton(black_box(CONTRACT_BYTES_MINIMAL)).unwrap();
-                let mut module =
-                    validate::validate_module(&ConcordiumAllowedImports, &skeleton).unwrap();
+                let mut module = validate::validate_module(
+                    &validate::ValidationConfig::LEGACY,
+                    &ConcordiumAllowedImports,
+                    &skeleton,
+                )
+                .unwrap();
                 assert!(module.inject_metering().is_ok(), "Metering injection failed.")
             })
         });
@@ -212,8 +235,12 @@ pub fn criterion_benchmark(c: &mut Criterion) {
         group.bench_function("validate + inject metering + compile", |b| {
             b.iter(move || {
                 let skeleton = parse::parse_skeleton(black_box(CONTRACT_BYTES_MINIMAL)).unwrap();
-                let mut module =
-                    validate::validate_module(&ConcordiumAllowedImports, &skeleton).unwrap();
+                let mut module = validate::validate_module(
+                    &validate::ValidationConfig::LEGACY,
+                    &ConcordiumAllowedImports,
+                    &skeleton,
+                )
+                .unwrap();
                 module.inject_metering().unwrap();
                 assert!(module.compile::<ProcessedImports>().is_ok(), "Compilation failed.")
             })
@@ -231,7 +258,12 @@ pub fn criterion_benchmark(c: &mut Criterion) {
             b.iter(|| {
                 let skeleton = parse::parse_skeleton(black_box(CONTRACT_BYTES_COUNTER)).unwrap();
                 assert!(
-                    validate::validate_module(&ConcordiumAllowedImports, &skeleton).is_ok(),
+                    validate::validate_module(
+                        &validate::ValidationConfig::LEGACY,
+                        &ConcordiumAllowedImports,
+                        &skeleton
+                    )
+                    .is_ok(),
                     "Cannot validate module."
                 )
             })
@@ -240,8 +272,12 @@ pub fn criterion_benchmark(c: &mut Criterion) {
         group.bench_function("validate + inject metering", |b| {
             b.iter(move || {
                 let skeleton = parse::parse_skeleton(black_box(CONTRACT_BYTES_COUNTER)).unwrap();
-                let mut module =
-                    validate::validate_module(&ConcordiumAllowedImports, &skeleton).unwrap();
+                let mut module = validate::validate_module(
+                    &validate::ValidationConfig::LEGACY,
+                    &ConcordiumAllowedImports,
+                    &skeleton,
+                )
+                .unwrap();
                 assert!(module.inject_metering().is_ok(), "Metering injection failed.")
             })
         });
@@ -249,8 +285,12 @@ pub fn criterion_benchmark(c: &mut Criterion) {
         group.bench_function("validate + inject metering + compile", |b| {
             b.iter(move || {
                 let skeleton = parse::parse_skeleton(black_box(CONTRACT_BYTES_COUNTER)).unwrap();
-                let mut module =
-                    validate::validate_module(&ConcordiumAllowedImports, &skeleton).unwrap();
+                let mut module = validate::validate_module(
+                    &validate::ValidationConfig::LEGACY,
+                    &ConcordiumAllowedImports,
+                    &skeleton,
+                )
+                .unwrap();
                 module.inject_metering().unwrap();
                 assert!(module.compile::<ProcessedImports>().is_ok(), "Compilation failed.")
             })
@@ -266,13 +306,20 @@ pub fn criterion_benchmark(c: &mut Criterion) {
         group.measurement_time(Duration::from_secs(10));
 
         let skeleton = parse::parse_skeleton(black_box(CONTRACT_BYTES_INSTRUCTIONS)).unwrap();
-        let module = validate::validate_module(&TestHost, &skeleton).unwrap();
+        let module = validate::validate_module(
+            &validate::ValidationConfig::LEGACY,
+            &TestHost::default(),
+            &skeleton,
+        )
+        .unwrap();
         let artifact = module.compile::<ArtifactNamedImport>().unwrap();
         for n in [0, 1, 10000, 100000, 200000].iter() {
             group.bench_with_input(format!("execute n = {}", n), n, |b, m| {
                 b.iter(|| {
                     assert!(
-                        artifact.run(&mut TestHost, "foo_extern", &[Value::I64(*m)]).is_ok(),
+                        artifact
+                            .run(&mut TestHost::default(), "foo_extern", &[Value::I64(*m)])
+                            .is_ok(),
                         "Precondition violation."
                     )
                 })
@@ -281,13 +328,20 @@ pub fn criterion_benchmark(c: &mut Criterion) {
 
         let skeleton =
             parse::parse_skeleton(black_box(CONTRACT_BYTES_MEMORY_INSTRUCTIONS)).unwrap();
-        let module = validate::validate_module(&TestHost, &skeleton).unwrap();
+        let module = validate::validate_module(
+            &validate::ValidationConfig::LEGACY,
+            &TestHost::default(),
+            &skeleton,
+        )
+        .unwrap();
         let artifact = module.compile::<ArtifactNamedImport>().unwrap();
         for n in [1, 10, 50, 100, 250, 500, 1000, 1024].iter() {
             group.bench_with_input(format!("allocate n = {} pages", n), n, |b, m| {
                 b.iter(|| {
                     assert!(
-                        artifact.run(&mut TestHost, "foo_extern", &[Value::I32(*m)]).is_ok(),
+                        artifact
+                            .run(&mut TestHost::default(), "foo_extern", &[Value::I32(*m)])
+                            .is_ok(),
                         "Precondition violation."
                     )
                 })
@@ -300,7 +354,9 @@ pub fn criterion_benchmark(c: &mut Criterion) {
             group.bench_with_input(format!("write u32 n = {} times", n / 4), n, |b, m| {
                 b.iter(|| {
                     assert!(
-                        artifact.run(&mut TestHost, "write_u32", &[Value::I32(*m)]).is_ok(),
+                        artifact
+                            .run(&mut TestHost::default(), "write_u32", &[Value::I32(*m)])
+                            .is_ok(),
                         "Precondition violation."
                     )
                 })
@@ -313,7 +369,9 @@ pub fn criterion_benchmark(c: &mut Criterion) {
             group.bench_with_input(format!("write u64 n = {} times", n / 8), n, |b, m| {
                 b.iter(|| {
                     assert!(
-                        artifact.run(&mut TestHost, "write_u64", &[Value::I32(*m)]).is_ok(),
+                        artifact
+                            .run(&mut TestHost::default(), "write_u64", &[Value::I32(*m)])
+                            .is_ok(),
                         "Precondition violation."
                     )
                 })
@@ -326,7 +384,9 @@ pub fn criterion_benchmark(c: &mut Criterion) {
             group.bench_with_input(format!("write u8 n  = {} times as u32", n), n, |b, m| {
                 b.iter(|| {
                     assert!(
-                        artifact.run(&mut TestHost, "write_u32_u8", &[Value::I32(*m)]).is_ok(),
+                        artifact
+                            .run(&mut TestHost::default(), "write_u32_u8", &[Value::I32(*m)])
+                            .is_ok(),
                         "Precondition violation."
                     )
                 })
@@ -339,7 +399,9 @@ pub fn criterion_benchmark(c: &mut Criterion) {
             group.bench_with_input(format!("write u8 n  = {} times as u64", n), n, |b, m| {
                 b.iter(|| {
                     assert!(
-                        artifact.run(&mut TestHost, "write_u64_u8", &[Value::I32(*m)]).is_ok(),
+                        artifact
+                            .run(&mut TestHost::default(), "write_u64_u8", &[Value::I32(*m)])
+                            .is_ok(),
                         "Precondition violation."
                     )
                 })
@@ -362,7 +424,12 @@ pub fn criterion_benchmark(c: &mut Criterion) {
             .throughput(criterion::Throughput::Elements(nrg));
 
         let skeleton = parse::parse_skeleton(black_box(CONTRACT_BYTES_LOOP)).unwrap();
-        let mut module = validate::validate_module(&TestHost, &skeleton).unwrap();
+        let mut module = validate::validate_module(
+            &validate::ValidationConfig::LEGACY,
+            &TestHost::default(),
+            &skeleton,
+        )
+        .unwrap();
         module.inject_metering().unwrap();
         let artifact = module.compile::<MeteringImport>().unwrap();
 
@@ -382,7 +449,7 @@ pub fn criterion_benchmark(c: &mut Criterion) {
                         .run(&mut host, name, args)
                         .expect_err("Precondition violation, did not terminate with an error.");
                     assert!(
-                        r.downcast_ref::<wasm_chain_integration::OutOfEnergy>().is_some(),
+                        matches!(r, machine::RuntimeError::OutOfEnergy),
                         "Execution did not fail due to out of energy: {}",
                         r
                     )
@@ -482,6 +549,108 @@ pub fn criterion_benchmark(c: &mut Criterion) {
         group.finish();
     }
 
+    // Deeply nested calls, with the call depth tracked by host functions and by a
+    // global counter injected into the module.
+    {
+        let mut group = c.benchmark_group("Call depth tracking");
+
+        group.measurement_time(Duration::from_secs(10));
+
+        let skeleton = parse::parse_skeleton(black_box(CONTRACT_BYTES_FIB)).unwrap();
+        let configs = [
+            ("host functions", MeteringConfig::LEGACY),
+            ("inline", MeteringConfig {
+                inline_call_depth: Some(MAX_ACTIVATION_FRAMES),
+            }),
+        ];
+        for (name, config) in configs.iter() {
+            let mut module = validate::validate_module(
+                &validate::ValidationConfig::LEGACY,
+                &TestHost::default(),
+                &skeleton,
+            )
+            .unwrap();
+            module.inject_metering_with_config(config).unwrap();
+            let artifact = module.compile::<MeteringImport>().unwrap();
+            for n in [10i64, 20].iter() {
+                group.bench_with_input(format!("fib n = {} ({})", n, name), n, |b, n| {
+                    b.iter(|| {
+                        let mut host = MeteringHost {
+                            energy:            InterpreterEnergy {
+                                energy: u64::MAX,
+                            },
+                            activation_frames: MAX_ACTIVATION_FRAMES,
+                        };
+                        assert!(
+                            artifact.run(&mut host, "fib", &[Value::I64(*n)]).is_ok(),
+                            "Precondition violation."
+                        )
+                    })
+                });
+            }
+        }
+
+        group.finish();
+    }
+
+    // Independent executions of the same artifact on several threads. Since the
+    // executions share nothing but the artifact, the throughput should scale with
+    // the number of threads, up to the number of cores.
+    {
+        let mut group = c.benchmark_group("Concurrent execution");
+
+        let nrg = 1000;
+
+        group.measurement_time(Duration::from_secs(10));
+
+        let skeleton = parse::parse_skeleton(black_box(CONTRACT_BYTES_LOOP)).unwrap();
+        let mut module = validate::validate_module(
+            &validate::ValidationConfig::LEGACY,
+            &TestHost::default(),
+            &skeleton,
+        )
+        .unwrap();
+        module.inject_metering().unwrap();
+        let artifact = Arc::new(module.compile::<MeteringImport>().unwrap());
+
+        for &num_threads in [1u64, 2, 4, 8].iter() {
+            // the throughput is meant to correspond to 1NRG per element, summed over
+            // all threads.
+            group.throughput(criterion::Throughput::Elements(nrg * num_threads));
+            let artifact = &artifact;
+            group.bench_function(format!("empty_loop, {} threads", num_threads), move |b| {
+                b.iter(|| {
+                    let handles = (0..num_threads)
+                        .map(|_| {
+                            let artifact = Arc::clone(artifact);
+                            std::thread::spawn(move || {
+                                let mut host = MeteringHost {
+                                    energy:            InterpreterEnergy {
+                                        energy: nrg * 1000,
+                                    },
+                                    activation_frames: MAX_ACTIVATION_FRAMES,
+                                };
+                                let r = artifact
+                                    .run(&mut host, "empty_loop", &[])
+                                    .expect_err("Execution should fail due to out of energy.");
+                                assert!(
+                                    matches!(r, machine::RuntimeError::OutOfEnergy),
+                                    "Execution did not fail due to out of energy: {}",
+                                    r
+                                )
+                            })
+                        })
+                        .collect::<Vec<_>>();
+                    for handle in handles {
+                        handle.join().expect("Execution should not panic.");
+                    }
+                })
+            });
+        }
+
+        group.finish();
+    }
+
     {
         // Benchmarks for host functions.
         // The preconditions (expected state and param) for each function are specified
@@ -498,8 +667,12 @@ pub fn criterion_benchmark(c: &mut Criterion) {
 
         let skeleton = parse::parse_skeleton(black_box(CONTRACT_BYTES_HOST_FUNCTIONS)).unwrap();
         let module = {
-            let mut module =
-                validate::validate_module(&ConcordiumAllowedImports, &skeleton).unwrap();
+            let mut module = validate::validate_module(
+                &validate::ValidationConfig::LEGACY,
+                &ConcordiumAllowedImports,
+                &skeleton,
+            )
+            .unwrap();
             module.inject_metering().expect("Metering injection should succeed.");
             module
         };
@@ -545,7 +718,7 @@ pub fn criterion_benchmark(c: &mut Criterion) {
         };
 
         let setup_receive_host =
-            |state, param| -> ReceiveHost<Parameter<'_>, &ReceiveContext<PolicyBytes<'_>>> {
+            |state, param| -> ReceiveHost<'_, Parameter<'_>, &ReceiveContext<PolicyBytes<'_>>> {
                 ReceiveHost {
                     energy: InterpreterEnergy {
                         energy: nrg * 1000,
@@ -564,18 +737,18 @@ pub fn criterion_benchmark(c: &mut Criterion) {
             // only move the reference to the artifact making this closure copyable.
             let artifact = &artifact;
             move |b: &mut criterion::Bencher| {
-                b.iter( || {
-                let mut host = setup_init_host();
-                let r = artifact
-                    .run(&mut host, name, args)
-                    .expect_err("Execution should fail due to out of energy.");
-                assert!(
-                    r.downcast_ref::<wasm_chain_integration::OutOfEnergy>().is_some(), /* Should fail due to out of energy. */
-                    "Execution did not fail due to out of energy: {}.",
-                    r
-                );
-                }
-                )
+                b.iter(|| {
+                    let mut host = setup_init_host();
+                    let r = artifact
+                        .run(&mut host, name, args)
+                        .expect_err("Execution should fail due to out of energy.");
+                    assert!(
+                        matches!(r, machine::RuntimeError::OutOfEnergy), /* Should fail due to
+                                                                          * out of energy. */
+                        "Execution did not fail due to out of energy: {}.",
+                        r
+                    );
+                })
             }
         };
 
@@ -590,11 +763,12 @@ pub fn criterion_benchmark(c: &mut Criterion) {
                         .run(&mut host, name, args)
                         .expect_err("Execution should fail due to out of energy.");
                     assert!(
-                        r.downcast_ref::<wasm_chain_integration::OutOfEnergy>().is_some(), /* Should fail due to out of energy. */
+                        matches!(r, machine::RuntimeError::OutOfEnergy), /* Should fail due to
+                                                                          * out of energy. */
                         "Execution did not fail due to out of energy: {}.",
                         r
                     );
-            })
+                })
             }
         };
 
@@ -0,0 +1,135 @@
+//! Report the instructions the interpreter spends most of its time on when
+//! executing a V0 contract.
+//!
+//! Usage:
+//!
+//! ```shell
+//! cargo run --release --features dispatch-stats --example dispatch-stats -- \
+//!     benches/counter.wasm init_counter counter.receive counter.receive_optimized
+//! ```
+//!
+//! The init function is executed with an empty parameter, and then each of the
+//! listed receive functions is executed, in order, on the state produced by
+//! the previous successful invocation. The number of instructions listed can be
+//! changed by setting the `TOP` environment variable.
+use anyhow::{bail, Context};
+use concordium_contracts_common::{
+    AccountAddress, Address, Amount, ChainMetadata, ContractAddress, Parameter, Timestamp,
+};
+use wasm_chain_integration::{
+    v0::{self, InitContext, InitResult, ReceiveContext, ReceiveResult},
+    InterpreterEnergy,
+};
+use wasm_transform::dispatch_stats::take_dispatch_stats;
+
+const ENERGY: u64 = 1_000_000_000;
+
+fn main() -> anyhow::Result<()> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let (path, init_name, receive_names) = match args.as_slice() {
+        [path, init_name, receive_names @ ..] => (path, init_name, receive_names),
+        _ => bail!("Usage: dispatch-stats <module.wasm> <init name> [<receive name> ...]"),
+    };
+    let top = match std::env::var("TOP") {
+        Ok(s) => s.parse().context("TOP must be a number.")?,
+        Err(_) => 20,
+    };
+    let source = std::fs::read(path).with_context(|| format!("Could not read {}.", path))?;
+    let artifact = wasm_transform::utils::instantiate_with_metering::<v0::ProcessedImports, _>(
+        &v0::ConcordiumAllowedImports,
+        &source,
+    )?;
+
+    let owner = AccountAddress([0u8; 32]);
+    let init_ctx: InitContext<&[u8]> = InitContext {
+        metadata:        ChainMetadata {
+            slot_time: Timestamp::from_timestamp_millis(0),
+        },
+        init_origin:     owner,
+        sender_policies: &[],
+    };
+    let receive_ctx: ReceiveContext<&[u8]> = ReceiveContext {
+        metadata: ChainMetadata {
+            slot_time: Timestamp::from_timestamp_millis(0),
+        },
+        invoker: owner,
+        self_address: ContractAddress {
+            index:    0,
+            subindex: 0,
+        },
+        self_balance: Amount::from_ccd(1000),
+        sender: Address::Account(owner),
+        owner,
+        sender_policies: &[],
+    };
+
+    let energy = InterpreterEnergy {
+        energy: ENERGY,
+    };
+    let mut state = match v0::invoke_init(
+        &artifact,
+        0,
+        &init_ctx,
+        init_name,
+        Parameter::from(&[] as &[u8]),
+        energy,
+    )? {
+        InitResult::Success {
+            state,
+            remaining_energy,
+            ..
+        } => {
+            eprintln!("{}: success, energy used {}.", init_name, ENERGY - remaining_energy);
+            state.to_vec()
+        }
+        InitResult::Reject {
+            reason,
+            ..
+        } => {
+            eprintln!("{}: rejected with reason {}.", init_name, reason);
+            Vec::new()
+        }
+        InitResult::OutOfEnergy => {
+            eprintln!("{}: out of energy.", init_name);
+            Vec::new()
+        }
+    };
+
+    for receive_name in receive_names {
+        let modified = match v0::invoke_receive(
+            &artifact,
+            0,
+            &receive_ctx,
+            &state,
+            receive_name,
+            Parameter::from(&[] as &[u8]),
+            energy,
+        )? {
+            ReceiveResult::Success {
+                state: new_state,
+                remaining_energy,
+                ..
+            } => {
+                eprintln!("{}: success, energy used {}.", receive_name, ENERGY - remaining_energy);
+                Some(new_state.into_modified_regions())
+            }
+            ReceiveResult::Reject {
+                reason,
+                ..
+            } => {
+                eprintln!("{}: rejected with reason {}.", receive_name, reason);
+                None
+            }
+            ReceiveResult::OutOfEnergy => {
+                eprintln!("{}: out of energy.", receive_name);
+                None
+            }
+        };
+        if let Some(modified) = modified {
+            state = modified.apply(&state);
+        }
+    }
+
+    print!("{}", take_dispatch_stats().report(top));
+    Ok(())
+}
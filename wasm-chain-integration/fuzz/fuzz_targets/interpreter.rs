@@ -13,7 +13,7 @@ use wasm_transform::{
     parse::parse_skeleton,
     types::Name,
     utils::parse_artifact,
-    validate::validate_module,
+    validate::{validate_module, ValidationConfig},
 };
 
 /// The energy limit on the mainnet is 3 mln NRG. However, we increase the limit
@@ -65,13 +65,19 @@ fuzz_target!(|input: RandomizedInterpreterInput<InterpreterConfig>| {
     if CONFIG.print_module_before_interpreting {
         print_module(&bytes);
     }
-    let maybe_module = validate_module(&ConcordiumAllowedImports, &parse_skeleton(&bytes).unwrap());
+    let maybe_module = validate_module(
+        &ValidationConfig::LEGACY,
+        &ConcordiumAllowedImports,
+        &parse_skeleton(&bytes).unwrap(),
+    );
     match maybe_module {
         Ok(mut module) => {
             module.inject_metering().unwrap();
             let init_names: Vec<Name> = get_inits(&module).into_iter().cloned().collect();
             let receive_names: Vec<Name> = get_receives(&module).into_iter().cloned().collect();
-            let artifact = module.compile().expect("Compilation of validated module failed.");
+            let artifact = module
+                .compile::<ProcessedImports>()
+                .expect("Compilation of validated module failed.");
             // Ensuring that artifact can be serialized and deserialized
             let mut out_buf = Vec::new();
             artifact.output(&mut out_buf).unwrap();
@@ -12,12 +12,20 @@ pub const MAX_LOG_SIZE: u32 = 512;
 
 /// Maximum number of log messages per execution.
 /// This together with the previous constant limits the amount of data that can
-/// be logged to 16kB.
+/// be logged to 32kB.
 pub const MAX_NUM_LOGS: usize = 64;
 
+/// Maximum number of parameter cursors that may be open at the same time in a
+/// V1 execution. Closed cursors do not count towards the limit.
+pub const MAX_PARAMETER_CURSORS: usize = 64;
+
 /// Base cost of a log event call.
 pub const LOG_EVENT_BASE_COST: u64 = 500;
 
+/// Cost of each byte of a logged event, in addition to the base cost. This
+/// corresponds to 1NRG per byte stored.
+pub const LOG_EVENT_BYTE_COST: u64 = 1000;
+
 /// Base cost of any action. With this cost there can be at most
 /// 3_000_000 actions produced in a block (with 3_000_000NRG maximum).
 /// A memory representation of a single action is 16 bytes, which
@@ -55,10 +63,7 @@ pub fn additional_state_size_cost(x: u64) -> u64 { x / 100 }
 
 /// Cost of logging an event of a given size.
 #[inline(always)]
-pub fn log_event_cost(x: u32) -> u64 {
-    // this corresponds to 1NRG per byte stored + base cost
-    LOG_EVENT_BASE_COST + 1000 * u64::from(x)
-}
+pub fn log_event_cost(x: u32) -> u64 { LOG_EVENT_BASE_COST + LOG_EVENT_BYTE_COST * u64::from(x) }
 
 /// Cost of a "send" action. `x` is the size of the parameter in bytes.
 #[inline(always)]
@@ -113,16 +118,49 @@ pub fn lookup_entry_cost(key_len: u32) -> u64 {
 pub const BASE_STATE_COST: u64 = 20;
 
 /// Cost of allocation of one page of memory in relation to execution cost.
-/// FIXME: It is unclear whether this is really necessary with the hard limit we
-/// have on memory use.
-/// If we keep it, the cost must be analyzed and put into perspective
+/// It is charged for each page of the initial memory, and for each page
+/// requested by `memory.grow`, even if the memory is not grown, see
+/// [InterpreterEnergy::charge_memory_alloc](crate::InterpreterEnergy::charge_memory_alloc).
+/// With [memory_accounting](crate::v1::HostFeatures::memory_accounting) V1
+/// contracts are only charged for the pages the memory is actually grown by.
 pub const MEMORY_COST_FACTOR: u32 = 100;
 
+/// Maximum number of pages of linear memory of a single V1 contract instance,
+/// including its initial memory, if
+/// [memory_accounting](crate::v1::HostFeatures::memory_accounting) is enabled.
+/// Growing the memory beyond this fails with
+/// [OutOfMemory](crate::OutOfMemory). This is half of the
+/// [maximum](wasm_transform::constants::MAX_NUM_PAGES) the interpreter
+/// supports, i.e., 16MB.
+pub const MAX_MEMORY_PAGES: u32 = 256;
+
 /// Cost of the invoke action. This is just the base cost to cover
 /// administrative costs of an invoke. Specific costs of the action are charged
 /// later by the scheduler.
 pub const INVOKE_BASE_COST: u64 = 500;
 
+/// Cost of querying whether a contract exists, or the size of its state. This
+/// covers the administrative costs of the interrupt. It is lower than
+/// [INVOKE_BASE_COST] since no contract is executed and the state of the
+/// queried contract does not have to be loaded.
+pub const CONTRACT_QUERY_COST: u64 = 200;
+
+/// Cost of querying the balance of an account. This is the same as
+/// [CONTRACT_QUERY_COST] since the query is handled by the scheduler in the
+/// same way.
+pub const ACCOUNT_BALANCE_QUERY_COST: u64 = CONTRACT_QUERY_COST;
+
+/// Cost of conversions at the current exchange rates, e.g., between energy
+/// and microCCD. This is the same as [CONTRACT_QUERY_COST] since the exchange
+/// rates are queried from the scheduler in the same way. The conversion itself
+/// is cheap.
+pub const EXCHANGE_RATES_QUERY_COST: u64 = CONTRACT_QUERY_COST;
+
+/// Cost of the upgrade host function. Like [INVOKE_BASE_COST] this only covers
+/// the administrative costs of the interrupt. Looking up the new module is
+/// charged by the scheduler.
+pub const UPGRADE_BASE_COST: u64 = INVOKE_BASE_COST;
+
 /// Cost of delete_prefix which accounts for finding the prefix. It is
 /// parametrized by the length of the key.
 #[inline(always)]
@@ -214,6 +252,21 @@ pub fn write_entry_cost(x: u32) -> u64 { 32 + u64::from(x / 8) }
 #[inline(always)]
 pub fn write_output_cost(x: u32) -> u64 { 10 + u64::from(x) }
 
+/// Cost of making the return value of an `invoke`, of the given size, available
+/// to the caller. This accounts for retaining the return value for the rest of
+/// the caller's execution, since it can be read any number of times after it
+/// is received. Reads are charged separately as copies from the host. This is
+/// only charged with
+/// [return_value_accounting](crate::v1::HostFeatures::return_value_accounting).
+#[inline(always)]
+pub fn receive_return_value_cost(x: u32) -> u64 { 10 + u64::from(x) }
+
+/// Maximum total size (in bytes) of the return values of `invoke`s that a
+/// single V1 execution can retain. Together with
+/// [receive_return_value_cost] this bounds the memory used by return values
+/// that are never read.
+pub const MAX_RETAINED_RETURN_VALUES_SIZE: usize = 1 << 24;
+
 /// Cost of adding an additional byte to the output. With the factor of 30
 /// and 3000000NRG there can be at most 100MB of output produced.
 #[inline(always)]
@@ -228,6 +281,43 @@ pub fn verify_ed25519_cost(message_len: u32) -> u64 { 100_000 + 100 * u64::from(
 /// (which are meant to be hashes) the cost is constant.
 pub const VERIFY_ECDSA_SECP256K1_COST: u64 = 100_000;
 
+/// Cost of verification of an ecdsa signature over secp256r1 (P-256). Like for
+/// secp256k1 the message is 32 bytes so the cost is constant. Verification,
+/// including decompression of the public key, is about eight times slower than
+/// with secp256k1 according to benchmarks.
+pub const VERIFY_ECDSA_SECP256R1_COST: u64 = 800_000;
+
+/// Cost of verification of a BLS signature over BLS12-381. This is dominated by
+/// the two pairings and by decompressing the public key, and is based on
+/// benchmarking relative to [verify_ed25519_cost]. Hashing the message to the
+/// curve adds a cost linear in its length.
+pub fn bls_verify_cost(message_len: u32) -> u64 { 5_500_000 + 100 * u64::from(message_len) }
+
+/// Cost of verification of a BLS signature aggregated from signatures of the
+/// same message by the given number of keys. Each key has to be decompressed
+/// and added to the aggregate key, which is then verified as a single key.
+pub fn bls_aggregate_verify_cost(num_keys: u32, message_len: u32) -> u64 {
+    bls_verify_cost(message_len) + 1_100_000 * u64::from(num_keys)
+}
+
+/// Cost of adding two compressed G1 points of BLS12-381, including
+/// decompression of the inputs and compression of the output.
+pub const BLS_G1_ADD_COST: u64 = 650_000;
+
+/// Cost of multiplying a compressed G1 point of BLS12-381 by a scalar.
+pub const BLS_G1_MUL_COST: u64 = 1_500_000;
+
+/// Cost of adding two compressed G2 points of BLS12-381, including
+/// decompression of the inputs and compression of the output.
+pub const BLS_G2_ADD_COST: u64 = 2_100_000;
+
+/// Cost of multiplying a compressed G2 point of BLS12-381 by a scalar.
+pub const BLS_G2_MUL_COST: u64 = 4_500_000;
+
+/// Cost of producing the given number of pseudo-random bytes. Every 32 bytes
+/// require a SHA3-256 digest of 57 bytes.
+pub fn get_random_cost(len: u32) -> u64 { 500 + 25 * u64::from(len) }
+
 /// Cost of computing a SHA2-256 digest of the message of the given length.
 pub fn hash_sha2_256_cost(data_len: u32) -> u64 { 500 + 7 * u64::from(data_len) }
 
@@ -236,3 +326,315 @@ pub fn hash_sha3_256_cost(data_len: u32) -> u64 { 500 + 5 * u64::from(data_len)
 
 /// Cost of computing a Keccak-256 digest of the message of the given length.
 pub fn hash_keccak_256_cost(data_len: u32) -> u64 { 500 + 5 * u64::from(data_len) }
+
+/// A cost that is linear in a number of bytes, `base + per_unit * (len /
+/// unit_size)`. Most costs are charged per byte, i.e., with a `unit_size` of
+/// 1. The `unit_size` must not be 0, which is checked when the cost is
+/// deserialized.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", try_from = "UncheckedLinearCost")]
+pub struct LinearCost {
+    pub base:      u64,
+    pub per_unit:  u64,
+    pub unit_size: u32,
+}
+
+/// A [LinearCost] as it is deserialized, before its unit size is checked.
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UncheckedLinearCost {
+    base:      u64,
+    per_unit:  u64,
+    unit_size: u32,
+}
+
+/// Error raised when deserializing a [LinearCost] with a `unit_size` of 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("The unit size of a linear cost must not be 0.")]
+pub struct ZeroUnitSize;
+
+impl std::convert::TryFrom<UncheckedLinearCost> for LinearCost {
+    type Error = ZeroUnitSize;
+
+    fn try_from(cost: UncheckedLinearCost) -> Result<Self, Self::Error> {
+        if cost.unit_size == 0 {
+            return Err(ZeroUnitSize);
+        }
+        Ok(Self {
+            base:      cost.base,
+            per_unit:  cost.per_unit,
+            unit_size: cost.unit_size,
+        })
+    }
+}
+
+impl LinearCost {
+    /// A cost of `base` plus `per_byte` for each byte.
+    pub const fn per_byte(base: u64, per_byte: u64) -> Self {
+        Self {
+            base,
+            per_unit: per_byte,
+            unit_size: 1,
+        }
+    }
+
+    /// The cost for the given number of bytes. The cost saturates at
+    /// [u64::MAX], which exceeds any energy available to an execution. A
+    /// `unit_size` of 0, which can only be constructed directly, is treated
+    /// as 1.
+    #[inline(always)]
+    pub fn cost(&self, len: u32) -> u64 {
+        let units = u64::from(len / std::cmp::max(self.unit_size, 1));
+        self.base.saturating_add(self.per_unit.saturating_mul(units))
+    }
+}
+
+/// The costs of the V1 host functions, as data. The table is set by the
+/// options of an invocation, see
+/// [InvokeOptions::costs](crate::v1::InvokeOptions::costs), so that
+/// different protocol versions can supply different tables. [CostTable::V1]
+/// is the table that corresponds to the cost functions and constants of this
+/// module, and is the default.
+///
+/// The table is serializable so that the host function benchmarks can emit a
+/// candidate table.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CostTable {
+    /// See [copy_from_host_cost].
+    pub copy_from_host:           LinearCost,
+    /// See [copy_to_host_cost].
+    pub copy_to_host:             LinearCost,
+    /// See [write_output_cost].
+    pub write_output:             LinearCost,
+    /// See [additional_output_size_cost].
+    pub additional_output_byte:   u64,
+    /// See [receive_return_value_cost].
+    pub receive_return_value:     LinearCost,
+    /// See [INVOKE_BASE_COST].
+    pub invoke:                   u64,
+    /// See [CONTRACT_QUERY_COST].
+    pub contract_query:           u64,
+    /// See [ACCOUNT_BALANCE_QUERY_COST].
+    pub account_balance_query:    u64,
+    /// See [EXCHANGE_RATES_QUERY_COST].
+    pub exchange_rates_query:     u64,
+    /// See [UPGRADE_BASE_COST].
+    pub upgrade:                  u64,
+    /// See [lookup_entry_cost].
+    pub lookup_entry:             LinearCost,
+    /// See [create_entry_cost]. This applies to keys of up to 64 bytes.
+    pub create_entry:             LinearCost,
+    /// Factor of the cost of creating an entry with a key of more than 64
+    /// bytes, which is charged quadratically in the length of the key instead
+    /// of linearly. See [create_entry_cost].
+    pub create_entry_long_key:    u64,
+    /// See [delete_entry_cost].
+    pub delete_entry:             LinearCost,
+    /// See [delete_prefix_find_cost].
+    pub delete_prefix_find:       LinearCost,
+    /// See [new_iterator_cost].
+    pub new_iterator:             LinearCost,
+    /// See [DELETE_ITERATOR_BASE_COST].
+    pub delete_iterator_base:     u64,
+    /// See [delete_iterator_cost].
+    pub delete_iterator:          LinearCost,
+    /// See [ITERATOR_KEY_SIZE_COST].
+    pub iterator_key_size:        u64,
+    /// See [ITERATOR_NEXT_COST].
+    pub iterator_next:            u64,
+    /// See [TREE_TRAVERSAL_STEP_COST].
+    pub tree_traversal_step:      u64,
+    /// See [ENTRY_SIZE_COST].
+    pub entry_size:               u64,
+    /// See [RESIZE_ENTRY_BASE_COST].
+    pub resize_entry_base:        u64,
+    /// See [additional_entry_size_cost].
+    pub additional_entry_byte:    u64,
+    /// See [read_entry_cost].
+    pub read_entry:               LinearCost,
+    /// See [write_entry_cost].
+    pub write_entry:              LinearCost,
+    /// See [verify_ed25519_cost].
+    pub verify_ed25519:           LinearCost,
+    /// See [VERIFY_ECDSA_SECP256K1_COST].
+    pub verify_ecdsa_secp256k1:   u64,
+    /// See [VERIFY_ECDSA_SECP256R1_COST].
+    pub verify_ecdsa_secp256r1:   u64,
+    /// See [bls_verify_cost].
+    pub bls_verify:               LinearCost,
+    /// Cost of each key of an aggregate verification, see
+    /// [bls_aggregate_verify_cost].
+    pub bls_aggregate_verify_key: u64,
+    /// See [BLS_G1_ADD_COST].
+    pub bls_g1_add:               u64,
+    /// See [BLS_G1_MUL_COST].
+    pub bls_g1_mul:               u64,
+    /// See [BLS_G2_ADD_COST].
+    pub bls_g2_add:               u64,
+    /// See [BLS_G2_MUL_COST].
+    pub bls_g2_mul:               u64,
+    /// See [get_random_cost].
+    pub get_random:               LinearCost,
+    /// See [hash_sha2_256_cost].
+    pub hash_sha2_256:            LinearCost,
+    /// See [hash_sha3_256_cost].
+    pub hash_sha3_256:            LinearCost,
+    /// See [hash_keccak_256_cost].
+    pub hash_keccak_256:          LinearCost,
+}
+
+impl CostTable {
+    /// The costs of the current protocol, which are those of the cost
+    /// functions and constants of this module.
+    pub const V1: CostTable = CostTable {
+        copy_from_host:           LinearCost::per_byte(10, 1),
+        copy_to_host:             LinearCost::per_byte(10, 1),
+        write_output:             LinearCost::per_byte(10, 1),
+        additional_output_byte:   30,
+        receive_return_value:     LinearCost::per_byte(10, 1),
+        invoke:                   INVOKE_BASE_COST,
+        contract_query:           CONTRACT_QUERY_COST,
+        account_balance_query:    ACCOUNT_BALANCE_QUERY_COST,
+        exchange_rates_query:     EXCHANGE_RATES_QUERY_COST,
+        upgrade:                  UPGRADE_BASE_COST,
+        // 80 + 4 * copy_from_host_cost(len) + 16 * len
+        lookup_entry:             LinearCost::per_byte(120, 20),
+        // 48 + 8 * copy_from_host_cost(len) + 100 * len
+        create_entry:             LinearCost::per_byte(128, 108),
+        create_entry_long_key:    100,
+        // 80 + 4 * copy_from_host_cost(len) + 16 * len
+        delete_entry:             LinearCost::per_byte(120, 20),
+        delete_prefix_find:       LinearCost::per_byte(0, 10),
+        new_iterator:             LinearCost::per_byte(80, 100),
+        delete_iterator_base:     DELETE_ITERATOR_BASE_COST,
+        delete_iterator:          LinearCost::per_byte(32, 32),
+        iterator_key_size:        ITERATOR_KEY_SIZE_COST,
+        iterator_next:            ITERATOR_NEXT_COST,
+        tree_traversal_step:      TREE_TRAVERSAL_STEP_COST,
+        entry_size:               ENTRY_SIZE_COST,
+        resize_entry_base:        RESIZE_ENTRY_BASE_COST,
+        additional_entry_byte:    100,
+        read_entry:               LinearCost {
+            base:      32,
+            per_unit:  1,
+            unit_size: 8,
+        },
+        write_entry:              LinearCost {
+            base:      32,
+            per_unit:  1,
+            unit_size: 8,
+        },
+        verify_ed25519:           LinearCost::per_byte(100_000, 100),
+        verify_ecdsa_secp256k1:   VERIFY_ECDSA_SECP256K1_COST,
+        verify_ecdsa_secp256r1:   VERIFY_ECDSA_SECP256R1_COST,
+        bls_verify:               LinearCost::per_byte(5_500_000, 100),
+        bls_aggregate_verify_key: 1_100_000,
+        bls_g1_add:               BLS_G1_ADD_COST,
+        bls_g1_mul:               BLS_G1_MUL_COST,
+        bls_g2_add:               BLS_G2_ADD_COST,
+        bls_g2_mul:               BLS_G2_MUL_COST,
+        get_random:               LinearCost::per_byte(500, 25),
+        hash_sha2_256:            LinearCost::per_byte(500, 7),
+        hash_sha3_256:            LinearCost::per_byte(500, 5),
+        hash_keccak_256:          LinearCost::per_byte(500, 5),
+    };
+
+    /// Cost of creating an entry with a key of the given length. Keys of up
+    /// to 64 bytes are charged linearly, and longer keys quadratically, in the
+    /// same way as [create_entry_cost].
+    pub fn create_entry_cost(&self, key_len: u32) -> u64 {
+        if key_len <= 64 {
+            self.create_entry.cost(key_len)
+        } else {
+            let len = u64::from(key_len);
+            let linear = self.create_entry.per_unit.saturating_sub(self.create_entry_long_key);
+            match self.create_entry_long_key.checked_mul(len * len) {
+                Some(q) => self
+                    .create_entry
+                    .base
+                    .saturating_add(linear.saturating_mul(len))
+                    .saturating_add(q / 64),
+                None => u64::MAX,
+            }
+        }
+    }
+
+    /// Cost of verifying a BLS signature aggregated from signatures by the
+    /// given number of keys, see [bls_aggregate_verify_cost].
+    pub fn bls_aggregate_verify_cost(&self, num_keys: u32, message_len: u32) -> u64 {
+        self.bls_verify
+            .cost(message_len)
+            .saturating_add(self.bls_aggregate_verify_key.saturating_mul(u64::from(num_keys)))
+    }
+}
+
+impl Default for CostTable {
+    fn default() -> Self { Self::V1 }
+}
+
+/// The effective limits of the execution engine for contracts of a given
+/// version, as returned by [limits]. Limits that do not apply to contracts of
+/// the version are [None]. This is intended for tooling that displays the
+/// limits, so that the numbers come from the engine itself.
+#[derive(serde::Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Limits {
+    /// Maximum size of the contract state in bytes. Only V0 contracts have a
+    /// limit on the size of the whole state.
+    pub max_state_size:        Option<u32>,
+    /// Maximum size of the value of a single state entry in bytes.
+    pub max_state_entry_size:  Option<usize>,
+    /// Maximum size of the key of a single state entry in bytes.
+    pub max_state_key_size:    Option<usize>,
+    /// Maximum size of the parameter of an init or receive function.
+    pub max_parameter_size:    usize,
+    /// Maximum size of the return value of an init or receive function.
+    pub max_return_value_size: Option<u32>,
+    /// Maximum size of a single log message.
+    pub max_log_size:          u32,
+    /// Maximum number of log messages of an execution.
+    pub max_num_logs:          usize,
+    /// Maximum number of pages of linear memory. A page is 64kB.
+    pub max_memory_pages:      u32,
+    /// Maximum number of nested function calls in an execution.
+    pub max_call_depth:        u32,
+    /// Maximum length of the name of an exported function.
+    pub max_export_name_len:   usize,
+    /// Maximum number of parameter cursors that may be open at the same time.
+    pub max_parameter_cursors: Option<usize>,
+}
+
+/// Get the effective limits for contracts of the given version.
+pub fn limits(version: crate::utils::WasmVersion) -> Limits {
+    match version {
+        crate::utils::WasmVersion::V0 => Limits {
+            max_state_size:        Some(MAX_CONTRACT_STATE),
+            max_state_entry_size:  None,
+            max_state_key_size:    None,
+            max_parameter_size:    MAX_PARAMETER_SIZE,
+            max_return_value_size: None,
+            max_log_size:          MAX_LOG_SIZE,
+            max_num_logs:          MAX_NUM_LOGS,
+            max_memory_pages:      wasm_transform::constants::MAX_NUM_PAGES,
+            max_call_depth:        MAX_ACTIVATION_FRAMES,
+            max_export_name_len:   crate::v0::MAX_EXPORT_NAME_LEN,
+            max_parameter_cursors: None,
+        },
+        crate::utils::WasmVersion::V1 => Limits {
+            max_state_size:        None,
+            max_state_entry_size:  Some(MAX_ENTRY_SIZE),
+            max_state_key_size:    Some(MAX_KEY_SIZE),
+            max_parameter_size:    MAX_PARAMETER_SIZE,
+            // The return value is truncated to this size when it is written.
+            max_return_value_size: Some(MAX_CONTRACT_STATE),
+            max_log_size:          MAX_LOG_SIZE,
+            max_num_logs:          MAX_NUM_LOGS,
+            // With memory accounting instances are limited to MAX_MEMORY_PAGES.
+            max_memory_pages:      wasm_transform::constants::MAX_NUM_PAGES,
+            max_call_depth:        MAX_ACTIVATION_FRAMES,
+            max_export_name_len:   crate::v1::MAX_EXPORT_NAME_LEN,
+            max_parameter_cursors: Some(MAX_PARAMETER_CURSORS),
+        },
+    }
+}
@@ -0,0 +1,241 @@
+//! Regression tests over a corpus of small modules that reproduce past
+//! problems, e.g., modules using floating point types, exporting functions
+//! with invalid names, or accessing memory at addresses that overflow.
+//!
+//! The modules are in [CORPUS_DIR], and [EXPECTATIONS_FILE] lists, for each
+//! of them, the version it is deployed with, the init function to run, if
+//! any, and the expected outcome. The outcome is the exact parse or
+//! validation error if the module is rejected, and otherwise the result of
+//! running the init function with [ENERGY] interpreter energy.
+//!
+//! To add a module, e.g., one from a field report, copy it to [CORPUS_DIR],
+//! together with its source if available, and add a line to
+//! [EXPECTATIONS_FILE] with only the first three columns. Running the tests
+//! with the environment variable `UPDATE_CORPUS_EXPECTATIONS` set then fills
+//! in the current outcome of all modules, which should be reviewed before
+//! committing.
+use crate::{utils::WasmVersion, v0, v1, InterpreterEnergy};
+use anyhow::{bail, ensure, Context};
+use concordium_contracts_common::{AccountAddress, ChainMetadata, Timestamp};
+use std::{fmt::Write, sync::Arc};
+use wasm_transform::{
+    machine::RuntimeError, parse::ParseError, utils::instantiate_with_metering,
+    validate::ValidationError,
+};
+
+/// Directory, relative to the crate root, that contains the modules.
+const CORPUS_DIR: &str = "test-data/corpus";
+
+/// File with the expected outcome of each module. Each line is of the form
+/// `<module> <version> <init function or -> <outcome>`.
+const EXPECTATIONS_FILE: &str = "test-data/corpus/expectations.txt";
+
+/// Interpreter energy available to init functions.
+const ENERGY: u64 = 1_000_000_000;
+
+/// A module of the corpus and its expected outcome.
+struct Expectation {
+    module:  String,
+    version: WasmVersion,
+    /// The init function to run, if the module is valid.
+    init:    Option<String>,
+    /// Missing for modules that have just been added.
+    outcome: Option<String>,
+}
+
+fn parse_expectations(contents: &str) -> anyhow::Result<Vec<Expectation>> {
+    let mut out = Vec::new();
+    for line in contents.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        let parts = line.splitn(4, ' ').collect::<Vec<_>>();
+        ensure!(parts.len() >= 3, "Malformed expectation line: {}", line);
+        out.push(Expectation {
+            module:  parts[0].to_owned(),
+            version: parts[1].parse()?,
+            init:    if parts[2] == "-" {
+                None
+            } else {
+                Some(parts[2].to_owned())
+            },
+            outcome: parts.get(3).map(|o| o.to_string()),
+        });
+    }
+    Ok(out)
+}
+
+fn render_expectations(expectations: &[Expectation]) -> String {
+    let mut out = String::from(
+        "# Expected outcome of each module in the corpus of\n# \
+         wasm-chain-integration/src/corpus_tests.rs.\n# Columns: module, version, init function \
+         or -, outcome.\n",
+    );
+    for e in expectations {
+        let version = match e.version {
+            WasmVersion::V0 => "v0",
+            WasmVersion::V1 => "v1",
+        };
+        writeln!(
+            out,
+            "{} {} {} {}",
+            e.module,
+            version,
+            e.init.as_deref().unwrap_or("-"),
+            e.outcome.as_deref().unwrap_or("")
+        )
+        .expect("Writing to a string succeeds.");
+    }
+    out
+}
+
+/// Describe an error by its type, so that a change in the kind of error is
+/// detected even if its message stays the same.
+fn describe_error(e: &anyhow::Error) -> String {
+    if let Some(e) = e.downcast_ref::<ParseError>() {
+        format!("parse error: {:?}", e)
+    } else if let Some(e) = e.downcast_ref::<ValidationError>() {
+        format!("validation error: {:?}", e)
+    } else if let Some(RuntimeError::Trap(reason)) = e.downcast_ref::<RuntimeError>() {
+        format!("trap: {:?}", reason)
+    } else {
+        format!("error: {}", e)
+    }
+}
+
+fn init_context() -> v0::InitContext<&'static [u8]> {
+    v0::InitContext {
+        metadata:        ChainMetadata {
+            slot_time: Timestamp::from_timestamp_millis(0),
+        },
+        init_origin:     AccountAddress([0; 32]),
+        sender_policies: &[],
+    }
+}
+
+/// Deploy the module as it is done on chain, and run the init function if
+/// given.
+fn outcome(version: WasmVersion, bytes: &[u8], init: Option<&str>) -> String {
+    let energy = InterpreterEnergy::from(ENERGY);
+    match version {
+        WasmVersion::V0 => {
+            let artifact = match instantiate_with_metering::<v0::ProcessedImports, _>(
+                &v0::ConcordiumAllowedImports,
+                bytes,
+            ) {
+                Ok(artifact) => artifact,
+                Err(e) => return describe_error(&e),
+            };
+            let init = match init {
+                Some(init) => init,
+                None => return "valid".into(),
+            };
+            match v0::invoke_init(&artifact, 0, init_context(), init, (&[] as &[u8]).into(), energy)
+            {
+                Ok(v0::InitResult::Success {
+                    ..
+                }) => "success".into(),
+                Ok(v0::InitResult::Reject {
+                    reason,
+                    ..
+                }) => format!("reject: {}", reason),
+                Ok(v0::InitResult::OutOfEnergy) => "out of energy".into(),
+                Err(e) => describe_error(&e),
+            }
+        }
+        WasmVersion::V1 => {
+            let artifact = match instantiate_with_metering::<v1::ProcessedImports, _>(
+                &v1::ConcordiumAllowedImports::LATEST,
+                bytes,
+            ) {
+                Ok(artifact) => artifact,
+                Err(e) => return describe_error(&e),
+            };
+            let init = match init {
+                Some(init) => init,
+                None => return "valid".into(),
+            };
+            let loader = v1::trie::Loader {
+                inner: Vec::<u8>::new(),
+            };
+            match v1::invoke_init(
+                Arc::new(artifact),
+                0,
+                init_context(),
+                init,
+                &[],
+                energy,
+                loader,
+                v1::InvokeOptions::default(),
+            ) {
+                Ok(v1::InitResult::Success {
+                    ..
+                }) => "success".into(),
+                Ok(v1::InitResult::Reject {
+                    reason,
+                    ..
+                }) => format!("reject: {}", reason),
+                Ok(v1::InitResult::Trap {
+                    error,
+                    ..
+                }) => describe_error(&error),
+                Ok(v1::InitResult::Interrupt {
+                    interrupt,
+                    ..
+                }) => format!("interrupt: {}", interrupt),
+                Ok(v1::InitResult::OutOfEnergy) => "out of energy".into(),
+                Err(e) => describe_error(&e),
+            }
+        }
+    }
+}
+
+#[test]
+/// Check that each module has the expected outcome, or record the outcomes if
+/// requested.
+fn corpus_outcomes_match_expectations() -> anyhow::Result<()> {
+    let mut expectations = parse_expectations(&std::fs::read_to_string(EXPECTATIONS_FILE)?)?;
+    let mut mismatches = String::new();
+    for e in expectations.iter_mut() {
+        let path = format!("{}/{}", CORPUS_DIR, e.module);
+        let bytes = std::fs::read(&path).with_context(|| format!("Cannot read {}.", path))?;
+        let actual = outcome(e.version, &bytes, e.init.as_deref());
+        if e.outcome.as_deref() != Some(actual.as_str()) {
+            writeln!(
+                mismatches,
+                "{}: expected {}, got {}",
+                e.module,
+                e.outcome.as_deref().unwrap_or("nothing"),
+                actual
+            )?;
+        }
+        e.outcome = Some(actual);
+    }
+    if std::env::var_os("UPDATE_CORPUS_EXPECTATIONS").is_some() {
+        std::fs::write(EXPECTATIONS_FILE, render_expectations(&expectations))?;
+        return Ok(());
+    }
+    if !mismatches.is_empty() {
+        bail!(
+            "Outcomes differ from {}. If the change is intentional rerun the tests with \
+             UPDATE_CORPUS_EXPECTATIONS set and commit the result.\n{}",
+            EXPECTATIONS_FILE,
+            mismatches
+        );
+    }
+    Ok(())
+}
+
+#[test]
+/// Check that every module in the corpus is listed in the expectations, so
+/// that added modules are not silently ignored.
+fn corpus_modules_listed() -> anyhow::Result<()> {
+    let expectations = parse_expectations(&std::fs::read_to_string(EXPECTATIONS_FILE)?)?;
+    for entry in std::fs::read_dir(CORPUS_DIR)? {
+        let file_name = entry?.file_name().to_string_lossy().into_owned();
+        ensure!(
+            !file_name.ends_with(".wasm") || expectations.iter().any(|e| e.module == file_name),
+            "The module {} is not listed in {}.",
+            file_name,
+            EXPECTATIONS_FILE
+        );
+    }
+    Ok(())
+}
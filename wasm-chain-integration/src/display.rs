@@ -0,0 +1,54 @@
+//! Helpers for displaying addresses and amounts in error messages, logs, and
+//! the output of tools built on this library.
+//!
+//! Account addresses are displayed in the base58check encoding used by the
+//! rest of the chain tooling, contract addresses as `<index, subindex>`, and
+//! amounts in CCD with all six decimals.
+use concordium_contracts_common::{AccountAddress, Address, Amount, ContractAddress};
+use std::fmt;
+
+/// Display an account address in base58check encoding.
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayAccountAddress<'a>(pub &'a AccountAddress);
+
+impl<'a> fmt::Display for DisplayAccountAddress<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { self.0.fmt(f) }
+}
+
+/// Display a contract address as `<index, subindex>`.
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayContractAddress<'a>(pub &'a ContractAddress);
+
+impl<'a> fmt::Display for DisplayContractAddress<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<{}, {}>", self.0.index, self.0.subindex)
+    }
+}
+
+/// Display an address as either [DisplayAccountAddress] or
+/// [DisplayContractAddress], depending on its kind.
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayAddress<'a>(pub &'a Address);
+
+impl<'a> fmt::Display for DisplayAddress<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Address::Account(addr) => DisplayAccountAddress(addr).fmt(f),
+            Address::Contract(addr) => DisplayContractAddress(addr).fmt(f),
+        }
+    }
+}
+
+/// Display an amount given in microCCD as CCD, e.g., `1.500000 CCD`.
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayAmount(pub u64);
+
+impl From<Amount> for DisplayAmount {
+    fn from(amount: Amount) -> Self { Self(amount.micro_ccd) }
+}
+
+impl fmt::Display for DisplayAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{:06} CCD", self.0 / 1_000_000, self.0 % 1_000_000)
+    }
+}
@@ -0,0 +1,205 @@
+//! Typed events of smart contracts.
+//!
+//! Events are logged as plain bytes, which the engine does not interpret. V1
+//! contracts may additionally log events with the host function
+//! `log_event_typed`, which takes a `u32` tag in addition to the bytes of the
+//! event. The logged event is the tag, serialized as 4 little-endian bytes, as
+//! by `concordium-contracts-common`, followed by the payload. Hence such an
+//! event is the serialization of the pair of the tag and the payload, and is
+//! stored and charged for like any other event of that size.
+//!
+//! The tag identifies the type of the payload. The types of the payloads of the
+//! events of the contracts of a module can be embedded in the module as an
+//! [EventSchemas], so that tools can decode the events logged by the
+//! contracts. The engine does not consult it.
+#[cfg(feature = "tooling")]
+use crate::{schema_json::schema_value_to_json, utils::SchemaMismatch};
+use anyhow::ensure;
+use concordium_contracts_common::{from_bytes, schema, to_bytes};
+use std::{collections::BTreeMap, convert::TryInto};
+
+/// The number of bytes of the tag at the start of a typed event.
+pub const TYPED_EVENT_TAG_SIZE: u32 = 4;
+
+/// The event that is logged for a payload with the given tag.
+pub fn typed_event(tag: u32, payload: &[u8]) -> Vec<u8> {
+    let mut event = Vec::with_capacity(TYPED_EVENT_TAG_SIZE as usize + payload.len());
+    event.extend_from_slice(&tag.to_le_bytes());
+    event.extend_from_slice(payload);
+    event
+}
+
+/// Split an event into its tag and payload, assuming it is typed. Returns
+/// [None] if the event is too short to contain a tag.
+pub fn split_typed_event(event: &[u8]) -> Option<(u32, &[u8])> {
+    if event.len() < TYPED_EVENT_TAG_SIZE as usize {
+        return None;
+    }
+    let (tag, payload) = event.split_at(TYPED_EVENT_TAG_SIZE as usize);
+    Some((u32::from_le_bytes(tag.try_into().ok()?), payload))
+}
+
+/// Name of the custom section that contains the schemas of the typed events of
+/// the contracts of a module. See [EventSchemas].
+pub const EVENT_SCHEMAS_SECTION: &str = "concordium-event-schemas";
+
+/// The types of the payloads of the typed events of the contracts of a module,
+/// by their tags. It is embedded in the custom section
+/// [EVENT_SCHEMAS_SECTION] by
+/// [embed_event_schemas](crate::utils::embed_event_schemas).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EventSchemas {
+    /// For each contract, by its name without the `init_` prefix, the types of
+    /// the payloads of its events by their tags.
+    pub contracts: BTreeMap<String, BTreeMap<u32, schema::Type>>,
+}
+
+#[cfg(feature = "tooling")]
+/// Reasons why an event cannot be decoded by [EventSchemas::decode].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum EventDecodeError {
+    #[error("The event is too short to contain a tag.")]
+    MissingTag,
+    #[error("There is no schema for the events of the contract {contract} with tag {tag}.")]
+    UnknownTag {
+        contract: String,
+        tag:      u32,
+    },
+    #[error("{0}")]
+    Mismatch(#[from] SchemaMismatch),
+}
+
+impl EventSchemas {
+    /// Set the type of the payload of the events of the contract with the
+    /// given tag. Returns the previous type, if any.
+    pub fn insert(
+        &mut self,
+        contract: impl Into<String>,
+        tag: u32,
+        ty: schema::Type,
+    ) -> Option<schema::Type> {
+        self.contracts.entry(contract.into()).or_default().insert(tag, ty)
+    }
+
+    /// The type of the payload of the events of the contract with the given
+    /// tag, if it is known.
+    pub fn schema(&self, contract: &str, tag: u32) -> Option<&schema::Type> {
+        self.contracts.get(contract)?.get(&tag)
+    }
+
+    #[cfg(feature = "tooling")]
+    /// Decode a typed event logged by the contract, and render its payload as
+    /// JSON, see [schema_value_to_json]. Returns the tag together with the
+    /// payload.
+    pub fn decode(
+        &self,
+        contract: &str,
+        event: &[u8],
+    ) -> Result<(u32, serde_json::Value), EventDecodeError> {
+        let (tag, payload) = split_typed_event(event).ok_or(EventDecodeError::MissingTag)?;
+        let ty = self.schema(contract, tag).ok_or_else(|| EventDecodeError::UnknownTag {
+            contract: contract.into(),
+            tag,
+        })?;
+        Ok((tag, schema_value_to_json(ty, payload)?))
+    }
+
+    /// Serialize the schemas as the contents of the custom section. They are
+    /// serialized as a list of contracts, each with a list of pairs of tags
+    /// and types, in the binary format of `concordium-contracts-common`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let contracts: Vec<(String, Vec<(u32, schema::Type)>)> = self
+            .contracts
+            .iter()
+            .map(|(name, types)| {
+                (name.clone(), types.iter().map(|(tag, ty)| (*tag, ty.clone())).collect())
+            })
+            .collect();
+        to_bytes(&contracts)
+    }
+
+    /// Parse the schemas from the contents of the custom section. This fails
+    /// if a contract or a tag occurs more than once.
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let contracts: Vec<(String, Vec<(u32, schema::Type)>)> =
+            from_bytes(bytes).map_err(|_| anyhow::anyhow!("Could not parse the event schemas."))?;
+        let mut schemas = Self::default();
+        for (name, types) in contracts {
+            ensure!(
+                !schemas.contracts.contains_key(&name),
+                "The event schemas of {} are given more than once.",
+                name
+            );
+            let mut by_tag = BTreeMap::new();
+            for (tag, ty) in types {
+                ensure!(
+                    by_tag.insert(tag, ty).is_none(),
+                    "The event schemas of {} give tag {} more than once.",
+                    name,
+                    tag
+                );
+            }
+            schemas.contracts.insert(name, by_tag);
+        }
+        Ok(schemas)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::v0::Logs;
+
+    #[test]
+    fn test_typed_logs() {
+        let mut logs = Logs::new();
+        assert_eq!(logs.log_event(vec![1, 2, 3]), 1);
+        assert_eq!(logs.log_event_typed(7, &[5, 6]), 1);
+        assert_eq!(logs.logs[1], [7, 0, 0, 0, 5, 6], "The tag is prefixed to the payload.");
+        assert_eq!(logs.iterate_tagged().collect::<Vec<_>>(), [
+            (None, &[1u8, 2, 3][..]),
+            (Some(7), &[5u8, 6][..])
+        ]);
+        assert_eq!(split_typed_event(&logs.logs[1]), Some((7, &[5u8, 6][..])));
+        assert_eq!(split_typed_event(&[1, 2, 3]), None);
+        assert_eq!(
+            logs.to_bytes(),
+            [0, 0, 0, 2, 0, 0, 0, 3, 1, 2, 3, 0, 0, 0, 6, 7, 0, 0, 0, 5, 6],
+            "Typed events are serialized like other events."
+        );
+    }
+
+    #[test]
+    fn test_event_schemas() {
+        let mut schemas = EventSchemas::default();
+        assert_eq!(schemas.insert("token", 1, schema::Type::U16), None);
+        schemas.insert("token", 2, schema::Type::Bool);
+        #[cfg(feature = "tooling")]
+        {
+            assert_eq!(
+                schemas.decode("token", &typed_event(1, &[0x34, 0x12])),
+                Ok((1, serde_json::json!(0x1234)))
+            );
+            assert_eq!(schemas.decode("token", &[1, 0]), Err(EventDecodeError::MissingTag));
+            assert_eq!(
+                schemas.decode("other", &typed_event(1, &[])),
+                Err(EventDecodeError::UnknownTag {
+                    contract: "other".into(),
+                    tag:      1,
+                })
+            );
+            assert!(matches!(
+                schemas.decode("token", &typed_event(2, &[1, 0])),
+                Err(EventDecodeError::Mismatch(SchemaMismatch::TrailingBytes(1)))
+            ));
+        }
+
+        let module = crate::utils::embed_event_schemas(b"\0asm\x01\0\0\0", &schemas).unwrap();
+        assert_eq!(crate::utils::get_event_schemas(&module).unwrap(), Some(schemas));
+        let duplicate = to_bytes(&vec![("token".to_string(), vec![
+            (1u32, schema::Type::U8),
+            (1u32, schema::Type::U16),
+        ])]);
+        assert!(EventSchemas::from_bytes(&duplicate).is_err(), "Tags must be unique.");
+    }
+}
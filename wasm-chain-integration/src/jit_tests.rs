@@ -0,0 +1,204 @@
+//! Differential tests of the JIT executor against the interpreter on the
+//! example V0 contracts.
+//!
+//! Each init and receive function of the contracts is run with both executors
+//! with a few parameters, and the serialized results must be identical. This
+//! includes the remaining energy, so both must charge exactly the same. The
+//! functions are also run with exactly the energy they use, and one unit less,
+//! where they must run out of energy at the same point.
+use crate::{v0, InterpreterEnergy};
+use anyhow::ensure;
+use concordium_contracts_common::{
+    AccountAddress, Address, Amount, ChainMetadata, ContractAddress, Timestamp,
+};
+use wasm_transform::{
+    executor::{jit::JitModule, Executor},
+    utils::{instantiate_jit_with_metering, instantiate_with_metering},
+};
+
+/// The contracts, with their init function and their receive functions.
+const CONTRACTS: &[(&str, &str, &[&str])] = &[
+    ("benches/counter.wasm", "init_counter", &["counter.receive", "counter.receive_optimized"]),
+    ("benches/simple_game.wasm", "init_simple_game", &[
+        "simple_game.receive_contribute",
+        "simple_game.receive_finalize",
+        "simple_game.receive_help_yourself",
+    ]),
+];
+
+/// Parameters the functions are run with.
+const PARAMETERS: &[&[u8]] = &[&[], &[0; 8], &[1, 0, 0, 0, 0, 0, 0, 0], &[0xff; 40]];
+
+/// Energy the functions are run with, unless it is the boundary that is tested.
+const ENERGY: u64 = 10_000_000;
+
+const OWNER: AccountAddress = AccountAddress([0; 32]);
+
+fn init_context() -> v0::InitContext<&'static [u8]> {
+    v0::InitContext {
+        metadata:        ChainMetadata {
+            slot_time: Timestamp::from_timestamp_millis(0),
+        },
+        init_origin:     OWNER,
+        sender_policies: &[],
+    }
+}
+
+fn receive_context() -> v0::ReceiveContext<&'static [u8]> {
+    v0::ReceiveContext {
+        metadata:        ChainMetadata {
+            slot_time: Timestamp::from_timestamp_millis(0),
+        },
+        invoker:         OWNER,
+        self_address:    ContractAddress {
+            index:    0,
+            subindex: 0,
+        },
+        self_balance:    Amount::from_ccd(1000),
+        sender:          Address::Account(OWNER),
+        owner:           OWNER,
+        sender_policies: &[],
+    }
+}
+
+/// The serialized result of running the init function, if it did not fail,
+/// and the energy it used, if it did not run out of energy.
+fn init(
+    executor: &impl Executor<v0::ProcessedImports>,
+    name: &str,
+    param: &[u8],
+    energy: u64,
+) -> (Option<Vec<u8>>, Option<u64>) {
+    match v0::invoke_init(
+        executor,
+        0,
+        init_context(),
+        name,
+        param.into(),
+        InterpreterEnergy::from(energy),
+    ) {
+        Ok(result) => {
+            let used = match result {
+                v0::InitResult::Success {
+                    remaining_energy,
+                    ..
+                }
+                | v0::InitResult::Reject {
+                    remaining_energy,
+                    ..
+                } => Some(energy - remaining_energy),
+                v0::InitResult::OutOfEnergy => None,
+            };
+            (Some(result.to_bytes()), used)
+        }
+        Err(_) => (None, None),
+    }
+}
+
+/// Like [init], for the receive function.
+fn receive(
+    executor: &impl Executor<v0::ProcessedImports>,
+    name: &str,
+    state: &[u8],
+    param: &[u8],
+    energy: u64,
+) -> (Option<Vec<u8>>, Option<u64>) {
+    match v0::invoke_receive(
+        executor,
+        0,
+        receive_context(),
+        state,
+        name,
+        param.into(),
+        InterpreterEnergy::from(energy),
+    ) {
+        Ok(result) => {
+            let used = match result {
+                v0::ReceiveResult::Success {
+                    remaining_energy,
+                    ..
+                }
+                | v0::ReceiveResult::Reject {
+                    remaining_energy,
+                    ..
+                } => Some(energy - remaining_energy),
+                v0::ReceiveResult::OutOfEnergy => None,
+            };
+            (Some(result.to_bytes()), used)
+        }
+        Err(_) => (None, None),
+    }
+}
+
+/// Run the function with both executors with the default energy, and with the
+/// energy it uses and one less, and check that the results are the same. The
+/// first argument of `run` selects the JIT executor.
+fn check(
+    name: &str,
+    param: &[u8],
+    run: impl Fn(bool, u64) -> (Option<Vec<u8>>, Option<u64>),
+) -> anyhow::Result<()> {
+    let expected = run(false, ENERGY);
+    ensure!(expected == run(true, ENERGY), "{} differs with parameter {:?}.", name, param);
+    if let Some(used) = expected.1 {
+        for energy in std::iter::once(used).chain(used.checked_sub(1)) {
+            ensure!(
+                run(false, energy) == run(true, energy),
+                "{} differs with parameter {:?} and energy {}.",
+                name,
+                param,
+                energy
+            );
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn jit_matches_interpreter() -> anyhow::Result<()> {
+    for (path, init_name, receive_names) in CONTRACTS {
+        let bytes = std::fs::read(path)?;
+        let artifact = instantiate_with_metering::<v0::ProcessedImports, _>(
+            &v0::ConcordiumAllowedImports,
+            &bytes,
+        )?;
+        let jit: JitModule<v0::ProcessedImports> =
+            instantiate_jit_with_metering(&v0::ConcordiumAllowedImports, &bytes)?;
+        for param in PARAMETERS {
+            check(init_name, param, |use_jit, energy| {
+                if use_jit {
+                    init(&jit, init_name, param, energy)
+                } else {
+                    init(&artifact, init_name, param, energy)
+                }
+            })?;
+        }
+        // Run the receive functions on the state of a successful init.
+        let state = match v0::invoke_init(
+            &artifact,
+            0,
+            init_context(),
+            init_name,
+            (&[] as &[u8]).into(),
+            InterpreterEnergy::from(ENERGY),
+        )? {
+            v0::InitResult::Success {
+                state,
+                ..
+            } => state.to_vec(),
+            _ => Vec::new(),
+        };
+        for receive_name in receive_names.iter() {
+            for param in PARAMETERS {
+                check(receive_name, param, |use_jit, energy| {
+                    if use_jit {
+                        receive(&jit, receive_name, &state, param, energy)
+                    } else {
+                        receive(&artifact, receive_name, &state, param, energy)
+                    }
+                })?;
+            }
+        }
+    }
+    Ok(())
+}
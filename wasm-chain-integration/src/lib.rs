@@ -1,15 +1,40 @@
 pub mod constants;
+#[cfg(test)]
+mod corpus_tests;
+pub mod display;
+pub mod events;
 #[cfg(feature = "fuzz")]
 pub mod fuzz;
+#[cfg(all(test, feature = "jit"))]
+mod jit_tests;
+pub mod reject;
 pub mod resumption;
+pub mod sandbox;
+#[cfg(feature = "tooling")]
+pub mod schema_json;
+#[cfg(test)]
+mod test_host_tests;
 pub mod utils;
 pub mod v0;
 pub mod v1;
 #[cfg(test)]
 mod validation_tests;
+#[cfg(all(test, feature = "enable-ffi"))]
+mod wire_format_tests;
 use anyhow::{bail, Context};
 use derive_more::{Display, From, Into};
 
+/// Error signalled by hosts when execution runs out of energy. It is defined by
+/// the machine so that it can be reported as
+/// [RuntimeError::OutOfEnergy](wasm_transform::machine::RuntimeError::OutOfEnergy).
+pub use wasm_transform::machine::OutOfEnergy;
+
+/// Error signalled by hosts when the memory of an instance would exceed
+/// [MAX_MEMORY_PAGES](constants::MAX_MEMORY_PAGES), see
+/// [InterpreterEnergy::charge_memory_grow]. It is reported as
+/// [RuntimeError::OutOfMemory](wasm_transform::machine::RuntimeError::OutOfMemory).
+pub use wasm_transform::machine::OutOfMemory;
+
 /// A helper macro used to check that the declared type of a Wasm import matches
 /// the required one.
 ///
@@ -93,13 +118,6 @@ impl std::str::FromStr for InterpreterEnergy {
     }
 }
 
-#[derive(Debug)]
-pub struct OutOfEnergy;
-
-impl std::fmt::Display for OutOfEnergy {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { "Out of energy".fmt(f) }
-}
-
 impl InterpreterEnergy {
     pub fn tick_energy(&mut self, amount: u64) -> ExecResult<()> {
         if self.energy >= amount {
@@ -132,9 +150,30 @@ impl InterpreterEnergy {
     /// This function will charge regardless of whether memory allocation
     /// actually happens, i.e., even if growing the memory would go over the
     /// maximum. This is OK since trying to allocate too much memory is likely
-    /// going to lead to program failure anyhow.
+    /// going to lead to program failure anyhow. V1 contracts with
+    /// [memory_accounting](v1::HostFeatures::memory_accounting) are instead
+    /// charged by [Self::charge_memory_grow].
     pub fn charge_memory_alloc(&mut self, num_pages: u32) -> ExecResult<()> {
         let to_charge = u64::from(num_pages) * u64::from(constants::MEMORY_COST_FACTOR); // this cannot overflow because of the cast.
         self.tick_energy(to_charge)
     }
+
+    /// Charge energy for growing the memory of an instance from
+    /// `current_pages` by `num_pages` pages. This is used by V1 contracts with
+    /// [memory_accounting](v1::HostFeatures::memory_accounting), for which it
+    /// is called by the machine only when the memory is actually grown, so
+    /// unlike the requested amount the charge reflects the memory that is
+    /// used. If the memory would exceed
+    /// [MAX_MEMORY_PAGES](constants::MAX_MEMORY_PAGES) this fails with
+    /// [OutOfMemory] without charging.
+    pub fn charge_memory_grow(&mut self, current_pages: u32, num_pages: u32) -> ExecResult<()> {
+        let requested = current_pages.saturating_add(num_pages);
+        if requested > constants::MAX_MEMORY_PAGES {
+            bail!(OutOfMemory {
+                requested,
+                limit: constants::MAX_MEMORY_PAGES,
+            })
+        }
+        self.charge_memory_alloc(num_pages)
+    }
 }
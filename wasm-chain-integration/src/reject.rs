@@ -0,0 +1,299 @@
+//! Reject reasons of smart contracts.
+//!
+//! Contracts reject by returning a negative `i32`. The reasons from
+//! `i32::MIN` up to, but not including, [RESERVED_REJECT_REASONS_END] are
+//! reserved for errors produced by contract libraries on behalf of the
+//! contract, e.g., when the parameter cannot be parsed or an invocation of
+//! another contract fails. The remaining negative values are available for
+//! errors defined by the contract itself.
+//!
+//! The engine does not treat reserved reasons differently from others. This
+//! module only assigns them meaning so that libraries agree on the codes, and
+//! so that tools can display them symbolically.
+//!
+//! Names of the reasons defined by contracts can be embedded in a module in a
+//! [RejectReasonRegistry], so that tools can display those symbolically as
+//! well.
+use anyhow::ensure;
+use serde::{Deserialize as SerdeDeserialize, Serialize as SerdeSerialize};
+use std::{collections::BTreeMap, convert::TryFrom, fmt};
+
+/// End (exclusive) of the range of reject reasons reserved for contract
+/// libraries.
+pub const RESERVED_REJECT_REASONS_END: i32 = i32::MIN + 0x1_0000;
+
+/// Reject reasons with a meaning assigned by the engine. These are all in the
+/// reserved range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(i32)]
+pub enum ReservedReason {
+    /// The contract rejected without giving a more specific reason.
+    Unspecified = i32::MIN,
+    /// The parameter could not be parsed.
+    ParseError,
+    /// The log is full.
+    LogFull,
+    /// The event could not be logged because it is too big.
+    LogMalformed,
+    /// The function does not accept a non-zero amount.
+    NotPayable,
+    /// The balance of the contract is insufficient for a transfer or call.
+    InvokeAmountTooLarge,
+    /// The account to transfer to does not exist.
+    InvokeMissingAccount,
+    /// The contract to call does not exist.
+    InvokeMissingContract,
+    /// The entrypoint to call does not exist.
+    InvokeMissingEntrypoint,
+    /// Sending a message to a V0 contract failed.
+    InvokeMessageFailed,
+    /// The called contract trapped.
+    InvokeTrap,
+    /// The module to upgrade to does not exist.
+    UpgradeMissingModule,
+    /// The module to upgrade to is not a V1 module.
+    UpgradeUnsupportedModuleVersion,
+    /// The module to upgrade to does not contain the contract of the instance.
+    UpgradeMissingContract,
+}
+
+impl ReservedReason {
+    const ALL: [ReservedReason; 14] = [
+        ReservedReason::Unspecified,
+        ReservedReason::ParseError,
+        ReservedReason::LogFull,
+        ReservedReason::LogMalformed,
+        ReservedReason::NotPayable,
+        ReservedReason::InvokeAmountTooLarge,
+        ReservedReason::InvokeMissingAccount,
+        ReservedReason::InvokeMissingContract,
+        ReservedReason::InvokeMissingEntrypoint,
+        ReservedReason::InvokeMessageFailed,
+        ReservedReason::InvokeTrap,
+        ReservedReason::UpgradeMissingModule,
+        ReservedReason::UpgradeUnsupportedModuleVersion,
+        ReservedReason::UpgradeMissingContract,
+    ];
+
+    /// The code the contract returns to reject with this reason.
+    pub fn code(self) -> i32 { self as i32 }
+
+    /// The symbolic name of the reason.
+    pub fn name(self) -> &'static str {
+        match self {
+            ReservedReason::Unspecified => "Unspecified",
+            ReservedReason::ParseError => "ParseError",
+            ReservedReason::LogFull => "LogFull",
+            ReservedReason::LogMalformed => "LogMalformed",
+            ReservedReason::NotPayable => "NotPayable",
+            ReservedReason::InvokeAmountTooLarge => "InvokeAmountTooLarge",
+            ReservedReason::InvokeMissingAccount => "InvokeMissingAccount",
+            ReservedReason::InvokeMissingContract => "InvokeMissingContract",
+            ReservedReason::InvokeMissingEntrypoint => "InvokeMissingEntrypoint",
+            ReservedReason::InvokeMessageFailed => "InvokeMessageFailed",
+            ReservedReason::InvokeTrap => "InvokeTrap",
+            ReservedReason::UpgradeMissingModule => "UpgradeMissingModule",
+            ReservedReason::UpgradeUnsupportedModuleVersion => "UpgradeUnsupportedModuleVersion",
+            ReservedReason::UpgradeMissingContract => "UpgradeMissingContract",
+        }
+    }
+}
+
+impl TryFrom<i32> for ReservedReason {
+    type Error = ();
+
+    fn try_from(code: i32) -> Result<Self, Self::Error> {
+        let index = usize::try_from(i64::from(code) - i64::from(i32::MIN)).map_err(|_| ())?;
+        Self::ALL.get(index).copied().ok_or(())
+    }
+}
+
+/// Classification of the reason a contract rejected with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RejectReason {
+    /// A reserved reason with a meaning assigned by the engine.
+    Reserved(ReservedReason),
+    /// A reason in the reserved range that has not been assigned a meaning.
+    Unassigned(i32),
+    /// A reason defined by the contract.
+    Contract(i32),
+}
+
+impl RejectReason {
+    /// The code the contract returned.
+    pub fn code(self) -> i32 {
+        match self {
+            RejectReason::Reserved(r) => r.code(),
+            RejectReason::Unassigned(code) => code,
+            RejectReason::Contract(code) => code,
+        }
+    }
+}
+
+impl From<i32> for RejectReason {
+    fn from(code: i32) -> Self {
+        if code >= RESERVED_REJECT_REASONS_END {
+            RejectReason::Contract(code)
+        } else if let Ok(r) = ReservedReason::try_from(code) {
+            RejectReason::Reserved(r)
+        } else {
+            RejectReason::Unassigned(code)
+        }
+    }
+}
+
+impl From<ReservedReason> for RejectReason {
+    fn from(r: ReservedReason) -> Self { RejectReason::Reserved(r) }
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RejectReason::Reserved(r) => write!(f, "{} ({})", r.name(), r.code()),
+            RejectReason::Unassigned(code) => write!(f, "unassigned reserved reason {}", code),
+            RejectReason::Contract(code) => code.fmt(f),
+        }
+    }
+}
+
+/// Name of the custom section that contains the names of the reject reasons
+/// defined by the contracts of a module. See [RejectReasonRegistry].
+pub const REJECT_REASONS_SECTION: &str = "concordium-reject-reasons";
+
+/// Names of the reject reasons defined by the contracts of a module, e.g.,
+/// derived from the variants of their error types at build time. It is
+/// embedded in the custom section [REJECT_REASONS_SECTION] by
+/// [embed_reject_reasons](crate::utils::embed_reject_reasons), and serialized
+/// as JSON. The engine does not consult it.
+#[derive(SerdeSerialize, SerdeDeserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct RejectReasonRegistry {
+    /// For each contract, by its name without the `init_` prefix, the names
+    /// of its reject reasons by their codes.
+    pub contracts: BTreeMap<String, BTreeMap<i32, String>>,
+}
+
+impl RejectReasonRegistry {
+    /// Name the reject reason of the contract with the given code. Returns the
+    /// previous name of the reason, if any. Only reasons defined by the
+    /// contract can be named, see [RejectReason::Contract].
+    pub fn insert(
+        &mut self,
+        contract: impl Into<String>,
+        code: i32,
+        name: impl Into<String>,
+    ) -> anyhow::Result<Option<String>> {
+        ensure!(
+            matches!(RejectReason::from(code), RejectReason::Contract(_)),
+            "Reject reason {} is reserved and cannot be named.",
+            code
+        );
+        Ok(self.contracts.entry(contract.into()).or_default().insert(code, name.into()))
+    }
+
+    /// The name of the reject reason of the contract with the given code, if
+    /// it is named.
+    pub fn name(&self, contract: &str, code: i32) -> Option<&str> {
+        self.contracts.get(contract)?.get(&code).map(String::as_str)
+    }
+
+    /// Classify the reject reason of the contract, and look up its name.
+    pub fn decode(&self, contract: &str, code: i32) -> DecodedRejectReason {
+        DecodedRejectReason {
+            reason: RejectReason::from(code),
+            name:   self.name(contract, code),
+        }
+    }
+
+    #[cfg(feature = "tooling")]
+    /// Serialize the registry as the contents of the custom section.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("Serializing the registry succeeds.")
+    }
+
+    #[cfg(feature = "tooling")]
+    /// Parse the registry from the contents of the custom section. This fails
+    /// if it names a reserved reason.
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let registry: Self = serde_json::from_slice(bytes)?;
+        for (contract, names) in registry.contracts.iter() {
+            for code in names.keys() {
+                ensure!(
+                    matches!(RejectReason::from(*code), RejectReason::Contract(_)),
+                    "The reject reasons of {} name the reserved reason {}.",
+                    contract,
+                    code
+                );
+            }
+        }
+        Ok(registry)
+    }
+}
+
+/// A reject reason with its name, if it is known, see
+/// [RejectReasonRegistry::decode]. Named reasons are displayed as
+/// `<code> => <name>`, e.g., `-2 => IncorrectAmount`, and others as the
+/// [RejectReason].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodedRejectReason<'a> {
+    pub reason: RejectReason,
+    pub name:   Option<&'a str>,
+}
+
+impl<'a> fmt::Display for DecodedRejectReason<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name {
+            Some(name) => write!(f, "{} => {}", self.reason.code(), name),
+            None => self.reason.fmt(f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserved_reasons() {
+        for (i, r) in ReservedReason::ALL.iter().enumerate() {
+            assert_eq!(r.code(), i32::MIN + i as i32, "Reserved reasons must be consecutive.");
+            assert_eq!(RejectReason::from(r.code()), RejectReason::Reserved(*r));
+        }
+        assert_eq!(
+            RejectReason::from(i32::MIN + ReservedReason::ALL.len() as i32),
+            RejectReason::Unassigned(i32::MIN + ReservedReason::ALL.len() as i32)
+        );
+        assert_eq!(
+            RejectReason::from(RESERVED_REJECT_REASONS_END - 1),
+            RejectReason::Unassigned(RESERVED_REJECT_REASONS_END - 1)
+        );
+        assert_eq!(
+            RejectReason::from(RESERVED_REJECT_REASONS_END),
+            RejectReason::Contract(RESERVED_REJECT_REASONS_END)
+        );
+        assert_eq!(RejectReason::from(-1), RejectReason::Contract(-1));
+        assert_eq!(RejectReason::from(i32::MIN + 1).to_string(), "ParseError (-2147483647)");
+        assert_eq!(RejectReason::from(-3).to_string(), "-3");
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut registry = RejectReasonRegistry::default();
+        assert_eq!(registry.insert("escrow", -2, "IncorrectAmount").unwrap(), None);
+        assert!(registry.insert("escrow", i32::MIN + 1, "Parse").is_err());
+        assert_eq!(registry.decode("escrow", -2).to_string(), "-2 => IncorrectAmount");
+        assert_eq!(registry.decode("escrow", -3).to_string(), "-3");
+        assert_eq!(registry.decode("other", -2).to_string(), "-2");
+        assert_eq!(registry.decode("escrow", i32::MIN + 1).to_string(), "ParseError (-2147483647)");
+
+        #[cfg(feature = "tooling")]
+        {
+            let module = crate::utils::embed_reject_reasons(b"\0asm\x01\0\0\0", &registry).unwrap();
+            assert_eq!(crate::utils::get_reject_reasons(&module).unwrap(), Some(registry));
+            assert!(
+                RejectReasonRegistry::from_bytes(br#"{"escrow":{"-2147483647":"Parse"}}"#).is_err(),
+                "Reserved reasons cannot be named."
+            );
+        }
+    }
+}
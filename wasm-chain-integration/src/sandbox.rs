@@ -0,0 +1,407 @@
+//! Analysis of untrusted modules with hard resource limits.
+//!
+//! Services that inspect arbitrary modules uploaded by users, e.g., to extract
+//! their schemas, should not trust the modules to be well-behaved. The
+//! functions in this module wrap parsing, validation, and schema extraction so
+//! that
+//! - the size of the module, the number of execution steps, the memory used by
+//!   the executed code, and the size of the output are bounded by
+//!   [SandboxLimits], and
+//! - panics are caught and returned as [SandboxError::Panic] instead of
+//!   unwinding into the caller.
+//!
+//! Exceeding a limit results in a specific [SandboxError] so that services can
+//! report it to the user.
+use crate::{constants, utils, utils::WasmVersion, v0, v1};
+use anyhow::{anyhow, bail, ensure};
+use concordium_contracts_common::{schema, to_bytes};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use wasm_transform::{
+    artifact::ArtifactNamedImport,
+    machine::{Host, NoInterrupt, RunResult, RuntimeStack},
+    parse::{parse_custom, parse_skeleton},
+    utils::instantiate_with_metering,
+};
+
+/// Limits on the resources used when analysing a module.
+#[derive(Debug, Clone, Copy)]
+pub struct SandboxLimits {
+    /// Maximum size of the module, in bytes.
+    pub max_module_size:  usize,
+    /// Maximum number of execution steps, counted in interpreter energy, of
+    /// all code executed during the analysis.
+    pub max_steps:        u64,
+    /// Maximum number of pages of memory of executed code.
+    pub max_memory_pages: u32,
+    /// Maximum size of the output, in bytes. For schemas this is the size of
+    /// their serialization.
+    pub max_output_size:  usize,
+}
+
+impl Default for SandboxLimits {
+    /// Limits that admit all modules that can be deployed on chain and schemas
+    /// generated by the standard contract libraries.
+    fn default() -> Self {
+        Self {
+            // The maximum size of V1 modules on chain, which is larger than the
+            // one of V0 modules.
+            max_module_size:  512 * 1024,
+            max_steps:        10_000_000,
+            max_memory_pages: wasm_transform::constants::MAX_NUM_PAGES,
+            max_output_size:  1 << 20,
+        }
+    }
+}
+
+/// Errors of analyses in the sandbox.
+#[derive(Debug, thiserror::Error)]
+pub enum SandboxError {
+    #[error("The module is {size} bytes, which exceeds the limit of {limit} bytes.")]
+    ModuleTooLarge {
+        size:  usize,
+        limit: usize,
+    },
+    #[error("Execution exceeded the limit of {limit} steps.")]
+    StepLimitExceeded {
+        limit: u64,
+    },
+    #[error("Execution exceeded the limit of {limit} pages of memory.")]
+    MemoryLimitExceeded {
+        limit: u32,
+    },
+    #[error("The output is {size} bytes, which exceeds the limit of {limit} bytes.")]
+    OutputTooLarge {
+        size:  usize,
+        limit: usize,
+    },
+    #[error("The analysis panicked: {0}")]
+    Panic(String),
+    #[error("The analysis failed: {0:#}")]
+    Failed(anyhow::Error),
+}
+
+impl SandboxError {
+    /// Whether the error is due to a resource limit being exceeded, as
+    /// opposed to the module being malformed or the analysis panicking.
+    pub fn is_resource_exceeded(&self) -> bool {
+        matches!(
+            self,
+            SandboxError::ModuleTooLarge { .. }
+                | SandboxError::StepLimitExceeded { .. }
+                | SandboxError::MemoryLimitExceeded { .. }
+                | SandboxError::OutputTooLarge { .. }
+        )
+    }
+}
+
+impl From<anyhow::Error> for SandboxError {
+    fn from(e: anyhow::Error) -> Self { SandboxError::Failed(e) }
+}
+
+pub type SandboxResult<A> = Result<A, SandboxError>;
+
+/// Run the analysis, checking the size of the module first, and converting
+/// panics to [SandboxError::Panic].
+fn sandboxed<A>(
+    bytes: &[u8],
+    limits: &SandboxLimits,
+    analysis: impl FnOnce() -> SandboxResult<A>,
+) -> SandboxResult<A> {
+    if bytes.len() > limits.max_module_size {
+        return Err(SandboxError::ModuleTooLarge {
+            size:  bytes.len(),
+            limit: limits.max_module_size,
+        });
+    }
+    catch_unwind(AssertUnwindSafe(analysis)).unwrap_or_else(|payload| {
+        let msg = if let Some(msg) = payload.downcast_ref::<&str>() {
+            (*msg).to_owned()
+        } else if let Some(msg) = payload.downcast_ref::<String>() {
+            msg.clone()
+        } else {
+            "unknown panic".to_owned()
+        };
+        Err(SandboxError::Panic(msg))
+    })
+}
+
+/// Check that the output of the given size is within the limits.
+fn check_output_size(size: usize, limits: &SandboxLimits) -> SandboxResult<()> {
+    if size > limits.max_output_size {
+        return Err(SandboxError::OutputTooLarge {
+            size,
+            limit: limits.max_output_size,
+        });
+    }
+    Ok(())
+}
+
+/// Parse and validate the module of the given version, as it is done when the
+/// module is deployed. The module bytes do not include the version prefix.
+pub fn validate(version: WasmVersion, bytes: &[u8], limits: &SandboxLimits) -> SandboxResult<()> {
+    sandboxed(bytes, limits, || {
+        match version {
+            WasmVersion::V0 => {
+                instantiate_with_metering::<v0::ProcessedImports, _>(
+                    &v0::ConcordiumAllowedImports,
+                    bytes,
+                )?;
+            }
+            WasmVersion::V1 => {
+                instantiate_with_metering::<v1::ProcessedImports, _>(
+                    &v1::ConcordiumAllowedImports::LATEST,
+                    bytes,
+                )?;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Get the schema embedded in the module of the given version. The module
+/// bytes do not include the version prefix. The sections containing the schema
+/// are checked against the output limit before they are deserialized.
+pub fn embedded_schema(
+    version: WasmVersion,
+    bytes: &[u8],
+    limits: &SandboxLimits,
+) -> SandboxResult<schema::VersionedModuleSchema> {
+    sandboxed(bytes, limits, || {
+        for ucs in parse_skeleton(bytes)?.custom.iter() {
+            let cs = parse_custom(ucs)?;
+            if cs.name.as_ref().starts_with(utils::SCHEMA_SECTION) {
+                check_output_size(cs.contents.len(), limits)?;
+            }
+        }
+        let schema = match version {
+            WasmVersion::V0 => utils::get_embedded_schema_v0(bytes)?,
+            WasmVersion::V1 => utils::get_embedded_schema_v1(bytes)?,
+        };
+        Ok(schema)
+    })
+}
+
+/// Generate the schema of the module of the given version by running its
+/// schema functions. The module bytes do not include the version prefix. All
+/// of the schema functions share the step limit, and each of them is subject
+/// to the memory limit.
+pub fn generate_schema(
+    version: WasmVersion,
+    bytes: &[u8],
+    limits: &SandboxLimits,
+) -> SandboxResult<schema::VersionedModuleSchema> {
+    sandboxed(bytes, limits, || {
+        let artifact = instantiate_with_metering::<ArtifactNamedImport, _>(
+            &utils::TestHost::default(),
+            bytes,
+        )?;
+        let mut host = SandboxHost::new(limits);
+        let schema = match version {
+            WasmVersion::V0 => utils::contract_schema_v0_from_artifact(&artifact, &mut host),
+            WasmVersion::V1 => utils::contract_schema_v1_from_artifact(&artifact, &mut host),
+        };
+        // A limit that was exceeded takes precedence over the error it caused.
+        if let Some(e) = host.exceeded {
+            return Err(e);
+        }
+        let schema = schema?;
+        check_output_size(to_bytes(&schema).len(), limits)?;
+        Ok(schema)
+    })
+}
+
+/// A host that implements the functions added by the metering transformation
+/// subject to the limits, and traps on calls to all other imports.
+struct SandboxHost {
+    /// Remaining steps.
+    steps:             u64,
+    /// Remaining number of nested calls.
+    activation_frames: u32,
+    /// Number of pages of memory of the current execution, including pages
+    /// that are requested but not yet allocated.
+    memory_pages:      u32,
+    limits:            SandboxLimits,
+    /// The limit that was exceeded, if any.
+    exceeded:          Option<SandboxError>,
+}
+
+impl SandboxHost {
+    fn new(limits: &SandboxLimits) -> Self {
+        Self {
+            steps:             limits.max_steps,
+            activation_frames: constants::MAX_ACTIVATION_FRAMES,
+            memory_pages:      0,
+            limits:            *limits,
+            exceeded:          None,
+        }
+    }
+
+    fn charge_steps(&mut self, amount: u64) -> RunResult<()> {
+        if let Some(steps) = self.steps.checked_sub(amount) {
+            self.steps = steps;
+            Ok(())
+        } else {
+            self.steps = 0;
+            self.exceeded = Some(SandboxError::StepLimitExceeded {
+                limit: self.limits.max_steps,
+            });
+            bail!("Step limit exceeded.")
+        }
+    }
+
+    fn use_memory(&mut self, num_pages: u32) -> RunResult<()> {
+        match self.memory_pages.checked_add(num_pages) {
+            Some(pages) if pages <= self.limits.max_memory_pages => {
+                self.memory_pages = pages;
+                Ok(())
+            }
+            _ => {
+                self.exceeded = Some(SandboxError::MemoryLimitExceeded {
+                    limit: self.limits.max_memory_pages,
+                });
+                bail!("Memory limit exceeded.")
+            }
+        }
+    }
+}
+
+impl Host<ArtifactNamedImport> for SandboxHost {
+    type Interrupt = NoInterrupt;
+
+    fn tick_initial_memory(&mut self, num_pages: u32) -> RunResult<()> {
+        // Each execution starts with a fresh memory.
+        self.memory_pages = 0;
+        self.use_memory(num_pages)
+    }
+
+    fn call(
+        &mut self,
+        f: &ArtifactNamedImport,
+        _memory: &mut Vec<u8>,
+        stack: &mut RuntimeStack,
+    ) -> RunResult<Option<NoInterrupt>> {
+        ensure!(
+            f.get_mod_name().as_ref() == "concordium_metering",
+            "Unsupported import {} in the sandbox.",
+            f
+        );
+        match f.get_item_name().as_ref() {
+            "account_energy" => self.charge_steps(unsafe { stack.pop_u64() })?,
+            "track_call" => {
+                ensure!(self.activation_frames > 0, "Too many nested functions.");
+                self.activation_frames -= 1;
+            }
+            "track_return" => self.activation_frames += 1,
+            "account_memory" => self.use_memory(unsafe { stack.peek_u32() })?,
+            _ => return Err(anyhow!("Unsupported import {} in the sandbox.", f)),
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema_test_module() -> Vec<u8> {
+        std::fs::read("../testdata/contracts/sandbox-schema-test.wasm")
+            .expect("Could not read file.")
+    }
+
+    #[test]
+    fn test_generate_schema_limits() {
+        let module = schema_test_module();
+        let limits = SandboxLimits::default();
+        let schema = generate_schema(WasmVersion::V0, &module, &limits)
+            .expect("Schema generation within the limits should succeed.");
+        match schema {
+            schema::VersionedModuleSchema::V0(module_schema) => assert!(
+                module_schema.contracts["test"].init.is_some(),
+                "The init schema should be generated."
+            ),
+            _ => panic!("Expected a V0 schema."),
+        }
+
+        let res = generate_schema(WasmVersion::V0, &module, &SandboxLimits {
+            max_steps: 1000,
+            ..limits
+        });
+        assert!(
+            matches!(
+                res,
+                Err(SandboxError::StepLimitExceeded {
+                    limit: 1000,
+                })
+            ),
+            "Expected the step limit to be exceeded: {:?}",
+            res
+        );
+
+        let res = generate_schema(WasmVersion::V0, &module, &SandboxLimits {
+            max_memory_pages: 2,
+            ..limits
+        });
+        assert!(
+            matches!(
+                res,
+                Err(SandboxError::MemoryLimitExceeded {
+                    limit: 2,
+                })
+            ),
+            "Expected the memory limit to be exceeded: {:?}",
+            res
+        );
+
+        let res = generate_schema(WasmVersion::V0, &module, &SandboxLimits {
+            max_output_size: 1,
+            ..limits
+        });
+        assert!(
+            matches!(
+                res,
+                Err(SandboxError::OutputTooLarge {
+                    limit: 1,
+                    ..
+                })
+            ),
+            "Expected the output limit to be exceeded: {:?}",
+            res
+        );
+
+        let res = generate_schema(WasmVersion::V0, &module, &SandboxLimits {
+            max_module_size: module.len() - 1,
+            ..limits
+        });
+        assert!(
+            matches!(res, Err(SandboxError::ModuleTooLarge { .. })),
+            "Expected the module to be too large: {:?}",
+            res
+        );
+    }
+
+    #[test]
+    fn test_embedded_schema_limits() {
+        let module =
+            std::fs::read("../testdata/schemas/cis2-wccd-embedded-schema-v1-versioned.wasm.v1")
+                .expect("Could not read file.");
+        let limits = SandboxLimits::default();
+        validate(WasmVersion::V1, &module[8..], &limits).expect("The module should be valid.");
+        embedded_schema(WasmVersion::V1, &module[8..], &limits)
+            .expect("Extracting the schema within the limits should succeed.");
+        let res = embedded_schema(WasmVersion::V1, &module[8..], &SandboxLimits {
+            max_output_size: 100,
+            ..limits
+        });
+        assert!(
+            matches!(
+                res,
+                Err(SandboxError::OutputTooLarge {
+                    limit: 100,
+                    ..
+                })
+            ),
+            "Expected the output limit to be exceeded: {:?}",
+            res
+        );
+    }
+}
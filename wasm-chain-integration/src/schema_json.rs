@@ -0,0 +1,850 @@
+//! Conversion between JSON and the binary serialization of values described by
+//! schemas, and construction of contract states from JSON descriptions. These
+//! are intended for tools built on the engine, and are only available with the
+//! `tooling` feature.
+
+use crate::{
+    display::DisplayAccountAddress,
+    utils::{get_embedded_schema_v0, mismatch, take, take_length, with_segment, SchemaMismatch},
+    v1, ExecResult,
+};
+use anyhow::{bail, ensure, Context};
+use concordium_contracts_common::{from_bytes, schema, AccountAddress, ContractAddress, Deserial};
+use std::convert::TryFrom;
+
+/// Decode the bytes as a value of the given type and render it as JSON. The
+/// bytes must be exactly the serialization of the value. The JSON follows the
+/// conventions of the JSON parameters of the chain tools where possible:
+///
+/// - Integers up to 64 bits are numbers, and larger ones, including LEB128
+///   encoded ones, and amounts in microCCD, are decimal strings.
+/// - Timestamps and durations are numbers of milliseconds.
+/// - Account addresses are base58check strings, and contract addresses are
+///   objects with fields `index` and `subindex`.
+/// - Pairs, lists, sets, and arrays are arrays, and maps are arrays of
+///   key-value pairs.
+/// - Structs with named fields are objects, and structs with unnamed fields are
+///   arrays. Enums are objects with a single field, named after the variant,
+///   whose value is the fields of the variant.
+/// - Byte lists and byte arrays are hex strings.
+///
+/// Errors locate the offending part of the value in the same way as
+/// [validate_parameter](crate::utils::validate_parameter), with `value` as the
+/// root of the path.
+pub fn schema_value_to_json(
+    ty: &schema::Type,
+    bytes: &[u8],
+) -> Result<serde_json::Value, SchemaMismatch> {
+    let mut source = bytes;
+    let value = decode_value(ty, &mut source, &mut String::from("value"))?;
+    if source.is_empty() {
+        Ok(value)
+    } else {
+        Err(SchemaMismatch::TrailingBytes(source.len()))
+    }
+}
+
+/// Maximum number of elements of a collection whose elements are serialized
+/// as no bytes, such as lists of unit values, that [schema_value_to_json]
+/// decodes. This bounds the size of the output, since the length of such a
+/// collection is not bounded by the size of the input.
+const MAX_EMPTY_ELEMENTS: usize = 1 << 16;
+
+fn decode_value(
+    ty: &schema::Type,
+    source: &mut &[u8],
+    path: &mut String,
+) -> Result<serde_json::Value, SchemaMismatch> {
+    use schema::Type;
+    use serde_json::{json, Value as Json};
+    let value = match ty {
+        Type::Unit => Json::Null,
+        Type::Bool => match take(source, 1, path)?[0] {
+            0 => Json::Bool(false),
+            1 => Json::Bool(true),
+            b => return Err(mismatch(path, format!("{} is not a boolean", b))),
+        },
+        Type::U8 => json!(take_fixed::<u8>(source, 1, path)?),
+        Type::I8 => json!(take_fixed::<i8>(source, 1, path)?),
+        Type::U16 => json!(take_fixed::<u16>(source, 2, path)?),
+        Type::I16 => json!(take_fixed::<i16>(source, 2, path)?),
+        Type::U32 => json!(take_fixed::<u32>(source, 4, path)?),
+        Type::I32 => json!(take_fixed::<i32>(source, 4, path)?),
+        Type::U64 | Type::Timestamp | Type::Duration => json!(take_fixed::<u64>(source, 8, path)?),
+        Type::I64 => json!(take_fixed::<i64>(source, 8, path)?),
+        Type::Amount => Json::String(take_fixed::<u64>(source, 8, path)?.to_string()),
+        Type::U128 => Json::String(take_fixed::<u128>(source, 16, path)?.to_string()),
+        Type::I128 => Json::String(take_fixed::<i128>(source, 16, path)?.to_string()),
+        Type::AccountAddress => {
+            let address = take_fixed::<AccountAddress>(source, 32, path)?;
+            Json::String(DisplayAccountAddress(&address).to_string())
+        }
+        Type::ContractAddress => {
+            let address = take_fixed::<ContractAddress>(source, 16, path)?;
+            json!({
+                "index": address.index,
+                "subindex": address.subindex,
+            })
+        }
+        Type::Pair(l, r) => {
+            let l = with_segment(path, ".0", |path| decode_value(l, source, path))?;
+            let r = with_segment(path, ".1", |path| decode_value(r, source, path))?;
+            Json::Array(vec![l, r])
+        }
+        Type::List(size, elem) | Type::Set(size, elem) => {
+            let len = take_length(size, source, path)?;
+            decode_elements(len, source, path, |source, path| decode_value(elem, source, path))?
+        }
+        Type::Map(size, k, v) => {
+            let len = take_length(size, source, path)?;
+            decode_elements(len, source, path, |source, path| {
+                let k = with_segment(path, ".key", |path| decode_value(k, source, path))?;
+                let v = with_segment(path, ".value", |path| decode_value(v, source, path))?;
+                Ok(Json::Array(vec![k, v]))
+            })?
+        }
+        Type::Array(len, elem) => decode_elements(*len as usize, source, path, |source, path| {
+            decode_value(elem, source, path)
+        })?,
+        Type::Struct(fields) => decode_fields(fields, source, path)?,
+        Type::Enum(variants) => {
+            // Enums are serialized the same way as by the derived Serial instances.
+            let tag = if variants.len() <= 256 {
+                usize::from(take(source, 1, path)?[0])
+            } else {
+                usize::from(take_fixed::<u16>(source, 2, path)?)
+            };
+            let (name, fields) = variants.get(tag).ok_or_else(|| {
+                mismatch(
+                    path,
+                    format!("{} is not a tag of any of the {} variants", tag, variants.len()),
+                )
+            })?;
+            let fields = with_segment(path, &format!(".{}", name), |path| {
+                decode_fields(fields, source, path)
+            })?;
+            json!({ name: fields })
+        }
+        Type::String(size) | Type::ContractName(size) | Type::ReceiveName(size) => {
+            let len = take_length(size, source, path)?;
+            let bytes = take(source, len, path)?;
+            let s = std::str::from_utf8(bytes)
+                .map_err(|_| mismatch(path, "the string is not valid UTF-8"))?;
+            Json::String(s.to_owned())
+        }
+        Type::ULeb128(max) | Type::ILeb128(max) => {
+            let mut groups = Vec::new();
+            loop {
+                if groups.len() == *max as usize {
+                    return Err(mismatch(
+                        path,
+                        format!("the LEB128 encoding is longer than {} bytes", max),
+                    ));
+                }
+                let byte = take(source, 1, path)?[0];
+                groups.push(byte & 0x7f);
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+            let negative = matches!(ty, Type::ILeb128(_)) && groups[groups.len() - 1] & 0x40 != 0;
+            Json::String(leb128_to_decimal(&groups, negative))
+        }
+        Type::ByteList(size) => {
+            let len = take_length(size, source, path)?;
+            Json::String(hex::encode(take(source, len, path)?))
+        }
+        Type::ByteArray(len) => Json::String(hex::encode(take(source, *len as usize, path)?)),
+    };
+    Ok(value)
+}
+
+/// Take `n` bytes and deserialize them as a value of fixed size `n`.
+fn take_fixed<T: Deserial>(source: &mut &[u8], n: usize, path: &str) -> Result<T, SchemaMismatch> {
+    from_bytes(take(source, n, path)?)
+        .map_err(|_| mismatch(path, format!("{} bytes are not a valid value", n)))
+}
+
+/// Render the number with the given 7-bit groups, least significant first, in
+/// decimal. If the number is negative the groups are its two's complement.
+fn leb128_to_decimal(groups: &[u8], negative: bool) -> String {
+    // Digits in base 10^9, least significant first.
+    let mut limbs: Vec<u64> = vec![0];
+    let mut mul_add = |factor: u64, addend: u64| {
+        let mut carry = addend;
+        for limb in limbs.iter_mut() {
+            let x = *limb * factor + carry;
+            *limb = x % 1_000_000_000;
+            carry = x / 1_000_000_000;
+        }
+        if carry > 0 {
+            limbs.push(carry);
+        }
+    };
+    for group in groups.iter().rev() {
+        // The magnitude of a negative number is the complement plus one.
+        let group = if negative {
+            !group & 0x7f
+        } else {
+            *group
+        };
+        mul_add(128, u64::from(group));
+    }
+    if negative {
+        mul_add(1, 1);
+    }
+    let mut out = String::new();
+    if negative {
+        out.push('-');
+    }
+    let mut limbs = limbs.iter().rev();
+    if let Some(first) = limbs.next() {
+        out.push_str(&first.to_string());
+    }
+    for limb in limbs {
+        out.push_str(&format!("{:09}", limb));
+    }
+    out
+}
+
+/// Decode the given number of elements of a collection.
+fn decode_elements(
+    len: usize,
+    source: &mut &[u8],
+    path: &mut String,
+    mut decode: impl FnMut(&mut &[u8], &mut String) -> Result<serde_json::Value, SchemaMismatch>,
+) -> Result<serde_json::Value, SchemaMismatch> {
+    let mut elements = Vec::new();
+    for i in 0..len {
+        let remaining = source.len();
+        let element = with_segment(path, &format!("[{}]", i), |path| decode(source, path))?;
+        // All elements have the same type, so if one consumed no input the rest
+        // are the same.
+        if source.len() == remaining {
+            if len > MAX_EMPTY_ELEMENTS {
+                return Err(mismatch(
+                    path,
+                    format!("{} elements that are serialized as no bytes is too many", len),
+                ));
+            }
+            elements.resize(len, element);
+            break;
+        }
+        elements.push(element);
+    }
+    Ok(serde_json::Value::Array(elements))
+}
+
+fn decode_fields(
+    fields: &schema::Fields,
+    source: &mut &[u8],
+    path: &mut String,
+) -> Result<serde_json::Value, SchemaMismatch> {
+    match fields {
+        schema::Fields::Named(fields) => {
+            let mut object = serde_json::Map::new();
+            for (name, ty) in fields {
+                let value = with_segment(path, &format!(".{}", name), |path| {
+                    decode_value(ty, source, path)
+                })?;
+                object.insert(name.clone(), value);
+            }
+            Ok(serde_json::Value::Object(object))
+        }
+        schema::Fields::Unnamed(fields) => {
+            let mut values = Vec::with_capacity(fields.len());
+            for (i, ty) in fields.iter().enumerate() {
+                values.push(with_segment(path, &format!(".{}", i), |path| {
+                    decode_value(ty, source, path)
+                })?);
+            }
+            Ok(serde_json::Value::Array(values))
+        }
+        schema::Fields::None => Ok(serde_json::Value::Array(Vec::new())),
+    }
+}
+
+/// Serialize a JSON value as a value of the given type. This is the inverse of
+/// [schema_value_to_json] and uses the same conventions, so that the JSON
+/// rendering of a value is serialized back to the same bytes. Errors locate
+/// the offending part of the JSON value, with `value` as the root of the path.
+pub fn schema_json_to_bytes(
+    ty: &schema::Type,
+    json: &serde_json::Value,
+) -> Result<Vec<u8>, SchemaMismatch> {
+    let mut out = Vec::new();
+    encode_value(ty, json, &mut out, &mut String::from("value"))?;
+    Ok(out)
+}
+
+fn encode_value(
+    ty: &schema::Type,
+    json: &serde_json::Value,
+    out: &mut Vec<u8>,
+    path: &mut String,
+) -> Result<(), SchemaMismatch> {
+    use schema::Type;
+    match ty {
+        Type::Unit => {
+            if !json.is_null() {
+                return Err(mismatch(path, "expected null"));
+            }
+        }
+        Type::Bool => {
+            let b = json.as_bool().ok_or_else(|| mismatch(path, "expected a boolean"))?;
+            out.push(u8::from(b));
+        }
+        Type::U8 => out.extend_from_slice(&json_integer::<u8>(json, path)?.to_le_bytes()),
+        Type::I8 => out.extend_from_slice(&json_integer::<i8>(json, path)?.to_le_bytes()),
+        Type::U16 => out.extend_from_slice(&json_integer::<u16>(json, path)?.to_le_bytes()),
+        Type::I16 => out.extend_from_slice(&json_integer::<i16>(json, path)?.to_le_bytes()),
+        Type::U32 => out.extend_from_slice(&json_integer::<u32>(json, path)?.to_le_bytes()),
+        Type::I32 => out.extend_from_slice(&json_integer::<i32>(json, path)?.to_le_bytes()),
+        Type::U64 | Type::Timestamp | Type::Duration => {
+            out.extend_from_slice(&json_integer::<u64>(json, path)?.to_le_bytes())
+        }
+        Type::I64 => out.extend_from_slice(&json_integer::<i64>(json, path)?.to_le_bytes()),
+        Type::Amount => out.extend_from_slice(&json_decimal::<u64>(json, path)?.to_le_bytes()),
+        Type::U128 => out.extend_from_slice(&json_decimal::<u128>(json, path)?.to_le_bytes()),
+        Type::I128 => out.extend_from_slice(&json_decimal::<i128>(json, path)?.to_le_bytes()),
+        Type::AccountAddress => {
+            let address = json
+                .as_str()
+                .and_then(|s| s.parse::<AccountAddress>().ok())
+                .ok_or_else(|| mismatch(path, "expected an account address in base58check"))?;
+            out.extend_from_slice(&address.0);
+        }
+        Type::ContractAddress => {
+            let field = |name: &str| json.get(name).and_then(serde_json::Value::as_u64);
+            match (field("index"), field("subindex")) {
+                (Some(index), Some(subindex)) => {
+                    out.extend_from_slice(&index.to_le_bytes());
+                    out.extend_from_slice(&subindex.to_le_bytes());
+                }
+                _ => {
+                    return Err(mismatch(
+                        path,
+                        "expected an object with the fields index and subindex",
+                    ))
+                }
+            }
+        }
+        Type::Pair(l, r) => {
+            let elements = json_array(json, Some(2), path)?;
+            with_segment(path, ".0", |path| encode_value(l, &elements[0], out, path))?;
+            with_segment(path, ".1", |path| encode_value(r, &elements[1], out, path))?;
+        }
+        Type::List(size, elem) | Type::Set(size, elem) => {
+            let elements = json_array(json, None, path)?;
+            put_length(size, elements.len(), out, path)?;
+            encode_elements(elements, out, path, |json, out, path| {
+                encode_value(elem, json, out, path)
+            })?;
+        }
+        Type::Map(size, k, v) => {
+            let elements = json_array(json, None, path)?;
+            put_length(size, elements.len(), out, path)?;
+            encode_elements(elements, out, path, |json, out, path| {
+                let entry = json_array(json, Some(2), path)?;
+                with_segment(path, ".key", |path| encode_value(k, &entry[0], out, path))?;
+                with_segment(path, ".value", |path| encode_value(v, &entry[1], out, path))
+            })?;
+        }
+        Type::Array(len, elem) => {
+            let elements = json_array(json, Some(*len as usize), path)?;
+            encode_elements(elements, out, path, |json, out, path| {
+                encode_value(elem, json, out, path)
+            })?;
+        }
+        Type::Struct(fields) => encode_fields(fields, json, out, path)?,
+        Type::Enum(variants) => {
+            let (name, fields) = match json.as_object() {
+                Some(object) if object.len() == 1 => object.iter().next().unwrap(),
+                _ => return Err(mismatch(path, "expected an object with a single field")),
+            };
+            let tag = variants
+                .iter()
+                .position(|(variant, _)| variant == name)
+                .ok_or_else(|| mismatch(path, format!("{} is not a variant", name)))?;
+            if variants.len() <= 256 {
+                out.push(tag as u8);
+            } else {
+                out.extend_from_slice(&(tag as u16).to_le_bytes());
+            }
+            with_segment(path, &format!(".{}", name), |path| {
+                encode_fields(&variants[tag].1, fields, out, path)
+            })?;
+        }
+        Type::String(size) | Type::ContractName(size) | Type::ReceiveName(size) => {
+            let s = json.as_str().ok_or_else(|| mismatch(path, "expected a string"))?;
+            put_length(size, s.len(), out, path)?;
+            out.extend_from_slice(s.as_bytes());
+        }
+        Type::ULeb128(max) | Type::ILeb128(max) => {
+            let s = json.as_str().ok_or_else(|| mismatch(path, "expected a decimal string"))?;
+            let signed = matches!(ty, Type::ILeb128(_));
+            let groups = decimal_to_leb128(s, signed)
+                .ok_or_else(|| mismatch(path, format!("{} is not a valid integer", s)))?;
+            if groups.len() > *max as usize {
+                return Err(mismatch(
+                    path,
+                    format!("the LEB128 encoding is longer than {} bytes", max),
+                ));
+            }
+            let last = groups.len() - 1;
+            out.extend(groups.iter().enumerate().map(|(i, g)| {
+                if i == last {
+                    *g
+                } else {
+                    g | 0x80
+                }
+            }));
+        }
+        Type::ByteList(size) => {
+            let bytes = json_hex(json, path)?;
+            put_length(size, bytes.len(), out, path)?;
+            out.extend_from_slice(&bytes);
+        }
+        Type::ByteArray(len) => {
+            let bytes = json_hex(json, path)?;
+            if bytes.len() != *len as usize {
+                return Err(mismatch(
+                    path,
+                    format!("expected {} bytes, but got {}", len, bytes.len()),
+                ));
+            }
+            out.extend_from_slice(&bytes);
+        }
+    }
+    Ok(())
+}
+
+/// An integer that is rendered as a JSON number.
+fn json_integer<T: TryFrom<i64> + TryFrom<u64>>(
+    json: &serde_json::Value,
+    path: &str,
+) -> Result<T, SchemaMismatch> {
+    let n = match json.as_u64() {
+        Some(n) => T::try_from(n).ok(),
+        None => json.as_i64().and_then(|n| T::try_from(n).ok()),
+    };
+    n.ok_or_else(|| mismatch(path, format!("{} is not an integer in range", json)))
+}
+
+/// An integer that is rendered as a decimal string.
+fn json_decimal<T: std::str::FromStr>(
+    json: &serde_json::Value,
+    path: &str,
+) -> Result<T, SchemaMismatch> {
+    json.as_str()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| mismatch(path, format!("{} is not a decimal string in range", json)))
+}
+
+/// Bytes that are rendered as a hex string.
+fn json_hex(json: &serde_json::Value, path: &str) -> Result<Vec<u8>, SchemaMismatch> {
+    json.as_str()
+        .and_then(|s| hex::decode(s).ok())
+        .ok_or_else(|| mismatch(path, "expected a hex string"))
+}
+
+/// The elements of a JSON array, optionally of the given length.
+fn json_array<'a>(
+    json: &'a serde_json::Value,
+    len: Option<usize>,
+    path: &str,
+) -> Result<&'a [serde_json::Value], SchemaMismatch> {
+    let elements = json.as_array().ok_or_else(|| mismatch(path, "expected an array"))?;
+    match len {
+        Some(len) if elements.len() != len => {
+            Err(mismatch(path, format!("expected {} elements, but got {}", len, elements.len())))
+        }
+        _ => Ok(elements),
+    }
+}
+
+/// Write a little-endian length of the given size.
+fn put_length(
+    size: &schema::SizeLength,
+    len: usize,
+    out: &mut Vec<u8>,
+    path: &str,
+) -> Result<(), SchemaMismatch> {
+    let width = match size {
+        schema::SizeLength::U8 => 1,
+        schema::SizeLength::U16 => 2,
+        schema::SizeLength::U32 => 4,
+        schema::SizeLength::U64 => 8,
+    };
+    let len = len as u64;
+    if width < 8 && len >> (8 * width) != 0 {
+        return Err(mismatch(path, format!("length {} is too large", len)));
+    }
+    out.extend_from_slice(&len.to_le_bytes()[..width]);
+    Ok(())
+}
+
+/// Encode the elements of a collection.
+fn encode_elements(
+    elements: &[serde_json::Value],
+    out: &mut Vec<u8>,
+    path: &mut String,
+    mut encode: impl FnMut(&serde_json::Value, &mut Vec<u8>, &mut String) -> Result<(), SchemaMismatch>,
+) -> Result<(), SchemaMismatch> {
+    for (i, element) in elements.iter().enumerate() {
+        with_segment(path, &format!("[{}]", i), |path| encode(element, out, path))?;
+    }
+    Ok(())
+}
+
+fn encode_fields(
+    fields: &schema::Fields,
+    json: &serde_json::Value,
+    out: &mut Vec<u8>,
+    path: &mut String,
+) -> Result<(), SchemaMismatch> {
+    match fields {
+        schema::Fields::Named(fields) => {
+            let object = json.as_object().ok_or_else(|| mismatch(path, "expected an object"))?;
+            if let Some(name) = object.keys().find(|k| fields.iter().all(|(name, _)| name != *k)) {
+                return Err(mismatch(path, format!("{} is not a field", name)));
+            }
+            for (name, ty) in fields {
+                let value = object
+                    .get(name)
+                    .ok_or_else(|| mismatch(path, format!("the field {} is missing", name)))?;
+                with_segment(path, &format!(".{}", name), |path| {
+                    encode_value(ty, value, out, path)
+                })?;
+            }
+        }
+        schema::Fields::Unnamed(fields) => {
+            let values = json_array(json, Some(fields.len()), path)?;
+            for (i, (ty, value)) in fields.iter().zip(values).enumerate() {
+                with_segment(path, &format!(".{}", i), |path| encode_value(ty, value, out, path))?;
+            }
+        }
+        schema::Fields::None => {
+            json_array(json, Some(0), path)?;
+        }
+    }
+    Ok(())
+}
+
+/// The 7-bit groups, least significant first, of the shortest LEB128 encoding
+/// of the decimal number. Negative numbers are only allowed if `signed`, and
+/// are encoded in two's complement. Returns [None] if the string is not a
+/// decimal number.
+fn decimal_to_leb128(s: &str, signed: bool) -> Option<Vec<u8>> {
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(digits) if signed => (true, digits),
+        _ => (false, s),
+    };
+    if digits.is_empty() || !digits.bytes().all(|d| d.is_ascii_digit()) {
+        return None;
+    }
+    // The magnitude, least significant group first.
+    let mut groups: Vec<u8> = vec![0];
+    for d in digits.bytes() {
+        let mut carry = u32::from(d - b'0');
+        for group in groups.iter_mut() {
+            let x = u32::from(*group) * 10 + carry;
+            *group = (x & 0x7f) as u8;
+            carry = x >> 7;
+        }
+        while carry > 0 {
+            groups.push((carry & 0x7f) as u8);
+            carry >>= 7;
+        }
+    }
+    let negative = negative && groups.iter().any(|g| *g != 0);
+    if negative {
+        // The two's complement of the magnitude is the complement of the
+        // magnitude minus one.
+        for group in groups.iter_mut() {
+            if *group == 0 {
+                *group = 0x7f;
+            } else {
+                *group -= 1;
+                break;
+            }
+        }
+        for group in groups.iter_mut() {
+            *group = !*group & 0x7f;
+        }
+    }
+    // Drop groups that are implied by the sign, and add one if the sign of the
+    // last group is not the sign of the number.
+    let fill = if negative {
+        0x7f
+    } else {
+        0
+    };
+    while groups.len() > 1 && groups[groups.len() - 1] == fill {
+        let sign = groups[groups.len() - 2] & 0x40 != 0;
+        if signed && sign != negative {
+            break;
+        }
+        groups.pop();
+    }
+    if signed && (groups[groups.len() - 1] & 0x40 != 0) != negative {
+        groups.push(fill);
+    }
+    Some(groups)
+}
+
+/// Construct the state of a V0 contract from a JSON description. This is
+/// intended for hand-authoring states for testing contracts. The state of a V0
+/// contract is a flat byte array, and the JSON is serialized to it using the
+/// type of the state of the contract in the schema embedded in the module, see
+/// [schema_json_to_bytes] for how values of each type are described. The
+/// contract is given by its name without the `init_` prefix.
+pub fn state_from_json_v0(
+    module_bytes: &[u8],
+    contract: &str,
+    json: &serde_json::Value,
+) -> ExecResult<Vec<u8>> {
+    let ty = match get_embedded_schema_v0(module_bytes)? {
+        schema::VersionedModuleSchema::V0(module) => module
+            .contracts
+            .get(contract)
+            .with_context(|| format!("The schema does not describe the contract {}.", contract))?
+            .state
+            .clone()
+            .with_context(|| format!("The schema does not describe the state of {}.", contract))?,
+        _ => bail!("The embedded schema is not a schema for V0 modules."),
+    };
+    Ok(schema_json_to_bytes(&ty, json)?)
+}
+
+/// Construct a V1 contract state from a JSON description. This is intended
+/// for hand-authoring states for testing contracts. The schemas of V1 modules
+/// do not describe the state of contracts, so unlike [state_from_json_v0] no
+/// schema is used. Instead keys and values are given as raw hex-encoded bytes,
+/// using the following conventions to map JSON to the key-value store of the
+/// contract.
+///
+/// - The description is a JSON object. Each field of the object describes an
+///   entry, or a group of entries, of the state.
+/// - The name of a field is a hex-encoded key, or part of a key.
+/// - If the value of a field is a string it must be the hex-encoded value of
+///   the entry at the key.
+/// - If the value of a field is an object then its name is a prefix of the keys
+///   of the entries that the object describes. The keys of those entries are
+///   the concatenation of all the prefixes leading to them, followed by the
+///   name of the field that contains the value. A field with an empty name
+///   inside such an object thus describes the entry at the prefix itself.
+///
+/// For example, the descriptions
+///
+/// ```json
+/// { "00": "aa", "01": { "": "bb", "02": "cc" } }
+/// ```
+///
+/// and
+///
+/// ```json
+/// { "00": "aa", "01": "bb", "0102": "cc" }
+/// ```
+///
+/// both describe the state with three entries, `00 -> aa`, `01 -> bb`, and
+/// `0102 -> cc`. It is an error if the same key is described more than once.
+pub fn state_from_json_v1(json: &serde_json::Value) -> ExecResult<v1::trie::PersistentState> {
+    let mut loader = v1::trie::Loader::new(&[][..]);
+    let mut state = v1::trie::MutableState::initial_state();
+    {
+        let inner = state.get_inner(&mut loader);
+        let mut trie = inner.lock();
+        let mut insert = |key: &[u8], value: Vec<u8>| -> ExecResult<()> {
+            let (_, existed) = trie.insert(&mut loader, key, value)?;
+            ensure!(!existed, "The key {} is described more than once.", hex::encode(key));
+            Ok(())
+        };
+        insert_json_entries(&mut Vec::new(), json, &mut insert)?;
+    }
+    Ok(state.freeze(&mut loader, &mut v1::trie::EmptyCollector))
+}
+
+/// Insert all the entries described by the JSON value, using the convention
+/// documented in [state_from_json_v1]. The `prefix` is the key described by
+/// the enclosing objects.
+fn insert_json_entries(
+    prefix: &mut Vec<u8>,
+    json: &serde_json::Value,
+    insert: &mut impl FnMut(&[u8], Vec<u8>) -> ExecResult<()>,
+) -> ExecResult<()> {
+    let entries = json.as_object().context("The state must be described by a JSON object.")?;
+    for (name, value) in entries {
+        let key_part =
+            hex::decode(name).with_context(|| format!("Key {} is not hex-encoded.", name))?;
+        let prefix_len = prefix.len();
+        prefix.extend_from_slice(&key_part);
+        match value {
+            serde_json::Value::String(s) => {
+                let value = hex::decode(s)
+                    .with_context(|| format!("Value at key {} is not hex-encoded.", name))?;
+                insert(prefix, value)?;
+            }
+            serde_json::Value::Object(_) => insert_json_entries(prefix, value, insert)?,
+            _ => bail!(
+                "The value at key {} must be either a hex-encoded string or an object.",
+                hex::encode(prefix.as_slice())
+            ),
+        }
+        prefix.truncate(prefix_len);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_schema_value_to_json() {
+        use super::*;
+        use concordium_contracts_common::schema::{Fields, SizeLength, Type};
+        use serde_json::json;
+        let ty = Type::Struct(Fields::Named(vec![
+            ("flag".into(), Type::Bool),
+            ("items".into(), Type::List(SizeLength::U8, Box::new(Type::U16))),
+            (
+                "choice".into(),
+                Type::Enum(vec![
+                    ("A".into(), Fields::None),
+                    ("B".into(), Fields::Unnamed(vec![Type::String(SizeLength::U8)])),
+                ]),
+            ),
+            ("big".into(), Type::U128),
+            ("leb".into(), Type::ILeb128(4)),
+            ("bytes".into(), Type::ByteArray(2)),
+            ("owner".into(), Type::ContractAddress),
+        ]));
+        let mut value = vec![1, 2, 10, 0, 11, 0, 1, 2, b'h', b'i'];
+        value.extend_from_slice(&u128::MAX.to_le_bytes());
+        value.extend_from_slice(&[0xc0, 0xbb, 0x78]); // -123456
+        value.extend_from_slice(&[0xab, 0xcd]);
+        value.extend_from_slice(&[3, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            schema_value_to_json(&ty, &value),
+            Ok(json!({
+                "flag": true,
+                "items": [10, 11],
+                "choice": { "B": ["hi"] },
+                "big": u128::MAX.to_string(),
+                "leb": "-123456",
+                "bytes": "abcd",
+                "owner": { "index": 3, "subindex": 1 },
+            }))
+        );
+        value.push(0);
+        assert_eq!(schema_value_to_json(&ty, &value), Err(SchemaMismatch::TrailingBytes(1)));
+        assert!(matches!(
+            schema_value_to_json(&ty, &[0, 1, 10]),
+            Err(SchemaMismatch::Mismatch { path, .. }) if path == "value.items[0]"
+        ));
+        // Lists of values serialized as no bytes are bounded.
+        let units = Type::List(SizeLength::U32, Box::new(Type::Unit));
+        assert_eq!(schema_value_to_json(&units, &[3, 0, 0, 0]), Ok(json!([null, null, null])));
+        assert!(schema_value_to_json(&units, &[0xff, 0xff, 0xff, 0xff]).is_err());
+    }
+
+    #[test]
+    fn test_schema_json_to_bytes() {
+        use super::*;
+        use crate::utils::embed_schema;
+        use concordium_contracts_common::schema::{Fields, SizeLength, Type};
+        use serde_json::json;
+        let ty = Type::Struct(Fields::Named(vec![
+            ("flag".into(), Type::Bool),
+            ("items".into(), Type::Map(SizeLength::U8, Box::new(Type::U8), Box::new(Type::I64))),
+            (
+                "choice".into(),
+                Type::Enum(vec![
+                    ("A".into(), Fields::None),
+                    ("B".into(), Fields::Unnamed(vec![Type::String(SizeLength::U8)])),
+                ]),
+            ),
+            ("amount".into(), Type::Amount),
+            ("leb".into(), Type::ILeb128(4)),
+            ("uleb".into(), Type::ULeb128(2)),
+            ("bytes".into(), Type::ByteList(SizeLength::U16)),
+            ("owner".into(), Type::ContractAddress),
+        ]));
+        let value = json!({
+            "flag": true,
+            "items": [[1, -2], [3, 4]],
+            "choice": { "B": ["hi"] },
+            "amount": "1000000",
+            "leb": "-123456",
+            "uleb": "300",
+            "bytes": "abcd",
+            "owner": { "index": 3, "subindex": 1 },
+        });
+        let bytes = schema_json_to_bytes(&ty, &value).expect("The value matches the type.");
+        assert_eq!(&bytes[bytes.len() - 25..bytes.len() - 19], [0xc0, 0xbb, 0x78, 0xac, 0x02, 2]);
+        assert_eq!(schema_value_to_json(&ty, &bytes), Ok(value.clone()), "JSON round trip.");
+        for leb in ["0", "63", "64", "-64", "-65", "-1"] {
+            let ty = Type::ILeb128(4);
+            let bytes = schema_json_to_bytes(&ty, &json!(leb)).expect("Valid integer.");
+            assert_eq!(schema_value_to_json(&ty, &bytes), Ok(json!(leb)));
+        }
+
+        let mut wrong = value.clone();
+        wrong["uleb"] = json!("100000");
+        assert!(matches!(
+            schema_json_to_bytes(&ty, &wrong),
+            Err(SchemaMismatch::Mismatch { path, .. }) if path == "value.uleb"
+        ));
+        wrong["uleb"] = json!("-1");
+        assert!(schema_json_to_bytes(&ty, &wrong).is_err(), "Unsigned values are not negative.");
+        let mut wrong = value.clone();
+        wrong["items"][1][0] = json!(256);
+        assert!(matches!(
+            schema_json_to_bytes(&ty, &wrong),
+            Err(SchemaMismatch::Mismatch { path, .. }) if path == "value.items[1].key"
+        ));
+        let mut wrong = value;
+        wrong["extra"] = json!(null);
+        assert!(schema_json_to_bytes(&ty, &wrong).is_err(), "Unknown fields are rejected.");
+
+        let contract = schema::ContractV0 {
+            state: Some(ty),
+            ..Default::default()
+        };
+        let schema = schema::VersionedModuleSchema::V0(schema::ModuleV0 {
+            contracts: std::iter::once(("counter".to_string(), contract)).collect(),
+        });
+        let module = embed_schema(b"\0asm\x01\0\0\0", &schema).expect("Embedding succeeds.");
+        let state = state_from_json_v0(
+            &module,
+            "counter",
+            &json!({
+                "flag": false,
+                "items": [],
+                "choice": { "A": [] },
+                "amount": "0",
+                "leb": "0",
+                "uleb": "0",
+                "bytes": "",
+                "owner": { "index": 0, "subindex": 0 },
+            }),
+        )
+        .expect("The state matches the schema.");
+        assert_eq!(state.len(), 1 + 1 + 1 + 8 + 1 + 1 + 2 + 16);
+        assert!(state_from_json_v0(&module, "other", &json!(null)).is_err());
+    }
+
+    #[test]
+    fn test_state_from_json_v1() {
+        use crate::v1::trie::Loader;
+        let nested = serde_json::json!({ "00": "aa", "01": { "": "bb", "02": "cc" } });
+        let flat = serde_json::json!({ "00": "aa", "01": "bb", "0102": "cc" });
+        let nested = super::state_from_json_v1(&nested).expect("Valid state description.");
+        let flat = super::state_from_json_v1(&flat).expect("Valid state description.");
+        let mut loader = Loader::new(&[][..]);
+        assert_eq!(nested.hash(&mut loader), flat.hash(&mut loader));
+        assert_eq!(nested.lookup(&mut loader, &[0x01, 0x02]), Some(vec![0xcc]));
+
+        let duplicate = serde_json::json!({ "01": "bb", "": { "01": "cc" } });
+        assert!(super::state_from_json_v1(&duplicate).is_err(), "Duplicate keys are rejected.");
+    }
+}
@@ -0,0 +1,149 @@
+//! Tests of the mock host functions of [TestHost] used by
+//! [run_module_tests_with_host].
+use crate::utils::{
+    run_module_tests_with_expectations, run_module_tests_with_host, TestExpectation, TestHost,
+};
+
+/// A module that imports `concordium.get_parameter_size`, and exports the test
+/// `param` which traps unless the parameter is 3 bytes long.
+fn parameter_test_module() -> Vec<u8> {
+    let mut out = vec![0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00];
+    // The types () -> i32 and () -> ().
+    out.extend_from_slice(&[0x01, 0x08, 0x02, 0x60, 0x00, 0x01, 0x7F, 0x60, 0x00, 0x00]);
+    out.extend_from_slice(&[0x02, 0x21, 0x01, 0x0A]);
+    out.extend_from_slice(b"concordium");
+    out.push(0x12);
+    out.extend_from_slice(b"get_parameter_size");
+    out.extend_from_slice(&[0x00, 0x00]);
+    out.extend_from_slice(&[0x03, 0x02, 0x01, 0x01]);
+    out.extend_from_slice(&[0x07, 0x19, 0x01, 0x15]);
+    out.extend_from_slice(b"concordium_test param");
+    out.extend_from_slice(&[0x00, 0x01]);
+    // call 0; i32.const 3; i32.ne; if unreachable end
+    out.extend_from_slice(&[
+        0x0A, 0x0D, 0x01, 0x0B, 0x00, 0x10, 0x00, 0x41, 0x03, 0x47, 0x04, 0x40, 0x00, 0x0B, 0x0B,
+    ]);
+    out
+}
+
+#[test]
+fn test_host_parameter() {
+    let module = parameter_test_module();
+    let results =
+        run_module_tests_with_host(&module, &TestHost::default()).expect("The module is valid.");
+    assert_eq!(results.len(), 1, "There is one test.");
+    assert!(results[0].1.is_some(), "The default parameter is empty.");
+    let host = TestHost {
+        parameter: vec![1, 2, 3],
+        ..TestHost::default()
+    };
+    let results = run_module_tests_with_host(&module, &host).expect("The module is valid.");
+    assert_eq!(results[0].0, "param");
+    assert!(results[0].1.is_none(), "The test passes with the mock parameter: {:?}", results[0].1);
+}
+
+/// Append the unsigned LEB128 encoding of the value.
+fn leb128(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Append the section with the given id and contents.
+fn section(out: &mut Vec<u8>, id: u8, contents: &[u8]) {
+    out.push(id);
+    leb128(out, contents.len() as u32);
+    out.extend_from_slice(contents);
+}
+
+/// A module with tests that have expectations. The tests `loop`,
+/// `unexpected_trap` and `wrong_code` do not meet them.
+fn expectation_test_module() -> Vec<u8> {
+    // Pairs of the name of the export, and the type and body of the function.
+    module_with_functions(&[
+        ("concordium_test ok", 0, &[0x0B]),
+        ("concordium_test reject", 1, &[0x41, 0x7E, 0x0B]),
+        ("concordium_test_expect_reject reject", 1, &[0x41, 0x7E, 0x0B]),
+        ("concordium_test trap", 0, &[0x00, 0x0B]),
+        ("concordium_test_expect_trap trap", 0, &[0x0B]),
+        ("concordium_test loop", 0, &[0x03, 0x40, 0x0C, 0x00, 0x0B, 0x0B]),
+        ("concordium_test_max_energy loop", 2, &[0x42, 0xE8, 0x07, 0x0B]),
+        ("concordium_test unexpected_trap", 0, &[0x00, 0x0B]),
+        ("concordium_test wrong_code", 1, &[0x41, 0x7F, 0x0B]),
+    ])
+}
+
+/// A module that exports the given functions, each given by the name of the
+/// export, the index of its type, and its body. The types are () -> (),
+/// () -> i32 and () -> i64.
+fn module_with_functions(funcs: &[(&str, u8, &[u8])]) -> Vec<u8> {
+    let mut out = vec![0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00];
+    // The types () -> (), () -> i32 and () -> i64.
+    section(&mut out, 1, &[0x03, 0x60, 0x00, 0x00, 0x60, 0x00, 0x01, 0x7F, 0x60, 0x00, 0x01, 0x7E]);
+    let mut func_section = vec![funcs.len() as u8];
+    let mut export_section = vec![funcs.len() as u8];
+    let mut code_section = vec![funcs.len() as u8];
+    for (idx, (name, ty, body)) in funcs.iter().enumerate() {
+        func_section.push(*ty);
+        leb128(&mut export_section, name.len() as u32);
+        export_section.extend_from_slice(name.as_bytes());
+        export_section.extend_from_slice(&[0x00, idx as u8]);
+        // The functions have no locals.
+        code_section.push(body.len() as u8 + 1);
+        code_section.push(0x00);
+        code_section.extend_from_slice(body);
+    }
+    section(&mut out, 3, &func_section);
+    section(&mut out, 7, &export_section);
+    section(&mut out, 10, &code_section);
+    out
+}
+
+#[test]
+fn test_expectations() {
+    let module = expectation_test_module();
+    let summary = run_module_tests_with_expectations(&module, &TestHost::default())
+        .expect("The module is valid.");
+    let results: Vec<(&str, TestExpectation, bool)> = summary
+        .outcomes
+        .iter()
+        .map(|outcome| (outcome.name.as_str(), outcome.expected, outcome.failure.is_none()))
+        .collect();
+    assert_eq!(results, [
+        ("loop", TestExpectation::Success, false),
+        ("ok", TestExpectation::Success, true),
+        ("reject", TestExpectation::Reject(-2), true),
+        ("trap", TestExpectation::Trap, true),
+        ("unexpected_trap", TestExpectation::Success, false),
+        ("wrong_code", TestExpectation::Success, false),
+    ]);
+    assert_eq!(summary.outcomes[0].max_energy, Some(1000));
+    assert_eq!(summary.outcomes[0].energy_used, 1000, "The loop uses all the energy.");
+    assert!(summary.outcomes[1].energy_used > 0, "Energy is counted.");
+    assert_eq!(summary.num_passed(), 3);
+    assert_eq!(summary.num_failed(), 3);
+    assert_eq!(summary.exit_code(), 1);
+
+    let results =
+        run_module_tests_with_host(&module, &TestHost::default()).expect("The module is valid.");
+    assert_eq!(results.len(), 6, "Expectations are not tests.");
+    assert!(results[3].1.is_none(), "The expected trap is not a failure.");
+}
+
+#[test]
+fn test_negative_max_energy() {
+    let module = module_with_functions(&[
+        ("concordium_test ok", 0, &[0x0B]),
+        // i64.const -1
+        ("concordium_test_max_energy ok", 2, &[0x42, 0x7F, 0x0B]),
+    ]);
+    let err = run_module_tests_with_expectations(&module, &TestHost::default())
+        .expect_err("A negative maximum energy is rejected.");
+    assert!(err.to_string().contains("negative"), "Unexpected error: {}", err);
+}
@@ -1,18 +1,32 @@
 //! Various utilities for testing and extraction of schemas.
 
-use crate::ExecResult;
+#[cfg(feature = "tooling")]
+use crate::reject::{RejectReasonRegistry, REJECT_REASONS_SECTION};
+use crate::{
+    events::{EventSchemas, EVENT_SCHEMAS_SECTION},
+    v0, v1, ExecResult, InterpreterEnergy,
+};
 use anyhow::{anyhow, bail, ensure, Context};
-use concordium_contracts_common::{from_bytes, schema, Cursor, Deserial};
-use std::{collections::BTreeMap, default::Default};
+use concordium_contracts_common::{
+    from_bytes, schema, to_bytes, AccountAddress, Address, Amount, ChainMetadata, ContractAddress,
+    Cursor, Deserial, SlotTime,
+};
+#[cfg(feature = "tooling")]
+use serde::{Deserialize as SerdeDeserialize, Serialize as SerdeSerialize};
+use std::{collections::BTreeMap, convert::TryFrom, default::Default};
 use wasm_transform::{
     artifact::{Artifact, ArtifactNamedImport, RunnableCode, TryFromImport},
-    machine::{self, NoInterrupt, Value},
-    parse::{parse_custom, parse_skeleton},
-    types::{ExportDescription, Module, Name},
+    machine::{self, NoInterrupt, RuntimeError, Value},
+    output::{write_custom_section, Output},
+    parse::{parse_custom, parse_sec_with_default, parse_skeleton, GetParseable},
+    types::{
+        CustomSection, ExportDescription, ExportSection, FuncIndex, ImportDescription, Module,
+        Name, ValueType,
+    },
     utils, validate,
 };
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WasmVersion {
     V0,
     V1,
@@ -45,6 +59,25 @@ impl WasmVersion {
     }
 }
 
+/// Get the bytes of a module given either in the binary format or in the text
+/// format. Modules in the text format are assembled in-process, which requires
+/// the `wat` feature. The resulting bytes still have to be validated, e.g., by
+/// [utils::instantiate_with_metering], like any other module.
+pub fn module_from_source(source: &[u8]) -> ExecResult<std::borrow::Cow<[u8]>> {
+    if source.starts_with(b"\0asm") {
+        return Ok(std::borrow::Cow::Borrowed(source));
+    }
+    #[cfg(feature = "wat")]
+    {
+        let bytes = wat_parser::parse_bytes(source).context("Could not assemble the module.")?;
+        Ok(std::borrow::Cow::Owned(bytes.into_owned()))
+    }
+    #[cfg(not(feature = "wat"))]
+    bail!(
+        "The module is not in the binary format. Enable the `wat` feature to use the text format."
+    )
+}
+
 /// A host which traps for any function call.
 pub struct TrapHost;
 
@@ -63,9 +96,58 @@ impl<I> machine::Host<I> for TrapHost {
     }
 }
 
-/// A host which traps for any function call apart from `report_error` which it
-/// prints to standard out.
-pub struct TestHost;
+/// A host for running the tests of a module, see [run_module_tests]. The
+/// `report_error` function aborts the test with a [ReportError]. The host
+/// functions of V0 contracts are implemented against the mock data in the
+/// fields of the host, and functions that read data which has not been set
+/// trap. Energy is not counted.
+///
+/// The [Default] instance has an empty parameter and state, and no context.
+#[derive(Clone)]
+pub struct TestHost {
+    /// The parameter returned by `get_parameter_section`.
+    pub parameter:    Vec<u8>,
+    /// The serialized sender policies returned by `get_policy_section`.
+    pub policies:     Option<Vec<u8>>,
+    /// The state of the contract.
+    pub state:        v0::State<'static>,
+    /// The events logged by the module.
+    pub logs:         v0::Logs,
+    /// The actions produced by the module, e.g., by `accept` and `send`.
+    pub outcomes:     v0::Outcome,
+    pub slot_time:    Option<SlotTime>,
+    pub init_origin:  Option<AccountAddress>,
+    pub invoker:      Option<AccountAddress>,
+    pub self_address: Option<ContractAddress>,
+    pub self_balance: Option<Amount>,
+    pub sender:       Option<Address>,
+    pub owner:        Option<AccountAddress>,
+}
+
+impl Default for TestHost {
+    fn default() -> Self {
+        Self {
+            parameter:    Vec::new(),
+            policies:     None,
+            state:        v0::State::new(None),
+            logs:         v0::Logs::new(),
+            outcomes:     v0::Outcome::new(),
+            slot_time:    None,
+            init_origin:  None,
+            invoker:      None,
+            self_address: None,
+            self_balance: None,
+            sender:       None,
+            owner:        None,
+        }
+    }
+}
+
+/// Get a value of the mock context of the [TestHost], failing if it has not
+/// been set.
+fn mock_value<'a, A>(value: &'a Option<A>, name: &str) -> ExecResult<&'a A> {
+    value.as_ref().ok_or_else(|| anyhow!("The {} is not set in the test host.", name))
+}
 
 impl validate::ValidateImportExport for TestHost {
     /// Simply ensure that there are no duplicates.
@@ -158,9 +240,80 @@ impl machine::Host<ArtifactNamedImport> for TestHost {
                 column,
                 msg
             })
+        }
+        // The host functions below rely on the import having the type of the V0
+        // host function of the same name.
+        ensure!(
+            validate::ValidateImportExport::validate_import_function(
+                &v0::ConcordiumAllowedImports,
+                false,
+                f.get_mod_name(),
+                f.get_item_name(),
+                f.get_type()
+            ),
+            "Unsupported host function call {}.{}.",
+            f.get_mod_name(),
+            f.get_item_name()
+        );
+        let mut energy = InterpreterEnergy::from(u64::MAX);
+        let energy = &mut energy;
+        if f.matches("concordium", "get_parameter_size") {
+            v0::host::get_parameter_size(stack, self.parameter.len() as u32)?
+        } else if f.matches("concordium", "get_parameter_section") {
+            v0::host::get_parameter_section(memory, stack, energy, &self.parameter)?
+        } else if f.matches("concordium", "get_policy_section") {
+            let policies = mock_value(&self.policies, "sender policies").map(Vec::as_slice);
+            v0::host::get_policy_section(memory, stack, energy, policies)?
+        } else if f.matches("concordium", "log_event") {
+            v0::host::log_event(memory, stack, energy, &mut self.logs)?
+        } else if f.matches("concordium", "log_event_typed") {
+            v0::host::log_event_typed(memory, stack, energy, &mut self.logs)?
+        } else if f.matches("concordium", "load_state") {
+            v0::host::load_state(memory, stack, energy, &mut self.state)?
+        } else if f.matches("concordium", "write_state") {
+            v0::host::write_state(memory, stack, energy, &mut self.state)?
+        } else if f.matches("concordium", "resize_state") {
+            v0::host::resize_state(stack, energy, &mut self.state)?
+        } else if f.matches("concordium", "state_size") {
+            v0::host::state_size(stack, &mut self.state)?
+        } else if f.matches("concordium", "get_slot_time") {
+            let metadata = ChainMetadata {
+                slot_time: *mock_value(&self.slot_time, "slot time")?,
+            };
+            v0::host::get_slot_time(stack, &metadata)?
+        } else if f.matches("concordium", "get_init_origin") {
+            v0::host::get_init_origin(memory, stack, mock_value(&self.init_origin, "init origin"))?
+        } else if f.matches("concordium", "get_receive_invoker") {
+            v0::host::get_receive_invoker(memory, stack, mock_value(&self.invoker, "invoker"))?
+        } else if f.matches("concordium", "get_receive_self_address") {
+            v0::host::get_receive_self_address(
+                memory,
+                stack,
+                mock_value(&self.self_address, "self address"),
+            )?
+        } else if f.matches("concordium", "get_receive_self_balance") {
+            let self_balance = self
+                .self_balance
+                .ok_or_else(|| anyhow!("The self balance is not set in the test host."));
+            v0::host::get_receive_self_balance(stack, self_balance)?
+        } else if f.matches("concordium", "get_receive_sender") {
+            v0::host::get_receive_sender(memory, stack, mock_value(&self.sender, "sender"))?
+        } else if f.matches("concordium", "get_receive_owner") {
+            v0::host::get_receive_owner(memory, stack, mock_value(&self.owner, "owner"))?
+        } else if f.matches("concordium", "accept") {
+            v0::host::accept(stack, energy, &mut self.outcomes)?
+        } else if f.matches("concordium", "simple_transfer") {
+            v0::host::simple_transfer(memory, stack, energy, &mut self.outcomes)?
+        } else if f.matches("concordium", "send") {
+            v0::host::send(memory, stack, energy, &mut self.outcomes)?
+        } else if f.matches("concordium", "combine_and") {
+            v0::host::combine_and(stack, energy, &mut self.outcomes)?
+        } else if f.matches("concordium", "combine_or") {
+            v0::host::combine_or(stack, energy, &mut self.outcomes)?
         } else {
             bail!("Unsupported host function call.")
         }
+        Ok(None)
     }
 }
 
@@ -173,29 +326,272 @@ impl machine::Host<ArtifactNamedImport> for TestHost {
 /// if it failed. The error message is the one reported to by report_error, or
 /// some internal invariant violation.
 pub fn run_module_tests(module_bytes: &[u8]) -> ExecResult<Vec<(String, Option<ReportError>)>> {
-    let artifact = utils::instantiate::<ArtifactNamedImport, _>(&TestHost, module_bytes)?;
-    let mut out = Vec::with_capacity(artifact.export.len());
-    for name in artifact.export.keys() {
-        if let Some(test_name) = name.as_ref().strip_prefix("concordium_test ") {
-            let res = artifact.run(&mut TestHost, name, &[]);
-            match res {
-                Ok(_) => out.push((test_name.to_owned(), None)),
-                Err(msg) => {
-                    if let Some(err) = msg.downcast_ref::<ReportError>() {
-                        out.push((test_name.to_owned(), Some(err.clone())));
-                    } else {
-                        out.push((
-                            test_name.to_owned(),
-                            Some(ReportError::Other {
-                                msg: msg.to_string(),
-                            }),
-                        ))
-                    }
+    run_module_tests_with_host(module_bytes, &TestHost::default())
+}
+
+/// Like [run_module_tests], but the host functions used by the tests operate
+/// on the mock data of the given host. Each test is run with a fresh copy of
+/// it, so changes made by one test, e.g., to the state, are not visible to
+/// the others.
+///
+/// A test passes if it meets its expectations, see
+/// [run_module_tests_with_expectations]. Otherwise the result is the reason it
+/// did not.
+pub fn run_module_tests_with_host(
+    module_bytes: &[u8],
+    host: &TestHost,
+) -> ExecResult<Vec<(String, Option<ReportError>)>> {
+    let summary = run_module_tests_with_expectations(module_bytes, host)?;
+    Ok(summary.outcomes.into_iter().map(|outcome| (outcome.name, outcome.failure)).collect())
+}
+
+/// Prefix of the names of exported test functions.
+pub const TEST_PREFIX: &str = "concordium_test ";
+
+/// Prefix of the names of exported functions of type `() -> i32` that return
+/// the code the test of the same name is expected to return. See
+/// [TestExpectation::Reject].
+pub const EXPECT_REJECT_PREFIX: &str = "concordium_test_expect_reject ";
+
+/// Prefix of the names of exports whose presence declares that the test of the
+/// same name is expected to fail. See [TestExpectation::Trap].
+pub const EXPECT_TRAP_PREFIX: &str = "concordium_test_expect_trap ";
+
+/// Prefix of the names of exported functions of type `() -> i64` that return
+/// the maximum interpreter energy the test of the same name may use.
+pub const MAX_ENERGY_PREFIX: &str = "concordium_test_max_energy ";
+
+/// The outcome a test is expected to have. A test either has no result, or
+/// returns an `i32` code where 0 means success.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestExpectation {
+    /// The test returns normally, with code 0 if it returns a code.
+    Success,
+    /// The test returns the given code, e.g., the reject code of the contract
+    /// function it calls.
+    Reject(i32),
+    /// The test fails, either by calling `report_error` or by trapping.
+    Trap,
+}
+
+/// The result of running a single test, see [TestSummary].
+#[derive(Debug, Clone)]
+pub struct TestOutcome {
+    /// Name of the test, without the [TEST_PREFIX].
+    pub name:        String,
+    /// The expected outcome of the test.
+    pub expected:    TestExpectation,
+    /// The maximum interpreter energy the test may use, if it is limited.
+    pub max_energy:  Option<u64>,
+    /// The interpreter energy used by the test. This is the energy charged
+    /// for the instructions and memory of the test, but not for the host
+    /// functions it calls.
+    pub energy_used: u64,
+    /// `None` if the test met its expectations, and the reason it did not
+    /// otherwise.
+    pub failure:     Option<ReportError>,
+}
+
+/// The results of all the tests of a module, ordered by the names of the
+/// tests.
+#[derive(Debug, Clone, Default)]
+pub struct TestSummary {
+    pub outcomes: Vec<TestOutcome>,
+}
+
+impl TestSummary {
+    /// The number of tests that met their expectations.
+    pub fn num_passed(&self) -> usize {
+        self.outcomes.iter().filter(|outcome| outcome.failure.is_none()).count()
+    }
+
+    /// The number of tests that did not meet their expectations.
+    pub fn num_failed(&self) -> usize { self.outcomes.len() - self.num_passed() }
+
+    /// The exit code of a test runner, which is 0 if all tests passed and 1
+    /// otherwise.
+    pub fn exit_code(&self) -> i32 {
+        if self.num_failed() == 0 {
+            0
+        } else {
+            1
+        }
+    }
+}
+
+impl std::fmt::Display for TestSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for outcome in self.outcomes.iter() {
+            match &outcome.failure {
+                None => {
+                    writeln!(f, "test {} ... ok ({} energy)", outcome.name, outcome.energy_used)?
                 }
-            };
+                Some(err) => writeln!(f, "test {} ... FAILED: {}", outcome.name, err)?,
+            }
+        }
+        write!(f, "{} passed; {} failed", self.num_passed(), self.num_failed())
+    }
+}
+
+/// The [TestHost] together with the accounting of energy and nested calls
+/// that the metering transformation relies on.
+struct MeteredTestHost {
+    host:              TestHost,
+    energy:            InterpreterEnergy,
+    activation_frames: u32,
+}
+
+impl MeteredTestHost {
+    fn new(host: &TestHost, energy: u64) -> Self {
+        Self {
+            host:              host.clone(),
+            energy:            InterpreterEnergy {
+                energy,
+            },
+            activation_frames: crate::constants::MAX_ACTIVATION_FRAMES,
         }
     }
-    Ok(out)
+}
+
+impl machine::Host<ArtifactNamedImport> for MeteredTestHost {
+    type Interrupt = NoInterrupt;
+
+    fn tick_initial_memory(&mut self, num_pages: u32) -> machine::RunResult<()> {
+        self.energy.charge_memory_alloc(num_pages)
+    }
+
+    fn call(
+        &mut self,
+        f: &ArtifactNamedImport,
+        memory: &mut Vec<u8>,
+        stack: &mut machine::RuntimeStack,
+    ) -> machine::RunResult<Option<NoInterrupt>> {
+        if f.matches("concordium_metering", "account_energy") {
+            self.energy.tick_energy(unsafe { stack.pop_u64() })?
+        } else if f.matches("concordium_metering", "track_call") {
+            v0::host::track_call(&mut self.activation_frames)?
+        } else if f.matches("concordium_metering", "track_return") {
+            v0::host::track_return(&mut self.activation_frames)
+        } else if f.matches("concordium_metering", "account_memory") {
+            v0::host::charge_memory_alloc(stack, &mut self.energy)?
+        } else {
+            return machine::Host::call(&mut self.host, f, memory, stack);
+        }
+        Ok(None)
+    }
+}
+
+/// Run the tests of the module like [run_module_tests_with_host], and check
+/// that each test meets the expectations the module exports for it:
+/// - a function named [EXPECT_REJECT_PREFIX] followed by the name of the test
+///   returns the code the test is expected to return,
+/// - an export named [EXPECT_TRAP_PREFIX] followed by the name of the test
+///   declares that the test is expected to fail,
+/// - a function named [MAX_ENERGY_PREFIX] followed by the name of the test
+///   returns the maximum interpreter energy the test may use.
+///
+/// Tests without expectations are expected to succeed. The module is metered
+/// like a contract, so that the energy used by each test is reported.
+pub fn run_module_tests_with_expectations(
+    module_bytes: &[u8],
+    host: &TestHost,
+) -> ExecResult<TestSummary> {
+    let artifact = utils::instantiate_with_metering::<ArtifactNamedImport, _>(host, module_bytes)?;
+    let has_export = |name: &str| artifact.export.keys().any(|export| export.as_ref() == name);
+    // Evaluate the expectation function with the given name, if it is exported.
+    let expectation = |name: String| -> ExecResult<Option<Value>> {
+        if !has_export(&name) {
+            return Ok(None);
+        }
+        match artifact.run(&mut MeteredTestHost::new(host, u64::MAX), name.as_str(), &[]) {
+            Ok(machine::ExecutionOutcome::Success {
+                result: Some(value),
+                ..
+            }) => Ok(Some(value)),
+            Ok(_) => bail!("The expectation {} does not return a value.", name),
+            Err(e) => Err(e.into_anyhow().context(format!("Could not evaluate {}.", name))),
+        }
+    };
+    let mut summary = TestSummary::default();
+    for name in artifact.export.keys() {
+        let test_name = if let Some(test_name) = name.as_ref().strip_prefix(TEST_PREFIX) {
+            test_name
+        } else {
+            continue;
+        };
+        let expected = if has_export(&format!("{}{}", EXPECT_TRAP_PREFIX, test_name)) {
+            TestExpectation::Trap
+        } else {
+            match expectation(format!("{}{}", EXPECT_REJECT_PREFIX, test_name))? {
+                None => TestExpectation::Success,
+                Some(Value::I32(code)) => TestExpectation::Reject(code),
+                Some(_) => bail!("The expected reject code of {} is not an i32.", test_name),
+            }
+        };
+        let max_energy = match expectation(format!("{}{}", MAX_ENERGY_PREFIX, test_name))? {
+            None => None,
+            Some(Value::I64(energy)) => Some(u64::try_from(energy).map_err(|_| {
+                anyhow!("The maximum energy of {} is negative: {}.", test_name, energy)
+            })?),
+            Some(_) => bail!("The maximum energy of {} is not an i64.", test_name),
+        };
+        let initial_energy = max_energy.unwrap_or(u64::MAX);
+        let mut test_host = MeteredTestHost::new(host, initial_energy);
+        let res = artifact.run(&mut test_host, name, &[]);
+        let energy_used = initial_energy - test_host.energy.energy;
+        let failure = match (res, expected) {
+            (Err(RuntimeError::OutOfEnergy), _) if max_energy.is_some() => {
+                Some(ReportError::Other {
+                    msg: format!("The test used more than {} energy.", initial_energy),
+                })
+            }
+            (Err(_), TestExpectation::Trap) => None,
+            (Err(e), _) => {
+                let msg = e.into_anyhow();
+                if let Some(err) = msg.downcast_ref::<ReportError>() {
+                    Some(err.clone())
+                } else {
+                    Some(ReportError::Other {
+                        msg: msg.to_string(),
+                    })
+                }
+            }
+            (Ok(_), TestExpectation::Trap) => Some(ReportError::Other {
+                msg: "The test was expected to fail, but succeeded.".into(),
+            }),
+            (Ok(outcome), expected) => {
+                let code = match outcome {
+                    machine::ExecutionOutcome::Success {
+                        result: Some(Value::I32(code)),
+                        ..
+                    } => code,
+                    _ => 0,
+                };
+                let expected_code = match expected {
+                    TestExpectation::Reject(code) => code,
+                    _ => 0,
+                };
+                if code == expected_code {
+                    None
+                } else {
+                    Some(ReportError::Other {
+                        msg: format!(
+                            "The test returned code {}, but code {} was expected.",
+                            code, expected_code
+                        ),
+                    })
+                }
+            }
+        };
+        summary.outcomes.push(TestOutcome {
+            name: test_name.to_owned(),
+            expected,
+            max_energy,
+            energy_used,
+            failure,
+        });
+    }
+    Ok(summary)
 }
 
 /// Tries to generate a state schema and schemas for parameters of methods of a
@@ -203,13 +599,26 @@ pub fn run_module_tests(module_bytes: &[u8]) -> ExecResult<Vec<(String, Option<R
 pub fn generate_contract_schema_v0(
     module_bytes: &[u8],
 ) -> ExecResult<schema::VersionedModuleSchema> {
-    let artifact = utils::instantiate::<ArtifactNamedImport, _>(&TestHost, module_bytes)?;
+    let artifact =
+        utils::instantiate::<ArtifactNamedImport, _>(&TestHost::default(), module_bytes)?;
+    contract_schema_v0_from_artifact(&artifact, &mut TrapHost)
+}
 
+/// Generate the schema of a V0 module by running its schema functions with the
+/// given host.
+pub(crate) fn contract_schema_v0_from_artifact<
+    I: TryFromImport,
+    C: RunnableCode,
+    H: machine::Host<I>,
+>(
+    artifact: &Artifact<I, C>,
+    host: &mut H,
+) -> ExecResult<schema::VersionedModuleSchema> {
     let mut contract_schemas = BTreeMap::new();
 
     for name in artifact.export.keys() {
         if let Some(contract_name) = name.as_ref().strip_prefix("concordium_schema_state_") {
-            let schema_type = generate_schema_run(&artifact, name.as_ref())?;
+            let schema_type = generate_schema_run(artifact, host, name.as_ref())?;
 
             // Get the mutable reference to the contract schema, or make a new empty one if
             // an entry does not yet exist.
@@ -220,14 +629,14 @@ pub fn generate_contract_schema_v0(
             contract_schema.state = Some(schema_type);
         } else if let Some(rest) = name.as_ref().strip_prefix("concordium_schema_function_") {
             if let Some(contract_name) = rest.strip_prefix("init_") {
-                let schema_type = generate_schema_run(&artifact, name.as_ref())?;
+                let schema_type = generate_schema_run(artifact, host, name.as_ref())?;
 
                 let contract_schema = contract_schemas
                     .entry(contract_name.to_owned())
                     .or_insert_with(schema::ContractV0::default);
                 contract_schema.init = Some(schema_type);
             } else if rest.contains('.') {
-                let schema_type = generate_schema_run(&artifact, name.as_ref())?;
+                let schema_type = generate_schema_run(artifact, host, name.as_ref())?;
 
                 // Generates receive-function parameter schema type
                 let split_name: Vec<_> = rest.splitn(2, '.').collect();
@@ -252,25 +661,53 @@ pub fn generate_contract_schema_v0(
 }
 
 /// Tries to generate schemas for parameters and return values of methods for a
-/// V1 contract.
+/// V1 contract. The schema of the return value of an entrypoint can be part of
+/// the function schema, or be given by a separate
+/// `concordium_schema_function_return_<entrypoint>` export.
 pub fn generate_contract_schema_v1(
     module_bytes: &[u8],
 ) -> ExecResult<schema::VersionedModuleSchema> {
-    let artifact = utils::instantiate::<ArtifactNamedImport, _>(&TestHost, module_bytes)?;
+    let artifact =
+        utils::instantiate::<ArtifactNamedImport, _>(&TestHost::default(), module_bytes)?;
+    contract_schema_v1_from_artifact(&artifact, &mut TrapHost)
+}
 
+/// Generate the schema of a V1 module by running its schema functions with the
+/// given host.
+pub(crate) fn contract_schema_v1_from_artifact<
+    I: TryFromImport,
+    C: RunnableCode,
+    H: machine::Host<I>,
+>(
+    artifact: &Artifact<I, C>,
+    host: &mut H,
+) -> ExecResult<schema::VersionedModuleSchema> {
     let mut contract_schemas = BTreeMap::new();
+    // Schemas of return values given by separate exports, indexed by the name
+    // of the entrypoint. They are added to the function schemas at the end since
+    // the exports are not ordered by entrypoint.
+    let mut return_values = BTreeMap::new();
 
     for name in artifact.export.keys() {
         if let Some(rest) = name.as_ref().strip_prefix("concordium_schema_function_") {
-            if let Some(contract_name) = rest.strip_prefix("init_") {
-                let function_schema = generate_schema_run(&artifact, name.as_ref())?;
+            // A contract could be named `return_...`, so the export is only
+            // treated as a return value schema if the entrypoint exists.
+            if let Some(entrypoint) = rest
+                .strip_prefix("return_")
+                .filter(|entrypoint| artifact.export.contains_key(*entrypoint))
+            {
+                let return_value: schema::Type =
+                    generate_schema_run(artifact, host, name.as_ref())?;
+                return_values.insert(entrypoint, return_value);
+            } else if let Some(contract_name) = rest.strip_prefix("init_") {
+                let function_schema = generate_schema_run(artifact, host, name.as_ref())?;
 
                 let contract_schema = contract_schemas
                     .entry(contract_name.to_owned())
                     .or_insert_with(schema::ContractV1::default);
                 contract_schema.init = Some(function_schema);
             } else if rest.contains('.') {
-                let function_schema = generate_schema_run(&artifact, name.as_ref())?;
+                let function_schema = generate_schema_run(artifact, host, name.as_ref())?;
 
                 // Generates receive-function parameter schema type
                 let split_name: Vec<_> = rest.splitn(2, '.').collect();
@@ -289,21 +726,65 @@ pub fn generate_contract_schema_v1(
         }
     }
 
+    for (entrypoint, return_value) in return_values {
+        if let Some(contract_name) = entrypoint.strip_prefix("init_") {
+            let contract_schema = contract_schemas
+                .entry(contract_name.to_owned())
+                .or_insert_with(schema::ContractV1::default);
+            contract_schema.init =
+                Some(with_return_value(contract_schema.init.as_ref(), return_value));
+        } else {
+            let split_name: Vec<_> = entrypoint.splitn(2, '.').collect();
+            let (contract_name, function_name) = match split_name[..] {
+                [contract_name, function_name] => (contract_name, function_name),
+                _ => continue,
+            };
+            let contract_schema = contract_schemas
+                .entry(contract_name.to_owned())
+                .or_insert_with(schema::ContractV1::default);
+            let function_schema =
+                with_return_value(contract_schema.receive.get(function_name), return_value);
+            contract_schema.receive.insert(function_name.to_owned(), function_schema);
+        }
+    }
+
     Ok(schema::VersionedModuleSchema::V1(schema::ModuleV1 {
         contracts: contract_schemas,
     }))
 }
 
+/// Combine the schema of the return value of a function with the parameter
+/// schema of the function, if it has one. A return value schema that was
+/// already part of the function schema is replaced.
+fn with_return_value(
+    function: Option<&schema::FunctionV1>,
+    return_value: schema::Type,
+) -> schema::FunctionV1 {
+    match function.and_then(schema::FunctionV1::parameter) {
+        Some(parameter) => schema::FunctionV1::Both {
+            param: parameter.clone(),
+            rv:    return_value,
+        },
+        None => schema::FunctionV1::Rv(return_value),
+    }
+}
+
 /// Runs the given schema function and reads the resulting function schema from
 /// memory, attempting to parse it. If this fails, an error is returned.
-fn generate_schema_run<I: TryFromImport, C: RunnableCode, SchemaType: Deserial>(
+fn generate_schema_run<
+    I: TryFromImport,
+    C: RunnableCode,
+    H: machine::Host<I>,
+    SchemaType: Deserial,
+>(
     artifact: &Artifact<I, C>,
+    host: &mut H,
     schema_fn_name: &str,
 ) -> ExecResult<SchemaType> {
     let (ptr, memory) = if let machine::ExecutionOutcome::Success {
         result: Some(Value::I32(ptr)),
         memory,
-    } = artifact.run(&mut TrapHost, schema_fn_name, &[])?
+    } = artifact.run(host, schema_fn_name, &[])?
     {
         (ptr as u32 as usize, memory)
     } else {
@@ -358,63 +839,1225 @@ pub fn get_receives(module: &Module) -> Vec<&Name> {
 ///
 /// First attempt to use the schema in the custom section "concordium-schema"
 /// and if this is not present try to use the custom section
-/// "concordium-schema-v1".
+/// "concordium-schema-v1". A schema in "concordium-schema" must be for modules
+/// of version 0.
 pub fn get_embedded_schema_v0(bytes: &[u8]) -> ExecResult<schema::VersionedModuleSchema> {
+    get_embedded_schema(bytes, WasmVersion::V0)
+}
+
+/// Get the embedded schema for smart contract modules version 1 if it exists.
+///
+/// First attempt to use the schema in the custom section "concordium-schema"
+/// and if this is not present try to use the custom section
+/// "concordium-schema-v2". A schema in "concordium-schema" must be for modules
+/// of version 1.
+pub fn get_embedded_schema_v1(bytes: &[u8]) -> ExecResult<schema::VersionedModuleSchema> {
+    get_embedded_schema(bytes, WasmVersion::V1)
+}
+
+/// Get the embedded schema for smart contract modules of the given version,
+/// see [get_embedded_schema_v0] and [get_embedded_schema_v1].
+fn get_embedded_schema(
+    bytes: &[u8],
+    version: WasmVersion,
+) -> ExecResult<schema::VersionedModuleSchema> {
     let skeleton = parse_skeleton(bytes)?;
-    let mut schema_v1_section = None;
-    let mut schema_versioned_section = None;
+    let mut unversioned_section = None;
+    let mut versioned_section = None;
     for ucs in skeleton.custom.iter() {
         let cs = parse_custom(ucs)?;
-
-        if cs.name.as_ref() == "concordium-schema" && schema_versioned_section.is_none() {
-            schema_versioned_section = Some(cs)
-        } else if cs.name.as_ref() == "concordium-schema-v1" && schema_v1_section.is_none() {
-            schema_v1_section = Some(cs)
+        if cs.name.as_ref() == SCHEMA_SECTION && versioned_section.is_none() {
+            versioned_section = Some(cs)
+        } else if cs.name.as_ref() == SchemaVersion::unversioned(version).section_name()
+            && unversioned_section.is_none()
+        {
+            unversioned_section = Some(cs)
         }
     }
 
-    if let Some(cs) = schema_versioned_section {
-        let module: schema::VersionedModuleSchema =
-            from_bytes(cs.contents).map_err(|_| anyhow!("Failed parsing schema"))?;
-        Ok(module)
-    } else if let Some(cs) = schema_v1_section {
-        let module = from_bytes(cs.contents).map_err(|_| anyhow!("Failed parsing schema"))?;
-        Ok(schema::VersionedModuleSchema::V0(module))
+    let (schema_version, module) = if let Some(cs) = versioned_section {
+        parse_schema_section(&cs)?
+    } else if let Some(cs) = unversioned_section {
+        parse_schema_section(&cs)?
     } else {
         bail!("No schema found in the module")
     }
+    .ok_or_else(|| anyhow!("Failed parsing schema"))?;
+    ensure!(
+        schema_version.wasm_version() == version,
+        "The module contains a schema for modules of version {:?}, not {:?}.",
+        schema_version.wasm_version(),
+        version
+    );
+    Ok(module)
 }
 
-/// Get the embedded schema for smart contract modules version 1 if it exists.
+/// Name of the custom section that contains the versioned schema of a module.
+pub const SCHEMA_SECTION: &str = "concordium-schema";
+
+/// Names of the custom sections that contain the unversioned schemas of
+/// modules of version 0 and 1, respectively.
+const UNVERSIONED_SCHEMA_SECTIONS: [&str; 2] = ["concordium-schema-v1", "concordium-schema-v2"];
+
+/// Serialize the schema in the canonical form in which it is embedded in
+/// modules. Contracts and their functions are kept in [BTreeMap]s, so they are
+/// serialized in the order of their names regardless of the order in which
+/// they were generated, and the output only depends on the schema itself.
+pub fn schema_to_bytes(schema: &schema::VersionedModuleSchema) -> Vec<u8> { to_bytes(schema) }
+
+/// Embed the schema in the module, in the custom section [SCHEMA_SECTION].
+/// Any existing schema sections, versioned or not, are replaced.
 ///
-/// First attempt to use the schema in the custom section "concordium-schema"
-/// and if this is not present try to use the custom section
-/// "concordium-schema-v2".
-pub fn get_embedded_schema_v1(bytes: &[u8]) -> ExecResult<schema::VersionedModuleSchema> {
+/// The schema section is added at the end of the module, which is also where
+/// the build tools put it. Embedding the schema extracted from such a module
+/// thus reproduces the module exactly.
+pub fn embed_schema(bytes: &[u8], schema: &schema::VersionedModuleSchema) -> ExecResult<Vec<u8>> {
+    replace_custom_sections(
+        bytes,
+        |name| name == SCHEMA_SECTION || UNVERSIONED_SCHEMA_SECTIONS.contains(&name),
+        SCHEMA_SECTION,
+        &schema_to_bytes(schema),
+    )
+}
+
+/// Get the contents of the custom section with the given name, if the module
+/// has one. This fails if the module has more than one such section.
+pub fn get_custom_section<'a>(bytes: &'a [u8], name: &str) -> ExecResult<Option<&'a [u8]>> {
     let skeleton = parse_skeleton(bytes)?;
-    let mut schema_v2_section = None;
-    let mut schema_versioned_section = None;
+    let mut contents = None;
     for ucs in skeleton.custom.iter() {
         let cs = parse_custom(ucs)?;
-        if cs.name.as_ref() == "concordium-schema" && schema_versioned_section.is_none() {
-            schema_versioned_section = Some(cs)
-        } else if cs.name.as_ref() == "concordium-schema-v2" && schema_v2_section.is_none() {
-            schema_v2_section = Some(cs)
+        if cs.name.as_ref() == name {
+            ensure!(contents.is_none(), "The module contains more than one {} section.", name);
+            contents = Some(cs.contents);
+        }
+    }
+    Ok(contents)
+}
+
+/// Embed the contents in the module, in the custom section with the given
+/// name. Any existing sections with the name are replaced. The section is
+/// added at the end of the module.
+pub fn embed_custom_section(bytes: &[u8], name: &str, contents: &[u8]) -> ExecResult<Vec<u8>> {
+    replace_custom_sections(bytes, |n| n == name, name, contents)
+}
+
+/// Remove the custom sections whose names satisfy `remove` from the module,
+/// and add a custom section with the given name and contents at the end.
+fn replace_custom_sections(
+    bytes: &[u8],
+    remove: impl Fn(&str) -> bool,
+    name: &str,
+    contents: &[u8],
+) -> ExecResult<Vec<u8>> {
+    let mut skeleton = parse_skeleton(bytes)?;
+    let mut custom = Vec::with_capacity(skeleton.custom.len());
+    for ucs in skeleton.custom {
+        if !remove(parse_custom(&ucs)?.name.as_ref()) {
+            custom.push(ucs);
         }
     }
+    skeleton.custom = custom;
+    let mut out = Vec::new();
+    skeleton.output(&mut out)?;
+    write_custom_section(&mut out, &CustomSection {
+        name: name.into(),
+        contents,
+    })?;
+    Ok(out)
+}
 
-    if let Some(cs) = schema_versioned_section {
-        let module: schema::VersionedModuleSchema =
-            from_bytes(cs.contents).map_err(|_| anyhow!("Failed parsing schema"))?;
-        Ok(module)
-    } else if let Some(cs) = schema_v2_section {
-        let module = from_bytes(cs.contents).map_err(|_| anyhow!("Failed parsing schema"))?;
-        Ok(schema::VersionedModuleSchema::V1(module))
+/// Reasons why a parameter is rejected by [validate_parameter], or why bytes
+/// cannot be decoded by
+/// [schema_value_to_json](crate::schema_json::schema_value_to_json).
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum SchemaMismatch {
+    /// The module has no embedded schema, or it could not be parsed.
+    #[error("The module has no usable schema: {0}")]
+    NoSchema(String),
+    /// The schema does not describe the parameter of the entrypoint.
+    #[error("The schema does not describe the parameter of {0}.")]
+    NoParameterSchema(String),
+    /// The bytes do not match the schema. The path locates the offending part
+    /// of the value, e.g., `parameter.to.Contract.1`.
+    #[error("The value does not match the schema at {path}: {reason}")]
+    Mismatch {
+        path:   String,
+        reason: String,
+    },
+    /// The bytes are longer than the value described by the schema.
+    #[error("The value has {0} trailing bytes not described by the schema.")]
+    TrailingBytes(usize),
+}
+
+/// Check that the parameter is a serialization of a value of the type that the
+/// embedded schema of the module gives for the parameter of the entrypoint.
+/// The entrypoint is the name of the exported function, i.e., either
+/// `init_<contract>` or `<contract>.<function>`.
+///
+/// This is intended to be called before execution so that a malformed
+/// parameter is reported in terms of the schema, instead of as a failure of
+/// the contract. The check is purely structural. Invariants that the contract
+/// might additionally enforce, such as the ordering of elements of sets and
+/// maps, are not checked.
+pub fn validate_parameter(
+    version: WasmVersion,
+    module_bytes: &[u8],
+    entrypoint: &str,
+    parameter: &[u8],
+) -> Result<(), SchemaMismatch> {
+    let schema = match version {
+        WasmVersion::V0 => get_embedded_schema_v0(module_bytes),
+        WasmVersion::V1 => get_embedded_schema_v1(module_bytes),
+    }
+    .map_err(|e| SchemaMismatch::NoSchema(e.to_string()))?;
+    let ty = parameter_schema(&schema, entrypoint)
+        .ok_or_else(|| SchemaMismatch::NoParameterSchema(entrypoint.into()))?;
+    validate_parameter_with_schema(ty, parameter)
+}
+
+/// Check that the parameter is a serialization of a value of the given type,
+/// with no trailing bytes. See [validate_parameter].
+pub fn validate_parameter_with_schema(
+    ty: &schema::Type,
+    parameter: &[u8],
+) -> Result<(), SchemaMismatch> {
+    let mut source = parameter;
+    check_value(ty, &mut source, &mut String::from("parameter"))?;
+    if source.is_empty() {
+        Ok(())
     } else {
-        bail!("No schema found in the module")
+        Err(SchemaMismatch::TrailingBytes(source.len()))
+    }
+}
+
+/// Split the name of an entrypoint into the name of the contract and, for
+/// receive functions, the name of the function.
+fn split_entrypoint(entrypoint: &str) -> Option<(&str, Option<&str>)> {
+    match entrypoint.strip_prefix("init_") {
+        Some(contract) if !contract.contains('.') => Some((contract, None)),
+        _ => {
+            let mut parts = entrypoint.splitn(2, '.');
+            let contract = parts.next()?;
+            Some((contract, Some(parts.next()?)))
+        }
+    }
+}
+
+/// Look up the type of the parameter of the given entrypoint in the schema.
+fn parameter_schema<'a>(
+    schema: &'a schema::VersionedModuleSchema,
+    entrypoint: &str,
+) -> Option<&'a schema::Type> {
+    let (contract, function) = split_entrypoint(entrypoint)?;
+    match schema {
+        schema::VersionedModuleSchema::V0(module) => {
+            let contract = module.contracts.get(contract)?;
+            match function {
+                None => contract.init.as_ref(),
+                Some(function) => contract.receive.get(function),
+            }
+        }
+        schema::VersionedModuleSchema::V1(module) => {
+            let contract = module.contracts.get(contract)?;
+            match function {
+                None => contract.init.as_ref()?.parameter(),
+                Some(function) => contract.receive.get(function)?.parameter(),
+            }
+        }
+    }
+}
+
+pub(crate) fn mismatch(path: &str, reason: impl Into<String>) -> SchemaMismatch {
+    SchemaMismatch::Mismatch {
+        path:   path.into(),
+        reason: reason.into(),
+    }
+}
+
+/// Run the check with the segment appended to the path, and restore the path
+/// afterwards.
+pub(crate) fn with_segment<A>(
+    path: &mut String,
+    segment: &str,
+    f: impl FnOnce(&mut String) -> A,
+) -> A {
+    let len = path.len();
+    path.push_str(segment);
+    let r = f(path);
+    path.truncate(len);
+    r
+}
+
+/// Consume the given number of bytes from the source.
+pub(crate) fn take<'a>(
+    source: &mut &'a [u8],
+    n: usize,
+    path: &str,
+) -> Result<&'a [u8], SchemaMismatch> {
+    if source.len() < n {
+        return Err(mismatch(
+            path,
+            format!("expected {} more bytes, but only {} remain", n, source.len()),
+        ));
+    }
+    let (taken, rest) = source.split_at(n);
+    *source = rest;
+    Ok(taken)
+}
+
+/// Consume a little-endian length of the given size.
+pub(crate) fn take_length(
+    size: &schema::SizeLength,
+    source: &mut &[u8],
+    path: &str,
+) -> Result<usize, SchemaMismatch> {
+    let bytes = match size {
+        schema::SizeLength::U8 => take(source, 1, path)?,
+        schema::SizeLength::U16 => take(source, 2, path)?,
+        schema::SizeLength::U32 => take(source, 4, path)?,
+        schema::SizeLength::U64 => take(source, 8, path)?,
+    };
+    let mut buf = [0u8; 8];
+    buf[..bytes.len()].copy_from_slice(bytes);
+    let len = u64::from_le_bytes(buf);
+    usize::try_from(len).map_err(|_| mismatch(path, format!("length {} is too large", len)))
+}
+
+fn check_value(
+    ty: &schema::Type,
+    source: &mut &[u8],
+    path: &mut String,
+) -> Result<(), SchemaMismatch> {
+    use schema::Type;
+    match ty {
+        Type::Unit => Ok(()),
+        Type::Bool => match take(source, 1, path)?[0] {
+            0 | 1 => Ok(()),
+            b => Err(mismatch(path, format!("{} is not a boolean", b))),
+        },
+        Type::U8 | Type::I8 => take(source, 1, path).map(drop),
+        Type::U16 | Type::I16 => take(source, 2, path).map(drop),
+        Type::U32 | Type::I32 => take(source, 4, path).map(drop),
+        Type::U64 | Type::I64 | Type::Amount | Type::Timestamp | Type::Duration => {
+            take(source, 8, path).map(drop)
+        }
+        Type::U128 | Type::I128 | Type::ContractAddress => take(source, 16, path).map(drop),
+        Type::AccountAddress => take(source, 32, path).map(drop),
+        Type::Pair(l, r) => {
+            with_segment(path, ".0", |path| check_value(l, source, path))?;
+            with_segment(path, ".1", |path| check_value(r, source, path))
+        }
+        Type::List(size, elem) | Type::Set(size, elem) => {
+            let len = take_length(size, source, path)?;
+            check_elements(len, source, path, |source, path| check_value(elem, source, path))
+        }
+        Type::Map(size, k, v) => {
+            let len = take_length(size, source, path)?;
+            check_elements(len, source, path, |source, path| {
+                with_segment(path, ".key", |path| check_value(k, source, path))?;
+                with_segment(path, ".value", |path| check_value(v, source, path))
+            })
+        }
+        Type::Array(len, elem) => check_elements(*len as usize, source, path, |source, path| {
+            check_value(elem, source, path)
+        }),
+        Type::Struct(fields) => check_fields(fields, source, path),
+        Type::Enum(variants) => {
+            // Enums are serialized the same way as by the derived Serial instances.
+            let tag = if variants.len() <= 256 {
+                usize::from(take(source, 1, path)?[0])
+            } else {
+                let bytes = take(source, 2, path)?;
+                usize::from(u16::from_le_bytes([bytes[0], bytes[1]]))
+            };
+            let (name, fields) = variants.get(tag).ok_or_else(|| {
+                mismatch(
+                    path,
+                    format!("{} is not a tag of any of the {} variants", tag, variants.len()),
+                )
+            })?;
+            with_segment(path, &format!(".{}", name), |path| check_fields(fields, source, path))
+        }
+        Type::String(size) | Type::ContractName(size) | Type::ReceiveName(size) => {
+            let len = take_length(size, source, path)?;
+            let bytes = take(source, len, path)?;
+            std::str::from_utf8(bytes)
+                .map_err(|_| mismatch(path, "the string is not valid UTF-8"))?;
+            Ok(())
+        }
+        Type::ULeb128(max) | Type::ILeb128(max) => {
+            for _ in 0..*max {
+                if take(source, 1, path)?[0] & 0x80 == 0 {
+                    return Ok(());
+                }
+            }
+            Err(mismatch(path, format!("the LEB128 encoding is longer than {} bytes", max)))
+        }
+        Type::ByteList(size) => {
+            let len = take_length(size, source, path)?;
+            take(source, len, path).map(drop)
+        }
+        Type::ByteArray(len) => take(source, *len as usize, path).map(drop),
+    }
+}
+
+/// Check the given number of elements of a collection.
+fn check_elements(
+    len: usize,
+    source: &mut &[u8],
+    path: &mut String,
+    mut check: impl FnMut(&mut &[u8], &mut String) -> Result<(), SchemaMismatch>,
+) -> Result<(), SchemaMismatch> {
+    for i in 0..len {
+        let remaining = source.len();
+        with_segment(path, &format!("[{}]", i), |path| check(source, path))?;
+        // All elements have the same type, so if one consumed no input the rest
+        // are valid as well. This bounds the work by the size of the parameter.
+        if source.len() == remaining {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn check_fields(
+    fields: &schema::Fields,
+    source: &mut &[u8],
+    path: &mut String,
+) -> Result<(), SchemaMismatch> {
+    match fields {
+        schema::Fields::Named(fields) => {
+            for (name, ty) in fields {
+                with_segment(path, &format!(".{}", name), |path| check_value(ty, source, path))?;
+            }
+            Ok(())
+        }
+        schema::Fields::Unnamed(fields) => {
+            for (i, ty) in fields.iter().enumerate() {
+                with_segment(path, &format!(".{}", i), |path| check_value(ty, source, path))?;
+            }
+            Ok(())
+        }
+        schema::Fields::None => Ok(()),
+    }
+}
+
+/// Name of the custom section that contains the entrypoint table of a module.
+/// See [EntrypointTable].
+pub const ENTRYPOINT_TABLE_SECTION: &str = "concordium-entrypoints";
+
+/// A table mapping the names of the exported functions of a module to their
+/// function indices. The table is generated at build time and embedded in the
+/// custom section [ENTRYPOINT_TABLE_SECTION], see [embed_entrypoint_table]. It
+/// is serialized as a Wasm vector of pairs of a name and a function index.
+///
+/// The table is optional, but if present it must list exactly the exported
+/// functions of the module, which [get_entrypoint_table] checks. The indices
+/// refer to the module as it was built. Execution does not consult the table
+/// since the exports of an artifact already map names to functions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntrypointTable {
+    pub entries: BTreeMap<Name, FuncIndex>,
+}
+
+/// Differences between the entrypoint tables of two versions of a module, see
+/// [EntrypointTable::diff].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntrypointTableDiff {
+    /// Entrypoints of the old version that are not in the new one.
+    pub removed: Vec<Name>,
+    /// Entrypoints of the new version that are not in the old one.
+    pub added:   Vec<Name>,
+    /// Pairs of an entrypoint of the old version that is not in the new one,
+    /// and an entrypoint of the new version that is not in the old one, which
+    /// refer to the same function index. These are likely renamed.
+    pub renamed: Vec<(Name, Name)>,
+}
+
+impl EntrypointTable {
+    /// Construct the table that lists the given exported functions.
+    pub fn from_exports(exports: &ExportSection) -> Self {
+        let entries = exports
+            .exports
+            .iter()
+            .filter_map(|export| match export.description {
+                ExportDescription::Func {
+                    index,
+                } => Some((export.name.clone(), index)),
+                _ => None,
+            })
+            .collect();
+        Self {
+            entries,
+        }
+    }
+
+    /// Serialize the table as the contents of the custom section.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        (self.entries.len() as u32).output(&mut out).expect("Writing to a vector succeeds.");
+        for (name, index) in self.entries.iter() {
+            name.output(&mut out).expect("Writing to a vector succeeds.");
+            index.output(&mut out).expect("Writing to a vector succeeds.");
+        }
+        out
+    }
+
+    /// Parse the table from the contents of the custom section. This fails if
+    /// a name is listed more than once, or if there is trailing data.
+    pub fn from_bytes(bytes: &[u8]) -> ExecResult<Self> {
+        let mut cursor = std::io::Cursor::new(bytes);
+        let len: u32 = cursor.next(())?;
+        let mut entries = BTreeMap::new();
+        for _ in 0..len {
+            let name: Name = cursor.next(())?;
+            let index: FuncIndex = cursor.next(())?;
+            ensure!(!entries.contains_key(&name), "Entrypoint {} is listed more than once.", name);
+            entries.insert(name, index);
+        }
+        ensure!(
+            cursor.position() as usize == bytes.len(),
+            "Trailing data in the entrypoint table."
+        );
+        Ok(Self {
+            entries,
+        })
+    }
+
+    /// Compare the table of an older version of a module with the table of a
+    /// newer version. Function indices are only stable as long as the other
+    /// functions of the module are unchanged, so the detected renames are a
+    /// heuristic intended for warnings, e.g., when checking the safety of an
+    /// upgrade.
+    pub fn diff(&self, newer: &EntrypointTable) -> EntrypointTableDiff {
+        let mut removed: Vec<Name> =
+            self.entries.keys().filter(|name| !newer.entries.contains_key(name)).cloned().collect();
+        let mut added: Vec<Name> =
+            newer.entries.keys().filter(|name| !self.entries.contains_key(name)).cloned().collect();
+        let mut renamed = Vec::new();
+        removed.retain(|old_name| {
+            let index = self.entries[old_name];
+            if let Some(pos) = added.iter().position(|new_name| newer.entries[new_name] == index) {
+                renamed.push((old_name.clone(), added.remove(pos)));
+                false
+            } else {
+                true
+            }
+        });
+        EntrypointTableDiff {
+            removed,
+            added,
+            renamed,
+        }
     }
 }
 
+/// Get the entrypoint table embedded in the module, if there is one. This
+/// fails if the table is malformed, if there is more than one table, or if the
+/// table does not list exactly the exported functions of the module.
+pub fn get_entrypoint_table(bytes: &[u8]) -> ExecResult<Option<EntrypointTable>> {
+    if let Some(contents) = get_custom_section(bytes, ENTRYPOINT_TABLE_SECTION)? {
+        let table = EntrypointTable::from_bytes(contents)?;
+        let skeleton = parse_skeleton(bytes)?;
+        let exports: ExportSection = parse_sec_with_default((), &skeleton.export)?;
+        let expected = EntrypointTable::from_exports(&exports);
+        let diff = table.diff(&expected);
+        ensure!(
+            diff == EntrypointTableDiff::default(),
+            "The entrypoint table does not match the exports of the module: {:?}.",
+            diff
+        );
+        ensure!(table == expected, "The entrypoint table lists incorrect function indices.");
+        Ok(Some(table))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Embed the entrypoint table of the module, generated from its exports, in
+/// the module. Any existing entrypoint tables are replaced.
+pub fn embed_entrypoint_table(bytes: &[u8]) -> ExecResult<Vec<u8>> {
+    let skeleton = parse_skeleton(bytes)?;
+    let exports: ExportSection = parse_sec_with_default((), &skeleton.export)?;
+    let contents = EntrypointTable::from_exports(&exports).to_bytes();
+    embed_custom_section(bytes, ENTRYPOINT_TABLE_SECTION, &contents)
+}
+
+#[cfg(feature = "tooling")]
+/// Name of the custom section that contains the build information of a module.
+/// See [BuildInfo].
+pub const BUILD_INFO_SECTION: &str = "concordium-build-info";
+
+#[cfg(feature = "tooling")]
+/// Information about how a module was built, which is embedded in the custom
+/// section [BUILD_INFO_SECTION] by [embed_build_info]. It is serialized as
+/// JSON. Rebuilding the module from the same sources with the same toolchain
+/// and flags should reproduce it exactly, which [verify_build] checks.
+#[derive(SerdeSerialize, SerdeDeserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildInfo {
+    /// Version of the Rust compiler, as reported by `rustc --version`.
+    pub compiler_version: String,
+    /// Version of `concordium-sc-base` the contract is built with.
+    pub sc_base_version:  String,
+    /// Flags that affect the output of the build, e.g., the optimization
+    /// level and enabled features.
+    pub build_flags:      Vec<String>,
+    /// Hash of the sources, see [hash_sources].
+    pub source_hash:      String,
+}
+
+#[cfg(feature = "tooling")]
+/// Compute the hex-encoded SHA-256 hash of the source files of a contract,
+/// given as pairs of their paths, relative to the root of the crate, and
+/// contents. The hash does not depend on the order of the files.
+pub fn hash_sources<'a>(sources: impl IntoIterator<Item = (&'a str, &'a [u8])>) -> String {
+    let sources: BTreeMap<&str, &[u8]> = sources.into_iter().collect();
+    use sha2::Digest;
+    let mut hasher = sha2::Sha256::new();
+    for (path, contents) in sources {
+        // Lengths are included so that the boundaries between the files are
+        // unambiguous.
+        hasher.update((path.len() as u64).to_be_bytes());
+        hasher.update(path.as_bytes());
+        hasher.update((contents.len() as u64).to_be_bytes());
+        hasher.update(contents);
+    }
+    hex::encode(hasher.finalize())
+}
+
+#[cfg(feature = "tooling")]
+/// Get the build information embedded in the module, if there is any. This
+/// fails if the information is malformed, or if there is more than one
+/// [BUILD_INFO_SECTION].
+pub fn get_build_info(bytes: &[u8]) -> ExecResult<Option<BuildInfo>> {
+    get_custom_section(bytes, BUILD_INFO_SECTION)?
+        .map(|contents| serde_json::from_slice(contents).context("Malformed build information."))
+        .transpose()
+}
+
+#[cfg(feature = "tooling")]
+/// Embed the build information in the module, in the custom section
+/// [BUILD_INFO_SECTION]. Any existing build information is replaced.
+pub fn embed_build_info(bytes: &[u8], info: &BuildInfo) -> ExecResult<Vec<u8>> {
+    embed_custom_section(bytes, BUILD_INFO_SECTION, &serde_json::to_vec(info)?)
+}
+
+#[cfg(feature = "tooling")]
+/// Get the names of the reject reasons embedded in the module, if there are
+/// any. This fails if they are malformed, or if there is more than one
+/// [REJECT_REASONS_SECTION].
+pub fn get_reject_reasons(bytes: &[u8]) -> ExecResult<Option<RejectReasonRegistry>> {
+    get_custom_section(bytes, REJECT_REASONS_SECTION)?
+        .map(|contents| {
+            RejectReasonRegistry::from_bytes(contents).context("Malformed reject reasons.")
+        })
+        .transpose()
+}
+
+#[cfg(feature = "tooling")]
+/// Embed the names of reject reasons in the module, in the custom section
+/// [REJECT_REASONS_SECTION]. Any existing names are replaced.
+pub fn embed_reject_reasons(bytes: &[u8], registry: &RejectReasonRegistry) -> ExecResult<Vec<u8>> {
+    embed_custom_section(bytes, REJECT_REASONS_SECTION, &registry.to_bytes())
+}
+
+/// Get the schemas of the typed events embedded in the module, if there are
+/// any. This fails if they are malformed, or if there is more than one
+/// [EVENT_SCHEMAS_SECTION].
+pub fn get_event_schemas(bytes: &[u8]) -> ExecResult<Option<EventSchemas>> {
+    get_custom_section(bytes, EVENT_SCHEMAS_SECTION)?
+        .map(|contents| EventSchemas::from_bytes(contents).context("Malformed event schemas."))
+        .transpose()
+}
+
+/// Embed the schemas of the typed events in the module, in the custom section
+/// [EVENT_SCHEMAS_SECTION]. Any existing schemas are replaced.
+pub fn embed_event_schemas(bytes: &[u8], schemas: &EventSchemas) -> ExecResult<Vec<u8>> {
+    embed_custom_section(bytes, EVENT_SCHEMAS_SECTION, &schemas.to_bytes())
+}
+
+#[cfg(feature = "tooling")]
+/// Reasons why a module is not reproduced by rebuilding it, see
+/// [verify_build].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum BuildMismatch {
+    #[error("The {0} module has no build information.")]
+    MissingBuildInfo(&'static str),
+    #[error("The build information differs: {0}.")]
+    BuildInfo(&'static str),
+    #[error("The modules differ in their {0}.")]
+    Section(String),
+}
+
+#[cfg(feature = "tooling")]
+/// Check that a module rebuilt from source reproduces a deployed module. Both
+/// modules must contain the same build information, and be identical. If they
+/// are not, this fails with a [BuildMismatch] that describes the first
+/// difference. The module bytes do not include the version prefix.
+pub fn verify_build(deployed: &[u8], rebuilt: &[u8]) -> ExecResult<()> {
+    let deployed_info =
+        get_build_info(deployed)?.ok_or(BuildMismatch::MissingBuildInfo("deployed"))?;
+    let rebuilt_info =
+        get_build_info(rebuilt)?.ok_or(BuildMismatch::MissingBuildInfo("rebuilt"))?;
+    let fields = [
+        ("compiler version", deployed_info.compiler_version == rebuilt_info.compiler_version),
+        ("sc-base version", deployed_info.sc_base_version == rebuilt_info.sc_base_version),
+        ("build flags", deployed_info.build_flags == rebuilt_info.build_flags),
+        ("source hash", deployed_info.source_hash == rebuilt_info.source_hash),
+    ];
+    for &(field, equal) in fields.iter() {
+        ensure!(equal, BuildMismatch::BuildInfo(field));
+    }
+    if deployed == rebuilt {
+        return Ok(());
+    }
+    let deployed = parse_skeleton(deployed)?;
+    let rebuilt = parse_skeleton(rebuilt)?;
+    let sections = [
+        ("type section", &deployed.ty, &rebuilt.ty),
+        ("import section", &deployed.import, &rebuilt.import),
+        ("function section", &deployed.func, &rebuilt.func),
+        ("table section", &deployed.table, &rebuilt.table),
+        ("memory section", &deployed.memory, &rebuilt.memory),
+        ("global section", &deployed.global, &rebuilt.global),
+        ("export section", &deployed.export, &rebuilt.export),
+        ("start section", &deployed.start, &rebuilt.start),
+        ("element section", &deployed.element, &rebuilt.element),
+        ("code section", &deployed.code, &rebuilt.code),
+        ("data section", &deployed.data, &rebuilt.data),
+    ];
+    for &(name, deployed, rebuilt) in sections.iter() {
+        let equal = deployed.as_ref().map(|s| s.bytes) == rebuilt.as_ref().map(|s| s.bytes);
+        ensure!(equal, BuildMismatch::Section(name.to_owned()));
+    }
+    let deployed_custom = custom_sections(&deployed)?;
+    let rebuilt_custom = custom_sections(&rebuilt)?;
+    for (name, contents) in deployed_custom.iter() {
+        ensure!(
+            rebuilt_custom.iter().any(|(n, c)| n == name && c == contents),
+            BuildMismatch::Section(format!("custom section {}", name))
+        );
+    }
+    for (name, _) in rebuilt_custom.iter() {
+        ensure!(
+            deployed_custom.iter().any(|(n, _)| n == name),
+            BuildMismatch::Section(format!("custom section {}", name))
+        );
+    }
+    // The sections are the same, but in a different order.
+    bail!(BuildMismatch::Section("order of custom sections".into()))
+}
+
+#[cfg(feature = "tooling")]
+/// Get the names and contents of the custom sections of the module, in order.
+fn custom_sections<'a>(
+    skeleton: &wasm_transform::parse::Skeleton<'a>,
+) -> ExecResult<Vec<(Name, &'a [u8])>> {
+    skeleton
+        .custom
+        .iter()
+        .map(|ucs| {
+            let cs = parse_custom(ucs)?;
+            Ok((cs.name, cs.contents))
+        })
+        .collect()
+}
+
+/// The format of a schema embedded in a module, which is determined by the
+/// custom section it is in and, for versioned schemas, the version tag of the
+/// schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SchemaVersion {
+    /// A schema for modules of version 0 without a version tag, in the custom
+    /// section "concordium-schema-v1".
+    UnversionedV0,
+    /// A schema for modules of version 1 without a version tag, in the custom
+    /// section "concordium-schema-v2".
+    UnversionedV1,
+    /// A schema for modules of version 0 in the custom section
+    /// [SCHEMA_SECTION].
+    VersionedV0,
+    /// A schema for modules of version 1 in the custom section
+    /// [SCHEMA_SECTION].
+    VersionedV1,
+}
+
+impl SchemaVersion {
+    /// The unversioned schema format for modules of the given version.
+    fn unversioned(version: WasmVersion) -> Self {
+        match version {
+            WasmVersion::V0 => SchemaVersion::UnversionedV0,
+            WasmVersion::V1 => SchemaVersion::UnversionedV1,
+        }
+    }
+
+    /// The version of the modules the schema describes.
+    pub fn wasm_version(self) -> WasmVersion {
+        match self {
+            SchemaVersion::UnversionedV0 | SchemaVersion::VersionedV0 => WasmVersion::V0,
+            SchemaVersion::UnversionedV1 | SchemaVersion::VersionedV1 => WasmVersion::V1,
+        }
+    }
+
+    /// The name of the custom section that contains schemas of this format.
+    pub fn section_name(self) -> &'static str {
+        match self {
+            SchemaVersion::UnversionedV0 => UNVERSIONED_SCHEMA_SECTIONS[0],
+            SchemaVersion::UnversionedV1 => UNVERSIONED_SCHEMA_SECTIONS[1],
+            SchemaVersion::VersionedV0 | SchemaVersion::VersionedV1 => SCHEMA_SECTION,
+        }
+    }
+}
+
+impl std::fmt::Display for SchemaVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchemaVersion::UnversionedV0 => write!(f, "unversioned schema for V0 modules"),
+            SchemaVersion::UnversionedV1 => write!(f, "unversioned schema for V1 modules"),
+            SchemaVersion::VersionedV0 => write!(f, "versioned schema for V0 modules"),
+            SchemaVersion::VersionedV1 => write!(f, "versioned schema for V1 modules"),
+        }
+    }
+}
+
+/// Parse the schema in the custom section, together with its format. Returns
+/// `Ok(None)` if the section is not a schema section.
+fn parse_schema_section(
+    cs: &CustomSection,
+) -> ExecResult<Option<(SchemaVersion, schema::VersionedModuleSchema)>> {
+    let parsed = match cs.name.as_ref() {
+        SCHEMA_SECTION => {
+            let module: schema::VersionedModuleSchema =
+                from_bytes(cs.contents).map_err(|_| anyhow!("Failed parsing schema"))?;
+            let version = match module {
+                schema::VersionedModuleSchema::V0(_) => SchemaVersion::VersionedV0,
+                schema::VersionedModuleSchema::V1(_) => SchemaVersion::VersionedV1,
+            };
+            (version, module)
+        }
+        name if name == UNVERSIONED_SCHEMA_SECTIONS[0] => {
+            let module = from_bytes(cs.contents).map_err(|_| anyhow!("Failed parsing schema"))?;
+            (SchemaVersion::UnversionedV0, schema::VersionedModuleSchema::V0(module))
+        }
+        name if name == UNVERSIONED_SCHEMA_SECTIONS[1] => {
+            let module = from_bytes(cs.contents).map_err(|_| anyhow!("Failed parsing schema"))?;
+            (SchemaVersion::UnversionedV1, schema::VersionedModuleSchema::V1(module))
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(parsed))
+}
+
+/// The contents of a custom section of a module, see [ModuleMetadata].
+#[derive(Debug, Clone)]
+pub enum MetadataContents {
+    /// A schema in one of the schema sections.
+    Schema(SchemaVersion, schema::VersionedModuleSchema),
+    #[cfg(feature = "tooling")]
+    /// The build information in [BUILD_INFO_SECTION].
+    BuildInfo(BuildInfo),
+    /// The entrypoint table in [ENTRYPOINT_TABLE_SECTION]. It is not checked
+    /// against the exports of the module, which [get_entrypoint_table] does.
+    EntrypointTable(EntrypointTable),
+    #[cfg(feature = "tooling")]
+    /// The names of reject reasons in [REJECT_REASONS_SECTION].
+    RejectReasons(RejectReasonRegistry),
+    /// The schemas of typed events in [EVENT_SCHEMAS_SECTION].
+    EventSchemas(EventSchemas),
+    /// The contents of a section that is not known to the tools.
+    Unknown(Vec<u8>),
+}
+
+/// A custom section of a module, with its contents parsed if it is known.
+#[derive(Debug, Clone)]
+pub struct MetadataSection {
+    pub name:     Name,
+    pub contents: MetadataContents,
+}
+
+/// All the custom sections of a module, in the order in which they appear in
+/// the module, see [get_module_metadata].
+#[derive(Debug, Clone, Default)]
+pub struct ModuleMetadata {
+    pub sections: Vec<MetadataSection>,
+}
+
+impl ModuleMetadata {
+    /// The formats of the schemas embedded in the module, in the order of
+    /// their sections. A module may carry several, e.g., both a versioned and
+    /// an unversioned schema.
+    pub fn schema_versions(&self) -> Vec<SchemaVersion> {
+        self.sections
+            .iter()
+            .filter_map(|section| match section.contents {
+                MetadataContents::Schema(version, _) => Some(version),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Get the schema that [get_embedded_schema_v0] or
+    /// [get_embedded_schema_v1] would return for a module of the given
+    /// version, i.e., the first schema for that version, preferring
+    /// versioned schemas over unversioned ones.
+    pub fn schema(&self, version: WasmVersion) -> Option<&schema::VersionedModuleSchema> {
+        let find = |wanted: &dyn Fn(SchemaVersion) -> bool| {
+            self.sections.iter().find_map(|section| match &section.contents {
+                MetadataContents::Schema(v, module) if wanted(*v) => Some(module),
+                _ => None,
+            })
+        };
+        find(&|v| v.section_name() == SCHEMA_SECTION && v.wasm_version() == version)
+            .or_else(|| find(&|v| v == SchemaVersion::unversioned(version)))
+    }
+
+    #[cfg(feature = "tooling")]
+    /// Get the build information of the module, if there is any.
+    pub fn build_info(&self) -> Option<&BuildInfo> {
+        self.sections.iter().find_map(|section| match &section.contents {
+            MetadataContents::BuildInfo(info) => Some(info),
+            _ => None,
+        })
+    }
+
+    #[cfg(feature = "tooling")]
+    /// Get the names of the reject reasons of the module, if there are any.
+    pub fn reject_reasons(&self) -> Option<&RejectReasonRegistry> {
+        self.sections.iter().find_map(|section| match &section.contents {
+            MetadataContents::RejectReasons(registry) => Some(registry),
+            _ => None,
+        })
+    }
+
+    /// Get the schemas of the typed events of the module, if there are any.
+    pub fn event_schemas(&self) -> Option<&EventSchemas> {
+        self.sections.iter().find_map(|section| match &section.contents {
+            MetadataContents::EventSchemas(schemas) => Some(schemas),
+            _ => None,
+        })
+    }
+}
+
+/// Get all the custom sections of the module, and parse the contents of the
+/// known ones. This fails if a known section is malformed. The build
+/// information and the names of reject reasons are only parsed with the
+/// `tooling` feature, and are otherwise [Unknown](MetadataContents::Unknown).
+pub fn get_module_metadata(bytes: &[u8]) -> ExecResult<ModuleMetadata> {
+    let skeleton = parse_skeleton(bytes)?;
+    let mut sections = Vec::with_capacity(skeleton.custom.len());
+    for ucs in skeleton.custom.iter() {
+        let cs = parse_custom(ucs)?;
+        let contents = if let Some((version, module)) =
+            parse_schema_section(&cs).with_context(|| format!("Malformed section {}.", cs.name))?
+        {
+            MetadataContents::Schema(version, module)
+        } else {
+            match cs.name.as_ref() {
+                #[cfg(feature = "tooling")]
+                BUILD_INFO_SECTION => MetadataContents::BuildInfo(
+                    serde_json::from_slice(cs.contents).context("Malformed build information.")?,
+                ),
+                ENTRYPOINT_TABLE_SECTION => {
+                    MetadataContents::EntrypointTable(EntrypointTable::from_bytes(cs.contents)?)
+                }
+                #[cfg(feature = "tooling")]
+                REJECT_REASONS_SECTION => MetadataContents::RejectReasons(
+                    RejectReasonRegistry::from_bytes(cs.contents)
+                        .context("Malformed reject reasons.")?,
+                ),
+                EVENT_SCHEMAS_SECTION => MetadataContents::EventSchemas(
+                    EventSchemas::from_bytes(cs.contents).context("Malformed event schemas.")?,
+                ),
+                _ => MetadataContents::Unknown(cs.contents.to_vec()),
+            }
+        };
+        sections.push(MetadataSection {
+            name: cs.name,
+            contents,
+        });
+    }
+    Ok(ModuleMetadata {
+        sections,
+    })
+}
+
+/// A difference between two versions of a V1 module that is relevant when
+/// upgrading a contract instance from the older to the newer one, see
+/// [check_upgrade].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum UpgradeIssue {
+    #[error("Entrypoint {0} is removed.")]
+    RemovedEntrypoint(String),
+    #[error("Entrypoint {old} is removed, and is likely renamed to {new}.")]
+    RenamedEntrypoint {
+        old: String,
+        new: String,
+    },
+    #[error("Entrypoint {0} is added.")]
+    AddedEntrypoint(String),
+    #[error("The parameter schema of entrypoint {0} changed.")]
+    ChangedParameterSchema(String),
+    #[error("The return value schema of entrypoint {0} changed.")]
+    ChangedReturnValueSchema(String),
+    #[error("The schema of entrypoint {0} is removed.")]
+    RemovedSchema(String),
+    #[error("The {0} module has no embedded schema, so schemas are not compared.")]
+    MissingSchema(&'static str),
+}
+
+/// The result of [check_upgrade].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UpgradeReport {
+    /// Changes that break existing users of the contract.
+    pub breaking: Vec<UpgradeIssue>,
+    /// Changes that are likely safe, but should be reviewed.
+    pub warnings: Vec<UpgradeIssue>,
+}
+
+impl UpgradeReport {
+    /// Whether the upgrade has no breaking changes.
+    pub fn is_compatible(&self) -> bool { self.breaking.is_empty() }
+}
+
+/// Check the compatibility of upgrading a contract instance from the older to
+/// the newer V1 module. Removed entrypoints and changed parameter or return
+/// value schemas are breaking changes, while added entrypoints and removed
+/// schemas are warnings. V1 modules have no state schemas, so the state of the
+/// instance is not checked. The module bytes do not include the version
+/// prefix.
+pub fn check_upgrade(old_bytes: &[u8], new_bytes: &[u8]) -> ExecResult<UpgradeReport> {
+    // Only the init and receive functions of the modules are compared.
+    let entrypoints = |bytes: &[u8]| -> ExecResult<EntrypointTable> {
+        let exports = parse_sec_with_default((), &parse_skeleton(bytes)?.export)?;
+        let entries = EntrypointTable::from_exports(&exports)
+            .entries
+            .into_iter()
+            .filter(|(name, _)| split_entrypoint(name.as_ref()).is_some())
+            .collect();
+        Ok(EntrypointTable {
+            entries,
+        })
+    };
+    let old_entrypoints = entrypoints(old_bytes)?;
+    let diff = old_entrypoints.diff(&entrypoints(new_bytes)?);
+    let mut report = UpgradeReport::default();
+    for name in diff.removed {
+        report.breaking.push(UpgradeIssue::RemovedEntrypoint(name.as_ref().to_owned()));
+    }
+    for (old, new) in diff.renamed {
+        report.breaking.push(UpgradeIssue::RenamedEntrypoint {
+            old: old.as_ref().to_owned(),
+            new: new.as_ref().to_owned(),
+        });
+    }
+    for name in diff.added {
+        report.warnings.push(UpgradeIssue::AddedEntrypoint(name.as_ref().to_owned()));
+    }
+
+    let (old_schema, new_schema) =
+        match (get_embedded_schema_v1(old_bytes), get_embedded_schema_v1(new_bytes)) {
+            (Ok(old_schema), Ok(new_schema)) => (old_schema, new_schema),
+            (old_schema, _) => {
+                let which = if old_schema.is_err() {
+                    "old"
+                } else {
+                    "new"
+                };
+                report.warnings.push(UpgradeIssue::MissingSchema(which));
+                return Ok(report);
+            }
+        };
+    for name in old_entrypoints.entries.keys() {
+        let name = name.as_ref();
+        let old_function = match function_schema_v1(&old_schema, name) {
+            Some(function) => function,
+            None => continue,
+        };
+        let new_function = match function_schema_v1(&new_schema, name) {
+            Some(function) => function,
+            None => {
+                report.warnings.push(UpgradeIssue::RemovedSchema(name.to_owned()));
+                continue;
+            }
+        };
+        let changed = |old: Option<&schema::Type>, new: Option<&schema::Type>| match (old, new) {
+            (Some(old), Some(new)) => to_bytes(old) != to_bytes(new),
+            // A schema that is added or removed does not change the encoding.
+            _ => false,
+        };
+        if changed(old_function.parameter(), new_function.parameter()) {
+            report.breaking.push(UpgradeIssue::ChangedParameterSchema(name.to_owned()));
+        }
+        if changed(old_function.return_value(), new_function.return_value()) {
+            report.breaking.push(UpgradeIssue::ChangedReturnValueSchema(name.to_owned()));
+        }
+    }
+    Ok(report)
+}
+
+/// Get the schema of the V1 entrypoint with the given name.
+fn function_schema_v1<'a>(
+    schema: &'a schema::VersionedModuleSchema,
+    entrypoint: &str,
+) -> Option<&'a schema::FunctionV1> {
+    let module = match schema {
+        schema::VersionedModuleSchema::V1(module) => module,
+        _ => return None,
+    };
+    let (contract, function) = split_entrypoint(entrypoint)?;
+    let contract = module.contracts.get(contract)?;
+    match function {
+        None => contract.init.as_ref(),
+        Some(function) => contract.receive.get(function),
+    }
+}
+
+/// A problem with an exported function of a module that prevents it from being
+/// called as an init or receive function on chain, see [check_exports].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ExportIssue {
+    #[error(
+        "The name of export {0} contains characters that are not ASCII alphanumeric or \
+         punctuation."
+    )]
+    InvalidCharacters(String),
+    #[error(
+        "Export {0} starts with `init_`, but the name of an init function cannot contain a `.`."
+    )]
+    InitNameWithDot(String),
+    #[error("Export {0} is an init or receive function, but its type is not `(i64) -> i32`.")]
+    WrongSignature(String),
+    #[error("Export {0} is neither an init function nor a receive function.")]
+    NotAnEntrypoint(String),
+    #[error("Receive function {name} belongs to contract {contract}, which has no init function.")]
+    MissingInit {
+        name:     String,
+        contract: String,
+    },
+}
+
+/// The result of [check_exports].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportReport {
+    /// Problems that make the chain reject the module.
+    pub errors:   Vec<ExportIssue>,
+    /// Exported functions that are accepted, but can never be called.
+    pub warnings: Vec<ExportIssue>,
+}
+
+impl ExportReport {
+    /// Whether the module has no problems that make the chain reject it.
+    pub fn is_valid(&self) -> bool { self.errors.is_empty() }
+}
+
+/// Check the exported functions of a module of the given version, and report
+/// those that cannot be called on chain together with the reason. This is
+/// intended to catch typos in the names of init and receive functions before a
+/// module is deployed, and checks the same rules as validation of modules on
+/// chain, but reports all problems rather than only the first one.
+///
+/// Both versions reject names that contain unsupported characters, names of
+/// init functions with a `.`, and init and receive functions of the wrong
+/// type. V0 modules additionally reject any other exported function, while V1
+/// modules accept them, but they cannot be called, so they are reported as
+/// warnings. Receive functions of contracts without an init
+/// function are accepted by both versions, and reported as warnings.
+///
+/// The module bytes do not include the version prefix. Problems of the module
+/// other than its exports, e.g., unsupported imports, are not reported, and
+/// modules that cannot be parsed, e.g., because an export name is too long,
+/// are an error.
+pub fn check_exports(module_bytes: &[u8], version: WasmVersion) -> ExecResult<ExportReport> {
+    let skeleton = parse_skeleton(module_bytes)?;
+    let module = validate::validate_module(
+        &validate::ValidationConfig::ALL,
+        &TestHost::default(),
+        &skeleton,
+    )?;
+    // The types of all functions, including the imported ones, in the order of
+    // their indices.
+    let function_types: Vec<_> = module
+        .import
+        .imports
+        .iter()
+        .map(|i| match i.description {
+            ImportDescription::Func {
+                type_idx,
+            } => type_idx,
+        })
+        .chain(module.func.types.iter().copied())
+        .collect();
+    let inits = get_inits(&module);
+    let mut report = ExportReport::default();
+    for export in module.export.exports.iter() {
+        let index = match export.description {
+            ExportDescription::Func {
+                index,
+            } => index,
+            _ => continue,
+        };
+        let name = export.name.as_ref();
+        if !name.chars().all(|c| c.is_ascii_alphanumeric() || c.is_ascii_punctuation()) {
+            report.errors.push(ExportIssue::InvalidCharacters(name.to_owned()));
+            continue;
+        }
+        let is_init = name.starts_with("init_");
+        if is_init && name.contains('.') {
+            report.errors.push(ExportIssue::InitNameWithDot(name.to_owned()));
+            continue;
+        }
+        if !is_init && !name.contains('.') {
+            match version {
+                WasmVersion::V0 => {
+                    report.errors.push(ExportIssue::NotAnEntrypoint(name.to_owned()))
+                }
+                WasmVersion::V1 => {
+                    report.warnings.push(ExportIssue::NotAnEntrypoint(name.to_owned()))
+                }
+            }
+            continue;
+        }
+        let correct_type = function_types
+            .get(index as usize)
+            .and_then(|ty_idx| module.ty.get(*ty_idx))
+            .map_or(false, |ty| {
+                ty.parameters.as_slice() == [ValueType::I64] && ty.result == Some(ValueType::I32)
+            });
+        if !correct_type {
+            report.errors.push(ExportIssue::WrongSignature(name.to_owned()));
+            continue;
+        }
+        if let Some((contract, Some(_))) = split_entrypoint(name) {
+            if !inits.iter().any(|init| init.as_ref().strip_prefix("init_") == Some(contract)) {
+                report.warnings.push(ExportIssue::MissingInit {
+                    name:     name.to_owned(),
+                    contract: contract.to_owned(),
+                });
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Create a span for compiling the given Wasm module. The span records the
+/// size of the module, and the SHA256 hash of its source, the latter only if
+/// the span is enabled since it is expensive to compute.
+#[cfg(feature = "instrumentation")]
+pub(crate) fn compilation_span(wasm_bytes: &[u8]) -> tracing::Span {
+    use sha2::Digest;
+    let span = tracing::info_span!(
+        "compile_module",
+        module_size = wasm_bytes.len(),
+        module_hash = tracing::field::Empty
+    );
+    if !span.is_disabled() {
+        span.record("module_hash", &hex::encode(sha2::Sha256::digest(wasm_bytes)).as_str());
+    }
+    span
+}
+
 #[cfg(test)]
 /// Tests for schema parsing functions.
 mod tests {
@@ -449,4 +2092,449 @@ mod tests {
             panic!("Failed to parse versioned v1 module schema: {}", e);
         }
     }
+
+    #[test]
+    fn test_schema_serialization() {
+        use super::*;
+        let cases = [
+            (
+                WasmVersion::V0,
+                "cis1-wccd-embedded-schema-v0-versioned.wasm.v0",
+                "cis1-wccd-schema-v0-versioned.bin",
+            ),
+            (
+                WasmVersion::V1,
+                "cis2-wccd-embedded-schema-v1-versioned.wasm.v1",
+                "cis2-wccd-schema-v1-versioned.bin",
+            ),
+            (
+                WasmVersion::V1,
+                "cis2-wccd-embedded-schema-v1-unversioned.wasm.v1",
+                "cis2-wccd-schema-v1-versioned.bin",
+            ),
+        ];
+        for (version, module_file, golden_file) in cases.iter() {
+            let module = std::fs::read(format!("../testdata/schemas/{}", module_file))
+                .expect("Could not read file.");
+            let module = &module[8..];
+            let golden = std::fs::read(format!("../testdata/schemas/{}", golden_file))
+                .expect("Could not read file.");
+            let get_schema = match version {
+                WasmVersion::V0 => get_embedded_schema_v0,
+                WasmVersion::V1 => get_embedded_schema_v1,
+            };
+            let schema = get_schema(module).expect("The module has a schema.");
+            let bytes = schema_to_bytes(&schema);
+            assert_eq!(bytes, golden, "The schema of {} is serialized canonically.", module_file);
+
+            // Inserting the functions in a different order does not change the output.
+            let reversed = match schema.clone() {
+                schema::VersionedModuleSchema::V0(mut module) => {
+                    for contract in module.contracts.values_mut() {
+                        contract.receive =
+                            std::mem::take(&mut contract.receive).into_iter().rev().collect();
+                    }
+                    schema::VersionedModuleSchema::V0(module)
+                }
+                schema::VersionedModuleSchema::V1(mut module) => {
+                    for contract in module.contracts.values_mut() {
+                        contract.receive =
+                            std::mem::take(&mut contract.receive).into_iter().rev().collect();
+                    }
+                    schema::VersionedModuleSchema::V1(module)
+                }
+            };
+            assert_eq!(schema_to_bytes(&reversed), bytes);
+
+            let embedded = embed_schema(module, &schema).expect("Embedding should succeed.");
+            if module_file.contains("unversioned") {
+                // The unversioned section is replaced by a versioned one.
+                assert_eq!(schema_to_bytes(&get_schema(&embedded).unwrap()), bytes);
+                let re_embedded = embed_schema(&embedded, &get_schema(&embedded).unwrap())
+                    .expect("Embedding should succeed.");
+                assert_eq!(re_embedded, embedded, "Re-embedding is stable.");
+            } else {
+                assert_eq!(embedded, module, "Re-embedding {} reproduces it.", module_file);
+            }
+        }
+    }
+
+    #[test]
+    fn test_generate_return_value_schemas() {
+        use super::*;
+        let module = std::fs::read("../testdata/contracts/schema-return-value-test.wasm")
+            .expect("Could not read file.");
+        let schema =
+            generate_contract_schema_v1(&module).expect("Schema generation should succeed.");
+        let contract = match &schema {
+            schema::VersionedModuleSchema::V1(module) => &module.contracts["test"],
+            _ => panic!("Expected a V1 schema."),
+        };
+        let init = contract.init.as_ref().expect("The init schema should be generated.");
+        assert!(matches!(init.parameter(), Some(schema::Type::U8)));
+        assert!(matches!(init.return_value(), Some(schema::Type::U16)));
+        let get = &contract.receive["get"];
+        assert!(get.parameter().is_none(), "The receive function has no parameter schema.");
+        assert!(matches!(get.return_value(), Some(schema::Type::U32)));
+    }
+
+    #[test]
+    fn test_check_upgrade() {
+        use super::*;
+        let data =
+            std::fs::read("../testdata/schemas/cis2-wccd-embedded-schema-v1-versioned.wasm.v1")
+                .expect("Could not read file.");
+        let module = &data[8..];
+        let report = check_upgrade(module, module).expect("Checking should succeed.");
+        assert_eq!(report, UpgradeReport::default(), "A module is compatible with itself.");
+
+        // Give one receive function the schema of another one with a different
+        // parameter, and remove the schema of a third one.
+        let mut schema = get_embedded_schema_v1(module).unwrap();
+        let (changed, removed) = match &mut schema {
+            schema::VersionedModuleSchema::V1(module_schema) => {
+                let (contract_name, contract) = module_schema.contracts.iter_mut().next().unwrap();
+                let parameters: Vec<_> = contract
+                    .receive
+                    .iter()
+                    .filter_map(|(name, function)| {
+                        Some((name.clone(), to_bytes(function.parameter()?)))
+                    })
+                    .collect();
+                let (changed, other) = parameters
+                    .iter()
+                    .find_map(|(a, pa)| {
+                        parameters
+                            .iter()
+                            .find(|(_, pb)| pa != pb)
+                            .map(|(b, _)| (a.clone(), b.clone()))
+                    })
+                    .expect("The contract has functions with different parameters.");
+                let replacement = contract.receive[&other].clone();
+                contract.receive.insert(changed.clone(), replacement);
+                let removed = contract
+                    .receive
+                    .keys()
+                    .find(|name| **name != changed && **name != other)
+                    .cloned()
+                    .expect("The contract has at least three functions.");
+                contract.receive.remove(&removed);
+                (format!("{}.{}", contract_name, changed), format!("{}.{}", contract_name, removed))
+            }
+            _ => panic!("Expected a V1 schema."),
+        };
+        let modified = embed_schema(module, &schema).expect("Embedding should succeed.");
+        let report = check_upgrade(module, &modified).expect("Checking should succeed.");
+        assert!(!report.is_compatible(), "Changing a parameter schema is breaking.");
+        // The return value schema may have changed as well.
+        assert!(report.breaking.contains(&UpgradeIssue::ChangedParameterSchema(changed)));
+        assert_eq!(report.warnings, [UpgradeIssue::RemovedSchema(removed)]);
+
+        // Entrypoints that are not in the newer module are breaking changes.
+        let old = std::fs::read("../testdata/contracts/schema-return-value-test.wasm")
+            .expect("Could not read file.");
+        let report = check_upgrade(&old, module).expect("Checking should succeed.");
+        assert_eq!(report.breaking, [
+            UpgradeIssue::RemovedEntrypoint("init_test".into()),
+            UpgradeIssue::RemovedEntrypoint("test.get".into())
+        ]);
+        assert!(report.warnings.contains(&UpgradeIssue::MissingSchema("old")));
+    }
+
+    #[test]
+    fn test_check_exports() {
+        use super::*;
+        let data =
+            std::fs::read("../testdata/schemas/cis2-wccd-embedded-schema-v1-versioned.wasm.v1")
+                .expect("Could not read file.");
+        let report = check_exports(&data[8..], WasmVersion::V1).expect("Checking should succeed.");
+        assert_eq!(report, ExportReport::default(), "A deployable module has no issues.");
+
+        fn section(out: &mut Vec<u8>, id: u8, contents: &[u8]) {
+            out.push(id);
+            let mut len = contents.len();
+            while len >= 0x80 {
+                out.push((len & 0x7f) as u8 | 0x80);
+                len >>= 7;
+            }
+            out.push(len as u8);
+            out.extend_from_slice(contents);
+        }
+        // Functions of type `(i64) -> i32` and `() -> ()` exported under the
+        // given names.
+        let exports = [
+            ("init_counter", true),
+            ("counter.inc", true),
+            ("init_counter.bad", true),
+            ("counter.wrong", false),
+            ("other.receive", true),
+            ("helper", false),
+        ];
+        let mut bytes = b"\0asm\x01\0\0\0".to_vec();
+        section(&mut bytes, 1, &[2, 0x60, 1, 0x7E, 1, 0x7F, 0x60, 0, 0]);
+        let mut func = vec![exports.len() as u8];
+        let mut export = vec![exports.len() as u8];
+        let mut code = vec![exports.len() as u8];
+        for (i, (name, entrypoint_type)) in exports.iter().enumerate() {
+            export.push(name.len() as u8);
+            export.extend_from_slice(name.as_bytes());
+            export.extend_from_slice(&[0, i as u8]);
+            if *entrypoint_type {
+                func.push(0);
+                code.extend_from_slice(&[4, 0, 0x41, 0, 0x0B]);
+            } else {
+                func.push(1);
+                code.extend_from_slice(&[2, 0, 0x0B]);
+            }
+        }
+        section(&mut bytes, 3, &func);
+        section(&mut bytes, 7, &export);
+        section(&mut bytes, 10, &code);
+
+        let errors = vec![
+            ExportIssue::InitNameWithDot("init_counter.bad".into()),
+            ExportIssue::WrongSignature("counter.wrong".into()),
+        ];
+        let missing_init = ExportIssue::MissingInit {
+            name:     "other.receive".into(),
+            contract: "other".into(),
+        };
+        let not_entrypoint = ExportIssue::NotAnEntrypoint("helper".into());
+        let report = check_exports(&bytes, WasmVersion::V1).expect("Checking should succeed.");
+        assert!(!report.is_valid());
+        assert_eq!(report.errors, errors);
+        assert_eq!(report.warnings, [missing_init.clone(), not_entrypoint.clone()]);
+
+        // Exports that are not init or receive functions are rejected in V0.
+        let report = check_exports(&bytes, WasmVersion::V0).expect("Checking should succeed.");
+        let mut v0_errors = errors;
+        v0_errors.push(not_entrypoint);
+        assert_eq!(report.errors, v0_errors);
+        assert_eq!(report.warnings, [missing_init]);
+    }
+
+    #[test]
+    fn test_module_metadata() {
+        use super::*;
+        let data =
+            std::fs::read("../testdata/schemas/cis2-wccd-embedded-schema-v1-versioned.wasm.v1")
+                .expect("Could not read file.");
+        let module = &data[8..];
+        let metadata = get_module_metadata(module).expect("Reading should succeed.");
+        assert_eq!(metadata.schema_versions(), [SchemaVersion::VersionedV1]);
+        assert!(metadata.schema(WasmVersion::V0).is_none());
+        let schema = get_embedded_schema_v1(module).expect("The module has a schema.");
+        assert_eq!(
+            metadata.schema(WasmVersion::V1).map(schema_to_bytes),
+            Some(schema_to_bytes(&schema))
+        );
+        assert!(
+            get_embedded_schema_v0(module).is_err(),
+            "The schema is not for modules of version 0."
+        );
+
+        #[cfg(feature = "tooling")]
+        {
+            assert!(metadata.build_info().is_none());
+            let info = BuildInfo {
+                compiler_version: "rustc 1.60.0".into(),
+                sc_base_version:  "3.0.0".into(),
+                build_flags:      Vec::new(),
+                source_hash:      hash_sources(vec![("src/lib.rs", &b"contract"[..])]),
+            };
+            let with_info = embed_build_info(module, &info).expect("Embedding should succeed.");
+            let metadata = get_module_metadata(&with_info).expect("Reading should succeed.");
+            assert_eq!(metadata.build_info(), Some(&info));
+            let names: Vec<&str> = metadata.sections.iter().map(|s| s.name.as_ref()).collect();
+            assert_eq!(names.last(), Some(&BUILD_INFO_SECTION));
+        }
+
+        let data =
+            std::fs::read("../testdata/schemas/cis2-wccd-embedded-schema-v1-unversioned.wasm.v1")
+                .expect("Could not read file.");
+        let metadata = get_module_metadata(&data[8..]).expect("Reading should succeed.");
+        assert_eq!(metadata.schema_versions(), [SchemaVersion::UnversionedV1]);
+        assert!(metadata.schema(WasmVersion::V1).is_some());
+    }
+
+    #[test]
+    fn test_custom_sections() {
+        use super::*;
+        let module = b"\0asm\x01\0\0\0";
+        assert_eq!(get_custom_section(module, "test").expect("Reading should succeed."), None);
+        let embedded = embed_custom_section(module, "test", b"first").unwrap();
+        let embedded = embed_custom_section(&embedded, "other", b"other").unwrap();
+        let replaced = embed_custom_section(&embedded, "test", b"second").unwrap();
+        assert_eq!(get_custom_section(&replaced, "test").unwrap(), Some(&b"second"[..]));
+        assert_eq!(get_custom_section(&replaced, "other").unwrap(), Some(&b"other"[..]));
+        let mut duplicate = embedded.clone();
+        write_custom_section(&mut duplicate, &CustomSection {
+            name:     "test".into(),
+            contents: b"again",
+        })
+        .unwrap();
+        assert!(get_custom_section(&duplicate, "test").is_err(), "Sections must be unique.");
+    }
+
+    #[cfg(feature = "tooling")]
+    #[test]
+    fn test_build_info() {
+        use super::*;
+        let data =
+            std::fs::read("../testdata/schemas/cis2-wccd-embedded-schema-v1-versioned.wasm.v1")
+                .expect("Could not read file.");
+        let module = &data[8..];
+        assert_eq!(get_build_info(module).expect("Reading should succeed."), None);
+        let info = BuildInfo {
+            compiler_version: "rustc 1.60.0".into(),
+            sc_base_version:  "3.0.0".into(),
+            build_flags:      vec!["--release".into()],
+            source_hash:      hash_sources(vec![
+                ("src/lib.rs", &b"contract"[..]),
+                ("Cargo.toml", &b"manifest"[..]),
+            ]),
+        };
+        assert_eq!(
+            info.source_hash,
+            hash_sources(vec![("Cargo.toml", &b"manifest"[..]), ("src/lib.rs", &b"contract"[..])]),
+            "The source hash does not depend on the order of the files."
+        );
+        let deployed = embed_build_info(module, &info).expect("Embedding should succeed.");
+        assert_eq!(get_build_info(&deployed).expect("Reading should succeed."), Some(info.clone()));
+        let reembedded = embed_build_info(&deployed, &info).expect("Embedding should succeed.");
+        assert_eq!(reembedded, deployed, "Existing build information is replaced.");
+        verify_build(&deployed, &reembedded).expect("A module reproduces itself.");
+
+        let mismatch = |rebuilt: &[u8]| {
+            verify_build(&deployed, rebuilt)
+                .expect_err("Verification should fail.")
+                .downcast::<BuildMismatch>()
+                .expect("Verification should fail with a mismatch.")
+        };
+        assert_eq!(mismatch(module), BuildMismatch::MissingBuildInfo("rebuilt"));
+        let other_flags = BuildInfo {
+            build_flags: Vec::new(),
+            ..info.clone()
+        };
+        let rebuilt = embed_build_info(module, &other_flags).expect("Embedding should succeed.");
+        assert_eq!(mismatch(&rebuilt), BuildMismatch::BuildInfo("build flags"));
+        let schema = get_embedded_schema_v1(module).expect("The module has a schema.");
+        let without_schema = {
+            let mut skeleton = parse_skeleton(&deployed).unwrap();
+            skeleton
+                .custom
+                .retain(|ucs| parse_custom(ucs).unwrap().name.as_ref() != SCHEMA_SECTION);
+            let mut out = Vec::new();
+            skeleton.output(&mut out).unwrap();
+            out
+        };
+        assert_eq!(
+            mismatch(&without_schema),
+            BuildMismatch::Section(format!("custom section {}", SCHEMA_SECTION))
+        );
+        // Embedding the schema again moves it after the build information.
+        let reordered = embed_schema(&without_schema, &schema).expect("Embedding should succeed.");
+        assert_eq!(mismatch(&reordered), BuildMismatch::Section("order of custom sections".into()));
+    }
+
+    #[test]
+    fn test_entrypoint_table() {
+        use super::*;
+        let module = std::fs::read("../testdata/contracts/global-offset-test.wasm")
+            .expect("Could not read file.");
+        assert_eq!(get_entrypoint_table(&module).unwrap(), None, "The module has no table.");
+        let with_table = embed_entrypoint_table(&module).expect("Embedding should succeed.");
+        let table = get_entrypoint_table(&with_table)
+            .expect("The generated table is valid.")
+            .expect("The module has a table.");
+        let names: Vec<&str> = table.entries.keys().map(|name| name.as_ref()).collect();
+        assert_eq!(names, ["init_test", "test.receive"]);
+        let re_embedded = embed_entrypoint_table(&with_table).expect("Embedding should succeed.");
+        assert_eq!(re_embedded, with_table, "An existing table is replaced.");
+
+        // A table that refers to the wrong function is rejected.
+        let mut wrong = table.clone();
+        *wrong.entries.get_mut(&Name::from("init_test")).unwrap() += 1;
+        let mut with_wrong_table = module.clone();
+        write_custom_section(&mut with_wrong_table, &CustomSection {
+            name:     ENTRYPOINT_TABLE_SECTION.into(),
+            contents: &wrong.to_bytes(),
+        })
+        .unwrap();
+        assert!(get_entrypoint_table(&with_wrong_table).is_err(), "Wrong indices are rejected.");
+
+        let mut renamed = table.clone();
+        let index = renamed.entries.remove(&Name::from("test.receive")).unwrap();
+        renamed.entries.insert(Name::from("test.receive_v2"), index);
+        renamed.entries.insert(Name::from("test.new"), index + 10);
+        assert_eq!(table.diff(&renamed), EntrypointTableDiff {
+            removed: Vec::new(),
+            added:   vec![Name::from("test.new")],
+            renamed: vec![(Name::from("test.receive"), Name::from("test.receive_v2"))],
+        });
+    }
+
+    #[test]
+    fn test_validate_parameter() {
+        use super::*;
+        use concordium_contracts_common::schema::{Fields, SizeLength, Type};
+        let ty = Type::Struct(Fields::Named(vec![
+            ("flag".into(), Type::Bool),
+            ("items".into(), Type::List(SizeLength::U8, Box::new(Type::U16))),
+            (
+                "choice".into(),
+                Type::Enum(vec![
+                    ("A".into(), Fields::None),
+                    ("B".into(), Fields::Unnamed(vec![Type::String(SizeLength::U8)])),
+                ]),
+            ),
+        ]));
+        let valid = [1, 2, 10, 0, 11, 0, 1, 2, b'h', b'i'];
+        assert_eq!(validate_parameter_with_schema(&ty, &valid), Ok(()));
+        assert_eq!(validate_parameter_with_schema(&ty, &[0, 0, 0]), Ok(()));
+        assert_eq!(
+            validate_parameter_with_schema(&ty, &[0, 0, 0, 7]),
+            Err(SchemaMismatch::TrailingBytes(1))
+        );
+        let mismatch_at = |param: &[u8]| match validate_parameter_with_schema(&ty, param) {
+            Err(SchemaMismatch::Mismatch {
+                path,
+                ..
+            }) => path,
+            other => panic!("Expected a mismatch, got {:?}", other),
+        };
+        assert_eq!(mismatch_at(&[2, 0, 0]), "parameter.flag");
+        assert_eq!(mismatch_at(&[0, 2, 10, 0, 11]), "parameter.items[1]");
+        assert_eq!(mismatch_at(&[0, 0, 2]), "parameter.choice");
+        assert_eq!(mismatch_at(&[0, 0, 1, 2, b'h']), "parameter.choice.B.0");
+        assert_eq!(mismatch_at(&[0, 0, 1, 1, 0xff]), "parameter.choice.B.0");
+
+        let module =
+            std::fs::read("../testdata/schemas/cis2-wccd-embedded-schema-v1-versioned.wasm.v1")
+                .expect("Could not read file.");
+        let module = &module[8..];
+        // The parameter of unwrap is the amount, the owner, the receiver, and
+        // additional data.
+        let mut unwrap = vec![5, 0];
+        unwrap.extend_from_slice(&[1u8; 32]);
+        unwrap.push(0);
+        unwrap.extend_from_slice(&[2u8; 32]);
+        unwrap.extend_from_slice(&[0, 0]);
+        assert_eq!(
+            validate_parameter(WasmVersion::V1, module, "CIS2-wCCD.unwrap", &unwrap),
+            Ok(())
+        );
+        assert!(matches!(
+            validate_parameter(WasmVersion::V1, module, "CIS2-wCCD.unwrap", &unwrap[..40]),
+            Err(SchemaMismatch::Mismatch { .. })
+        ));
+        assert_eq!(
+            validate_parameter(WasmVersion::V1, module, "CIS2-wCCD.missing", &unwrap),
+            Err(SchemaMismatch::NoParameterSchema("CIS2-wCCD.missing".into()))
+        );
+        assert!(matches!(
+            validate_parameter(WasmVersion::V1, &module[..8], "CIS2-wCCD.unwrap", &unwrap),
+            Err(SchemaMismatch::NoSchema(_))
+        ));
+    }
 }
@@ -0,0 +1,153 @@
+//! Rendering of the action trees produced by V0 receive functions, for
+//! debugging contracts, e.g., by tools that simulate them.
+//!
+//! As in [simulation](super::simulation), the root of the tree is the last of
+//! the actions. Actions are referred to by their index in the list, written as
+//! `#<index>`. The operands of [Action::And] and [Action::Or] may be shared by
+//! several combinators, in which case they are rendered once, and referred to
+//! by their index afterwards.
+use super::Action;
+use anyhow::bail;
+use std::fmt::{self, Write};
+
+/// The ways an action tree can be rendered, see [render_actions].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionsFormat {
+    /// An indented tree, see [DisplayActionTree].
+    Tree,
+    /// A graph in the Graphviz dot language, see [actions_to_dot].
+    Dot,
+}
+
+impl std::str::FromStr for ActionsFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tree" => Ok(Self::Tree),
+            "dot" => Ok(Self::Dot),
+            _ => bail!("Unknown format of actions '{}', expected 'tree' or 'dot'.", s),
+        }
+    }
+}
+
+/// Render the action tree in the given format.
+pub fn render_actions(format: ActionsFormat, actions: &[Action]) -> String {
+    match format {
+        ActionsFormat::Tree => DisplayActionTree(actions).to_string(),
+        ActionsFormat::Dot => actions_to_dot(actions),
+    }
+}
+
+/// Display an action tree with one action per line, and the operands of
+/// [Action::And] and [Action::Or] indented below them. For example
+/// ```text
+/// #3: or else
+///   #2: and then
+///     #0: transfer 1.000000 CCD to <address>
+///     #1: accept
+///   #1: (see above)
+/// ```
+/// Operands that do not refer to earlier actions, which is not possible in
+/// trees produced by receive functions, are displayed as invalid.
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayActionTree<'a>(pub &'a [Action]);
+
+impl<'a> DisplayActionTree<'a> {
+    fn fmt_action(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        idx: u32,
+        depth: usize,
+        displayed: &mut [bool],
+    ) -> fmt::Result {
+        write!(f, "{:indent$}#{}: ", "", idx, indent = 2 * depth)?;
+        if displayed[idx as usize] {
+            return writeln!(f, "(see above)");
+        }
+        displayed[idx as usize] = true;
+        let (name, l, r) = match self.0[idx as usize] {
+            Action::And {
+                l,
+                r,
+            } => ("and then", l, r),
+            Action::Or {
+                l,
+                r,
+            } => ("or else", l, r),
+            ref action => return writeln!(f, "{}", action),
+        };
+        writeln!(f, "{}", name)?;
+        for operand in [l, r] {
+            if operand < idx {
+                self.fmt_action(f, operand, depth + 1, displayed)?;
+            } else {
+                writeln!(f, "{:indent$}#{}: invalid operand", "", operand, indent = 2 * depth + 2)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> fmt::Display for DisplayActionTree<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.len() {
+            0 => writeln!(f, "no actions"),
+            n => self.fmt_action(f, n as u32 - 1, 0, &mut vec![false; n]),
+        }
+    }
+}
+
+/// Escape a label for use in a quoted string of the dot language.
+fn escape_label(label: &str) -> String { label.replace('\\', "\\\\").replace('"', "\\\"") }
+
+/// Render the action tree as a directed graph in the Graphviz dot language,
+/// with a node for each action reachable from the root, and an edge from each
+/// combinator to each of its operands. Shared operands are a single node. The
+/// output can be rendered with, e.g., `dot -Tsvg`.
+pub fn actions_to_dot(actions: &[Action]) -> String {
+    let mut out = String::from("digraph actions {\n  node [fontname=\"monospace\"];\n");
+    let mut reachable = vec![false; actions.len()];
+    if let Some(last) = reachable.last_mut() {
+        *last = true;
+    }
+    // Operands refer to earlier actions, so a single pass from the root
+    // downwards finds all the reachable actions, as in `prune_actions`.
+    // Writing to a string does not fail, so the results are ignored.
+    for idx in (0..actions.len()).rev() {
+        if !reachable[idx] {
+            continue;
+        }
+        let (name, l, r) = match actions[idx] {
+            Action::And {
+                l,
+                r,
+            } => ("and", l, r),
+            Action::Or {
+                l,
+                r,
+            } => ("or", l, r),
+            ref action => {
+                let label = escape_label(&action.to_string());
+                let _ = writeln!(out, "  a{} [shape=box, label=\"#{}: {}\"];", idx, idx, label);
+                continue;
+            }
+        };
+        let _ = writeln!(out, "  a{} [shape=ellipse, label=\"#{}: {}\"];", idx, idx, name);
+        for (operand, edge) in [(l, "left"), (r, "right")] {
+            if (operand as usize) < idx {
+                reachable[operand as usize] = true;
+                let _ = writeln!(out, "  a{} -> a{} [label=\"{}\"];", idx, operand, edge);
+            } else {
+                let _ = writeln!(
+                    out,
+                    "  invalid{} [shape=plaintext, label=\"#{}: invalid operand\"];",
+                    operand, operand
+                );
+                let _ = writeln!(out, "  a{} -> invalid{} [label=\"{}\"];", idx, operand, edge);
+            }
+        }
+    }
+    out.push_str("}\n");
+    out
+}
@@ -1,7 +1,9 @@
 use crate::{slice_from_c_bytes, v0::*};
 use libc::size_t;
 use std::sync::Arc;
-use wasm_transform::{artifact::CompiledFunction, output::Output, utils::parse_artifact};
+use wasm_transform::{
+    artifact::CompiledFunction, compression, output::Output, utils::parse_artifact,
+};
 
 /// All functions in this module operate on an Arc<ArtifactV0>. The reason for
 /// choosing an Arc as opposed to Box or Rc is that we need to sometimes share
@@ -148,6 +150,8 @@ unsafe extern "C" fn validate_and_process_v0(
                               * be written. */
 ) -> *mut u8 {
     let wasm_bytes = slice_from_c_bytes!(wasm_bytes_ptr, wasm_bytes_len as usize);
+    #[cfg(feature = "instrumentation")]
+    let _span = crate::utils::compilation_span(wasm_bytes).entered();
     match utils::instantiate_with_metering::<ProcessedImports, _>(
         &ConcordiumAllowedImports,
         wasm_bytes,
@@ -192,7 +196,8 @@ unsafe extern "C" fn artifact_v0_free(artifact_ptr: *const ArtifactV0) {
 #[no_mangle]
 /// Convert an artifact to a byte array and return a pointer to it, storing its
 /// length in `output_len`. To avoid leaking memory the return value should be
-/// freed with `rs_free_array_len`.
+/// freed with `rs_free_array_len`. If the `compression` feature is enabled the
+/// serialized artifact is compressed.
 ///
 /// # Safety
 /// This function is safe provided the `artifact_ptr` was obtained with
@@ -204,6 +209,8 @@ unsafe extern "C" fn artifact_v0_to_bytes(
     let artifact = Arc::from_raw(artifact_ptr);
     let mut bytes = Vec::new();
     artifact.output(&mut bytes).expect("Artifact serialization does not fail.");
+    #[cfg(feature = "compression")]
+    let mut bytes = compression::compress(&bytes).expect("Compressing to a vector does not fail.");
     bytes.shrink_to_fit();
     *output_len = bytes.len() as size_t;
     let ptr = bytes.as_mut_ptr();
@@ -213,7 +220,8 @@ unsafe extern "C" fn artifact_v0_to_bytes(
 }
 
 #[no_mangle]
-/// Deserialize an artifact from bytes and return a pointer to it.
+/// Deserialize an artifact from bytes, which are decompressed first if they
+/// are compressed, and return a pointer to it.
 /// If deserialization fails this returns [None](https://doc.rust-lang.org/std/option/enum.Option.html#variant.None)
 /// and otherwise it returns a valid pointer to the artifact. To avoid leaking
 /// memory the memory must be freed using [artifact_v0_free].
@@ -228,7 +236,11 @@ unsafe extern "C" fn artifact_v0_from_bytes(
     input_len: size_t,
 ) -> *const ArtifactV0 {
     let bytes = slice_from_c_bytes!(bytes_ptr, input_len as usize);
-    if let Ok(borrowed_artifact) = parse_artifact(bytes) {
+    let bytes = match compression::decompress(bytes) {
+        Ok(bytes) => bytes,
+        Err(_) => return std::ptr::null(),
+    };
+    if let Ok(borrowed_artifact) = parse_artifact(&bytes) {
         Arc::into_raw(Arc::new(borrowed_artifact.into()))
     } else {
         std::ptr::null()
@@ -1,51 +1,101 @@
+pub mod action_tree;
 #[cfg(feature = "enable-ffi")]
 mod ffi;
+pub mod simulation;
+#[cfg(test)]
+mod tests;
 mod types;
 
-use crate::{constants, ExecResult, InterpreterEnergy, OutOfEnergy};
+use crate::{constants, ExecResult, InterpreterEnergy};
 use anyhow::{anyhow, bail, ensure};
 use concordium_contracts_common::*;
 use machine::Value;
-use std::{collections::LinkedList, convert::TryInto, io::Write};
+use std::{borrow::Cow, collections::BTreeMap, convert::TryInto, io::Write};
 pub use types::*;
 use wasm_transform::{
-    artifact::{Artifact, RunnableCode},
-    machine::{self, ExecutionOutcome, NoInterrupt},
+    executor::Executor,
+    machine::{self, ExecutionOutcome, NoInterrupt, RuntimeError},
     utils,
 };
 
 impl Logs {
     pub fn new() -> Self {
         Self {
-            logs: LinkedList::new(),
+            logs: Vec::new(),
+            tags: BTreeMap::new(),
         }
     }
 
-    /// The return value is
+    /// Log the event, unless it exceeds one of the limits
+    /// [MAX_LOG_SIZE](constants::MAX_LOG_SIZE) and
+    /// [MAX_NUM_LOGS](constants::MAX_NUM_LOGS). Events that exceed a limit
+    /// are ignored, and the contract is told so by the return value, which is
     ///
+    /// - -1 if data was not logged because it exceeds the maximum size of an
+    ///   event
     /// - 0 if data was not logged because it would exceed maximum number of
     ///   logs
     /// - 1 if data was logged.
+    ///
+    /// Host functions check the size before copying the event out of memory,
+    /// and only charge for events that are within the size limit, see
+    /// [host::log_event].
     pub fn log_event(&mut self, event: Vec<u8>) -> i32 {
+        if event.len() > constants::MAX_LOG_SIZE as usize {
+            return -1;
+        }
         let cur_len = self.logs.len();
         if cur_len < constants::MAX_NUM_LOGS {
-            self.logs.push_back(event);
+            self.logs.push(event);
             1
         } else {
             0
         }
     }
 
+    /// Log a typed event, i.e., the tag followed by the payload, see
+    /// [events](crate::events). The return value is as for
+    /// [log_event](Self::log_event).
+    pub fn log_event_typed(&mut self, tag: u32, payload: &[u8]) -> i32 {
+        let idx = self.logs.len();
+        let logged = self.log_event(crate::events::typed_event(tag, payload));
+        if logged == 1 {
+            self.tags.insert(idx, tag);
+        }
+        logged
+    }
+
     pub fn iterate(&self) -> impl Iterator<Item = &Vec<u8>> { self.logs.iter() }
 
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let len = self.logs.len();
-        let mut out = Vec::with_capacity(4 * len + 4);
-        out.extend_from_slice(&(len as u32).to_be_bytes());
+    /// Iterate over the events together with their tags, if they were logged
+    /// as typed events. The payloads of typed events are returned without the
+    /// tag.
+    pub fn iterate_tagged(&self) -> impl Iterator<Item = (Option<u32>, &[u8])> {
+        self.logs.iter().enumerate().map(move |(idx, event)| match self.tags.get(&idx) {
+            Some(&tag) => (Some(tag), &event[crate::events::TYPED_EVENT_TAG_SIZE as usize..]),
+            None => (None, &event[..]),
+        })
+    }
+
+    /// The number of bytes written by [serialize_into](Self::serialize_into).
+    pub fn serialized_size(&self) -> usize {
+        4 + self.logs.iter().map(|v| 4 + v.len()).sum::<usize>()
+    }
+
+    /// Write the number of logs, followed by each log prefixed by its length,
+    /// with all lengths as big-endian `u32`s.
+    pub fn serialize_into(&self, out: &mut impl Write) -> std::io::Result<()> {
+        out.write_all(&(self.logs.len() as u32).to_be_bytes())?;
         for v in self.iterate() {
-            out.extend_from_slice(&(v.len() as u32).to_be_bytes());
-            out.extend_from_slice(v);
+            out.write_all(&(v.len() as u32).to_be_bytes())?;
+            out.write_all(v)?;
         }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.serialized_size());
+        self.serialize_into(&mut out).expect("Serialization to a vector never fails.");
         out
     }
 }
@@ -137,24 +187,130 @@ impl Outcome {
     }
 }
 
-impl State {
-    pub fn is_empty(&self) -> bool { self.state.is_empty() }
-
-    // FIXME: This should not be copying so much data around, but for POC it is
-    // fine. We should probably do some sort of copy-on-write here in the near term,
-    // and in the long term we need to keep track of which parts were written.
-    pub fn new(st: Option<&[u8]>) -> Self {
-        match st {
-            None => Self {
-                state: Vec::new(),
+/// Keep only the actions that are reachable from the action with index `root`,
+/// in their original order, and update the operands of [Action::And] and
+/// [Action::Or] to refer to the new indices. The root is the last of the
+/// returned actions.
+///
+/// The actions must be as produced by [Outcome], i.e., the operands of each
+/// action must refer to earlier actions. If `root` is not the index of an
+/// action the result is empty.
+pub fn prune_actions(root: u32, actions: Vec<Action>) -> Vec<Action> {
+    let root = root as usize;
+    if root >= actions.len() {
+        return Vec::new();
+    }
+    let mut reachable = vec![false; root + 1];
+    reachable[root] = true;
+    // Operands refer to earlier actions, so a single pass from the root
+    // downwards finds all the reachable actions.
+    for idx in (0..=root).rev() {
+        if reachable[idx] {
+            if let Action::And {
+                l,
+                r,
+            }
+            | Action::Or {
+                l,
+                r,
+            } = actions[idx]
+            {
+                reachable[l as usize] = true;
+                reachable[r as usize] = true;
+            }
+        }
+    }
+    // The new index of each reachable action.
+    let mut new_index = vec![0u32; root + 1];
+    let mut next = 0;
+    for (idx, &is_reachable) in reachable.iter().enumerate() {
+        if is_reachable {
+            new_index[idx] = next;
+            next += 1;
+        }
+    }
+    actions
+        .into_iter()
+        .take(root + 1)
+        .enumerate()
+        .filter(|(idx, _)| reachable[*idx])
+        .map(|(_, action)| match action {
+            Action::And {
+                l,
+                r,
+            } => Action::And {
+                l: new_index[l as usize],
+                r: new_index[r as usize],
             },
-            Some(bytes) => Self {
-                state: Vec::from(bytes),
+            Action::Or {
+                l,
+                r,
+            } => Action::Or {
+                l: new_index[l as usize],
+                r: new_index[r as usize],
             },
+            action => action,
+        })
+        .collect()
+}
+
+impl<'a> State<'a> {
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+
+    /// Construct the state with the given contents. The bytes are shared with
+    /// the state, and only copied when they are modified.
+    pub fn new(st: Option<&'a [u8]>) -> Self {
+        let original = st.unwrap_or_default();
+        Self {
+            original:   Cow::Borrowed(original),
+            shared_len: original.len(),
+            pages:      BTreeMap::new(),
+            len:        original.len(),
         }
     }
 
-    pub fn len(&self) -> u32 { self.state.len() as u32 }
+    pub fn len(&self) -> u32 { self.len as u32 }
+
+    /// Get the copy of the page with the given number, copying it first if it
+    /// has not been modified before.
+    fn page_mut(&mut self, page: usize) -> &mut [u8] {
+        let original = &self.original;
+        let shared_len = self.shared_len;
+        self.pages.entry(page).or_insert_with(|| {
+            let mut bytes = vec![0u8; STATE_PAGE_SIZE].into_boxed_slice();
+            let start = page * STATE_PAGE_SIZE;
+            if start < shared_len {
+                let end = std::cmp::min(start + STATE_PAGE_SIZE, shared_len);
+                bytes[..end - start].copy_from_slice(&original[start..end]);
+            }
+            bytes
+        })
+    }
+
+    /// Copy the bytes of the state starting at the offset to the buffer. The
+    /// caller must ensure that the state has enough bytes to fill the buffer.
+    fn read_into(&self, mut offset: usize, out: &mut [u8]) {
+        let mut done = 0;
+        while done < out.len() {
+            let page = offset / STATE_PAGE_SIZE;
+            let in_page = offset % STATE_PAGE_SIZE;
+            let n = std::cmp::min(STATE_PAGE_SIZE - in_page, out.len() - done);
+            let dest = &mut out[done..done + n];
+            if let Some(bytes) = self.pages.get(&page) {
+                dest.copy_from_slice(&bytes[in_page..in_page + n]);
+            } else {
+                let shared = std::cmp::min(n, self.shared_len.saturating_sub(offset));
+                if shared > 0 {
+                    dest[..shared].copy_from_slice(&self.original[offset..offset + shared]);
+                }
+                for b in dest[shared..].iter_mut() {
+                    *b = 0;
+                }
+            }
+            done += n;
+            offset += n;
+        }
+    }
 
     pub fn write_state(&mut self, offset: u32, bytes: &[u8]) -> ExecResult<u32> {
         let length = bytes.len();
@@ -163,20 +319,27 @@ impl State {
         let end = offset
             .checked_add(length)
             .ok_or_else(|| anyhow!("Writing past the end of memory."))? as usize;
-        let end = std::cmp::min(end, constants::MAX_CONTRACT_STATE as usize) as u32;
-        if self.len() < end {
-            self.state.resize(end as usize, 0u8);
+        let end = std::cmp::min(end, constants::MAX_CONTRACT_STATE as usize);
+        if self.len < end {
+            // Bytes past the end of the state are already 0.
+            self.len = end;
+        }
+        let mut pos = offset;
+        while pos < end {
+            let in_page = pos % STATE_PAGE_SIZE;
+            let n = std::cmp::min(STATE_PAGE_SIZE - in_page, end - pos);
+            self.page_mut(pos / STATE_PAGE_SIZE)[in_page..in_page + n]
+                .copy_from_slice(&bytes[pos - offset..pos - offset + n]);
+            pos += n;
         }
-        let written = (&mut self.state[offset..end as usize]).write(bytes)?;
-        Ok(written as u32)
+        Ok((end - offset) as u32)
     }
 
-    pub fn load_state(&self, offset: u32, mut bytes: &mut [u8]) -> ExecResult<u32> {
+    pub fn load_state(&self, offset: u32, bytes: &mut [u8]) -> ExecResult<u32> {
         let offset = offset as usize;
-        ensure!(offset <= self.state.len());
-        // Write on slices overwrites the buffer and returns how many bytes were
-        // written.
-        let amt = bytes.write(&self.state[offset..])?;
+        ensure!(offset <= self.len);
+        let amt = std::cmp::min(bytes.len(), self.len - offset);
+        self.read_into(offset, &mut bytes[..amt]);
         Ok(amt as u32)
     }
 
@@ -184,10 +347,81 @@ impl State {
         if new_size > constants::MAX_CONTRACT_STATE {
             0
         } else {
-            self.state.resize(new_size as usize, 0u8);
+            let new_len = new_size as usize;
+            if new_len < self.len {
+                // Make sure that the bytes past the new end are 0 in case the
+                // state grows again.
+                self.shared_len = std::cmp::min(self.shared_len, new_len);
+                drop(self.pages.split_off(&((new_len + STATE_PAGE_SIZE - 1) / STATE_PAGE_SIZE)));
+                if let Some(bytes) = self.pages.get_mut(&(new_len / STATE_PAGE_SIZE)) {
+                    for b in bytes[new_len % STATE_PAGE_SIZE..].iter_mut() {
+                        *b = 0;
+                    }
+                }
+            }
+            self.len = new_len;
             1
         }
     }
+
+    /// Append the contents of the state to the buffer.
+    pub fn write_to(&self, out: &mut Vec<u8>) {
+        let start = out.len();
+        out.resize(start + self.len, 0u8);
+        self.read_into(0, &mut out[start..]);
+    }
+
+    /// Get the contents of the state.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.len);
+        self.write_to(&mut out);
+        out
+    }
+
+    /// Get the modifications of the state relative to the bytes it was
+    /// constructed from. Only the modified pages are copied, so this is
+    /// intended for the scheduler to update a stored state without
+    /// materializing the whole of it.
+    pub fn into_modified_regions(mut self) -> ModifiedRegions {
+        // Original bytes that are zero because the state was shrunk and grown
+        // again are not covered by the regions unless their pages are copied.
+        let zeroed_end = std::cmp::min(self.len, self.original.len());
+        if self.shared_len < zeroed_end {
+            for page in self.shared_len / STATE_PAGE_SIZE..=(zeroed_end - 1) / STATE_PAGE_SIZE {
+                self.page_mut(page);
+            }
+        }
+        let mut regions: Vec<(u32, Vec<u8>)> = Vec::new();
+        for (page, bytes) in self.pages.iter() {
+            let start = page * STATE_PAGE_SIZE;
+            let bytes = &bytes[..std::cmp::min(STATE_PAGE_SIZE, self.len - start)];
+            match regions.last_mut() {
+                Some((offset, region)) if *offset as usize + region.len() == start => {
+                    region.extend_from_slice(bytes)
+                }
+                _ => regions.push((start as u32, bytes.to_vec())),
+            }
+        }
+        ModifiedRegions {
+            len: self.len as u32,
+            regions,
+        }
+    }
+}
+
+impl From<Vec<u8>> for State<'static> {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self {
+            shared_len: bytes.len(),
+            len:        bytes.len(),
+            original:   Cow::Owned(bytes),
+            pages:      BTreeMap::new(),
+        }
+    }
+}
+
+impl<'a> From<State<'a>> for Vec<u8> {
+    fn from(state: State<'a>) -> Self { state.to_vec() }
 }
 
 pub struct InitHost<ParamType, Ctx> {
@@ -199,14 +433,14 @@ pub struct InitHost<ParamType, Ctx> {
     /// Logs produced during execution.
     pub logs:              Logs,
     /// The contract's state.
-    pub state:             State,
+    pub state:             State<'static>,
     /// The parameter to the init method.
     pub param:             ParamType,
     /// The init context for this invocation.
     pub init_ctx:          Ctx,
 }
 
-pub struct ReceiveHost<ParamType, Ctx> {
+pub struct ReceiveHost<'a, ParamType, Ctx> {
     /// Remaining energy for execution.
     pub energy:            InterpreterEnergy,
     /// Remaining amount of activation frames.
@@ -215,7 +449,7 @@ pub struct ReceiveHost<ParamType, Ctx> {
     /// Logs produced during execution.
     pub logs:              Logs,
     /// The contract's state.
-    pub state:             State,
+    pub state:             State<'a>,
     /// The parameter to the receive method.
     pub param:             ParamType,
     /// Outcomes of the execution, i.e., the actions tree.
@@ -240,6 +474,9 @@ pub trait HasInitContext {
     fn metadata(&self) -> &Self::MetadataType;
     fn init_origin(&self) -> ExecResult<&AccountAddress>;
     fn sender_policies(&self) -> ExecResult<&[u8]>;
+    /// The seed of the randomness returned by the `get_random` host function
+    /// of V1 contracts, see [RandomSeed].
+    fn random_seed(&self) -> ExecResult<&RandomSeed>;
 }
 
 /// Generic implementation for all references to types that already implement
@@ -254,6 +491,8 @@ impl<'a, X: HasInitContext> HasInitContext for &'a X {
     fn init_origin(&self) -> ExecResult<&AccountAddress> { (*self).init_origin() }
 
     fn sender_policies(&self) -> ExecResult<&[u8]> { (*self).sender_policies() }
+
+    fn random_seed(&self) -> ExecResult<&RandomSeed> { (*self).random_seed() }
 }
 
 impl<X: AsRef<[u8]>> HasInitContext for InitContext<X> {
@@ -264,6 +503,8 @@ impl<X: AsRef<[u8]>> HasInitContext for InitContext<X> {
     fn init_origin(&self) -> ExecResult<&AccountAddress> { Ok(&self.init_origin) }
 
     fn sender_policies(&self) -> ExecResult<&[u8]> { Ok(self.sender_policies.as_ref()) }
+
+    fn random_seed(&self) -> ExecResult<&RandomSeed> { bail!(MissingRandomSeed) }
 }
 
 /// Types which can act as receive contexts.
@@ -286,6 +527,9 @@ pub trait HasReceiveContext {
     fn sender(&self) -> ExecResult<&Address>;
     fn owner(&self) -> ExecResult<&AccountAddress>;
     fn sender_policies(&self) -> ExecResult<&[u8]>;
+    /// The seed of the randomness returned by the `get_random` host function
+    /// of V1 contracts, see [RandomSeed].
+    fn random_seed(&self) -> ExecResult<&RandomSeed>;
 }
 
 /// Generic implementation for all references to types that already implement
@@ -308,6 +552,8 @@ impl<'a, X: HasReceiveContext> HasReceiveContext for &'a X {
     fn owner(&self) -> ExecResult<&AccountAddress> { (*self).owner() }
 
     fn sender_policies(&self) -> ExecResult<&[u8]> { (*self).sender_policies() }
+
+    fn random_seed(&self) -> ExecResult<&RandomSeed> { (*self).random_seed() }
 }
 
 impl<X: AsRef<[u8]>> HasReceiveContext for ReceiveContext<X> {
@@ -326,6 +572,77 @@ impl<X: AsRef<[u8]>> HasReceiveContext for ReceiveContext<X> {
     fn owner(&self) -> ExecResult<&AccountAddress> { Ok(&self.owner) }
 
     fn sender_policies(&self) -> ExecResult<&[u8]> { Ok(self.sender_policies.as_ref()) }
+
+    fn random_seed(&self) -> ExecResult<&RandomSeed> { bail!(MissingRandomSeed) }
+}
+
+/// Seed of the pseudo-randomness available to contracts. The block seed is
+/// fixed for a block, e.g., derived from the block nonce. The nonce identifies
+/// the invocation within the block, and whoever executes contracts must not
+/// use the same nonce with the same block seed for two invocations, e.g., by
+/// counting the invocations of contracts in the block. A resumed invocation
+/// keeps its nonce. The values returned to a contract are derived from the
+/// seed, the address of the contract, or the origin for init functions, and
+/// the number of values returned so far in the invocation. The randomness is
+/// thus deterministic, and known to whoever knows the block seed, which
+/// includes the producer of the block.
+///
+/// The node supplies the seed of an execution with
+/// [InvokeOptions::random_seed](crate::v1::InvokeOptions::random_seed).
+/// Contexts can supply a seed as well, e.g., with [WithRandomSeed] when
+/// simulating contracts, which is used if the execution has none.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RandomSeed {
+    /// The seed of the block.
+    pub block_seed: [u8; 32],
+    /// The nonce of the invocation within the block.
+    pub nonce:      u64,
+}
+
+/// Error raised when a contract requests randomness but neither the execution
+/// nor the context provides a [RandomSeed].
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("The execution does not provide a random seed.")]
+pub struct MissingRandomSeed;
+
+/// A context together with a [RandomSeed]. The context is otherwise used as
+/// it is.
+#[derive(Debug, Clone)]
+pub struct WithRandomSeed<Ctx> {
+    pub ctx:  Ctx,
+    pub seed: RandomSeed,
+}
+
+impl<Ctx: HasInitContext> HasInitContext for WithRandomSeed<Ctx> {
+    type MetadataType = Ctx::MetadataType;
+
+    fn metadata(&self) -> &Self::MetadataType { self.ctx.metadata() }
+
+    fn init_origin(&self) -> ExecResult<&AccountAddress> { self.ctx.init_origin() }
+
+    fn sender_policies(&self) -> ExecResult<&[u8]> { self.ctx.sender_policies() }
+
+    fn random_seed(&self) -> ExecResult<&RandomSeed> { Ok(&self.seed) }
+}
+
+impl<Ctx: HasReceiveContext> HasReceiveContext for WithRandomSeed<Ctx> {
+    type MetadataType = Ctx::MetadataType;
+
+    fn metadata(&self) -> &Self::MetadataType { self.ctx.metadata() }
+
+    fn invoker(&self) -> ExecResult<&AccountAddress> { self.ctx.invoker() }
+
+    fn self_address(&self) -> ExecResult<&ContractAddress> { self.ctx.self_address() }
+
+    fn self_balance(&self) -> ExecResult<Amount> { self.ctx.self_balance() }
+
+    fn sender(&self) -> ExecResult<&Address> { self.ctx.sender() }
+
+    fn owner(&self) -> ExecResult<&AccountAddress> { self.ctx.owner() }
+
+    fn sender_policies(&self) -> ExecResult<&[u8]> { self.ctx.sender_policies() }
+
+    fn random_seed(&self) -> ExecResult<&RandomSeed> { Ok(&self.seed) }
 }
 
 pub trait HasChainMetadata {
@@ -418,6 +735,32 @@ pub(crate) mod host {
         Ok(())
     }
 
+    /// Like [log_event], but the event is the tag, which is the first argument,
+    /// followed by the payload in memory, see [events](crate::events). The
+    /// limit on the size of events and the cost apply to the whole event,
+    /// including the tag.
+    #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
+    pub fn log_event_typed(
+        memory: &mut Vec<u8>,
+        stack: &mut machine::RuntimeStack,
+        energy: &mut InterpreterEnergy,
+        logs: &mut Logs,
+    ) -> machine::RunResult<()> {
+        let length = unsafe { stack.pop_u32() };
+        let start = unsafe { stack.pop_u32() } as usize;
+        let tag = unsafe { stack.pop_u32() };
+        let end = start + length as usize;
+        ensure!(end <= memory.len(), "Illegal memory access.");
+        let event_length = length.saturating_add(crate::events::TYPED_EVENT_TAG_SIZE);
+        if event_length <= constants::MAX_LOG_SIZE {
+            energy.tick_energy(constants::log_event_cost(event_length))?;
+            stack.push_value(logs.log_event_typed(tag, &memory[start..end]))
+        } else {
+            stack.push_value(-1i32)
+        }
+        Ok(())
+    }
+
     #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
     pub fn load_state(
         memory: &mut Vec<u8>,
@@ -632,6 +975,43 @@ pub(crate) mod host {
         Ok(())
     }
 
+    /// Write the encoding of the address to memory at the given location, and
+    /// return the number of bytes written. The encoding is a tag, `0` for
+    /// account addresses and `1` for contract addresses, followed by either
+    /// the 32 bytes of the account address, or the index and subindex of the
+    /// contract address as little-endian `u64`. It is thus 33 or 17 bytes
+    /// long, and is the same as the serialization of [Address].
+    ///
+    /// The entire encoding is checked to fit into memory before anything is
+    /// written.
+    pub fn write_address(
+        memory: &mut [u8],
+        start: usize,
+        address: &Address,
+    ) -> machine::RunResult<u32> {
+        let len = match address {
+            Address::Account(_) => 1 + ACCOUNT_ADDRESS_SIZE,
+            Address::Contract(_) => 1 + 16,
+        };
+        ensure!(
+            start.checked_add(len).map_or(false, |end| end <= memory.len()),
+            "Illegal memory access for address."
+        );
+        let out = &mut memory[start..start + len];
+        match address {
+            Address::Account(acc) => {
+                out[0] = 0;
+                out[1..].copy_from_slice(acc.as_ref());
+            }
+            Address::Contract(ca) => {
+                out[0] = 1;
+                out[1..9].copy_from_slice(&ca.index.to_le_bytes());
+                out[9..].copy_from_slice(&ca.subindex.to_le_bytes());
+            }
+        }
+        Ok(len as u32)
+    }
+
     #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
     pub fn get_receive_sender(
         memory: &mut Vec<u8>,
@@ -639,10 +1019,7 @@ pub(crate) mod host {
         sender: ExecResult<&Address>,
     ) -> machine::RunResult<()> {
         let start = unsafe { stack.pop_u32() } as usize;
-        ensure!(start < memory.len(), "Illegal memory access for receive sender.");
-        sender?
-            .serial::<&mut [u8]>(&mut &mut memory[start..])
-            .map_err(|_| anyhow!("Memory out of bounds."))?;
+        write_address(memory, start, sender?)?;
         Ok(())
     }
 
@@ -744,8 +1121,8 @@ impl<ParamType: AsRef<[u8]>, Ctx: HasInitContext> machine::Host<ProcessedImports
     }
 }
 
-impl<ParamType: AsRef<[u8]>, Ctx: HasReceiveContext> machine::Host<ProcessedImports>
-    for ReceiveHost<ParamType, Ctx>
+impl<'a, ParamType: AsRef<[u8]>, Ctx: HasReceiveContext> machine::Host<ProcessedImports>
+    for ReceiveHost<'a, ParamType, Ctx>
 {
     type Interrupt = NoInterrupt;
 
@@ -838,8 +1215,16 @@ impl<ParamType: AsRef<[u8]>, Ctx: HasReceiveContext> machine::Host<ProcessedImpo
 }
 
 /// Invokes an init-function from a given artifact.
-pub fn invoke_init<C: RunnableCode, Ctx: HasInitContext>(
-    artifact: &Artifact<ProcessedImports, C>,
+#[cfg_attr(
+    feature = "instrumentation",
+    tracing::instrument(
+        level = "info",
+        skip_all,
+        fields(entrypoint = init_name, energy = energy.energy)
+    )
+)]
+pub fn invoke_init<E: Executor<ProcessedImports>, Ctx: HasInitContext>(
+    artifact: &E,
     amount: u64,
     init_ctx: Ctx,
     init_name: &str,
@@ -864,13 +1249,8 @@ pub fn invoke_init<C: RunnableCode, Ctx: HasInitContext>(
             reason,
             ..
         }) => match reason {}, // impossible case, InitHost has no interrupts
-        Err(e) => {
-            if e.downcast_ref::<OutOfEnergy>().is_some() {
-                return Ok(InitResult::OutOfEnergy);
-            } else {
-                return Err(e);
-            }
-        }
+        Err(RuntimeError::OutOfEnergy) => return Ok(InitResult::OutOfEnergy),
+        Err(e) => return Err(e.into_anyhow()),
     };
     let remaining_energy = host.energy.energy;
     // process the return value.
@@ -905,7 +1285,7 @@ pub fn invoke_init_from_artifact<Ctx: HasInitContext>(
     parameter: Parameter,
     energy: InterpreterEnergy,
 ) -> ExecResult<InitResult> {
-    let artifact = utils::parse_artifact(artifact_bytes)?;
+    let artifact = utils::parse_artifact::<ProcessedImports>(artifact_bytes)?;
     invoke_init(&artifact, amount, init_ctx, init_name, parameter, energy)
 }
 
@@ -919,7 +1299,8 @@ pub fn invoke_init_from_source<Ctx: HasInitContext>(
     parameter: Parameter,
     energy: InterpreterEnergy,
 ) -> ExecResult<InitResult> {
-    let artifact = utils::instantiate(&ConcordiumAllowedImports, source_bytes)?;
+    let artifact =
+        utils::instantiate::<ProcessedImports, _>(&ConcordiumAllowedImports, source_bytes)?;
     invoke_init(&artifact, amount, init_ctx, init_name, parameter, energy)
 }
 
@@ -934,20 +1315,31 @@ pub fn invoke_init_with_metering_from_source<Ctx: HasInitContext>(
     parameter: Parameter,
     energy: InterpreterEnergy,
 ) -> ExecResult<InitResult> {
-    let artifact = utils::instantiate_with_metering(&ConcordiumAllowedImports, source_bytes)?;
+    let artifact = utils::instantiate_with_metering::<ProcessedImports, _>(
+        &ConcordiumAllowedImports,
+        source_bytes,
+    )?;
     invoke_init(&artifact, amount, init_ctx, init_name, parameter, energy)
 }
 
 /// Invokes an receive-function from a given artifact
-pub fn invoke_receive<C: RunnableCode, Ctx: HasReceiveContext>(
-    artifact: &Artifact<ProcessedImports, C>,
+#[cfg_attr(
+    feature = "instrumentation",
+    tracing::instrument(
+        level = "info",
+        skip_all,
+        fields(entrypoint = receive_name, energy = energy.energy)
+    )
+)]
+pub fn invoke_receive<'a, E: Executor<ProcessedImports>, Ctx: HasReceiveContext>(
+    artifact: &E,
     amount: u64,
     receive_ctx: Ctx,
-    current_state: &[u8],
+    current_state: &'a [u8],
     receive_name: &str,
     parameter: Parameter,
     energy: InterpreterEnergy,
-) -> ExecResult<ReceiveResult> {
+) -> ExecResult<ReceiveResult<'a>> {
     let mut host = ReceiveHost {
         energy,
         activation_frames: constants::MAX_ACTIVATION_FRAMES,
@@ -967,22 +1359,14 @@ pub fn invoke_receive<C: RunnableCode, Ctx: HasReceiveContext>(
             reason,
             ..
         }) => match reason {}, // impossible case, ReceiveHost has no interrupts
-        Err(e) => {
-            if e.downcast_ref::<OutOfEnergy>().is_some() {
-                return Ok(ReceiveResult::OutOfEnergy);
-            } else {
-                return Err(e);
-            }
-        }
+        Err(RuntimeError::OutOfEnergy) => return Ok(ReceiveResult::OutOfEnergy),
+        Err(e) => return Err(e.into_anyhow()),
     };
     let remaining_energy = host.energy.energy;
     if let Some(Value::I32(n)) = res {
-        // FIXME: We should filter out to only return the ones reachable from
-        // the root.
-        let mut actions = host.outcomes.cur_state;
+        let actions = host.outcomes.cur_state;
         if n >= 0 && (n as usize) < actions.len() {
-            let n = n as usize;
-            actions.truncate(n + 1);
+            let actions = prune_actions(n as u32, actions);
             Ok(ReceiveResult::Success {
                 logs: host.logs,
                 state: host.state,
@@ -1018,46 +1402,50 @@ fn reason_from_wasm_error_code(n: i32) -> ExecResult<i32> {
 
 /// Invokes an receive-function from a given artifact *bytes*
 #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
-pub fn invoke_receive_from_artifact<Ctx: HasReceiveContext>(
+pub fn invoke_receive_from_artifact<'a, Ctx: HasReceiveContext>(
     artifact_bytes: &[u8],
     amount: u64,
     receive_ctx: Ctx,
-    current_state: &[u8],
+    current_state: &'a [u8],
     receive_name: &str,
     parameter: Parameter,
     energy: InterpreterEnergy,
-) -> ExecResult<ReceiveResult> {
-    let artifact = utils::parse_artifact(artifact_bytes)?;
+) -> ExecResult<ReceiveResult<'a>> {
+    let artifact = utils::parse_artifact::<ProcessedImports>(artifact_bytes)?;
     invoke_receive(&artifact, amount, receive_ctx, current_state, receive_name, parameter, energy)
 }
 
 /// Invokes an receive-function from Wasm module bytes
 #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
-pub fn invoke_receive_from_source<Ctx: HasReceiveContext>(
+pub fn invoke_receive_from_source<'a, Ctx: HasReceiveContext>(
     source_bytes: &[u8],
     amount: u64,
     receive_ctx: Ctx,
-    current_state: &[u8],
+    current_state: &'a [u8],
     receive_name: &str,
     parameter: Parameter,
     energy: InterpreterEnergy,
-) -> ExecResult<ReceiveResult> {
-    let artifact = utils::instantiate(&ConcordiumAllowedImports, source_bytes)?;
+) -> ExecResult<ReceiveResult<'a>> {
+    let artifact =
+        utils::instantiate::<ProcessedImports, _>(&ConcordiumAllowedImports, source_bytes)?;
     invoke_receive(&artifact, amount, receive_ctx, current_state, receive_name, parameter, energy)
 }
 
 /// Invokes an receive-function from Wasm module bytes, injects the module with
 /// metering.
 #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
-pub fn invoke_receive_with_metering_from_source<Ctx: HasReceiveContext>(
+pub fn invoke_receive_with_metering_from_source<'a, Ctx: HasReceiveContext>(
     source_bytes: &[u8],
     amount: u64,
     receive_ctx: Ctx,
-    current_state: &[u8],
+    current_state: &'a [u8],
     receive_name: &str,
     parameter: Parameter,
     energy: InterpreterEnergy,
-) -> ExecResult<ReceiveResult> {
-    let artifact = utils::instantiate_with_metering(&ConcordiumAllowedImports, source_bytes)?;
+) -> ExecResult<ReceiveResult<'a>> {
+    let artifact = utils::instantiate_with_metering::<ProcessedImports, _>(
+        &ConcordiumAllowedImports,
+        source_bytes,
+    )?;
     invoke_receive(&artifact, amount, receive_ctx, current_state, receive_name, parameter, energy)
 }
@@ -0,0 +1,209 @@
+//! Execution of the action trees produced by V0 receive functions, with the
+//! balance of the instance accounted as on the chain. This is intended for
+//! simulating contracts, where the chain is not available to execute the
+//! actions.
+//!
+//! The tree is executed from its root, which is the last of the actions
+//! returned in [ReceiveResult::Success](super::ReceiveResult::Success).
+//! - [Action::Accept] always succeeds.
+//! - [Action::SimpleTransfer] and [Action::Send] fail if the balance of the
+//!   instance is less than the amount. Otherwise the amount is deducted from
+//!   the balance. A send may additionally be rejected by the receiving
+//!   contract, which the caller of [execute_actions] decides.
+//! - [Action::And] executes its left operand and, if that succeeds, its right
+//!   operand. It fails if either fails.
+//! - [Action::Or] executes its left operand and, if that fails, rolls back its
+//!   effects and executes the right operand instead.
+//!
+//! If the tree fails as a whole all effects are rolled back, and the balance
+//! of the instance is the one it started with.
+use super::{Action, SendAction};
+use crate::ExecResult;
+use anyhow::{bail, ensure};
+use concordium_contracts_common::{AccountAddress, Amount, ContractAddress};
+use std::collections::BTreeMap;
+
+/// The maximum number of actions executed by [execute_actions]. Operands of
+/// [Action::And] and [Action::Or] may be shared, so the number of executed
+/// actions can be exponential in the number of actions of the tree.
+pub const MAX_EXECUTED_ACTIONS: u64 = 1_000_000;
+
+/// A transfer that took effect, see [ActionsExecution].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transfer {
+    /// A transfer to an account, by [Action::SimpleTransfer].
+    Account(AccountAddress, Amount),
+    /// A transfer to a contract, by [Action::Send].
+    Contract(ContractAddress, Amount),
+}
+
+/// The reason an action failed, see [ActionsExecution::failures].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionFailure {
+    /// The balance of the instance was less than the amount, which is the
+    /// first field. The second field is the balance at the time.
+    InsufficientFunds {
+        amount:  Amount,
+        balance: Amount,
+    },
+    /// The receiving contract rejected the message.
+    Rejected,
+}
+
+/// The result of executing an action tree with [execute_actions].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActionsExecution {
+    /// Whether the tree succeeded as a whole.
+    pub success:       bool,
+    /// The balance of the instance after executing the tree.
+    pub final_balance: Amount,
+    /// The transfers that took effect, in the order in which they were made.
+    /// This is empty if the tree failed.
+    pub transfers:     Vec<Transfer>,
+    /// The indices of the actions that failed, with the reason, in the order
+    /// in which they were executed. This includes failures that were
+    /// recovered from by [Action::Or].
+    pub failures:      Vec<(u32, ActionFailure)>,
+}
+
+impl ActionsExecution {
+    /// The total amounts transferred to each account.
+    pub fn account_totals(&self) -> BTreeMap<AccountAddress, Amount> {
+        let mut totals = BTreeMap::new();
+        for transfer in self.transfers.iter() {
+            if let Transfer::Account(addr, amount) = transfer {
+                let total = totals.entry(*addr).or_insert(Amount::from_micro_ccd(0));
+                *total = Amount::from_micro_ccd(total.micro_ccd + amount.micro_ccd);
+            }
+        }
+        totals
+    }
+}
+
+/// The state of an execution, which is rolled back when an action fails.
+struct Executor<'a, F> {
+    actions:   &'a [Action],
+    on_send:   F,
+    balance:   u64,
+    transfers: Vec<Transfer>,
+    failures:  Vec<(u32, ActionFailure)>,
+    steps:     u64,
+}
+
+impl<'a, F: FnMut(&SendAction) -> bool> Executor<'a, F> {
+    /// Deduct the amount from the balance if it suffices.
+    fn withdraw(&mut self, idx: u32, amount: u64) -> bool {
+        if let Some(balance) = self.balance.checked_sub(amount) {
+            self.balance = balance;
+            true
+        } else {
+            self.failures.push((idx, ActionFailure::InsufficientFunds {
+                amount:  Amount::from_micro_ccd(amount),
+                balance: Amount::from_micro_ccd(self.balance),
+            }));
+            false
+        }
+    }
+
+    /// Execute the action with the given index, and return whether it
+    /// succeeded. The effects of a failed action are not rolled back here.
+    fn execute(&mut self, idx: u32) -> ExecResult<bool> {
+        self.steps += 1;
+        ensure!(
+            self.steps <= MAX_EXECUTED_ACTIONS,
+            "The action tree executes more than {} actions.",
+            MAX_EXECUTED_ACTIONS
+        );
+        let action = match self.actions.get(idx as usize) {
+            Some(action) => action,
+            None => bail!("Action {} does not exist.", idx),
+        };
+        match action {
+            Action::Accept => Ok(true),
+            Action::SimpleTransfer {
+                data,
+            } => {
+                if !self.withdraw(idx, data.amount) {
+                    return Ok(false);
+                }
+                self.transfers
+                    .push(Transfer::Account(data.to_addr, Amount::from_micro_ccd(data.amount)));
+                Ok(true)
+            }
+            Action::Send {
+                data,
+            } => {
+                if !self.withdraw(idx, data.amount) {
+                    return Ok(false);
+                }
+                if !(self.on_send)(data) {
+                    self.failures.push((idx, ActionFailure::Rejected));
+                    return Ok(false);
+                }
+                self.transfers
+                    .push(Transfer::Contract(data.to_addr, Amount::from_micro_ccd(data.amount)));
+                Ok(true)
+            }
+            &Action::And {
+                l,
+                r,
+            } => {
+                ensure!(l < idx && r < idx, "Action {} combines later actions.", idx);
+                Ok(self.execute(l)? && self.execute(r)?)
+            }
+            &Action::Or {
+                l,
+                r,
+            } => {
+                ensure!(l < idx && r < idx, "Action {} combines later actions.", idx);
+                let balance = self.balance;
+                let num_transfers = self.transfers.len();
+                if self.execute(l)? {
+                    return Ok(true);
+                }
+                self.balance = balance;
+                self.transfers.truncate(num_transfers);
+                self.execute(r)
+            }
+        }
+    }
+}
+
+/// Execute the action tree whose root is the last of the actions, starting
+/// with the given balance of the instance. See the module documentation for
+/// the semantics. The function `on_send` is called for each [Action::Send]
+/// for which the instance has sufficient funds, and returns whether the
+/// receiving contract accepts the message.
+///
+/// This fails if there are no actions, or if the actions are malformed.
+pub fn execute_actions(
+    actions: &[Action],
+    self_balance: Amount,
+    on_send: impl FnMut(&SendAction) -> bool,
+) -> ExecResult<ActionsExecution> {
+    ensure!(!actions.is_empty(), "There are no actions to execute.");
+    let mut executor = Executor {
+        actions,
+        on_send,
+        balance: self_balance.micro_ccd,
+        transfers: Vec::new(),
+        failures: Vec::new(),
+        steps: 0,
+    };
+    let success = executor.execute(actions.len() as u32 - 1)?;
+    if success {
+        Ok(ActionsExecution {
+            success,
+            final_balance: Amount::from_micro_ccd(executor.balance),
+            transfers: executor.transfers,
+            failures: executor.failures,
+        })
+    } else {
+        Ok(ActionsExecution {
+            success,
+            final_balance: self_balance,
+            transfers: Vec::new(),
+            failures: executor.failures,
+        })
+    }
+}
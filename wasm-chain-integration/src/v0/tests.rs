@@ -0,0 +1,215 @@
+use super::*;
+use quickcheck::*;
+
+const NUM_TESTS: u64 = 10000;
+
+#[test]
+/// Check that the copy-on-write state behaves as a plain vector of bytes under
+/// writes, loads and resizes, and that the modified regions applied to the
+/// original bytes give the final state.
+fn prop_state_copy_on_write() {
+    let prop = |original: Vec<u8>, ops: Vec<(u8, u16, Vec<u8>)>| -> anyhow::Result<()> {
+        let mut state = State::new(Some(&original));
+        let mut model = original.clone();
+        for (op, arg, bytes) in ops {
+            match op % 3 {
+                0 => {
+                    let offset = u32::from(arg) % (model.len() as u32 + 2);
+                    let res = state.write_state(offset, &bytes);
+                    if offset as usize > model.len() {
+                        ensure!(res.is_err(), "Writing past the end should fail.");
+                    } else {
+                        let offset = offset as usize;
+                        let end = std::cmp::min(
+                            offset + bytes.len(),
+                            constants::MAX_CONTRACT_STATE as usize,
+                        );
+                        if model.len() < end {
+                            model.resize(end, 0u8);
+                        }
+                        model[offset..end].copy_from_slice(&bytes[..end - offset]);
+                        ensure!(res? as usize == end - offset, "Incorrect write length.");
+                    }
+                }
+                1 => {
+                    let new_size = u32::from(arg) % (constants::MAX_CONTRACT_STATE + 10);
+                    let res = state.resize_state(new_size);
+                    if new_size > constants::MAX_CONTRACT_STATE {
+                        ensure!(res == 0, "Resizing past the maximum should fail.");
+                    } else {
+                        ensure!(res == 1, "Resizing should succeed.");
+                        model.resize(new_size as usize, 0u8);
+                    }
+                }
+                _ => {
+                    let offset = u32::from(arg) % (model.len() as u32 + 1);
+                    let mut buf = vec![0xffu8; bytes.len()];
+                    let read = state.load_state(offset, &mut buf)? as usize;
+                    let expected = &model[offset as usize..];
+                    let expected = &expected[..std::cmp::min(expected.len(), buf.len())];
+                    ensure!(&buf[..read] == expected, "Incorrect bytes loaded.");
+                }
+            }
+            ensure!(state.len() as usize == model.len(), "Incorrect state length.");
+        }
+        ensure!(state.to_vec() == model, "Incorrect final state.");
+        let regions = state.into_modified_regions();
+        ensure!(regions.apply(&original) == model, "Incorrect modified regions.");
+        Ok(())
+    };
+    QuickCheck::new().tests(NUM_TESTS).quickcheck(prop as fn(_, _) -> anyhow::Result<()>);
+}
+
+#[test]
+/// Check that only modified pages are reported, and that adjacent pages are
+/// merged into one region.
+fn test_state_modified_regions() {
+    let original = vec![1u8; 4 * STATE_PAGE_SIZE];
+    let mut state = State::new(Some(&original));
+    assert!(state.clone().into_modified_regions().regions.is_empty(), "Nothing was modified.");
+    state.write_state(STATE_PAGE_SIZE as u32 - 1, &[2, 2]).unwrap();
+    state.write_state(3 * STATE_PAGE_SIZE as u32, &[3]).unwrap();
+    let regions = state.into_modified_regions();
+    assert_eq!(regions.len, 4 * STATE_PAGE_SIZE as u32);
+    let offsets: Vec<_> = regions.regions.iter().map(|(o, r)| (*o as usize, r.len())).collect();
+    assert_eq!(offsets, [(0, 2 * STATE_PAGE_SIZE), (3 * STATE_PAGE_SIZE, STATE_PAGE_SIZE)]);
+}
+
+#[test]
+/// Check that logs are serialized as a length-prefixed list of length-prefixed
+/// events, and that the size is computed exactly.
+fn test_logs_serialization() {
+    let mut logs = Logs::new();
+    assert_eq!(logs.to_bytes(), [0, 0, 0, 0]);
+    assert_eq!(logs.log_event(vec![1, 2, 3]), 1);
+    assert_eq!(logs.log_event(Vec::new()), 1);
+    let bytes = logs.to_bytes();
+    assert_eq!(bytes, [0, 0, 0, 2, 0, 0, 0, 3, 1, 2, 3, 0, 0, 0, 0]);
+    assert_eq!(bytes.len(), logs.serialized_size());
+}
+
+#[test]
+/// Check that events beyond the limits are ignored with distinct results, and
+/// that the cost of logging is proportional to the size of the event.
+fn test_logs_limits() {
+    let mut logs = Logs::new();
+    let max_size = constants::MAX_LOG_SIZE as usize;
+    assert_eq!(logs.log_event(vec![0; max_size + 1]), -1, "Too big events are rejected.");
+    assert_eq!(logs.log_event_typed(0, &[0; 509]), -1, "The tag counts towards the size.");
+    for _ in 0..constants::MAX_NUM_LOGS {
+        assert_eq!(logs.log_event(vec![0; max_size]), 1);
+    }
+    assert_eq!(logs.log_event(Vec::new()), 0, "Events beyond the maximum number are ignored.");
+    assert_eq!(logs.log_event_typed(1, &[]), 0);
+    assert_eq!(logs.logs.len(), constants::MAX_NUM_LOGS);
+    assert!(logs.tags.is_empty(), "Ignored typed events are not tagged.");
+    assert_eq!(
+        constants::log_event_cost(10) - constants::log_event_cost(9),
+        constants::LOG_EVENT_BYTE_COST
+    );
+}
+
+#[test]
+/// Check that the balance of the instance is accounted across the transfers of
+/// an action tree, and that failed subtrees are rolled back.
+fn test_execute_actions_balance() {
+    use simulation::*;
+    let alice = AccountAddress([1u8; 32]);
+    let bob = AccountAddress([2u8; 32]);
+    let mut outcome = Outcome::new();
+    let to_alice = outcome.simple_transfer(&alice.0, 60).unwrap();
+    let to_bob = outcome.simple_transfer(&bob.0, 60).unwrap();
+    let accept = outcome.accept();
+    // Transfer to alice and then to bob, or else do nothing.
+    let both = outcome.combine_and(to_alice, to_bob).unwrap();
+    outcome.combine_or(both, accept).unwrap();
+    let actions = outcome.cur_state;
+
+    let execution = execute_actions(&actions, Amount::from_micro_ccd(100), |_| true).unwrap();
+    assert!(execution.success, "The Or recovers from the failed transfer.");
+    assert_eq!(execution.final_balance, Amount::from_micro_ccd(100), "The transfers are undone.");
+    assert!(execution.transfers.is_empty());
+    assert_eq!(execution.failures, [(to_bob, ActionFailure::InsufficientFunds {
+        amount:  Amount::from_micro_ccd(60),
+        balance: Amount::from_micro_ccd(40),
+    })]);
+
+    let execution = execute_actions(&actions, Amount::from_micro_ccd(150), |_| true).unwrap();
+    assert!(execution.success);
+    assert_eq!(execution.final_balance, Amount::from_micro_ccd(30));
+    assert_eq!(execution.transfers, [
+        Transfer::Account(alice, Amount::from_micro_ccd(60)),
+        Transfer::Account(bob, Amount::from_micro_ccd(60))
+    ]);
+    assert_eq!(execution.account_totals().get(&bob), Some(&Amount::from_micro_ccd(60)));
+
+    // Without the Or, the tree fails as a whole.
+    let execution =
+        execute_actions(&actions[..=both as usize], Amount::from_micro_ccd(100), |_| true).unwrap();
+    assert!(!execution.success);
+    assert_eq!(execution.final_balance, Amount::from_micro_ccd(100));
+
+    // A rejected send does not transfer its amount.
+    let mut outcome = Outcome::new();
+    let send = outcome.send(0, 0, b"c.f", 10, &[]).unwrap();
+    let to_alice = outcome.simple_transfer(&alice.0, 95).unwrap();
+    outcome.combine_or(send, to_alice).unwrap();
+    let execution =
+        execute_actions(&outcome.cur_state, Amount::from_micro_ccd(100), |_| false).unwrap();
+    assert!(execution.success);
+    assert_eq!(execution.final_balance, Amount::from_micro_ccd(5));
+    assert_eq!(execution.failures, [(send, ActionFailure::Rejected)]);
+}
+
+#[test]
+/// Check that pruning keeps exactly the actions reachable from the root, and
+/// remaps the operands of the combinators.
+fn test_prune_actions() {
+    let mut outcome = Outcome::new();
+    let unreachable = outcome.accept();
+    let to_alice = outcome.simple_transfer(&[1u8; 32], 10).unwrap();
+    let accept = outcome.accept();
+    outcome.combine_and(unreachable, to_alice).unwrap();
+    let root = outcome.combine_or(to_alice, accept).unwrap();
+    outcome.accept();
+    let pruned = prune_actions(root, outcome.cur_state);
+    let displayed: Vec<String> = pruned.iter().map(ToString::to_string).collect();
+    assert_eq!(displayed.len(), 3, "Only the reachable actions are kept: {:?}", displayed);
+    assert!(matches!(pruned[0], Action::SimpleTransfer { .. }));
+    assert!(matches!(pruned[1], Action::Accept));
+    assert!(matches!(pruned[2], Action::Or {
+        l: 0,
+        r: 1,
+    }));
+    assert!(prune_actions(5, vec![Action::Accept]).is_empty(), "The root must exist.");
+}
+
+#[test]
+/// Check the rendering of an action tree in which an operand is shared.
+fn test_render_actions() {
+    use action_tree::*;
+    let mut outcome = Outcome::new();
+    let to_alice = outcome.simple_transfer(&[1u8; 32], 1_000_000).unwrap();
+    let accept = outcome.accept();
+    let both = outcome.combine_and(to_alice, accept).unwrap();
+    outcome.combine_or(both, accept).unwrap();
+    let actions = outcome.cur_state;
+
+    let tree = render_actions("tree".parse().unwrap(), &actions);
+    let lines: Vec<&str> = tree.lines().collect();
+    assert_eq!(lines.len(), 5, "Each action is on a line: {}", tree);
+    assert_eq!(lines[0], "#3: or else");
+    assert_eq!(lines[1], "  #2: and then");
+    assert!(lines[2].starts_with("    #0: transfer 1.000000 CCD to "));
+    assert_eq!(lines[3], "    #1: accept");
+    assert_eq!(lines[4], "  #1: (see above)", "Shared operands are displayed once.");
+
+    let dot = render_actions(ActionsFormat::Dot, &actions);
+    assert!(dot.starts_with("digraph actions {"));
+    assert_eq!(dot.matches("  a1 [shape").count(), 1, "Shared operands are a single node: {}", dot);
+    assert!(dot.contains("a3 -> a1 [label=\"right\"];"));
+    assert!(dot.contains("a2 -> a0 [label=\"left\"];"));
+
+    assert!("graph".parse::<ActionsFormat>().is_err());
+    assert_eq!(DisplayActionTree(&[]).to_string(), "no actions\n");
+}
@@ -1,12 +1,15 @@
+use crate::{
+    display::{DisplayAccountAddress, DisplayAmount, DisplayContractAddress},
+    reject::RejectReason,
+};
 use anyhow::bail;
 #[cfg(feature = "fuzz")]
 use arbitrary::Arbitrary;
 use concordium_contracts_common::*;
-use derive_more::{AsRef, From, Into};
-use serde::Deserialize as SerdeDeserialize;
-use std::collections::LinkedList;
+use serde::{Deserialize as SerdeDeserialize, Serialize as SerdeSerialize};
+use std::{borrow::Cow, collections::BTreeMap};
 use wasm_transform::{
-    artifact::TryFromImport,
+    artifact::{HostInterface, TryFromImport},
     output::Output,
     parse::{Byte, GetParseable, Parseable},
     types::{FunctionType, Import, Name, ValueType},
@@ -23,7 +26,7 @@ pub type OwnedPolicyBytes = Vec<u8>;
 /// Chain context accessible to the init methods.
 ///
 /// TODO: We could optimize this to be initialized lazily
-#[derive(SerdeDeserialize, Debug, Clone)]
+#[derive(SerdeSerialize, SerdeDeserialize, Debug, Clone)]
 #[cfg_attr(feature = "fuzz", derive(Arbitrary))]
 #[serde(rename_all = "camelCase")]
 pub struct InitContext<Policies = Vec<OwnedPolicy>> {
@@ -47,7 +50,7 @@ impl<'a> From<InitContext<PolicyBytes<'a>>> for InitContext<OwnedPolicyBytes> {
 /// Chain context accessible to the receive methods.
 ///
 /// TODO: We could optimize this to be initialized lazily.
-#[derive(SerdeDeserialize, Debug, Clone)]
+#[derive(SerdeSerialize, SerdeDeserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 #[cfg_attr(feature = "fuzz", derive(Arbitrary))]
 pub struct ReceiveContext<Policies = Vec<OwnedPolicy>> {
@@ -102,7 +105,29 @@ impl<Policies> ReceiveContext<Policies> {
     pub fn self_address(&self) -> &ContractAddress { &self.self_address }
 }
 
-pub(crate) fn deserial_receive_context(source: &[u8]) -> ParseResult<ReceiveContext<&[u8]>> {
+/// Version of the serialization of contexts written by
+/// [serial_versioned_init_context] and [serial_versioned_receive_context].
+/// This must be changed whenever the canonical serialization changes.
+pub const CONTEXT_SERIALIZATION_VERSION: u8 = 0;
+
+/// Serialize the receive context in the canonical binary format. This is the
+/// format in which the node passes the context over FFI, and it is the inverse
+/// of [deserial_receive_context]. The sender policies are written as they are,
+/// and take up the remainder of the output.
+pub fn serial_receive_context<Policies: AsRef<[u8]>>(ctx: &ReceiveContext<Policies>) -> Vec<u8> {
+    let mut out = to_bytes(&ctx.metadata);
+    out.extend_from_slice(&to_bytes(&ctx.invoker));
+    out.extend_from_slice(&to_bytes(&ctx.self_address));
+    out.extend_from_slice(&to_bytes(&ctx.self_balance));
+    out.extend_from_slice(&to_bytes(&ctx.sender));
+    out.extend_from_slice(&to_bytes(&ctx.owner));
+    out.extend_from_slice(ctx.sender_policies.as_ref());
+    out
+}
+
+/// Deserialize the receive context from the canonical binary format, see
+/// [serial_receive_context].
+pub fn deserial_receive_context(source: &[u8]) -> ParseResult<ReceiveContext<&[u8]>> {
     let mut cursor = Cursor::new(source);
     let metadata = cursor.get()?;
     let invoker = cursor.get()?;
@@ -126,7 +151,20 @@ pub(crate) fn deserial_receive_context(source: &[u8]) -> ParseResult<ReceiveCont
     }
 }
 
-pub(crate) fn deserial_init_context(source: &[u8]) -> ParseResult<InitContext<&[u8]>> {
+/// Serialize the init context in the canonical binary format. This is the
+/// format in which the node passes the context over FFI, and it is the inverse
+/// of [deserial_init_context]. The sender policies are written as they are,
+/// and take up the remainder of the output.
+pub fn serial_init_context<Policies: AsRef<[u8]>>(ctx: &InitContext<Policies>) -> Vec<u8> {
+    let mut out = to_bytes(&ctx.metadata);
+    out.extend_from_slice(&to_bytes(&ctx.init_origin));
+    out.extend_from_slice(ctx.sender_policies.as_ref());
+    out
+}
+
+/// Deserialize the init context from the canonical binary format, see
+/// [serial_init_context].
+pub fn deserial_init_context(source: &[u8]) -> ParseResult<InitContext<&[u8]>> {
     let mut cursor = Cursor::new(source);
     let metadata = cursor.get()?;
     let init_origin = cursor.get()?;
@@ -142,22 +180,114 @@ pub(crate) fn deserial_init_context(source: &[u8]) -> ParseResult<InitContext<&[
     }
 }
 
-/// Smart contract state.
-#[derive(Clone, Debug, From, Into, AsRef)]
-pub struct State {
-    pub state: Vec<u8>,
+/// Serialize the receive context for storing, e.g., in a file. This is the
+/// canonical binary format prefixed with [CONTEXT_SERIALIZATION_VERSION].
+pub fn serial_versioned_receive_context<Policies: AsRef<[u8]>>(
+    ctx: &ReceiveContext<Policies>,
+) -> Vec<u8> {
+    let mut out = vec![CONTEXT_SERIALIZATION_VERSION];
+    out.extend_from_slice(&serial_receive_context(ctx));
+    out
+}
+
+/// Deserialize a receive context written by
+/// [serial_versioned_receive_context], failing if it was written with an
+/// unsupported version.
+pub fn deserial_versioned_receive_context(source: &[u8]) -> anyhow::Result<ReceiveContext<&[u8]>> {
+    let rest = check_context_version(source)?;
+    deserial_receive_context(rest).map_err(|_| anyhow::anyhow!("Malformed receive context."))
+}
+
+/// Serialize the init context for storing, e.g., in a file. This is the
+/// canonical binary format prefixed with [CONTEXT_SERIALIZATION_VERSION].
+pub fn serial_versioned_init_context<Policies: AsRef<[u8]>>(
+    ctx: &InitContext<Policies>,
+) -> Vec<u8> {
+    let mut out = vec![CONTEXT_SERIALIZATION_VERSION];
+    out.extend_from_slice(&serial_init_context(ctx));
+    out
+}
+
+/// Deserialize an init context written by [serial_versioned_init_context],
+/// failing if it was written with an unsupported version.
+pub fn deserial_versioned_init_context(source: &[u8]) -> anyhow::Result<InitContext<&[u8]>> {
+    let rest = check_context_version(source)?;
+    deserial_init_context(rest).map_err(|_| anyhow::anyhow!("Malformed init context."))
+}
+
+/// Check the version of a versioned context, and return the remaining bytes.
+pub(crate) fn check_context_version(source: &[u8]) -> anyhow::Result<&[u8]> {
+    match source.split_first() {
+        Some((&CONTEXT_SERIALIZATION_VERSION, rest)) => Ok(rest),
+        Some((version, _)) => bail!("Unsupported context version {}.", version),
+        None => bail!("Missing context version."),
+    }
+}
+
+/// Size of the pages in which [State] tracks modifications, in bytes.
+pub const STATE_PAGE_SIZE: usize = 256;
+
+/// Smart contract state. The state is copy-on-write. It shares the bytes it
+/// was constructed from, and only keeps copies of the pages of
+/// [STATE_PAGE_SIZE] bytes that were modified. The modifications can be
+/// extracted with [State::into_modified_regions].
+#[derive(Clone, Debug)]
+pub struct State<'a> {
+    /// The bytes the state was constructed from.
+    pub(crate) original:   Cow<'a, [u8]>,
+    /// Length of the prefix of the original bytes that is still part of the
+    /// state. Bytes after it that are not in a modified page are 0, since the
+    /// state was shrunk below them at some point.
+    pub(crate) shared_len: usize,
+    /// Copies of the modified pages, indexed by their number. Bytes of a page
+    /// that are past the end of the state are 0.
+    pub(crate) pages:      BTreeMap<usize, Box<[u8]>>,
+    /// The length of the state.
+    pub(crate) len:        usize,
+}
+
+/// Modifications of a [State] relative to the bytes it was constructed from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModifiedRegions {
+    /// The length of the modified state.
+    pub len:     u32,
+    /// Regions of the modified state, as pairs of an offset and the bytes at
+    /// the offset. The regions are in increasing order of offset and do not
+    /// overlap. They may include bytes that are unchanged.
+    pub regions: Vec<(u32, Vec<u8>)>,
+}
+
+impl ModifiedRegions {
+    /// Apply the modifications to the bytes the state was constructed from.
+    /// The original bytes are truncated, or extended with zeros, to the new
+    /// length, and then the regions are written.
+    pub fn apply(&self, original: &[u8]) -> Vec<u8> {
+        let len = self.len as usize;
+        let mut out = original[..std::cmp::min(len, original.len())].to_vec();
+        out.resize(len, 0u8);
+        for (offset, bytes) in self.regions.iter() {
+            let offset = *offset as usize;
+            out[offset..offset + bytes.len()].copy_from_slice(bytes);
+        }
+        out
+    }
 }
 
 #[derive(Clone, Debug, Default)]
 /// Structure to support logging of events from smart contracts.
 pub struct Logs {
-    pub logs: LinkedList<Vec<u8>>,
+    pub logs: Vec<Vec<u8>>,
+    /// Tags of the events in [logs](Self::logs) that were logged as typed
+    /// events, by their index. They are not part of the serialized logs,
+    /// where typed events are ordinary events that start with the tag, see
+    /// [events](crate::events).
+    pub tags: BTreeMap<usize, u32>,
 }
 
 #[derive(Debug)]
 pub enum InitResult {
     Success {
-        state:            State,
+        state:            State<'static>,
         logs:             Logs,
         remaining_energy: u64,
     },
@@ -169,6 +299,19 @@ pub enum InitResult {
 }
 
 impl InitResult {
+    /// The reason the contract rejected with, classified into reasons reserved
+    /// for contract libraries and reasons defined by the contract. Returns
+    /// [None] if the contract did not reject.
+    pub fn reject_reason(&self) -> Option<RejectReason> {
+        match self {
+            InitResult::Reject {
+                reason,
+                ..
+            } => Some(RejectReason::from(*reason)),
+            _ => None,
+        }
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         match self {
             InitResult::OutOfEnergy => vec![0],
@@ -187,11 +330,12 @@ impl InitResult {
                 logs,
                 remaining_energy,
             } => {
-                let mut out = Vec::with_capacity(5 + state.len() as usize + 8);
+                let mut out =
+                    Vec::with_capacity(5 + state.len() as usize + logs.serialized_size() + 8);
                 out.push(2);
-                out.extend_from_slice(&(state.len() as u32).to_be_bytes());
-                out.extend_from_slice(&state.state);
-                out.extend_from_slice(&logs.to_bytes());
+                out.extend_from_slice(&state.len().to_be_bytes());
+                state.write_to(&mut out);
+                logs.serialize_into(&mut out).expect("Serialization to a vector never fails.");
                 out.extend_from_slice(&remaining_energy.to_be_bytes());
                 out
             }
@@ -215,6 +359,30 @@ pub struct SimpleTransferAction {
     pub amount:  u64,            // 8 bytes
 }
 
+impl std::fmt::Display for SendAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "send {} to {}, entrypoint '{}', with a parameter of {} bytes",
+            DisplayAmount(self.amount),
+            DisplayContractAddress(&self.to_addr),
+            String::from_utf8_lossy(&self.name),
+            self.parameter.len()
+        )
+    }
+}
+
+impl std::fmt::Display for SimpleTransferAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "transfer {} to {}",
+            DisplayAmount(self.amount),
+            DisplayAccountAddress(&self.to_addr)
+        )
+    }
+}
+
 /// Actions produced by running a receive function.
 /// NB: The first two variants are deliberately using an Rc as opposed to just
 /// inlining the SendAction/SimpleTransferAction. The reason for this is that
@@ -243,6 +411,30 @@ pub enum Action {
     Accept,
 }
 
+/// Display an action. The operands of [Action::And] and [Action::Or] are
+/// displayed as the indices of the actions they combine.
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Action::Send {
+                data,
+            } => data.fmt(f),
+            Action::SimpleTransfer {
+                data,
+            } => data.fmt(f),
+            Action::And {
+                l,
+                r,
+            } => write!(f, "{} and then {}", l, r),
+            Action::Or {
+                l,
+                r,
+            } => write!(f, "{} or else {}", l, r),
+            Action::Accept => "accept".fmt(f),
+        }
+    }
+}
+
 /// This is not implementing serialize because that is currently set-up for
 /// little-endian only, and we need big-endian for interoperability with the
 /// rest of the system.
@@ -301,9 +493,9 @@ impl Action {
 }
 
 #[derive(Debug)]
-pub enum ReceiveResult {
+pub enum ReceiveResult<'a> {
     Success {
-        state:            State,
+        state:            State<'a>,
         logs:             Logs,
         actions:          Vec<Action>,
         remaining_energy: u64,
@@ -315,7 +507,20 @@ pub enum ReceiveResult {
     OutOfEnergy,
 }
 
-impl ReceiveResult {
+impl<'a> ReceiveResult<'a> {
+    /// The reason the contract rejected with, classified into reasons reserved
+    /// for contract libraries and reasons defined by the contract. Returns
+    /// [None] if the contract did not reject.
+    pub fn reject_reason(&self) -> Option<RejectReason> {
+        match self {
+            ReceiveResult::Reject {
+                reason,
+                ..
+            } => Some(RejectReason::from(*reason)),
+            _ => None,
+        }
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         use ReceiveResult::*;
         match self {
@@ -336,11 +541,12 @@ impl ReceiveResult {
                 actions,
                 remaining_energy,
             } => {
-                let mut out = vec![2];
-                let state = &state.state;
-                out.extend_from_slice(&(state.len() as u32).to_be_bytes());
-                out.extend_from_slice(state);
-                out.extend_from_slice(&logs.to_bytes());
+                let mut out =
+                    Vec::with_capacity(5 + state.len() as usize + logs.serialized_size() + 12);
+                out.push(2);
+                out.extend_from_slice(&state.len().to_be_bytes());
+                state.write_to(&mut out);
+                logs.serialize_into(&mut out).expect("Serialization to a vector never fails.");
                 out.extend_from_slice(&(actions.len() as u32).to_be_bytes());
                 for a in actions.iter() {
                     out.extend_from_slice(&a.to_bytes());
@@ -486,6 +692,12 @@ pub struct ProcessedImports {
     ty:             FunctionType,
 }
 
+/// Display the host function the import resolves to, e.g., in the
+/// [disassembly](wasm_transform::artifact::disassemble) of artifacts.
+impl std::fmt::Display for ProcessedImports {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result { write!(f, "{:?}", self.tag) }
+}
+
 impl<'a, Ctx: Copy> Parseable<'a, Ctx> for ProcessedImports {
     fn parse(
         ctx: Ctx,
@@ -507,6 +719,12 @@ impl Output for ProcessedImports {
     }
 }
 
+/// The host functions of V0 contracts have not changed since artifacts were
+/// versioned.
+impl HostInterface for ProcessedImports {
+    const HOST_INTERFACE_VERSION: u32 = 0;
+}
+
 pub struct ConcordiumAllowedImports;
 
 impl validate::ValidateImportExport for ConcordiumAllowedImports {
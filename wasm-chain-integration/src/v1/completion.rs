@@ -0,0 +1,162 @@
+//! Running receive functions to completion, handling the interrupts of
+//! execution with a user-supplied handler. This is the loop of invoking a
+//! receive function, and resuming it with the response to each [Interrupt]
+//! until it terminates, which embedders such as the scheduler of the node,
+//! tests, and simulations otherwise implement themselves.
+//!
+//! The handler is given each interrupt together with the state of the
+//! contract, which it may modify, e.g., when it executes a call to the
+//! contract itself. A checkpoint of the state is created before calls are
+//! handled, and if the call fails the state is rolled back to it, so that
+//! the contract is resumed in the state it had at the time of the call.
+use super::{
+    trie::{self, BackingStoreLoad},
+    HasReceiveContext, InstanceState, Interrupt, InvokeOptions, InvokeResponse, ParameterRef,
+    ProcessedImports, ReceiveContext, ReceiveResult,
+};
+use crate::{v0, InterpreterEnergy};
+use concordium_contracts_common::ReceiveName;
+use std::sync::Arc;
+use wasm_transform::artifact::{Artifact, CompiledFunction};
+
+/// An interrupt of execution, passed to the handler of [run_to_completion].
+pub struct Interruption<'a> {
+    /// The operation that needs to be handled.
+    pub interrupt:        Interrupt,
+    /// Logs produced since the last interrupt (or beginning of execution).
+    pub logs:             v0::Logs,
+    /// Whether the state has changed since the last interrupt (or beginning
+    /// of execution).
+    pub state_changed:    bool,
+    /// Remaining interpreter energy.
+    pub remaining_energy: u64,
+    /// The state of the contract.
+    pub state:            &'a mut trie::MutableState,
+}
+
+/// The response of a handler to an [Interruption].
+pub enum HandlerResponse {
+    /// Resume execution with the given response, after charging the given
+    /// amount of interpreter energy for the operation.
+    Resume {
+        response:    InvokeResponse,
+        energy_used: u64,
+    },
+    /// Stop execution, e.g., because the handler does not support the
+    /// interrupt.
+    Abort(anyhow::Error),
+}
+
+/// Errors of [run_to_completion].
+#[derive(Debug, thiserror::Error)]
+pub enum CompletionError {
+    #[error("Execution failed: {0:#}")]
+    Execution(anyhow::Error),
+    #[error("The handler aborted execution: {0:#}")]
+    Aborted(anyhow::Error),
+    #[error("Could not roll back the state after a failed call: {0:#}")]
+    Rollback(anyhow::Error),
+}
+
+/// Invoke a receive function and resume it after each interrupt with the
+/// response of the handler until execution terminates. The result is never
+/// [ReceiveResult::Interrupt]. The logs of a successful execution are only
+/// those produced after the last interrupt, the earlier ones are passed to the
+/// handler.
+///
+/// Execution runs out of energy if the handler uses more energy than remains.
+/// If it fails in any way the state is left as it is, and should be discarded
+/// by the caller.
+#[allow(clippy::too_many_arguments)]
+pub fn run_to_completion<BackingStore, Ctx>(
+    artifact: Arc<Artifact<ProcessedImports, CompiledFunction>>,
+    amount: u64,
+    receive_ctx: Ctx,
+    receive_name: ReceiveName,
+    parameter: ParameterRef,
+    energy: InterpreterEnergy,
+    state: &mut trie::MutableState,
+    mut loader: BackingStore,
+    mut handler: impl FnMut(Interruption<'_>) -> HandlerResponse,
+) -> Result<ReceiveResult<CompiledFunction>, CompletionError>
+where
+    BackingStore: BackingStoreLoad + Clone,
+    Ctx: HasReceiveContext,
+    ReceiveContext<v0::OwnedPolicyBytes>: From<Ctx>, {
+    let inner = state.get_inner(&mut loader);
+    let instance_state = InstanceState::new(0, loader.clone(), inner);
+    let mut result = super::invoke_receive(
+        artifact,
+        amount,
+        receive_ctx,
+        receive_name,
+        parameter,
+        energy,
+        instance_state,
+        InvokeOptions::default(),
+    )
+    .map_err(CompletionError::Execution)?;
+    loop {
+        let (config, remaining_energy, checkpoint, response) = match result {
+            ReceiveResult::Interrupt {
+                remaining_energy,
+                state_changed,
+                logs,
+                config,
+                interrupt,
+                ..
+            } => {
+                // Only calls can execute code that modifies the state.
+                let checkpoint = match interrupt {
+                    Interrupt::Call {
+                        ..
+                    } => Some(state.checkpoint(&mut loader)),
+                    _ => None,
+                };
+                let response = handler(Interruption {
+                    interrupt,
+                    logs,
+                    state_changed,
+                    remaining_energy,
+                    state,
+                });
+                (config, remaining_energy, checkpoint, response)
+            }
+            other => return Ok(other),
+        };
+        let (response, energy_used) = match response {
+            HandlerResponse::Resume {
+                response,
+                energy_used,
+            } => (response, energy_used),
+            HandlerResponse::Abort(e) => return Err(CompletionError::Aborted(e)),
+        };
+        if energy_used > remaining_energy {
+            return Ok(ReceiveResult::OutOfEnergy);
+        }
+        let state_updated = match &response {
+            InvokeResponse::Success {
+                state_updated,
+                ..
+            } => *state_updated,
+            InvokeResponse::Failure {
+                ..
+            } => {
+                if let Some(checkpoint) = checkpoint {
+                    state.rollback_to(checkpoint).map_err(CompletionError::Rollback)?;
+                }
+                false
+            }
+        };
+        result = super::resume_receive(
+            config,
+            response,
+            InterpreterEnergy::from(remaining_energy - energy_used),
+            state,
+            state_updated,
+            loader.clone(),
+            InvokeOptions::default(),
+        )
+        .map_err(CompletionError::Execution)?;
+    }
+}
@@ -7,8 +7,8 @@ use crate::{
         trie::{
             self, low_level::MutableTrie, EmptyCollector, Loader, MutableState, PersistentState,
         },
-        ConcordiumAllowedImports, InstanceState, ProcessedImports, ReceiveContext, ReceiveHost,
-        StateLessReceiveHost,
+        ConcordiumAllowedImports, InstanceState, ParameterCursors, ProcessedImports,
+        ReceiveContext, ReceiveHost, StateLessReceiveHost,
     },
     InterpreterEnergy,
 };
@@ -45,6 +45,22 @@ where
     }
 }
 
+/// Sign the message with the given secret key using the same scheme as
+/// `bls_verify`, i.e., signatures in G1 and public keys in G2.
+fn bls_sign(sk: bls12_381::Scalar, message: &[u8]) -> [u8; 48] {
+    use bls12_381::hash_to_curve::{ExpandMsgXmd, HashToCurve};
+    let hashed =
+        <bls12_381::G1Projective as HashToCurve<ExpandMsgXmd<sha2_09::Sha256>>>::hash_to_curve(
+            message,
+            b"BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_POP_",
+        );
+    bls12_381::G1Affine::from(hashed * sk).to_compressed()
+}
+
+fn bls_public_key(sk: bls12_381::Scalar) -> [u8; 96] {
+    bls12_381::G2Affine::from(bls12_381::G2Affine::generator() * sk).to_compressed()
+}
+
 #[test]
 fn test_crypto_prims() -> anyhow::Result<()> {
     let nrg = 1_000_000_000;
@@ -55,7 +71,12 @@ fn test_crypto_prims() -> anyhow::Result<()> {
 
     let skeleton = parse::parse_skeleton(CONTRACT_BYTES).unwrap();
     let module = {
-        let mut module = validate::validate_module(&ConcordiumAllowedImports, &skeleton).unwrap();
+        let mut module = validate::validate_module(
+            &validate::ValidationConfig::LEGACY,
+            &ConcordiumAllowedImports::LATEST,
+            &skeleton,
+        )
+        .unwrap();
         module.inject_metering().expect("Metering injection should succeed.");
         module
     };
@@ -113,6 +134,8 @@ fn test_crypto_prims() -> anyhow::Result<()> {
                 receive_ctx,
                 return_value: Vec::new(),
                 parameters,
+                parameter_cursors: ParameterCursors::default(),
+                random_counter: 0,
             },
             state,
         };
@@ -313,6 +336,55 @@ fn test_crypto_prims() -> anyhow::Result<()> {
         );
     }
 
+    {
+        // ecdsa verification has a fixed message length
+        use p256::ecdsa::{signature::hazmat::PrehashSigner, Signature, SigningKey, VerifyingKey};
+        let name = "hostfn.verify_ecdsa_secp256r1_signature";
+        let sk = SigningKey::from_bytes(&[7u8; 32]).expect("Valid secret key.");
+        let other_sk = SigningKey::from_bytes(&[8u8; 32]).expect("Valid secret key.");
+        let public_key = |sk: &SigningKey| VerifyingKey::from(sk).to_encoded_point(true);
+        let message = sha2::Sha256::digest(&[]);
+        let sig: Signature = sk.sign_prehash(&message).expect("Signing succeeds.");
+        // Signing does not necessarily produce a low s.
+        let sig = sig.normalize_s().unwrap_or(sig);
+        // The same signature with s replaced by n - s.
+        let high_s_sig = Signature::from_scalars(sig.r(), -*sig.s()).expect("Valid signature.");
+        let mk_params = |pk: &[u8], sig: &[u8], message: &[u8]| {
+            let mut params = Vec::with_capacity(129);
+            params.extend_from_slice(pk);
+            params.extend_from_slice(sig);
+            params.extend_from_slice(message);
+            params
+        };
+        let pk = public_key(&sk);
+        let other_pk = public_key(&other_sk);
+        let incorrect_message = sha2::Sha256::digest(&[0]);
+        // (public key, signature, message, expected result)
+        let cases = [
+            (pk.as_bytes(), sig.to_vec(), &message[..], 1u8),
+            // signature with a high s
+            (pk.as_bytes(), high_s_sig.to_vec(), &message[..], 0),
+            // incorrect message
+            (pk.as_bytes(), sig.to_vec(), &incorrect_message[..], 0),
+            // non-matching public key
+            (other_pk.as_bytes(), sig.to_vec(), &message[..], 0),
+            // public key that is not a point on the curve
+            (&[0x02u8; 33][..], sig.to_vec(), &message[..], 0),
+            // signature with r = 0
+            (pk.as_bytes(), [&[0u8; 32][..], &sig.to_vec()[32..]].concat(), &message[..], 0),
+        ];
+        for (i, (pk, sig, message, expected)) in cases.iter().enumerate() {
+            let rv = test_crypto_primitive(name, mk_params(pk, sig, message));
+            anyhow::ensure!(
+                rv[..] == [*expected, 0, 0, 0],
+                "Incorrect verification result for {}, case {}, got {:?}.",
+                name,
+                i + 1,
+                rv
+            );
+        }
+    }
+
     {
         // n is the length of the data to be hashed
         for n in [0u32, 10, 20, 50, 100, 1000, 10_000, 100_000] {
@@ -346,5 +418,149 @@ fn test_crypto_prims() -> anyhow::Result<()> {
         }
     }
 
+    {
+        let name = "hostfn.bls_verify";
+        let sk = bls12_381::Scalar::from(123_456_789u64);
+        let mk_params = |pk: &[u8], sig: &[u8], len: u32| {
+            let mut params = Vec::with_capacity(148);
+            params.extend_from_slice(pk);
+            params.extend_from_slice(sig);
+            params.extend_from_slice(&len.to_le_bytes());
+            params
+        };
+        // (public key, signature, message length, expected result)
+        let cases = [
+            (bls_public_key(sk), bls_sign(sk, &[0u8; 17]), 17, 1u8),
+            // incorrect message
+            (bls_public_key(sk), bls_sign(sk, &[]), 17, 0),
+            // incorrect public key
+            (bls_public_key(sk + bls12_381::Scalar::one()), bls_sign(sk, &[0u8; 17]), 17, 0),
+            // public key that is not a point on the curve
+            ([0xffu8; 96], bls_sign(sk, &[0u8; 17]), 17, 0),
+            // the identity key and signature verify any message and are rejected
+            (
+                bls12_381::G2Affine::identity().to_compressed(),
+                bls12_381::G1Affine::identity().to_compressed(),
+                17,
+                0,
+            ),
+        ];
+        for (i, (pk, sig, len, expected)) in cases.iter().enumerate() {
+            let rv = test_crypto_primitive(name, mk_params(pk, sig, *len));
+            anyhow::ensure!(
+                rv[..] == [*expected, 0, 0, 0],
+                "Incorrect verification result for {}, case {}, got {:?}.",
+                name,
+                i + 1,
+                rv
+            );
+        }
+    }
+
+    {
+        let name = "hostfn.bls_aggregate_verify";
+        let sks: Vec<_> = (1..=5u64).map(bls12_381::Scalar::from).collect();
+        let cancelling_sks = [sks[0], -sks[0]];
+        let message = [0u8; 100];
+        let aggregate_signature = |sks: &[bls12_381::Scalar]| {
+            let sum = sks.iter().fold(bls12_381::G1Projective::identity(), |acc, sk| {
+                acc + bls12_381::G1Affine::from_compressed(&bls_sign(*sk, &message)).unwrap()
+            });
+            bls12_381::G1Affine::from(sum).to_compressed()
+        };
+        let mk_params = |sig: &[u8], keys: &[bls12_381::Scalar]| {
+            let mut params = Vec::new();
+            params.extend_from_slice(sig);
+            params.extend_from_slice(&(keys.len() as u32).to_le_bytes());
+            params.extend_from_slice(&(message.len() as u32).to_le_bytes());
+            for sk in keys {
+                params.extend_from_slice(&bls_public_key(*sk));
+            }
+            params
+        };
+        // (signature, keys, expected result)
+        let cases = [
+            (aggregate_signature(&sks), &sks[..], 1u8),
+            (aggregate_signature(&sks[..1]), &sks[..1], 1),
+            // a signer is missing from the signature
+            (aggregate_signature(&sks[1..]), &sks[..], 0),
+            // a signer is missing from the keys
+            (aggregate_signature(&sks), &sks[1..], 0),
+            // no keys
+            (bls12_381::G1Affine::identity().to_compressed(), &[][..], 0),
+            // keys that aggregate to the identity with the identity signature
+            (bls12_381::G1Affine::identity().to_compressed(), &cancelling_sks[..], 0),
+        ];
+        for (i, (sig, keys, expected)) in cases.iter().enumerate() {
+            let rv = test_crypto_primitive(name, mk_params(sig, keys));
+            anyhow::ensure!(
+                rv[..] == [*expected, 0, 0, 0],
+                "Incorrect verification result for {}, case {}, got {:?}.",
+                name,
+                i + 1,
+                rv
+            );
+        }
+    }
+
+    {
+        let a = bls12_381::Scalar::from(17u64);
+        let b = bls12_381::Scalar::from(42u64);
+        let g1 = |x: bls12_381::Scalar| {
+            bls12_381::G1Affine::from(bls12_381::G1Affine::generator() * x).to_compressed()
+        };
+        let g2 = |x: bls12_381::Scalar| {
+            bls12_381::G2Affine::from(bls12_381::G2Affine::generator() * x).to_compressed()
+        };
+        // (entrypoint, parameter, expected output)
+        let cases = [
+            (
+                "hostfn.bls_g1_add",
+                [g1(a), g1(b)].concat(),
+                [&[1, 0, 0, 0][..], &g1(a + b)].concat(),
+            ),
+            (
+                "hostfn.bls_g1_mul",
+                [&g1(a)[..], &b.to_bytes()].concat(),
+                [&[1, 0, 0, 0][..], &g1(a * b)].concat(),
+            ),
+            (
+                "hostfn.bls_g2_add",
+                [g2(a), g2(b)].concat(),
+                [&[1, 0, 0, 0][..], &g2(a + b)].concat(),
+            ),
+            (
+                "hostfn.bls_g2_mul",
+                [&g2(a)[..], &b.to_bytes()].concat(),
+                [&[1, 0, 0, 0][..], &g2(a * b)].concat(),
+            ),
+            // invalid points and scalars leave the output unchanged.
+            (
+                "hostfn.bls_g1_add",
+                [[0xffu8; 48], g1(b)].concat(),
+                [&[0, 0, 0, 0][..], &[0xffu8; 48]].concat(),
+            ),
+            (
+                "hostfn.bls_g1_mul",
+                [&g1(a)[..], &[0xffu8; 32]].concat(),
+                [&[0, 0, 0, 0][..], &g1(a)].concat(),
+            ),
+            (
+                "hostfn.bls_g2_add",
+                [g2(a), [0xffu8; 96]].concat(),
+                [&[0, 0, 0, 0][..], &g2(a)].concat(),
+            ),
+            (
+                "hostfn.bls_g2_mul",
+                [&[0xffu8; 96][..], &b.to_bytes()].concat(),
+                [&[0, 0, 0, 0][..], &[0xffu8; 96]].concat(),
+            ),
+        ];
+        for (name, params, expected) in cases.iter() {
+            let rv = test_crypto_primitive(name, params.clone());
+            anyhow::ensure!(rv == *expected, "Incorrect result for {}, got {:?}.", name, rv);
+        }
+    }
+
     Ok(())
 }
@@ -0,0 +1,280 @@
+//! Injection of host function failures into the execution of V1 contracts.
+//! This is intended for simulating contracts during development, so that the
+//! paths handling failures of host functions can be tested, e.g., an `invoke`
+//! of a particular contract failing, or a write to the state failing, which
+//! are otherwise hard to trigger. It must never be used on the chain.
+//!
+//! A [FaultPlan] lists the host functions that should fail, how often, and
+//! the value they return instead. It can be read from JSON, e.g.,
+//!
+//! ```json
+//! {
+//!   "faults": [
+//!     { "function": "state_entry_write", "every": 3, "result": 4294967295 },
+//!     {
+//!       "function": "invoke",
+//!       "target": { "contract": { "index": 5, "subindex": 0 } },
+//!       "result": 12884901888
+//!     }
+//!   ],
+//!   "denied": ["get_slot_time"]
+//! }
+//! ```
+//!
+//! Calls to the host functions listed as `denied`, which is an
+//! [ImportPolicy], make execution trap with a [DeniedHostFunction] error. This
+//! allows checking that a contract does not use these functions on the paths
+//! that are executed, even if it imports them.
+//!
+//! A host function that fails does not have any effect, and its arguments are
+//! consumed as usual. Functions that produce an [Interrupt], i.e., `invoke`,
+//! the queries, the conversions between energy and microCCD, and `upgrade`,
+//! are executed up to the interrupt, so they are charged for as usual, and the
+//! interrupt is then replaced by the failure.
+use super::{
+    import_policy::{DeniedHostFunction, ImportPolicy},
+    ImportFunc, Interrupt, ProcessedImports, ReceiveOnlyFunc,
+};
+use crate::ExecResult;
+use anyhow::{bail, ensure};
+use concordium_contracts_common::{AccountAddress, ContractAddress};
+use serde::{Deserialize as SerdeDeserialize, Serialize as SerdeSerialize};
+use wasm_transform::{artifact::TryFromImport, machine, types::ValueType};
+
+/// The host functions that fail during execution, see the
+/// [module documentation](self).
+#[derive(SerdeSerialize, SerdeDeserialize, Debug, Clone, Default)]
+pub struct FaultPlan {
+    pub faults: Vec<Fault>,
+    /// Host functions whose calls make execution trap.
+    #[serde(default)]
+    pub denied: ImportPolicy,
+}
+
+/// A host function that fails.
+#[derive(SerdeSerialize, SerdeDeserialize, Debug, Clone)]
+pub struct Fault {
+    /// The name the host function is imported with, e.g., `state_entry_write`.
+    pub function: String,
+    /// Fail every `every`-th call of the function that matches the target,
+    /// starting with the `every`-th one. Defaults to 1, i.e., every call
+    /// fails.
+    #[serde(default = "default_every")]
+    pub every:    u32,
+    /// Only fail calls that interrupt execution with the given target. If not
+    /// present all calls fail.
+    #[serde(default)]
+    pub target:   Option<FaultTarget>,
+    /// The value the host function returns instead, which is truncated to 32
+    /// bits for functions that return an `i32`. For `invoke` this is the
+    /// response as decoded by
+    /// [decode_invoke_response](super::decode_invoke_response),
+    /// e.g., `0x03_0000_0000` indicates that the contract does not exist.
+    pub result:   u64,
+}
+
+fn default_every() -> u32 { 1 }
+
+/// The target of an interrupt.
+#[derive(SerdeSerialize, SerdeDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum FaultTarget {
+    /// Transfers to, and balance queries of, the account.
+    Account(AccountAddress),
+    /// Calls to, and queries of, the contract.
+    Contract(ContractAddress),
+}
+
+/// Interrupts whose target can be matched by a [FaultTarget].
+pub trait HasFaultTarget {
+    fn has_target(&self, target: &FaultTarget) -> bool;
+}
+
+impl HasFaultTarget for Interrupt {
+    fn has_target(&self, target: &FaultTarget) -> bool {
+        match (self, target) {
+            (
+                Interrupt::Transfer {
+                    to,
+                    ..
+                },
+                FaultTarget::Account(account),
+            ) => to == account,
+            (
+                Interrupt::QueryAccountBalance {
+                    address,
+                },
+                FaultTarget::Account(account),
+            ) => address == account,
+            (
+                Interrupt::Call {
+                    address,
+                    ..
+                },
+                FaultTarget::Contract(contract),
+            ) => address == contract,
+            (
+                Interrupt::QueryContract {
+                    address,
+                    ..
+                },
+                FaultTarget::Contract(contract),
+            ) => address == contract,
+            _ => false,
+        }
+    }
+}
+
+/// The state of a [FaultPlan] during execution. The same injector should be
+/// used when execution is resumed after an interrupt, so that calls are
+/// counted across the whole execution.
+#[derive(Debug, Clone)]
+pub struct FaultInjector {
+    plan:     FaultPlan,
+    /// The number of calls that matched each fault of the plan.
+    calls:    Vec<u32>,
+    /// The number of failures injected so far.
+    injected: u64,
+}
+
+impl FaultInjector {
+    /// Construct an injector, checking that the plan is well-formed.
+    pub fn new(plan: FaultPlan) -> ExecResult<Self> {
+        for fault in plan.faults.iter() {
+            ensure!(
+                fault.every > 0,
+                "The fault of {} must have `every` at least 1.",
+                fault.function
+            );
+            if fault.target.is_some() {
+                match fault.function.as_str() {
+                    "invoke"
+                    | "contract_exists"
+                    | "contract_state_size"
+                    | "get_account_balance" => {}
+                    name => bail!("The fault of {} cannot have a target.", name),
+                }
+            }
+        }
+        Ok(Self {
+            calls: vec![0; plan.faults.len()],
+            plan,
+            injected: 0,
+        })
+    }
+
+    /// The number of failures injected so far.
+    pub fn num_injected(&self) -> u64 { self.injected }
+
+    /// Count a call of the named function, and return the value the function
+    /// should return if it fails. If the call interrupted execution the
+    /// interrupt is matched against the targets of the faults.
+    fn check(&mut self, name: &str, interrupt: Option<&impl HasFaultTarget>) -> Option<u64> {
+        let mut result = None;
+        for (fault, calls) in self.plan.faults.iter().zip(self.calls.iter_mut()) {
+            if fault.function != name {
+                continue;
+            }
+            if let Some(target) = &fault.target {
+                if !interrupt.map_or(false, |i| i.has_target(target)) {
+                    continue;
+                }
+            }
+            *calls += 1;
+            if result.is_none() && *calls % fault.every == 0 {
+                result = Some(fault.result);
+            }
+        }
+        if result.is_some() {
+            self.injected += 1;
+        }
+        result
+    }
+}
+
+/// A host that makes calls fail according to a [FaultInjector], and otherwise
+/// delegates to the given host.
+pub struct FaultInjectingHost<'a, H> {
+    pub host:   &'a mut H,
+    pub faults: &'a mut FaultInjector,
+}
+
+impl<'a, H: machine::Host<ProcessedImports>> machine::Host<ProcessedImports>
+    for FaultInjectingHost<'a, H>
+where
+    H::Interrupt: HasFaultTarget,
+{
+    type Interrupt = H::Interrupt;
+
+    fn tick_initial_memory(&mut self, num_pages: u32) -> machine::RunResult<()> {
+        self.host.tick_initial_memory(num_pages)
+    }
+
+    fn tick_memory_grow(&mut self, current_pages: u32, num_pages: u32) -> machine::RunResult<()> {
+        self.host.tick_memory_grow(current_pages, num_pages)
+    }
+
+    fn call(
+        &mut self,
+        f: &ProcessedImports,
+        memory: &mut Vec<u8>,
+        stack: &mut machine::RuntimeStack,
+    ) -> machine::RunResult<Option<Self::Interrupt>> {
+        let interrupts = match f.tag {
+            // The accounting functions are not called by the contract itself.
+            ImportFunc::ChargeEnergy
+            | ImportFunc::TrackCall
+            | ImportFunc::TrackReturn
+            | ImportFunc::ChargeMemoryAlloc => return self.host.call(f, memory, stack),
+            ImportFunc::ReceiveOnly(rof) => matches!(
+                rof,
+                ReceiveOnlyFunc::Invoke
+                    | ReceiveOnlyFunc::ContractExists
+                    | ReceiveOnlyFunc::ContractStateSize
+                    | ReceiveOnlyFunc::GetAccountBalance
+                    | ReceiveOnlyFunc::Upgrade
+                    | ReceiveOnlyFunc::EnergyToMicroCcd
+                    | ReceiveOnlyFunc::MicroCcdToEnergy
+                    | ReceiveOnlyFunc::MicroEuroToMicroCcd
+                    | ReceiveOnlyFunc::MicroCcdToMicroEuro
+            ),
+            _ => false,
+        };
+        let name = f.tag.name();
+        if self.faults.plan.denied.denies(name) {
+            bail!(DeniedHostFunction {
+                name,
+            })
+        }
+        let ty = f.ty();
+        if interrupts {
+            // These are executed so that the interrupt can be matched against
+            // the targets of the faults.
+            let interrupt = self.host.call(f, memory, stack)?;
+            if let Some(interrupt) = &interrupt {
+                if let Some(result) = self.faults.check(name, Some(interrupt)) {
+                    push_result(stack, ty.result, result);
+                    return Ok(None);
+                }
+            }
+            Ok(interrupt)
+        } else if let Some(result) = self.faults.check(name, None::<&H::Interrupt>) {
+            for _ in ty.parameters.iter() {
+                stack.pop();
+            }
+            push_result(stack, ty.result, result);
+            Ok(None)
+        } else {
+            self.host.call(f, memory, stack)
+        }
+    }
+}
+
+/// Replace the result of the host function by the given value.
+fn push_result(stack: &mut machine::RuntimeStack, result: Option<ValueType>, value: u64) {
+    match result {
+        Some(ValueType::I32) => stack.push_value(value as u32),
+        Some(ValueType::I64) => stack.push_value(value),
+        None => {}
+    }
+}
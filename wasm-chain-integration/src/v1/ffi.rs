@@ -17,6 +17,7 @@
 use super::trie::{
     foreign::{LoadCallback, StoreCallback},
     EmptyCollector, Loadable, MutableState, PersistentState, Reference, SizeCollector,
+    StateCheckpoint,
 };
 use crate::{slice_from_c_bytes, v1::*};
 use concordium_contracts_common::OwnedReceiveName;
@@ -25,6 +26,7 @@ use sha2::Digest;
 use std::sync::Arc;
 use wasm_transform::{
     artifact::{CompiledFunction, OwnedArtifact},
+    compression,
     output::Output,
     utils::parse_artifact,
 };
@@ -72,6 +74,79 @@ type ReturnValue = Vec<u8>;
 /// reason.
 type ReceiveInterruptedStateV1 = ReceiveInterruptedState<CompiledFunction>;
 
+/// Interrupted state of execution of an init function. This is exchanged with
+/// foreign code in the same way as [ReceiveInterruptedStateV1], except that it
+/// is first allocated in [call_init_v1], resumed with [resume_init_v1], and
+/// must be deallocated with [init_interrupted_state_free].
+type InitInterruptedStateV1 = InitInterruptedState<CompiledFunction>;
+
+/// A callback that allocates a buffer of the given length in memory managed by
+/// foreign code, and returns a pointer to it. This is used by the
+/// `*_with_alloc` variants of the execution functions to write the results of
+/// execution directly into foreign memory, so that foreign code does not have
+/// to copy them out of buffers allocated in Rust and free those afterwards. The
+/// callback must return a valid, non-null, pointer for every length, including
+/// 0.
+pub type AllocCallback = extern "C" fn(len: size_t) -> *mut u8;
+
+/// Hand over the status buffer produced by execution to foreign code, storing
+/// its length in `output_len`. The buffer must be deallocated with
+/// `rs_free_array_len`.
+unsafe fn output_status(mut status: Vec<u8>, output_len: *mut size_t) -> *mut u8 {
+    status.shrink_to_fit();
+    *output_len = status.len() as size_t;
+    let ptr = status.as_mut_ptr();
+    std::mem::forget(status);
+    ptr
+}
+
+/// Copy the bytes into a buffer allocated with the callback, storing their
+/// length in `output_len`, and return a pointer to the buffer.
+unsafe fn copy_to_alloc(alloc: AllocCallback, bytes: &[u8], output_len: *mut size_t) -> *mut u8 {
+    let ptr = alloc(bytes.len() as size_t);
+    if !bytes.is_empty() {
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr, bytes.len());
+    }
+    *output_len = bytes.len() as size_t;
+    ptr
+}
+
+/// The seed of the randomness of an execution, given to the `*_with_alloc`
+/// execution functions as a pointer to the 32 bytes of the block seed, or a
+/// null pointer if there is none, and the nonce of the invocation. See
+/// [RandomSeed](v0::RandomSeed) for the requirements on the nonce.
+unsafe fn random_seed_from_c(random_seed: *const u8, random_nonce: u64) -> Option<v0::RandomSeed> {
+    if random_seed.is_null() {
+        return None;
+    }
+    let mut block_seed = [0u8; 32];
+    block_seed.copy_from_slice(std::slice::from_raw_parts(random_seed, 32));
+    Some(v0::RandomSeed {
+        block_seed,
+        nonce: random_nonce,
+    })
+}
+
+/// Write the status buffer and return value produced by execution to buffers
+/// allocated with the callback. The return value is written to
+/// `output_return_value` and `output_return_value_len` if it is present, and
+/// otherwise `output_return_value` is set to a null pointer.
+unsafe fn output_with_alloc(
+    alloc: AllocCallback,
+    status: Vec<u8>,
+    return_value: Option<ReturnValue>,
+    output_len: *mut size_t,
+    output_return_value: *mut *mut u8,
+    output_return_value_len: *mut size_t,
+) -> *mut u8 {
+    if let Some(return_value) = return_value {
+        *output_return_value = copy_to_alloc(alloc, &return_value, output_return_value_len);
+    } else {
+        *output_return_value = std::ptr::null_mut();
+    }
+    copy_to_alloc(alloc, &status, output_len)
+}
+
 /// Invoke an init function creating the contract instance.
 /// # Safety
 /// This function is safe provided the following preconditions hold
@@ -97,6 +172,10 @@ type ReceiveInterruptedStateV1 = ReceiveInterruptedState<CompiledFunction>;
 ///   [box_vec_u8_free] otherwise memory will be leaked.
 /// In case of execution failure, a panic, or failure to parse a null pointer is
 /// returned.
+///
+/// The init function is executed with the default [HostFeatures], so it is
+/// never interrupted. Use [call_init_v1_with_alloc] to enable
+/// [init_interrupts](HostFeatures::init_interrupts).
 #[no_mangle]
 unsafe extern "C" fn call_init_v1(
     // Operationally this is not really needed since nothing is loaded, since a fresh empty state
@@ -115,44 +194,162 @@ unsafe extern "C" fn call_init_v1(
     output_return_value: *mut *mut ReturnValue,
     output_len: *mut size_t,
     output_state_ptr: *mut *mut MutableState,
+) -> *mut u8 {
+    // Without init interrupts there is never an interrupted state to return.
+    let mut output_config = std::ptr::null_mut();
+    call_init_v1_with(
+        loader,
+        artifact_ptr,
+        init_ctx_bytes,
+        init_ctx_bytes_len,
+        amount,
+        init_name,
+        init_name_len,
+        param_bytes,
+        param_bytes_len,
+        energy,
+        HostFeatures::default(),
+        None,
+        output_state_ptr,
+        &mut output_config,
+        |status, return_value| {
+            if let Some(return_value) = return_value {
+                *output_return_value = Box::into_raw(Box::new(return_value));
+            } else {
+                *output_return_value = std::ptr::null_mut();
+            }
+            output_status(status, output_len)
+        },
+    )
+}
+
+/// Invoke an init function creating the contract instance, writing the results
+/// to buffers allocated with the `alloc` callback.
+/// # Safety
+/// This function is safe provided the preconditions of [call_init_v1] hold,
+/// with the difference that `output_return_value` points to a memory location
+/// that can store a pointer to a byte array, and `output_return_value_len`
+/// points to a memory location that can store a [libc::size_t] value, and
+/// `output_config` points to a memory location that can store a pointer.
+/// `host_features` selects the behaviour of the host, see
+/// [HostFeatures::from_bits], whereas [call_init_v1] uses the default.
+/// `random_seed` is either a null pointer or points to the 32 bytes of the
+/// seed of the block, which together with `random_nonce` is the seed of the
+/// randomness returned by `get_random`, see [RandomSeed](v0::RandomSeed).
+/// Without a seed `get_random` fails, as it always does with [call_init_v1].
+/// # Return value
+/// The return value and out parameters are the same as for [call_init_v1],
+/// except that the status buffer and the return value are written to buffers
+/// allocated with `alloc`, and are thus owned by foreign code. If there is a
+/// return value its length is written to `output_return_value_len`, and
+/// otherwise `output_return_value` is set to a null pointer.
+/// In case of [InitResult::Interrupt], which only happens with
+/// [init_interrupts](HostFeatures::init_interrupts), the `output_config`
+/// pointer is set to a freshly allocated [InitInterruptedState] structure,
+/// which retains the state of the contract, and is otherwise set to a null
+/// pointer. The structure should be deallocated with
+/// [init_interrupted_state_free] so that memory is not leaked. Execution is
+/// resumed with [resume_init_v1_with_alloc].
+#[no_mangle]
+unsafe extern "C" fn call_init_v1_with_alloc(
+    loader: LoadCallback,
+    alloc: AllocCallback,
+    artifact_ptr: *const ArtifactV1,
+    init_ctx_bytes: *const u8, // pointer to an initcontext
+    init_ctx_bytes_len: size_t,
+    amount: u64,
+    init_name: *const u8, // the name of the contract init method
+    init_name_len: size_t,
+    param_bytes: *const u8, // parameters to the init method
+    param_bytes_len: size_t,
+    energy: InterpreterEnergy,
+    host_features: u64,
+    random_seed: *const u8,
+    random_nonce: u64,
+    output_return_value: *mut *mut u8,
+    output_return_value_len: *mut size_t,
+    output_len: *mut size_t,
+    output_state_ptr: *mut *mut MutableState,
+    output_config: *mut *mut InitInterruptedStateV1,
+) -> *mut u8 {
+    call_init_v1_with(
+        loader,
+        artifact_ptr,
+        init_ctx_bytes,
+        init_ctx_bytes_len,
+        amount,
+        init_name,
+        init_name_len,
+        param_bytes,
+        param_bytes_len,
+        energy,
+        HostFeatures::from_bits(host_features),
+        random_seed_from_c(random_seed, random_nonce),
+        output_state_ptr,
+        output_config,
+        |status, return_value| {
+            output_with_alloc(
+                alloc,
+                status,
+                return_value,
+                output_len,
+                output_return_value,
+                output_return_value_len,
+            )
+        },
+    )
+}
+
+/// Shared implementation of [call_init_v1] and [call_init_v1_with_alloc]. The
+/// status buffer and return value of execution are handed to foreign code by
+/// `output`, whose result is returned.
+#[allow(clippy::too_many_arguments)]
+unsafe fn call_init_v1_with(
+    loader: LoadCallback,
+    artifact_ptr: *const ArtifactV1,
+    init_ctx_bytes: *const u8,
+    init_ctx_bytes_len: size_t,
+    amount: u64,
+    init_name: *const u8,
+    init_name_len: size_t,
+    param_bytes: *const u8,
+    param_bytes_len: size_t,
+    energy: InterpreterEnergy,
+    host_features: HostFeatures,
+    random_seed: Option<v0::RandomSeed>,
+    output_state_ptr: *mut *mut MutableState,
+    output_config: *mut *mut InitInterruptedStateV1,
+    output: impl FnOnce(Vec<u8>, Option<ReturnValue>) -> *mut u8 + std::panic::UnwindSafe,
 ) -> *mut u8 {
     let artifact = Arc::from_raw(artifact_ptr);
     let res = std::panic::catch_unwind(|| {
         let init_name = slice_from_c_bytes!(init_name, init_name_len as usize);
         let parameter = slice_from_c_bytes!(param_bytes, param_bytes_len as usize);
-        let init_ctx = v0::deserial_init_context(slice_from_c_bytes!(
-            init_ctx_bytes,
-            init_ctx_bytes_len as usize
-        ))
-        .expect("Precondition violation: invalid init ctx given by host.");
+        // The context is retained if execution is interrupted, so it is owned.
+        let init_ctx: v0::InitContext<v0::OwnedPolicyBytes> = v0::deserial_init_context(
+            slice_from_c_bytes!(init_ctx_bytes, init_ctx_bytes_len as usize),
+        )
+        .expect("Precondition violation: invalid init ctx given by host.")
+        .into();
         match std::str::from_utf8(init_name) {
             Ok(name) => {
                 let res = invoke_init(
-                    artifact.as_ref(),
+                    artifact.clone(),
                     amount,
                     init_ctx,
                     name,
                     parameter,
                     energy,
                     loader,
+                    InvokeOptions {
+                        host_features,
+                        random_seed,
+                        ..InvokeOptions::default()
+                    },
                 );
                 match res {
                     Ok(result) => {
-                        let (mut out, initial_state, return_value) = result.extract();
-                        out.shrink_to_fit();
-                        *output_len = out.len() as size_t;
-                        let ptr = out.as_mut_ptr();
-                        std::mem::forget(out);
-                        if let Some(return_value) = return_value {
-                            *output_return_value = Box::into_raw(Box::new(return_value));
-                        } else {
-                            *output_return_value = std::ptr::null_mut();
-                        }
-                        if let Some(initial_state) = initial_state {
-                            let initial_state = Box::into_raw(Box::new(initial_state));
-                            *output_state_ptr = initial_state;
-                        }
-                        ptr
+                        output_init_result(result, output_state_ptr, output_config, output)
                     }
                     Err(_trap) => std::ptr::null_mut(),
                 }
@@ -166,6 +363,172 @@ unsafe extern "C" fn call_init_v1(
     res.unwrap_or(std::ptr::null_mut())
 }
 
+/// Hand over the result of executing, or resuming, an init function to
+/// foreign code. The initial state is written to `output_state_ptr` in case of
+/// success, and the interrupted state is written to `output_config` in case of
+/// an interrupt, which is otherwise set to a null pointer.
+unsafe fn output_init_result(
+    result: InitResult<CompiledFunction>,
+    output_state_ptr: *mut *mut MutableState,
+    output_config: *mut *mut InitInterruptedStateV1,
+    output: impl FnOnce(Vec<u8>, Option<ReturnValue>) -> *mut u8,
+) -> *mut u8 {
+    let InitResultExtract {
+        status,
+        state,
+        interrupt_state,
+        return_value,
+    } = result.extract();
+    if let Some(initial_state) = state {
+        *output_state_ptr = Box::into_raw(Box::new(initial_state));
+    }
+    if let Some(config) = interrupt_state {
+        *output_config = Box::into_raw(config);
+    } else {
+        // make sure to set it to null to make the finalizer work correctly.
+        *output_config = std::ptr::null_mut();
+    }
+    output(status, return_value)
+}
+
+/// Resume execution of an init function after an interrupt.
+///
+/// # Safety
+/// This function is safe provided
+/// - `config_ptr` points to a memory location which in turn points to an
+///   InitInterruptedState structure. The latter pointer must have been
+///   constructed with [Box::into_raw] and must be non-null.
+/// - the remaining arguments have the same requirements as they do for
+///   [call_init_v1] and [resume_receive_v1].
+///
+/// # Return value
+/// The return value and out parameters have the same semantics as for
+/// [call_init_v1], except that `config_ptr` takes the role of the
+/// `output_config` of [call_init_v1_with_alloc]. Since the instance does not
+/// exist yet its state is never updated by the operation, and the balance of
+/// the instance is not tracked, so unlike for [resume_receive_v1] neither is
+/// passed.
+#[no_mangle]
+unsafe extern "C" fn resume_init_v1(
+    loader: LoadCallback,
+    // mutable pointer, we will mutate this, either to a new state in case another interrupt
+    // occurred, or null
+    config_ptr: *mut *mut InitInterruptedStateV1,
+    // whether the call succeeded or not.
+    response_status: u64,
+    // response from the call.
+    response: *mut ReturnValue,
+    // remaining energy available for execution
+    energy: InterpreterEnergy,
+    output_return_value: *mut *mut ReturnValue,
+    output_len: *mut size_t,
+    output_state_ptr: *mut *mut MutableState,
+) -> *mut u8 {
+    let data = {
+        if response.is_null() {
+            None
+        } else {
+            let mut response_data = Box::from_raw(response);
+            let data = std::mem::take(response_data.as_mut()); // write empty vector to the pointer.
+            Box::into_raw(response_data); // make it safe to reclaim data
+            Some(data)
+        }
+    };
+    resume_init_v1_with(
+        loader,
+        config_ptr,
+        response_status,
+        data,
+        energy,
+        output_state_ptr,
+        |status, return_value| {
+            if let Some(return_value) = return_value {
+                *output_return_value = Box::into_raw(Box::new(return_value));
+            }
+            output_status(status, output_len)
+        },
+    )
+}
+
+/// Resume execution of an init function after an interrupt, and write the
+/// results to buffers allocated with the `alloc` callback.
+///
+/// # Safety
+/// This function is safe provided the preconditions of [resume_init_v1] hold,
+/// with the same differences as for [resume_receive_v1_with_alloc].
+///
+/// # Return value
+/// The return value and out parameters are the same as for [resume_init_v1],
+/// except that the status buffer and the return value are written to buffers
+/// allocated with `alloc`, as for [resume_receive_v1_with_alloc].
+#[no_mangle]
+unsafe extern "C" fn resume_init_v1_with_alloc(
+    loader: LoadCallback,
+    alloc: AllocCallback,
+    config_ptr: *mut *mut InitInterruptedStateV1,
+    response_status: u64,
+    response: *const u8,
+    response_len: size_t,
+    energy: InterpreterEnergy,
+    output_return_value: *mut *mut u8,
+    output_return_value_len: *mut size_t,
+    output_len: *mut size_t,
+    output_state_ptr: *mut *mut MutableState,
+) -> *mut u8 {
+    let data = if response.is_null() {
+        None
+    } else {
+        Some(slice_from_c_bytes!(response, response_len as usize).to_vec())
+    };
+    resume_init_v1_with(
+        loader,
+        config_ptr,
+        response_status,
+        data,
+        energy,
+        output_state_ptr,
+        |status, return_value| {
+            output_with_alloc(
+                alloc,
+                status,
+                return_value,
+                output_len,
+                output_return_value,
+                output_return_value_len,
+            )
+        },
+    )
+}
+
+/// Shared implementation of [resume_init_v1] and [resume_init_v1_with_alloc].
+/// The status buffer and return value of execution are handed to foreign code
+/// by `output`, whose result is returned.
+unsafe fn resume_init_v1_with(
+    loader: LoadCallback,
+    config_ptr: *mut *mut InitInterruptedStateV1,
+    response_status: u64,
+    data: Option<ReturnValue>,
+    energy: InterpreterEnergy,
+    output_state_ptr: *mut *mut MutableState,
+    output: impl FnOnce(Vec<u8>, Option<ReturnValue>) -> *mut u8 + std::panic::UnwindSafe,
+) -> *mut u8 {
+    let res = std::panic::catch_unwind(|| {
+        // The balance of the instance is not tracked during initialization.
+        let response = match decode_response(response_status, false, 0, data) {
+            Some(response) => response,
+            None => return std::ptr::null_mut(),
+        };
+        // mark the interrupted state as consumed, see resume_receive_v1_with.
+        let config = std::ptr::replace(config_ptr, std::ptr::null_mut());
+        let config = Box::from_raw(config);
+        match resume_init(config, response, energy, loader, InvokeOptions::default()) {
+            Ok(result) => output_init_result(result, output_state_ptr, config_ptr, output),
+            Err(_trap) => std::ptr::null_mut(),
+        }
+    });
+    res.unwrap_or(std::ptr::null_mut())
+}
+
 /// Invoke a receive function, updating the contract instance.
 /// # Safety
 /// This function is safe provided the following preconditions hold
@@ -218,6 +581,124 @@ unsafe extern "C" fn call_receive_v1(
     output_return_value: *mut *mut ReturnValue,
     output_config: *mut *mut ReceiveInterruptedStateV1,
     output_len: *mut size_t,
+) -> *mut u8 {
+    call_receive_v1_with(
+        loader,
+        artifact_ptr,
+        receive_ctx_bytes,
+        receive_ctx_bytes_len,
+        amount,
+        receive_name,
+        receive_name_len,
+        call_default,
+        state_ptr_ptr,
+        param_bytes,
+        param_bytes_len,
+        energy,
+        HostFeatures::default(),
+        None,
+        output_config,
+        |status, return_value| {
+            if let Some(return_value) = return_value {
+                *output_return_value = Box::into_raw(Box::new(return_value));
+            } else {
+                *output_return_value = std::ptr::null_mut();
+            }
+            output_status(status, output_len)
+        },
+    )
+}
+
+/// Invoke a receive function, updating the contract instance, and write the
+/// results to buffers allocated with the `alloc` callback.
+/// # Safety
+/// This function is safe provided the preconditions of [call_receive_v1] hold,
+/// with the difference that `output_return_value` points to a memory location
+/// that can store a pointer to a byte array, and `output_return_value_len`
+/// points to a memory location that can store a [libc::size_t] value.
+/// `host_features` selects the behaviour of the host, see
+/// [HostFeatures::from_bits], whereas [call_receive_v1] uses the default.
+/// `random_seed` and `random_nonce` give the seed of the randomness returned
+/// by `get_random` as for [call_init_v1_with_alloc].
+/// # Return value
+/// The return value and out parameters are the same as for [call_receive_v1],
+/// except that the status buffer and the return value are written to buffers
+/// allocated with `alloc`, and are thus owned by foreign code. If there is a
+/// return value its length is written to `output_return_value_len`, and
+/// otherwise `output_return_value` is set to a null pointer.
+#[no_mangle]
+unsafe extern "C" fn call_receive_v1_with_alloc(
+    loader: LoadCallback,
+    alloc: AllocCallback,
+    artifact_ptr: *const ArtifactV1,
+    receive_ctx_bytes: *const u8, // receive context
+    receive_ctx_bytes_len: size_t,
+    amount: u64,
+    receive_name: *const u8,
+    receive_name_len: size_t,
+    call_default: u8, // non-zero if to call the default/fallback instead
+    state_ptr_ptr: *mut *mut MutableState,
+    param_bytes: *const u8, // parameters to the entrypoint
+    param_bytes_len: size_t,
+    energy: InterpreterEnergy,
+    host_features: u64,
+    random_seed: *const u8,
+    random_nonce: u64,
+    output_return_value: *mut *mut u8,
+    output_return_value_len: *mut size_t,
+    output_config: *mut *mut ReceiveInterruptedStateV1,
+    output_len: *mut size_t,
+) -> *mut u8 {
+    call_receive_v1_with(
+        loader,
+        artifact_ptr,
+        receive_ctx_bytes,
+        receive_ctx_bytes_len,
+        amount,
+        receive_name,
+        receive_name_len,
+        call_default,
+        state_ptr_ptr,
+        param_bytes,
+        param_bytes_len,
+        energy,
+        HostFeatures::from_bits(host_features),
+        random_seed_from_c(random_seed, random_nonce),
+        output_config,
+        |status, return_value| {
+            output_with_alloc(
+                alloc,
+                status,
+                return_value,
+                output_len,
+                output_return_value,
+                output_return_value_len,
+            )
+        },
+    )
+}
+
+/// Shared implementation of [call_receive_v1] and [call_receive_v1_with_alloc].
+/// The status buffer and return value of execution are handed to foreign code
+/// by `output`, whose result is returned.
+#[allow(clippy::too_many_arguments)]
+unsafe fn call_receive_v1_with(
+    loader: LoadCallback,
+    artifact_ptr: *const ArtifactV1,
+    receive_ctx_bytes: *const u8,
+    receive_ctx_bytes_len: size_t,
+    amount: u64,
+    receive_name: *const u8,
+    receive_name_len: size_t,
+    call_default: u8,
+    state_ptr_ptr: *mut *mut MutableState,
+    param_bytes: *const u8,
+    param_bytes_len: size_t,
+    energy: InterpreterEnergy,
+    host_features: HostFeatures,
+    random_seed: Option<v0::RandomSeed>,
+    output_config: *mut *mut ReceiveInterruptedStateV1,
+    output: impl FnOnce(Vec<u8>, Option<ReturnValue>) -> *mut u8 + std::panic::UnwindSafe,
 ) -> *mut u8 {
     let artifact = Arc::from_raw(artifact_ptr);
     let res = std::panic::catch_unwind(|| -> *mut u8 {
@@ -261,35 +742,31 @@ unsafe extern "C" fn call_receive_v1(
                     parameter,
                     energy,
                     instance_state,
+                    InvokeOptions {
+                        host_features,
+                        random_seed,
+                        ..InvokeOptions::default()
+                    },
                 );
                 match res {
                     Ok(result) => {
                         let ReceiveResultExtract {
-                            mut status,
+                            status,
                             state_changed,
                             interrupt_state,
                             return_value,
                         } = result.extract();
-                        status.shrink_to_fit();
-                        *output_len = status.len() as size_t;
-                        let ptr = status.as_mut_ptr();
-                        std::mem::forget(status);
                         if let Some(config) = interrupt_state {
                             std::ptr::replace(output_config, Box::into_raw(config));
                         } else {
                             // make sure to set it to null to make the finalizer work correctly.
                             *output_config = std::ptr::null_mut();
                         }
-                        if let Some(return_value) = return_value {
-                            *output_return_value = Box::into_raw(Box::new(return_value));
-                        } else {
-                            *output_return_value = std::ptr::null_mut();
-                        }
                         if state_changed {
                             let new_state = Box::into_raw(Box::new(state));
                             *state_ptr_ptr = new_state;
                         }
-                        ptr
+                        output(status, return_value)
                     }
                     Err(_trap) => std::ptr::null_mut(),
                 }
@@ -314,6 +791,10 @@ unsafe extern "C" fn call_receive_v1(
 /// - `wasm_bytes_ptr` a pointer to the Wasm module in Wasm binary format,
 ///   version 1.
 /// - `wasm_bytes_len` the length of the data pointed to by `wasm_bytes_ptr`
+/// - `host_interface_version` the latest version of the host interface whose
+///   imports are allowed, as selected by the protocol version, see
+///   [ConcordiumAllowedImports]. It also determines how the call depth is
+///   limited, see [ConcordiumAllowedImports::metering_config].
 /// - `artifact_out` a pointer where the pointer to the artifact will be
 ///   written.
 /// - `output_len` a pointer where the total length of the output will be
@@ -331,13 +812,18 @@ unsafe extern "C" fn call_receive_v1(
 unsafe extern "C" fn validate_and_process_v1(
     wasm_bytes_ptr: *const u8,
     wasm_bytes_len: size_t,
+    host_interface_version: u32,
     output_len: *mut size_t, // this is the total length of the output byte array
     output_artifact: *mut *const ArtifactV1, /* location where the pointer to the artifact will
                               * be written. */
 ) -> *mut u8 {
     let wasm_bytes = slice_from_c_bytes!(wasm_bytes_ptr, wasm_bytes_len as usize);
-    match utils::instantiate_with_metering::<ProcessedImports, _>(
-        &ConcordiumAllowedImports,
+    #[cfg(feature = "instrumentation")]
+    let _span = crate::utils::compilation_span(wasm_bytes).entered();
+    let allowed_imports = ConcordiumAllowedImports::new(host_interface_version);
+    match utils::instantiate_with_metering_config::<ProcessedImports, _>(
+        &allowed_imports,
+        &allowed_imports.metering_config(),
         wasm_bytes,
     ) {
         Ok(artifact) => {
@@ -394,44 +880,155 @@ unsafe extern "C" fn resume_receive_v1(
     output_return_value: *mut *mut ReturnValue,
     output_len: *mut size_t,
 ) -> *mut u8 {
-    let res = std::panic::catch_unwind(|| {
-        let data = {
-            if response.is_null() {
-                None
-            } else {
-                let mut response_data = Box::from_raw(response);
-                let data = std::mem::take(response_data.as_mut()); // write empty vector to the pointer.
-                Box::into_raw(response_data); // make it safe to reclaim data
-                Some(data)
-            }
-        };
-        let state_updated = state_updated_tag != 0;
-        // NB: This must match the response encoding in V1.hs in consensus
-        // If the first 3 bytes are all set that indicates an error.
-        let response = if response_status & 0xffff_ff00_0000_0000 == 0xffff_ff00_0000_0000 {
-            if response_status & 0x0000_00ff_0000_0000 != 0 {
-                // this is an environment error. No return value is produced.
-                InvokeResponse::Failure {
-                    code: response_status & 0x0000_00ff_0000_0000,
-                    data: None,
-                }
-            } else {
-                // The return value is present since this was a logic error.
-                if response_status & 0x0000_0000_ffff_ffff == 0 {
-                    // Host violated precondition. There must be a non-zero error code.
-                    return std::ptr::null_mut();
-                }
-                InvokeResponse::Failure {
-                    code: response_status & 0x0000_0000_ffff_ffff,
-                    data,
-                }
+    let data = {
+        if response.is_null() {
+            None
+        } else {
+            let mut response_data = Box::from_raw(response);
+            let data = std::mem::take(response_data.as_mut()); // write empty vector to the pointer.
+            Box::into_raw(response_data); // make it safe to reclaim data
+            Some(data)
+        }
+    };
+    resume_receive_v1_with(
+        loader,
+        config_ptr,
+        state_updated_tag,
+        state_ptr_ptr,
+        new_amount,
+        response_status,
+        data,
+        energy,
+        |status, return_value| {
+            if let Some(return_value) = return_value {
+                *output_return_value = Box::into_raw(Box::new(return_value));
             }
+            output_status(status, output_len)
+        },
+    )
+}
+
+#[no_mangle]
+/// Resume execution of a contract after an interrupt, and write the results to
+/// buffers allocated with the `alloc` callback.
+///
+/// # Safety
+/// This function is safe provided the preconditions of [resume_receive_v1]
+/// hold, with the difference that
+/// - the response from the call is given as a byte array of length
+///   `response_len` pointed to by `response`, or a null pointer if there is no
+///   response
+/// - `output_return_value` points to a memory location that can store a pointer
+///   to a byte array, and `output_return_value_len` points to a memory location
+///   that can store a [libc::size_t] value.
+///
+/// # Return value
+/// The return value and out parameters are the same as for
+/// [resume_receive_v1], except that the status buffer and the return value are
+/// written to buffers allocated with `alloc`, and are thus owned by foreign
+/// code. If there is a return value its length is written to
+/// `output_return_value_len`, and otherwise `output_return_value` is set to a
+/// null pointer.
+unsafe extern "C" fn resume_receive_v1_with_alloc(
+    loader: LoadCallback,
+    alloc: AllocCallback,
+    config_ptr: *mut *mut ReceiveInterruptedStateV1,
+    state_updated_tag: u8,
+    state_ptr_ptr: *mut *mut MutableState,
+    new_amount: u64,
+    response_status: u64,
+    response: *const u8,
+    response_len: size_t,
+    energy: InterpreterEnergy,
+    output_return_value: *mut *mut u8,
+    output_return_value_len: *mut size_t,
+    output_len: *mut size_t,
+) -> *mut u8 {
+    let data = if response.is_null() {
+        None
+    } else {
+        Some(slice_from_c_bytes!(response, response_len as usize).to_vec())
+    };
+    resume_receive_v1_with(
+        loader,
+        config_ptr,
+        state_updated_tag,
+        state_ptr_ptr,
+        new_amount,
+        response_status,
+        data,
+        energy,
+        |status, return_value| {
+            output_with_alloc(
+                alloc,
+                status,
+                return_value,
+                output_len,
+                output_return_value,
+                output_return_value_len,
+            )
+        },
+    )
+}
+
+/// Decode the response to an interrupt passed by foreign code. Returns [None]
+/// if the host violated the precondition that logic errors have a non-zero
+/// error code.
+fn decode_response(
+    response_status: u64,
+    state_updated: bool,
+    new_amount: u64,
+    data: Option<ReturnValue>,
+) -> Option<InvokeResponse> {
+    // NB: This must match the response encoding in V1.hs in consensus
+    // If the first 3 bytes are all set that indicates an error.
+    if response_status & 0xffff_ff00_0000_0000 == 0xffff_ff00_0000_0000 {
+        if response_status & 0x0000_00ff_0000_0000 != 0 {
+            // this is an environment error. No return value is produced.
+            Some(InvokeResponse::Failure {
+                code: response_status & 0x0000_00ff_0000_0000,
+                data: None,
+            })
         } else {
-            InvokeResponse::Success {
-                state_updated,
-                new_balance: Amount::from_micro_ccd(new_amount),
-                data,
+            // The return value is present since this was a logic error.
+            if response_status & 0x0000_0000_ffff_ffff == 0 {
+                // Host violated precondition. There must be a non-zero error code.
+                return None;
             }
+            Some(InvokeResponse::Failure {
+                code: response_status & 0x0000_0000_ffff_ffff,
+                data,
+            })
+        }
+    } else {
+        Some(InvokeResponse::Success {
+            state_updated,
+            new_balance: Amount::from_micro_ccd(new_amount),
+            data,
+        })
+    }
+}
+
+/// Shared implementation of [resume_receive_v1] and
+/// [resume_receive_v1_with_alloc]. The status buffer and return value of
+/// execution are handed to foreign code by `output`, whose result is returned.
+#[allow(clippy::too_many_arguments)]
+unsafe fn resume_receive_v1_with(
+    loader: LoadCallback,
+    config_ptr: *mut *mut ReceiveInterruptedStateV1,
+    state_updated_tag: u8,
+    state_ptr_ptr: *mut *mut MutableState,
+    new_amount: u64,
+    response_status: u64,
+    data: Option<ReturnValue>,
+    energy: InterpreterEnergy,
+    output: impl FnOnce(Vec<u8>, Option<ReturnValue>) -> *mut u8 + std::panic::UnwindSafe,
+) -> *mut u8 {
+    let res = std::panic::catch_unwind(|| {
+        let state_updated = state_updated_tag != 0;
+        let response = match decode_response(response_status, state_updated, new_amount, data) {
+            Some(response) => response,
+            None => return std::ptr::null_mut(),
         };
         // mark the interrupted state as consumed in case any panics happen from here to
         // the end. this means the state is in a consistent state and the
@@ -450,29 +1047,30 @@ unsafe extern "C" fn resume_receive_v1(
         // it is important to invalidate all previous iterators and entries we have
         // given out. so we start a new generation.
         let config = Box::from_raw(config);
-        let res = resume_receive(config, response, energy, &mut state, state_updated, loader);
+        let res = resume_receive(
+            config,
+            response,
+            energy,
+            &mut state,
+            state_updated,
+            loader,
+            InvokeOptions::default(),
+        );
         match res {
             Ok(result) => {
                 let ReceiveResultExtract {
-                    mut status,
+                    status,
                     state_changed,
                     interrupt_state,
                     return_value,
                 } = result.extract();
-                status.shrink_to_fit();
-                *output_len = status.len() as size_t;
-                let ptr = status.as_mut_ptr();
-                std::mem::forget(status);
                 if let Some(config) = interrupt_state {
                     std::ptr::replace(config_ptr, Box::into_raw(config));
                 } // otherwise leave config_ptr pointing to null
-                if let Some(return_value) = return_value {
-                    *output_return_value = Box::into_raw(Box::new(return_value));
-                }
                 if state_changed {
                     *state_ptr_ptr = Box::into_raw(Box::new(state))
                 }
-                ptr
+                output(status, return_value)
             }
             Err(_trap) => std::ptr::null_mut(),
         }
@@ -519,10 +1117,24 @@ unsafe extern "C" fn receive_interrupted_state_free(ptr_ptr: *mut *mut ReceiveIn
     }
 }
 
+#[no_mangle]
+/// # Safety
+/// This function is safe provided the supplied pointer is
+/// constructed with [Box::into_raw].
+unsafe extern "C" fn init_interrupted_state_free(ptr_ptr: *mut *mut InitInterruptedStateV1) {
+    if !ptr_ptr.is_null() && !(*ptr_ptr).is_null() {
+        // drop
+        let _: Box<InitInterruptedStateV1> = Box::from_raw(*ptr_ptr);
+        // and store null so that future calls (which there should not be any) are safe.
+        *ptr_ptr = std::ptr::null_mut();
+    }
+}
+
 #[no_mangle]
 /// Convert an artifact to a byte array and return a pointer to it, storing its
 /// length in `output_len`. To avoid leaking memory the return value should be
-/// freed with `rs_free_array_len`.
+/// freed with `rs_free_array_len`. If the `compression` feature is enabled the
+/// serialized artifact is compressed.
 ///
 /// # Safety
 /// This function is safe provided the `artifact_ptr` was obtained with
@@ -534,6 +1146,8 @@ unsafe extern "C" fn artifact_v1_to_bytes(
     let artifact = Arc::from_raw(artifact_ptr);
     let mut bytes = Vec::new();
     artifact.output(&mut bytes).expect("Artifact serialization does not fail.");
+    #[cfg(feature = "compression")]
+    let mut bytes = compression::compress(&bytes).expect("Compressing to a vector does not fail.");
     bytes.shrink_to_fit();
     *output_len = bytes.len() as size_t;
     let ptr = bytes.as_mut_ptr();
@@ -543,7 +1157,8 @@ unsafe extern "C" fn artifact_v1_to_bytes(
 }
 
 #[no_mangle]
-/// Deserialize an artifact from bytes and return a pointer to it.
+/// Deserialize an artifact from bytes, which are decompressed first if they
+/// are compressed, and return a pointer to it.
 /// If deserialization fails this returns [None](https://doc.rust-lang.org/std/option/enum.Option.html#variant.None)
 /// and otherwise it returns a valid pointer to the artifact. To avoid leaking
 /// memory the memory must be freed using [artifact_v1_free].
@@ -558,7 +1173,11 @@ unsafe extern "C" fn artifact_v1_from_bytes(
     input_len: size_t,
 ) -> *const ArtifactV1 {
     let bytes = slice_from_c_bytes!(bytes_ptr, input_len as usize);
-    if let Ok(borrowed_artifact) = parse_artifact(bytes) {
+    let bytes = match compression::decompress(bytes) {
+        Ok(bytes) => bytes,
+        Err(_) => return std::ptr::null(),
+    };
+    if let Ok(borrowed_artifact) = parse_artifact(&bytes) {
         Arc::into_raw(Arc::new(borrowed_artifact.into()))
     } else {
         std::ptr::null()
@@ -667,6 +1286,43 @@ extern "C" fn get_new_state_size_v1(mut loader: LoadCallback, tree: *mut Mutable
     collector.collect()
 }
 
+#[no_mangle]
+/// Compute the work that freezing the mutable state would do, without freezing
+/// it. The number of nodes created, the number of values written, and the
+/// number of bytes hashed are written, in that order, to the provided buffer,
+/// which must be sufficient to hold 3 `u64` values.
+extern "C" fn get_freeze_cost_v1(tree: *mut MutableState, cost_buf: *mut u64) {
+    let tree = unsafe { &*tree };
+    let cost = tree.freeze_cost();
+    let out = [cost.nodes_created, cost.values_written, cost.bytes_hashed];
+    unsafe { std::ptr::copy_nonoverlapping(out.as_ptr(), cost_buf, out.len()) };
+}
+
+#[no_mangle]
+/// Create a checkpoint of the mutable state, see [MutableState::checkpoint].
+/// The returned value identifies the checkpoint and must only be passed to
+/// [rollback_mutable_state_v1] with the same state.
+extern "C" fn checkpoint_mutable_state_v1(
+    mut loader: LoadCallback,
+    tree: *mut MutableState,
+) -> u64 {
+    let tree = unsafe { &mut *tree };
+    tree.checkpoint(&mut loader).nonce
+}
+
+#[no_mangle]
+/// Roll back the mutable state to a checkpoint created by
+/// [checkpoint_mutable_state_v1]. Returns 1 if successful, and 0 if the
+/// checkpoint is not valid for the state, in particular if it was invalidated
+/// by an earlier rollback.
+extern "C" fn rollback_mutable_state_v1(tree: *mut MutableState, checkpoint: u64) -> u8 {
+    let tree = unsafe { &mut *tree };
+    let checkpoint = StateCheckpoint {
+        nonce: checkpoint,
+    };
+    tree.rollback_to(checkpoint).is_ok() as u8
+}
+
 #[no_mangle]
 /// Load the entire tree into memory. If any data is in the backing store it is
 /// loaded using the provided callback.
@@ -760,6 +1416,40 @@ extern "C" fn persistent_state_v1_lookup(
     }
 }
 
+/// Callback invoked on each key-value pair by
+/// [persistent_state_v1_stream_prefix]. The key and value are only valid for
+/// the duration of the call. Returning a non-zero value stops the traversal.
+type StreamCallback =
+    extern "C" fn(key: *const u8, key_len: size_t, value: *const u8, value_len: size_t) -> u8;
+
+#[no_mangle]
+/// Call the provided callback on each key-value pair in the persistent state
+/// whose key starts with the given prefix, in lexicographic order of keys. If
+/// any of the state is in the backing store it is loaded using the provided
+/// callback. Returns 1 if the traversal was stopped by the callback, and 0
+/// otherwise.
+extern "C" fn persistent_state_v1_stream_prefix(
+    mut loader: LoadCallback,
+    tree: *mut PersistentState,
+    prefix: *const u8,
+    prefix_len: size_t,
+    callback: StreamCallback,
+) -> u8 {
+    let tree = unsafe { &*tree };
+    let prefix = unsafe { slice_from_c_bytes!(prefix, prefix_len) };
+    let mut f = |key: &[u8], value: &[u8]| {
+        if callback(key.as_ptr(), key.len(), value.as_ptr(), value.len()) == 0 {
+            std::ops::ControlFlow::Continue(())
+        } else {
+            std::ops::ControlFlow::Break(())
+        }
+    };
+    match tree.stream_prefix(&mut loader, prefix, &mut f) {
+        std::ops::ControlFlow::Continue(()) => 0,
+        std::ops::ControlFlow::Break(()) => 1,
+    }
+}
+
 #[no_mangle]
 /// Generate a persistent tree from a seed for testing. **This should only be
 /// used for testing.**
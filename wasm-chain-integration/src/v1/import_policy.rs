@@ -0,0 +1,109 @@
+//! Policies restricting the host functions that a V1 contract may use. These
+//! are intended for tooling that checks properties of contracts, e.g., that a
+//! contract is a pure state machine whose behaviour depends only on its
+//! state, parameter, and the amount it receives. They are not used on the
+//! chain.
+//!
+//! A policy is enforced either when the module is validated, by
+//! [PolicyAllowedImports], which rejects modules that import a denied function,
+//! or during execution, by listing the denied functions in the
+//! [FaultPlan](super::faults::FaultPlan) of a simulation, in which case calls
+//! to them fail with a [DeniedHostFunction] error.
+use super::{ConcordiumAllowedImports, ProcessedImports};
+use serde::{Deserialize as SerdeDeserialize, Serialize as SerdeSerialize};
+use std::collections::BTreeSet;
+use wasm_transform::{
+    artifact::Artifact,
+    types::{FunctionType, Name},
+    validate::ValidateImportExport,
+};
+
+/// The host functions that give access to the context of an execution, i.e.,
+/// the chain metadata, the details of the transaction, randomness, and the
+/// state of the chain outside the contract.
+pub const CONTEXT_FUNCTIONS: &[&str] = &[
+    "get_slot_time",
+    "get_init_origin",
+    "get_receive_invoker",
+    "get_receive_self_address",
+    "get_receive_self_balance",
+    "get_receive_sender",
+    "get_receive_owner",
+    "get_policy_section",
+    "get_random",
+    "contract_exists",
+    "contract_state_size",
+    "get_account_balance",
+    "energy_to_micro_ccd",
+    "micro_ccd_to_energy",
+    "micro_euro_to_micro_ccd",
+    "micro_ccd_to_micro_euro",
+];
+
+/// A set of host functions, by the name they are imported with from the
+/// `concordium` module, that a contract may not use. The default policy
+/// allows all host functions.
+#[derive(SerdeSerialize, SerdeDeserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct ImportPolicy {
+    denied: BTreeSet<String>,
+}
+
+impl ImportPolicy {
+    /// A policy that denies the given host functions.
+    pub fn deny<'a>(names: impl IntoIterator<Item = &'a str>) -> Self {
+        Self {
+            denied: names.into_iter().map(String::from).collect(),
+        }
+    }
+
+    /// The policy of pure contracts, which denies the [CONTEXT_FUNCTIONS].
+    pub fn pure() -> Self { Self::deny(CONTEXT_FUNCTIONS.iter().copied()) }
+
+    /// Whether the host function with the given name is denied.
+    pub fn denies(&self, name: &str) -> bool { self.denied.contains(name) }
+
+    /// The names of the denied host functions, in alphabetical order.
+    pub fn denied(&self) -> impl Iterator<Item = &str> { self.denied.iter().map(String::as_str) }
+
+    /// The denied host functions that the artifact imports, in the order of
+    /// the imports. This allows checking modules that were validated without
+    /// the policy, e.g., modules already deployed on the chain.
+    pub fn violations<R>(&self, artifact: &Artifact<ProcessedImports, R>) -> Vec<&'static str> {
+        artifact.imports.iter().map(|i| i.tag.name()).filter(|name| self.denies(name)).collect()
+    }
+}
+
+/// Validation of V1 modules that additionally rejects modules importing host
+/// functions denied by the policy. Such modules are rejected with a
+/// [DisallowedImport](wasm_transform::validate::ValidationError::DisallowedImport)
+/// error.
+pub struct PolicyAllowedImports<'a> {
+    pub policy: &'a ImportPolicy,
+}
+
+impl<'a> ValidateImportExport for PolicyAllowedImports<'a> {
+    fn validate_import_function(
+        &self,
+        duplicate: bool,
+        mod_name: &Name,
+        item_name: &Name,
+        ty: &FunctionType,
+    ) -> bool {
+        !(mod_name.name == "concordium" && self.policy.denies(item_name.as_ref()))
+            && ConcordiumAllowedImports::LATEST
+                .validate_import_function(duplicate, mod_name, item_name, ty)
+    }
+
+    fn validate_export_function(&self, item_name: &Name, ty: &FunctionType) -> bool {
+        ConcordiumAllowedImports::LATEST.validate_export_function(item_name, ty)
+    }
+}
+
+/// Error of calling a host function that is denied by the [ImportPolicy] of a
+/// simulation. Execution traps with this error.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("The host function {name} is denied by the import policy.")]
+pub struct DeniedHostFunction {
+    pub name: &'static str,
+}
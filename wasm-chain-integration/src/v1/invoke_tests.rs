@@ -0,0 +1,735 @@
+//! Integration tests for the `invoke` host function, and for resuming
+//! execution after the interrupts it causes.
+use crate::{
+    constants, v0,
+    v1::{
+        completion::{run_to_completion, CompletionError, HandlerResponse},
+        faults::{FaultInjector, FaultPlan},
+        import_policy::{DeniedHostFunction, ImportPolicy, PolicyAllowedImports},
+        trace::Trace,
+        trie::{Loader, MutableState},
+        CallDepth, CallDepthExceeded, ConcordiumAllowedImports, HostFeatures, InitResult,
+        InstanceState, Interrupt, InvokeOptions, InvokeResponse, OperationInReadOnly,
+        ProcessedImports, ReceiveContext, ReceiveResult, ReturnValuesTooLarge,
+    },
+    ExecResult, InterpreterEnergy,
+};
+use anyhow::{bail, ensure};
+use concordium_contracts_common::{
+    AccountAddress, Address, Amount, ChainMetadata, ContractAddress, OwnedEntrypointName,
+    ReceiveName, Timestamp,
+};
+use std::sync::Arc;
+use wasm_transform::{artifact::Artifact, utils, validate::ValidationError};
+
+static CONTRACT_BYTES: &[u8] = include_bytes!("../../test-data/code/v1/invoke-tests.wasm");
+
+type ArtifactV1 = Artifact<ProcessedImports, wasm_transform::artifact::CompiledFunction>;
+
+const ENERGY: u64 = 1_000_000_000;
+
+fn artifact() -> anyhow::Result<Arc<ArtifactV1>> {
+    let artifact =
+        utils::instantiate_with_metering(&ConcordiumAllowedImports::LATEST, CONTRACT_BYTES)?;
+    Ok(Arc::new(artifact))
+}
+
+fn receive_ctx() -> ReceiveContext<v0::OwnedPolicyBytes> {
+    let owner = AccountAddress([0u8; 32]);
+    ReceiveContext {
+        common:     v0::ReceiveContext {
+            metadata: ChainMetadata {
+                slot_time: Timestamp::from_timestamp_millis(0),
+            },
+            invoker: owner,
+            self_address: ContractAddress {
+                index:    0,
+                subindex: 0,
+            },
+            self_balance: Amount::from_ccd(1000),
+            sender: Address::Account(owner),
+            owner,
+            sender_policies: Vec::new(),
+        },
+        entrypoint: OwnedEntrypointName::new_unchecked("entrypoint".into()),
+    }
+}
+
+fn init_ctx() -> v0::InitContext<v0::OwnedPolicyBytes> {
+    v0::InitContext {
+        metadata:        ChainMetadata {
+            slot_time: Timestamp::from_timestamp_millis(0),
+        },
+        init_origin:     AccountAddress([0u8; 32]),
+        sender_policies: Vec::new(),
+    }
+}
+
+/// Invoke the entrypoint of the test contract, with the instance state
+/// configured by the given function, and the host by the options.
+fn invoke_with(
+    artifact: &Arc<ArtifactV1>,
+    state: &mut MutableState,
+    name: &str,
+    configure: impl FnOnce(InstanceState<Loader<Vec<u8>>>) -> InstanceState<Loader<Vec<u8>>>,
+    options: InvokeOptions,
+) -> ExecResult<ReceiveResult<wasm_transform::artifact::CompiledFunction>> {
+    let mut loader = Loader {
+        inner: Vec::<u8>::new(),
+    };
+    let inner = state.get_inner(&mut loader);
+    let instance_state = configure(InstanceState::new(0, loader, inner));
+    super::invoke_receive(
+        artifact.clone(),
+        0,
+        receive_ctx(),
+        ReceiveName::new_unchecked(name),
+        &[],
+        InterpreterEnergy::from(ENERGY),
+        instance_state,
+        options,
+    )
+}
+
+#[test]
+/// Check that read-only entrypoints may not transfer or call contracts.
+fn test_read_only_operations() -> anyhow::Result<()> {
+    let artifact = artifact()?;
+    let mut state = MutableState::initial_state();
+    for name in ["test.transfer", "test.call"].iter() {
+        let result = invoke_with(&artifact, &mut state, name, |s| s, InvokeOptions::default())?;
+        ensure!(
+            matches!(result, ReceiveResult::Interrupt { .. }),
+            "{} should be interrupted if not read-only.",
+            name
+        );
+        let result = invoke_with(
+            &artifact,
+            &mut state,
+            name,
+            |s| s.with_read_only(true),
+            InvokeOptions::default(),
+        )?;
+        match result {
+            ReceiveResult::Trap {
+                error,
+                ..
+            } => ensure!(
+                error.downcast_ref::<OperationInReadOnly>().is_some(),
+                "Unexpected error for {}: {}.",
+                name,
+                error
+            ),
+            other => bail!("{} should fail if read-only, got {:?}.", name, other.extract().status),
+        }
+    }
+    Ok(())
+}
+
+#[test]
+/// Check that view entrypoints are executed with a read-only state only with
+/// the [read_only_views](HostFeatures::read_only_views) feature.
+fn test_read_only_views() -> anyhow::Result<()> {
+    let artifact = artifact()?;
+    let mut state = MutableState::initial_state();
+    let read_only_views = HostFeatures {
+        read_only_views: true,
+        ..HostFeatures::default()
+    };
+    let invoke = |state: &mut MutableState, name: &str, host_features: HostFeatures| {
+        invoke_with(&artifact, state, name, |s| s, InvokeOptions {
+            host_features,
+            ..InvokeOptions::default()
+        })
+    };
+    let result = invoke(&mut state, "test.view", HostFeatures::default())?;
+    ensure!(
+        matches!(result, ReceiveResult::Interrupt { .. }),
+        "Views should not be read-only without the feature."
+    );
+    let result = invoke(&mut state, "test.viewer", read_only_views)?;
+    ensure!(
+        matches!(result, ReceiveResult::Interrupt { .. }),
+        "Entrypoints that are not views should not be read-only."
+    );
+    match invoke(&mut state, "test.view", read_only_views)? {
+        ReceiveResult::Trap {
+            error,
+            ..
+        } => ensure!(
+            error.downcast_ref::<OperationInReadOnly>().is_some(),
+            "Unexpected error: {}.",
+            error
+        ),
+        other => bail!("Views should be read-only, got {:?}.", other.extract().status),
+    }
+    Ok(())
+}
+
+#[test]
+/// Check that calls to contracts fail if they exceed the maximum call depth,
+/// and that the depth of a nested call is reported on interrupts.
+fn test_call_depth() -> anyhow::Result<()> {
+    let artifact = artifact()?;
+    let mut state = MutableState::initial_state();
+    let call_depth = CallDepth {
+        depth: 3,
+        limit: Some(4),
+    };
+    let result = invoke_with(
+        &artifact,
+        &mut state,
+        "test.call",
+        |s| s.with_call_depth(call_depth),
+        InvokeOptions::default(),
+    )?;
+    match result {
+        ReceiveResult::Interrupt {
+            config,
+            ..
+        } => ensure!(
+            config.nested_call_depth()
+                == CallDepth {
+                    depth: 4,
+                    limit: Some(4),
+                },
+            "Incorrect depth of the nested call."
+        ),
+        other => bail!("The call should be interrupted, got {:?}.", other.extract().status),
+    }
+    let result = invoke_with(
+        &artifact,
+        &mut state,
+        "test.call",
+        |s| {
+            s.with_call_depth(CallDepth {
+                depth: 4,
+                limit: Some(4),
+            })
+        },
+        InvokeOptions::default(),
+    )?;
+    match result {
+        ReceiveResult::Trap {
+            error,
+            ..
+        } => ensure!(
+            error.downcast_ref::<CallDepthExceeded>()
+                == Some(&CallDepthExceeded {
+                    depth: 5,
+                }),
+            "Unexpected error: {}.",
+            error
+        ),
+        other => bail!("The call should exceed the depth, got {:?}.", other.extract().status),
+    }
+    let result = invoke_with(
+        &artifact,
+        &mut state,
+        "test.transfer",
+        |s| {
+            s.with_call_depth(CallDepth {
+                depth: 4,
+                limit: Some(4),
+            })
+        },
+        InvokeOptions::default(),
+    )?;
+    ensure!(
+        matches!(result, ReceiveResult::Interrupt { .. }),
+        "Transfers are not limited by the call depth."
+    );
+    Ok(())
+}
+
+#[test]
+/// Check that injected failures of `invoke` replace the interrupt, and only
+/// apply to the operations with the given target.
+fn test_fault_injection() -> anyhow::Result<()> {
+    let artifact = artifact()?;
+    let mut state = MutableState::initial_state();
+    let plan: FaultPlan = serde_json::from_str(
+        r#"{"faults": [{"function": "invoke", "target": {"contract": {"index": 1, "subindex": 2}}, "every": 2, "result": 12884901888}]}"#,
+    )?;
+    let mut faults = FaultInjector::new(plan)?;
+    let mut invoke = |name: &str, faults: &mut FaultInjector| {
+        let mut loader = Loader {
+            inner: Vec::<u8>::new(),
+        };
+        let inner = state.get_inner(&mut loader);
+        super::invoke_receive(
+            artifact.clone(),
+            0,
+            receive_ctx(),
+            ReceiveName::new_unchecked(name),
+            &[],
+            InterpreterEnergy::from(ENERGY),
+            InstanceState::new(0, loader, inner),
+            InvokeOptions {
+                faults: Some(faults),
+                ..InvokeOptions::default()
+            },
+        )
+    };
+    let result: ReceiveResult<_> = invoke("test.call", &mut faults)?;
+    ensure!(matches!(result, ReceiveResult::Interrupt { .. }), "The first call does not fail.");
+    let result: ReceiveResult<_> = invoke("test.call", &mut faults)?;
+    match result {
+        ReceiveResult::Success {
+            return_value,
+            ..
+        } => ensure!(
+            return_value == 0x3_0000_0000u64.to_le_bytes(),
+            "The contract should observe the injected failure."
+        ),
+        other => bail!("The second call should fail, got {:?}.", other.extract().status),
+    }
+    ensure!(faults.num_injected() == 1, "One failure is injected.");
+    let result: ReceiveResult<_> = invoke("test.transfer", &mut faults)?;
+    ensure!(
+        matches!(result, ReceiveResult::Interrupt { .. }),
+        "Transfers do not match the target."
+    );
+    ensure!(
+        FaultInjector::new(serde_json::from_str(
+            r#"{"faults": [{"function": "state_entry_write", "every": 0, "result": 0}]}"#
+        )?)
+        .is_err(),
+        "Faults must have a positive period."
+    );
+    Ok(())
+}
+
+#[test]
+/// Check that the import policy of pure contracts rejects the test contract,
+/// which reads the slot time, and that calls to denied functions trap.
+fn test_import_policy() -> anyhow::Result<()> {
+    let pure = ImportPolicy::pure();
+    let err = utils::instantiate_with_metering::<ProcessedImports, _>(
+        &PolicyAllowedImports {
+            policy: &pure,
+        },
+        CONTRACT_BYTES,
+    )
+    .expect_err("The contract imports a context function.");
+    ensure!(
+        matches!(
+            err.downcast_ref::<ValidationError>(),
+            Some(ValidationError::DisallowedImport { item_name, .. }) if item_name.as_ref() == "get_slot_time"
+        ),
+        "Unexpected error {}",
+        err
+    );
+    utils::instantiate_with_metering::<ProcessedImports, _>(
+        &PolicyAllowedImports {
+            policy: &ImportPolicy::default(),
+        },
+        CONTRACT_BYTES,
+    )?;
+    let artifact = artifact()?;
+    ensure!(
+        pure.violations(artifact.as_ref()) == ["get_slot_time"],
+        "Only the slot time is denied."
+    );
+
+    let mut state = MutableState::initial_state();
+    let plan: FaultPlan = serde_json::from_str(r#"{"faults": [], "denied": ["get_slot_time"]}"#)?;
+    ensure!(plan.denied.denied().eq(["get_slot_time"].iter().copied()), "The policy is parsed.");
+    let mut faults = FaultInjector::new(plan)?;
+    let mut invoke = |name: &str| {
+        let mut loader = Loader {
+            inner: Vec::<u8>::new(),
+        };
+        let inner = state.get_inner(&mut loader);
+        super::invoke_receive(
+            artifact.clone(),
+            0,
+            receive_ctx(),
+            ReceiveName::new_unchecked(name),
+            &[],
+            InterpreterEnergy::from(ENERGY),
+            InstanceState::new(0, loader, inner),
+            InvokeOptions {
+                faults: Some(&mut faults),
+                ..InvokeOptions::default()
+            },
+        )
+    };
+    let result: ReceiveResult<_> = invoke("test.slot_time")?;
+    match result {
+        ReceiveResult::Trap {
+            error,
+            ..
+        } => ensure!(
+            error.downcast_ref::<DeniedHostFunction>()
+                == Some(&DeniedHostFunction {
+                    name: "get_slot_time",
+                }),
+            "Unexpected error {}",
+            error
+        ),
+        other => bail!("Reading the slot time should trap, got {:?}.", other.extract().status),
+    }
+    let result: ReceiveResult<_> = invoke("test.transfer")?;
+    ensure!(
+        matches!(result, ReceiveResult::Interrupt { .. }),
+        "Transfers are allowed by the policy."
+    );
+    Ok(())
+}
+
+#[test]
+/// Check that the trace of an interrupted call is continued when execution
+/// resumes, and that it records the host calls of the contract, and the
+/// executed instructions if they are traced.
+fn test_trace() -> anyhow::Result<()> {
+    let artifact = artifact()?;
+    let mut state = MutableState::initial_state();
+    let mut trace = Trace::new();
+    let mut loader = Loader {
+        inner: Vec::<u8>::new(),
+    };
+    let inner = state.get_inner(&mut loader);
+    let result: ReceiveResult<_> = super::invoke_receive(
+        artifact,
+        0,
+        receive_ctx(),
+        ReceiveName::new_unchecked("test.call"),
+        &[],
+        InterpreterEnergy::from(ENERGY),
+        InstanceState::new(0, loader, inner),
+        InvokeOptions {
+            trace: Some(&mut trace),
+            ..InvokeOptions::default()
+        },
+    )?;
+    let config = match result {
+        ReceiveResult::Interrupt {
+            config,
+            ..
+        } => config,
+        other => bail!("The call should be interrupted, got {:?}.", other.extract().status),
+    };
+    ensure!(trace.host_calls().eq(["invoke"].iter().copied()), "Only invoke is called.");
+    let steps = trace.num_steps();
+    let response = InvokeResponse::Success {
+        state_updated: false,
+        new_balance:   Amount::from_ccd(1000),
+        data:          Some(vec![0u8; 8]),
+    };
+    let result = super::resume_receive(
+        config,
+        response,
+        InterpreterEnergy::from(ENERGY),
+        &mut state,
+        false,
+        Loader {
+            inner: Vec::<u8>::new(),
+        },
+        InvokeOptions {
+            trace: Some(&mut trace),
+            ..InvokeOptions::default()
+        },
+    )?;
+    ensure!(
+        matches!(result, ReceiveResult::Success { .. }),
+        "Execution should succeed, got {:?}.",
+        result.extract().status
+    );
+    ensure!(
+        trace.host_calls().eq(["invoke", "write_output"].iter().copied()),
+        "The response is written after resuming."
+    );
+    if cfg!(feature = "execution-trace") {
+        ensure!(steps > 0 && trace.num_steps() > steps, "Instructions are traced.");
+    } else {
+        ensure!(trace.num_steps() == 0, "Instructions are only traced if enabled.");
+    }
+    let mut out = Vec::new();
+    trace.write(&mut out)?;
+    ensure!(
+        String::from_utf8(out)?.lines().filter(|l| l.starts_with("call ")).count() == 2,
+        "Each host call is written on its own line."
+    );
+    Ok(())
+}
+
+#[test]
+/// Check that calls are resumed with the response of the handler, that the
+/// changes the handler makes to the state are rolled back if the call fails,
+/// and that the handler can abort execution or use up its energy.
+fn test_run_to_completion() -> anyhow::Result<()> {
+    let artifact = artifact()?;
+    let loader = Loader {
+        inner: &[] as &[u8],
+    };
+    // Run test.call with a handler that writes to the state and then responds
+    // as given, and return the result and whether the write was kept.
+    let run = |response: fn() -> HandlerResponse| -> anyhow::Result<_> {
+        let mut state = MutableState::initial_state();
+        let result = run_to_completion(
+            artifact.clone(),
+            0,
+            receive_ctx(),
+            ReceiveName::new_unchecked("test.call"),
+            &[],
+            InterpreterEnergy::from(ENERGY),
+            &mut state,
+            loader,
+            |interruption| {
+                let mut loader = loader;
+                interruption
+                    .state
+                    .get_inner(&mut loader)
+                    .lock()
+                    .insert(&mut loader, b"written", vec![1])
+                    .expect("There are no iterators.");
+                response()
+            },
+        );
+        let mut loader = loader;
+        let written = state.get_inner(&mut loader).lock().get_entry(&mut loader, b"written");
+        Ok((result, written.is_some()))
+    };
+    let (result, written) = run(|| HandlerResponse::Resume {
+        response:    InvokeResponse::Success {
+            state_updated: true,
+            new_balance:   Amount::from_ccd(1000),
+            data:          Some(vec![0u8; 8]),
+        },
+        energy_used: 1000,
+    })?;
+    ensure!(matches!(result, Ok(ReceiveResult::Success { .. })), "The call should succeed.");
+    ensure!(written, "The state is kept if the call succeeds.");
+    let (result, written) = run(|| HandlerResponse::Resume {
+        response:    InvokeResponse::Failure {
+            code: 1 << 32,
+            data: None,
+        },
+        energy_used: 1000,
+    })?;
+    ensure!(matches!(result, Ok(ReceiveResult::Success { .. })), "The failure is handled.");
+    ensure!(!written, "The state is rolled back if the call fails.");
+    let (result, _) = run(|| HandlerResponse::Abort(anyhow::anyhow!("Calls are not supported.")))?;
+    ensure!(matches!(result, Err(CompletionError::Aborted(_))), "The handler aborts.");
+    let (result, _) = run(|| HandlerResponse::Resume {
+        response:    InvokeResponse::Success {
+            state_updated: false,
+            new_balance:   Amount::from_ccd(1000),
+            data:          None,
+        },
+        energy_used: ENERGY,
+    })?;
+    ensure!(matches!(result, Ok(ReceiveResult::OutOfEnergy)), "The handler uses all energy.");
+    Ok(())
+}
+
+#[test]
+/// Check that receiving a return value is charged by its size when execution
+/// resumes, and that the total size of the return values is limited, but only
+/// with return value accounting.
+fn test_return_value_charging() -> anyhow::Result<()> {
+    let artifact = artifact()?;
+    let mut state = MutableState::initial_state();
+    // Resume the call with a return value of the given length and the given
+    // amount of energy.
+    let mut resume = |len: usize,
+                      energy: u64,
+                      host_features: HostFeatures|
+     -> anyhow::Result<ReceiveResult<_>> {
+        let config = match invoke_with(&artifact, &mut state, "test.call", |s| s, InvokeOptions {
+            host_features,
+            ..InvokeOptions::default()
+        })? {
+            ReceiveResult::Interrupt {
+                config,
+                ..
+            } => config,
+            other => bail!("The call should be interrupted, got {:?}.", other.extract().status),
+        };
+        let response = InvokeResponse::Success {
+            state_updated: false,
+            new_balance:   Amount::from_ccd(1000),
+            data:          Some(vec![0u8; len]),
+        };
+        super::resume_receive(
+            config,
+            response,
+            InterpreterEnergy::from(energy),
+            &mut state,
+            false,
+            Loader {
+                inner: Vec::<u8>::new(),
+            },
+            InvokeOptions::default(),
+        )
+    };
+    let remaining = |result: ReceiveResult<_>| match result {
+        ReceiveResult::Success {
+            remaining_energy,
+            ..
+        } => Ok(remaining_energy),
+        other => bail!("Execution should succeed, got {:?}.", other.extract().status),
+    };
+    let legacy = HostFeatures::default();
+    ensure!(
+        remaining(resume(8, ENERGY, legacy)?)? == remaining(resume(1008, ENERGY, legacy)?)?,
+        "The return value should not be charged for without return value accounting."
+    );
+    let accounting = HostFeatures {
+        return_value_accounting: true,
+        ..HostFeatures::default()
+    };
+    let small = remaining(resume(8, ENERGY, accounting)?)?;
+    let large = remaining(resume(1008, ENERGY, accounting)?)?;
+    ensure!(
+        small - large
+            == constants::receive_return_value_cost(1008) - constants::receive_return_value_cost(8),
+        "The return value should be charged by its size."
+    );
+    ensure!(
+        matches!(
+            resume(1008, constants::receive_return_value_cost(1008) - 1, accounting)?,
+            ReceiveResult::OutOfEnergy
+        ),
+        "Receiving the return value should run out of energy."
+    );
+    match resume(constants::MAX_RETAINED_RETURN_VALUES_SIZE + 1, ENERGY, accounting)? {
+        ReceiveResult::Trap {
+            error,
+            ..
+        } => ensure!(
+            error.downcast_ref::<ReturnValuesTooLarge>()
+                == Some(&ReturnValuesTooLarge {
+                    size: constants::MAX_RETAINED_RETURN_VALUES_SIZE + 1,
+                }),
+            "Unexpected error: {}.",
+            error
+        ),
+        other => bail!("The return value should be too large, got {:?}.", other.extract().status),
+    }
+    Ok(())
+}
+
+#[test]
+/// Check that init functions can call contracts, and that the response to the
+/// call and its return value are available to the contract when execution is
+/// resumed, but only with init interrupts.
+fn test_init_interrupt() -> anyhow::Result<()> {
+    let artifact = artifact()?;
+    let invoke = |host_features: HostFeatures| {
+        super::invoke_init(
+            artifact.clone(),
+            0,
+            init_ctx(),
+            "init_test",
+            &[],
+            InterpreterEnergy::from(ENERGY),
+            Loader {
+                inner: Vec::<u8>::new(),
+            },
+            InvokeOptions {
+                host_features,
+                ..InvokeOptions::default()
+            },
+        )
+    };
+    ensure!(
+        matches!(invoke(HostFeatures::default())?, InitResult::Trap { .. }),
+        "Init should trap on invoke without init interrupts."
+    );
+    let result = invoke(HostFeatures {
+        init_interrupts: true,
+        ..HostFeatures::default()
+    })?;
+    let config = match result {
+        InitResult::Interrupt {
+            config,
+            interrupt: Interrupt::Call {
+                address,
+                ..
+            },
+            ..
+        } => {
+            ensure!(
+                address
+                    == ContractAddress {
+                        index:    1,
+                        subindex: 2,
+                    },
+                "Incorrect address of the called contract."
+            );
+            config
+        }
+        other => bail!("Init should be interrupted, got {:?}.", other.extract().status),
+    };
+    ensure!(config.nested_call_depth().depth == 1, "Incorrect depth of the nested call.");
+    let response = InvokeResponse::Success {
+        state_updated: false,
+        new_balance:   Amount::from_micro_ccd(0),
+        data:          Some(vec![7u8; 8]),
+    };
+    let result = super::resume_init(
+        config,
+        response,
+        InterpreterEnergy::from(ENERGY),
+        Loader {
+            inner: Vec::<u8>::new(),
+        },
+        InvokeOptions::default(),
+    )?;
+    match result {
+        InitResult::Success {
+            return_value,
+            ..
+        } => {
+            // The return value of the call is the second parameter.
+            let expected = [(1u64 << 40).to_le_bytes(), [7u8; 8]].concat();
+            ensure!(return_value == expected, "Unexpected return value {:?}.", return_value);
+        }
+        other => bail!("Init should succeed, got {:?}.", other.extract().status),
+    }
+    Ok(())
+}
+
+#[test]
+/// Check that init functions are charged according to the cost table of their
+/// options.
+fn test_init_cost_table() -> anyhow::Result<()> {
+    let artifact = artifact()?;
+    let remaining = |costs: constants::CostTable| -> anyhow::Result<u64> {
+        let result = super::invoke_init(
+            artifact.clone(),
+            0,
+            init_ctx(),
+            "init_test",
+            &[],
+            InterpreterEnergy::from(ENERGY),
+            Loader {
+                inner: Vec::<u8>::new(),
+            },
+            InvokeOptions {
+                host_features: HostFeatures {
+                    init_interrupts: true,
+                    ..HostFeatures::default()
+                },
+                costs,
+                ..InvokeOptions::default()
+            },
+        )?;
+        match result {
+            InitResult::Interrupt {
+                remaining_energy,
+                ..
+            } => Ok(remaining_energy),
+            other => bail!("Init should be interrupted, got {:?}.", other.extract().status),
+        }
+    };
+    let default = remaining(constants::CostTable::V1)?;
+    let expensive = remaining(constants::CostTable {
+        invoke: constants::CostTable::V1.invoke + 1000,
+        ..constants::CostTable::V1
+    })?;
+    ensure!(default - expensive == 1000, "The cost table should apply to init functions.");
+    Ok(())
+}
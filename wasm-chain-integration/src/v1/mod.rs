@@ -1,14 +1,25 @@
 #[cfg(test)]
 mod crypto_primitives_tests;
 #[cfg(test)]
+mod invoke_tests;
+#[cfg(test)]
 mod tests;
 
+pub mod completion;
+pub mod faults;
 #[cfg(feature = "enable-ffi")]
 mod ffi;
+pub mod import_policy;
+pub mod trace;
 pub mod trie;
 mod types;
 
-use crate::{constants, v0, ExecResult, InterpreterEnergy, OutOfEnergy};
+use crate::{
+    constants::{self, CostTable},
+    display::{DisplayAccountAddress, DisplayAmount, DisplayContractAddress},
+    reject::{RejectReason, RejectReasonRegistry, ReservedReason},
+    v0, ExecResult, InterpreterEnergy, OutOfEnergy,
+};
 use anyhow::{bail, ensure};
 use concordium_contracts_common::{
     AccountAddress, Address, Amount, ChainMetadata, ContractAddress, EntrypointName,
@@ -16,17 +27,23 @@ use concordium_contracts_common::{
 };
 use machine::Value;
 use sha3::Digest;
-use std::{borrow::Borrow, io::Write, sync::Arc};
+use std::{
+    convert::{TryFrom, TryInto},
+    io::Write,
+    sync::Arc,
+};
 use trie::BackingStoreLoad;
 pub use types::*;
 use wasm_transform::{
     artifact::{Artifact, CompiledFunction, CompiledFunctionBytes, RunnableCode},
-    machine::{self, ExecutionOutcome, NoInterrupt},
+    machine::{self, ExecutionOutcome, RuntimeError},
     utils,
 };
 
 /// Interrupt triggered by the smart contract to execute an instruction on the
-/// host, either an account transfer or a smart contract call.
+/// host, either an account transfer, a smart contract call, a query about
+/// another contract, an account or the exchange rates, or an upgrade of the
+/// contract.
 #[derive(Debug)]
 pub enum Interrupt {
     Transfer {
@@ -39,6 +56,419 @@ pub enum Interrupt {
         name:      OwnedEntrypointName,
         amount:    Amount,
     },
+    /// Query information about a contract. The scheduler must respond as
+    /// described in [ContractQuery::decode_response].
+    QueryContract {
+        address: ContractAddress,
+        query:   ContractQuery,
+    },
+    /// Query the balance of an account. The scheduler must respond as
+    /// described in [decode_account_balance_response].
+    QueryAccountBalance {
+        address: AccountAddress,
+    },
+    /// Query the current exchange rates in order to compute the conversion.
+    /// The scheduler must respond as described in
+    /// [ExchangeRates::decode_response]. The conversion itself is computed
+    /// when execution is resumed, so it is not sent to the scheduler.
+    QueryExchangeRates {
+        conversion: Conversion,
+    },
+    /// Replace the module of the instance with the module with the given
+    /// reference. The scheduler must respond as described in
+    /// [decode_upgrade_response].
+    Upgrade {
+        module_ref: [u8; MODULE_REFERENCE_SIZE],
+    },
+}
+
+/// Size of a module reference, which is the hash of the module.
+pub const MODULE_REFERENCE_SIZE: usize = 32;
+
+/// Information about another contract that can be queried by a contract,
+/// using the `contract_exists` and `contract_state_size` host functions.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContractQuery {
+    /// Whether the contract exists.
+    Exists    = 0,
+    /// The size of the state of the contract in bytes.
+    StateSize = 1,
+}
+
+impl std::fmt::Display for Interrupt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Interrupt::Transfer {
+                to,
+                amount,
+            } => write!(
+                f,
+                "transfer {} to {}",
+                DisplayAmount::from(*amount),
+                DisplayAccountAddress(to)
+            ),
+            Interrupt::Call {
+                address,
+                parameter,
+                name,
+                amount,
+            } => write!(
+                f,
+                "call {}, entrypoint '{}', with {} and a parameter of {} bytes",
+                DisplayContractAddress(address),
+                name,
+                DisplayAmount::from(*amount),
+                parameter.len()
+            ),
+            Interrupt::QueryContract {
+                address,
+                query: ContractQuery::Exists,
+            } => write!(f, "query whether {} exists", DisplayContractAddress(address)),
+            Interrupt::QueryContract {
+                address,
+                query: ContractQuery::StateSize,
+            } => write!(f, "query the state size of {}", DisplayContractAddress(address)),
+            Interrupt::QueryAccountBalance {
+                address,
+            } => write!(f, "query the balance of {}", DisplayAccountAddress(address)),
+            Interrupt::QueryExchangeRates {
+                ..
+            } => write!(f, "query the exchange rates"),
+            Interrupt::Upgrade {
+                module_ref,
+            } => {
+                write!(f, "upgrade to module ")?;
+                module_ref.iter().try_for_each(|b| write!(f, "{:02x}", b))
+            }
+        }
+    }
+}
+
+/// Failure code the scheduler responds with if the contract of a
+/// [Interrupt::QueryContract] does not exist. This is the same code as for
+/// calls to missing contracts, see [decode_invoke_response].
+const MISSING_CONTRACT_CODE: u64 = 0x03 << 32;
+
+impl ContractQuery {
+    /// Decode the response of the scheduler to the query into the value that
+    /// is returned to the contract.
+    ///
+    /// - If the contract does not exist the scheduler responds with
+    ///   [InvokeResponse::Failure] with code `0x03 << 32`, the same as for
+    ///   calls to a missing contract. The value is then `0` for
+    ///   [ContractQuery::Exists] and `-1` for [ContractQuery::StateSize].
+    /// - Otherwise it responds with [InvokeResponse::Success]. For
+    ///   [ContractQuery::StateSize] the data of the response must be the size
+    ///   of the state as a little-endian `u64`, and the value is the size. For
+    ///   [ContractQuery::Exists] the value is `1`.
+    pub(crate) fn decode_response(self, response: InvokeResponse) -> ExecResult<i64> {
+        match response {
+            InvokeResponse::Success {
+                data,
+                ..
+            } => match self {
+                ContractQuery::Exists => Ok(1),
+                ContractQuery::StateSize => {
+                    let data = data.unwrap_or_default();
+                    let bytes: [u8; 8] = data.as_slice().try_into().map_err(|_| {
+                        anyhow::anyhow!(
+                            "The state size must be 8 bytes, but the response has {}.",
+                            data.len()
+                        )
+                    })?;
+                    Ok(i64::try_from(u64::from_le_bytes(bytes))?)
+                }
+            },
+            InvokeResponse::Failure {
+                code,
+                ..
+            } => {
+                ensure!(
+                    code == MISSING_CONTRACT_CODE,
+                    "Unexpected failure {:#x} in response to a contract query.",
+                    code
+                );
+                match self {
+                    ContractQuery::Exists => Ok(0),
+                    ContractQuery::StateSize => Ok(-1),
+                }
+            }
+        }
+    }
+}
+
+/// Failure code the scheduler responds with if the account of a
+/// [Interrupt::QueryAccountBalance] does not exist. This is the same code as
+/// for transfers to missing accounts, see [decode_invoke_response].
+const MISSING_ACCOUNT_CODE: u64 = 0x02 << 32;
+
+/// Decode the response of the scheduler to a [Interrupt::QueryAccountBalance]
+/// into the value that is returned to the contract.
+///
+/// - If the account does not exist the scheduler responds with
+///   [InvokeResponse::Failure] with code `0x02 << 32`, the same as for
+///   transfers to a missing account. The value is then `-1`.
+/// - Otherwise it responds with [InvokeResponse::Success], with the balance in
+///   microCCD as a little-endian `u64` as data, and the value is the balance.
+pub(crate) fn decode_account_balance_response(response: InvokeResponse) -> ExecResult<i64> {
+    match response {
+        InvokeResponse::Success {
+            data,
+            ..
+        } => {
+            let data = data.unwrap_or_default();
+            let bytes: [u8; 8] = data.as_slice().try_into().map_err(|_| {
+                anyhow::anyhow!("The balance must be 8 bytes, but the response has {}.", data.len())
+            })?;
+            Ok(i64::try_from(u64::from_le_bytes(bytes))?)
+        }
+        InvokeResponse::Failure {
+            code,
+            ..
+        } => {
+            ensure!(
+                code == MISSING_ACCOUNT_CODE,
+                "Unexpected failure {:#x} in response to an account balance query.",
+                code
+            );
+            Ok(-1)
+        }
+    }
+}
+
+/// A conversion at the current exchange rates, requested by the
+/// `energy_to_micro_ccd`, `micro_ccd_to_energy`, `micro_euro_to_micro_ccd`,
+/// and `micro_ccd_to_micro_euro` host functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conversion {
+    /// Convert the given amount of energy to microCCD.
+    EnergyToMicroCcd(u64),
+    /// Convert the given amount of microCCD to energy.
+    MicroCcdToEnergy(u64),
+    /// Convert the given amount of microeuros to microCCD.
+    MicroEuroToMicroCcd(u64),
+    /// Convert the given amount of microCCD to microeuros.
+    MicroCcdToMicroEuro(u64),
+}
+
+impl Conversion {
+    /// Compute the conversion at the given rates, see the methods of
+    /// [ExchangeRates].
+    pub fn apply(self, rates: &ExchangeRates) -> u64 {
+        match self {
+            Conversion::EnergyToMicroCcd(energy) => rates.energy_to_micro_ccd(energy),
+            Conversion::MicroCcdToEnergy(amount) => rates.micro_ccd_to_energy(amount),
+            Conversion::MicroEuroToMicroCcd(amount) => rates.micro_euro_to_micro_ccd(amount),
+            Conversion::MicroCcdToMicroEuro(amount) => rates.micro_ccd_to_micro_euro(amount),
+        }
+    }
+}
+
+/// The exchange rates that determine the price of energy. Each rate is a
+/// fraction given by its numerator and denominator, both of which are
+/// non-zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExchangeRates {
+    /// The price of one unit of energy in euros.
+    pub euro_per_energy:    (u64, u64),
+    /// The price of one euro in microCCD.
+    pub micro_ccd_per_euro: (u64, u64),
+}
+
+impl ExchangeRates {
+    /// The price of the given amount of energy in microCCD, rounded up. This
+    /// is the same as the price the node charges for energy. The result is
+    /// computed exactly, and is [u64::MAX] if it does not fit in a `u64`.
+    pub fn energy_to_micro_ccd(&self, energy: u64) -> u64 {
+        let (e_num, e_den) = self.euro_per_energy;
+        let (c_num, c_den) = self.micro_ccd_per_euro;
+        mul_div(
+            energy,
+            u128::from(e_num) * u128::from(c_num),
+            u128::from(e_den) * u128::from(c_den),
+            true,
+        )
+        .unwrap_or(u64::MAX)
+    }
+
+    /// The amount of energy that can be bought with the given amount of
+    /// microCCD, rounded down. The result is computed exactly, and is
+    /// [u64::MAX] if it does not fit in a `u64`.
+    pub fn micro_ccd_to_energy(&self, amount: u64) -> u64 {
+        let (e_num, e_den) = self.euro_per_energy;
+        let (c_num, c_den) = self.micro_ccd_per_euro;
+        mul_div(
+            amount,
+            u128::from(e_den) * u128::from(c_den),
+            u128::from(e_num) * u128::from(c_num),
+            false,
+        )
+        .unwrap_or(u64::MAX)
+    }
+
+    /// The price of the given amount of microeuros in microCCD, rounded up, so
+    /// that a payment of the result covers a price given in euros. The result
+    /// is computed exactly, and is [u64::MAX] if it does not fit in a `u64`.
+    pub fn micro_euro_to_micro_ccd(&self, amount: u64) -> u64 {
+        let (c_num, c_den) = self.micro_ccd_per_euro;
+        mul_div(amount, u128::from(c_num), u128::from(c_den) * 1_000_000, true).unwrap_or(u64::MAX)
+    }
+
+    /// The value of the given amount of microCCD in microeuros, rounded down.
+    /// The result is computed exactly, and is [u64::MAX] if it does not fit in
+    /// a `u64`.
+    pub fn micro_ccd_to_micro_euro(&self, amount: u64) -> u64 {
+        let (c_num, c_den) = self.micro_ccd_per_euro;
+        mul_div(amount, u128::from(c_den) * 1_000_000, u128::from(c_num), false).unwrap_or(u64::MAX)
+    }
+
+    /// Decode the response of the scheduler to an
+    /// [Interrupt::QueryExchangeRates]. The scheduler always responds with
+    /// [InvokeResponse::Success], with the numerator and denominator of
+    /// [euro_per_energy](Self::euro_per_energy) followed by those of
+    /// [micro_ccd_per_euro](Self::micro_ccd_per_euro) as data, each as a
+    /// little-endian `u64`.
+    pub(crate) fn decode_response(response: InvokeResponse) -> ExecResult<Self> {
+        match response {
+            InvokeResponse::Success {
+                data,
+                ..
+            } => {
+                let data = data.unwrap_or_default();
+                ensure!(
+                    data.len() == 32,
+                    "The exchange rates must be 32 bytes, but the response has {}.",
+                    data.len()
+                );
+                let mut values = [0u64; 4];
+                for (value, bytes) in values.iter_mut().zip(data.chunks_exact(8)) {
+                    let mut buf = [0u8; 8];
+                    buf.copy_from_slice(bytes);
+                    *value = u64::from_le_bytes(buf);
+                }
+                ensure!(
+                    values.iter().all(|v| *v != 0),
+                    "The exchange rates must be non-zero, but are {:?}.",
+                    values
+                );
+                Ok(Self {
+                    euro_per_energy:    (values[0], values[1]),
+                    micro_ccd_per_euro: (values[2], values[3]),
+                })
+            }
+            InvokeResponse::Failure {
+                code,
+                ..
+            } => bail!("Unexpected failure {:#x} in response to an exchange rate query.", code),
+        }
+    }
+}
+
+/// Compute `x * num / den` exactly, rounding up if `round_up` is set and down
+/// otherwise. Returns [None] if the result does not fit in a `u64`. The
+/// denominator must be non-zero.
+fn mul_div(x: u64, num: u128, den: u128, round_up: bool) -> Option<u64> {
+    // The product has at most 192 bits. It is split into the upper 128 bits
+    // and the lower 64 bits.
+    let low = u128::from(x) * (num & u128::from(u64::MAX));
+    let high = u128::from(x) * (num >> 64) + (low >> 64);
+    if high >= den {
+        // The quotient is at least 2^64.
+        return None;
+    }
+    // Long division of the lower 64 bits, with the remainder of the upper
+    // bits as the initial remainder. The remainder is always less than the
+    // denominator, so the quotient fits in 64 bits.
+    let mut rem = high;
+    let mut quot = 0u64;
+    for i in (0..64).rev() {
+        let carry = rem >> 127;
+        rem = (rem << 1) | ((low >> i) & 1);
+        quot <<= 1;
+        if carry == 1 || rem >= den {
+            rem = rem.wrapping_sub(den);
+            quot |= 1;
+        }
+    }
+    if round_up && rem != 0 {
+        quot.checked_add(1)
+    } else {
+        Some(quot)
+    }
+}
+
+/// Failure codes the scheduler may respond with to an [Interrupt::Upgrade]. The
+/// module does not exist, it is not a V1 module, or it does not contain the
+/// contract of the instance, respectively.
+const UPGRADE_FAILURE_CODES: [u64; 3] = [0x07 << 32, 0x08 << 32, 0x09 << 32];
+
+/// Decode the response of the scheduler to an [Interrupt::Upgrade] into the
+/// value that is returned to the contract.
+///
+/// - If the upgrade succeeded the scheduler responds with
+///   [InvokeResponse::Success], and the value is `0`. The new module is used
+///   for subsequent invocations of the instance, while the current execution
+///   continues with the old one.
+/// - Otherwise it responds with [InvokeResponse::Failure] with code `0x07 <<
+///   32` if the module does not exist, `0x08 << 32` if it is not a V1 module,
+///   and `0x09 << 32` if it does not contain the contract of the instance. The
+///   value is the code, which follows the convention for errors of
+///   [decode_invoke_response].
+pub(crate) fn decode_upgrade_response(response: InvokeResponse) -> ExecResult<u64> {
+    match response {
+        InvokeResponse::Success {
+            ..
+        } => Ok(0),
+        InvokeResponse::Failure {
+            code,
+            ..
+        } => {
+            ensure!(
+                UPGRADE_FAILURE_CODES.contains(&code),
+                "Unexpected failure {:#x} in response to an upgrade.",
+                code
+            );
+            Ok(code)
+        }
+    }
+}
+
+/// A query or upgrade that interrupted execution. The response to these is
+/// returned to the contract differently from the response to an invoke.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PendingQuery {
+    Contract(ContractQuery),
+    AccountBalance,
+    Conversion(Conversion),
+    Upgrade,
+}
+
+impl PendingQuery {
+    /// The query that caused the interrupt, if any.
+    pub(crate) fn of_interrupt(interrupt: &Interrupt) -> Option<Self> {
+        match interrupt {
+            Interrupt::QueryContract {
+                query,
+                ..
+            } => Some(PendingQuery::Contract(*query)),
+            Interrupt::QueryAccountBalance {
+                ..
+            } => Some(PendingQuery::AccountBalance),
+            Interrupt::QueryExchangeRates {
+                conversion,
+            } => Some(PendingQuery::Conversion(*conversion)),
+            Interrupt::Upgrade {
+                ..
+            } => Some(PendingQuery::Upgrade),
+            Interrupt::Transfer {
+                ..
+            }
+            | Interrupt::Call {
+                ..
+            } => None,
+        }
+    }
 }
 
 impl Interrupt {
@@ -70,6 +500,36 @@ impl Interrupt {
                 out.write_all(&amount.micro_ccd.to_be_bytes())?;
                 Ok(())
             }
+            Interrupt::QueryContract {
+                address,
+                query,
+            } => {
+                out.push(2u8);
+                out.write_all(&address.index.to_be_bytes())?;
+                out.write_all(&address.subindex.to_be_bytes())?;
+                out.push(*query as u8);
+                Ok(())
+            }
+            Interrupt::QueryAccountBalance {
+                address,
+            } => {
+                out.push(3u8);
+                out.write_all(address.as_ref())?;
+                Ok(())
+            }
+            Interrupt::QueryExchangeRates {
+                ..
+            } => {
+                out.push(5u8);
+                Ok(())
+            }
+            Interrupt::Upgrade {
+                module_ref,
+            } => {
+                out.push(4u8);
+                out.write_all(module_ref)?;
+                Ok(())
+            }
         }
     }
 }
@@ -93,8 +553,14 @@ pub struct InitHost<'a, BackingStore, ParamType, Ctx> {
     pub state:             InstanceState<'a, BackingStore>,
     /// The response from the call.
     pub return_value:      ReturnValue,
-    /// The parameter to the init method.
-    pub parameter:         ParamType,
+    /// The parameter to the init method, as well as any responses from calls
+    /// to other contracts during execution.
+    pub parameters:        Vec<ParamType>,
+    /// Cursors into the parameters opened by the contract. These are retained
+    /// across interrupts.
+    pub parameter_cursors: ParameterCursors,
+    /// Number of blocks of randomness returned by `get_random` so far.
+    pub random_counter:    u64,
     /// The init context for this invocation.
     pub init_ctx:          Ctx,
 }
@@ -110,7 +576,9 @@ impl<'a, 'b, BackingStore, Ctx2, Ctx1: Into<Ctx2>>
             logs:              host.logs,
             state:             host.state,
             return_value:      host.return_value,
-            parameter:         host.parameter.into(),
+            parameters:        host.parameters.into_iter().map(|x| x.to_vec()).collect(),
+            parameter_cursors: host.parameter_cursors,
+            random_counter:    host.random_counter,
             init_ctx:          host.init_ctx.into(),
         }
     }
@@ -145,6 +613,12 @@ pub struct StateLessReceiveHost<ParamType, Ctx> {
     /// The parameter to the receive method, as well as any responses from
     /// calls to other contracts during execution.
     pub parameters:        Vec<ParamType>,
+    /// Cursors into the parameters opened by the contract. These are retained
+    /// across interrupts.
+    pub parameter_cursors: ParameterCursors,
+    /// Number of blocks of randomness returned by `get_random` so far. This
+    /// is retained across interrupts.
+    pub random_counter:    u64,
     /// The receive context for this call.
     pub receive_ctx:       Ctx,
 }
@@ -158,11 +632,66 @@ impl<'a, Ctx2, Ctx1: Into<Ctx2>> From<StateLessReceiveHost<ParameterRef<'a>, Ctx
             logs:              host.logs,
             return_value:      host.return_value,
             parameters:        host.parameters.into_iter().map(|x| x.to_vec()).collect(),
+            parameter_cursors: host.parameter_cursors,
+            random_counter:    host.random_counter,
             receive_ctx:       host.receive_ctx.into(),
         }
     }
 }
 
+/// The invocation that pseudo-random bytes are produced for, see
+/// [fill_random]. This separates the randomness of different invocations with
+/// the same seed.
+#[derive(Debug, Clone, Copy)]
+pub enum RandomInvocation<'a> {
+    /// An init function, invoked by the given account.
+    Init {
+        origin: &'a AccountAddress,
+    },
+    /// A receive function of the given contract.
+    Receive {
+        address: &'a ContractAddress,
+    },
+}
+
+/// Fill the buffer with pseudo-random bytes derived from the seed. The bytes
+/// are produced in blocks of 32, each of which is the SHA3-256 digest of the
+/// block seed, the nonce of the invocation, the invocation, which is the origin
+/// of an init function prefixed by 0 or the address of the contract prefixed by
+/// 1, and the counter, which is incremented for each block. The remainder of
+/// the last block is discarded.
+pub fn fill_random(
+    seed: &v0::RandomSeed,
+    invocation: RandomInvocation,
+    counter: &mut u64,
+    out: &mut [u8],
+) {
+    for chunk in out.chunks_mut(32) {
+        let mut hasher = sha3::Sha3_256::new();
+        hasher.update(seed.block_seed);
+        hasher.update(seed.nonce.to_be_bytes());
+        match invocation {
+            RandomInvocation::Init {
+                origin,
+            } => {
+                hasher.update([0u8]);
+                hasher.update(origin.0);
+            }
+            RandomInvocation::Receive {
+                address,
+            } => {
+                hasher.update([1u8]);
+                hasher.update(address.index.to_be_bytes());
+                hasher.update(address.subindex.to_be_bytes());
+            }
+        }
+        hasher.update(counter.to_be_bytes());
+        *counter += 1;
+        let digest = hasher.finalize();
+        chunk.copy_from_slice(&digest[..chunk.len()]);
+    }
+}
+
 mod host {
     //! v1 host function implementations. Functions in this inner module are
     //! mostly just wrappers. They parse relevant arguments from the
@@ -193,6 +722,7 @@ mod host {
     /// insufficient energy.
     fn parse_call_args(
         energy: &mut InterpreterEnergy,
+        costs: &CostTable,
         cursor: &mut Cursor<&[u8]>,
     ) -> ParseResult<Result<Interrupt, OutOfEnergy>> {
         let address = cursor.get()?;
@@ -200,7 +730,7 @@ mod host {
         if usize::from(parameter_len) > constants::MAX_PARAMETER_SIZE {
             return Err(ParseError {});
         }
-        if energy.tick_energy(constants::copy_to_host_cost(parameter_len.into())).is_err() {
+        if energy.tick_energy(costs.copy_to_host.cost(parameter_len.into())).is_err() {
             return Ok(Err(OutOfEnergy));
         }
         let start = cursor.offset;
@@ -224,6 +754,7 @@ mod host {
     fn write_return_value_helper(
         rv: &mut ReturnValue,
         energy: &mut InterpreterEnergy,
+        costs: &CostTable,
         offset: u32,
         bytes: &[u8],
     ) -> ExecResult<u32> {
@@ -236,9 +767,9 @@ mod host {
             as usize;
         let end = std::cmp::min(end, constants::MAX_CONTRACT_STATE as usize) as u32;
         if rv.len() < end as usize {
-            energy.tick_energy(constants::additional_output_size_cost(
-                u64::from(end) - rv.len() as u64,
-            ))?;
+            energy.tick_energy(
+                costs.additional_output_byte.saturating_mul(u64::from(end) - rv.len() as u64),
+            )?;
             rv.resize(end as usize, 0u8);
         }
         let written = (&mut rv[offset..end as usize]).write(bytes)?;
@@ -250,16 +781,17 @@ mod host {
         memory: &mut Vec<u8>,
         stack: &mut machine::RuntimeStack,
         energy: &mut InterpreterEnergy,
+        costs: &CostTable,
         rv: &mut ReturnValue,
     ) -> machine::RunResult<()> {
         let offset = unsafe { stack.pop_u32() };
         let length = unsafe { stack.pop_u32() };
         let start = unsafe { stack.pop_u32() } as usize;
         // charge energy linearly in the amount of data written.
-        energy.tick_energy(constants::write_output_cost(length))?;
+        energy.tick_energy(costs.write_output.cost(length))?;
         let end = start + length as usize; // this cannot overflow on 64-bit machines.
         ensure!(end <= memory.len(), "Illegal memory access.");
-        let res = write_return_value_helper(rv, energy, offset, &memory[start..end])?;
+        let res = write_return_value_helper(rv, energy, costs, offset, &memory[start..end])?;
         stack.push_value(res);
         Ok(())
     }
@@ -270,8 +802,9 @@ mod host {
         memory: &mut Vec<u8>,
         stack: &mut machine::RuntimeStack,
         energy: &mut InterpreterEnergy,
+        costs: &CostTable,
     ) -> machine::RunResult<Option<Interrupt>> {
-        energy.tick_energy(constants::INVOKE_BASE_COST)?;
+        energy.tick_energy(costs.invoke)?;
         let length = unsafe { stack.pop_u32() } as usize; // length of the instruction payload in memory
         let start = unsafe { stack.pop_u32() } as usize; // start of the instruction payload in memory
         let tag = unsafe { stack.pop_u32() }; // tag of the instruction
@@ -303,7 +836,7 @@ mod host {
             CALL_TAG => {
                 ensure!(start + length <= memory.len(), "Illegal memory access.");
                 let mut cursor = Cursor::new(&memory[start..start + length]);
-                match parse_call_args(energy, &mut cursor) {
+                match parse_call_args(energy, costs, &mut cursor) {
                     Ok(Ok(i)) => Ok(Some(i)),
                     Ok(Err(OutOfEnergy)) => bail!(OutOfEnergy),
                     Err(e) => bail!("Illegal call, cannot parse arguments: {:?}", e),
@@ -313,6 +846,99 @@ mod host {
         }
     }
 
+    #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
+    /// Handle the `contract_exists` and `contract_state_size` functions. The
+    /// index and subindex of the contract to query are on the stack.
+    pub fn query_contract(
+        stack: &mut machine::RuntimeStack,
+        energy: &mut InterpreterEnergy,
+        costs: &CostTable,
+        query: ContractQuery,
+    ) -> machine::RunResult<Interrupt> {
+        energy.tick_energy(costs.contract_query)?;
+        let subindex = unsafe { stack.pop_u64() };
+        let index = unsafe { stack.pop_u64() };
+        Ok(Interrupt::QueryContract {
+            address: ContractAddress {
+                index,
+                subindex,
+            },
+            query,
+        })
+    }
+
+    #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
+    /// Handle the `get_account_balance` function. The pointer to the address
+    /// of the account to query is on the stack.
+    pub fn get_account_balance(
+        memory: &mut Vec<u8>,
+        stack: &mut machine::RuntimeStack,
+        energy: &mut InterpreterEnergy,
+        costs: &CostTable,
+    ) -> machine::RunResult<Interrupt> {
+        energy.tick_energy(costs.account_balance_query)?;
+        let start = unsafe { stack.pop_u32() } as usize;
+        // Overflow is not possible in the next line on 64-bit machines.
+        ensure!(start + ACCOUNT_ADDRESS_SIZE <= memory.len(), "Illegal memory access.");
+        let mut addr_bytes = [0u8; ACCOUNT_ADDRESS_SIZE];
+        addr_bytes.copy_from_slice(&memory[start..start + ACCOUNT_ADDRESS_SIZE]);
+        Ok(Interrupt::QueryAccountBalance {
+            address: AccountAddress(addr_bytes),
+        })
+    }
+
+    #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
+    /// Handle the `energy_to_micro_ccd`, `micro_ccd_to_energy`,
+    /// `micro_euro_to_micro_ccd`, and `micro_ccd_to_micro_euro` functions.
+    /// The value to convert is on the stack, and the conversion is computed
+    /// when execution resumes with the exchange rates.
+    pub fn query_exchange_rates(
+        stack: &mut machine::RuntimeStack,
+        energy: &mut InterpreterEnergy,
+        costs: &CostTable,
+        conversion: fn(u64) -> Conversion,
+    ) -> machine::RunResult<Interrupt> {
+        energy.tick_energy(costs.exchange_rates_query)?;
+        let value = unsafe { stack.pop_u64() };
+        Ok(Interrupt::QueryExchangeRates {
+            conversion: conversion(value),
+        })
+    }
+
+    #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
+    /// Handle the `upgrade` function. The pointer to the reference of the
+    /// module to upgrade to is on the stack.
+    pub fn upgrade(
+        memory: &mut Vec<u8>,
+        stack: &mut machine::RuntimeStack,
+        energy: &mut InterpreterEnergy,
+        costs: &CostTable,
+    ) -> machine::RunResult<Interrupt> {
+        energy.tick_energy(costs.upgrade)?;
+        let start = unsafe { stack.pop_u32() } as usize;
+        // Overflow is not possible in the next line on 64-bit machines.
+        ensure!(start + MODULE_REFERENCE_SIZE <= memory.len(), "Illegal memory access.");
+        let mut module_ref = [0u8; MODULE_REFERENCE_SIZE];
+        module_ref.copy_from_slice(&memory[start..start + MODULE_REFERENCE_SIZE]);
+        Ok(Interrupt::Upgrade {
+            module_ref,
+        })
+    }
+
+    #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
+    /// Write the sender to memory as [v0::host::write_address] does, and
+    /// return the number of bytes written.
+    pub fn get_receive_sender_with_length(
+        memory: &mut Vec<u8>,
+        stack: &mut machine::RuntimeStack,
+        sender: ExecResult<&Address>,
+    ) -> machine::RunResult<()> {
+        let start = unsafe { stack.pop_u32() } as usize;
+        let len = v0::host::write_address(memory, start, sender?)?;
+        stack.push_value(len);
+        Ok(())
+    }
+
     #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
     /// Get the parameter size. This differs from the v0 version in that it
     /// expects an argument on the stack to indicate which parameter to use.
@@ -338,6 +964,7 @@ mod host {
         memory: &mut Vec<u8>,
         stack: &mut machine::RuntimeStack,
         energy: &mut InterpreterEnergy,
+        costs: &CostTable,
         parameters: &[impl AsRef<[u8]>],
     ) -> machine::RunResult<()> {
         let offset = unsafe { stack.pop_u32() } as usize;
@@ -345,7 +972,7 @@ mod host {
         let start = unsafe { stack.pop_u32() } as usize;
         let param_num = unsafe { stack.pop_u32() } as usize;
         // charge energy linearly in the amount of data written.
-        energy.tick_energy(constants::copy_from_host_cost(length))?;
+        energy.tick_energy(costs.copy_from_host.cost(length))?;
         if let Some(param) = parameters.get(param_num as usize) {
             let write_end = start + length as usize; // this cannot overflow on 64-bit machines.
             ensure!(write_end <= memory.len(), "Illegal memory access.");
@@ -359,6 +986,70 @@ mod host {
         Ok(())
     }
 
+    #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
+    /// Handle the `parameter_cursor_open` host function. See
+    /// [ParameterCursors::open] for detailed documentation.
+    pub fn parameter_cursor_open(
+        stack: &mut machine::RuntimeStack,
+        cursors: &mut ParameterCursors,
+        parameters: &[impl AsRef<[u8]>],
+    ) -> machine::RunResult<()> {
+        // the cost of this function is adequately reflected by the base cost of a
+        // function call so we do not charge extra.
+        let param_num = unsafe { stack.pop_u32() };
+        stack.push_value(cursors.open(param_num, parameters));
+        Ok(())
+    }
+
+    #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
+    /// Handle the `parameter_cursor_read` host function. See
+    /// [ParameterCursors::read] for detailed documentation.
+    pub fn parameter_cursor_read(
+        memory: &mut Vec<u8>,
+        stack: &mut machine::RuntimeStack,
+        energy: &mut InterpreterEnergy,
+        costs: &CostTable,
+        cursors: &mut ParameterCursors,
+        parameters: &[impl AsRef<[u8]>],
+    ) -> machine::RunResult<()> {
+        let length = unsafe { stack.pop_u32() };
+        let dest_start = unsafe { stack.pop_u32() } as usize;
+        let cursor = unsafe { stack.pop_u32() };
+        // charge energy linearly in the amount of data written.
+        energy.tick_energy(costs.copy_from_host.cost(length))?;
+        let dest_end = dest_start + length as usize; // this cannot overflow on 64-bit machines.
+        ensure!(dest_end <= memory.len(), "Illegal memory access.");
+        let result = cursors.read(cursor, parameters, &mut memory[dest_start..dest_end]);
+        stack.push_value(result);
+        Ok(())
+    }
+
+    #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
+    /// Handle the `parameter_cursor_seek` host function. See
+    /// [ParameterCursors::seek] for detailed documentation.
+    pub fn parameter_cursor_seek(
+        stack: &mut machine::RuntimeStack,
+        cursors: &mut ParameterCursors,
+        parameters: &[impl AsRef<[u8]>],
+    ) -> machine::RunResult<()> {
+        let position = unsafe { stack.pop_u32() };
+        let cursor = unsafe { stack.pop_u32() };
+        stack.push_value(cursors.seek(cursor, parameters, position));
+        Ok(())
+    }
+
+    #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
+    /// Handle the `parameter_cursor_close` host function. See
+    /// [ParameterCursors::close] for detailed documentation.
+    pub fn parameter_cursor_close(
+        stack: &mut machine::RuntimeStack,
+        cursors: &mut ParameterCursors,
+    ) -> machine::RunResult<()> {
+        let cursor = unsafe { stack.pop_u32() };
+        stack.push_value(cursors.close(cursor));
+        Ok(())
+    }
+
     #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
     /// Handle the `state_lookup_entry` host function. See
     /// [InstanceState::lookup_entry] for detailed documentation.
@@ -371,7 +1062,7 @@ mod host {
         let key_len = unsafe { stack.pop_u32() };
         let key_start = unsafe { stack.pop_u32() } as usize;
         let key_end = key_start + key_len as usize;
-        energy.tick_energy(constants::lookup_entry_cost(key_len))?;
+        energy.tick_energy(state.costs.lookup_entry.cost(key_len))?;
         ensure!(key_end <= memory.len(), "Illegal memory access.");
         let key = &memory[key_start..key_end];
         let result = state.lookup_entry(key);
@@ -391,7 +1082,7 @@ mod host {
         let key_len = unsafe { stack.pop_u32() };
         let key_start = unsafe { stack.pop_u32() } as usize;
         let key_end = key_start + key_len as usize;
-        energy.tick_energy(constants::create_entry_cost(key_len))?;
+        energy.tick_energy(state.costs.create_entry_cost(key_len))?;
         ensure!(key_end <= memory.len(), "Illegal memory access.");
         let key = &memory[key_start..key_end];
         let entry_index = state.create_entry(key)?;
@@ -411,7 +1102,7 @@ mod host {
         let key_len = unsafe { stack.pop_u32() };
         let key_start = unsafe { stack.pop_u32() } as usize;
         let key_end = key_start + key_len as usize;
-        energy.tick_energy(constants::delete_entry_cost(key_len))?;
+        energy.tick_energy(state.costs.delete_entry.cost(key_len))?;
         ensure!(key_end <= memory.len(), "Illegal memory access.");
         let key = &memory[key_start..key_end];
         let result = state.delete_entry(key)?;
@@ -434,7 +1125,7 @@ mod host {
         // this cannot overflow on 64-bit platforms, so it is safe to just add
         ensure!(key_end <= memory.len(), "Illegal memory access.");
         let key = &memory[key_start..key_end];
-        energy.tick_energy(constants::delete_prefix_find_cost(key_len))?;
+        energy.tick_energy(state.costs.delete_prefix_find.cost(key_len))?;
         let result = state.delete_prefix(energy, key)?;
         stack.push_value(result);
         Ok(())
@@ -453,7 +1144,7 @@ mod host {
         let prefix_start = unsafe { stack.pop_u32() } as usize;
         let prefix_end = prefix_start + prefix_len as usize;
         ensure!(prefix_end <= memory.len(), "Illegal memory access.");
-        energy.tick_energy(constants::new_iterator_cost(prefix_len))?;
+        energy.tick_energy(state.costs.new_iterator.cost(prefix_len))?;
         let prefix = &memory[prefix_start..prefix_end];
         let iterator_index = state.iterator(prefix);
         stack.push_value(u64::from(iterator_index));
@@ -495,7 +1186,7 @@ mod host {
         energy: &mut InterpreterEnergy,
         state: &mut InstanceState<BackingStore>,
     ) -> machine::RunResult<()> {
-        energy.tick_energy(constants::ITERATOR_KEY_SIZE_COST)?;
+        energy.tick_energy(state.costs.iterator_key_size)?;
         // the cost of this function is adequately reflected by the base cost of a
         // function call so we do not charge extra.
         let iter = unsafe { stack.pop_u64() };
@@ -516,7 +1207,7 @@ mod host {
         let length = unsafe { stack.pop_u32() };
         let start = unsafe { stack.pop_u32() } as usize;
         let iter = unsafe { stack.pop_u64() };
-        energy.tick_energy(constants::copy_from_host_cost(length))?;
+        energy.tick_energy(state.costs.copy_from_host.cost(length))?;
         let dest_end = start + length as usize;
         ensure!(dest_end <= memory.len(), "Illegal memory access.");
         let dest = &mut memory[start..dest_end];
@@ -525,6 +1216,76 @@ mod host {
         Ok(())
     }
 
+    /// Handle the `state_iterator_token_size` host function. See
+    /// [InstanceState::iterator_token_size] for detailed documentation.
+    pub fn state_iterator_token_size<BackingStore: BackingStoreLoad>(
+        stack: &mut machine::RuntimeStack,
+        energy: &mut InterpreterEnergy,
+        state: &mut InstanceState<BackingStore>,
+    ) -> machine::RunResult<()> {
+        energy.tick_energy(state.costs.iterator_key_size)?;
+        let iter = unsafe { stack.pop_u64() };
+        let result = state.iterator_token_size(InstanceStateIterator::from(iter));
+        stack.push_value(result);
+        Ok(())
+    }
+
+    /// Handle the `state_iterator_token_read` host function. See
+    /// [InstanceState::iterator_token_read] for detailed documentation.
+    pub fn state_iterator_token_read<BackingStore: BackingStoreLoad>(
+        memory: &mut Vec<u8>,
+        stack: &mut machine::RuntimeStack,
+        energy: &mut InterpreterEnergy,
+        state: &mut InstanceState<BackingStore>,
+    ) -> machine::RunResult<()> {
+        let offset = unsafe { stack.pop_u32() };
+        let length = unsafe { stack.pop_u32() };
+        let start = unsafe { stack.pop_u32() } as usize;
+        let iter = unsafe { stack.pop_u64() };
+        energy.tick_energy(state.costs.copy_from_host.cost(length))?;
+        let dest_end = start + length as usize;
+        ensure!(dest_end <= memory.len(), "Illegal memory access.");
+        let dest = &mut memory[start..dest_end];
+        let result = state.iterator_token_read(InstanceStateIterator::from(iter), dest, offset);
+        stack.push_value(result);
+        Ok(())
+    }
+
+    /// Handle the `state_iterator_resume` host function. See
+    /// [InstanceState::iterator_resume] for detailed documentation. This is
+    /// charged as creating an iterator with a prefix of the length of the
+    /// token, which accounts for storing the key the iterator resumes after.
+    pub fn state_iterator_resume<BackingStore: BackingStoreLoad>(
+        memory: &mut Vec<u8>,
+        stack: &mut machine::RuntimeStack,
+        energy: &mut InterpreterEnergy,
+        state: &mut InstanceState<BackingStore>,
+    ) -> machine::RunResult<()> {
+        let token_len = unsafe { stack.pop_u32() };
+        let token_start = unsafe { stack.pop_u32() } as usize;
+        let token_end = token_start + token_len as usize;
+        ensure!(token_end <= memory.len(), "Illegal memory access.");
+        energy.tick_energy(state.costs.new_iterator.cost(token_len))?;
+        let token = &memory[token_start..token_end];
+        let iterator_index = state.iterator_resume(token)?;
+        stack.push_value(u64::from(iterator_index));
+        Ok(())
+    }
+
+    /// Handle the `state_iterator_remaining` host function. See
+    /// [InstanceState::iterator_remaining] for detailed documentation.
+    pub fn state_iterator_remaining<BackingStore: BackingStoreLoad>(
+        stack: &mut machine::RuntimeStack,
+        energy: &mut InterpreterEnergy,
+        state: &mut InstanceState<BackingStore>,
+    ) -> machine::RunResult<()> {
+        let limit = unsafe { stack.pop_u32() };
+        let iter = unsafe { stack.pop_u64() };
+        let result = state.iterator_remaining(energy, InstanceStateIterator::from(iter), limit)?;
+        stack.push_value(result);
+        Ok(())
+    }
+
     /// Handle the `state_entry_read` host function. See
     /// [InstanceState::entry_read] for detailed documentation.
     pub fn state_entry_read<BackingStore: BackingStoreLoad>(
@@ -537,7 +1298,7 @@ mod host {
         let length = unsafe { stack.pop_u32() };
         let dest_start = unsafe { stack.pop_u32() } as usize;
         let entry_index = unsafe { stack.pop_u64() };
-        energy.tick_energy(constants::read_entry_cost(length))?;
+        energy.tick_energy(state.costs.read_entry.cost(length))?;
         let dest_end = dest_start + length as usize;
         ensure!(dest_end <= memory.len(), "Illegal memory access.");
         let dest = &mut memory[dest_start..dest_end];
@@ -558,7 +1319,7 @@ mod host {
         let length = unsafe { stack.pop_u32() };
         let source_start = unsafe { stack.pop_u32() } as usize;
         let entry_index = unsafe { stack.pop_u64() };
-        energy.tick_energy(constants::write_entry_cost(length))?;
+        energy.tick_energy(state.costs.write_entry.cost(length))?;
         let source_end = source_start + length as usize;
         ensure!(source_end <= memory.len(), "Illegal memory access.");
         let source = &memory[source_start..source_end];
@@ -577,24 +1338,51 @@ mod host {
         state: &mut InstanceState<BackingStore>,
     ) -> machine::RunResult<()> {
         let entry_index = unsafe { stack.pop_u64() };
-        energy.tick_energy(constants::ENTRY_SIZE_COST)?;
+        energy.tick_energy(state.costs.entry_size)?;
         let result = state.entry_size(InstanceStateEntry::from(entry_index));
         stack.push_value(result);
         Ok(())
     }
 
-    #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
-    /// Handle the `state_entry_resize` host function. See
-    /// [InstanceState::entry_resize] for detailed documentation.
-    pub fn state_entry_resize<BackingStore: BackingStoreLoad>(
+    /// Handle the `state_entry_hash` host function. This writes the hash of the
+    /// value of the entry to memory without copying the value to memory first.
+    /// Returns 0 on success, and u32::MAX if the entry has been invalidated, in
+    /// which case the memory is not modified. See [InstanceState::entry_hash].
+    pub fn state_entry_hash<BackingStore: BackingStoreLoad>(
+        memory: &mut Vec<u8>,
         stack: &mut machine::RuntimeStack,
         energy: &mut InterpreterEnergy,
         state: &mut InstanceState<BackingStore>,
     ) -> machine::RunResult<()> {
-        energy.tick_energy(constants::RESIZE_ENTRY_BASE_COST)?;
-        let new_size = unsafe { stack.pop_u32() };
+        let output_start = unsafe { stack.pop_u32() } as usize;
+        let algorithm = unsafe { stack.pop_u32() };
         let entry_index = unsafe { stack.pop_u64() };
-        let result = state.entry_resize(energy, InstanceStateEntry::from(entry_index), new_size)?;
+        let algorithm = HashAlgorithm::try_from(algorithm)?;
+        let output_end = output_start + 32;
+        ensure!(output_end <= memory.len(), "Illegal memory access.");
+        energy.tick_energy(state.costs.entry_size)?;
+        match state.entry_hash(energy, InstanceStateEntry::from(entry_index), algorithm)? {
+            Some(hash) => {
+                memory[output_start..output_end].copy_from_slice(&hash);
+                stack.push_value(0u32);
+            }
+            None => stack.push_value(u32::MAX),
+        }
+        Ok(())
+    }
+
+    #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
+    /// Handle the `state_entry_resize` host function. See
+    /// [InstanceState::entry_resize] for detailed documentation.
+    pub fn state_entry_resize<BackingStore: BackingStoreLoad>(
+        stack: &mut machine::RuntimeStack,
+        energy: &mut InterpreterEnergy,
+        state: &mut InstanceState<BackingStore>,
+    ) -> machine::RunResult<()> {
+        energy.tick_energy(state.costs.resize_entry_base)?;
+        let new_size = unsafe { stack.pop_u32() };
+        let entry_index = unsafe { stack.pop_u64() };
+        let result = state.entry_resize(energy, InstanceStateEntry::from(entry_index), new_size)?;
         stack.push_value(result);
         Ok(())
     }
@@ -632,6 +1420,7 @@ mod host {
         memory: &mut Vec<u8>,
         stack: &mut machine::RuntimeStack,
         energy: &mut InterpreterEnergy,
+        costs: &CostTable,
     ) -> machine::RunResult<()> {
         let message_len = unsafe { stack.pop_u32() };
         let message_start = unsafe { stack.pop_u32() };
@@ -644,7 +1433,7 @@ mod host {
         let signature_end = signature_start as usize + 64;
         ensure!(signature_end <= memory.len(), "Illegal memory access.");
         // expensive operations start now.
-        energy.tick_energy(constants::verify_ed25519_cost(message_len))?;
+        energy.tick_energy(costs.verify_ed25519.cost(message_len))?;
         let signature =
             ed25519_zebra::Signature::try_from(&memory[signature_start as usize..signature_end]);
         let message = &memory[message_start as usize..message_end];
@@ -669,6 +1458,7 @@ mod host {
         memory: &mut Vec<u8>,
         stack: &mut machine::RuntimeStack,
         energy: &mut InterpreterEnergy,
+        costs: &CostTable,
     ) -> machine::RunResult<()> {
         let message_start = unsafe { stack.pop_u32() };
         let signature_start = unsafe { stack.pop_u32() };
@@ -680,7 +1470,7 @@ mod host {
         let signature_end = signature_start as usize + 64;
         ensure!(signature_end <= memory.len(), "Illegal memory access.");
         // expensive operations start now.
-        energy.tick_energy(constants::VERIFY_ECDSA_SECP256K1_COST)?;
+        energy.tick_energy(costs.verify_ecdsa_secp256k1)?;
         let signature = secp256k1::ecdsa::Signature::from_compact(
             &memory[signature_start as usize..signature_end],
         );
@@ -701,11 +1491,328 @@ mod host {
         Ok(())
     }
 
+    #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
+    /// Handle the `verify_ecdsa_secp256r1_signature` function. The arguments
+    /// are the same as for `verify_ecdsa_secp256k1_signature`, i.e., a 33
+    /// byte compressed public key, a 64 byte signature consisting of `r`
+    /// followed by `s`, and a 32 byte message, which is meant to be a hash.
+    /// As for secp256k1, signatures with a high `s` are rejected so that
+    /// signatures are not malleable. Signers that do not normalize `s`, such as
+    /// WebAuthn authenticators, must have their signatures normalized before
+    /// they are verified.
+    pub fn verify_ecdsa_secp256r1_signature(
+        memory: &mut Vec<u8>,
+        stack: &mut machine::RuntimeStack,
+        energy: &mut InterpreterEnergy,
+        costs: &CostTable,
+    ) -> machine::RunResult<()> {
+        use p256::ecdsa::signature::hazmat::PrehashVerifier;
+        let message_start = unsafe { stack.pop_u32() } as usize;
+        let signature_start = unsafe { stack.pop_u32() } as usize;
+        let public_key_start = unsafe { stack.pop_u32() } as usize;
+        let message_end = message_start + 32;
+        ensure!(message_end <= memory.len(), "Illegal memory access.");
+        let public_key_end = public_key_start + 33;
+        ensure!(public_key_end <= memory.len(), "Illegal memory access.");
+        let signature_end = signature_start + 64;
+        ensure!(signature_end <= memory.len(), "Illegal memory access.");
+        // expensive operations start now.
+        energy.tick_energy(costs.verify_ecdsa_secp256r1)?;
+        let signature = p256::ecdsa::Signature::try_from(&memory[signature_start..signature_end]);
+        let public_key =
+            p256::ecdsa::VerifyingKey::from_sec1_bytes(&memory[public_key_start..public_key_end]);
+        let verified = match (signature, public_key) {
+            (Ok(signature), Ok(public_key)) => {
+                signature.normalize_s().is_none()
+                    && public_key
+                        .verify_prehash(&memory[message_start..message_end], &signature)
+                        .is_ok()
+            }
+            _ => false,
+        };
+        stack.push_value(u32::from(verified));
+        Ok(())
+    }
+
+    /// Domain separation tag for hashing messages to G1 for BLS signatures,
+    /// using the proof-of-possession scheme of the IETF BLS signature draft
+    /// with signatures in G1 and public keys in G2. Aggregate verification of
+    /// a single message is only secure against rogue key attacks if each of
+    /// the keys has a verified proof of possession, which is the
+    /// responsibility of the contract.
+    const BLS_SIGNATURE_DST: &[u8] = b"BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_POP_";
+
+    /// Size of a compressed G1 point.
+    const BLS_G1_SIZE: usize = 48;
+    /// Size of a compressed G2 point.
+    const BLS_G2_SIZE: usize = 96;
+    /// Size of a serialized scalar.
+    const BLS_SCALAR_SIZE: usize = 32;
+
+    /// Check that the signature is valid for the message with respect to the
+    /// given public key, i.e., that `e(signature, g2) = e(H(message),
+    /// public_key)`.
+    fn bls_check_signature(
+        public_key: bls12_381::G2Affine,
+        signature: &bls12_381::G1Affine,
+        message: &[u8],
+    ) -> bool {
+        use bls12_381::hash_to_curve::{ExpandMsgXmd, HashToCurve};
+        let hashed =
+            bls12_381::G1Affine::from(<bls12_381::G1Projective as HashToCurve<
+                ExpandMsgXmd<sha2_09::Sha256>,
+            >>::hash_to_curve(message, BLS_SIGNATURE_DST));
+        let neg_g2 = bls12_381::G2Prepared::from(-bls12_381::G2Affine::generator());
+        let public_key = bls12_381::G2Prepared::from(public_key);
+        bls12_381::multi_miller_loop(&[(signature, &neg_g2), (&hashed, &public_key)])
+            .final_exponentiation()
+            == bls12_381::Gt::identity()
+    }
+
+    fn bls_g1_from_memory(memory: &[u8], start: usize) -> Option<bls12_381::G1Affine> {
+        let bytes = <&[u8; BLS_G1_SIZE]>::try_from(&memory[start..start + BLS_G1_SIZE]).ok()?;
+        Option::from(bls12_381::G1Affine::from_compressed(bytes))
+    }
+
+    fn bls_g2_from_memory(memory: &[u8], start: usize) -> Option<bls12_381::G2Affine> {
+        let bytes = <&[u8; BLS_G2_SIZE]>::try_from(&memory[start..start + BLS_G2_SIZE]).ok()?;
+        Option::from(bls12_381::G2Affine::from_compressed(bytes))
+    }
+
+    /// Read a public key for signature verification. The identity is rejected
+    /// since it verifies the identity signature on any message.
+    fn bls_public_key_from_memory(memory: &[u8], start: usize) -> Option<bls12_381::G2Affine> {
+        bls_g2_from_memory(memory, start).filter(|key| !bool::from(key.is_identity()))
+    }
+
+    /// Read a signature for signature verification. The identity is rejected,
+    /// see [bls_public_key_from_memory].
+    fn bls_signature_from_memory(memory: &[u8], start: usize) -> Option<bls12_381::G1Affine> {
+        bls_g1_from_memory(memory, start).filter(|sig| !bool::from(sig.is_identity()))
+    }
+
+    fn bls_scalar_from_memory(memory: &[u8], start: usize) -> Option<bls12_381::Scalar> {
+        let bytes =
+            <&[u8; BLS_SCALAR_SIZE]>::try_from(&memory[start..start + BLS_SCALAR_SIZE]).ok()?;
+        Option::from(bls12_381::Scalar::from_bytes(bytes))
+    }
+
+    #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
+    pub fn bls_verify(
+        memory: &mut Vec<u8>,
+        stack: &mut machine::RuntimeStack,
+        energy: &mut InterpreterEnergy,
+        costs: &CostTable,
+    ) -> machine::RunResult<()> {
+        let message_len = unsafe { stack.pop_u32() };
+        let message_start = unsafe { stack.pop_u32() };
+        let signature_start = unsafe { stack.pop_u32() };
+        let public_key_start = unsafe { stack.pop_u32() };
+        let message_end = message_start as usize + message_len as usize;
+        ensure!(message_end <= memory.len(), "Illegal memory access.");
+        let public_key_end = public_key_start as usize + BLS_G2_SIZE;
+        ensure!(public_key_end <= memory.len(), "Illegal memory access.");
+        let signature_end = signature_start as usize + BLS_G1_SIZE;
+        ensure!(signature_end <= memory.len(), "Illegal memory access.");
+        // expensive operations start now.
+        energy.tick_energy(costs.bls_verify.cost(message_len))?;
+        let public_key = bls_public_key_from_memory(memory, public_key_start as usize);
+        let signature = bls_signature_from_memory(memory, signature_start as usize);
+        let message = &memory[message_start as usize..message_end];
+        match (public_key, signature) {
+            (Some(public_key), Some(ref signature))
+                if bls_check_signature(public_key, signature, message) =>
+            {
+                stack.push_value(1u32)
+            }
+            _ => stack.push_value(0u32),
+        }
+        Ok(())
+    }
+
+    #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
+    pub fn bls_aggregate_verify(
+        memory: &mut Vec<u8>,
+        stack: &mut machine::RuntimeStack,
+        energy: &mut InterpreterEnergy,
+        costs: &CostTable,
+    ) -> machine::RunResult<()> {
+        let message_len = unsafe { stack.pop_u32() };
+        let message_start = unsafe { stack.pop_u32() };
+        let signature_start = unsafe { stack.pop_u32() };
+        let num_keys = unsafe { stack.pop_u32() };
+        let public_keys_start = unsafe { stack.pop_u32() };
+        let message_end = message_start as usize + message_len as usize;
+        ensure!(message_end <= memory.len(), "Illegal memory access.");
+        let public_keys_end = public_keys_start as usize + num_keys as usize * BLS_G2_SIZE;
+        ensure!(public_keys_end <= memory.len(), "Illegal memory access.");
+        let signature_end = signature_start as usize + BLS_G1_SIZE;
+        ensure!(signature_end <= memory.len(), "Illegal memory access.");
+        // expensive operations start now.
+        energy.tick_energy(costs.bls_aggregate_verify_cost(num_keys, message_len))?;
+        // The aggregate of the public keys, or None if there are no keys, or one
+        // of them is not valid. The aggregate itself is rejected below if it is
+        // the identity.
+        let mut aggregate_key: Option<bls12_381::G2Projective> = None;
+        for i in 0..num_keys as usize {
+            if let Some(key) =
+                bls_public_key_from_memory(memory, public_keys_start as usize + i * BLS_G2_SIZE)
+            {
+                aggregate_key = Some(aggregate_key.map_or_else(|| key.into(), |acc| acc + key));
+            } else {
+                aggregate_key = None;
+                break;
+            }
+        }
+        let aggregate_key = aggregate_key.filter(|key| !bool::from(key.is_identity()));
+        let signature = bls_signature_from_memory(memory, signature_start as usize);
+        let message = &memory[message_start as usize..message_end];
+        match (aggregate_key, signature) {
+            (Some(aggregate_key), Some(ref signature))
+                if bls_check_signature(aggregate_key.into(), signature, message) =>
+            {
+                stack.push_value(1u32)
+            }
+            _ => stack.push_value(0u32),
+        }
+        Ok(())
+    }
+
+    /// Read two G1 points, and write their sum to memory.
+    #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
+    pub fn bls_g1_add(
+        memory: &mut Vec<u8>,
+        stack: &mut machine::RuntimeStack,
+        energy: &mut InterpreterEnergy,
+        costs: &CostTable,
+    ) -> machine::RunResult<()> {
+        let output_start = unsafe { stack.pop_u32() } as usize;
+        let right_start = unsafe { stack.pop_u32() } as usize;
+        let left_start = unsafe { stack.pop_u32() } as usize;
+        ensure!(output_start + BLS_G1_SIZE <= memory.len(), "Illegal memory access.");
+        ensure!(right_start + BLS_G1_SIZE <= memory.len(), "Illegal memory access.");
+        ensure!(left_start + BLS_G1_SIZE <= memory.len(), "Illegal memory access.");
+        energy.tick_energy(costs.bls_g1_add)?;
+        let left = bls_g1_from_memory(memory, left_start);
+        let right = bls_g1_from_memory(memory, right_start);
+        if let (Some(left), Some(right)) = (left, right) {
+            let sum = bls12_381::G1Affine::from(left + bls12_381::G1Projective::from(right));
+            memory[output_start..output_start + BLS_G1_SIZE].copy_from_slice(&sum.to_compressed());
+            stack.push_value(1u32);
+        } else {
+            stack.push_value(0u32);
+        }
+        Ok(())
+    }
+
+    /// Read a G1 point and a scalar, and write their product to memory.
+    #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
+    pub fn bls_g1_mul(
+        memory: &mut Vec<u8>,
+        stack: &mut machine::RuntimeStack,
+        energy: &mut InterpreterEnergy,
+        costs: &CostTable,
+    ) -> machine::RunResult<()> {
+        let output_start = unsafe { stack.pop_u32() } as usize;
+        let scalar_start = unsafe { stack.pop_u32() } as usize;
+        let point_start = unsafe { stack.pop_u32() } as usize;
+        ensure!(output_start + BLS_G1_SIZE <= memory.len(), "Illegal memory access.");
+        ensure!(scalar_start + BLS_SCALAR_SIZE <= memory.len(), "Illegal memory access.");
+        ensure!(point_start + BLS_G1_SIZE <= memory.len(), "Illegal memory access.");
+        energy.tick_energy(costs.bls_g1_mul)?;
+        let point = bls_g1_from_memory(memory, point_start);
+        let scalar = bls_scalar_from_memory(memory, scalar_start);
+        if let (Some(point), Some(scalar)) = (point, scalar) {
+            let product = bls12_381::G1Affine::from(point * scalar);
+            memory[output_start..output_start + BLS_G1_SIZE]
+                .copy_from_slice(&product.to_compressed());
+            stack.push_value(1u32);
+        } else {
+            stack.push_value(0u32);
+        }
+        Ok(())
+    }
+
+    /// Read two G2 points, and write their sum to memory.
+    #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
+    pub fn bls_g2_add(
+        memory: &mut Vec<u8>,
+        stack: &mut machine::RuntimeStack,
+        energy: &mut InterpreterEnergy,
+        costs: &CostTable,
+    ) -> machine::RunResult<()> {
+        let output_start = unsafe { stack.pop_u32() } as usize;
+        let right_start = unsafe { stack.pop_u32() } as usize;
+        let left_start = unsafe { stack.pop_u32() } as usize;
+        ensure!(output_start + BLS_G2_SIZE <= memory.len(), "Illegal memory access.");
+        ensure!(right_start + BLS_G2_SIZE <= memory.len(), "Illegal memory access.");
+        ensure!(left_start + BLS_G2_SIZE <= memory.len(), "Illegal memory access.");
+        energy.tick_energy(costs.bls_g2_add)?;
+        let left = bls_g2_from_memory(memory, left_start);
+        let right = bls_g2_from_memory(memory, right_start);
+        if let (Some(left), Some(right)) = (left, right) {
+            let sum = bls12_381::G2Affine::from(left + bls12_381::G2Projective::from(right));
+            memory[output_start..output_start + BLS_G2_SIZE].copy_from_slice(&sum.to_compressed());
+            stack.push_value(1u32);
+        } else {
+            stack.push_value(0u32);
+        }
+        Ok(())
+    }
+
+    /// Read a G2 point and a scalar, and write their product to memory.
+    #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
+    pub fn bls_g2_mul(
+        memory: &mut Vec<u8>,
+        stack: &mut machine::RuntimeStack,
+        energy: &mut InterpreterEnergy,
+        costs: &CostTable,
+    ) -> machine::RunResult<()> {
+        let output_start = unsafe { stack.pop_u32() } as usize;
+        let scalar_start = unsafe { stack.pop_u32() } as usize;
+        let point_start = unsafe { stack.pop_u32() } as usize;
+        ensure!(output_start + BLS_G2_SIZE <= memory.len(), "Illegal memory access.");
+        ensure!(scalar_start + BLS_SCALAR_SIZE <= memory.len(), "Illegal memory access.");
+        ensure!(point_start + BLS_G2_SIZE <= memory.len(), "Illegal memory access.");
+        energy.tick_energy(costs.bls_g2_mul)?;
+        let point = bls_g2_from_memory(memory, point_start);
+        let scalar = bls_scalar_from_memory(memory, scalar_start);
+        if let (Some(point), Some(scalar)) = (point, scalar) {
+            let product = bls12_381::G2Affine::from(point * scalar);
+            memory[output_start..output_start + BLS_G2_SIZE]
+                .copy_from_slice(&product.to_compressed());
+            stack.push_value(1u32);
+        } else {
+            stack.push_value(0u32);
+        }
+        Ok(())
+    }
+
+    #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
+    pub fn get_random(
+        memory: &mut Vec<u8>,
+        stack: &mut machine::RuntimeStack,
+        energy: &mut InterpreterEnergy,
+        costs: &CostTable,
+        seed: ExecResult<&v0::RandomSeed>,
+        invocation: RandomInvocation,
+        counter: &mut u64,
+    ) -> machine::RunResult<()> {
+        let length = unsafe { stack.pop_u32() };
+        let start = unsafe { stack.pop_u32() } as usize;
+        let end = start + length as usize;
+        ensure!(end <= memory.len(), "Illegal memory access.");
+        energy.tick_energy(costs.get_random.cost(length))?;
+        fill_random(seed?, invocation, counter, &mut memory[start..end]);
+        Ok(())
+    }
+
     #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
     pub fn hash_sha2_256(
         memory: &mut Vec<u8>,
         stack: &mut machine::RuntimeStack,
         energy: &mut InterpreterEnergy,
+        costs: &CostTable,
     ) -> machine::RunResult<()> {
         let output_start = unsafe { stack.pop_u32() };
         let data_len = unsafe { stack.pop_u32() };
@@ -715,7 +1822,7 @@ mod host {
         let output_end = output_start as usize + 32;
         ensure!(output_end <= memory.len(), "Illegal memory access.");
         // expensive operations start here
-        energy.tick_energy(constants::hash_sha2_256_cost(data_len))?;
+        energy.tick_energy(costs.hash_sha2_256.cost(data_len))?;
         let hash = sha2::Sha256::digest(&memory[data_start as usize..data_end]);
         memory[output_start as usize..output_end].copy_from_slice(&hash);
         Ok(())
@@ -726,6 +1833,7 @@ mod host {
         memory: &mut Vec<u8>,
         stack: &mut machine::RuntimeStack,
         energy: &mut InterpreterEnergy,
+        costs: &CostTable,
     ) -> machine::RunResult<()> {
         let output_start = unsafe { stack.pop_u32() };
         let data_len = unsafe { stack.pop_u32() };
@@ -735,7 +1843,7 @@ mod host {
         let output_end = output_start as usize + 32;
         ensure!(output_end <= memory.len(), "Illegal memory access.");
         // expensive operations start here
-        energy.tick_energy(constants::hash_sha3_256_cost(data_len))?;
+        energy.tick_energy(costs.hash_sha3_256.cost(data_len))?;
         let hash = sha3::Sha3_256::digest(&memory[data_start as usize..data_end]);
         memory[output_start as usize..output_end].copy_from_slice(&hash);
         Ok(())
@@ -746,6 +1854,7 @@ mod host {
         memory: &mut Vec<u8>,
         stack: &mut machine::RuntimeStack,
         energy: &mut InterpreterEnergy,
+        costs: &CostTable,
     ) -> machine::RunResult<()> {
         let output_start = unsafe { stack.pop_u32() };
         let data_len = unsafe { stack.pop_u32() };
@@ -755,7 +1864,7 @@ mod host {
         let output_end = output_start as usize + 32;
         ensure!(output_end <= memory.len(), "Illegal memory access.");
         // expensive operations start here
-        energy.tick_energy(constants::hash_keccak_256_cost(data_len))?;
+        energy.tick_energy(costs.hash_keccak_256.cost(data_len))?;
         let hash = sha3::Keccak256::digest(&memory[data_start as usize..data_end]);
         memory[output_start as usize..output_end].copy_from_slice(&hash);
         Ok(())
@@ -767,11 +1876,24 @@ mod host {
 impl<'a, BackingStore: BackingStoreLoad, ParamType: AsRef<[u8]>, Ctx: v0::HasInitContext>
     machine::Host<ProcessedImports> for InitHost<'a, BackingStore, ParamType, Ctx>
 {
-    type Interrupt = NoInterrupt;
+    type Interrupt = Interrupt;
 
     #[cfg_attr(not(feature = "fuzz-coverage"), inline(always))]
     fn tick_initial_memory(&mut self, num_pages: u32) -> machine::RunResult<()> {
-        self.energy.charge_memory_alloc(num_pages)
+        if self.state.host_features.memory_accounting {
+            self.energy.charge_memory_grow(0, num_pages)
+        } else {
+            self.energy.charge_memory_alloc(num_pages)
+        }
+    }
+
+    #[cfg_attr(not(feature = "fuzz-coverage"), inline(always))]
+    fn tick_memory_grow(&mut self, current_pages: u32, num_pages: u32) -> machine::RunResult<()> {
+        if self.state.host_features.memory_accounting {
+            self.energy.charge_memory_grow(current_pages, num_pages)
+        } else {
+            Ok(())
+        }
     }
 
     #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
@@ -781,23 +1903,58 @@ impl<'a, BackingStore: BackingStoreLoad, ParamType: AsRef<[u8]>, Ctx: v0::HasIni
         memory: &mut Vec<u8>,
         stack: &mut machine::RuntimeStack,
     ) -> machine::RunResult<Option<Self::Interrupt>> {
+        let cold_loads = self.state.cold_loads();
+        let state_access = matches!(f.tag, ImportFunc::Common(cf) if cf.is_state_access());
+        if state_access {
+            self.state.enter_state_budget(&mut self.energy);
+        }
         match f.tag {
             ImportFunc::ChargeEnergy => self.energy.tick_energy(unsafe { stack.pop_u64() })?,
             ImportFunc::TrackCall => v0::host::track_call(&mut self.activation_frames)?,
             ImportFunc::TrackReturn => v0::host::track_return(&mut self.activation_frames),
+            // With memory accounting, memory is charged when it is actually
+            // grown, see `tick_memory_grow`, instead of when it is requested.
             ImportFunc::ChargeMemoryAlloc => {
-                v0::host::charge_memory_alloc(stack, &mut self.energy)?
+                if !self.state.host_features.memory_accounting {
+                    v0::host::charge_memory_alloc(stack, &mut self.energy)?
+                }
             }
             ImportFunc::Common(cf) => match cf {
                 CommonFunc::WriteOutput => host::write_return_value(
                     memory,
                     stack,
                     &mut self.energy,
+                    &self.state.costs,
                     &mut self.return_value,
                 ),
-                CommonFunc::GetParameterSize => host::get_parameter_size(stack, &[&self.parameter]),
-                CommonFunc::GetParameterSection => {
-                    host::get_parameter_section(memory, stack, &mut self.energy, &[&self.parameter])
+                CommonFunc::GetParameterSize => host::get_parameter_size(stack, &self.parameters),
+                CommonFunc::GetParameterSection => host::get_parameter_section(
+                    memory,
+                    stack,
+                    &mut self.energy,
+                    &self.state.costs,
+                    &self.parameters,
+                ),
+                CommonFunc::ParameterCursorOpen => host::parameter_cursor_open(
+                    stack,
+                    &mut self.parameter_cursors,
+                    &self.parameters,
+                ),
+                CommonFunc::ParameterCursorRead => host::parameter_cursor_read(
+                    memory,
+                    stack,
+                    &mut self.energy,
+                    &self.state.costs,
+                    &mut self.parameter_cursors,
+                    &self.parameters,
+                ),
+                CommonFunc::ParameterCursorSeek => host::parameter_cursor_seek(
+                    stack,
+                    &mut self.parameter_cursors,
+                    &self.parameters,
+                ),
+                CommonFunc::ParameterCursorClose => {
+                    host::parameter_cursor_close(stack, &mut self.parameter_cursors)
                 }
                 CommonFunc::GetPolicySection => v0::host::get_policy_section(
                     memory,
@@ -808,6 +1965,9 @@ impl<'a, BackingStore: BackingStoreLoad, ParamType: AsRef<[u8]>, Ctx: v0::HasIni
                 CommonFunc::LogEvent => {
                     v0::host::log_event(memory, stack, &mut self.energy, &mut self.logs)
                 }
+                CommonFunc::LogEventTyped => {
+                    v0::host::log_event_typed(memory, stack, &mut self.energy, &mut self.logs)
+                }
                 CommonFunc::GetSlotTime => v0::host::get_slot_time(stack, self.init_ctx.metadata()),
                 CommonFunc::StateLookupEntry => {
                     host::state_lookup_entry(memory, stack, &mut self.energy, &mut self.state)
@@ -836,6 +1996,21 @@ impl<'a, BackingStore: BackingStoreLoad, ParamType: AsRef<[u8]>, Ctx: v0::HasIni
                 CommonFunc::StateIteratorKeyRead => {
                     host::state_iterator_key_read(memory, stack, &mut self.energy, &mut self.state)
                 }
+                CommonFunc::StateIteratorTokenSize => {
+                    host::state_iterator_token_size(stack, &mut self.energy, &mut self.state)
+                }
+                CommonFunc::StateIteratorTokenRead => host::state_iterator_token_read(
+                    memory,
+                    stack,
+                    &mut self.energy,
+                    &mut self.state,
+                ),
+                CommonFunc::StateIteratorResume => {
+                    host::state_iterator_resume(memory, stack, &mut self.energy, &mut self.state)
+                }
+                CommonFunc::StateIteratorRemaining => {
+                    host::state_iterator_remaining(stack, &mut self.energy, &mut self.state)
+                }
                 CommonFunc::StateEntryRead => {
                     host::state_entry_read(memory, stack, &mut self.energy, &mut self.state)
                 }
@@ -848,22 +2023,166 @@ impl<'a, BackingStore: BackingStoreLoad, ParamType: AsRef<[u8]>, Ctx: v0::HasIni
                 CommonFunc::StateEntryResize => {
                     host::state_entry_resize(stack, &mut self.energy, &mut self.state)
                 }
-                CommonFunc::VerifyEd25519 => {
-                    host::verify_ed25519_signature(memory, stack, &mut self.energy)
+                CommonFunc::VerifyEd25519 => host::verify_ed25519_signature(
+                    memory,
+                    stack,
+                    &mut self.energy,
+                    &self.state.costs,
+                ),
+                CommonFunc::VerifySecp256k1 => host::verify_ecdsa_secp256k1_signature(
+                    memory,
+                    stack,
+                    &mut self.energy,
+                    &self.state.costs,
+                ),
+                CommonFunc::VerifySecp256r1 => host::verify_ecdsa_secp256r1_signature(
+                    memory,
+                    stack,
+                    &mut self.energy,
+                    &self.state.costs,
+                ),
+                CommonFunc::BlsVerify => {
+                    host::bls_verify(memory, stack, &mut self.energy, &self.state.costs)
+                }
+                CommonFunc::BlsAggregateVerify => {
+                    host::bls_aggregate_verify(memory, stack, &mut self.energy, &self.state.costs)
+                }
+                CommonFunc::BlsG1Add => {
+                    host::bls_g1_add(memory, stack, &mut self.energy, &self.state.costs)
+                }
+                CommonFunc::BlsG1Mul => {
+                    host::bls_g1_mul(memory, stack, &mut self.energy, &self.state.costs)
+                }
+                CommonFunc::BlsG2Add => {
+                    host::bls_g2_add(memory, stack, &mut self.energy, &self.state.costs)
+                }
+                CommonFunc::BlsG2Mul => {
+                    host::bls_g2_mul(memory, stack, &mut self.energy, &self.state.costs)
                 }
-                CommonFunc::VerifySecp256k1 => {
-                    host::verify_ecdsa_secp256k1_signature(memory, stack, &mut self.energy)
+                CommonFunc::HashSHA2_256 => {
+                    host::hash_sha2_256(memory, stack, &mut self.energy, &self.state.costs)
                 }
-                CommonFunc::HashSHA2_256 => host::hash_sha2_256(memory, stack, &mut self.energy),
-                CommonFunc::HashSHA3_256 => host::hash_sha3_256(memory, stack, &mut self.energy),
-                CommonFunc::HashKeccak256 => host::hash_keccak_256(memory, stack, &mut self.energy),
+                CommonFunc::HashSHA3_256 => {
+                    host::hash_sha3_256(memory, stack, &mut self.energy, &self.state.costs)
+                }
+                CommonFunc::HashKeccak256 => {
+                    host::hash_keccak_256(memory, stack, &mut self.energy, &self.state.costs)
+                }
+                CommonFunc::StateEntryHash => {
+                    host::state_entry_hash(memory, stack, &mut self.energy, &mut self.state)
+                }
+                CommonFunc::GetRandom => host::get_random(
+                    memory,
+                    stack,
+                    &mut self.energy,
+                    &self.state.costs,
+                    match &self.state.random_seed {
+                        Some(seed) => Ok(seed),
+                        None => self.init_ctx.random_seed(),
+                    },
+                    RandomInvocation::Init {
+                        origin: self.init_ctx.init_origin()?,
+                    },
+                    &mut self.random_counter,
+                ),
             }?,
             ImportFunc::InitOnly(InitOnlyFunc::GetInitOrigin) => {
                 v0::host::get_init_origin(memory, stack, self.init_ctx.init_origin())?
             }
-            ImportFunc::ReceiveOnly(_) => {
+            // Without init interrupts all of these trap, as they did initially.
+            ImportFunc::ReceiveOnly(_) if !self.state.host_features.init_interrupts => {
                 bail!("Not implemented for init {:#?}.", f);
             }
+            // Init functions may make transfers, call and query other contracts, and query
+            // the chain. The remaining functions need an existing instance.
+            ImportFunc::ReceiveOnly(rof) => match rof {
+                ReceiveOnlyFunc::Invoke => {
+                    let interrupt =
+                        host::invoke(memory, stack, &mut self.energy, &self.state.costs)?;
+                    if let Some(interrupt) = &interrupt {
+                        self.state.check_call_depth(interrupt)?;
+                    }
+                    return Ok(interrupt);
+                }
+                ReceiveOnlyFunc::ContractExists => {
+                    let interrupt = host::query_contract(
+                        stack,
+                        &mut self.energy,
+                        &self.state.costs,
+                        ContractQuery::Exists,
+                    )?;
+                    return Ok(Some(interrupt));
+                }
+                ReceiveOnlyFunc::ContractStateSize => {
+                    let interrupt = host::query_contract(
+                        stack,
+                        &mut self.energy,
+                        &self.state.costs,
+                        ContractQuery::StateSize,
+                    )?;
+                    return Ok(Some(interrupt));
+                }
+                ReceiveOnlyFunc::GetAccountBalance => {
+                    let interrupt = host::get_account_balance(
+                        memory,
+                        stack,
+                        &mut self.energy,
+                        &self.state.costs,
+                    )?;
+                    return Ok(Some(interrupt));
+                }
+                ReceiveOnlyFunc::EnergyToMicroCcd => {
+                    let interrupt = host::query_exchange_rates(
+                        stack,
+                        &mut self.energy,
+                        &self.state.costs,
+                        Conversion::EnergyToMicroCcd,
+                    )?;
+                    return Ok(Some(interrupt));
+                }
+                ReceiveOnlyFunc::MicroCcdToEnergy => {
+                    let interrupt = host::query_exchange_rates(
+                        stack,
+                        &mut self.energy,
+                        &self.state.costs,
+                        Conversion::MicroCcdToEnergy,
+                    )?;
+                    return Ok(Some(interrupt));
+                }
+                ReceiveOnlyFunc::MicroEuroToMicroCcd => {
+                    let interrupt = host::query_exchange_rates(
+                        stack,
+                        &mut self.energy,
+                        &self.state.costs,
+                        Conversion::MicroEuroToMicroCcd,
+                    )?;
+                    return Ok(Some(interrupt));
+                }
+                ReceiveOnlyFunc::MicroCcdToMicroEuro => {
+                    let interrupt = host::query_exchange_rates(
+                        stack,
+                        &mut self.energy,
+                        &self.state.costs,
+                        Conversion::MicroCcdToMicroEuro,
+                    )?;
+                    return Ok(Some(interrupt));
+                }
+                ReceiveOnlyFunc::Upgrade
+                | ReceiveOnlyFunc::GetReceiveInvoker
+                | ReceiveOnlyFunc::GetReceiveSelfAddress
+                | ReceiveOnlyFunc::GetReceiveSelfBalance
+                | ReceiveOnlyFunc::GetReceiveSender
+                | ReceiveOnlyFunc::GetReceiveOwner
+                | ReceiveOnlyFunc::GetReceiveSenderWithLength
+                | ReceiveOnlyFunc::GetReceiveEntrypointSize
+                | ReceiveOnlyFunc::GetReceiveEntryPoint => {
+                    bail!("Not implemented for init {:#?}.", f);
+                }
+            },
+        }
+        if state_access {
+            self.state.charge_access(&mut self.energy, cold_loads)?;
+            self.state.leave_state_budget(&mut self.energy);
         }
         Ok(None)
     }
@@ -893,6 +2212,8 @@ impl<X: AsRef<[u8]>> v0::HasReceiveContext for ReceiveContext<X> {
     fn owner(&self) -> ExecResult<&AccountAddress> { Ok(&self.common.owner) }
 
     fn sender_policies(&self) -> ExecResult<&[u8]> { Ok(self.common.sender_policies.as_ref()) }
+
+    fn random_seed(&self) -> ExecResult<&v0::RandomSeed> { bail!(v0::MissingRandomSeed) }
 }
 
 impl<X: AsRef<[u8]>> HasReceiveContext for ReceiveContext<X> {
@@ -905,6 +2226,11 @@ impl<'a, X: HasReceiveContext> HasReceiveContext for &'a X {
     fn entrypoint(&self) -> ExecResult<EntrypointName> { (*self).entrypoint() }
 }
 
+impl<X: HasReceiveContext> HasReceiveContext for v0::WithRandomSeed<X> {
+    #[inline(always)]
+    fn entrypoint(&self) -> ExecResult<EntrypointName> { self.ctx.entrypoint() }
+}
+
 impl<'a, BackingStore: BackingStoreLoad, ParamType: AsRef<[u8]>, Ctx: HasReceiveContext>
     machine::Host<ProcessedImports> for ReceiveHost<'a, BackingStore, ParamType, Ctx>
 {
@@ -912,7 +2238,20 @@ impl<'a, BackingStore: BackingStoreLoad, ParamType: AsRef<[u8]>, Ctx: HasReceive
 
     #[cfg_attr(not(feature = "fuzz-coverage"), inline(always))]
     fn tick_initial_memory(&mut self, num_pages: u32) -> machine::RunResult<()> {
-        self.energy.charge_memory_alloc(num_pages)
+        if self.state.host_features.memory_accounting {
+            self.energy.charge_memory_grow(0, num_pages)
+        } else {
+            self.energy.charge_memory_alloc(num_pages)
+        }
+    }
+
+    #[cfg_attr(not(feature = "fuzz-coverage"), inline(always))]
+    fn tick_memory_grow(&mut self, current_pages: u32, num_pages: u32) -> machine::RunResult<()> {
+        if self.state.host_features.memory_accounting {
+            self.energy.charge_memory_grow(current_pages, num_pages)
+        } else {
+            Ok(())
+        }
     }
 
     #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
@@ -922,20 +2261,30 @@ impl<'a, BackingStore: BackingStoreLoad, ParamType: AsRef<[u8]>, Ctx: HasReceive
         memory: &mut Vec<u8>,
         stack: &mut machine::RuntimeStack,
     ) -> machine::RunResult<Option<Self::Interrupt>> {
+        let cold_loads = self.state.cold_loads();
+        let state_access = matches!(f.tag, ImportFunc::Common(cf) if cf.is_state_access());
+        if state_access {
+            self.state.enter_state_budget(&mut self.energy);
+        }
         match f.tag {
             ImportFunc::ChargeEnergy => self.energy.tick_energy(unsafe { stack.pop_u64() })?,
             ImportFunc::TrackCall => v0::host::track_call(&mut self.stateless.activation_frames)?,
             ImportFunc::TrackReturn => {
                 v0::host::track_return(&mut self.stateless.activation_frames)
             }
+            // With memory accounting, memory is charged when it is actually
+            // grown, see `tick_memory_grow`, instead of when it is requested.
             ImportFunc::ChargeMemoryAlloc => {
-                v0::host::charge_memory_alloc(stack, &mut self.energy)?
+                if !self.state.host_features.memory_accounting {
+                    v0::host::charge_memory_alloc(stack, &mut self.energy)?
+                }
             }
             ImportFunc::Common(cf) => match cf {
                 CommonFunc::WriteOutput => host::write_return_value(
                     memory,
                     stack,
                     &mut self.energy,
+                    &self.state.costs,
                     &mut self.stateless.return_value,
                 ),
                 CommonFunc::GetParameterSize => {
@@ -945,8 +2294,30 @@ impl<'a, BackingStore: BackingStoreLoad, ParamType: AsRef<[u8]>, Ctx: HasReceive
                     memory,
                     stack,
                     &mut self.energy,
+                    &self.state.costs,
+                    &self.stateless.parameters,
+                ),
+                CommonFunc::ParameterCursorOpen => host::parameter_cursor_open(
+                    stack,
+                    &mut self.stateless.parameter_cursors,
+                    &self.stateless.parameters,
+                ),
+                CommonFunc::ParameterCursorRead => host::parameter_cursor_read(
+                    memory,
+                    stack,
+                    &mut self.energy,
+                    &self.state.costs,
+                    &mut self.stateless.parameter_cursors,
                     &self.stateless.parameters,
                 ),
+                CommonFunc::ParameterCursorSeek => host::parameter_cursor_seek(
+                    stack,
+                    &mut self.stateless.parameter_cursors,
+                    &self.stateless.parameters,
+                ),
+                CommonFunc::ParameterCursorClose => {
+                    host::parameter_cursor_close(stack, &mut self.stateless.parameter_cursors)
+                }
                 CommonFunc::GetPolicySection => v0::host::get_policy_section(
                     memory,
                     stack,
@@ -956,6 +2327,12 @@ impl<'a, BackingStore: BackingStoreLoad, ParamType: AsRef<[u8]>, Ctx: HasReceive
                 CommonFunc::LogEvent => {
                     v0::host::log_event(memory, stack, &mut self.energy, &mut self.stateless.logs)
                 }
+                CommonFunc::LogEventTyped => v0::host::log_event_typed(
+                    memory,
+                    stack,
+                    &mut self.energy,
+                    &mut self.stateless.logs,
+                ),
                 CommonFunc::GetSlotTime => {
                     v0::host::get_slot_time(stack, self.stateless.receive_ctx.metadata())
                 }
@@ -986,6 +2363,21 @@ impl<'a, BackingStore: BackingStoreLoad, ParamType: AsRef<[u8]>, Ctx: HasReceive
                 CommonFunc::StateIteratorKeyRead => {
                     host::state_iterator_key_read(memory, stack, &mut self.energy, &mut self.state)
                 }
+                CommonFunc::StateIteratorTokenSize => {
+                    host::state_iterator_token_size(stack, &mut self.energy, &mut self.state)
+                }
+                CommonFunc::StateIteratorTokenRead => host::state_iterator_token_read(
+                    memory,
+                    stack,
+                    &mut self.energy,
+                    &mut self.state,
+                ),
+                CommonFunc::StateIteratorResume => {
+                    host::state_iterator_resume(memory, stack, &mut self.energy, &mut self.state)
+                }
+                CommonFunc::StateIteratorRemaining => {
+                    host::state_iterator_remaining(stack, &mut self.energy, &mut self.state)
+                }
                 CommonFunc::StateEntryRead => {
                     host::state_entry_read(memory, stack, &mut self.energy, &mut self.state)
                 }
@@ -998,19 +2390,147 @@ impl<'a, BackingStore: BackingStoreLoad, ParamType: AsRef<[u8]>, Ctx: HasReceive
                 CommonFunc::StateEntryResize => {
                     host::state_entry_resize(stack, &mut self.energy, &mut self.state)
                 }
-                CommonFunc::VerifyEd25519 => {
-                    host::verify_ed25519_signature(memory, stack, &mut self.energy)
+                CommonFunc::VerifyEd25519 => host::verify_ed25519_signature(
+                    memory,
+                    stack,
+                    &mut self.energy,
+                    &self.state.costs,
+                ),
+                CommonFunc::VerifySecp256k1 => host::verify_ecdsa_secp256k1_signature(
+                    memory,
+                    stack,
+                    &mut self.energy,
+                    &self.state.costs,
+                ),
+                CommonFunc::VerifySecp256r1 => host::verify_ecdsa_secp256r1_signature(
+                    memory,
+                    stack,
+                    &mut self.energy,
+                    &self.state.costs,
+                ),
+                CommonFunc::BlsVerify => {
+                    host::bls_verify(memory, stack, &mut self.energy, &self.state.costs)
+                }
+                CommonFunc::BlsAggregateVerify => {
+                    host::bls_aggregate_verify(memory, stack, &mut self.energy, &self.state.costs)
+                }
+                CommonFunc::BlsG1Add => {
+                    host::bls_g1_add(memory, stack, &mut self.energy, &self.state.costs)
+                }
+                CommonFunc::BlsG1Mul => {
+                    host::bls_g1_mul(memory, stack, &mut self.energy, &self.state.costs)
+                }
+                CommonFunc::BlsG2Add => {
+                    host::bls_g2_add(memory, stack, &mut self.energy, &self.state.costs)
                 }
-                CommonFunc::VerifySecp256k1 => {
-                    host::verify_ecdsa_secp256k1_signature(memory, stack, &mut self.energy)
+                CommonFunc::BlsG2Mul => {
+                    host::bls_g2_mul(memory, stack, &mut self.energy, &self.state.costs)
                 }
-                CommonFunc::HashSHA2_256 => host::hash_sha2_256(memory, stack, &mut self.energy),
-                CommonFunc::HashSHA3_256 => host::hash_sha3_256(memory, stack, &mut self.energy),
-                CommonFunc::HashKeccak256 => host::hash_keccak_256(memory, stack, &mut self.energy),
+                CommonFunc::HashSHA2_256 => {
+                    host::hash_sha2_256(memory, stack, &mut self.energy, &self.state.costs)
+                }
+                CommonFunc::HashSHA3_256 => {
+                    host::hash_sha3_256(memory, stack, &mut self.energy, &self.state.costs)
+                }
+                CommonFunc::HashKeccak256 => {
+                    host::hash_keccak_256(memory, stack, &mut self.energy, &self.state.costs)
+                }
+                CommonFunc::StateEntryHash => {
+                    host::state_entry_hash(memory, stack, &mut self.energy, &mut self.state)
+                }
+                CommonFunc::GetRandom => host::get_random(
+                    memory,
+                    stack,
+                    &mut self.energy,
+                    &self.state.costs,
+                    match &self.state.random_seed {
+                        Some(seed) => Ok(seed),
+                        None => self.stateless.receive_ctx.random_seed(),
+                    },
+                    RandomInvocation::Receive {
+                        address: self.stateless.receive_ctx.self_address()?,
+                    },
+                    &mut self.stateless.random_counter,
+                ),
             }?,
             ImportFunc::ReceiveOnly(rof) => match rof {
                 ReceiveOnlyFunc::Invoke => {
-                    return host::invoke(memory, stack, &mut self.energy);
+                    let interrupt =
+                        host::invoke(memory, stack, &mut self.energy, &self.state.costs)?;
+                    if let Some(interrupt) = &interrupt {
+                        self.state.check_read_only(interrupt)?;
+                        self.state.check_call_depth(interrupt)?;
+                    }
+                    return Ok(interrupt);
+                }
+                ReceiveOnlyFunc::ContractExists => {
+                    let interrupt = host::query_contract(
+                        stack,
+                        &mut self.energy,
+                        &self.state.costs,
+                        ContractQuery::Exists,
+                    )?;
+                    return Ok(Some(interrupt));
+                }
+                ReceiveOnlyFunc::ContractStateSize => {
+                    let interrupt = host::query_contract(
+                        stack,
+                        &mut self.energy,
+                        &self.state.costs,
+                        ContractQuery::StateSize,
+                    )?;
+                    return Ok(Some(interrupt));
+                }
+                ReceiveOnlyFunc::GetAccountBalance => {
+                    let interrupt = host::get_account_balance(
+                        memory,
+                        stack,
+                        &mut self.energy,
+                        &self.state.costs,
+                    )?;
+                    return Ok(Some(interrupt));
+                }
+                ReceiveOnlyFunc::EnergyToMicroCcd => {
+                    let interrupt = host::query_exchange_rates(
+                        stack,
+                        &mut self.energy,
+                        &self.state.costs,
+                        Conversion::EnergyToMicroCcd,
+                    )?;
+                    return Ok(Some(interrupt));
+                }
+                ReceiveOnlyFunc::MicroCcdToEnergy => {
+                    let interrupt = host::query_exchange_rates(
+                        stack,
+                        &mut self.energy,
+                        &self.state.costs,
+                        Conversion::MicroCcdToEnergy,
+                    )?;
+                    return Ok(Some(interrupt));
+                }
+                ReceiveOnlyFunc::MicroEuroToMicroCcd => {
+                    let interrupt = host::query_exchange_rates(
+                        stack,
+                        &mut self.energy,
+                        &self.state.costs,
+                        Conversion::MicroEuroToMicroCcd,
+                    )?;
+                    return Ok(Some(interrupt));
+                }
+                ReceiveOnlyFunc::MicroCcdToMicroEuro => {
+                    let interrupt = host::query_exchange_rates(
+                        stack,
+                        &mut self.energy,
+                        &self.state.costs,
+                        Conversion::MicroCcdToMicroEuro,
+                    )?;
+                    return Ok(Some(interrupt));
+                }
+                ReceiveOnlyFunc::Upgrade => {
+                    let interrupt =
+                        host::upgrade(memory, stack, &mut self.energy, &self.state.costs)?;
+                    self.state.check_read_only(&interrupt)?;
+                    return Ok(Some(interrupt));
                 }
                 ReceiveOnlyFunc::GetReceiveInvoker => v0::host::get_receive_invoker(
                     memory,
@@ -1032,6 +2552,13 @@ impl<'a, BackingStore: BackingStoreLoad, ParamType: AsRef<[u8]>, Ctx: HasReceive
                 ReceiveOnlyFunc::GetReceiveOwner => {
                     v0::host::get_receive_owner(memory, stack, self.stateless.receive_ctx.owner())
                 }
+                ReceiveOnlyFunc::GetReceiveSenderWithLength => {
+                    host::get_receive_sender_with_length(
+                        memory,
+                        stack,
+                        self.stateless.receive_ctx.sender(),
+                    )
+                }
                 ReceiveOnlyFunc::GetReceiveEntrypointSize => host::get_receive_entrypoint_size(
                     stack,
                     self.stateless.receive_ctx.entrypoint()?,
@@ -1046,6 +2573,10 @@ impl<'a, BackingStore: BackingStoreLoad, ParamType: AsRef<[u8]>, Ctx: HasReceive
                 bail!("Not implemented for receive.");
             }
         }
+        if state_access {
+            self.state.charge_access(&mut self.energy, cold_loads)?;
+            self.state.leave_state_budget(&mut self.energy);
+        }
         Ok(None)
     }
 }
@@ -1059,34 +2590,183 @@ pub type ParameterRef<'a> = &'a [u8];
 /// on Vec<u8>.
 pub type ParameterVec = Vec<u8>;
 
-/// Invokes an init-function from a given artifact
-pub fn invoke_init<BackingStore: BackingStoreLoad, R: RunnableCode>(
-    artifact: impl Borrow<Artifact<ProcessedImports, R>>,
+/// Options of invoking an init or receive function, or of resuming it after an
+/// interrupt. The default is an execution that is configured as by
+/// [InstanceState::new], without faults or a trace.
+#[derive(Default)]
+pub struct InvokeOptions<'a> {
+    /// Make host functions fail as described by the injector. This is only
+    /// intended for simulating contracts, see the [faults] module. An
+    /// interrupted execution should be resumed with the same injector.
+    pub faults:        Option<&'a mut faults::FaultInjector>,
+    /// Record the execution in the trace. This is only intended for
+    /// simulating contracts, see the [trace] module. An interrupted execution
+    /// should be resumed with the same trace.
+    pub trace:         Option<&'a mut trace::Trace>,
+    /// The behaviour of the host that depends on the protocol version. The
+    /// default is the behaviour of the initial release of V1 contracts, see
+    /// [HostFeatures].
+    pub host_features: HostFeatures,
+    /// The seed of the randomness returned by the `get_random` host function,
+    /// see [RandomSeed](v0::RandomSeed). If there is none the seed is taken
+    /// from the context of the execution, and if the context has none as well
+    /// `get_random` fails with [MissingRandomSeed](v0::MissingRandomSeed),
+    /// which terminates execution.
+    pub random_seed:   Option<v0::RandomSeed>,
+    /// The costs of the host functions. The default is [CostTable::V1].
+    pub costs:         CostTable,
+}
+
+impl InvokeOptions<'_> {
+    /// Configure the state of an execution with the features, random seed and
+    /// costs of the options. These are retained if execution is interrupted,
+    /// so they are not used when it is resumed.
+    fn configure<'b, BackingStore: BackingStoreLoad>(
+        &self,
+        state: InstanceState<'b, BackingStore>,
+    ) -> InstanceState<'b, BackingStore> {
+        state
+            .with_host_features(self.host_features)
+            .with_random_seed(self.random_seed)
+            .with_cost_table(self.costs)
+    }
+}
+
+/// Invokes an init-function from a given artifact, with the host configured by
+/// the options. Init functions may be interrupted, e.g., to make a transfer or
+/// call another contract, in which case execution is continued with
+/// [resume_init].
+#[cfg_attr(
+    feature = "instrumentation",
+    tracing::instrument(
+        level = "info",
+        skip_all,
+        fields(entrypoint = init_name, energy = energy.energy)
+    )
+)]
+#[allow(clippy::too_many_arguments)]
+pub fn invoke_init<BackingStore: BackingStoreLoad, R: RunnableCode, Ctx: v0::HasInitContext>(
+    artifact: Arc<Artifact<ProcessedImports, R>>,
     amount: u64,
-    init_ctx: impl v0::HasInitContext,
+    init_ctx: Ctx,
     init_name: &str,
     parameter: ParameterRef,
     energy: InterpreterEnergy,
     mut loader: BackingStore,
-) -> ExecResult<InitResult> {
+    options: InvokeOptions,
+) -> ExecResult<InitResult<R, Ctx>> {
     let mut initial_state = trie::MutableState::initial_state();
     let inner = initial_state.get_inner(&mut loader);
-    let state_ref = InstanceState::new(0, loader, inner);
+    let state_ref = options.configure(InstanceState::new(0, loader, inner));
     let mut host = InitHost {
         energy,
         activation_frames: constants::MAX_ACTIVATION_FRAMES,
         logs: v0::Logs::new(),
         state: state_ref,
         return_value: Vec::new(),
-        parameter,
+        parameters: vec![parameter],
+        parameter_cursors: ParameterCursors::default(),
+        random_counter: 0,
         init_ctx,
     };
-    let result = artifact.borrow().run(&mut host, init_name, &[Value::I64(amount as i64)]);
-    let return_value = std::mem::take(&mut host.return_value);
-    let remaining_energy = host.energy.energy;
-    let logs = std::mem::take(&mut host.logs);
+    let args = [Value::I64(amount as i64)];
+    let result = match (options.faults, options.trace) {
+        (Some(faults), Some(trace)) => artifact.run(
+            &mut trace::TracingHost {
+                host: &mut faults::FaultInjectingHost {
+                    host: &mut host,
+                    faults,
+                },
+                trace,
+            },
+            init_name,
+            &args,
+        ),
+        (Some(faults), None) => artifact.run(
+            &mut faults::FaultInjectingHost {
+                host: &mut host,
+                faults,
+            },
+            init_name,
+            &args,
+        ),
+        (None, Some(trace)) => artifact.run(
+            &mut trace::TracingHost {
+                host: &mut host,
+                trace,
+            },
+            init_name,
+            &args,
+        ),
+        (None, None) => artifact.run(&mut host, init_name, &args),
+    };
     // release lock on the state
-    drop(host);
+    let host = StoppedInitHost::from(host);
+    process_init_result(artifact, host, initial_state, result)
+}
+
+/// An [InitHost] whose execution has stopped, and which no longer holds the
+/// lock on the state. The state is owned separately, so that it can be either
+/// returned as the initial state of the contract, or retained in the
+/// [SavedInitHost] to resume execution.
+struct StoppedInitHost<Ctx> {
+    energy:             InterpreterEnergy,
+    activation_frames:  u32,
+    logs:               v0::Logs,
+    return_value:       ReturnValue,
+    parameters:         Vec<ParameterVec>,
+    parameter_cursors:  ParameterCursors,
+    random_counter:     u64,
+    init_ctx:           Ctx,
+    state_accesses:     StateAccessCounts,
+    current_generation: InstanceCounter,
+    entry_mapping:      Vec<trie::EntryId>,
+    iterators:          Vec<Option<trie::Iterator>>,
+    access_costs:       StateAccessCosts,
+    costs:              CostTable,
+    state_energy:       Option<InterpreterEnergy>,
+    call_depth:         CallDepth,
+    host_features:      HostFeatures,
+    random_seed:        Option<v0::RandomSeed>,
+}
+
+impl<'a, BackingStore, Param: Into<ParameterVec>, Ctx> From<InitHost<'a, BackingStore, Param, Ctx>>
+    for StoppedInitHost<Ctx>
+{
+    fn from(mut host: InitHost<'a, BackingStore, Param, Ctx>) -> Self {
+        // Execution might have stopped in the middle of a state operation.
+        host.state.leave_state_budget(&mut host.energy);
+        Self {
+            energy:             host.energy,
+            activation_frames:  host.activation_frames,
+            logs:               host.logs,
+            return_value:       host.return_value,
+            parameters:         host.parameters.into_iter().map(Into::into).collect(),
+            parameter_cursors:  host.parameter_cursors,
+            random_counter:     host.random_counter,
+            init_ctx:           host.init_ctx,
+            state_accesses:     host.state.access_counts,
+            current_generation: host.state.current_generation,
+            entry_mapping:      host.state.entry_mapping,
+            iterators:          host.state.iterators,
+            access_costs:       host.state.access_costs,
+            costs:              host.state.costs,
+            state_energy:       host.state.state_energy,
+            call_depth:         host.state.call_depth,
+            host_features:      host.state.host_features,
+            random_seed:        host.state.random_seed,
+        }
+    }
+}
+
+fn process_init_result<R: RunnableCode, Ctx>(
+    artifact: Arc<Artifact<ProcessedImports, R>>,
+    host: StoppedInitHost<Ctx>,
+    state: trie::MutableState,
+    result: machine::ExecutionResult<ExecutionOutcome<Interrupt>>,
+) -> ExecResult<InitResult<R, Ctx>> {
+    let remaining_energy = host.energy.energy;
+    let remaining_state_energy = host.state_energy.map(|e| e.energy);
     match result {
         Ok(ExecutionOutcome::Success {
             result,
@@ -1099,16 +2779,19 @@ pub fn invoke_init<BackingStore: BackingStoreLoad, R: RunnableCode>(
             if let Some(Value::I32(n)) = result {
                 if n == 0 {
                     Ok(InitResult::Success {
-                        logs,
-                        return_value,
+                        logs: host.logs,
+                        return_value: host.return_value,
                         remaining_energy,
-                        state: initial_state,
+                        state,
+                        state_accesses: host.state_accesses,
+                        remaining_state_energy,
                     })
                 } else {
                     Ok(InitResult::Reject {
                         reason: reason_from_wasm_error_code(n)?,
-                        return_value,
+                        return_value: host.return_value,
                         remaining_energy,
+                        remaining_state_energy,
                     })
                 }
             } else {
@@ -1117,19 +2800,165 @@ pub fn invoke_init<BackingStore: BackingStoreLoad, R: RunnableCode>(
         }
         Ok(ExecutionOutcome::Interrupted {
             reason,
-            config: _,
-        }) => match reason {},
-        Err(error) => {
-            if error.downcast_ref::<OutOfEnergy>().is_some() {
-                Ok(InitResult::OutOfEnergy)
-            } else {
-                Ok(InitResult::Trap {
-                    error,
-                    remaining_energy,
-                })
+            config,
+        }) => {
+            // Logs are returned per section that is executed, as for receive functions.
+            let saved = SavedInitHost {
+                activation_frames: host.activation_frames,
+                return_value: host.return_value,
+                parameters: host.parameters,
+                parameter_cursors: host.parameter_cursors,
+                random_counter: host.random_counter,
+                init_ctx: host.init_ctx,
+                state,
+                current_generation: host.current_generation,
+                entry_mapping: host.entry_mapping,
+                iterators: host.iterators,
+                access_costs: host.access_costs,
+                costs: host.costs,
+                state_energy: host.state_energy,
+                call_depth: host.call_depth,
+                host_features: host.host_features,
+                random_seed: host.random_seed,
+                pending_query: PendingQuery::of_interrupt(&reason),
+            };
+            Ok(InitResult::Interrupt {
+                remaining_energy,
+                state_accesses: host.state_accesses,
+                remaining_state_energy,
+                logs: host.logs,
+                config: Box::new(InitInterruptedState {
+                    host: saved,
+                    artifact,
+                    config,
+                }),
+                interrupt: reason,
+            })
+        }
+        Err(RuntimeError::OutOfEnergy) => Ok(InitResult::OutOfEnergy),
+        Err(error) => Ok(InitResult::Trap {
+            error: error.into_anyhow(),
+            remaining_energy,
+        }),
+    }
+}
+
+/// Resume execution of an init function after an interrupt, given the
+/// response of the operation that caused it. Unlike for receive functions the
+/// state is retained in the interrupted state, since the instance does not
+/// exist yet and thus its state cannot be modified by the operation. Only the
+/// faults and the trace of the options are used, see [InvokeOptions].
+#[cfg_attr(
+    feature = "instrumentation",
+    tracing::instrument(level = "info", skip_all, fields(energy = energy.energy))
+)]
+pub fn resume_init<BackingStore: BackingStoreLoad, Ctx: v0::HasInitContext>(
+    interrupted_state: Box<InitInterruptedState<CompiledFunction, Ctx>>,
+    response: InvokeResponse,
+    energy: InterpreterEnergy,
+    mut backing_store: BackingStore,
+    options: InvokeOptions,
+) -> ExecResult<InitResult<CompiledFunction, Ctx>> {
+    let InitInterruptedState {
+        host: saved,
+        artifact,
+        mut config,
+    } = *interrupted_state;
+    let mut state_trie = saved.state;
+    let inner = state_trie.get_inner(&mut backing_store);
+    // Nothing else can access the state of an instance that is being
+    // initialized, so the state is never updated by the operation.
+    let state = InstanceState::migrate(
+        false,
+        saved.current_generation,
+        saved.entry_mapping,
+        saved.iterators,
+        backing_store,
+        inner,
+    )
+    .with_access_costs(saved.access_costs)
+    .with_cost_table(saved.costs)
+    .with_state_energy(saved.state_energy)
+    .with_call_depth(saved.call_depth)
+    .with_host_features(saved.host_features)
+    .with_random_seed(saved.random_seed);
+    let mut host = InitHost {
+        energy,
+        activation_frames: saved.activation_frames,
+        logs: v0::Logs::new(),
+        state,
+        return_value: saved.return_value,
+        parameters: saved.parameters,
+        parameter_cursors: saved.parameter_cursors,
+        random_counter: saved.random_counter,
+        init_ctx: saved.init_ctx,
+    };
+    if let Some(query) = saved.pending_query {
+        match query {
+            PendingQuery::Contract(query) => {
+                let value = query.decode_response(response)?;
+                match query {
+                    ContractQuery::Exists => config.push_value(value as u32),
+                    ContractQuery::StateSize => config.push_value(value),
+                }
+            }
+            PendingQuery::AccountBalance => {
+                config.push_value(decode_account_balance_response(response)?)
+            }
+            PendingQuery::Conversion(conversion) => {
+                config.push_value(conversion.apply(&ExchangeRates::decode_response(response)?))
             }
+            PendingQuery::Upgrade => bail!("Init functions cannot upgrade."),
         }
+    } else {
+        if let Err(error) = response.charge_data(
+            &host.parameters,
+            &mut host.energy,
+            &host.state.costs,
+            host.state.host_features,
+        ) {
+            let host = StoppedInitHost::from(host);
+            return process_init_result(
+                artifact,
+                host,
+                state_trie,
+                Err(machine::RuntimeError::from_host(error)),
+            );
+        }
+        let response = response.encode(&mut host.parameters)?;
+        // push the response from the invoke
+        config.push_value(response);
     }
+    let result = match (options.faults, options.trace) {
+        (Some(faults), Some(trace)) => artifact.run_config(
+            &mut trace::TracingHost {
+                host: &mut faults::FaultInjectingHost {
+                    host: &mut host,
+                    faults,
+                },
+                trace,
+            },
+            config,
+        ),
+        (Some(faults), None) => artifact.run_config(
+            &mut faults::FaultInjectingHost {
+                host: &mut host,
+                faults,
+            },
+            config,
+        ),
+        (None, Some(trace)) => artifact.run_config(
+            &mut trace::TracingHost {
+                host: &mut host,
+                trace,
+            },
+            config,
+        ),
+        (None, None) => artifact.run_config(&mut host, config),
+    };
+    // release lock on the state
+    let host = StoppedInitHost::from(host);
+    process_init_result(artifact, host, state_trie, result)
 }
 
 /// Response from an invoke call.
@@ -1151,59 +2980,403 @@ pub enum InvokeResponse {
     },
 }
 
+impl InvokeResponse {
+    /// Encode the response as the value that is returned to the contract by
+    /// the `invoke` host function. If the response has data it is added to
+    /// the given parameters, and the encoding contains its index. See
+    /// [decode_invoke_response] for the details of the encoding.
+    pub(crate) fn encode(self, parameters: &mut Vec<ParameterVec>) -> ExecResult<u64> {
+        match self {
+            InvokeResponse::Success {
+                state_updated,
+                data,
+                ..
+            } => {
+                // the response value is constructed by setting the last 5 bytes to 0
+                // for the first 3 bytes, the first bit is 1 if the state changed, and 0
+                // otherwise the remaining bits are the index of the parameter.
+                let tag = if state_updated {
+                    0b1000_0000_0000_0000_0000_0000u64
+                } else {
+                    0
+                };
+                if let Some(data) = data {
+                    let len = parameters.len();
+                    if len > 0b0111_1111_1111_1111_1111_1111 {
+                        bail!("Too many calls.")
+                    }
+                    parameters.push(data);
+                    // return the index of the parameter to retrieve.
+                    Ok((len as u64 | tag) << 40)
+                } else {
+                    // modulo the tag, 0 indicates that there is no new response. This works
+                    // because if there is a response
+                    // len must be at least 1 since every contract starts by being
+                    // called with a parameter
+                    Ok(tag << 40)
+                }
+            }
+            InvokeResponse::Failure {
+                code,
+                data,
+            } => {
+                // state did not change
+                if let Some(data) = data {
+                    let len = parameters.len();
+                    if len > 0b0111_1111_1111_1111_1111_1111 {
+                        bail!("Too many calls.")
+                    }
+                    parameters.push(data);
+                    // return the index of the parameter to retrieve.
+                    Ok((len as u64) << 40 | code)
+                } else {
+                    Ok(code)
+                }
+            }
+        }
+    }
+
+    /// Charge for making the data of the response, if any, available to the
+    /// caller, and check that the total size of the return values the caller
+    /// retains, given by the parameters beyond its own parameter, stays within
+    /// [constants::MAX_RETAINED_RETURN_VALUES_SIZE]. This must be called
+    /// before [encode](Self::encode). It does nothing unless
+    /// [return_value_accounting](HostFeatures::return_value_accounting) is
+    /// enabled.
+    pub(crate) fn charge_data(
+        &self,
+        parameters: &[ParameterVec],
+        energy: &mut InterpreterEnergy,
+        costs: &CostTable,
+        host_features: HostFeatures,
+    ) -> ExecResult<()> {
+        if !host_features.return_value_accounting {
+            return Ok(());
+        }
+        let data = match self {
+            InvokeResponse::Success {
+                data,
+                ..
+            } => data,
+            InvokeResponse::Failure {
+                data,
+                ..
+            } => data,
+        };
+        if let Some(data) = data {
+            let size = parameters.iter().skip(1).map(|p| p.len()).sum::<usize>() + data.len();
+            ensure!(size <= constants::MAX_RETAINED_RETURN_VALUES_SIZE, ReturnValuesTooLarge {
+                size
+            });
+            energy.tick_energy(costs.receive_return_value.cost(data.len() as u32))?;
+        }
+        Ok(())
+    }
+}
+
+/// Successful outcome of an `invoke` operation, as seen by the contract.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvokeSuccess {
+    /// Whether the state of the contract was modified by the operation.
+    pub state_changed: bool,
+    /// Index of the return value of the operation, if there is one. Transfers
+    /// to accounts do not produce a return value.
+    pub return_value:  Option<u32>,
+}
+
+/// Failed outcome of an `invoke` operation, as seen by the contract. The
+/// state of the contract is never modified by a failed operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum InvokeFailure {
+    #[error("The balance of the contract is insufficient for the operation.")]
+    AmountTooLarge,
+    #[error("The account to transfer to does not exist.")]
+    MissingAccount,
+    #[error("The contract to call does not exist.")]
+    MissingContract,
+    #[error("The entrypoint to call does not exist.")]
+    MissingEntrypoint,
+    #[error("Sending a message to a V0 contract failed.")]
+    MessageFailed,
+    #[error("The called contract trapped.")]
+    Trap,
+    #[error("The called contract rejected with reason {}.", RejectReason::from(*reason))]
+    LogicReject {
+        /// The reject reason returned by the called contract.
+        reason:       i32,
+        /// Index of the return value produced by the called contract.
+        return_value: u32,
+    },
+    #[error("Unrecognized response {0:#018x}.")]
+    Unrecognized(u64),
+}
+
+impl InvokeFailure {
+    /// The reason a contract should reject with if it cannot handle the
+    /// failure. Rejections of the called contract are propagated as is, and
+    /// other failures are mapped to the corresponding [ReservedReason].
+    pub fn reject_reason(&self) -> i32 {
+        let reserved = match self {
+            InvokeFailure::AmountTooLarge => ReservedReason::InvokeAmountTooLarge,
+            InvokeFailure::MissingAccount => ReservedReason::InvokeMissingAccount,
+            InvokeFailure::MissingContract => ReservedReason::InvokeMissingContract,
+            InvokeFailure::MissingEntrypoint => ReservedReason::InvokeMissingEntrypoint,
+            InvokeFailure::MessageFailed => ReservedReason::InvokeMessageFailed,
+            InvokeFailure::Trap => ReservedReason::InvokeTrap,
+            InvokeFailure::LogicReject {
+                reason,
+                ..
+            } => return *reason,
+            InvokeFailure::Unrecognized(_) => ReservedReason::Unspecified,
+        };
+        reserved.code()
+    }
+
+    /// Describe the failure, with the reject reason of the called contract, if
+    /// it rejected, decoded by the reject reasons of its module. The name of
+    /// the called contract is without the `init_` prefix.
+    pub fn describe(&self, registry: &RejectReasonRegistry, called_contract: &str) -> String {
+        match self {
+            InvokeFailure::LogicReject {
+                reason,
+                ..
+            } => format!(
+                "The called contract rejected with reason {}.",
+                registry.decode(called_contract, *reason)
+            ),
+            other => other.to_string(),
+        }
+    }
+}
+
+/// Decode the value returned to the contract by the `invoke` host function.
+/// The value is produced by the host from an [InvokeResponse], and contract
+/// libraries must decode it in the same way as this function.
+///
+/// - If the lower 5 bytes are all 0 the operation succeeded. The highest bit
+///   indicates whether the state of the contract changed, and the next 23 bits
+///   are the index of the return value, with 0 meaning that there is no return
+///   value.
+/// - Otherwise, if the 5th byte (counting from the least significant byte) is
+///   non-zero it is the code of an error that occurred before or outside of
+///   execution of the called contract. No return value is produced.
+/// - Otherwise the called contract rejected. The lower 4 bytes are the reject
+///   reason, and the upper 3 bytes are the index of the return value.
+pub fn decode_invoke_response(response: u64) -> Result<InvokeSuccess, InvokeFailure> {
+    if response & 0x0000_00ff_ffff_ffff == 0 {
+        let rv = (response >> 40) as u32;
+        let index = rv & 0b0111_1111_1111_1111_1111_1111;
+        Ok(InvokeSuccess {
+            state_changed: rv & 0b1000_0000_0000_0000_0000_0000 != 0,
+            return_value:  if index == 0 {
+                None
+            } else {
+                Some(index)
+            },
+        })
+    } else if response & 0x0000_00ff_0000_0000 == 0 {
+        let reason = response as u32 as i32;
+        let return_value = (response >> 40) as u32;
+        if reason >= 0 || return_value == 0 {
+            return Err(InvokeFailure::Unrecognized(response));
+        }
+        Err(InvokeFailure::LogicReject {
+            reason,
+            return_value,
+        })
+    } else {
+        match (response >> 32) & 0xff {
+            0x01 => Err(InvokeFailure::AmountTooLarge),
+            0x02 => Err(InvokeFailure::MissingAccount),
+            0x03 => Err(InvokeFailure::MissingContract),
+            0x04 => Err(InvokeFailure::MissingEntrypoint),
+            0x05 => Err(InvokeFailure::MessageFailed),
+            0x06 => Err(InvokeFailure::Trap),
+            _ => Err(InvokeFailure::Unrecognized(response)),
+        }
+    }
+}
+
 /// Invokes an init-function from a given artifact *bytes*
 #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
-pub fn invoke_init_from_artifact<BackingStore: BackingStoreLoad>(
-    artifact_bytes: &[u8],
+pub fn invoke_init_from_artifact<'a, BackingStore: BackingStoreLoad, Ctx: v0::HasInitContext>(
+    artifact_bytes: &'a [u8],
     amount: u64,
-    init_ctx: impl v0::HasInitContext,
+    init_ctx: Ctx,
     init_name: &str,
     parameter: ParameterRef,
     energy: InterpreterEnergy,
     loader: BackingStore,
-) -> ExecResult<InitResult> {
+) -> ExecResult<InitResult<CompiledFunctionBytes<'a>, Ctx>> {
     let artifact = utils::parse_artifact(artifact_bytes)?;
-    invoke_init(artifact, amount, init_ctx, init_name, parameter, energy, loader)
+    invoke_init(
+        Arc::new(artifact),
+        amount,
+        init_ctx,
+        init_name,
+        parameter,
+        energy,
+        loader,
+        InvokeOptions::default(),
+    )
 }
 
 /// Invokes an init-function from Wasm module bytes
 #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
-pub fn invoke_init_from_source<BackingStore: BackingStoreLoad>(
+pub fn invoke_init_from_source<BackingStore: BackingStoreLoad, Ctx: v0::HasInitContext>(
     source_bytes: &[u8],
     amount: u64,
-    init_ctx: impl v0::HasInitContext,
+    init_ctx: Ctx,
     init_name: &str,
     parameter: ParameterRef,
     energy: InterpreterEnergy,
     loader: BackingStore,
-) -> ExecResult<InitResult> {
-    let artifact = utils::instantiate(&ConcordiumAllowedImports, source_bytes)?;
-    invoke_init(artifact, amount, init_ctx, init_name, parameter, energy, loader)
+) -> ExecResult<InitResult<CompiledFunction, Ctx>> {
+    let artifact = utils::instantiate(&ConcordiumAllowedImports::LATEST, source_bytes)?;
+    invoke_init(
+        Arc::new(artifact),
+        amount,
+        init_ctx,
+        init_name,
+        parameter,
+        energy,
+        loader,
+        InvokeOptions::default(),
+    )
 }
 
 /// Same as `invoke_init_from_source`, except that the module has cost
 /// accounting instructions inserted before the init function is called.
 #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
-pub fn invoke_init_with_metering_from_source<BackingStore: BackingStoreLoad>(
+pub fn invoke_init_with_metering_from_source<
+    BackingStore: BackingStoreLoad,
+    Ctx: v0::HasInitContext,
+>(
     source_bytes: &[u8],
     amount: u64,
-    init_ctx: impl v0::HasInitContext,
+    init_ctx: Ctx,
     init_name: &str,
     parameter: ParameterRef,
     energy: InterpreterEnergy,
     loader: BackingStore,
-) -> ExecResult<InitResult> {
-    let artifact = utils::instantiate_with_metering(&ConcordiumAllowedImports, source_bytes)?;
-    invoke_init(artifact, amount, init_ctx, init_name, parameter, energy, loader)
+) -> ExecResult<InitResult<CompiledFunction, Ctx>> {
+    let artifact =
+        utils::instantiate_with_metering(&ConcordiumAllowedImports::LATEST, source_bytes)?;
+    invoke_init(
+        Arc::new(artifact),
+        amount,
+        init_ctx,
+        init_name,
+        parameter,
+        energy,
+        loader,
+        InvokeOptions::default(),
+    )
+}
+
+#[cfg(feature = "tooling")]
+/// Invoke an init function, and if it succeeds, persist the resulting state to
+/// the file at the given path, see [persist_state].
+///
+/// Returns the result of execution together with the hash of the new state
+/// in case of success. The state is not written if execution did not succeed.
+/// The init function is executed with the default [HostFeatures], so it is
+/// not interrupted. The state of an init function that is interrupted and
+/// resumed with [resume_init] can be persisted with [persist_state] when it
+/// succeeds.
+pub fn invoke_init_and_persist<R: RunnableCode, Ctx: v0::HasInitContext>(
+    artifact: Arc<Artifact<ProcessedImports, R>>,
+    amount: u64,
+    init_ctx: Ctx,
+    init_name: &str,
+    parameter: ParameterRef,
+    energy: InterpreterEnergy,
+    path: impl AsRef<std::path::Path>,
+) -> ExecResult<(InitResult<R, Ctx>, Option<trie::Hash>)> {
+    // The initial state is empty, so the loader is never used to load any data.
+    let loader = trie::Loader::new(&[][..]);
+    let mut result = invoke_init(
+        artifact,
+        amount,
+        init_ctx,
+        init_name,
+        parameter,
+        energy,
+        loader,
+        InvokeOptions::default(),
+    )?;
+    match &mut result {
+        InitResult::Success {
+            state,
+            ..
+        } => {
+            let hash = persist_state(state, path)?;
+            Ok((result, Some(hash)))
+        }
+        InitResult::Interrupt {
+            ..
+        } => bail!("The state of an interrupted init function cannot be persisted."),
+        _ => Ok((result, None)),
+    }
+}
+
+#[cfg(feature = "tooling")]
+/// Persist the state of a new instance to the file at the given path, and
+/// return its hash. The state is written in the format of
+/// [PersistentState::save](trie::PersistentState::save), and can be read back
+/// with [PersistentState::load_saved](trie::PersistentState::load_saved).
+///
+/// The state is first written to a temporary file with a unique name in the
+/// same directory, which is synced to disk and then renamed to the given path.
+/// Thus the file at the given path is either left untouched, or contains the
+/// complete new state, even if the process is interrupted or other states are
+/// persisted to the same path concurrently.
+pub fn persist_state(
+    state: &mut trie::MutableState,
+    path: impl AsRef<std::path::Path>,
+) -> ExecResult<trie::Hash> {
+    // The state of a new instance is not backed by any stored data, so the
+    // loader is never used to load any data.
+    let mut loader = trie::Loader::new(&[][..]);
+    let persistent = state.freeze(&mut loader, &mut trie::EmptyCollector);
+    let mut bytes = Vec::new();
+    let hash = persistent.save(&mut loader, &mut bytes)?;
+    write_file_atomically(path.as_ref(), &bytes)?;
+    Ok(hash)
+}
+
+#[cfg(feature = "tooling")]
+/// Write the data to the given path by first writing it to a temporary file
+/// with a unique name in the same directory, syncing it to disk, and then
+/// renaming it to the target path. Concurrent writers thus never write to the
+/// same temporary file, and the temporary file is removed if writing fails.
+fn write_file_atomically(path: &std::path::Path, data: &[u8]) -> anyhow::Result<()> {
+    ensure!(path.file_name().is_some(), "{} is not a file.", path.display());
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => std::path::Path::new("."),
+    };
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+    file.write_all(data)?;
+    file.as_file().sync_all()?;
+    file.persist(path)?;
+    // Make sure the rename itself is durable. Directories cannot be opened as
+    // files on all platforms, so this is only done where it is supported.
+    #[cfg(unix)]
+    std::fs::File::open(dir)?.sync_all()?;
+    Ok(())
 }
 
 fn process_receive_result<BackingStore, Param, R: RunnableCode, Ctx1, Ctx2>(
     artifact: Arc<Artifact<ProcessedImports, R>>,
-    host: ReceiveHost<'_, BackingStore, Param, Ctx1>,
-    result: machine::RunResult<ExecutionOutcome<Interrupt>>,
+    mut host: ReceiveHost<'_, BackingStore, Param, Ctx1>,
+    result: machine::ExecutionResult<ExecutionOutcome<Interrupt>>,
 ) -> ExecResult<ReceiveResult<R, Ctx2>>
 where
     StateLessReceiveHost<ParameterVec, Ctx2>: From<StateLessReceiveHost<Param, Ctx1>>, {
+    // Execution might have stopped in the middle of a state operation.
+    host.state.leave_state_budget(&mut host.energy);
+    let remaining_state_energy = host.state.state_energy.map(|e| e.energy);
     let mut stateless = host.stateless;
     match result {
         Ok(ExecutionOutcome::Success {
@@ -1218,12 +3391,16 @@ where
                         state_changed: host.state.changed,
                         return_value: stateless.return_value,
                         remaining_energy,
+                        state_accesses: host.state.access_counts,
+                        remaining_state_energy,
+                        freeze_cost: host.state.freeze_cost(),
                     })
                 } else {
                     Ok(ReceiveResult::Reject {
                         reason: reason_from_wasm_error_code(n)?,
                         return_value: stateless.return_value,
                         remaining_energy,
+                        remaining_state_energy,
                     })
                 }
             } else {
@@ -1243,15 +3420,26 @@ where
             // existing logs.
             let logs = std::mem::take(&mut stateless.logs);
             let state_changed = host.state.changed;
+            let state_accesses = host.state.access_counts;
             let host = SavedHost {
                 stateless:          stateless.into(),
                 current_generation: host.state.current_generation,
                 entry_mapping:      host.state.entry_mapping,
                 iterators:          host.state.iterators,
+                access_costs:       host.state.access_costs,
+                costs:              host.state.costs,
+                read_only:          host.state.read_only,
+                state_energy:       host.state.state_energy,
+                call_depth:         host.state.call_depth,
+                host_features:      host.state.host_features,
+                random_seed:        host.state.random_seed,
+                pending_query:      PendingQuery::of_interrupt(&reason),
             };
             Ok(ReceiveResult::Interrupt {
                 remaining_energy,
                 state_changed,
+                state_accesses,
+                remaining_state_energy,
                 logs,
                 config: Box::new(ReceiveInterruptedState {
                     host,
@@ -1261,20 +3449,27 @@ where
                 interrupt: reason,
             })
         }
-        Err(error) => {
-            if error.downcast_ref::<OutOfEnergy>().is_some() {
-                Ok(ReceiveResult::OutOfEnergy)
-            } else {
-                Ok(ReceiveResult::Trap {
-                    error,
-                    remaining_energy: host.energy.energy,
-                })
-            }
-        }
+        Err(RuntimeError::OutOfEnergy) => Ok(ReceiveResult::OutOfEnergy),
+        Err(error) => Ok(ReceiveResult::Trap {
+            error:            error.into_anyhow(),
+            remaining_energy: host.energy.energy,
+        }),
     }
 }
 
-/// Invokes an receive-function from a given artifact
+/// Invokes an receive-function from a given artifact, with the host configured
+/// by the options. With
+/// [read_only_views](HostFeatures::read_only_views), view entrypoints are
+/// executed with a read-only state, see [is_view_entrypoint].
+#[cfg_attr(
+    feature = "instrumentation",
+    tracing::instrument(
+        level = "info",
+        skip_all,
+        fields(entrypoint = receive_name.get_chain_name(), energy = energy.energy)
+    )
+)]
+#[allow(clippy::too_many_arguments)]
 pub fn invoke_receive<
     BackingStore: BackingStoreLoad,
     R: RunnableCode,
@@ -1288,7 +3483,13 @@ pub fn invoke_receive<
     param: ParameterRef,
     energy: InterpreterEnergy,
     instance_state: InstanceState<BackingStore>,
+    options: InvokeOptions,
 ) -> ExecResult<ReceiveResult<R, Ctx2>> {
+    let name = receive_name.get_chain_name();
+    let mut state = options.configure(instance_state);
+    if options.host_features.read_only_views && is_view_entrypoint(name) {
+        state = state.with_read_only(true);
+    }
     let mut host = ReceiveHost {
         energy,
         stateless: StateLessReceiveHost {
@@ -1296,16 +3497,54 @@ pub fn invoke_receive<
             logs: v0::Logs::new(),
             return_value: Vec::new(),
             parameters: vec![param],
+            parameter_cursors: ParameterCursors::default(),
+            random_counter: 0,
             receive_ctx,
         },
-        state: instance_state,
+        state,
     };
 
-    let result =
-        artifact.run(&mut host, receive_name.get_chain_name(), &[Value::I64(amount as i64)]);
+    let args = [Value::I64(amount as i64)];
+    let result = match (options.faults, options.trace) {
+        (Some(faults), Some(trace)) => artifact.run(
+            &mut trace::TracingHost {
+                host: &mut faults::FaultInjectingHost {
+                    host: &mut host,
+                    faults,
+                },
+                trace,
+            },
+            name,
+            &args,
+        ),
+        (Some(faults), None) => artifact.run(
+            &mut faults::FaultInjectingHost {
+                host: &mut host,
+                faults,
+            },
+            name,
+            &args,
+        ),
+        (None, Some(trace)) => artifact.run(
+            &mut trace::TracingHost {
+                host: &mut host,
+                trace,
+            },
+            name,
+            &args,
+        ),
+        (None, None) => artifact.run(&mut host, name, &args),
+    };
     process_receive_result(artifact, host, result)
 }
 
+/// Resume execution of a receive function after an interrupt, given the
+/// response of the operation that caused it. Only the faults and the trace of
+/// the options are used, see [InvokeOptions].
+#[cfg_attr(
+    feature = "instrumentation",
+    tracing::instrument(level = "info", skip_all, fields(energy = energy.energy))
+)]
 pub fn resume_receive<BackingStore: BackingStoreLoad>(
     interrupted_state: Box<ReceiveInterruptedState<CompiledFunction>>,
     response: InvokeResponse,  // response from the call
@@ -1313,6 +3552,7 @@ pub fn resume_receive<BackingStore: BackingStoreLoad>(
     state_trie: &mut trie::MutableState,
     state_updated: bool,
     mut backing_store: BackingStore,
+    options: InvokeOptions,
 ) -> ExecResult<ReceiveResult<CompiledFunction>> {
     let inner = state_trie.get_inner(&mut backing_store);
     let state = InstanceState::migrate(
@@ -1322,65 +3562,90 @@ pub fn resume_receive<BackingStore: BackingStoreLoad>(
         interrupted_state.host.iterators,
         backing_store,
         inner,
-    );
+    )
+    .with_access_costs(interrupted_state.host.access_costs)
+    .with_cost_table(interrupted_state.host.costs)
+    .with_read_only(interrupted_state.host.read_only)
+    .with_state_energy(interrupted_state.host.state_energy)
+    .with_call_depth(interrupted_state.host.call_depth)
+    .with_host_features(interrupted_state.host.host_features)
+    .with_random_seed(interrupted_state.host.random_seed);
     let mut host = ReceiveHost {
         stateless: interrupted_state.host.stateless,
         energy,
         state,
     };
-    let response = match response {
-        InvokeResponse::Success {
-            state_updated,
-            new_balance,
-            data,
-        } => {
-            host.stateless.receive_ctx.common.self_balance = new_balance;
-            // the response value is constructed by setting the last 5 bytes to 0
-            // for the first 3 bytes, the first bit is 1 if the state changed, and 0
-            // otherwise the remaining bits are the index of the parameter.
-            let tag = if state_updated {
-                0b1000_0000_0000_0000_0000_0000u64
-            } else {
-                0
-            };
-            if let Some(data) = data {
-                let len = host.stateless.parameters.len();
-                if len > 0b0111_1111_1111_1111_1111_1111 {
-                    bail!("Too many calls.")
-                }
-                host.stateless.parameters.push(data);
-                // return the index of the parameter to retrieve.
-                (len as u64 | tag) << 40
-            } else {
-                // modulo the tag, 0 indicates that there is no new response. This works
-                // because if there is a response
-                // len must be at least 1 since every contract starts by being
-                // called with a parameter
-                tag << 40
+    let mut config = interrupted_state.config;
+    if let Some(query) = interrupted_state.host.pending_query {
+        // Queries and upgrades do not execute anything, so the balance is
+        // unchanged.
+        match query {
+            PendingQuery::Contract(query) => {
+                let value = query.decode_response(response)?;
+                match query {
+                    ContractQuery::Exists => config.push_value(value as u32),
+                    ContractQuery::StateSize => config.push_value(value),
+                }
             }
-        }
-        InvokeResponse::Failure {
-            code,
-            data,
-        } => {
-            // state did not change
-            if let Some(data) = data {
-                let len = host.stateless.parameters.len();
-                if len > 0b0111_1111_1111_1111_1111_1111 {
-                    bail!("Too many calls.")
-                }
-                host.stateless.parameters.push(data);
-                // return the index of the parameter to retrieve.
-                (len as u64) << 40 | code
-            } else {
-                code
+            PendingQuery::AccountBalance => {
+                config.push_value(decode_account_balance_response(response)?)
             }
+            PendingQuery::Conversion(conversion) => {
+                config.push_value(conversion.apply(&ExchangeRates::decode_response(response)?))
+            }
+            PendingQuery::Upgrade => config.push_value(decode_upgrade_response(response)?),
+        }
+    } else {
+        if let InvokeResponse::Success {
+            new_balance,
+            ..
+        } = &response
+        {
+            host.stateless.receive_ctx.common.self_balance = *new_balance;
+        }
+        if let Err(error) = response.charge_data(
+            &host.stateless.parameters,
+            &mut host.energy,
+            &host.state.costs,
+            host.state.host_features,
+        ) {
+            return process_receive_result(
+                interrupted_state.artifact,
+                host,
+                Err(machine::RuntimeError::from_host(error)),
+            );
         }
+        let response = response.encode(&mut host.stateless.parameters)?;
+        // push the response from the invoke
+        config.push_value(response);
+    }
+    let result = match (options.faults, options.trace) {
+        (Some(faults), Some(trace)) => interrupted_state.artifact.run_config(
+            &mut trace::TracingHost {
+                host: &mut faults::FaultInjectingHost {
+                    host: &mut host,
+                    faults,
+                },
+                trace,
+            },
+            config,
+        ),
+        (Some(faults), None) => interrupted_state.artifact.run_config(
+            &mut faults::FaultInjectingHost {
+                host: &mut host,
+                faults,
+            },
+            config,
+        ),
+        (None, Some(trace)) => interrupted_state.artifact.run_config(
+            &mut trace::TracingHost {
+                host: &mut host,
+                trace,
+            },
+            config,
+        ),
+        (None, None) => interrupted_state.artifact.run_config(&mut host, config),
     };
-    // push the response from the invoke
-    let mut config = interrupted_state.config;
-    config.push_value(response);
-    let result = interrupted_state.artifact.run_config(&mut host, config);
     process_receive_result(interrupted_state.artifact, host, result)
 }
 
@@ -1420,6 +3685,7 @@ pub fn invoke_receive_from_artifact<
         parameter,
         energy,
         instance_state,
+        InvokeOptions::default(),
     )
 }
 
@@ -1438,7 +3704,7 @@ pub fn invoke_receive_from_source<
     energy: InterpreterEnergy,
     instance_state: InstanceState<BackingStore>,
 ) -> ExecResult<ReceiveResult<CompiledFunction, Ctx2>> {
-    let artifact = utils::instantiate(&ConcordiumAllowedImports, source_bytes)?;
+    let artifact = utils::instantiate(&ConcordiumAllowedImports::LATEST, source_bytes)?;
     invoke_receive(
         Arc::new(artifact),
         amount,
@@ -1447,6 +3713,7 @@ pub fn invoke_receive_from_source<
         parameter,
         energy,
         instance_state,
+        InvokeOptions::default(),
     )
 }
 
@@ -1466,7 +3733,8 @@ pub fn invoke_receive_with_metering_from_source<
     energy: InterpreterEnergy,
     instance_state: InstanceState<BackingStore>,
 ) -> ExecResult<ReceiveResult<CompiledFunction, Ctx2>> {
-    let artifact = utils::instantiate_with_metering(&ConcordiumAllowedImports, source_bytes)?;
+    let artifact =
+        utils::instantiate_with_metering(&ConcordiumAllowedImports::LATEST, source_bytes)?;
     invoke_receive(
         Arc::new(artifact),
         amount,
@@ -1475,5 +3743,6 @@ pub fn invoke_receive_with_metering_from_source<
         parameter,
         energy,
         instance_state,
+        InvokeOptions::default(),
     )
 }
@@ -1,8 +1,17 @@
 use super::{
+    decode_account_balance_response, decode_invoke_response, decode_upgrade_response,
     trie::{self, MutableState},
     types::*,
+    ContractQuery, Conversion, ExchangeRates, Interrupt, InvokeFailure, InvokeResponse,
+    InvokeSuccess,
+};
+use crate::{
+    constants::{self, CostTable},
+    reject::{RejectReason, RejectReasonRegistry},
+    v0,
 };
 use anyhow::{ensure, Context};
+use concordium_contracts_common::{to_bytes, AccountAddress, Address, Amount, ContractAddress};
 use quickcheck::*;
 
 const NUM_TESTS: u64 = 100000;
@@ -716,3 +725,1298 @@ fn test_invalid_generation_operations() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+/// Check that decoding the encoding of an invoke response recovers the
+/// response, and the index of any return value.
+fn prop_invoke_response_encoding() {
+    let prop = |state_updated: bool,
+                data: Option<Vec<u8>>,
+                reason: i32,
+                num_params: u8|
+     -> anyhow::Result<()> {
+        let mut parameters = vec![Vec::new(); usize::from(num_params) + 1];
+        let expected_index = parameters.len() as u32;
+        let has_data = data.is_some();
+        let success = InvokeResponse::Success {
+            state_updated,
+            new_balance: concordium_contracts_common::Amount::from_micro_ccd(0),
+            data,
+        };
+        let decoded = decode_invoke_response(success.encode(&mut parameters)?);
+        ensure!(
+            decoded
+                == Ok(InvokeSuccess {
+                    state_changed: state_updated,
+                    return_value:  if has_data {
+                        Some(expected_index)
+                    } else {
+                        None
+                    },
+                }),
+            "Incorrectly decoded success: {:?}.",
+            decoded
+        );
+        // only negative reject reasons are allowed.
+        let reason = if reason >= 0 {
+            -1
+        } else {
+            reason
+        };
+        let expected_index = parameters.len() as u32;
+        let reject = InvokeResponse::Failure {
+            code: u64::from(reason as u32),
+            data: Some(Vec::new()),
+        };
+        let decoded = decode_invoke_response(reject.encode(&mut parameters)?);
+        ensure!(
+            decoded
+                == Err(InvokeFailure::LogicReject {
+                    reason,
+                    return_value: expected_index,
+                }),
+            "Incorrectly decoded rejection: {:?}.",
+            decoded
+        );
+        Ok(())
+    };
+    QuickCheck::new().tests(NUM_TESTS).quickcheck(prop as fn(_, _, _, _) -> anyhow::Result<()>);
+}
+
+#[test]
+/// Check that environment errors are decoded to the correct failures.
+fn test_invoke_environment_errors() -> anyhow::Result<()> {
+    let mut parameters = Vec::new();
+    let failure = |code: u64| InvokeResponse::Failure {
+        code: code << 32,
+        data: None,
+    };
+    for (code, expected) in [
+        (0x01, InvokeFailure::AmountTooLarge),
+        (0x02, InvokeFailure::MissingAccount),
+        (0x03, InvokeFailure::MissingContract),
+        (0x04, InvokeFailure::MissingEntrypoint),
+        (0x05, InvokeFailure::MessageFailed),
+        (0x06, InvokeFailure::Trap),
+    ]
+    .iter()
+    {
+        let decoded = decode_invoke_response(failure(*code).encode(&mut parameters)?);
+        ensure!(decoded == Err(*expected), "Code {} decoded to {:?}.", code, decoded);
+        ensure!(
+            matches!(RejectReason::from(expected.reject_reason()), RejectReason::Reserved(_)),
+            "Failure {:?} must map to a reserved reject reason.",
+            expected
+        );
+    }
+    ensure!(parameters.is_empty(), "Environment errors do not produce return values.");
+    Ok(())
+}
+
+#[test]
+/// Check that rejections of called contracts are described with the names of
+/// their reject reasons.
+fn test_describe_invoke_failure() -> anyhow::Result<()> {
+    let mut registry = RejectReasonRegistry::default();
+    registry.insert("escrow", -2, "IncorrectAmount")?;
+    let reject = |reason| InvokeFailure::LogicReject {
+        reason,
+        return_value: 0,
+    };
+    assert_eq!(
+        reject(-2).describe(&registry, "escrow"),
+        "The called contract rejected with reason -2 => IncorrectAmount."
+    );
+    assert_eq!(
+        reject(-2).describe(&registry, "other"),
+        "The called contract rejected with reason -2."
+    );
+    assert_eq!(InvokeFailure::Trap.describe(&registry, "escrow"), InvokeFailure::Trap.to_string());
+    Ok(())
+}
+
+#[test]
+/// Check that responses to contract queries are decoded to the values returned
+/// to the contract, and that malformed responses are rejected.
+fn test_contract_query_responses() -> anyhow::Result<()> {
+    let success = |data: Option<Vec<u8>>| InvokeResponse::Success {
+        state_updated: false,
+        new_balance: Amount::from_micro_ccd(0),
+        data,
+    };
+    let failure = |code: u64| InvokeResponse::Failure {
+        code: code << 32,
+        data: None,
+    };
+    ensure!(ContractQuery::Exists.decode_response(success(None))? == 1, "Contract should exist.");
+    ensure!(ContractQuery::Exists.decode_response(failure(0x03))? == 0, "Contract is missing.");
+    ensure!(
+        ContractQuery::StateSize.decode_response(success(Some(1234u64.to_le_bytes().to_vec())))?
+            == 1234,
+        "Incorrect state size."
+    );
+    ensure!(ContractQuery::StateSize.decode_response(failure(0x03))? == -1, "Contract is missing.");
+    ensure!(
+        ContractQuery::StateSize.decode_response(success(Some(vec![0u8; 4]))).is_err(),
+        "The state size must be 8 bytes."
+    );
+    ensure!(
+        ContractQuery::StateSize
+            .decode_response(success(Some(u64::MAX.to_le_bytes().to_vec())))
+            .is_err(),
+        "The state size must fit into an i64."
+    );
+    ensure!(
+        ContractQuery::Exists.decode_response(failure(0x06)).is_err(),
+        "Only missing contracts are expected failures."
+    );
+    let mut out = Vec::new();
+    Interrupt::QueryContract {
+        address: ContractAddress {
+            index:    1,
+            subindex: 2,
+        },
+        query:   ContractQuery::StateSize,
+    }
+    .to_bytes(&mut out)?;
+    ensure!(
+        out == [&[2u8][..], &1u64.to_be_bytes(), &2u64.to_be_bytes(), &[1u8]].concat(),
+        "Incorrect serialization of the query: {:?}.",
+        out
+    );
+    Ok(())
+}
+
+#[test]
+/// Check that responses to account balance queries are decoded to the values
+/// returned to the contract, and that the query is serialized and displayed
+/// with the address of the account.
+fn test_account_balance_query() -> anyhow::Result<()> {
+    let success = |data: Option<Vec<u8>>| InvokeResponse::Success {
+        state_updated: false,
+        new_balance: Amount::from_micro_ccd(0),
+        data,
+    };
+    let failure = |code: u64| InvokeResponse::Failure {
+        code: code << 32,
+        data: None,
+    };
+    ensure!(
+        decode_account_balance_response(success(Some(1234u64.to_le_bytes().to_vec())))? == 1234,
+        "Incorrect balance."
+    );
+    ensure!(decode_account_balance_response(failure(0x02))? == -1, "Account is missing.");
+    ensure!(
+        decode_account_balance_response(success(None)).is_err(),
+        "The balance must be 8 bytes."
+    );
+    ensure!(
+        decode_account_balance_response(failure(0x03)).is_err(),
+        "Only missing accounts are expected failures."
+    );
+    let address = AccountAddress([7u8; 32]);
+    let query = Interrupt::QueryAccountBalance {
+        address,
+    };
+    let mut out = Vec::new();
+    query.to_bytes(&mut out)?;
+    ensure!(
+        out == [&[3u8][..], &[7u8; 32]].concat(),
+        "Incorrect serialization of the query: {:?}.",
+        out
+    );
+    ensure!(
+        query.to_string() == format!("query the balance of {}", address),
+        "Incorrect display of a query: {}.",
+        query
+    );
+    Ok(())
+}
+
+#[test]
+/// Check that conversions between energy and microCCD are rounded as
+/// specified, are exact even if intermediate results exceed 128 bits, and that
+/// responses to exchange rate queries are decoded.
+fn test_exchange_rates() -> anyhow::Result<()> {
+    let rates = ExchangeRates {
+        euro_per_energy:    (1, 1000),
+        micro_ccd_per_euro: (1_000_000, 7),
+    };
+    ensure!(rates.energy_to_micro_ccd(21) == 3000, "Exact conversions are not rounded.");
+    ensure!(rates.energy_to_micro_ccd(1) == 143, "Energy is converted rounding up.");
+    ensure!(rates.energy_to_micro_ccd(0) == 0, "No energy costs nothing.");
+    ensure!(rates.micro_ccd_to_energy(3000) == 21, "Exact conversions are not rounded.");
+    ensure!(rates.micro_ccd_to_energy(143) == 1, "Amounts are converted rounding down.");
+    ensure!(rates.micro_ccd_to_energy(142) == 0, "Amounts are converted rounding down.");
+    ensure!(
+        rates.micro_euro_to_micro_ccd(7_000_000) == 1_000_000,
+        "Exact conversions are not rounded."
+    );
+    ensure!(rates.micro_euro_to_micro_ccd(1) == 1, "Euros are converted rounding up.");
+    ensure!(
+        rates.micro_ccd_to_micro_euro(1_000_000) == 7_000_000,
+        "Incorrect conversion to euros."
+    );
+
+    let max = u64::MAX;
+    let one = ExchangeRates {
+        euro_per_energy:    (max, max),
+        micro_ccd_per_euro: (max, max),
+    };
+    ensure!(one.energy_to_micro_ccd(max) == max, "The conversion should be exact.");
+    ensure!(one.micro_ccd_to_energy(max) == max, "The conversion should be exact.");
+    ensure!(one.micro_euro_to_micro_ccd(1_000_001) == 2, "Euros are converted rounding up.");
+    ensure!(one.micro_ccd_to_micro_euro(max) == max, "Overflowing results saturate.");
+    let expensive = ExchangeRates {
+        euro_per_energy:    (max, 1),
+        micro_ccd_per_euro: (max, 1),
+    };
+    ensure!(expensive.energy_to_micro_ccd(2) == max, "Overflowing results saturate.");
+    ensure!(expensive.micro_ccd_to_energy(max) == 0, "Large denominators are exact.");
+
+    let success = |data: Option<Vec<u8>>| InvokeResponse::Success {
+        state_updated: false,
+        new_balance: Amount::from_micro_ccd(0),
+        data,
+    };
+    let encode = |values: [u64; 4]| {
+        Some(values.iter().flat_map(|v| v.to_le_bytes().to_vec()).collect::<Vec<u8>>())
+    };
+    ensure!(
+        ExchangeRates::decode_response(success(encode([1, 1000, 1_000_000, 7])))? == rates,
+        "Incorrect exchange rates."
+    );
+    ensure!(
+        ExchangeRates::decode_response(success(encode([1, 0, 1_000_000, 7]))).is_err(),
+        "Rates must be non-zero."
+    );
+    ensure!(
+        ExchangeRates::decode_response(success(Some(vec![1; 24]))).is_err(),
+        "The rates must be 32 bytes."
+    );
+    ensure!(
+        ExchangeRates::decode_response(InvokeResponse::Failure {
+            code: 0x03 << 32,
+            data: None,
+        })
+        .is_err(),
+        "Failures are not expected."
+    );
+
+    let query = Interrupt::QueryExchangeRates {
+        conversion: Conversion::EnergyToMicroCcd(1),
+    };
+    let mut out = Vec::new();
+    query.to_bytes(&mut out)?;
+    ensure!(out == [5u8], "Incorrect serialization of the query: {:?}.", out);
+    ensure!(
+        query.to_string() == "query the exchange rates",
+        "Incorrect display of a query: {}.",
+        query
+    );
+    let mut loader = trie::Loader::new(&[][..]);
+    let mut mutable = MutableState::initial_state();
+    let inner = mutable.get_inner(&mut loader);
+    let state = InstanceState::new(0, loader, inner).with_read_only(true);
+    ensure!(state.check_read_only(&query).is_ok(), "Queries are allowed in read-only executions.");
+    Ok(())
+}
+
+#[test]
+/// Check that conversions agree with a direct computation when the
+/// intermediate results fit in 128 bits.
+fn prop_exchange_rates() {
+    let prop = |value: u32, rates: (u16, u16, u16, u16)| -> bool {
+        let (a, b, c, d) = rates;
+        let (a, b, c, d) = (a.max(1), b.max(1), c.max(1), d.max(1));
+        let rates = ExchangeRates {
+            euro_per_energy:    (a.into(), b.into()),
+            micro_ccd_per_euro: (c.into(), d.into()),
+        };
+        let value = u128::from(value);
+        let num = u128::from(a) * u128::from(c);
+        let den = u128::from(b) * u128::from(d);
+        let to_ccd = (value * num + den - 1) / den;
+        let to_energy = value * den / num;
+        u128::from(rates.energy_to_micro_ccd(value as u64)) == to_ccd
+            && u128::from(rates.micro_ccd_to_energy(value as u64)) == to_energy
+    };
+    QuickCheck::new().tests(NUM_TESTS).quickcheck(prop as fn(u32, (u16, u16, u16, u16)) -> bool);
+}
+
+#[test]
+/// Check that responses to upgrades are decoded to the values returned to the
+/// contract, that upgrades are serialized with the module reference, and that
+/// they are not allowed in read-only executions.
+fn test_upgrade() -> anyhow::Result<()> {
+    let success = InvokeResponse::Success {
+        state_updated: false,
+        new_balance:   Amount::from_micro_ccd(0),
+        data:          None,
+    };
+    let failure = |code: u64| InvokeResponse::Failure {
+        code: code << 32,
+        data: None,
+    };
+    ensure!(decode_upgrade_response(success)? == 0, "The upgrade succeeded.");
+    for code in [0x07, 0x08, 0x09].iter() {
+        let value = decode_upgrade_response(failure(*code))?;
+        ensure!(value == code << 32, "Incorrect value {:#x} for failure {:#x}.", value, code);
+        ensure!(
+            decode_invoke_response(value) == Err(InvokeFailure::Unrecognized(value)),
+            "Upgrade failures do not overlap with invoke failures."
+        );
+    }
+    ensure!(decode_upgrade_response(failure(0x03)).is_err(), "Only upgrade failures are expected.");
+    let upgrade = Interrupt::Upgrade {
+        module_ref: [5u8; 32],
+    };
+    let mut out = Vec::new();
+    upgrade.to_bytes(&mut out)?;
+    ensure!(
+        out == [&[4u8][..], &[5u8; 32]].concat(),
+        "Incorrect serialization of the upgrade: {:?}.",
+        out
+    );
+    ensure!(
+        upgrade.to_string() == format!("upgrade to module {}", "05".repeat(32)),
+        "Incorrect display of an upgrade: {}.",
+        upgrade
+    );
+    let mut loader = trie::Loader::new(&[][..]);
+    let mut mutable = MutableState::initial_state();
+    let inner = mutable.get_inner(&mut loader);
+    let state = InstanceState::new(0, loader, inner).with_read_only(true);
+    ensure!(
+        state.check_read_only(&upgrade).is_err(),
+        "Upgrades are not allowed in read-only executions."
+    );
+    Ok(())
+}
+
+#[test]
+/// Check that senders are written to memory with the length of their encoding,
+/// and that nothing is written if the encoding does not fit into memory.
+fn test_write_sender_address() -> anyhow::Result<()> {
+    let contract = Address::Contract(ContractAddress {
+        index:    5,
+        subindex: 7,
+    });
+    let account = Address::Account(AccountAddress([3u8; 32]));
+    for (address, len) in [(contract, 17), (account, 33)].iter() {
+        let mut memory = vec![0xffu8; 40];
+        let written = v0::host::write_address(&mut memory, 2, address)? as usize;
+        ensure!(written == *len, "Incorrect length {} of {:?}.", written, address);
+        ensure!(
+            memory[2..2 + len] == to_bytes(address)[..],
+            "Incorrect encoding of {:?}.",
+            address
+        );
+        ensure!(
+            memory[2 + len..].iter().all(|b| *b == 0xff),
+            "Wrote past the end of {:?}.",
+            address
+        );
+        let mut memory = vec![0xffu8; len + 1];
+        ensure!(
+            v0::host::write_address(&mut memory, 2, address).is_err(),
+            "{:?} should not fit into memory.",
+            address
+        );
+        ensure!(memory.iter().all(|b| *b == 0xff), "Partially wrote {:?}.", address);
+    }
+    ensure!(
+        v0::host::write_address(&mut [0u8; 40], usize::MAX, &contract).is_err(),
+        "Overflowing writes should fail."
+    );
+    Ok(())
+}
+
+#[test]
+/// Check that `get_receive_sender` may be imported with or without a result,
+/// and that the result determines which variant is used.
+fn test_receive_sender_import_variants() -> anyhow::Result<()> {
+    use wasm_transform::{
+        artifact::TryFromImport,
+        types::{FunctionType, Import, ImportDescription, ValueType},
+        validate::ValidateImportExport,
+    };
+    let types = [
+        FunctionType {
+            parameters: vec![ValueType::I32],
+            result:     None,
+        },
+        FunctionType {
+            parameters: vec![ValueType::I32],
+            result:     Some(ValueType::I32),
+        },
+        FunctionType {
+            parameters: vec![ValueType::I32],
+            result:     Some(ValueType::I64),
+        },
+    ];
+    let valid = |ty| {
+        ConcordiumAllowedImports::LATEST.validate_import_function(
+            false,
+            &"concordium".into(),
+            &"get_receive_sender".into(),
+            ty,
+        )
+    };
+    ensure!(valid(&types[0]) && valid(&types[1]), "Both variants should be allowed.");
+    ensure!(!valid(&types[2]), "Only i32 results are allowed.");
+    for (type_idx, with_length) in [(0, false), (1, true)].iter() {
+        let import = ProcessedImports::try_from_import(&types, Import {
+            mod_name:    "concordium".into(),
+            item_name:   "get_receive_sender".into(),
+            description: ImportDescription::Func {
+                type_idx: *type_idx,
+            },
+        })?;
+        let expected = if *with_length {
+            matches!(
+                import.tag,
+                ImportFunc::ReceiveOnly(ReceiveOnlyFunc::GetReceiveSenderWithLength)
+            )
+        } else {
+            matches!(import.tag, ImportFunc::ReceiveOnly(ReceiveOnlyFunc::GetReceiveSender))
+        };
+        ensure!(expected, "Incorrect variant {:?} for type {}.", import.tag, type_idx);
+    }
+    Ok(())
+}
+
+#[test]
+/// Check that a read-only state can be queried, but that all modifications
+/// fail with the dedicated error.
+fn test_read_only_state() -> anyhow::Result<()> {
+    let mut loader = trie::Loader {
+        inner: Vec::<u8>::new(),
+    };
+    let mut m_state = MutableState::initial_state();
+    let inner = m_state.get_inner(&mut loader);
+    let mut state = InstanceState::new(0, loader, inner).with_read_only(true);
+    let mut energy = crate::InterpreterEnergy::from(u64::MAX);
+    fn is_read_only_error<A>(r: anyhow::Result<A>) -> bool {
+        r.err().map_or(false, |e| e.downcast_ref::<StateModificationInReadOnly>().is_some())
+    }
+    ensure!(state.lookup_entry(&[1]).convert().is_none(), "The state should be empty.");
+    ensure!(is_read_only_error(state.create_entry(&[1])), "Creating an entry should fail.");
+    ensure!(is_read_only_error(state.delete_entry(&[1])), "Deleting an entry should fail.");
+    ensure!(
+        is_read_only_error(state.delete_prefix(&mut energy, &[])),
+        "Deleting a prefix should fail."
+    );
+    ensure!(!state.changed, "The state should not be marked as changed.");
+    ensure!(is_view_entrypoint("contract.view"), "Views are recognized.");
+    ensure!(is_view_entrypoint("contract.viewBalance"), "Views are recognized.");
+    ensure!(is_view_entrypoint("contract.view_balance"), "Views are recognized.");
+    ensure!(!is_view_entrypoint("contract.update"), "Only views are recognized.");
+    ensure!(!is_view_entrypoint("contract.viewer"), "Only the word view is recognized.");
+    ensure!(!is_view_entrypoint("contract.views"), "Only the word view is recognized.");
+    ensure!(!is_view_entrypoint("view.update"), "The contract name is not considered.");
+    Ok(())
+}
+
+#[test]
+/// Check that state operations are charged to the separate budget, if there is
+/// one, and that the energy of the execution is restored afterwards.
+fn test_state_energy_budget() -> anyhow::Result<()> {
+    let mut loader = trie::Loader {
+        inner: Vec::<u8>::new(),
+    };
+    let mut m_state = MutableState::initial_state();
+    let inner = m_state.get_inner(&mut loader);
+    let mut state = InstanceState::new(0, loader, inner)
+        .with_state_energy(Some(crate::InterpreterEnergy::from(100)));
+    let mut energy = crate::InterpreterEnergy::from(1000);
+    state.enter_state_budget(&mut energy);
+    state.enter_state_budget(&mut energy);
+    energy.tick_energy(10)?;
+    state.leave_state_budget(&mut energy);
+    state.leave_state_budget(&mut energy);
+    ensure!(energy.energy == 1000, "Execution energy should be unchanged.");
+    ensure!(
+        state.state_energy.map(|e| e.energy) == Some(90),
+        "State operations should be charged to the state budget."
+    );
+    state.enter_state_budget(&mut energy);
+    ensure!(
+        energy.tick_energy(91).is_err(),
+        "State operations should fail if the state budget is exhausted."
+    );
+    Ok(())
+}
+
+/// The receive context that is stored in the golden files
+/// `test-data/contexts/v0-receive-context.bin` and
+/// `test-data/contexts/v1-receive-context.bin`.
+fn golden_receive_context() -> ReceiveContext<v0::OwnedPolicyBytes> {
+    use concordium_contracts_common::*;
+    ReceiveContext {
+        common:     v0::ReceiveContext {
+            metadata:        ChainMetadata {
+                slot_time: Timestamp::from_timestamp_millis(1000),
+            },
+            invoker:         AccountAddress([0; ACCOUNT_ADDRESS_SIZE]),
+            self_address:    ContractAddress {
+                index:    10,
+                subindex: 5,
+            },
+            self_balance:    Amount::from_ccd(1),
+            sender:          Address::Account(AccountAddress([7; ACCOUNT_ADDRESS_SIZE])),
+            owner:           AccountAddress([6; ACCOUNT_ADDRESS_SIZE]),
+            sender_policies: vec![0, 0],
+        },
+        entrypoint: OwnedEntrypointName::new_unchecked("receive".into()),
+    }
+}
+
+#[test]
+/// Check that the canonical serialization of contexts matches the golden
+/// files, which contain contexts in the format the node passes over FFI, and
+/// that the JSON and versioned serializations agree with it.
+fn test_context_serialization_golden() -> anyhow::Result<()> {
+    use concordium_contracts_common::{AccountAddress, ACCOUNT_ADDRESS_SIZE};
+    let init_golden = std::fs::read("test-data/contexts/v0-init-context.bin")?;
+    let init_ctx = v0::deserial_init_context(&init_golden)
+        .map_err(|_| anyhow::anyhow!("Cannot parse the init context."))?;
+    ensure!(init_ctx.metadata.slot_time.timestamp_millis() == 1000, "Incorrect slot time.");
+    ensure!(init_ctx.init_origin == AccountAddress([5; ACCOUNT_ADDRESS_SIZE]), "Incorrect origin.");
+    ensure!(init_ctx.sender_policies == [0, 0], "Incorrect policies.");
+    ensure!(v0::serial_init_context(&init_ctx) == init_golden, "Init context round trip.");
+
+    let expected = golden_receive_context();
+    let v0_golden = std::fs::read("test-data/contexts/v0-receive-context.bin")?;
+    let v1_golden = std::fs::read("test-data/contexts/v1-receive-context.bin")?;
+    ensure!(
+        v0::serial_receive_context(&expected.common) == v0_golden,
+        "V0 receive context does not match the golden file."
+    );
+    ensure!(
+        serial_receive_context(&expected) == v1_golden,
+        "V1 receive context does not match the golden file."
+    );
+    let v0_ctx = v0::deserial_receive_context(&v0_golden)
+        .map_err(|_| anyhow::anyhow!("Cannot parse the v0 receive context."))?;
+    ensure!(v0::serial_receive_context(&v0_ctx) == v0_golden, "V0 receive context round trip.");
+    let v1_ctx = deserial_receive_context(&v1_golden)
+        .map_err(|_| anyhow::anyhow!("Cannot parse the v1 receive context."))?;
+    ensure!(v1_ctx.entrypoint == expected.entrypoint, "Incorrect entrypoint.");
+    ensure!(serial_receive_context(&v1_ctx) == v1_golden, "V1 receive context round trip.");
+
+    let json = serde_json::to_string(&expected)?;
+    let from_json: ReceiveContext<v0::OwnedPolicyBytes> = serde_json::from_str(&json)?;
+    ensure!(serial_receive_context(&from_json) == v1_golden, "JSON round trip.");
+
+    let versioned = serial_versioned_receive_context(&v1_ctx);
+    ensure!(
+        serial_receive_context(&deserial_versioned_receive_context(&versioned)?) == v1_golden,
+        "Versioned round trip."
+    );
+    let mut unsupported = versioned;
+    unsupported[0] = v0::CONTEXT_SERIALIZATION_VERSION + 1;
+    ensure!(
+        deserial_versioned_receive_context(&unsupported).is_err(),
+        "Unsupported versions are rejected."
+    );
+    Ok(())
+}
+
+#[test]
+/// Check that hashing an entry host-side agrees with hashing its value
+/// directly, that the cost depends on the size of the entry, and that
+/// invalidated entries are reported.
+fn test_entry_hash() -> anyhow::Result<()> {
+    use sha2::Digest;
+    use std::convert::TryFrom;
+    let mut loader = trie::Loader {
+        inner: Vec::<u8>::new(),
+    };
+    let mut m_state = MutableState::initial_state();
+    let inner = m_state.get_inner(&mut loader);
+    let mut state = InstanceState::new(0, loader, inner);
+    let mut energy = crate::InterpreterEnergy::from(u64::MAX);
+    let value = vec![17u8; 1000];
+    let entry = state.create_entry(b"key")?.convert().context("Entry should be created.")?;
+    state.entry_write(&mut energy, entry, &value, 0)?;
+    let expected: [u8; 32] = sha3::Keccak256::digest(&value).into();
+    let before = energy.energy;
+    let hash = state.entry_hash(&mut energy, entry, HashAlgorithm::Keccak256)?;
+    ensure!(hash == Some(expected), "The hash of the entry is incorrect.");
+    ensure!(
+        before - energy.energy == HashAlgorithm::Keccak256.cost(1000),
+        "Hashing should be charged based on the size of the entry."
+    );
+    ensure!(
+        state.entry_hash(&mut energy, entry, HashAlgorithm::Sha2_256)?
+            == Some(sha2::Sha256::digest(&value).into()),
+        "The SHA2-256 hash of the entry is incorrect."
+    );
+    ensure!(HashAlgorithm::try_from(3u32).is_err(), "Unknown algorithms are rejected.");
+    state.delete_entry(b"key")?;
+    ensure!(
+        state.entry_hash(&mut energy, entry, HashAlgorithm::Sha3_256)?.is_none(),
+        "Hashing an invalidated entry should fail."
+    );
+    Ok(())
+}
+
+#[test]
+/// Check that interrupts display contract addresses as `<index, subindex>`
+/// and amounts in CCD.
+fn test_display_interrupts() -> anyhow::Result<()> {
+    let address = ContractAddress {
+        index:    17,
+        subindex: 3,
+    };
+    let call = Interrupt::Call {
+        address,
+        parameter: vec![0u8; 5],
+        name: concordium_contracts_common::OwnedEntrypointName::new_unchecked("receive".into()),
+        amount: Amount::from_micro_ccd(1_500_000),
+    };
+    ensure!(
+        call.to_string()
+            == "call <17, 3>, entrypoint 'receive', with 1.500000 CCD and a parameter of 5 bytes",
+        "Incorrect display of a call: {}.",
+        call
+    );
+    let query = Interrupt::QueryContract {
+        address,
+        query: ContractQuery::StateSize,
+    };
+    ensure!(
+        query.to_string() == "query the state size of <17, 3>",
+        "Incorrect display of a query: {}.",
+        query
+    );
+    let to = AccountAddress([1u8; 32]);
+    let transfer = Interrupt::Transfer {
+        to,
+        amount: Amount::from_micro_ccd(7),
+    };
+    ensure!(
+        transfer.to_string() == format!("transfer 0.000007 CCD to {}", to),
+        "Incorrect display of a transfer: {}.",
+        transfer
+    );
+    Ok(())
+}
+
+#[test]
+/// Check that parameter cursors read parameters incrementally, that seeking
+/// is bounded by the length of the parameter, and that closed cursors are
+/// invalid and their indices reused.
+fn test_parameter_cursors() -> anyhow::Result<()> {
+    let parameters = [vec![1u8, 2, 3, 4, 5], vec![6u8, 7]];
+    let mut cursors = ParameterCursors::default();
+    ensure!(cursors.open(2, &parameters) == u32::MAX, "Parameter 2 does not exist.");
+    let first = cursors.open(0, &parameters);
+    let second = cursors.open(1, &parameters);
+    ensure!(first == 0 && second == 1, "Unexpected cursor indices {} and {}.", first, second);
+    let mut buf = [0u8; 2];
+    ensure!(cursors.read(first, &parameters, &mut buf) == 2 && buf == [1, 2], "First page.");
+    ensure!(cursors.read(first, &parameters, &mut buf) == 2 && buf == [3, 4], "Second page.");
+    ensure!(cursors.read(first, &parameters, &mut buf) == 1 && buf[0] == 5, "Last page.");
+    ensure!(cursors.read(first, &parameters, &mut buf) == 0, "The cursor is at the end.");
+    ensure!(cursors.read(second, &parameters, &mut buf) == 2 && buf == [6, 7], "Cursors differ.");
+    ensure!(cursors.seek(first, &parameters, 1) == 1, "Seeking within the parameter.");
+    ensure!(cursors.read(first, &parameters, &mut buf) == 2 && buf == [2, 3], "Read after seek.");
+    ensure!(cursors.seek(first, &parameters, 100) == 5, "Seeking is bounded by the length.");
+    ensure!(cursors.close(first) == 0, "Closing an open cursor.");
+    ensure!(cursors.close(first) == u32::MAX, "The cursor is already closed.");
+    ensure!(cursors.read(first, &parameters, &mut buf) == u32::MAX, "Reading a closed cursor.");
+    ensure!(cursors.seek(first, &parameters, 0) == u32::MAX, "Seeking a closed cursor.");
+    ensure!(cursors.open(1, &parameters) == first, "The index of a closed cursor is reused.");
+    for _ in 2..crate::constants::MAX_PARAMETER_CURSORS {
+        ensure!(cursors.open(0, &parameters) != u32::MAX, "Opening should succeed.");
+    }
+    ensure!(cursors.open(0, &parameters) == u32::MAX, "Too many open cursors.");
+    Ok(())
+}
+
+#[test]
+/// Check that randomness is deterministic in the seed, and depends on the block
+/// seed, the nonce, the invocation, and on how much has been drawn already, and
+/// that contexts without a seed do not provide one.
+fn test_fill_random() -> anyhow::Result<()> {
+    use super::{fill_random, RandomInvocation};
+    use v0::HasReceiveContext;
+    let address = ContractAddress {
+        index:    10,
+        subindex: 5,
+    };
+    let receive = RandomInvocation::Receive {
+        address: &address,
+    };
+    let seed = |block: u8, nonce| v0::RandomSeed {
+        block_seed: [block; 32],
+        nonce,
+    };
+    let draw = |seed: v0::RandomSeed, invocation, counter: &mut u64, len| {
+        let mut out = vec![0u8; len];
+        fill_random(&seed, invocation, counter, &mut out);
+        out
+    };
+    let mut counter = 0;
+    let first = draw(seed(1, 0), receive, &mut counter, 40);
+    ensure!(counter == 2, "Two blocks are drawn for 40 bytes, but {} were.", counter);
+    ensure!(draw(seed(1, 0), receive, &mut 0, 40) == first, "Not deterministic.");
+    ensure!(
+        draw(seed(1, 0), receive, &mut 0, 10)[..] == first[..10],
+        "Shorter draws are prefixes of longer ones."
+    );
+    ensure!(draw(seed(1, 0), receive, &mut 1, 8)[..] == first[32..], "Counter is ignored.");
+    ensure!(draw(seed(2, 0), receive, &mut 0, 40) != first, "Block seed is ignored.");
+    ensure!(
+        draw(seed(1, 1), receive, &mut 0, 40) != first,
+        "Invocations of the same contract with different nonces get the same randomness."
+    );
+    let other = ContractAddress {
+        index:    11,
+        subindex: 5,
+    };
+    ensure!(
+        draw(
+            seed(1, 0),
+            RandomInvocation::Receive {
+                address: &other,
+            },
+            &mut 0,
+            40
+        ) != first,
+        "Address is ignored."
+    );
+    let alice = AccountAddress([0u8; 32]);
+    let bob = AccountAddress([1u8; 32]);
+    let init = |origin| {
+        draw(
+            seed(1, 0),
+            RandomInvocation::Init {
+                origin,
+            },
+            &mut 0,
+            40,
+        )
+    };
+    ensure!(init(&alice) != init(&bob), "Inits by different accounts get the same randomness.");
+    ensure!(init(&alice) != first, "Init and receive functions get the same randomness.");
+
+    let ctx = golden_receive_context();
+    ensure!(ctx.random_seed().is_err(), "Contexts from the node do not provide a seed.");
+    let with_seed = v0::WithRandomSeed {
+        ctx,
+        seed: seed(1, 0),
+    };
+    ensure!(with_seed.random_seed()? == &seed(1, 0), "The seed is provided.");
+    ensure!(with_seed.self_address()? == &address, "The context is used as it is.");
+    Ok(())
+}
+
+/// A V1 module with one page of memory and the receive function
+/// `test.random`, which fills the first 32 bytes of the memory with
+/// `get_random` and returns them.
+fn get_random_module() -> Vec<u8> {
+    let mut module = vec![0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00];
+    // The types (i32, i32) -> (), (i32, i32, i32) -> i32, and (i64) -> i32.
+    module.extend_from_slice(&[
+        0x01, 0x12, 0x03, 0x60, 0x02, 0x7F, 0x7F, 0x00, 0x60, 0x03, 0x7F, 0x7F, 0x7F, 0x01, 0x7F,
+        0x60, 0x01, 0x7E, 0x01, 0x7F,
+    ]);
+    module.extend_from_slice(&[0x02, 0x33, 0x02, 0x0A]);
+    module.extend_from_slice(b"concordium");
+    module.push(0x0A);
+    module.extend_from_slice(b"get_random");
+    module.extend_from_slice(&[0x00, 0x00, 0x0A]);
+    module.extend_from_slice(b"concordium");
+    module.push(0x0C);
+    module.extend_from_slice(b"write_output");
+    module.extend_from_slice(&[0x00, 0x01]);
+    module.extend_from_slice(&[0x03, 0x02, 0x01, 0x02]);
+    module.extend_from_slice(&[0x05, 0x03, 0x01, 0x00, 0x01]);
+    module.extend_from_slice(&[0x07, 0x0F, 0x01, 0x0B]);
+    module.extend_from_slice(b"test.random");
+    module.extend_from_slice(&[0x00, 0x02]);
+    // i32.const 0, i32.const 32, call get_random, i32.const 0, i32.const 32,
+    // i32.const 0, call write_output, drop, i32.const 0
+    module.extend_from_slice(&[
+        0x0A, 0x15, 0x01, 0x13, 0x00, 0x41, 0x00, 0x41, 0x20, 0x10, 0x00, 0x41, 0x00, 0x41, 0x20,
+        0x41, 0x00, 0x10, 0x01, 0x1A, 0x41, 0x00, 0x0B,
+    ]);
+    module
+}
+
+#[test]
+/// Check that `get_random` uses the seed of the execution, or of the context if
+/// the execution has none, and fails if neither has one.
+fn test_get_random_seed() -> anyhow::Result<()> {
+    use super::{fill_random, RandomInvocation};
+    use concordium_contracts_common::ReceiveName;
+    use std::sync::Arc;
+    let artifact =
+        Arc::new(wasm_transform::utils::instantiate_with_metering::<ProcessedImports, _>(
+            &ConcordiumAllowedImports::LATEST,
+            &get_random_module(),
+        )?);
+    fn invoke<Ctx: super::HasReceiveContext>(
+        artifact: &Arc<
+            wasm_transform::artifact::Artifact<
+                ProcessedImports,
+                wasm_transform::artifact::CompiledFunction,
+            >,
+        >,
+        ctx: Ctx,
+        seed: Option<v0::RandomSeed>,
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        let mut loader = trie::Loader {
+            inner: Vec::<u8>::new(),
+        };
+        let mut mutable = MutableState::initial_state();
+        let inner = mutable.get_inner(&mut loader);
+        let state = InstanceState::new(0, loader, inner).with_random_seed(seed);
+        let result = super::invoke_receive::<_, _, _, Ctx>(
+            artifact.clone(),
+            0,
+            ctx,
+            ReceiveName::new_unchecked("test.random"),
+            &[],
+            crate::InterpreterEnergy::from(1_000_000),
+            state,
+        )?;
+        match result {
+            ReceiveResult::Success {
+                return_value,
+                ..
+            } => Ok(Some(return_value)),
+            ReceiveResult::Trap {
+                ..
+            } => Ok(None),
+            _ => anyhow::bail!("Execution should either succeed or trap."),
+        }
+    }
+    let ctx = golden_receive_context();
+    let expected = |seed: v0::RandomSeed| {
+        let mut out = vec![0u8; 32];
+        fill_random(
+            &seed,
+            RandomInvocation::Receive {
+                address: &ctx.common.self_address,
+            },
+            &mut 0,
+            &mut out,
+        );
+        out
+    };
+    let execution_seed = v0::RandomSeed {
+        block_seed: [1; 32],
+        nonce:      3,
+    };
+    let context_seed = v0::RandomSeed {
+        block_seed: [2; 32],
+        nonce:      0,
+    };
+    let with_seed = v0::WithRandomSeed {
+        ctx:  ctx.clone(),
+        seed: context_seed,
+    };
+    ensure!(
+        invoke(&artifact, ctx.clone(), Some(execution_seed))? == Some(expected(execution_seed)),
+        "The seed of the execution is used."
+    );
+    ensure!(
+        invoke(&artifact, with_seed.clone(), None)? == Some(expected(context_seed)),
+        "The seed of the context is used if the execution has none."
+    );
+    ensure!(
+        invoke(&artifact, with_seed, Some(execution_seed))? == Some(expected(execution_seed)),
+        "The seed of the execution takes precedence."
+    );
+    ensure!(invoke(&artifact, ctx, None)?.is_none(), "get_random fails without a seed.");
+    Ok(())
+}
+
+#[test]
+/// Check that iteration can be resumed from the token of an iterator after
+/// the iterator is deleted and the state is modified, and that the remaining
+/// entries are counted up to the limit without advancing the iterator.
+fn test_iterator_resume() -> anyhow::Result<()> {
+    let mut loader = trie::Loader {
+        inner: Vec::<u8>::new(),
+    };
+    let mut m_state = MutableState::initial_state();
+    let inner = m_state.get_inner(&mut loader);
+    let mut state = InstanceState::new(0, loader, inner);
+    let mut energy = crate::InterpreterEnergy::from(u64::MAX);
+    for k in 1..=4u8 {
+        state.create_entry(&[0, k])?;
+    }
+    let key = |state: &mut InstanceState<_>, iter| {
+        let mut key = vec![0u8; state.iterator_key_size(iter) as usize];
+        state.iterator_key_read(iter, &mut key, 0);
+        key
+    };
+
+    let iter = state.iterator(&[0]).convert().context("Iterator should have been created.")?;
+    ensure!(state.iterator_remaining(&mut energy, iter, 10)? == 4, "All entries remain.");
+    ensure!(state.iterator_remaining(&mut energy, iter, 2)? == 2, "Counting is limited.");
+    state.iterator_next(&mut energy, iter)?;
+    state.iterator_next(&mut energy, iter)?;
+    ensure!(key(&mut state, iter) == [0, 2], "Counting should not advance the iterator.");
+    let size = state.iterator_token_size(iter);
+    let mut token = vec![0u8; size as usize];
+    ensure!(state.iterator_token_read(iter, &mut token, 0) == size, "Token should be read.");
+    ensure!(state.iterator_delete(&mut energy, iter)? == 1, "Iterator should be deleted.");
+
+    // The prefix is no longer locked, so the state can be modified in between.
+    ensure!(state.delete_entry(&[0, 3])? == 2, "Entry should be deleted.");
+    state.create_entry(&[0, 2, 5])?;
+    let resumed = state
+        .iterator_resume(&token)?
+        .convert()
+        .context("Resumed iterator should have been created.")?;
+    ensure!(state.iterator_remaining(&mut energy, resumed, 10)? == 2, "Two entries remain.");
+    state.iterator_next(&mut energy, resumed)?.convert().context("An entry should remain.")?;
+    ensure!(key(&mut state, resumed) == [0, 2, 5], "Entries added after the token are returned.");
+    state.iterator_next(&mut energy, resumed)?;
+    ensure!(key(&mut state, resumed) == [0, 4], "Deleted entries are skipped.");
+    ensure!(
+        state.iterator_next(&mut energy, resumed)?.convert().is_none(),
+        "The resumed iterator should be exhausted."
+    );
+
+    let size = state.iterator_token_size(resumed);
+    let mut token = vec![0u8; size as usize];
+    state.iterator_token_read(resumed, &mut token, 0);
+    ensure!(
+        state.iterator_resume(&token)?.convert().is_none(),
+        "Resuming an exhausted iterator should yield no iterator."
+    );
+    ensure!(state.iterator_resume(&[1, 0, 0]).is_err(), "Malformed tokens are rejected.");
+    ensure!(state.iterator_token_size(42.into()) == u32::MAX, "Invalid iterator.");
+    Ok(())
+}
+
+#[test]
+/// The default cost table charges the same as the cost functions in
+/// [constants], which are what the costs on the chain are defined by.
+fn test_cost_table_v1() {
+    assert_eq!(CostTable::default(), CostTable::V1);
+    let prop = |len: u16| {
+        let costs = CostTable::V1;
+        let len = u32::from(len);
+        costs.copy_from_host.cost(len) == constants::copy_from_host_cost(len)
+            && costs.copy_to_host.cost(len) == constants::copy_to_host_cost(len)
+            && costs.write_output.cost(len) == constants::write_output_cost(len)
+            && costs.create_entry_cost(len) == constants::create_entry_cost(len)
+            && costs.lookup_entry.cost(len) == constants::lookup_entry_cost(len)
+            && costs.delete_entry.cost(len) == constants::delete_entry_cost(len)
+            && costs.delete_prefix_find.cost(len) == constants::delete_prefix_find_cost(len)
+            && costs.new_iterator.cost(len) == constants::new_iterator_cost(len)
+            && costs.delete_iterator.cost(len) == constants::delete_iterator_cost(len)
+            && costs.read_entry.cost(len) == constants::read_entry_cost(len)
+            && costs.write_entry.cost(len) == constants::write_entry_cost(len)
+            && costs.verify_ed25519.cost(len) == constants::verify_ed25519_cost(len)
+            && costs.bls_aggregate_verify_cost(3, len)
+                == constants::bls_aggregate_verify_cost(3, len)
+            && costs.get_random.cost(len) == constants::get_random_cost(len)
+            && costs.hash_sha2_256.cost(len) == constants::hash_sha2_256_cost(len)
+            && costs.hash_sha3_256.cost(len) == constants::hash_sha3_256_cost(len)
+            && costs.hash_keccak_256.cost(len) == constants::hash_keccak_256_cost(len)
+    };
+    // Keys of more than 64 bytes are charged differently, so test around that.
+    for len in [0, 1, 63, 64, 65, 100, 1000, u16::MAX] {
+        assert!(prop(len), "The costs differ for length {}.", len);
+    }
+    QuickCheck::new().tests(NUM_TESTS).quickcheck(prop as fn(u16) -> bool);
+    assert_eq!(CostTable::V1.create_entry_cost(u32::MAX), constants::create_entry_cost(u32::MAX));
+}
+
+#[test]
+/// Check that cost tables with a unit size of 0 are rejected when they are
+/// deserialized, and that costs saturate instead of overflowing.
+fn test_cost_table_checks() -> anyhow::Result<()> {
+    let mut json = serde_json::to_value(CostTable::V1)?;
+    ensure!(
+        serde_json::from_value::<CostTable>(json.clone())? == CostTable::V1,
+        "The table should be preserved by serialization."
+    );
+    json["readEntry"]["unitSize"] = 0.into();
+    ensure!(
+        serde_json::from_value::<CostTable>(json).is_err(),
+        "A unit size of 0 should be rejected."
+    );
+    let huge = constants::LinearCost::per_byte(u64::MAX / 2, u64::MAX / 2);
+    ensure!(huge.cost(u32::MAX) == u64::MAX, "The cost should saturate.");
+    let costs = CostTable {
+        create_entry: huge,
+        bls_aggregate_verify_key: u64::MAX,
+        ..CostTable::V1
+    };
+    ensure!(costs.create_entry_cost(65) == u64::MAX, "The cost of long keys should saturate.");
+    ensure!(
+        costs.bls_aggregate_verify_cost(2, 0) == u64::MAX,
+        "The cost of aggregate verification should saturate."
+    );
+    Ok(())
+}
+
+#[test]
+/// Check that tags are preserved by parsing, and that the host interface
+/// version of the processed imports is that of the most recent change, i.e.,
+/// the most recent import or the inline tracking of the call depth.
+fn test_import_host_interface_versions() -> anyhow::Result<()> {
+    use wasm_transform::{artifact::HostInterface, parse::GetParseable};
+    let mut max_version = 0;
+    let mut previous_version = 0;
+    for tag in 0..=u8::MAX {
+        let import: ImportFunc = match (&[tag][..]).next(()) {
+            Ok(import) => import,
+            Err(_) => continue,
+        };
+        ensure!(import.tag() == tag, "Tag {} is not preserved.", tag);
+        let version = import.host_interface_version();
+        ensure!(version >= previous_version, "Versions increase with the tags.");
+        previous_version = version;
+        max_version = max_version.max(version);
+    }
+    ensure!(
+        max_version < INLINE_CALL_DEPTH_VERSION,
+        "Imports precede the inline tracking of the call depth."
+    );
+    ensure!(
+        ProcessedImports::HOST_INTERFACE_VERSION == INLINE_CALL_DEPTH_VERSION,
+        "The host interface version is that of the most recent change."
+    );
+    Ok(())
+}
+
+#[test]
+/// Check that imports are only allowed from the version of the host interface
+/// that introduced them, and that the default only allows the baseline.
+fn test_allowed_imports_versions() -> anyhow::Result<()> {
+    use wasm_transform::{
+        types::{FunctionType, ValueType},
+        validate::ValidateImportExport,
+    };
+    let valid = |allowed: ConcordiumAllowedImports, name: &str, ty: FunctionType| {
+        allowed.validate_import_function(false, &"concordium".into(), &name.into(), &ty)
+    };
+    let get_random = || FunctionType {
+        parameters: vec![ValueType::I32, ValueType::I32],
+        result:     None,
+    };
+    let sender_with_length = || FunctionType {
+        parameters: vec![ValueType::I32],
+        result:     Some(ValueType::I32),
+    };
+    let baseline = ConcordiumAllowedImports::default();
+    ensure!(baseline == ConcordiumAllowedImports::new(0), "The default is the baseline.");
+    ensure!(
+        valid(baseline, "get_parameter_size", sender_with_length()),
+        "Baseline imports are allowed."
+    );
+    ensure!(!valid(baseline, "get_random", get_random()), "Later imports are rejected.");
+    ensure!(
+        !valid(baseline, "get_receive_sender", sender_with_length()),
+        "The variant of get_receive_sender with a length is rejected."
+    );
+    ensure!(!valid(ConcordiumAllowedImports::new(7), "get_random", get_random()));
+    ensure!(valid(ConcordiumAllowedImports::new(8), "get_random", get_random()));
+    ensure!(valid(ConcordiumAllowedImports::LATEST, "log_event_typed", FunctionType {
+        parameters: vec![ValueType::I32; 3],
+        result:     Some(ValueType::I32),
+    }));
+    Ok(())
+}
+
+#[test]
+/// Check that modules limit the call depth with an injected counter from
+/// [INLINE_CALL_DEPTH_VERSION], with the same limit as the host functions.
+fn test_metering_config_versions() -> anyhow::Result<()> {
+    use wasm_transform::metering_transformation::MeteringConfig;
+    ensure!(
+        ConcordiumAllowedImports::new(INLINE_CALL_DEPTH_VERSION - 1).metering_config()
+            == MeteringConfig::LEGACY,
+        "Earlier versions track calls with the host functions."
+    );
+    ensure!(
+        ConcordiumAllowedImports::new(INLINE_CALL_DEPTH_VERSION).metering_config()
+            == MeteringConfig {
+                inline_call_depth: Some(constants::MAX_ACTIVATION_FRAMES),
+            },
+        "Later versions track calls with a counter."
+    );
+    Ok(())
+}
+
+/// A V1 module with one page of memory and two receive functions. The function
+/// `test.grow` grows the memory by 300 pages, and traps if this fails. The
+/// function `test.grow_fail` attempts to grow the memory by 1000 pages, more
+/// than the maximum size of the memory, and traps if this succeeds.
+fn memory_grow_module() -> Vec<u8> {
+    let mut module = vec![0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00];
+    // The type (i64) -> i32, used by both functions.
+    module.extend_from_slice(&[0x01, 0x06, 0x01, 0x60, 0x01, 0x7E, 0x01, 0x7F]);
+    module.extend_from_slice(&[0x03, 0x03, 0x02, 0x00, 0x00]);
+    // A memory with one page initially and no maximum.
+    module.extend_from_slice(&[0x05, 0x03, 0x01, 0x00, 0x01]);
+    module.extend_from_slice(&[0x07, 0x1E, 0x02, 0x09]);
+    module.extend_from_slice(b"test.grow");
+    module.extend_from_slice(&[0x00, 0x00, 0x0E]);
+    module.extend_from_slice(b"test.grow_fail");
+    module.extend_from_slice(&[0x00, 0x01]);
+    module.extend_from_slice(&[0x0A, 0x23, 0x02]);
+    // i32.const 300, memory.grow, i32.const -1, i32.eq, if unreachable end,
+    // i32.const 0
+    module.extend_from_slice(&[
+        0x10, 0x00, 0x41, 0xAC, 0x02, 0x40, 0x00, 0x41, 0x7F, 0x46, 0x04, 0x40, 0x00, 0x0B, 0x41,
+        0x00, 0x0B,
+    ]);
+    // i32.const 1000, memory.grow, i32.const -1, i32.ne, if unreachable end,
+    // i32.const 0
+    module.extend_from_slice(&[
+        0x10, 0x00, 0x41, 0xE8, 0x07, 0x40, 0x00, 0x41, 0x7F, 0x47, 0x04, 0x40, 0x00, 0x0B, 0x41,
+        0x00, 0x0B,
+    ]);
+    module
+}
+
+#[test]
+/// Check that memory is limited with memory accounting, and that memory that
+/// is not allocated is only charged for without it.
+fn test_memory_accounting() -> anyhow::Result<()> {
+    use concordium_contracts_common::ReceiveName;
+    use std::sync::Arc;
+    let artifact =
+        Arc::new(wasm_transform::utils::instantiate_with_metering::<ProcessedImports, _>(
+            &ConcordiumAllowedImports::LATEST,
+            &memory_grow_module(),
+        )?);
+    let invoke = |name: &str, host_features: HostFeatures| {
+        let mut loader = trie::Loader {
+            inner: Vec::<u8>::new(),
+        };
+        let mut mutable = MutableState::initial_state();
+        let inner = mutable.get_inner(&mut loader);
+        let state = InstanceState::new(0, loader, inner).with_host_features(host_features);
+        super::invoke_receive::<_, _, _, ReceiveContext<v0::OwnedPolicyBytes>>(
+            artifact.clone(),
+            0,
+            golden_receive_context(),
+            ReceiveName::new_unchecked(name),
+            &[],
+            crate::InterpreterEnergy::from(1_000_000),
+            state,
+        )
+    };
+    let remaining_energy =
+        |result: ReceiveResult<wasm_transform::artifact::CompiledFunction>| match result {
+            ReceiveResult::Success {
+                remaining_energy,
+                ..
+            } => Ok(remaining_energy),
+            _ => anyhow::bail!("Execution should succeed."),
+        };
+    let legacy = HostFeatures::default();
+    let accounting = HostFeatures::ALL;
+    ensure!(
+        matches!(invoke("test.grow", legacy)?, ReceiveResult::Success { .. }),
+        "The memory should be grown without memory accounting."
+    );
+    match invoke("test.grow", accounting)? {
+        ReceiveResult::Trap {
+            error,
+            ..
+        } => ensure!(
+            error.downcast_ref::<crate::OutOfMemory>()
+                == Some(&crate::OutOfMemory {
+                    requested: 301,
+                    limit:     constants::MAX_MEMORY_PAGES,
+                }),
+            "Unexpected error {}.",
+            error
+        ),
+        _ => anyhow::bail!("The memory limit should be exceeded."),
+    }
+    let charged = remaining_energy(invoke("test.grow_fail", legacy)?)?;
+    let uncharged = remaining_energy(invoke("test.grow_fail", accounting)?)?;
+    ensure!(
+        uncharged - charged == 1000 * u64::from(constants::MEMORY_COST_FACTOR),
+        "Only the requested pages should be charged for without memory accounting."
+    );
+    Ok(())
+}
+
+#[cfg(feature = "tooling")]
+#[test]
+/// Check that persisted states can be loaded, that persisting a state replaces
+/// the file, and that no temporary files are left behind.
+fn test_persist_state() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("state");
+    let mut mutable = MutableState::initial_state();
+    {
+        let mut loader = trie::Loader {
+            inner: Vec::<u8>::new(),
+        };
+        let inner = mutable.get_inner(&mut loader);
+        let mut state = InstanceState::new(0, loader, inner);
+        let mut energy = crate::InterpreterEnergy::from(u64::MAX);
+        let entry =
+            state.create_entry(b"abc")?.convert().context("The entry should be created.")?;
+        state.entry_write(&mut energy, entry, &[1, 2, 3], 0)?;
+    }
+    let hash = super::persist_state(&mut mutable, &path)?;
+    let mut loader = trie::Loader::new(&[][..]);
+    let load = || -> anyhow::Result<trie::PersistentState> {
+        trie::PersistentState::load_saved(&mut std::fs::File::open(&path)?)
+    };
+    let loaded = load()?;
+    ensure!(loaded.hash(&mut loader) == hash, "Hash of the loaded state differs.");
+    ensure!(
+        loaded.lookup(&mut loader, b"abc") == Some(vec![1, 2, 3]),
+        "Loaded state has incorrect contents."
+    );
+
+    // An init function that does not modify the state, in a V1 module.
+    let mut module = vec![0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00];
+    module.extend_from_slice(&[0x01, 0x06, 0x01, 0x60, 0x01, 0x7E, 0x01, 0x7F]);
+    module.extend_from_slice(&[0x03, 0x02, 0x01, 0x00]);
+    module.extend_from_slice(&[0x07, 0x0D, 0x01, 0x09]);
+    module.extend_from_slice(b"init_test");
+    module.extend_from_slice(&[0x00, 0x00]);
+    module.extend_from_slice(&[0x0A, 0x06, 0x01, 0x04, 0x00, 0x41, 0x00, 0x0B]);
+    let artifact = wasm_transform::utils::instantiate_with_metering::<ProcessedImports, _>(
+        &ConcordiumAllowedImports::LATEST,
+        &module,
+    )?;
+    let init_ctx: v0::InitContext<v0::OwnedPolicyBytes> = v0::InitContext {
+        metadata:        concordium_contracts_common::ChainMetadata {
+            slot_time: concordium_contracts_common::Timestamp::from_timestamp_millis(0),
+        },
+        init_origin:     AccountAddress([0u8; 32]),
+        sender_policies: Vec::new(),
+    };
+    let (result, hash) = super::invoke_init_and_persist(
+        std::sync::Arc::new(artifact),
+        0,
+        init_ctx,
+        "init_test",
+        &[],
+        crate::InterpreterEnergy::from(1_000_000),
+        &path,
+    )?;
+    ensure!(matches!(result, InitResult::Success { .. }), "Init should succeed.");
+    let loaded = load()?;
+    ensure!(
+        matches!(loaded, trie::PersistentState::Empty),
+        "The state of the new instance should replace the file."
+    );
+    ensure!(hash == Some(loaded.hash(&mut loader)), "Incorrect hash of the persisted state.");
+    ensure!(
+        std::fs::read_dir(dir.path())?.count() == 1,
+        "Temporary files should not be left behind."
+    );
+    Ok(())
+}
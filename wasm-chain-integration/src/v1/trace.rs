@@ -0,0 +1,153 @@
+//! Tracing of the execution of V1 contracts. This is intended for simulating
+//! contracts during development, e.g., to find out which path a contract takes
+//! on a given input, or where it runs out of energy. It must never be used on
+//! the chain.
+//!
+//! A [TracingHost] wraps the host of an execution and records the host
+//! functions that the contract calls in a [Trace]. If the `execution-trace`
+//! feature is enabled it also records each executed instruction. Since
+//! execution is deterministic, running the same contract on the same input
+//! produces the same trace, so traces of two runs can be compared to find
+//! where they diverge.
+//!
+//! The trace of a receive function that is interrupted, e.g., by an `invoke`,
+//! is continued by resuming execution with the same [Trace].
+use super::{ImportFunc, ProcessedImports};
+use std::io::Write;
+use wasm_transform::{
+    artifact::{InternalOpcode, StackValue},
+    machine,
+};
+
+/// An event during the execution of a contract.
+#[derive(Debug, Clone, Copy)]
+pub enum TraceEvent {
+    /// An instruction is about to be executed.
+    Step {
+        /// Index of the function in the list of defined functions, i.e.,
+        /// without the imported functions.
+        function:  usize,
+        /// Position of the instruction in the compiled code of the function.
+        offset:    usize,
+        opcode:    InternalOpcode,
+        /// The low 32 bits of the value at the top of the stack, if any. This
+        /// is the whole value if it is an `i32`.
+        stack_top: Option<i32>,
+    },
+    /// A host function is called by the contract.
+    HostCall {
+        /// The name the function is imported with.
+        name: &'static str,
+    },
+}
+
+/// The events of an execution, in the order they occurred. All events are
+/// kept in memory, so executions should be traced with a limited amount of
+/// energy.
+#[derive(Debug, Clone, Default)]
+pub struct Trace {
+    pub events: Vec<TraceEvent>,
+}
+
+impl Trace {
+    pub fn new() -> Self { Self::default() }
+
+    /// The number of executed instructions. This is zero if the
+    /// `execution-trace` feature is not enabled.
+    pub fn num_steps(&self) -> usize {
+        self.events.iter().filter(|e| matches!(e, TraceEvent::Step { .. })).count()
+    }
+
+    /// The names of the called host functions, in the order they were called.
+    pub fn host_calls(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.events.iter().filter_map(|e| match e {
+            TraceEvent::HostCall {
+                name,
+            } => Some(*name),
+            TraceEvent::Step {
+                ..
+            } => None,
+        })
+    }
+
+    /// Write the trace with one event per line, e.g., to a file. Steps are
+    /// written as `<function> <offset> <opcode> <top of the stack or ->`, and
+    /// host calls as `call <name>`.
+    pub fn write(&self, mut out: impl Write) -> std::io::Result<()> {
+        for event in self.events.iter() {
+            match event {
+                TraceEvent::Step {
+                    function,
+                    offset,
+                    opcode,
+                    stack_top,
+                } => match stack_top {
+                    Some(top) => writeln!(out, "{} {} {:?} {}", function, offset, opcode, top)?,
+                    None => writeln!(out, "{} {} {:?} -", function, offset, opcode)?,
+                },
+                TraceEvent::HostCall {
+                    name,
+                } => writeln!(out, "call {}", name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A host that records the execution in a [Trace] and otherwise behaves as
+/// the wrapped host.
+pub struct TracingHost<'a, H> {
+    pub host:  &'a mut H,
+    pub trace: &'a mut Trace,
+}
+
+impl<'a, H: machine::Host<ProcessedImports>> machine::Host<ProcessedImports>
+    for TracingHost<'a, H>
+{
+    type Interrupt = H::Interrupt;
+
+    fn tick_initial_memory(&mut self, num_pages: u32) -> machine::RunResult<()> {
+        self.host.tick_initial_memory(num_pages)
+    }
+
+    fn tick_memory_grow(&mut self, current_pages: u32, num_pages: u32) -> machine::RunResult<()> {
+        self.host.tick_memory_grow(current_pages, num_pages)
+    }
+
+    fn call(
+        &mut self,
+        f: &ProcessedImports,
+        memory: &mut Vec<u8>,
+        stack: &mut machine::RuntimeStack,
+    ) -> machine::RunResult<Option<Self::Interrupt>> {
+        match f.tag {
+            // The accounting functions are not called by the contract itself.
+            ImportFunc::ChargeEnergy
+            | ImportFunc::TrackCall
+            | ImportFunc::TrackReturn
+            | ImportFunc::ChargeMemoryAlloc => {}
+            _ => self.trace.events.push(TraceEvent::HostCall {
+                name: f.tag.name(),
+            }),
+        }
+        self.host.call(f, memory, stack)
+    }
+
+    fn trace(
+        &mut self,
+        function: usize,
+        offset: usize,
+        opcode: InternalOpcode,
+        stack_top: Option<StackValue>,
+    ) {
+        self.trace.events.push(TraceEvent::Step {
+            function,
+            offset,
+            opcode,
+            // Every value on the stack has at least its low 32 bits set, so reading
+            // them is safe.
+            stack_top: stack_top.map(|v| unsafe { v.short }),
+        });
+        self.host.trace(function, offset, opcode, stack_top)
+    }
+}
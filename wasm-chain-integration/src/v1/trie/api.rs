@@ -22,14 +22,18 @@
 //! state updates where we only have to store the parts of the state that are
 //! new.
 use super::{
-    low_level::{CachedRef, MutableTrie, Node},
+    low_level::{CachedRef, DiffEntry, MutableTrie, Node, TrieStats},
     types::*,
 };
-use byteorder::{ReadBytesExt, WriteBytesExt};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 #[cfg(feature = "display-state")]
 use ptree::TreeBuilder;
 use sha2::Digest;
-use std::sync::{Arc, Mutex, MutexGuard};
+use std::{
+    io::Read,
+    sync::{Arc, Mutex, MutexGuard},
+};
+use wasm_transform::compression;
 
 pub type Value = Vec<u8>;
 
@@ -94,6 +98,17 @@ impl PersistentState {
         backing_store.store_raw(&top)
     }
 
+    /// Like [Self::store_update], but the tree is written to the output as it
+    /// is stored, via a [StreamStorer], so that the memory used does not grow
+    /// with the size of the state. The output is the same as that of
+    /// [Self::store_update] with an empty vector as the backing store. The
+    /// returned reference of the root is an offset into the output, and the
+    /// state can be loaded from it with [Loadable::load_from_location] using a
+    /// [StreamLoader] or a [Loader].
+    pub fn store_update_stream<W: std::io::Write>(&mut self, out: W) -> StoreResult<Reference> {
+        self.store_update(&mut StreamStorer::new(out))
+    }
+
     /// Serialize the tree into the provided buffer. Note that this is very
     /// different from [Self::store_update]. Whereas that stores the part of
     /// the tree that are only in memory into the provided backing store, this
@@ -131,6 +146,98 @@ impl PersistentState {
         }
     }
 
+    /// Write the state, prefixed by a [`StateFileHeader`], to the provided
+    /// output. This is the format in which the state is saved to files, and
+    /// it can be read back with [Self::load_saved]. As with
+    /// [Self::serialize] the entire tree is loaded using the provided
+    /// loader.
+    ///
+    /// Returns the hash of the state that was recorded in the header.
+    pub fn save(
+        &self,
+        loader: &mut impl BackingStoreLoad,
+        out: &mut impl std::io::Write,
+    ) -> anyhow::Result<super::Hash> {
+        let mut body = Vec::new();
+        self.serialize(loader, &mut body)?;
+        let header = StateFileHeader {
+            version:   STATE_FILE_VERSION,
+            root_hash: self.hash(loader),
+            length:    body.len() as u64,
+        };
+        header.write(out)?;
+        out.write_all(&body)?;
+        Ok(header.root_hash)
+    }
+
+    /// Like [Self::save], but the output is compressed with zstd, see
+    /// [wasm_transform::compression]. It can be read back with
+    /// [Self::load_saved] as well.
+    #[cfg(feature = "compression")]
+    pub fn save_compressed(
+        &self,
+        loader: &mut impl BackingStoreLoad,
+        out: &mut impl std::io::Write,
+    ) -> anyhow::Result<super::Hash> {
+        let mut bytes = Vec::new();
+        let hash = self.save(loader, &mut bytes)?;
+        out.write_all(&compression::compress(&bytes)?)?;
+        Ok(hash)
+    }
+
+    /// Dual to [Self::save] and [Self::save_compressed]. Compressed files are
+    /// recognized by their magic bytes and decompressed, which requires the
+    /// `compression` feature. The header is validated, and the state is only
+    /// returned if it is exactly of the recorded length, it has the recorded
+    /// root hash, and all the hashes in the tree are consistent with the
+    /// data. Errors concerning the format of the file are reported as
+    /// [`StateFileError`].
+    pub fn load_saved(source: &mut impl std::io::Read) -> anyhow::Result<Self> {
+        let mut magic = [0u8; 4];
+        source.read_exact(&mut magic).map_err(|_| StateFileError::TruncatedHeader)?;
+        let mut source = (&magic[..]).chain(source);
+        if compression::is_compressed(&magic) {
+            Self::load_uncompressed(&mut compression::decompressing_reader(source)?)
+        } else {
+            Self::load_uncompressed(&mut source)
+        }
+    }
+
+    fn load_uncompressed(source: &mut impl std::io::Read) -> anyhow::Result<Self> {
+        let header = StateFileHeader::read(source)?;
+        let mut body = Vec::new();
+        source.take(header.length).read_to_end(&mut body)?;
+        if body.len() as u64 != header.length {
+            anyhow::bail!(StateFileError::LengthMismatch {
+                expected: header.length,
+                actual:   body.len() as u64,
+            });
+        }
+        let mut cursor = std::io::Cursor::new(&body);
+        let state = Self::deserialize(&mut cursor).map_err(|e| StateFileError::Malformed {
+            reason: e.to_string(),
+        })?;
+        if cursor.position() != header.length {
+            anyhow::bail!(StateFileError::LengthMismatch {
+                expected: header.length,
+                actual:   cursor.position(),
+            });
+        }
+        // The deserialized state is entirely in memory, so the loader is not used.
+        let mut loader = Loader::new(&[][..]);
+        let root_hash = state.hash(&mut loader);
+        let consistent = match &state {
+            PersistentState::Empty => true,
+            PersistentState::Root(root) => root.get(&mut loader).verify_hashes(&mut loader),
+        };
+        anyhow::ensure!(root_hash == header.root_hash, StateFileError::HashMismatch {
+            expected: header.root_hash,
+            actual:   root_hash,
+        });
+        anyhow::ensure!(consistent, StateFileError::InconsistentHashes);
+        Ok(state)
+    }
+
     /// Lookup a key in the tree. This is only meant for testing
     /// since performance is slow compared to lookup in the mutable tree.
     pub fn lookup(&self, loader: &mut impl BackingStoreLoad, key: &[u8]) -> Option<Value> {
@@ -145,11 +252,32 @@ impl PersistentState {
         }
     }
 
+    /// Call the provided function on each key-value pair of the state whose
+    /// key starts with the given prefix, in lexicographic order of keys. The
+    /// traversal stops early if the function returns
+    /// [`Break`](std::ops::ControlFlow::Break). In contrast to iterating over
+    /// the thawed state this does not require any copying of the tree, and
+    /// only the part of the tree under the prefix is loaded.
+    pub fn stream_prefix(
+        &self,
+        loader: &mut impl BackingStoreLoad,
+        prefix: &[u8],
+        f: &mut impl FnMut(&[u8], &[u8]) -> std::ops::ControlFlow<()>,
+    ) -> std::ops::ControlFlow<()> {
+        match self {
+            PersistentState::Empty => std::ops::ControlFlow::Continue(()),
+            PersistentState::Root(node) => node.get(loader).stream_prefix(loader, prefix, f),
+        }
+    }
+
     /// Generate a fresh mutable state from the persistent state.
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(level = "debug", skip_all))]
     pub fn thaw(&self) -> MutableState {
         MutableState {
-            inner:      None,
-            persistent: self.clone(),
+            inner:           None,
+            persistent:      self.clone(),
+            checkpoints:     Vec::new(),
+            next_checkpoint: 0,
         }
     }
 
@@ -176,6 +304,50 @@ impl PersistentState {
         }
     }
 
+    /// Compute the structural statistics of the state, see [Node::statistics].
+    /// **This loads the entire state.**
+    pub fn statistics(&self, loader: &mut impl BackingStoreLoad) -> TrieStats {
+        match self {
+            PersistentState::Empty => TrieStats::default(),
+            PersistentState::Root(node) => node.get(loader).data.statistics(loader),
+        }
+    }
+
+    /// Compute the differences between this state and the `new` one, ordered
+    /// by increasing key, see [Hashed::diff]. Parts of the state that
+    /// are unchanged are skipped without loading them, except for their
+    /// roots.
+    pub fn diff(&self, new: &Self, loader: &mut impl BackingStoreLoad) -> Vec<DiffEntry> {
+        let (root, added) = match (self, new) {
+            (PersistentState::Empty, PersistentState::Empty) => return Vec::new(),
+            (PersistentState::Root(old), PersistentState::Root(new)) => {
+                return old.get(loader).diff(&new.get(loader), loader)
+            }
+            (PersistentState::Root(old), PersistentState::Empty) => (old, false),
+            (PersistentState::Empty, PersistentState::Root(new)) => (new, true),
+        };
+        // If one of the states is empty all the entries of the other differ.
+        let mut diff = Vec::new();
+        let _ = root.get(loader).stream_prefix(loader, &[], &mut |key, value| {
+            let (key, value) = (key.to_vec(), value.to_vec());
+            diff.push(
+                if added {
+                    DiffEntry::Added {
+                        key,
+                        value,
+                    }
+                } else {
+                    DiffEntry::Removed {
+                        key,
+                        value,
+                    }
+                },
+            );
+            std::ops::ControlFlow::Continue(())
+        });
+        diff
+    }
+
     #[cfg(feature = "display-state")]
     pub fn display_tree(&self, builder: &mut TreeBuilder, loader: &mut impl BackingStoreLoad) {
         match self {
@@ -188,6 +360,96 @@ impl PersistentState {
     }
 }
 
+/// Magic bytes at the start of a state file written by
+/// [PersistentState::save].
+pub const STATE_FILE_MAGIC: [u8; 4] = *b"CCDS";
+
+/// Current version of the state file format. Files with any other version are
+/// rejected by [PersistentState::load_saved].
+pub const STATE_FILE_VERSION: u16 = 1;
+
+/// The header of a state file. It is serialized as the [STATE_FILE_MAGIC],
+/// followed by the version, the root hash, and the length of the serialized
+/// state. All integers are big endian.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StateFileHeader {
+    /// Version of the format of the state that follows.
+    pub version:   u16,
+    /// Hash of the state, as computed by [PersistentState::hash].
+    pub root_hash: super::Hash,
+    /// Length of the serialized state, in bytes.
+    pub length:    u64,
+}
+
+impl StateFileHeader {
+    /// Size of the serialized header in bytes.
+    pub const SIZE: usize = 4 + 2 + 32 + 8;
+
+    /// Write the header to the provided output.
+    pub fn write(&self, out: &mut impl std::io::Write) -> anyhow::Result<()> {
+        out.write_all(&STATE_FILE_MAGIC)?;
+        out.write_u16::<BigEndian>(self.version)?;
+        out.write_all(self.root_hash.as_ref())?;
+        out.write_u64::<BigEndian>(self.length)?;
+        Ok(())
+    }
+
+    /// Read the header from the provided source, checking that the magic
+    /// bytes are correct and that the version is supported.
+    pub fn read(source: &mut impl std::io::Read) -> anyhow::Result<Self> {
+        let mut bytes = [0u8; Self::SIZE];
+        source.read_exact(&mut bytes).map_err(|_| StateFileError::TruncatedHeader)?;
+        let source = &mut &bytes[..];
+        let mut magic = [0u8; 4];
+        source.read_exact(&mut magic)?;
+        anyhow::ensure!(magic == STATE_FILE_MAGIC, StateFileError::IncorrectMagic);
+        let version = source.read_u16::<BigEndian>()?;
+        anyhow::ensure!(version == STATE_FILE_VERSION, StateFileError::UnsupportedVersion {
+            version,
+        });
+        let root_hash = super::Hash::read(source)?;
+        let length = source.read_u64::<BigEndian>()?;
+        Ok(Self {
+            version,
+            root_hash,
+            length,
+        })
+    }
+}
+
+/// Reasons why a state file could not be loaded by
+/// [PersistentState::load_saved].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum StateFileError {
+    #[error("The state file is too short to contain a header.")]
+    TruncatedHeader,
+    #[error("Not a state file: incorrect magic bytes.")]
+    IncorrectMagic,
+    #[error(
+        "Unsupported state file version {version}, only version {} is supported.",
+        STATE_FILE_VERSION
+    )]
+    UnsupportedVersion {
+        version: u16,
+    },
+    #[error("The state file records a state of length {expected}, but it has length {actual}.")]
+    LengthMismatch {
+        expected: u64,
+        actual:   u64,
+    },
+    #[error("The state in the state file is malformed: {reason}")]
+    Malformed {
+        reason: String,
+    },
+    #[error("The state file records root hash {expected:?}, but the state has hash {actual:?}.")]
+    HashMismatch {
+        expected: super::Hash,
+        actual:   super::Hash,
+    },
+    #[error("The hashes in the state file are inconsistent with the state.")]
+    InconsistentHashes,
+}
+
 #[derive(Debug, Clone)]
 /// This type is a technical device to support lazy conversion of
 /// [`PersistentState`] to [`MutableState`]. It contains the runtime
@@ -234,13 +496,31 @@ impl MutableStateInner {
 /// time.
 pub type StateTrie<'a> = MutexGuard<'a, MutableTrie>;
 
+/// A checkpoint of a [MutableState], created by [MutableState::checkpoint].
+/// Rolling back to the checkpoint with [MutableState::rollback_to] consumes
+/// it, since modifications after the rollback are made in the generation of
+/// the checkpoint.
+#[derive(Debug)]
+pub struct StateCheckpoint {
+    /// The nonce that identifies the checkpoint in the state that created it.
+    pub(crate) nonce: u64,
+}
+
 /// The mutable contract state.
 #[derive(Debug, Clone)]
 pub struct MutableState {
-    inner:      Option<MutableStateInner>,
+    inner:           Option<MutableStateInner>,
     /// The original state this mutable state is derived from, or after
     /// freezing, the new persistent state.
-    persistent: PersistentState,
+    persistent:      PersistentState,
+    /// The checkpoints that can still be rolled back to, oldest first, by
+    /// their nonces together with the generation that contains the state at
+    /// the checkpoint.
+    checkpoints:     Vec<(u64, u32)>,
+    /// The nonce of the next checkpoint. Nonces are not reused, so that a
+    /// checkpoint invalidated by a rollback is not accepted again once
+    /// the generation it refers to exists again.
+    next_checkpoint: u64,
 }
 
 impl MutableState {
@@ -248,8 +528,10 @@ impl MutableState {
     /// executing in.
     pub fn initial_state() -> Self {
         Self {
-            inner:      None,
-            persistent: PersistentState::Empty,
+            inner:           None,
+            persistent:      PersistentState::Empty,
+            checkpoints:     Vec::new(),
+            next_checkpoint: 0,
         }
     }
 
@@ -296,11 +578,13 @@ impl MutableState {
             // and start a new one.
             trie.new_generation();
             Self {
-                inner:      Some(MutableStateInner {
+                inner:           Some(MutableStateInner {
                     root:  inner.root + 1,
                     state: inner.state.clone(),
                 }),
-                persistent: self.persistent.clone(),
+                persistent:      self.persistent.clone(),
+                checkpoints:     Vec::new(),
+                next_checkpoint: 0,
             }
         } else {
             let root = 0;
@@ -324,8 +608,53 @@ impl MutableState {
         }
     }
 
+    /// Create a checkpoint of the current state. Subsequent modifications are
+    /// made in a new generation of the trie, and can be undone with
+    /// [MutableState::rollback_to]. Entries and iterators handed out before
+    /// the checkpoint remain valid after a rollback.
+    pub fn checkpoint(&mut self, loader: &mut impl BackingStoreLoad) -> StateCheckpoint {
+        let inner = self.get_inner(loader);
+        let root = inner.root;
+        inner.lock().new_generation();
+        inner.root += 1;
+        let nonce = self.next_checkpoint;
+        self.next_checkpoint += 1;
+        self.checkpoints.push((nonce, root));
+        StateCheckpoint {
+            nonce,
+        }
+    }
+
+    /// Undo all modifications made since the checkpoint was created, and
+    /// continue in the generation of the checkpoint. Checkpoints created after
+    /// the given one are invalidated. This fails if the checkpoint is not valid
+    /// for this state, e.g., because it was invalidated by an earlier
+    /// rollback, or if the state has been frozen since the checkpoint was
+    /// created.
+    pub fn rollback_to(&mut self, checkpoint: StateCheckpoint) -> anyhow::Result<()> {
+        let inner = self.inner.as_mut().ok_or_else(|| {
+            anyhow::anyhow!("The state has been frozen since the checkpoint was created.")
+        })?;
+        let position =
+            self.checkpoints.iter().rposition(|(nonce, _)| *nonce == checkpoint.nonce).ok_or_else(
+                || anyhow::anyhow!("Checkpoint {} is not valid for this state.", checkpoint.nonce),
+            )?;
+        let (_, root) = self.checkpoints[position];
+        anyhow::ensure!(
+            root < inner.root,
+            "Checkpoint {} is not valid for a state in generation {}.",
+            checkpoint.nonce,
+            inner.root
+        );
+        self.checkpoints.truncate(position);
+        inner.lock().normalize(root);
+        inner.root = root;
+        Ok(())
+    }
+
     /// Make the state persistent. This leaves the mutable state empty.
     /// This function is idempotent.
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(level = "debug", skip_all))]
     pub fn freeze<C: Collector<Value>>(
         &mut self,
         loader: &mut impl BackingStoreLoad,
@@ -333,6 +662,7 @@ impl MutableState {
     ) -> PersistentState {
         // Replace the inner mutable state with None.
         let inner = self.inner.take();
+        self.checkpoints.clear();
         match inner {
             Some(inner) => {
                 let mut trie = std::mem::replace(&mut *inner.lock(), MutableTrie::empty());
@@ -346,4 +676,85 @@ impl MutableState {
             None => self.persistent.clone(),
         }
     }
+
+    /// Compute the work that [MutableState::freeze] would do, without freezing
+    /// the state. This is zero if the state was not modified.
+    pub fn freeze_cost(&self) -> FreezeCost {
+        let mut cost = FreezeCost::default();
+        if let Some(inner) = self.inner.as_ref() {
+            let mut trie = inner.lock();
+            // Forget any newer generations, in the same way as freeze does.
+            trie.normalize(inner.root);
+            trie.freeze_cost(&mut cost);
+        }
+        cost
+    }
+}
+
+/// Statistics produced by [compact].
+#[derive(Debug, Clone, Copy)]
+pub struct CompactionStats {
+    /// Size of the store, in bytes, before compaction.
+    pub original_size:  u64,
+    /// Size of the store, in bytes, after compaction.
+    pub compacted_size: u64,
+    /// Location of the root of the state in the compacted store.
+    pub new_root:       Reference,
+}
+
+impl CompactionStats {
+    /// The number of bytes that were reclaimed by compaction.
+    pub fn reclaimed(&self) -> u64 { self.original_size.saturating_sub(self.compacted_size) }
+}
+
+/// Compact the store so that it only contains the parts of the state reachable
+/// from the root at the given location. All reachable nodes are rewritten into
+/// a fresh store which then replaces the provided one. Note that as a result
+/// any references into the old store, apart from the root, are invalidated.
+///
+/// Before the store is replaced the state is reloaded from the fresh store and
+/// compared, including all the hashes, to the original state. If there is any
+/// discrepancy the original store is left unchanged and an error is returned.
+pub fn compact(store: &mut Vec<u8>, root: Reference) -> anyhow::Result<CompactionStats> {
+    let original_size = store.len() as u64;
+    let mut loader = Loader::new(&store[..]);
+    let state = PersistentState::load_from_location(&mut loader, root)?;
+    let original_hash = state.hash(&mut loader);
+    // Serialization loads the entire tree, and deserialization produces a tree
+    // that is purely in memory, so storing it writes every node to the new store.
+    let mut original_bytes = Vec::new();
+    state.serialize(&mut loader, &mut original_bytes)?;
+    let mut fresh = PersistentState::deserialize(&mut std::io::Cursor::new(&original_bytes))?;
+    let mut new_store = Vec::new();
+    let new_root = fresh.store_update(&mut new_store)?;
+
+    let mut new_loader = Loader::new(&new_store[..]);
+    let reloaded = PersistentState::load_from_location(&mut new_loader, new_root)?;
+    let new_hash = reloaded.hash(&mut new_loader);
+    anyhow::ensure!(
+        new_hash == original_hash,
+        "State hash changed during compaction: {:?} != {:?}.",
+        original_hash,
+        new_hash
+    );
+    if let PersistentState::Root(node) = &reloaded {
+        let recomputed = node.get(&mut new_loader).data.hash(&mut new_loader);
+        anyhow::ensure!(
+            recomputed == original_hash,
+            "Recomputed root hash {:?} does not match the stored one {:?}.",
+            recomputed,
+            original_hash
+        );
+    }
+    let mut reloaded_bytes = Vec::with_capacity(original_bytes.len());
+    reloaded.serialize(&mut new_loader, &mut reloaded_bytes)?;
+    anyhow::ensure!(reloaded_bytes == original_bytes, "Compacted state differs from the original.");
+
+    let compacted_size = new_store.len() as u64;
+    *store = new_store;
+    Ok(CompactionStats {
+        original_size,
+        compacted_size,
+        new_root,
+    })
 }
@@ -0,0 +1,91 @@
+//! Support for backing stores that can only be accessed asynchronously, e.g.,
+//! when trie nodes are served by a remote storage service.
+//!
+//! The trie implementation itself is synchronous, and loads nodes lazily via
+//! [BackingStoreLoad] during contract execution. The [AsyncBackingStoreLoad]
+//! trait is the asynchronous analogue of that trait, and [BlockingLoader]
+//! bridges the two by driving the asynchronous load to completion on a
+//! [tokio] runtime via its [Handle](tokio::runtime::Handle).
+//!
+//! # Determinism
+//!
+//! The result of contract execution must not depend on where the state is
+//! loaded from. An implementation of [AsyncBackingStoreLoad] must thus return
+//! exactly the bytes that were stored at the given [Reference] by the matching
+//! [BackingStoreStore] implementation. Retries, caching, and load balancing are
+//! all fine, as long as this invariant is maintained.
+//!
+//! # Timeouts
+//!
+//! A load that does not complete within the timeout configured on the
+//! [BlockingLoader] is reported as a [LoadError::IOError] with kind
+//! [std::io::ErrorKind::TimedOut]. Since the trie treats failure to load a node
+//! as a violation of its invariants this will abort execution of the contract
+//! with a panic. A timeout is thus **not** an outcome of execution, and the
+//! caller must not record any result of an execution that was aborted in this
+//! way. It should instead either retry the execution or give up on the
+//! transaction entirely. Timeouts should therefore be chosen generously, so
+//! that they only trigger when the store is genuinely unavailable.
+use super::*;
+use std::{future::Future, time::Duration};
+
+/// Trait implemented by types that can load data from given locations
+/// asynchronously. This is the asynchronous counterpart of
+/// [BackingStoreLoad], and the implementation must satisfy the same
+/// requirements. See the module documentation for details.
+pub trait AsyncBackingStoreLoad {
+    type R: AsRef<[u8]>;
+    /// The future that produces the loaded value.
+    type Fut: Future<Output = LoadResult<Self::R>>;
+    /// Start loading the value stored at the given location.
+    fn load_raw_async(&self, location: Reference) -> Self::Fut;
+}
+
+/// An adapter that implements [BackingStoreLoad] for any type that implements
+/// [AsyncBackingStoreLoad]. Each load blocks the current thread until the
+/// underlying future completes, or the timeout elapses.
+///
+/// Note that blocking is only allowed outside of the asynchronous context of
+/// the runtime, so contract execution using this loader must happen on a
+/// thread that is not driven by the runtime, e.g., via
+/// `tokio::task::spawn_blocking`. Calling [BackingStoreLoad::load_raw] from a
+/// task running on the runtime will panic.
+#[derive(Debug, Clone)]
+pub struct BlockingLoader<S> {
+    /// The asynchronous store to load the data from.
+    pub inner:   S,
+    /// Handle to the runtime that is used to drive loads to completion.
+    pub handle:  tokio::runtime::Handle,
+    /// Maximum amount of time to wait for a single load.
+    pub timeout: Duration,
+}
+
+impl<S> BlockingLoader<S> {
+    /// Construct a new loader that uses the given runtime handle.
+    pub fn new(inner: S, handle: tokio::runtime::Handle, timeout: Duration) -> Self {
+        Self {
+            inner,
+            handle,
+            timeout,
+        }
+    }
+}
+
+impl<S: AsyncBackingStoreLoad> BackingStoreLoad for BlockingLoader<S> {
+    type R = S::R;
+
+    fn load_raw(&mut self, location: Reference) -> LoadResult<Self::R> {
+        let fut = self.inner.load_raw_async(location);
+        // The timeout must be constructed in the context of the runtime since it
+        // needs access to the runtime's timer.
+        let _guard = self.handle.enter();
+        let with_timeout = tokio::time::timeout(self.timeout, fut);
+        match self.handle.block_on(with_timeout) {
+            Ok(result) => result,
+            Err(_) => Err(LoadError::IOError(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("Loading from location {} timed out.", location.reference),
+            ))),
+        }
+    }
+}
@@ -49,7 +49,7 @@ use std::{
     io::{Read, Write},
     iter::once,
     num::NonZeroU32,
-    ops::Deref,
+    ops::{ControlFlow, Deref},
     sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
 };
 
@@ -864,6 +864,18 @@ impl<'a> StemIter<'a> {
     }
 }
 
+#[inline(always)]
+/// Get the chunk at the given position of the data, which must be less than
+/// twice the length of the data.
+fn chunk_at(data: &[u8], pos: usize) -> Chunk<4> {
+    let byte = data[pos / 2];
+    if pos % 2 == 0 {
+        Chunk::new(byte >> 4)
+    } else {
+        Chunk::new(byte & 0x0f)
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
 #[repr(transparent)]
 /// A wrapper around u8 that indicates an N-bit value. N must be between 1 and
@@ -1750,7 +1762,7 @@ impl Entry {
 
 type Position = u8;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Iterator {
     /// The root of the iterator. This is stored to allow removal of the
     /// iterator.
@@ -1768,6 +1780,24 @@ pub struct Iterator {
     /// Whether [MutableTrie::next] has already been called on the iterator or
     /// not. This is only useful for the `get_key` method.
     started:      bool,
+    /// Whether [MutableTrie::next] has returned [None], i.e., the iterator is
+    /// exhausted. This is only useful for the `position` method.
+    finished:     bool,
+    /// The key the iterator was created after by [MutableTrie::iter_at], if
+    /// any. This is only useful for the `position` method.
+    after:        Option<Box<[u8]>>,
+}
+
+/// The position of an iterator among the entries under its root, see
+/// [Iterator::position] and [MutableTrie::iter_at].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IteratorPosition<'a> {
+    /// No entries have been returned yet.
+    Start,
+    /// The entries up to and including the given key have been returned.
+    After(&'a [u8]),
+    /// All the entries have been returned.
+    End,
 }
 
 impl Iterator {
@@ -1789,6 +1819,21 @@ impl Iterator {
     /// Get the key of which the iterator was initialized with.
     #[inline(always)]
     pub fn get_root(&self) -> &[u8] { &self.root }
+
+    /// Get the position of the iterator. An equivalent iterator can be created
+    /// from it with [MutableTrie::iter_at], also in a later generation or
+    /// after the tree has been modified.
+    pub fn position(&self) -> IteratorPosition {
+        if self.finished {
+            IteratorPosition::End
+        } else if self.started {
+            IteratorPosition::After(&self.key.data)
+        } else if let Some(after) = self.after.as_ref() {
+            IteratorPosition::After(after)
+        } else {
+            IteratorPosition::Start
+        }
+    }
 }
 
 impl CachedRef<Hashed<Node>> {
@@ -2081,6 +2126,7 @@ impl MutableTrie {
                     iterator.next_child = Some(next_child);
                 } else {
                     // we are done
+                    iterator.finished = true;
                     return Ok(None);
                 }
             }
@@ -2130,6 +2176,8 @@ impl MutableTrie {
                         next_child:   None,
                         stack:        Vec::new(),
                         started:      false,
+                        finished:     false,
+                        after:        None,
                     }));
                 }
                 FollowStem::KeyIsPrefix {
@@ -2149,6 +2197,8 @@ impl MutableTrie {
                         next_child: None,
                         stack: Vec::new(),
                         started: false,
+                        finished: false,
+                        after: None,
                     }));
                 }
                 FollowStem::StemIsPrefix {
@@ -2175,6 +2225,86 @@ impl MutableTrie {
         }
     }
 
+    /// Like [Self::iter], but the iterator continues from the given position,
+    /// i.e., the first call to [Self::next] returns the first entry under the
+    /// prefix whose key is greater than the key of the position. The key does
+    /// not need to be in the tree. Returns [None] if there are no entries
+    /// under the prefix, or the position is [IteratorPosition::End].
+    pub fn iter_at(
+        &mut self,
+        loader: &mut impl BackingStoreLoad,
+        prefix: &[u8],
+        position: IteratorPosition,
+    ) -> Result<Option<Iterator>, TooManyIterators> {
+        let after = match position {
+            IteratorPosition::Start => return self.iter(loader, prefix),
+            IteratorPosition::After(after) => after,
+            IteratorPosition::End => return Ok(None),
+        };
+        let mut iterator = if let Some(iterator) = self.iter(loader, prefix)? {
+            iterator
+        } else {
+            return Ok(None);
+        };
+        iterator.after = Some(after.into());
+        let after_len = 2 * after.len();
+        // Number of chunks of the key of the current node that are equal to those of
+        // `after`.
+        let mut matched = 0;
+        loop {
+            let node_idx = iterator.current_node;
+            let key_len = iterator.key.len();
+            while matched < key_len
+                && matched < after_len
+                && chunk_at(&iterator.key.data, matched) == chunk_at(after, matched)
+            {
+                matched += 1;
+            }
+            if matched < key_len {
+                // The keys differ, or `after` is a prefix of the key of the node. In the
+                // latter case, as well as if the key of the node is greater, all the
+                // entries of the subtree are still to be returned. Otherwise they are all
+                // skipped.
+                if matched < after_len
+                    && chunk_at(&iterator.key.data, matched) < chunk_at(after, matched)
+                {
+                    iterator.next_child = Some(self.nodes[node_idx].children.len() as Position);
+                }
+                return Ok(Some(iterator));
+            }
+            if matched == after_len {
+                // The value of the node is at `after`, so only the children remain.
+                iterator.next_child = Some(0);
+                return Ok(Some(iterator));
+            }
+            // The key of the node is a strict prefix of `after`, so the value of the node
+            // and the children before the next step of `after` are skipped.
+            let step = chunk_at(after, matched);
+            let (_, _, children) = make_owned(
+                node_idx,
+                &mut self.borrowed_values,
+                &mut self.nodes,
+                &mut self.entries,
+                loader,
+            );
+            let pos = children.iter().position(|c| c.key() >= step).unwrap_or(children.len());
+            match children.get(pos) {
+                Some(child) if child.key() == step => {
+                    let child_idx = child.index();
+                    iterator.stack.push((node_idx, pos as Position + 1, key_len));
+                    iterator.current_node = child_idx;
+                    iterator.key.push(step);
+                    iterator.key.extend(&self.nodes[child_idx].path);
+                    matched += 1;
+                }
+                _ => {
+                    iterator.next_child = Some(pos as Position);
+                    return Ok(Some(iterator));
+                }
+            }
+        }
+    }
+
     /// Set the entry value to the given value. Return a mutable reference to
     /// the value if successful. This is analogous to `get_mut`, except that
     /// it avoids copying the value in case the value is currently not owned
@@ -2358,6 +2488,62 @@ impl MutableTrie {
         }
     }
 
+    /// Report to the collector what [MutableTrie::freeze] would report when
+    /// freezing the current generation, without freezing it.
+    pub fn freeze_cost<C: Collector<Vec<u8>>>(&self, collector: &mut C) {
+        let root_idx = if let Some(root_idx) = self.generations.last().and_then(|g| g.root) {
+            root_idx
+        } else {
+            return;
+        };
+        // Traverse the reachable nodes bottom up, in the same way as freeze.
+        let mut reachable_stack = vec![root_idx];
+        let mut reachable = Vec::new();
+        while let Some(idx) = reachable_stack.pop() {
+            reachable.push(idx);
+            if let Some((_, children)) = self.nodes[idx].children.get_owned() {
+                for c in children {
+                    reachable_stack.push(c.index());
+                }
+            }
+        }
+        // Whether each of the visited nodes will be a new node.
+        let mut changed = HashMap::new();
+        for node_idx in reachable.into_iter().rev() {
+            let node = &self.nodes[node_idx];
+            let value_changed = match node.value.map(|entry_idx| self.entries[entry_idx]) {
+                None
+                | Some(Entry::ReadOnly {
+                    borrowed: true,
+                    ..
+                }) => false,
+                Some(Entry::ReadOnly {
+                    entry_idx,
+                    ..
+                })
+                | Some(Entry::Mutable {
+                    entry_idx,
+                }) => {
+                    collector.add_value(&self.values[entry_idx]);
+                    true
+                }
+                Some(Entry::Deleted) => true,
+            };
+            let mut children_changed = false;
+            if let Some((_, children)) = node.children.get_owned() {
+                for child in children {
+                    children_changed |= changed.remove(&child.index()).unwrap_or(false);
+                }
+            }
+            let node_changed = node.origin.is_none() || value_changed || children_changed;
+            if node_changed {
+                collector.add_path(node.path.len());
+                collector.add_children(node.children.len());
+            }
+            changed.insert(node_idx, node_changed);
+        }
+    }
+
     pub fn get_entry(&mut self, loader: &mut impl BackingStoreLoad, key: &[u8]) -> Option<EntryId> {
         let mut key_iter = StemIter::new(key);
         let owned_nodes = &mut self.nodes;
@@ -3142,6 +3328,108 @@ impl Hashed<Node> {
             unsafe { std::hint::unreachable_unchecked() };
         }
     }
+
+    /// Check that the hashes stored in the tree are consistent with the data.
+    /// The hash of each node is recomputed from its value and the hashes of its
+    /// children, and the hashes of values stored behind an indirection are
+    /// recomputed from the values. This loads the entire tree.
+    pub fn verify_hashes(&self, loader: &mut impl BackingStoreLoad) -> bool {
+        let mut stack = vec![self.clone()];
+        while let Some(node) = stack.pop() {
+            if node.data.hash(loader) != node.hash {
+                return false;
+            }
+            if let Some(v) = node.data.value.as_ref() {
+                let borrowed = v.borrow();
+                let (mhash, v) = borrowed.get_ref_and_hash(loader);
+                if let Some(hash) = mhash {
+                    let data: &[u8] = v.as_ref();
+                    if data.hash(loader) != *hash {
+                        return false;
+                    }
+                }
+            }
+            for (_, child) in node.data.children.iter() {
+                let child_ref = child.borrow();
+                let nd = child_ref.get(loader);
+                stack.push(nd.clone());
+            }
+        }
+        true
+    }
+
+    /// Call the provided function on each key-value pair in the tree whose key
+    /// starts with the given prefix, in lexicographic order of keys. The
+    /// traversal stops as soon as the function returns
+    /// [`Break`](ControlFlow::Break), in which case `Break` is returned.
+    /// Only the part of the tree under the prefix is loaded.
+    pub fn stream_prefix(
+        &self,
+        loader: &mut impl BackingStoreLoad,
+        prefix: &[u8],
+        f: &mut impl FnMut(&[u8], &[u8]) -> ControlFlow<()>,
+    ) -> ControlFlow<()> {
+        // First find the node at or below the prefix. The key is extended so that
+        // it is the full key of the node.
+        let mut key_iter = StemIter::new(prefix);
+        let mut key = MutStem::from(prefix);
+        let mut node = self.clone();
+        loop {
+            let mut stem_iter = node.data.path.iter();
+            match follow_stem(&mut key_iter, &mut stem_iter) {
+                FollowStem::Equal => break,
+                FollowStem::KeyIsPrefix {
+                    stem_step,
+                } => {
+                    key.push(stem_step);
+                    key.extend(&stem_iter.to_stem());
+                    break;
+                }
+                FollowStem::StemIsPrefix {
+                    key_step,
+                } => {
+                    let next = match node.data.children.iter().find(|&&(ck, _)| ck == key_step) {
+                        Some((_, c)) => {
+                            let node_ref = c.borrow();
+                            let nd = node_ref.get(loader);
+                            nd.clone()
+                        }
+                        None => return ControlFlow::Continue(()),
+                    };
+                    node = next;
+                }
+                FollowStem::Diff {
+                    ..
+                } => return ControlFlow::Continue(()),
+            }
+        }
+        // Then traverse the subtree depth first. Each entry on the stack records the
+        // step from the parent, if any, and the length of the parent's key.
+        let mut stack = vec![(node, None, key.len())];
+        while let Some((node, step, len)) = stack.pop() {
+            key.truncate(len);
+            if let Some(step) = step {
+                key.push(step);
+                key.extend(&node.data.path);
+            }
+            if let Some(v) = node.data.value.as_ref() {
+                let borrowed = v.borrow();
+                let (_, value) = borrowed.get_ref_and_hash(loader);
+                // Keys of nodes with values are always full bytes.
+                if f(&key.data, value.as_ref()).is_break() {
+                    return ControlFlow::Break(());
+                }
+            }
+            let len = key.len();
+            // Push in reverse so that children are visited in order.
+            for (step, child) in node.data.children.iter().rev() {
+                let node_ref = child.borrow();
+                let nd = node_ref.get(loader);
+                stack.push((nd.clone(), Some(*step), len));
+            }
+        }
+        ControlFlow::Continue(())
+    }
 }
 
 /// Result of [follow_stem] below.
@@ -3307,6 +3595,302 @@ impl Node {
     }
 }
 
+/// Structural statistics of a tree, as computed by [Node::statistics].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrieStats {
+    /// Number of nodes with a value and no children.
+    pub leaf_nodes:           u64,
+    /// Number of nodes with children and no value.
+    pub branch_nodes:         u64,
+    /// Number of nodes with both children and a value.
+    pub branch_value_nodes:   u64,
+    /// Number of nodes at each depth. The root is at depth 0.
+    pub depth_histogram:      Vec<u64>,
+    /// Total length, in **chunks**, of the key fragments (stems) of all nodes.
+    pub total_stem_len:       u64,
+    /// Number of values stored inline in their node.
+    pub inline_values:        u64,
+    /// Number of values stored behind an indirection.
+    pub indirect_values:      u64,
+    /// Total size of all the values, in bytes.
+    pub total_value_size:     u64,
+    /// Number of values by size. Index `i` counts the values whose size in
+    /// bytes needs `i` bits, that is, index 0 counts empty values, and index
+    /// `i > 0` counts values with size in the range `[2^(i-1), 2^i)`.
+    pub value_size_histogram: Vec<u64>,
+}
+
+impl TrieStats {
+    /// The total number of nodes.
+    pub fn nodes(&self) -> u64 { self.leaf_nodes + self.branch_nodes + self.branch_value_nodes }
+
+    /// The total number of values.
+    pub fn values(&self) -> u64 { self.inline_values + self.indirect_values }
+
+    /// The depth of the deepest node, or [None] if the tree is empty.
+    pub fn max_depth(&self) -> Option<usize> { self.depth_histogram.len().checked_sub(1) }
+
+    /// The average length, in **chunks**, of the key fragments of nodes.
+    pub fn average_stem_len(&self) -> f64 {
+        let nodes = self.nodes();
+        if nodes == 0 {
+            0.0
+        } else {
+            self.total_stem_len as f64 / nodes as f64
+        }
+    }
+
+    /// Increment the counter at the index of the histogram, extending it if
+    /// needed.
+    fn increment(histogram: &mut Vec<u64>, index: usize) {
+        if histogram.len() <= index {
+            histogram.resize(index + 1, 0);
+        }
+        histogram[index] += 1;
+    }
+}
+
+impl Node {
+    /// Compute the structural statistics of the tree rooted at this node.
+    /// **This loads the entire tree, including all the values.**
+    pub fn statistics(&self, loader: &mut impl BackingStoreLoad) -> TrieStats {
+        let mut stats = TrieStats::default();
+        let mut stack = vec![(self.clone(), 0usize)];
+        while let Some((node, depth)) = stack.pop() {
+            match (node.value.is_some(), node.children.is_empty()) {
+                (true, true) => stats.leaf_nodes += 1,
+                (false, _) => stats.branch_nodes += 1,
+                (true, false) => stats.branch_value_nodes += 1,
+            }
+            TrieStats::increment(&mut stats.depth_histogram, depth);
+            stats.total_stem_len += node.path.len() as u64;
+            if let Some(value) = node.value.as_ref() {
+                let borrowed = value.borrow();
+                if let InlineOrHashed::Inline {
+                    ..
+                } = &*borrowed
+                {
+                    stats.inline_values += 1;
+                } else {
+                    stats.indirect_values += 1;
+                }
+                let size = borrowed.get(loader).len();
+                stats.total_value_size += size as u64;
+                let bits = (64 - (size as u64).leading_zeros()) as usize;
+                TrieStats::increment(&mut stats.value_size_histogram, bits);
+            }
+            for (_, child) in node.children.iter() {
+                let child_ref = child.borrow();
+                stack.push((child_ref.get(loader).data.clone(), depth + 1));
+            }
+        }
+        stats
+    }
+}
+
+/// A difference between two trees, as computed by [Hashed::diff].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffEntry {
+    /// The key only has a value in the new tree.
+    Added {
+        key:   Vec<u8>,
+        value: Vec<u8>,
+    },
+    /// The key only has a value in the old tree.
+    Removed {
+        key:   Vec<u8>,
+        value: Vec<u8>,
+    },
+    /// The key has different values in the two trees.
+    Modified {
+        key: Vec<u8>,
+        old: Vec<u8>,
+        new: Vec<u8>,
+    },
+}
+
+impl DiffEntry {
+    /// The key the entry is about.
+    pub fn key(&self) -> &[u8] {
+        match self {
+            DiffEntry::Added {
+                key,
+                ..
+            } => key,
+            DiffEntry::Removed {
+                key,
+                ..
+            } => key,
+            DiffEntry::Modified {
+                key,
+                ..
+            } => key,
+        }
+    }
+}
+
+/// A position in a tree while computing a diff. This is either at a node, or
+/// inside its stem, after the given number of chunks of it.
+struct DiffPosition {
+    node:   Hashed<Node>,
+    offset: usize,
+}
+
+impl DiffPosition {
+    fn new(node: Hashed<Node>) -> Self {
+        Self {
+            node,
+            offset: 0,
+        }
+    }
+
+    /// The value at the position, if any. Only the end of the stem has a value.
+    fn value(&self) -> Option<&ValueLink> {
+        if self.offset == self.node.data.path.len() {
+            self.node.data.value.as_ref()
+        } else {
+            None
+        }
+    }
+
+    /// The positions one chunk below this one, ordered by increasing key.
+    fn children(&self, loader: &mut impl BackingStoreLoad) -> Vec<(Chunk<4>, DiffPosition)> {
+        let path = &self.node.data.path;
+        if self.offset < path.len() {
+            let step = chunk_at(&path.data, self.offset);
+            vec![(step, DiffPosition {
+                node:   self.node.clone(),
+                offset: self.offset + 1,
+            })]
+        } else {
+            self.node
+                .data
+                .children
+                .iter()
+                .map(|(step, child)| {
+                    let node = child.borrow().get(loader).clone();
+                    (*step, DiffPosition::new(node))
+                })
+                .collect()
+        }
+    }
+}
+
+/// Work remaining while computing a diff. The positions are at the same key in
+/// both trees.
+enum DiffWork {
+    Both(DiffPosition, DiffPosition),
+    Removed(DiffPosition),
+    Added(DiffPosition),
+}
+
+impl Hashed<Node> {
+    /// Compute the differences between this tree and the `new` one, ordered by
+    /// increasing key. Subtrees that are the same in both trees are recognized
+    /// by their hashes and skipped, so that only the roots of unchanged
+    /// subtrees are loaded. Values are compared by their hashes as well.
+    pub fn diff(&self, new: &Self, loader: &mut impl BackingStoreLoad) -> Vec<DiffEntry> {
+        let mut diff = Vec::new();
+        let mut key = MutStem::from(&[][..]);
+        // Each entry on the stack records the step from the parent position, if any,
+        // and the length of the key of the parent position.
+        let mut stack = vec![(
+            DiffWork::Both(DiffPosition::new(self.clone()), DiffPosition::new(new.clone())),
+            None,
+            0,
+        )];
+        while let Some((work, step, len)) = stack.pop() {
+            key.truncate(len);
+            if let Some(step) = step {
+                key.push(step);
+            }
+            let len = key.len();
+            match work {
+                DiffWork::Both(old, new) => {
+                    // The tree is canonical, so equal subtrees at the same key are at the
+                    // same offsets.
+                    if old.offset == new.offset && old.node.hash == new.node.hash {
+                        continue;
+                    }
+                    match (old.value(), new.value()) {
+                        (None, None) => (),
+                        (Some(old), None) => diff.push(DiffEntry::Removed {
+                            key:   key.data.clone(),
+                            value: old.borrow().get_copy(loader),
+                        }),
+                        (None, Some(new)) => diff.push(DiffEntry::Added {
+                            key:   key.data.clone(),
+                            value: new.borrow().get_copy(loader),
+                        }),
+                        (Some(old), Some(new)) => {
+                            let (old, new) = (old.borrow(), new.borrow());
+                            if old.hash(loader) != new.hash(loader) {
+                                diff.push(DiffEntry::Modified {
+                                    key: key.data.clone(),
+                                    old: old.get_copy(loader),
+                                    new: new.get_copy(loader),
+                                });
+                            }
+                        }
+                    }
+                    let mut old_children = old.children(loader).into_iter().peekable();
+                    let mut new_children = new.children(loader).into_iter().peekable();
+                    let mut children = Vec::new();
+                    loop {
+                        let order = match (old_children.peek(), new_children.peek()) {
+                            (None, None) => break,
+                            (Some(_), None) => std::cmp::Ordering::Less,
+                            (None, Some(_)) => std::cmp::Ordering::Greater,
+                            (Some((o, _)), Some((n, _))) => o.cmp(n),
+                        };
+                        let child = match order {
+                            std::cmp::Ordering::Less => {
+                                let (step, old) = old_children.next().expect("Peeked above.");
+                                (DiffWork::Removed(old), Some(step), len)
+                            }
+                            std::cmp::Ordering::Greater => {
+                                let (step, new) = new_children.next().expect("Peeked above.");
+                                (DiffWork::Added(new), Some(step), len)
+                            }
+                            std::cmp::Ordering::Equal => {
+                                let (step, old) = old_children.next().expect("Peeked above.");
+                                let (_, new) = new_children.next().expect("Peeked above.");
+                                (DiffWork::Both(old, new), Some(step), len)
+                            }
+                        };
+                        children.push(child);
+                    }
+                    // Push in reverse so that children are visited in order.
+                    stack.extend(children.into_iter().rev());
+                }
+                DiffWork::Removed(position) => {
+                    if let Some(value) = position.value() {
+                        diff.push(DiffEntry::Removed {
+                            key:   key.data.clone(),
+                            value: value.borrow().get_copy(loader),
+                        });
+                    }
+                    for (step, child) in position.children(loader).into_iter().rev() {
+                        stack.push((DiffWork::Removed(child), Some(step), len));
+                    }
+                }
+                DiffWork::Added(position) => {
+                    if let Some(value) = position.value() {
+                        diff.push(DiffEntry::Added {
+                            key:   key.data.clone(),
+                            value: value.borrow().get_copy(loader),
+                        });
+                    }
+                    for (step, child) in position.children(loader).into_iter().rev() {
+                        stack.push((DiffWork::Added(child), Some(step), len));
+                    }
+                }
+            }
+        }
+        diff
+    }
+}
+
 #[cfg(test)]
 /// Tests for the prefix map.
 mod prefix_map_tests {
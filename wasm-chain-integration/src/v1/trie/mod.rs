@@ -6,7 +6,11 @@ mod tests;
 
 mod api;
 pub use api::*;
-pub use low_level::Iterator;
+#[cfg(feature = "async-store")]
+mod async_store;
+#[cfg(feature = "async-store")]
+pub use async_store::*;
+pub use low_level::{DiffEntry, Iterator, IteratorPosition, TrieStats};
 pub(crate) mod foreign;
 // We need the low-level module for testing and benchmarks, but we do not wish
 // to expose it.
@@ -859,6 +859,43 @@ fn prop_freeze_collector() {
     QuickCheck::new().tests(NUM_TESTS).quickcheck(prop as fn(Vec<_>, _) -> _);
 }
 
+#[test]
+/// Check that the cost predicted by [MutableTrie::freeze_cost] is the same as
+/// the cost of actually freezing the trie, both after modifications and for
+/// an unmodified trie, where it must be zero.
+fn prop_freeze_cost_matches_freeze() {
+    let prop = |inputs: Vec<(Vec<u8>, Value)>,
+                new: Vec<(Vec<u8>, Value)>,
+                deletes: Vec<Vec<u8>>|
+     -> anyhow::Result<()> {
+        let (trie, mut loader) = make_mut_trie(inputs);
+        let trie = if let Some(trie) = trie.freeze(&mut loader, &mut EmptyCollector) {
+            trie
+        } else {
+            return Ok(());
+        };
+        let unmodified = trie.make_mutable(0, &mut loader);
+        let mut predicted = FreezeCost::default();
+        unmodified.freeze_cost(&mut predicted);
+        ensure!(predicted == FreezeCost::default(), "Non-zero cost {}.", predicted);
+
+        let mut m = trie.make_mutable(0, &mut loader);
+        for (k, v) in new {
+            m.insert(&mut loader, &k, v).expect("Inserting should succeed.");
+        }
+        for k in deletes {
+            m.delete(&mut loader, &k).expect("Deleting should succeed.");
+        }
+        let mut predicted = FreezeCost::default();
+        m.freeze_cost(&mut predicted);
+        let mut actual = FreezeCost::default();
+        m.freeze(&mut loader, &mut actual);
+        ensure!(predicted == actual, "Predicted cost {} != actual cost {}.", predicted, actual);
+        Ok(())
+    };
+    QuickCheck::new().tests(NUM_TESTS).quickcheck(prop as fn(Vec<_>, _, _) -> _);
+}
+
 #[test]
 /// Check that mutating the new generation does not affect the previous one.
 fn prop_matches_reference_after_new_gen_mutate() {
@@ -1024,3 +1061,677 @@ fn prop_iterator_get_key() {
     };
     QuickCheck::new().tests(NUM_TESTS).quickcheck(prop as fn(_, _) -> anyhow::Result<()>);
 }
+
+/// Advance the iterator until it is exhausted, and return the keys of the
+/// entries it returned. The iterator is deleted afterwards.
+fn remaining_keys(
+    trie: &mut MutableTrie,
+    loader: &mut Loader<Value>,
+    iterator: Option<Iterator>,
+) -> Vec<Vec<u8>> {
+    let mut keys = Vec::new();
+    if let Some(mut iterator) = iterator {
+        while trie
+            .next(loader, &mut iterator, &mut EmptyCounter)
+            .expect("Empty counter does not fail.")
+            .is_some()
+        {
+            keys.push(iterator.get_key().to_vec());
+        }
+        trie.delete_iter(&iterator);
+    }
+    keys
+}
+
+#[test]
+/// Check that an iterator created at a position returns exactly the entries
+/// under the prefix after the position, and that an iterator recreated from the
+/// position of another one returns the same entries as it.
+fn prop_iter_at() {
+    let prop = |inputs: Vec<(Vec<u8>, Value)>,
+                prefix_len: usize,
+                after: Vec<u8>,
+                steps: usize|
+     -> anyhow::Result<()> {
+        let reference = inputs.iter().cloned().collect::<BTreeMap<_, _>>();
+        let prefix = inputs
+            .first()
+            .map(|(k, _)| k[..prefix_len % (k.len() + 1)].to_vec())
+            .unwrap_or_default();
+        let mut candidates = vec![after];
+        if let Some((k, _)) = inputs.get(steps % (inputs.len() + 1)) {
+            candidates.push(k.clone());
+            candidates.push([&k[..], &[0]].concat());
+            candidates.push(k[..k.len().saturating_sub(1)].to_vec());
+        }
+        let (mut trie, mut loader) = make_mut_trie(inputs);
+        for after in candidates {
+            let iterator = trie.iter_at(&mut loader, &prefix, IteratorPosition::After(&after))?;
+            let expected = reference
+                .keys()
+                .filter(|k| k.starts_with(&prefix) && **k > after)
+                .cloned()
+                .collect::<Vec<_>>();
+            ensure!(
+                remaining_keys(&mut trie, &mut loader, iterator) == expected,
+                "Entries after {:?} differ from the reference.",
+                after
+            );
+        }
+        if let Some(mut iterator) = trie.iter(&mut loader, &prefix)? {
+            for _ in 0..steps % (reference.len() + 2) {
+                trie.next(&mut loader, &mut iterator, &mut EmptyCounter)
+                    .expect("Empty counter does not fail.");
+            }
+            let resumed = trie.iter_at(&mut loader, &prefix, iterator.position())?;
+            // The position of a resumed iterator is the one it was created at.
+            let resumed = match resumed {
+                Some(resumed) => trie.iter_at(&mut loader, &prefix, resumed.position())?,
+                None => None,
+            };
+            let resumed = remaining_keys(&mut trie, &mut loader, resumed);
+            ensure!(
+                remaining_keys(&mut trie, &mut loader, Some(iterator)) == resumed,
+                "The resumed iterator returns different entries."
+            );
+        }
+        Ok(())
+    };
+    QuickCheck::new()
+        .tests(NUM_TESTS)
+        .quickcheck(prop as fn(Vec<_>, _, _, _) -> anyhow::Result<()>);
+}
+
+#[test]
+/// Check that compacting a store that contains several versions of the state
+/// retains the latest version, and does not increase the size of the store.
+fn prop_compact_preserves_state() {
+    let prop = |inputs: Vec<(Vec<u8>, Value)>,
+                updates: Vec<(Vec<u8>, Value)>|
+     -> anyhow::Result<()> {
+        let mut reference = inputs.iter().cloned().collect::<BTreeMap<_, _>>();
+        let (trie, mut loader) = make_mut_trie(inputs);
+        let mut store = Vec::new();
+        let mut state: PersistentState = match trie.freeze(&mut loader, &mut EmptyCollector) {
+            Some(node) => node.into(),
+            None => PersistentState::Empty,
+        };
+        // store the initial version, which will be garbage after the update.
+        state.store_update(&mut store).expect("Storing the initial state should succeed.");
+        let mut mutable = state.thaw();
+        {
+            let inner = mutable.get_inner(&mut loader);
+            let mut trie = inner.lock();
+            for (k, v) in updates {
+                trie.insert(&mut loader, &k, v.clone()).expect("No iterators, so insert succeeds.");
+                reference.insert(k, v);
+            }
+        }
+        let mut new_state = mutable.freeze(&mut loader, &mut EmptyCollector);
+        let root =
+            new_state.store_update(&mut store).expect("Storing the new state should succeed.");
+        let stats = compact(&mut store, root)?;
+        ensure!(
+            stats.compacted_size <= stats.original_size,
+            "Compaction increased the size of the store: {} > {}.",
+            stats.compacted_size,
+            stats.original_size
+        );
+        let mut new_loader = Loader::new(&store[..]);
+        let compacted = PersistentState::load_from_location(&mut new_loader, stats.new_root)?;
+        for (k, v) in reference.iter() {
+            ensure!(
+                compacted.lookup(&mut new_loader, k).as_ref() == Some(v),
+                "Value at key {:?} does not match after compaction.",
+                k
+            );
+        }
+        Ok(())
+    };
+    QuickCheck::new().tests(NUM_TESTS / 10).quickcheck(prop as fn(_, _) -> anyhow::Result<()>);
+}
+
+#[test]
+/// Check that nodes are only loaded from the backing store the first time they
+/// are accessed.
+fn test_load_counter_counts_cold_loads() {
+    let (trie, mut loader) =
+        make_mut_trie(vec![(&b"abc"[..], vec![1u8]), (&b"abd"[..], vec![2u8])]);
+    let mut state: PersistentState = trie
+        .freeze(&mut loader, &mut EmptyCollector)
+        .expect("The trie is not empty, so freezing produces a root.")
+        .into();
+    let mut store = Vec::new();
+    let root = state.store_update(&mut store).expect("Storing to a vector should succeed.");
+    let mut counter = LoadCounter::new(Loader::new(&store[..]));
+    let loaded = PersistentState::load_from_location(&mut counter, root)
+        .expect("The state was just stored.");
+    let mut mutable = loaded.thaw();
+    let inner = mutable.get_inner(&mut counter);
+    let mut trie = inner.lock();
+    assert!(trie.get_entry(&mut counter, b"abc").is_some(), "The entry should exist.");
+    let cold = counter.loads;
+    assert!(cold > 0, "The first access should load nodes from the backing store.");
+    assert!(trie.get_entry(&mut counter, b"abc").is_some(), "The entry should exist.");
+    assert_eq!(counter.loads, cold, "Repeated access should not load any nodes.");
+}
+
+#[test]
+/// Check that streaming the state to a writer produces the same output as
+/// storing it in a vector, and that it can be loaded incrementally from the
+/// written bytes.
+fn test_store_update_stream() -> anyhow::Result<()> {
+    let contents = vec![
+        (b"abc".to_vec(), vec![1u8]),
+        (b"abd".to_vec(), vec![7u8; 100]),
+        (b"b".to_vec(), vec![2u8; 10]),
+    ];
+    let freeze = || -> PersistentState {
+        let (trie, mut loader) = make_mut_trie(contents.clone());
+        trie.freeze(&mut loader, &mut EmptyCollector)
+            .expect("The trie is not empty, so freezing produces a root.")
+            .into()
+    };
+    let mut store = Vec::new();
+    let root = freeze().store_update(&mut store)?;
+    let mut out = Vec::new();
+    let streamed_root = freeze().store_update_stream(&mut out)?;
+    ensure!(root == streamed_root, "The roots should be at the same location.");
+    ensure!(store == out, "The streamed output should be the same as the stored one.");
+
+    let mut loader = StreamLoader::new(std::io::Cursor::new(&out))?;
+    let loaded = PersistentState::load_from_location(&mut loader, streamed_root)?;
+    for (key, value) in contents.iter() {
+        ensure!(
+            loaded.lookup(&mut loader, key).as_ref() == Some(value),
+            "The value of {:?} should be loaded.",
+            key
+        );
+    }
+    ensure!(loaded.lookup(&mut loader, b"c").is_none(), "There should be no other entries.");
+    ensure!(
+        matches!(
+            loader.load_raw(Reference::from(out.len() as u64 - 4)),
+            Err(LoadError::OutOfBoundsRead) | Err(LoadError::IOError(_))
+        ),
+        "Reading past the end should fail."
+    );
+    // A length that exceeds the source is rejected before reading the data.
+    let mut corrupted = u64::MAX.to_be_bytes().to_vec();
+    corrupted.extend_from_slice(&out);
+    let mut loader = StreamLoader::new(std::io::Cursor::new(&corrupted))?;
+    ensure!(
+        matches!(loader.load_raw(Reference::from(0)), Err(LoadError::OutOfBoundsRead)),
+        "The corrupted length should be rejected."
+    );
+    Ok(())
+}
+
+#[test]
+/// Check that saved states can be loaded, and that corrupted state files are
+/// rejected with the appropriate error.
+fn test_state_file_header() -> anyhow::Result<()> {
+    let large_value = vec![7u8; 100];
+    let (trie, mut loader) = make_mut_trie(vec![
+        (&b"abc"[..], vec![1u8]),
+        (&b"abd"[..], large_value.clone()),
+        (&b"b"[..], vec![2u8; 10]),
+    ]);
+    let state: PersistentState = trie
+        .freeze(&mut loader, &mut EmptyCollector)
+        .expect("The trie is not empty, so freezing produces a root.")
+        .into();
+    let mut bytes = Vec::new();
+    let hash = state.save(&mut loader, &mut bytes)?;
+    ensure!(hash == state.hash(&mut loader), "Incorrect hash returned by save.");
+    let loaded = PersistentState::load_saved(&mut &bytes[..])?;
+    ensure!(loaded.hash(&mut loader) == hash, "Hash of the loaded state differs.");
+    ensure!(
+        loaded.lookup(&mut loader, b"abd") == Some(large_value.clone()),
+        "Loaded state has incorrect contents."
+    );
+
+    let mut empty_bytes = Vec::new();
+    PersistentState::Empty.save(&mut loader, &mut empty_bytes)?;
+    ensure!(
+        matches!(PersistentState::load_saved(&mut &empty_bytes[..])?, PersistentState::Empty),
+        "The empty state should be loaded as empty."
+    );
+
+    let expect_error = |data: &[u8], expected: StateFileError| -> anyhow::Result<()> {
+        match PersistentState::load_saved(&mut &data[..]) {
+            Ok(_) => bail!("Loading should fail with {}", expected),
+            Err(e) => ensure!(
+                e.downcast_ref::<StateFileError>() == Some(&expected),
+                "Expected {}, got {}",
+                expected,
+                e
+            ),
+        }
+        Ok(())
+    };
+    let body_len = (bytes.len() - StateFileHeader::SIZE) as u64;
+    expect_error(&bytes[..10], StateFileError::TruncatedHeader)?;
+    let mut wrong_magic = bytes.clone();
+    wrong_magic[0] ^= 1;
+    expect_error(&wrong_magic, StateFileError::IncorrectMagic)?;
+    let mut wrong_version = bytes.clone();
+    wrong_version[5] = 2;
+    expect_error(&wrong_version, StateFileError::UnsupportedVersion {
+        version: 2,
+    })?;
+    expect_error(&bytes[..bytes.len() - 1], StateFileError::LengthMismatch {
+        expected: body_len,
+        actual:   body_len - 1,
+    })?;
+    let mut corrupted = bytes.clone();
+    let value_pos = corrupted
+        .windows(large_value.len())
+        .position(|w| w == &large_value[..])
+        .context("The value should be in the serialized state.")?;
+    corrupted[value_pos] ^= 1;
+    expect_error(&corrupted, StateFileError::InconsistentHashes)?;
+    let mut wrong_hash = bytes.clone();
+    wrong_hash[6] ^= 1;
+    let mut expected = [0u8; 32];
+    expected.copy_from_slice(&wrong_hash[6..38]);
+    expect_error(&wrong_hash, StateFileError::HashMismatch {
+        expected: expected.into(),
+        actual:   hash,
+    })?;
+    Ok(())
+}
+
+#[cfg(feature = "compression")]
+#[test]
+/// Check that compressed state files are smaller and are loaded transparently.
+fn test_compressed_state_file() -> anyhow::Result<()> {
+    let (trie, mut loader) =
+        make_mut_trie(vec![(&b"abc"[..], vec![1u8; 100]), (&b"abd"[..], vec![2u8; 100])]);
+    let state: PersistentState = trie
+        .freeze(&mut loader, &mut EmptyCollector)
+        .expect("The trie is not empty, so freezing produces a root.")
+        .into();
+    let mut plain = Vec::new();
+    state.save(&mut loader, &mut plain)?;
+    let mut compressed = Vec::new();
+    let hash = state.save_compressed(&mut loader, &mut compressed)?;
+    ensure!(compressed.len() < plain.len(), "The state file should be compressed.");
+    let loaded = PersistentState::load_saved(&mut &compressed[..])?;
+    ensure!(loaded.hash(&mut loader) == hash, "Hash of the loaded state differs.");
+    ensure!(
+        PersistentState::load_saved(&mut &compressed[..compressed.len() - 1]).is_err(),
+        "A truncated compressed file should be rejected."
+    );
+    Ok(())
+}
+
+#[cfg(not(feature = "compression"))]
+#[test]
+/// Check that compressed state files are rejected if compression is not
+/// supported.
+fn test_compressed_state_file_not_supported() {
+    use wasm_transform::compression::{CompressionError, MAGIC};
+    let err = PersistentState::load_saved(&mut &MAGIC[..])
+        .expect_err("Compressed files are not supported.");
+    assert!(
+        matches!(err.downcast_ref::<CompressionError>(), Some(CompressionError::NotSupported)),
+        "Unexpected error {}",
+        err
+    );
+}
+
+#[test]
+/// Check that streaming the entries under a prefix of the persistent state
+/// produces exactly the entries of the reference map with that prefix, in
+/// order, and that the traversal stops when requested.
+fn prop_stream_prefix() {
+    let prop = |inputs: Vec<(Vec<u8>, Value)>, prefix_len: usize| -> anyhow::Result<()> {
+        let reference = inputs.iter().cloned().collect::<BTreeMap<_, _>>();
+        let prefix = inputs
+            .first()
+            .map(|(k, _)| k[..prefix_len % (k.len() + 1)].to_vec())
+            .unwrap_or_default();
+        let (trie, mut loader) = make_mut_trie(inputs);
+        let state: PersistentState = match trie.freeze(&mut loader, &mut EmptyCollector) {
+            Some(root) => root.into(),
+            None => PersistentState::Empty,
+        };
+        let expected = reference
+            .iter()
+            .filter(|(k, _)| k.starts_with(&prefix))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect::<Vec<_>>();
+        let mut streamed = Vec::new();
+        let result = state.stream_prefix(&mut loader, &prefix, &mut |k, v| {
+            streamed.push((k.to_vec(), v.to_vec()));
+            std::ops::ControlFlow::Continue(())
+        });
+        ensure!(result.is_continue(), "The traversal should not stop.");
+        ensure!(streamed == expected, "Streamed entries differ from the reference.");
+        let mut count = 0;
+        let result = state.stream_prefix(&mut loader, &prefix, &mut |_, _| {
+            count += 1;
+            std::ops::ControlFlow::Break(())
+        });
+        ensure!(
+            result.is_break() == !expected.is_empty() && count == expected.len().min(1),
+            "The traversal should stop after the first entry."
+        );
+        Ok(())
+    };
+    QuickCheck::new().tests(NUM_TESTS).quickcheck(prop as fn(Vec<_>, _) -> anyhow::Result<()>);
+}
+
+#[test]
+/// Check that rolling back to a checkpoint undoes all modifications made
+/// after it, and that checkpoints created after the one rolled back to are
+/// invalidated.
+fn prop_checkpoint_rollback() {
+    let prop = |inputs: Vec<(Vec<u8>, Value)>,
+                before: Vec<(Vec<u8>, Value)>,
+                after: Vec<(Vec<u8>, Value)>|
+     -> anyhow::Result<()> {
+        let mut reference = inputs.iter().cloned().collect::<BTreeMap<_, _>>();
+        let (trie, mut loader) = make_mut_trie(inputs);
+        let state: PersistentState = match trie.freeze(&mut loader, &mut EmptyCollector) {
+            Some(node) => node.into(),
+            None => PersistentState::Empty,
+        };
+        let mut mutable = state.thaw();
+        {
+            let inner = mutable.get_inner(&mut loader);
+            let mut trie = inner.lock();
+            for (k, v) in before {
+                trie.insert(&mut loader, &k, v.clone()).expect("No iterators, so insert succeeds.");
+                reference.insert(k, v);
+            }
+        }
+        let checkpoint = mutable.checkpoint(&mut loader);
+        {
+            let inner = mutable.get_inner(&mut loader);
+            let mut trie = inner.lock();
+            for (k, v) in after {
+                trie.insert(&mut loader, &k, v).expect("No iterators, so insert succeeds.");
+            }
+            for k in reference.keys() {
+                trie.delete(&mut loader, k).expect("No iterators, so delete succeeds.");
+            }
+        }
+        let nested = mutable.checkpoint(&mut loader);
+        mutable.rollback_to(checkpoint)?;
+        ensure!(mutable.rollback_to(nested).is_err(), "Nested checkpoint should be invalid.");
+        let inner = mutable.get_inner(&mut loader);
+        let mut trie = inner.lock();
+        compare_to_reference(&mut trie, &mut loader, &reference)
+    };
+    QuickCheck::new().tests(NUM_TESTS).quickcheck(prop as fn(Vec<_>, _, _) -> _);
+}
+
+#[test]
+/// Check that a checkpoint invalidated by a rollback is not accepted once the
+/// state again has as many generations as when the checkpoint was created,
+/// while nested checkpoints can be rolled back innermost first.
+fn test_stale_checkpoint() -> anyhow::Result<()> {
+    let mut loader = Loader {
+        inner: Vec::<u8>::new(),
+    };
+    let mut mutable = MutableState::initial_state();
+    let c1 = mutable.checkpoint(&mut loader);
+    let c2 = mutable.checkpoint(&mut loader);
+    mutable.rollback_to(c1)?;
+    let c3 = mutable.checkpoint(&mut loader);
+    let c4 = mutable.checkpoint(&mut loader);
+    ensure!(mutable.rollback_to(c2).is_err(), "The checkpoint was invalidated by the rollback.");
+    mutable.rollback_to(c4)?;
+    mutable.rollback_to(c3)?;
+    Ok(())
+}
+
+#[test]
+/// Check that the statistics of a state are consistent with its contents, and
+/// that they do not depend on whether the state is stored.
+fn prop_statistics() {
+    let prop = |inputs: Vec<(Vec<u8>, Value)>| -> anyhow::Result<()> {
+        let reference = inputs.iter().cloned().collect::<BTreeMap<_, _>>();
+        let (trie, mut loader) = make_mut_trie(inputs);
+        let mut state: PersistentState = match trie.freeze(&mut loader, &mut EmptyCollector) {
+            Some(root) => root.into(),
+            None => PersistentState::Empty,
+        };
+        let stats = state.statistics(&mut loader);
+        ensure!(stats.values() == reference.len() as u64, "Incorrect number of values.");
+        ensure!(
+            stats.total_value_size == reference.values().map(|v| v.len() as u64).sum::<u64>(),
+            "Incorrect total value size."
+        );
+        ensure!(
+            stats.value_size_histogram.iter().sum::<u64>() == stats.values(),
+            "The value size histogram should count every value."
+        );
+        ensure!(
+            stats.depth_histogram.iter().sum::<u64>() == stats.nodes(),
+            "The depth histogram should count every node."
+        );
+        ensure!(
+            stats.depth_histogram.first().map_or(reference.is_empty(), |&n| n == 1),
+            "There should be exactly one root."
+        );
+        ensure!(
+            stats.branch_nodes < stats.values().max(1),
+            "Branches without values should have at least two children."
+        );
+        let mut store = Vec::new();
+        let root = state.store_update(&mut store)?;
+        let mut store_loader = Loader::new(&store[..]);
+        let loaded = PersistentState::load_from_location(&mut store_loader, root)?;
+        ensure!(
+            loaded.statistics(&mut store_loader) == stats,
+            "Storing the state should not change the statistics."
+        );
+        Ok(())
+    };
+    QuickCheck::new().tests(NUM_TESTS).quickcheck(prop as fn(Vec<_>) -> anyhow::Result<()>);
+}
+
+#[test]
+/// Check that the diff of two states consists of exactly the entries that
+/// differ between the reference maps, in order, and that it is empty for equal
+/// states.
+fn prop_diff() {
+    let prop = |inputs: Vec<(Vec<u8>, Value)>,
+                changes: Vec<(Vec<u8>, Option<Value>)>|
+     -> anyhow::Result<()> {
+        let old_reference = inputs.iter().cloned().collect::<BTreeMap<_, _>>();
+        let mut new_reference = old_reference.clone();
+        let (trie, mut loader) = make_mut_trie(inputs);
+        let old: PersistentState = match trie.freeze(&mut loader, &mut EmptyCollector) {
+            Some(root) => root.into(),
+            None => PersistentState::Empty,
+        };
+        let mut mutable = old.thaw();
+        {
+            let inner = mutable.get_inner(&mut loader);
+            let mut trie = inner.lock();
+            for (k, v) in changes {
+                match v {
+                    Some(v) => {
+                        trie.insert(&mut loader, &k, v.clone())
+                            .expect("No iterators, so insert succeeds.");
+                        new_reference.insert(k, v);
+                    }
+                    None => {
+                        trie.delete(&mut loader, &k).expect("No iterators, so delete succeeds.");
+                        new_reference.remove(&k);
+                    }
+                }
+            }
+        }
+        let new = mutable.freeze(&mut loader, &mut EmptyCollector);
+        let mut expected = Vec::new();
+        for (k, v) in old_reference.iter() {
+            match new_reference.get(k) {
+                None => expected.push(DiffEntry::Removed {
+                    key:   k.clone(),
+                    value: v.clone(),
+                }),
+                Some(nv) if nv != v => expected.push(DiffEntry::Modified {
+                    key: k.clone(),
+                    old: v.clone(),
+                    new: nv.clone(),
+                }),
+                Some(_) => (),
+            }
+        }
+        for (k, v) in new_reference.iter() {
+            if !old_reference.contains_key(k) {
+                expected.push(DiffEntry::Added {
+                    key:   k.clone(),
+                    value: v.clone(),
+                });
+            }
+        }
+        expected.sort_by(|a, b| a.key().cmp(b.key()));
+        ensure!(old.diff(&new, &mut loader) == expected, "Diff differs from the reference.");
+        ensure!(old.diff(&old, &mut loader).is_empty(), "Diff of equal states is not empty.");
+        Ok(())
+    };
+    QuickCheck::new().tests(NUM_TESTS).quickcheck(prop as fn(Vec<_>, Vec<_>) -> anyhow::Result<()>);
+}
+
+#[test]
+/// Check that computing the diff of two stored states does not load the parts
+/// that are the same in both.
+fn test_diff_skips_unchanged() -> anyhow::Result<()> {
+    let inputs = (0u32..1000).map(|i| (i.to_be_bytes(), i.to_le_bytes().to_vec())).collect();
+    let (trie, mut loader) = make_mut_trie(inputs);
+    let mut old: PersistentState = trie
+        .freeze(&mut loader, &mut EmptyCollector)
+        .expect("The trie is not empty, so freezing produces a root.")
+        .into();
+    let mut mutable = old.thaw();
+    mutable.get_inner(&mut loader).lock().insert(&mut loader, &500u32.to_be_bytes(), vec![1])?;
+    let mut new = mutable.freeze(&mut loader, &mut EmptyCollector);
+    let mut store = Vec::new();
+    let old_root = old.store_update(&mut store)?;
+    let new_root = new.store_update(&mut store)?;
+    let mut counter = LoadCounter::new(Loader::new(&store[..]));
+    let old = PersistentState::load_from_location(&mut counter, old_root)?;
+    let new = PersistentState::load_from_location(&mut counter, new_root)?;
+    let diff = old.diff(&new, &mut counter);
+    ensure!(
+        diff == vec![DiffEntry::Modified {
+            key: 500u32.to_be_bytes().to_vec(),
+            old: 500u32.to_le_bytes().to_vec(),
+            new: vec![1],
+        }],
+        "Unexpected diff {:?}",
+        diff
+    );
+    let nodes = old.statistics(&mut Loader::new(&store[..])).nodes();
+    ensure!(
+        counter.loads < nodes / 10,
+        "The diff loaded {} nodes out of {}.",
+        counter.loads,
+        nodes
+    );
+    Ok(())
+}
+
+/// An asynchronous backing store that serves the data stored in a vector after
+/// the given delay.
+#[cfg(feature = "async-store")]
+struct DelayedStore {
+    store: std::sync::Arc<Vec<u8>>,
+    delay: std::time::Duration,
+}
+
+#[cfg(feature = "async-store")]
+impl AsyncBackingStoreLoad for DelayedStore {
+    type Fut = std::pin::Pin<Box<dyn std::future::Future<Output = LoadResult<Self::R>>>>;
+    type R = tinyvec::TinyVec<[u8; 28]>;
+
+    fn load_raw_async(&self, location: Reference) -> Self::Fut {
+        let store = self.store.clone();
+        let delay = self.delay;
+        Box::pin(async move {
+            tokio::time::sleep(delay).await;
+            Loader::new(&store[..]).load_raw(location)
+        })
+    }
+}
+
+/// Run the function with a [BlockingLoader] that loads from the store. As
+/// required by the loader, the function runs on a blocking thread of the
+/// runtime, while the runtime is driven by the current thread.
+#[cfg(feature = "async-store")]
+fn with_blocking_loader<A: Send + 'static>(
+    store: Vec<u8>,
+    delay: std::time::Duration,
+    timeout: std::time::Duration,
+    f: impl FnOnce(&mut BlockingLoader<DelayedStore>) -> A + Send + 'static,
+) -> A {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .expect("Constructing the runtime should succeed.");
+    let store = DelayedStore {
+        store: std::sync::Arc::new(store),
+        delay,
+    };
+    let mut loader = BlockingLoader::new(store, runtime.handle().clone(), timeout);
+    runtime
+        .block_on(tokio::task::spawn_blocking(move || f(&mut loader)))
+        .expect("The blocking task should not panic.")
+}
+
+#[cfg(feature = "async-store")]
+#[test]
+/// Check that a stored state can be loaded from an asynchronous backing store.
+fn test_async_store_round_trip() -> anyhow::Result<()> {
+    let contents = vec![
+        (b"abc".to_vec(), vec![1u8]),
+        (b"abd".to_vec(), vec![7u8; 100]),
+        (b"b".to_vec(), vec![2u8; 10]),
+    ];
+    let (trie, mut loader) = make_mut_trie(contents.clone());
+    let mut state: PersistentState = trie
+        .freeze(&mut loader, &mut EmptyCollector)
+        .expect("The trie is not empty, so freezing produces a root.")
+        .into();
+    let hash = state.hash(&mut loader);
+    let mut store = Vec::new();
+    let root = state.store_update(&mut store)?;
+    let delay = std::time::Duration::from_millis(1);
+    let timeout = std::time::Duration::from_secs(60);
+    with_blocking_loader(store, delay, timeout, move |loader| {
+        let loaded = PersistentState::load_from_location(loader, root)?;
+        ensure!(loaded.hash(loader) == hash, "Hash of the loaded state differs.");
+        for (key, value) in contents.iter() {
+            ensure!(
+                loaded.lookup(loader, key).as_ref() == Some(value),
+                "The value of {:?} should be loaded.",
+                key
+            );
+        }
+        ensure!(loaded.lookup(loader, b"c").is_none(), "There should be no other entries.");
+        Ok(())
+    })
+}
+
+#[cfg(feature = "async-store")]
+#[test]
+/// Check that a load that does not complete in time is reported as a timeout.
+fn test_async_store_timeout() -> anyhow::Result<()> {
+    let mut store = Vec::new();
+    let location = store.store_raw(b"abc")?;
+    let delay = std::time::Duration::from_secs(60);
+    let timeout = std::time::Duration::from_millis(10);
+    let result = with_blocking_loader(store, delay, timeout, move |loader| {
+        loader.load_raw(location).map(|data| data.to_vec())
+    });
+    match result {
+        Err(LoadError::IOError(e)) if e.kind() == std::io::ErrorKind::TimedOut => Ok(()),
+        other => bail!("Expected the load to time out, but got {:?}.", other),
+    }
+}
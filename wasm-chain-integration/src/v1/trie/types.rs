@@ -165,6 +165,53 @@ impl<V: AsRef<[u8]>> Collector<V> for SizeCollector {
     }
 }
 
+/// The work done when freezing a mutable state, collected either by freezing
+/// it, or in advance by
+/// [MutableState::freeze_cost](super::MutableState::freeze_cost).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FreezeCost {
+    /// Number of nodes that are created, i.e., that are new or modified.
+    pub nodes_created:  u64,
+    /// Number of values that are new or modified.
+    pub values_written: u64,
+    /// Number of bytes that are hashed to compute the hashes of the new nodes
+    /// and values.
+    pub bytes_hashed:   u64,
+}
+
+impl<V: AsRef<[u8]>> Collector<V> for FreezeCost {
+    #[inline]
+    fn add_value(&mut self, data: &V) {
+        self.values_written += 1;
+        // The value itself, and its hash as part of the hash of its node.
+        self.bytes_hashed += data.as_ref().len() as u64 + 32;
+    }
+
+    #[inline]
+    fn add_path(&mut self, path: usize) {
+        self.nodes_created += 1;
+        // The tag of the value, the length of the stem, the stem packed two
+        // chunks per byte, and the hash of the children.
+        self.bytes_hashed += 1 + 8 + (path as u64 + 1) / 2 + 32;
+    }
+
+    #[inline]
+    fn add_children(&mut self, num_children: usize) {
+        // The number of children, and the key and hash of each of them.
+        self.bytes_hashed += 2 + (num_children as u64) * (1 + 32);
+    }
+}
+
+impl std::fmt::Display for FreezeCost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} nodes created, {} values written, {} bytes hashed",
+            self.nodes_created, self.values_written, self.bytes_hashed
+        )
+    }
+}
+
 /// Trait implemented by types that can be used to store binary data, and return
 /// a handle for loading data.
 pub trait BackingStoreStore {
@@ -243,6 +290,115 @@ impl<A: AsRef<[u8]>> BackingStoreLoad for Loader<A> {
     }
 }
 
+/// A [BackingStoreStore] that writes the stored data to any [Write], e.g., a
+/// buffered file, as it is stored, instead of accumulating it in memory. The
+/// data is framed as by the implementation for `Vec<u8>`, and references are
+/// offsets from the first byte written, so the output can be loaded with either
+/// [Loader] or [StreamLoader].
+#[derive(Debug)]
+pub struct StreamStorer<W> {
+    pub inner: W,
+    /// The number of bytes written so far.
+    position:  u64,
+}
+
+impl<W> StreamStorer<W> {
+    /// Construct a new storer that writes to the given output.
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            position: 0,
+        }
+    }
+
+    /// The number of bytes written so far.
+    pub fn position(&self) -> u64 { self.position }
+}
+
+impl<W: Write> BackingStoreStore for StreamStorer<W> {
+    fn store_raw(&mut self, data: &[u8]) -> Result<Reference, WriteError> {
+        let pos = self.position;
+        let data_len = data.len() as u64;
+        self.inner.write_u64::<BigEndian>(data_len)?;
+        self.inner.write_all(data)?;
+        self.position += 8 + data_len;
+        Ok(pos.into())
+    }
+}
+
+/// A [BackingStoreLoad] that reads data in the format written by
+/// [StreamStorer] on demand from any source that supports [Seek], e.g., a
+/// buffered file. In contrast to [Loader] the source is not kept in memory,
+/// only the data that is loaded is read from it.
+#[derive(Debug)]
+pub struct StreamLoader<R> {
+    pub inner: R,
+    /// The length of the source, which bounds the locations that are read.
+    len:       u64,
+}
+
+impl<R: Seek> StreamLoader<R> {
+    /// Construct a new loader from the given source, which must contain the
+    /// data at the offsets at which it was stored.
+    pub fn new(mut inner: R) -> std::io::Result<Self> {
+        let len = inner.seek(SeekFrom::End(0))?;
+        Ok(Self {
+            inner,
+            len,
+        })
+    }
+}
+
+impl<R: Read + Seek> BackingStoreLoad for StreamLoader<R> {
+    type R = tinyvec::TinyVec<[u8; 28]>;
+
+    fn load_raw(&mut self, location: Reference) -> LoadResult<Self::R> {
+        let pos = self.inner.seek(SeekFrom::Start(location.into()))?;
+        let len = self.inner.read_u64::<BigEndian>()?;
+        // Check the length before allocating, in case the source is corrupted.
+        match pos.checked_add(8).and_then(|start| start.checked_add(len)) {
+            Some(end) if end <= self.len => {
+                let mut data = Self::R::new();
+                data.resize(len as usize, 0u8);
+                self.inner.read_exact(&mut data)?;
+                Ok(data)
+            }
+            _ => Err(LoadError::OutOfBoundsRead),
+        }
+    }
+}
+
+/// A wrapper around a [BackingStoreLoad] that counts the number of loads. Since
+/// the trie only loads a node from the backing store the first time it is
+/// accessed, this is the number of accesses to nodes that were not yet in
+/// memory.
+#[derive(Debug, Clone)]
+pub struct LoadCounter<L> {
+    pub inner: L,
+    /// The number of loads performed so far.
+    pub loads: u64,
+}
+
+impl<L> LoadCounter<L> {
+    /// Wrap the given loader. The count starts at 0.
+    pub fn new(inner: L) -> Self {
+        Self {
+            inner,
+            loads: 0,
+        }
+    }
+}
+
+impl<L: BackingStoreLoad> BackingStoreLoad for LoadCounter<L> {
+    type R = L::R;
+
+    #[inline(always)]
+    fn load_raw(&mut self, location: Reference) -> LoadResult<Self::R> {
+        self.loads += 1;
+        self.inner.load_raw(location)
+    }
+}
+
 /// A trait implemented by types that can be loaded from a [BackingStoreLoad]
 /// storage.
 pub trait Loadable: Sized {
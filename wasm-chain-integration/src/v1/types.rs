@@ -1,16 +1,24 @@
 use super::{
     trie::{self, MutableState},
-    Interrupt, ParameterVec, StateLessReceiveHost,
+    Interrupt, ParameterVec, PendingQuery, StateLessReceiveHost,
+};
+use crate::{
+    constants::{self, CostTable},
+    reject::RejectReason,
+    resumption::InterruptedState,
+    type_matches, v0, InterpreterEnergy,
 };
-use crate::{constants, resumption::InterruptedState, type_matches, v0, InterpreterEnergy};
 use anyhow::{bail, ensure, Context};
 #[cfg(feature = "fuzz")]
 use arbitrary::Arbitrary;
-use concordium_contracts_common::OwnedEntrypointName;
+use concordium_contracts_common::{
+    to_bytes, Cursor, Get, OwnedEntrypointName, ParseError, ParseResult,
+};
 use derive_more::{From, Into};
-use serde::Deserialize as SerdeDeserialize;
+use serde::{Deserialize as SerdeDeserialize, Serialize as SerdeSerialize};
 use wasm_transform::{
-    artifact::TryFromImport,
+    artifact::{HostInterface, TryFromImport},
+    metering_transformation::MeteringConfig,
     output::Output,
     parse::{Byte, GetParseable, Parseable},
     types::{FunctionType, Import, Name, ValueType},
@@ -22,19 +30,142 @@ pub const MAX_EXPORT_NAME_LEN: usize = 100;
 
 pub type ReturnValue = Vec<u8>;
 
+/// Cursors into the parameters of an execution. A cursor is opened on one of
+/// the parameters, and reading from it advances its position. This allows a
+/// contract to consume a large parameter incrementally without knowing its
+/// size in advance, and without copying all of it into its memory.
+///
+/// Cursors are identified by their index. Operations on an index that does not
+/// refer to an open cursor return `u32::MAX`.
+#[derive(Debug, Default, Clone)]
+pub struct ParameterCursors {
+    cursors: Vec<Option<ParameterCursor>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ParameterCursor {
+    /// Which parameter the cursor reads.
+    param_num: usize,
+    /// Position of the cursor in the parameter. This is at most the length of
+    /// the parameter.
+    position:  usize,
+}
+
+impl ParameterCursors {
+    /// Open a cursor at the start of the given parameter, and return its
+    /// index. Returns `u32::MAX` if the parameter does not exist, or if
+    /// [constants::MAX_PARAMETER_CURSORS] cursors are already open.
+    pub(crate) fn open(&mut self, param_num: u32, parameters: &[impl AsRef<[u8]>]) -> u32 {
+        let param_num = param_num as usize;
+        if param_num >= parameters.len() {
+            return u32::MAX;
+        }
+        let cursor = Some(ParameterCursor {
+            param_num,
+            position: 0,
+        });
+        if let Some(idx) = self.cursors.iter().position(Option::is_none) {
+            self.cursors[idx] = cursor;
+            idx as u32
+        } else if self.cursors.len() < constants::MAX_PARAMETER_CURSORS {
+            self.cursors.push(cursor);
+            (self.cursors.len() - 1) as u32
+        } else {
+            u32::MAX
+        }
+    }
+
+    /// Read from the current position of the cursor into `dest`, and advance
+    /// the cursor by the amount read. Returns how much was read, which is less
+    /// than the length of `dest` if the end of the parameter is reached.
+    pub(crate) fn read(
+        &mut self,
+        cursor: u32,
+        parameters: &[impl AsRef<[u8]>],
+        dest: &mut [u8],
+    ) -> u32 {
+        if let Some(cursor) = self.cursors.get_mut(cursor as usize).and_then(Option::as_mut) {
+            let param = parameters[cursor.param_num].as_ref();
+            let num_copied = std::cmp::min(param.len() - cursor.position, dest.len());
+            dest[0..num_copied]
+                .copy_from_slice(&param[cursor.position..cursor.position + num_copied]);
+            cursor.position += num_copied;
+            num_copied as u32
+        } else {
+            u32::MAX
+        }
+    }
+
+    /// Move the cursor to the given position, and return the new position. If
+    /// the position is beyond the end of the parameter the cursor is moved to
+    /// the end.
+    pub(crate) fn seek(
+        &mut self,
+        cursor: u32,
+        parameters: &[impl AsRef<[u8]>],
+        position: u32,
+    ) -> u32 {
+        if let Some(cursor) = self.cursors.get_mut(cursor as usize).and_then(Option::as_mut) {
+            let len = parameters[cursor.param_num].as_ref().len();
+            cursor.position = std::cmp::min(len, position as usize);
+            cursor.position as u32
+        } else {
+            u32::MAX
+        }
+    }
+
+    /// Close the cursor, freeing its index for reuse. Returns 0 if the cursor
+    /// was open.
+    pub(crate) fn close(&mut self, cursor: u32) -> u32 {
+        if self.cursors.get_mut(cursor as usize).and_then(Option::take).is_some() {
+            0
+        } else {
+            u32::MAX
+        }
+    }
+}
+
 #[derive(Debug)]
-pub enum InitResult {
+/// Result of execution of an init function.
+pub enum InitResult<R, Ctx = v0::InitContext<v0::OwnedPolicyBytes>> {
     Success {
-        logs:             v0::Logs,
-        return_value:     ReturnValue,
-        remaining_energy: u64,
+        logs:                   v0::Logs,
+        return_value:           ReturnValue,
+        remaining_energy:       u64,
         /// Initial state of the contract.
-        state:            MutableState,
+        state:                  MutableState,
+        /// Accesses to the state during execution.
+        state_accesses:         StateAccessCounts,
+        /// Remaining energy for state operations, if they had a separate
+        /// budget.
+        remaining_state_energy: Option<u64>,
+    },
+    /// Execution triggered an operation, e.g., a transfer or a call to another
+    /// contract. This only happens with
+    /// [init_interrupts](HostFeatures::init_interrupts).
+    Interrupt {
+        /// Remaining interpreter energy.
+        remaining_energy:       u64,
+        /// Accesses to the state since the start of the last resume.
+        state_accesses:         StateAccessCounts,
+        /// Remaining energy for state operations, if they had a separate
+        /// budget. The budget is retained in the interrupted state and
+        /// continues to be used when execution is resumed.
+        remaining_state_energy: Option<u64>,
+        /// Logs produced since the last interrupt (or beginning of execution).
+        logs:                   v0::Logs,
+        /// Stored execution state that can be used to resume execution.
+        config:                 Box<InitInterruptedState<R, Ctx>>,
+        /// The operation that needs to be handled.
+        interrupt:              Interrupt,
     },
     Reject {
-        reason:           i32,
-        return_value:     ReturnValue,
-        remaining_energy: u64,
+        reason:                 i32,
+        return_value:           ReturnValue,
+        remaining_energy:       u64,
+        /// Remaining energy for state operations, if they had a separate
+        /// budget.
+        remaining_state_energy: Option<u64>,
     },
     /// Execution stopped due to a runtime error.
     Trap {
@@ -45,45 +176,119 @@ pub enum InitResult {
     OutOfEnergy,
 }
 
-impl InitResult {
+#[cfg(feature = "enable-ffi")]
+/// Data extracted from the [InitResult] in a format suitable to pass to
+/// foreign code via FFI.
+pub(crate) struct InitResultExtract<R> {
+    /// Encoding of the status (i.e., whether it is success, interrupt, ...),
+    /// see [InitResult::extract] for the format.
+    pub status:          Vec<u8>,
+    /// If execution succeeded, this is the initial state of the contract.
+    pub state:           Option<MutableState>,
+    /// If execution triggered an operation, this is the current state of
+    /// execution.
+    pub interrupt_state: Option<Box<InitInterruptedState<R>>>,
+    /// If execution terminated, this is the return value that was produced.
+    pub return_value:    Option<ReturnValue>,
+}
+
+impl<R, Ctx> InitResult<R, Ctx> {
+    /// The reason the contract rejected with, classified into reasons reserved
+    /// for contract libraries and reasons defined by the contract. Returns
+    /// [None] if the contract did not reject.
+    pub fn reject_reason(&self) -> Option<RejectReason> {
+        match self {
+            InitResult::Reject {
+                reason,
+                ..
+            } => Some(RejectReason::from(*reason)),
+            _ => None,
+        }
+    }
+}
+
+impl<R> InitResult<R> {
     /// Extract the result into a byte array and potentially a return value.
     /// This is only meant to be used to pass the return value to foreign code.
     /// When using this from Rust the consumer should inspect the [InitResult]
-    /// enum directly.
+    /// enum directly. The status of an interrupt is encoded in the same way
+    /// as in [ReceiveResult::extract].
     #[cfg(feature = "enable-ffi")]
-    pub(crate) fn extract(self) -> (Vec<u8>, Option<MutableState>, Option<ReturnValue>) {
+    pub(crate) fn extract(self) -> InitResultExtract<R> {
+        use InitResult::*;
         match self {
-            InitResult::OutOfEnergy => (vec![0], None, None),
-            InitResult::Trap {
+            OutOfEnergy => InitResultExtract {
+                status:          vec![0],
+                state:           None,
+                interrupt_state: None,
+                return_value:    None,
+            },
+            Trap {
                 remaining_energy,
                 .. // ignore the error since it is not needed in ffi
             } => {
                 let mut out = vec![1; 9];
                 out[1..].copy_from_slice(&remaining_energy.to_be_bytes());
-                (out, None, None)
+                InitResultExtract {
+                    status:          out,
+                    state:           None,
+                    interrupt_state: None,
+                    return_value:    None,
+                }
             }
-            InitResult::Reject {
+            Reject {
                 reason,
                 return_value,
                 remaining_energy,
+                ..
             } => {
                 let mut out = Vec::with_capacity(13);
                 out.push(2);
                 out.extend_from_slice(&reason.to_be_bytes());
                 out.extend_from_slice(&remaining_energy.to_be_bytes());
-                (out, None, Some(return_value))
+                InitResultExtract {
+                    status:          out,
+                    state:           None,
+                    interrupt_state: None,
+                    return_value:    Some(return_value),
+                }
             }
-            InitResult::Success {
+            Success {
                 logs,
                 return_value,
                 remaining_energy,
                 state,
+                ..
             } => {
-                let mut out = Vec::with_capacity(5 + 8);
+                let mut out = Vec::with_capacity(1 + logs.serialized_size() + 8);
                 out.push(3);
-                out.extend_from_slice(&logs.to_bytes());
+                logs.serialize_into(&mut out).expect("Serialization to a vector never fails.");
+                out.extend_from_slice(&remaining_energy.to_be_bytes());
+                InitResultExtract {
+                    status:          out,
+                    state:           Some(state),
+                    interrupt_state: None,
+                    return_value:    Some(return_value),
+                }
+            }
+            Interrupt {
+                remaining_energy,
+                logs,
+                config,
+                interrupt,
+                ..
+            } => {
+                let mut out = Vec::with_capacity(1 + 8 + logs.serialized_size());
+                out.push(4);
                 out.extend_from_slice(&remaining_energy.to_be_bytes());
-                (out, Some(state), Some(return_value))
+                logs.serialize_into(&mut out).expect("Serialization to a vector never fails.");
+                interrupt.to_bytes(&mut out).expect("Serialization to a vector never fails.");
+                InitResultExtract {
+                    status:          out,
+                    state:           None,
+                    interrupt_state: Some(config),
+                    return_value:    None,
+                }
             }
         }
     }
@@ -106,9 +311,74 @@ pub struct SavedHost<Ctx> {
     /// A list of iterators that were handed out before the handler of the
     /// operation was invoked.
     pub(crate) iterators:          Vec<Option<trie::Iterator>>,
+    /// The costs of state accesses that apply to the execution.
+    pub(crate) access_costs:       StateAccessCosts,
+    /// The costs of host functions that apply to the execution.
+    pub(crate) costs:              CostTable,
+    /// Whether the state is read-only for the execution.
+    pub(crate) read_only:          bool,
+    /// The remaining budget for state operations, if there is a separate one.
+    pub(crate) state_energy:       Option<InterpreterEnergy>,
+    /// The call depth of the execution.
+    pub(crate) call_depth:         CallDepth,
+    /// The behaviour of the host for the execution.
+    pub(crate) host_features:      HostFeatures,
+    /// The seed of the randomness of the execution, if any.
+    pub(crate) random_seed:        Option<v0::RandomSeed>,
+    /// The query that caused the interrupt, if it was caused by a query
+    /// instead of an invoke. The response to a query is returned to the
+    /// contract differently from the response to an invoke.
+    pub(crate) pending_query:      Option<PendingQuery>,
 }
 
-#[derive(SerdeDeserialize, Debug, Clone)]
+#[derive(Debug)]
+/// Host of an init function that is saved between handling of operations.
+/// This is the counterpart of [SavedHost] for init functions. The instance
+/// does not exist until initialization succeeds, so nothing else can modify
+/// its state while the operation is handled. Hence, unlike for receive
+/// functions, the state is retained here as well.
+pub struct SavedInitHost<Ctx> {
+    /// Remaining amount of activation frames.
+    pub(crate) activation_frames:  u32,
+    /// Return value from execution.
+    pub(crate) return_value:       ReturnValue,
+    /// The parameter to the init function, as well as any responses from
+    /// calls to other contracts during execution.
+    pub(crate) parameters:         Vec<ParameterVec>,
+    /// Cursors into the parameters opened by the contract.
+    pub(crate) parameter_cursors:  ParameterCursors,
+    /// Number of blocks of randomness returned by `get_random` so far.
+    pub(crate) random_counter:     u64,
+    /// The init context for this invocation.
+    pub(crate) init_ctx:           Ctx,
+    /// The state of the contract that is being initialized.
+    pub(crate) state:              MutableState,
+    /// Current generation of the state.
+    pub(crate) current_generation: InstanceCounter,
+    /// A list of entries that were handed out before the handler of the
+    /// operation was invoked.
+    pub(crate) entry_mapping:      Vec<trie::EntryId>,
+    /// A list of iterators that were handed out before the handler of the
+    /// operation was invoked.
+    pub(crate) iterators:          Vec<Option<trie::Iterator>>,
+    /// The costs of state accesses that apply to the execution.
+    pub(crate) access_costs:       StateAccessCosts,
+    /// The costs of host functions that apply to the execution.
+    pub(crate) costs:              CostTable,
+    /// The remaining budget for state operations, if there is a separate one.
+    pub(crate) state_energy:       Option<InterpreterEnergy>,
+    /// The call depth of the execution.
+    pub(crate) call_depth:         CallDepth,
+    /// The behaviour of the host for the execution.
+    pub(crate) host_features:      HostFeatures,
+    /// The seed of the randomness of the execution, if any.
+    pub(crate) random_seed:        Option<v0::RandomSeed>,
+    /// The query that caused the interrupt, if it was caused by a query
+    /// instead of an invoke.
+    pub(crate) pending_query:      Option<PendingQuery>,
+}
+
+#[derive(SerdeSerialize, SerdeDeserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ReceiveContext<Policies> {
     #[serde(flatten)]
@@ -126,50 +396,139 @@ impl<'a> From<ReceiveContext<v0::PolicyBytes<'a>>> for ReceiveContext<v0::OwnedP
     }
 }
 
+/// Serialize the receive context in the canonical binary format. This is the
+/// entrypoint name followed by the common part of the context in the format of
+/// [v0::serial_receive_context]. Note that the node does not pass this context
+/// over FFI, it only passes the common part.
+pub fn serial_receive_context<Policies: AsRef<[u8]>>(ctx: &ReceiveContext<Policies>) -> Vec<u8> {
+    let mut out = to_bytes(&ctx.entrypoint);
+    out.extend_from_slice(&v0::serial_receive_context(&ctx.common));
+    out
+}
+
+/// Deserialize the receive context from the canonical binary format, see
+/// [serial_receive_context].
+pub fn deserial_receive_context(source: &[u8]) -> ParseResult<ReceiveContext<&[u8]>> {
+    let mut cursor = Cursor::new(source);
+    let entrypoint = cursor.get()?;
+    let rest = source.get(cursor.offset..).ok_or(ParseError {})?;
+    let common = v0::deserial_receive_context(rest)?;
+    Ok(ReceiveContext {
+        common,
+        entrypoint,
+    })
+}
+
+/// Serialize the receive context for storing, e.g., in a file. This is the
+/// canonical binary format prefixed with
+/// [v0::CONTEXT_SERIALIZATION_VERSION].
+pub fn serial_versioned_receive_context<Policies: AsRef<[u8]>>(
+    ctx: &ReceiveContext<Policies>,
+) -> Vec<u8> {
+    let mut out = vec![v0::CONTEXT_SERIALIZATION_VERSION];
+    out.extend_from_slice(&serial_receive_context(ctx));
+    out
+}
+
+/// Deserialize a receive context written by
+/// [serial_versioned_receive_context], failing if it was written with an
+/// unsupported version.
+pub fn deserial_versioned_receive_context(source: &[u8]) -> anyhow::Result<ReceiveContext<&[u8]>> {
+    let rest = v0::check_context_version(source)?;
+    deserial_receive_context(rest).map_err(|_| anyhow::anyhow!("Malformed receive context."))
+}
+
 /// State of the suspended execution of the receive function.
 /// This retains both the module that is executed, as well the host.
 pub type ReceiveInterruptedState<R, Ctx = ReceiveContext<v0::OwnedPolicyBytes>> =
     InterruptedState<ProcessedImports, R, SavedHost<Ctx>>;
 
+impl<R, Ctx> ReceiveInterruptedState<R, Ctx> {
+    /// The call depth at which a contract called to handle the interrupt
+    /// should be executed. The call has already been checked against the
+    /// limit when execution was interrupted.
+    pub fn nested_call_depth(&self) -> CallDepth {
+        let call_depth = self.host.call_depth;
+        CallDepth {
+            depth: call_depth.depth.saturating_add(1),
+            limit: call_depth.limit,
+        }
+    }
+}
+
+/// State of the suspended execution of an init function.
+/// This retains both the module that is executed, as well the host.
+pub type InitInterruptedState<R, Ctx = v0::InitContext<v0::OwnedPolicyBytes>> =
+    InterruptedState<ProcessedImports, R, SavedInitHost<Ctx>>;
+
+impl<R, Ctx> InitInterruptedState<R, Ctx> {
+    /// The call depth at which a contract called to handle the interrupt
+    /// should be executed, see [ReceiveInterruptedState::nested_call_depth].
+    pub fn nested_call_depth(&self) -> CallDepth {
+        let call_depth = self.host.call_depth;
+        CallDepth {
+            depth: call_depth.depth.saturating_add(1),
+            limit: call_depth.limit,
+        }
+    }
+}
+
 #[derive(Debug)]
 /// Result of execution of a receive function.
 pub enum ReceiveResult<R, Ctx = ReceiveContext<v0::OwnedPolicyBytes>> {
     /// Execution terminated.
     Success {
         /// Logs produced since the last interrupt (or beginning of execution).
-        logs:             v0::Logs,
+        logs:                   v0::Logs,
         /// Whether the state has changed as a result of execution. Note that
         /// the meaning of this is "since the start of the last resume".
-        state_changed:    bool,
+        state_changed:          bool,
         /// Return value that was produced. There is always a return value,
         /// although it might be empty.
-        return_value:     ReturnValue,
+        return_value:           ReturnValue,
         /// Remaining interpreter energy.
-        remaining_energy: u64,
+        remaining_energy:       u64,
+        /// Accesses to the state since the start of the last resume.
+        state_accesses:         StateAccessCounts,
+        /// Remaining energy for state operations, if they had a separate
+        /// budget.
+        remaining_state_energy: Option<u64>,
+        /// The work that freezing the resulting state will do. This is not
+        /// charged to the execution.
+        freeze_cost:            trie::FreezeCost,
     },
     /// Execution triggered an operation.
     Interrupt {
         /// Remaining interpreter energy.
-        remaining_energy: u64,
+        remaining_energy:       u64,
         /// Whether the state has changed as a result of execution. Note that
         /// the meaning of this is "since the start of the last resume".
-        state_changed:    bool,
+        state_changed:          bool,
+        /// Accesses to the state since the start of the last resume.
+        state_accesses:         StateAccessCounts,
+        /// Remaining energy for state operations, if they had a separate
+        /// budget. The budget is retained in the interrupted state and
+        /// continues to be used when execution is resumed.
+        remaining_state_energy: Option<u64>,
         /// Logs produced since the last interrupt (or beginning of execution).
-        logs:             v0::Logs,
+        logs:                   v0::Logs,
         /// Stored execution state that can be used to resume execution.
-        config:           Box<ReceiveInterruptedState<R, Ctx>>,
+        config:                 Box<ReceiveInterruptedState<R, Ctx>>,
         /// The operation that needs to be handled.
-        interrupt:        Interrupt,
+        interrupt:              Interrupt,
     },
     /// Contract execution terminated with a "logic error", i.e., contract
     /// decided to signal an error.
     Reject {
         /// Return code.
-        reason:           i32,
+        reason:                 i32,
         /// Return value, that may describe the error in more detail.
-        return_value:     ReturnValue,
+        return_value:           ReturnValue,
         /// Remaining interpreter energy.
-        remaining_energy: u64,
+        remaining_energy:       u64,
+        /// Remaining energy for state operations, if they had a separate
+        /// budget.
+        remaining_state_energy: Option<u64>,
     },
     /// Execution stopped due to a runtime error.
     Trap {
@@ -197,6 +556,21 @@ pub(crate) struct ReceiveResultExtract<R> {
     pub return_value:    Option<ReturnValue>,
 }
 
+impl<R, Ctx> ReceiveResult<R, Ctx> {
+    /// The reason the contract rejected with, classified into reasons reserved
+    /// for contract libraries and reasons defined by the contract. Returns
+    /// [None] if the contract did not reject.
+    pub fn reject_reason(&self) -> Option<RejectReason> {
+        match self {
+            ReceiveResult::Reject {
+                reason,
+                ..
+            } => Some(RejectReason::from(*reason)),
+            _ => None,
+        }
+    }
+}
+
 impl<R> ReceiveResult<R> {
     /// Extract the result into a byte array and potentially a return value.
     /// This is only meant to be used to pass the return value to foreign code.
@@ -229,6 +603,7 @@ impl<R> ReceiveResult<R> {
                 reason,
                 return_value,
                 remaining_energy,
+                ..
             } => {
                 let mut out = Vec::with_capacity(13);
                 out.push(2);
@@ -246,9 +621,11 @@ impl<R> ReceiveResult<R> {
                 state_changed,
                 return_value,
                 remaining_energy,
+                ..
             } => {
-                let mut out = vec![3];
-                out.extend_from_slice(&logs.to_bytes());
+                let mut out = Vec::with_capacity(1 + logs.serialized_size() + 8);
+                out.push(3);
+                logs.serialize_into(&mut out).expect("Serialization to a vector never fails.");
                 out.extend_from_slice(&remaining_energy.to_be_bytes());
                 ReceiveResultExtract{
                     status: out,
@@ -263,10 +640,12 @@ impl<R> ReceiveResult<R> {
                 logs,
                 config,
                 interrupt,
+                ..
             } => {
-                let mut out = vec![4];
+                let mut out = Vec::with_capacity(1 + 8 + logs.serialized_size());
+                out.push(4);
                 out.extend_from_slice(&remaining_energy.to_be_bytes());
-                out.extend_from_slice(&logs.to_bytes());
+                logs.serialize_into(&mut out).expect("Serialization to a vector never fails.");
                 interrupt.to_bytes(&mut out).expect("Serialization to a vector never fails.");
                 ReceiveResultExtract{
                     status: out,
@@ -288,6 +667,8 @@ pub enum CommonFunc {
     GetParameterSection,
     GetPolicySection,
     LogEvent,
+    /// Log an event prefixed by a tag, see [events](crate::events).
+    LogEventTyped,
     GetSlotTime,
     WriteOutput,
     StateLookupEntry,
@@ -299,6 +680,11 @@ pub enum CommonFunc {
     StateIteratorDelete,
     StateIteratorKeySize,
     StateIteratorKeyRead,
+    // Resumable iteration
+    StateIteratorTokenSize,
+    StateIteratorTokenRead,
+    StateIteratorResume,
+    StateIteratorRemaining,
     StateEntryRead,
     StateEntryWrite,
     StateEntrySize,
@@ -306,9 +692,101 @@ pub enum CommonFunc {
     // Cryptographic functions
     VerifyEd25519,
     VerifySecp256k1,
+    VerifySecp256r1,
     HashSHA2_256,
     HashSHA3_256,
     HashKeccak256,
+    StateEntryHash,
+    GetRandom,
+    // BLS12-381 signatures and group operations
+    BlsVerify,
+    BlsAggregateVerify,
+    BlsG1Add,
+    BlsG1Mul,
+    BlsG2Add,
+    BlsG2Mul,
+    // Parameter cursors
+    ParameterCursorOpen,
+    ParameterCursorRead,
+    ParameterCursorSeek,
+    ParameterCursorClose,
+}
+
+impl CommonFunc {
+    /// Whether the function accesses the contract state.
+    pub fn is_state_access(self) -> bool {
+        use CommonFunc::*;
+        matches!(
+            self,
+            StateLookupEntry
+                | StateCreateEntry
+                | StateDeleteEntry
+                | StateDeletePrefix
+                | StateIteratePrefix
+                | StateIteratorNext
+                | StateIteratorDelete
+                | StateIteratorKeySize
+                | StateIteratorKeyRead
+                | StateIteratorTokenSize
+                | StateIteratorTokenRead
+                | StateIteratorResume
+                | StateIteratorRemaining
+                | StateEntryRead
+                | StateEntryWrite
+                | StateEntrySize
+                | StateEntryResize
+                | StateEntryHash
+        )
+    }
+
+    /// The name the function is imported with from the `concordium` module.
+    pub fn name(self) -> &'static str {
+        use CommonFunc::*;
+        match self {
+            GetParameterSize => "get_parameter_size",
+            GetParameterSection => "get_parameter_section",
+            GetPolicySection => "get_policy_section",
+            LogEvent => "log_event",
+            LogEventTyped => "log_event_typed",
+            GetSlotTime => "get_slot_time",
+            WriteOutput => "write_output",
+            StateLookupEntry => "state_lookup_entry",
+            StateCreateEntry => "state_create_entry",
+            StateDeleteEntry => "state_delete_entry",
+            StateDeletePrefix => "state_delete_prefix",
+            StateIteratePrefix => "state_iterate_prefix",
+            StateIteratorNext => "state_iterator_next",
+            StateIteratorDelete => "state_iterator_delete",
+            StateIteratorKeySize => "state_iterator_key_size",
+            StateIteratorKeyRead => "state_iterator_key_read",
+            StateIteratorTokenSize => "state_iterator_token_size",
+            StateIteratorTokenRead => "state_iterator_token_read",
+            StateIteratorResume => "state_iterator_resume",
+            StateIteratorRemaining => "state_iterator_remaining",
+            StateEntryRead => "state_entry_read",
+            StateEntryWrite => "state_entry_write",
+            StateEntrySize => "state_entry_size",
+            StateEntryResize => "state_entry_resize",
+            VerifyEd25519 => "verify_ed25519_signature",
+            VerifySecp256k1 => "verify_ecdsa_secp256k1_signature",
+            VerifySecp256r1 => "verify_ecdsa_secp256r1_signature",
+            HashSHA2_256 => "hash_sha2_256",
+            HashSHA3_256 => "hash_sha3_256",
+            HashKeccak256 => "hash_keccak_256",
+            StateEntryHash => "state_entry_hash",
+            GetRandom => "get_random",
+            BlsVerify => "bls_verify",
+            BlsAggregateVerify => "bls_aggregate_verify",
+            BlsG1Add => "bls_g1_add",
+            BlsG1Mul => "bls_g1_mul",
+            BlsG2Add => "bls_g2_add",
+            BlsG2Mul => "bls_g2_mul",
+            ParameterCursorOpen => "parameter_cursor_open",
+            ParameterCursorRead => "parameter_cursor_read",
+            ParameterCursorSeek => "parameter_cursor_seek",
+            ParameterCursorClose => "parameter_cursor_close",
+        }
+    }
 }
 
 #[repr(u8)]
@@ -330,6 +808,52 @@ pub enum ReceiveOnlyFunc {
     GetReceiveOwner,
     GetReceiveEntrypointSize,
     GetReceiveEntryPoint,
+    ContractExists,
+    ContractStateSize,
+    /// The variant of `get_receive_sender` that is imported with an `i32`
+    /// result. It returns the length of the address it wrote.
+    GetReceiveSenderWithLength,
+    /// Query the balance of an account. This interrupts execution.
+    GetAccountBalance,
+    /// Upgrade the module of the instance. This interrupts execution.
+    Upgrade,
+    /// Convert energy to microCCD at the current exchange rates. This
+    /// interrupts execution to query the rates.
+    EnergyToMicroCcd,
+    /// Convert microCCD to energy at the current exchange rates. This
+    /// interrupts execution to query the rates.
+    MicroCcdToEnergy,
+    /// Convert microeuros to microCCD at the current exchange rates. This
+    /// interrupts execution to query the rates.
+    MicroEuroToMicroCcd,
+    /// Convert microCCD to microeuros at the current exchange rates. This
+    /// interrupts execution to query the rates.
+    MicroCcdToMicroEuro,
+}
+
+impl ReceiveOnlyFunc {
+    /// The name the function is imported with from the `concordium` module.
+    pub fn name(self) -> &'static str {
+        use ReceiveOnlyFunc::*;
+        match self {
+            Invoke => "invoke",
+            GetReceiveInvoker => "get_receive_invoker",
+            GetReceiveSelfAddress => "get_receive_self_address",
+            GetReceiveSelfBalance => "get_receive_self_balance",
+            GetReceiveSender | GetReceiveSenderWithLength => "get_receive_sender",
+            GetReceiveOwner => "get_receive_owner",
+            GetReceiveEntrypointSize => "get_receive_entrypoint_size",
+            GetReceiveEntryPoint => "get_receive_entrypoint",
+            ContractExists => "contract_exists",
+            ContractStateSize => "contract_state_size",
+            GetAccountBalance => "get_account_balance",
+            Upgrade => "upgrade",
+            EnergyToMicroCcd => "energy_to_micro_ccd",
+            MicroCcdToEnergy => "micro_ccd_to_energy",
+            MicroEuroToMicroCcd => "micro_euro_to_micro_ccd",
+            MicroCcdToMicroEuro => "micro_ccd_to_micro_euro",
+        }
+    }
 }
 
 #[repr(u8)]
@@ -352,6 +876,23 @@ pub enum ImportFunc {
     ReceiveOnly(ReceiveOnlyFunc),
 }
 
+impl ImportFunc {
+    /// The name the function is imported with. The accounting functions are
+    /// imported from the `concordium_metering` module, and the rest from the
+    /// `concordium` module.
+    pub fn name(self) -> &'static str {
+        match self {
+            ImportFunc::ChargeEnergy => "account_energy",
+            ImportFunc::TrackCall => "track_call",
+            ImportFunc::TrackReturn => "track_return",
+            ImportFunc::ChargeMemoryAlloc => "account_memory",
+            ImportFunc::Common(cf) => cf.name(),
+            ImportFunc::InitOnly(InitOnlyFunc::GetInitOrigin) => "get_init_origin",
+            ImportFunc::ReceiveOnly(rof) => rof.name(),
+        }
+    }
+}
+
 impl<'a, Ctx: Copy> Parseable<'a, Ctx> for ImportFunc {
     fn parse(
         ctx: Ctx,
@@ -395,14 +936,43 @@ impl<'a, Ctx: Copy> Parseable<'a, Ctx> for ImportFunc {
             34 => Ok(ImportFunc::Common(CommonFunc::HashSHA2_256)),
             35 => Ok(ImportFunc::Common(CommonFunc::HashSHA3_256)),
             36 => Ok(ImportFunc::Common(CommonFunc::HashKeccak256)),
+            37 => Ok(ImportFunc::Common(CommonFunc::StateEntryHash)),
+            38 => Ok(ImportFunc::ReceiveOnly(ReceiveOnlyFunc::ContractExists)),
+            39 => Ok(ImportFunc::ReceiveOnly(ReceiveOnlyFunc::ContractStateSize)),
+            40 => Ok(ImportFunc::ReceiveOnly(ReceiveOnlyFunc::GetReceiveSenderWithLength)),
+            41 => Ok(ImportFunc::ReceiveOnly(ReceiveOnlyFunc::GetAccountBalance)),
+            42 => Ok(ImportFunc::Common(CommonFunc::ParameterCursorOpen)),
+            43 => Ok(ImportFunc::Common(CommonFunc::ParameterCursorRead)),
+            44 => Ok(ImportFunc::Common(CommonFunc::ParameterCursorSeek)),
+            45 => Ok(ImportFunc::Common(CommonFunc::ParameterCursorClose)),
+            46 => Ok(ImportFunc::ReceiveOnly(ReceiveOnlyFunc::Upgrade)),
+            47 => Ok(ImportFunc::Common(CommonFunc::BlsVerify)),
+            48 => Ok(ImportFunc::Common(CommonFunc::BlsAggregateVerify)),
+            49 => Ok(ImportFunc::Common(CommonFunc::BlsG1Add)),
+            50 => Ok(ImportFunc::Common(CommonFunc::BlsG1Mul)),
+            51 => Ok(ImportFunc::Common(CommonFunc::BlsG2Add)),
+            52 => Ok(ImportFunc::Common(CommonFunc::BlsG2Mul)),
+            53 => Ok(ImportFunc::Common(CommonFunc::GetRandom)),
+            54 => Ok(ImportFunc::ReceiveOnly(ReceiveOnlyFunc::EnergyToMicroCcd)),
+            55 => Ok(ImportFunc::ReceiveOnly(ReceiveOnlyFunc::MicroCcdToEnergy)),
+            56 => Ok(ImportFunc::Common(CommonFunc::VerifySecp256r1)),
+            57 => Ok(ImportFunc::Common(CommonFunc::StateIteratorTokenSize)),
+            58 => Ok(ImportFunc::Common(CommonFunc::StateIteratorTokenRead)),
+            59 => Ok(ImportFunc::Common(CommonFunc::StateIteratorResume)),
+            60 => Ok(ImportFunc::Common(CommonFunc::StateIteratorRemaining)),
+            61 => Ok(ImportFunc::ReceiveOnly(ReceiveOnlyFunc::MicroEuroToMicroCcd)),
+            62 => Ok(ImportFunc::ReceiveOnly(ReceiveOnlyFunc::MicroCcdToMicroEuro)),
+            63 => Ok(ImportFunc::Common(CommonFunc::LogEventTyped)),
             tag => bail!("Unexpected ImportFunc tag {}.", tag),
         }
     }
 }
 
-impl Output for ImportFunc {
-    fn output(&self, out: &mut impl std::io::Write) -> wasm_transform::output::OutResult<()> {
-        let tag: u8 = match self {
+impl ImportFunc {
+    /// The tag of the import in artifacts. Tags are assigned in the order in
+    /// which the imports were added to the host interface.
+    pub fn tag(&self) -> u8 {
+        match self {
             ImportFunc::ChargeEnergy => 0,
             ImportFunc::TrackCall => 1,
             ImportFunc::TrackReturn => 2,
@@ -412,6 +982,7 @@ impl Output for ImportFunc {
                 CommonFunc::GetParameterSection => 5,
                 CommonFunc::GetPolicySection => 6,
                 CommonFunc::LogEvent => 7,
+                CommonFunc::LogEventTyped => 63,
                 CommonFunc::GetSlotTime => 8,
                 CommonFunc::StateLookupEntry => 9,
                 CommonFunc::StateCreateEntry => 10,
@@ -422,6 +993,10 @@ impl Output for ImportFunc {
                 CommonFunc::StateIteratorDelete => 15,
                 CommonFunc::StateIteratorKeySize => 16,
                 CommonFunc::StateIteratorKeyRead => 17,
+                CommonFunc::StateIteratorTokenSize => 57,
+                CommonFunc::StateIteratorTokenRead => 58,
+                CommonFunc::StateIteratorResume => 59,
+                CommonFunc::StateIteratorRemaining => 60,
                 CommonFunc::StateEntryRead => 18,
                 CommonFunc::StateEntryWrite => 19,
                 CommonFunc::StateEntrySize => 20,
@@ -429,9 +1004,22 @@ impl Output for ImportFunc {
                 CommonFunc::WriteOutput => 22,
                 CommonFunc::VerifyEd25519 => 32,
                 CommonFunc::VerifySecp256k1 => 33,
+                CommonFunc::VerifySecp256r1 => 56,
                 CommonFunc::HashSHA2_256 => 34,
                 CommonFunc::HashSHA3_256 => 35,
                 CommonFunc::HashKeccak256 => 36,
+                CommonFunc::StateEntryHash => 37,
+                CommonFunc::ParameterCursorOpen => 42,
+                CommonFunc::ParameterCursorRead => 43,
+                CommonFunc::ParameterCursorSeek => 44,
+                CommonFunc::ParameterCursorClose => 45,
+                CommonFunc::BlsVerify => 47,
+                CommonFunc::BlsAggregateVerify => 48,
+                CommonFunc::BlsG1Add => 49,
+                CommonFunc::BlsG1Mul => 50,
+                CommonFunc::BlsG2Add => 51,
+                CommonFunc::BlsG2Mul => 52,
+                CommonFunc::GetRandom => 53,
             },
             ImportFunc::InitOnly(io) => match io {
                 InitOnlyFunc::GetInitOrigin => 23,
@@ -445,9 +1033,200 @@ impl Output for ImportFunc {
                 ReceiveOnlyFunc::GetReceiveEntrypointSize => 29,
                 ReceiveOnlyFunc::GetReceiveEntryPoint => 30,
                 ReceiveOnlyFunc::Invoke => 31,
+                ReceiveOnlyFunc::ContractExists => 38,
+                ReceiveOnlyFunc::ContractStateSize => 39,
+                ReceiveOnlyFunc::GetReceiveSenderWithLength => 40,
+                ReceiveOnlyFunc::GetAccountBalance => 41,
+                ReceiveOnlyFunc::Upgrade => 46,
+                ReceiveOnlyFunc::EnergyToMicroCcd => 54,
+                ReceiveOnlyFunc::MicroCcdToEnergy => 55,
+                ReceiveOnlyFunc::MicroEuroToMicroCcd => 61,
+                ReceiveOnlyFunc::MicroCcdToMicroEuro => 62,
             },
+        }
+    }
+
+    /// The version of the host interface that introduced the import, see
+    /// [HostInterface]. The imports with tags up to 36 are version 0, and each
+    /// later group of imports increases the version:
+    ///
+    /// 1. `state_entry_hash` (tag 37)
+    /// 2. `contract_exists` and `contract_state_size` (38, 39)
+    /// 3. `get_receive_sender` with a length (40)
+    /// 4. `get_account_balance` (41)
+    /// 5. the parameter cursors (42 to 45)
+    /// 6. `upgrade` (46)
+    /// 7. the BLS12-381 functions (47 to 52)
+    /// 8. `get_random` (53)
+    /// 9. `energy_to_micro_ccd` and `micro_ccd_to_energy` (54, 55)
+    /// 10. `verify_ecdsa_secp256r1_signature` (56)
+    /// 11. the resumable state iterators (57 to 60)
+    /// 12. `micro_euro_to_micro_ccd` and `micro_ccd_to_micro_euro` (61, 62)
+    /// 13. `log_event_typed` (63)
+    ///
+    /// Version 14 adds no imports, see [INLINE_CALL_DEPTH_VERSION].
+    pub fn host_interface_version(&self) -> u32 {
+        match self.tag() {
+            0..=36 => 0,
+            37 => 1,
+            38 | 39 => 2,
+            40 => 3,
+            41 => 4,
+            42..=45 => 5,
+            46 => 6,
+            47..=52 => 7,
+            53 => 8,
+            54 | 55 => 9,
+            56 => 10,
+            57..=60 => 11,
+            61 | 62 => 12,
+            _ => 13,
+        }
+    }
+
+    /// The host function that an import of the given name from the
+    /// `concordium` module resolves to, if any. The type is needed to
+    /// distinguish the variants of `get_receive_sender`.
+    pub fn from_concordium_import(item_name: &str, ty: &FunctionType) -> Option<Self> {
+        let func = match item_name {
+            "write_output" => ImportFunc::Common(CommonFunc::WriteOutput),
+            "invoke" => ImportFunc::ReceiveOnly(ReceiveOnlyFunc::Invoke),
+            "contract_exists" => ImportFunc::ReceiveOnly(ReceiveOnlyFunc::ContractExists),
+            "contract_state_size" => ImportFunc::ReceiveOnly(ReceiveOnlyFunc::ContractStateSize),
+            "get_account_balance" => ImportFunc::ReceiveOnly(ReceiveOnlyFunc::GetAccountBalance),
+            "upgrade" => ImportFunc::ReceiveOnly(ReceiveOnlyFunc::Upgrade),
+            "energy_to_micro_ccd" => ImportFunc::ReceiveOnly(ReceiveOnlyFunc::EnergyToMicroCcd),
+            "micro_ccd_to_energy" => ImportFunc::ReceiveOnly(ReceiveOnlyFunc::MicroCcdToEnergy),
+            "micro_euro_to_micro_ccd" => {
+                ImportFunc::ReceiveOnly(ReceiveOnlyFunc::MicroEuroToMicroCcd)
+            }
+            "micro_ccd_to_micro_euro" => {
+                ImportFunc::ReceiveOnly(ReceiveOnlyFunc::MicroCcdToMicroEuro)
+            }
+            "get_parameter_size" => ImportFunc::Common(CommonFunc::GetParameterSize),
+            "get_parameter_section" => ImportFunc::Common(CommonFunc::GetParameterSection),
+            "get_policy_section" => ImportFunc::Common(CommonFunc::GetPolicySection),
+            "log_event" => ImportFunc::Common(CommonFunc::LogEvent),
+            "log_event_typed" => ImportFunc::Common(CommonFunc::LogEventTyped),
+            "get_init_origin" => ImportFunc::InitOnly(InitOnlyFunc::GetInitOrigin),
+            "get_receive_invoker" => ImportFunc::ReceiveOnly(ReceiveOnlyFunc::GetReceiveInvoker),
+            "get_receive_self_address" => {
+                ImportFunc::ReceiveOnly(ReceiveOnlyFunc::GetReceiveSelfAddress)
+            }
+            "get_receive_self_balance" => {
+                ImportFunc::ReceiveOnly(ReceiveOnlyFunc::GetReceiveSelfBalance)
+            }
+            "get_receive_sender" if ty.result.is_some() => {
+                ImportFunc::ReceiveOnly(ReceiveOnlyFunc::GetReceiveSenderWithLength)
+            }
+            "get_receive_sender" => ImportFunc::ReceiveOnly(ReceiveOnlyFunc::GetReceiveSender),
+            "get_receive_owner" => ImportFunc::ReceiveOnly(ReceiveOnlyFunc::GetReceiveOwner),
+            "get_receive_entrypoint_size" => {
+                ImportFunc::ReceiveOnly(ReceiveOnlyFunc::GetReceiveEntrypointSize)
+            }
+            "get_receive_entrypoint" => {
+                ImportFunc::ReceiveOnly(ReceiveOnlyFunc::GetReceiveEntryPoint)
+            }
+            "get_slot_time" => ImportFunc::Common(CommonFunc::GetSlotTime),
+            "state_lookup_entry" => ImportFunc::Common(CommonFunc::StateLookupEntry),
+            "state_create_entry" => ImportFunc::Common(CommonFunc::StateCreateEntry),
+            "state_delete_entry" => ImportFunc::Common(CommonFunc::StateDeleteEntry),
+            "state_delete_prefix" => ImportFunc::Common(CommonFunc::StateDeletePrefix),
+            "state_iterate_prefix" => ImportFunc::Common(CommonFunc::StateIteratePrefix),
+            "state_iterator_next" => ImportFunc::Common(CommonFunc::StateIteratorNext),
+            "state_iterator_delete" => ImportFunc::Common(CommonFunc::StateIteratorDelete),
+            "state_iterator_key_size" => ImportFunc::Common(CommonFunc::StateIteratorKeySize),
+            "state_iterator_key_read" => ImportFunc::Common(CommonFunc::StateIteratorKeyRead),
+            "state_iterator_token_size" => ImportFunc::Common(CommonFunc::StateIteratorTokenSize),
+            "state_iterator_token_read" => ImportFunc::Common(CommonFunc::StateIteratorTokenRead),
+            "state_iterator_resume" => ImportFunc::Common(CommonFunc::StateIteratorResume),
+            "state_iterator_remaining" => ImportFunc::Common(CommonFunc::StateIteratorRemaining),
+            "state_entry_read" => ImportFunc::Common(CommonFunc::StateEntryRead),
+            "state_entry_write" => ImportFunc::Common(CommonFunc::StateEntryWrite),
+            "state_entry_size" => ImportFunc::Common(CommonFunc::StateEntrySize),
+            "state_entry_resize" => ImportFunc::Common(CommonFunc::StateEntryResize),
+            "verify_ed25519_signature" => ImportFunc::Common(CommonFunc::VerifyEd25519),
+            "verify_ecdsa_secp256k1_signature" => ImportFunc::Common(CommonFunc::VerifySecp256k1),
+            "verify_ecdsa_secp256r1_signature" => ImportFunc::Common(CommonFunc::VerifySecp256r1),
+            "hash_sha2_256" => ImportFunc::Common(CommonFunc::HashSHA2_256),
+            "hash_sha3_256" => ImportFunc::Common(CommonFunc::HashSHA3_256),
+            "hash_keccak_256" => ImportFunc::Common(CommonFunc::HashKeccak256),
+            "state_entry_hash" => ImportFunc::Common(CommonFunc::StateEntryHash),
+            "get_random" => ImportFunc::Common(CommonFunc::GetRandom),
+            "bls_verify" => ImportFunc::Common(CommonFunc::BlsVerify),
+            "bls_aggregate_verify" => ImportFunc::Common(CommonFunc::BlsAggregateVerify),
+            "bls_g1_add" => ImportFunc::Common(CommonFunc::BlsG1Add),
+            "bls_g1_mul" => ImportFunc::Common(CommonFunc::BlsG1Mul),
+            "bls_g2_add" => ImportFunc::Common(CommonFunc::BlsG2Add),
+            "bls_g2_mul" => ImportFunc::Common(CommonFunc::BlsG2Mul),
+            "parameter_cursor_open" => ImportFunc::Common(CommonFunc::ParameterCursorOpen),
+            "parameter_cursor_read" => ImportFunc::Common(CommonFunc::ParameterCursorRead),
+            "parameter_cursor_seek" => ImportFunc::Common(CommonFunc::ParameterCursorSeek),
+            "parameter_cursor_close" => ImportFunc::Common(CommonFunc::ParameterCursorClose),
+            _ => return None,
         };
-        tag.output(out)
+        Some(func)
+    }
+}
+
+impl Output for ImportFunc {
+    fn output(&self, out: &mut impl std::io::Write) -> wasm_transform::output::OutResult<()> {
+        self.tag().output(out)
+    }
+}
+
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// Hash algorithms supported by the `state_entry_hash` host function. The
+/// discriminants are the identifiers used by contracts.
+pub enum HashAlgorithm {
+    Sha2_256  = 0,
+    Sha3_256  = 1,
+    Keccak256 = 2,
+}
+
+impl std::convert::TryFrom<u32> for HashAlgorithm {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(HashAlgorithm::Sha2_256),
+            1 => Ok(HashAlgorithm::Sha3_256),
+            2 => Ok(HashAlgorithm::Keccak256),
+            _ => bail!("Unknown hash algorithm {}.", value),
+        }
+    }
+}
+
+impl HashAlgorithm {
+    /// Cost of computing a digest of data of the given length. This is the same
+    /// as the cost of the corresponding host function that hashes data in
+    /// memory.
+    pub fn cost(self, data_len: u32) -> u64 {
+        match self {
+            HashAlgorithm::Sha2_256 => constants::hash_sha2_256_cost(data_len),
+            HashAlgorithm::Sha3_256 => constants::hash_sha3_256_cost(data_len),
+            HashAlgorithm::Keccak256 => constants::hash_keccak_256_cost(data_len),
+        }
+    }
+
+    /// Like [cost](Self::cost), but with the costs of the given table.
+    pub fn cost_with(self, costs: &CostTable, data_len: u32) -> u64 {
+        match self {
+            HashAlgorithm::Sha2_256 => costs.hash_sha2_256.cost(data_len),
+            HashAlgorithm::Sha3_256 => costs.hash_sha3_256.cost(data_len),
+            HashAlgorithm::Keccak256 => costs.hash_keccak_256.cost(data_len),
+        }
+    }
+
+    /// Compute the digest of the data.
+    pub fn digest(self, data: &[u8]) -> [u8; 32] {
+        use sha2::Digest;
+        match self {
+            HashAlgorithm::Sha2_256 => sha2::Sha256::digest(data).into(),
+            HashAlgorithm::Sha3_256 => sha3::Sha3_256::digest(data).into(),
+            HashAlgorithm::Keccak256 => sha3::Keccak256::digest(data).into(),
+        }
     }
 }
 
@@ -457,6 +1236,12 @@ pub struct ProcessedImports {
     ty:             FunctionType,
 }
 
+/// Display the host function the import resolves to, e.g., in the
+/// [disassembly](wasm_transform::artifact::disassemble) of artifacts.
+impl std::fmt::Display for ProcessedImports {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result { write!(f, "{:?}", self.tag) }
+}
+
 impl<'a, Ctx: Copy> Parseable<'a, Ctx> for ProcessedImports {
     fn parse(
         ctx: Ctx,
@@ -478,7 +1263,62 @@ impl Output for ProcessedImports {
     }
 }
 
-pub struct ConcordiumAllowedImports;
+/// The version is that of the most recent change to the interface, see
+/// [ImportFunc::host_interface_version].
+impl HostInterface for ProcessedImports {
+    const HOST_INTERFACE_VERSION: u32 = INLINE_CALL_DEPTH_VERSION;
+}
+
+/// The version of the host interface from which modules limit their call depth
+/// with a counter injected by the metering transformation, instead of by calls
+/// to the `track_call` and `track_return` host functions, see
+/// [ConcordiumAllowedImports::metering_config]. Artifacts of this version are
+/// refused by hosts of earlier versions.
+pub const INLINE_CALL_DEPTH_VERSION: u32 = 14;
+
+/// Validation of the imports and exports of V1 modules. Imports are only
+/// allowed if they were introduced in at most the given version of the host
+/// interface, see [ImportFunc::host_interface_version]. The node selects the
+/// version by protocol version. The default only allows the imports of the
+/// baseline, version 0. Since version 8 allows `get_random`, the node must
+/// supply a [RandomSeed](v0::RandomSeed) to executions in protocol versions
+/// that allow version 8 or later.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConcordiumAllowedImports {
+    /// The latest version of the host interface whose imports are allowed.
+    pub host_interface_version: u32,
+}
+
+impl ConcordiumAllowedImports {
+    /// Allow all imports supported by this library. This is intended for
+    /// tooling and tests, not for validating modules on the chain.
+    pub const LATEST: Self = Self {
+        host_interface_version: <ProcessedImports as HostInterface>::HOST_INTERFACE_VERSION,
+    };
+
+    /// Allow the imports introduced in at most the given version of the host
+    /// interface.
+    pub fn new(host_interface_version: u32) -> Self {
+        Self {
+            host_interface_version,
+        }
+    }
+
+    /// The metering transformation applied to modules of this version of the
+    /// host interface. From [INLINE_CALL_DEPTH_VERSION] the call depth is
+    /// limited to [MAX_ACTIVATION_FRAMES](constants::MAX_ACTIVATION_FRAMES)
+    /// by a counter in the module, which is the same limit that `track_call`
+    /// enforces.
+    pub fn metering_config(&self) -> MeteringConfig {
+        if self.host_interface_version >= INLINE_CALL_DEPTH_VERSION {
+            MeteringConfig {
+                inline_call_depth: Some(constants::MAX_ACTIVATION_FRAMES),
+            }
+        } else {
+            MeteringConfig::LEGACY
+        }
+    }
+}
 
 impl validate::ValidateImportExport for ConcordiumAllowedImports {
     fn validate_import_function(
@@ -493,44 +1333,79 @@ impl validate::ValidateImportExport for ConcordiumAllowedImports {
             return false;
         };
         if mod_name.name == "concordium" {
-            match item_name.name.as_ref() {
-                "invoke" => type_matches!(ty => [I32, I32, I32]; I64),
-                "write_output" => type_matches!(ty => [I32, I32, I32]; I32),
-                "get_parameter_size" => type_matches!(ty => [I32]; I32),
-                "get_parameter_section" => type_matches!(ty => [I32, I32, I32, I32]; I32),
-                "get_policy_section" => type_matches!(ty => [I32, I32, I32]; I32),
-                "log_event" => type_matches!(ty => [I32, I32]; I32),
-                "get_init_origin" => type_matches!(ty => [I32]),
-                "get_receive_invoker" => type_matches!(ty => [I32]),
-                "get_receive_self_address" => type_matches!(ty => [I32]),
-                "get_receive_self_balance" => type_matches!(ty => []; I64),
-                "get_receive_sender" => type_matches!(ty => [I32]),
-                "get_receive_owner" => type_matches!(ty => [I32]),
-                "get_receive_entrypoint_size" => type_matches!(ty => []; I32),
-                "get_receive_entrypoint" => type_matches!(ty => [I32]),
-                "get_slot_time" => type_matches!(ty => []; I64),
-                "state_lookup_entry" => type_matches!(ty => [I32, I32]; I64),
-                "state_create_entry" => type_matches!(ty => [I32, I32]; I64),
-                "state_delete_entry" => type_matches!(ty => [I32, I32]; I32),
-                "state_delete_prefix" => type_matches!(ty => [I32, I32]; I32),
-                "state_iterate_prefix" => type_matches!(ty => [I32, I32]; I64),
-                "state_iterator_next" => type_matches!(ty => [I64]; I64),
-                "state_iterator_delete" => type_matches!(ty => [I64]; I32),
-                "state_iterator_key_size" => type_matches!(ty => [I64]; I32),
-                "state_iterator_key_read" => type_matches!(ty => [I64, I32, I32, I32]; I32),
-                "state_entry_read" => type_matches!(ty => [I64, I32, I32, I32]; I32),
-                "state_entry_write" => type_matches!(ty => [I64, I32, I32, I32]; I32),
-                "state_entry_size" => type_matches!(ty => [I64]; I32),
-                "state_entry_resize" => type_matches!(ty => [I64, I32]; I32),
-                "verify_ed25519_signature" => type_matches!(ty => [I32, I32, I32, I32]; I32),
-                "verify_ecdsa_secp256k1_signature" => {
-                    type_matches!(ty => [I32, I32, I32]; I32)
+            let supported = ImportFunc::from_concordium_import(item_name.as_ref(), ty)
+                .map_or(false, |f| f.host_interface_version() <= self.host_interface_version);
+            supported
+                && match item_name.name.as_ref() {
+                    "invoke" => type_matches!(ty => [I32, I32, I32]; I64),
+                    "contract_exists" => type_matches!(ty => [I64, I64]; I32),
+                    "contract_state_size" => type_matches!(ty => [I64, I64]; I64),
+                    "get_account_balance" => type_matches!(ty => [I32]; I64),
+                    "upgrade" => type_matches!(ty => [I32]; I64),
+                    "energy_to_micro_ccd" => type_matches!(ty => [I64]; I64),
+                    "micro_ccd_to_energy" => type_matches!(ty => [I64]; I64),
+                    "micro_euro_to_micro_ccd" => type_matches!(ty => [I64]; I64),
+                    "micro_ccd_to_micro_euro" => type_matches!(ty => [I64]; I64),
+                    "write_output" => type_matches!(ty => [I32, I32, I32]; I32),
+                    "get_parameter_size" => type_matches!(ty => [I32]; I32),
+                    "get_parameter_section" => type_matches!(ty => [I32, I32, I32, I32]; I32),
+                    "parameter_cursor_open" => type_matches!(ty => [I32]; I32),
+                    "parameter_cursor_read" => type_matches!(ty => [I32, I32, I32]; I32),
+                    "parameter_cursor_seek" => type_matches!(ty => [I32, I32]; I32),
+                    "parameter_cursor_close" => type_matches!(ty => [I32]; I32),
+                    "get_policy_section" => type_matches!(ty => [I32, I32, I32]; I32),
+                    "log_event" => type_matches!(ty => [I32, I32]; I32),
+                    "log_event_typed" => type_matches!(ty => [I32, I32, I32]; I32),
+                    "get_init_origin" => type_matches!(ty => [I32]),
+                    "get_receive_invoker" => type_matches!(ty => [I32]),
+                    "get_receive_self_address" => type_matches!(ty => [I32]),
+                    "get_receive_self_balance" => type_matches!(ty => []; I64),
+                    // Modules may import the function with or without a result. With a
+                    // result the function returns the length of the address it wrote.
+                    "get_receive_sender" => {
+                        type_matches!(ty => [I32]) || type_matches!(ty => [I32]; I32)
+                    }
+                    "get_receive_owner" => type_matches!(ty => [I32]),
+                    "get_receive_entrypoint_size" => type_matches!(ty => []; I32),
+                    "get_receive_entrypoint" => type_matches!(ty => [I32]),
+                    "get_slot_time" => type_matches!(ty => []; I64),
+                    "state_lookup_entry" => type_matches!(ty => [I32, I32]; I64),
+                    "state_create_entry" => type_matches!(ty => [I32, I32]; I64),
+                    "state_delete_entry" => type_matches!(ty => [I32, I32]; I32),
+                    "state_delete_prefix" => type_matches!(ty => [I32, I32]; I32),
+                    "state_iterate_prefix" => type_matches!(ty => [I32, I32]; I64),
+                    "state_iterator_next" => type_matches!(ty => [I64]; I64),
+                    "state_iterator_delete" => type_matches!(ty => [I64]; I32),
+                    "state_iterator_key_size" => type_matches!(ty => [I64]; I32),
+                    "state_iterator_key_read" => type_matches!(ty => [I64, I32, I32, I32]; I32),
+                    "state_iterator_token_size" => type_matches!(ty => [I64]; I32),
+                    "state_iterator_token_read" => type_matches!(ty => [I64, I32, I32, I32]; I32),
+                    "state_iterator_resume" => type_matches!(ty => [I32, I32]; I64),
+                    "state_iterator_remaining" => type_matches!(ty => [I64, I32]; I32),
+                    "state_entry_read" => type_matches!(ty => [I64, I32, I32, I32]; I32),
+                    "state_entry_write" => type_matches!(ty => [I64, I32, I32, I32]; I32),
+                    "state_entry_size" => type_matches!(ty => [I64]; I32),
+                    "state_entry_resize" => type_matches!(ty => [I64, I32]; I32),
+                    "verify_ed25519_signature" => type_matches!(ty => [I32, I32, I32, I32]; I32),
+                    "verify_ecdsa_secp256k1_signature" => {
+                        type_matches!(ty => [I32, I32, I32]; I32)
+                    }
+                    "verify_ecdsa_secp256r1_signature" => {
+                        type_matches!(ty => [I32, I32, I32]; I32)
+                    }
+                    "hash_sha2_256" => type_matches!(ty => [I32, I32, I32]),
+                    "hash_sha3_256" => type_matches!(ty => [I32, I32, I32]),
+                    "hash_keccak_256" => type_matches!(ty => [I32, I32, I32]),
+                    "state_entry_hash" => type_matches!(ty => [I64, I32, I32]; I32),
+                    "get_random" => type_matches!(ty => [I32, I32]),
+                    "bls_verify" => type_matches!(ty => [I32, I32, I32, I32]; I32),
+                    "bls_aggregate_verify" => type_matches!(ty => [I32, I32, I32, I32, I32]; I32),
+                    "bls_g1_add" => type_matches!(ty => [I32, I32, I32]; I32),
+                    "bls_g1_mul" => type_matches!(ty => [I32, I32, I32]; I32),
+                    "bls_g2_add" => type_matches!(ty => [I32, I32, I32]; I32),
+                    "bls_g2_mul" => type_matches!(ty => [I32, I32, I32]; I32),
+                    _ => false,
                 }
-                "hash_sha2_256" => type_matches!(ty => [I32, I32, I32]),
-                "hash_sha3_256" => type_matches!(ty => [I32, I32, I32]),
-                "hash_keccak_256" => type_matches!(ty => [I32, I32, I32]),
-                _ => false,
-            }
         } else {
             false
         }
@@ -574,6 +1449,14 @@ impl TryFromImport for ProcessedImports {
         ctx: &[FunctionType],
         import: Import,
     ) -> wasm_transform::artifact::CompileResult<Self> {
+        let ty = match import.description {
+            wasm_transform::types::ImportDescription::Func {
+                type_idx,
+            } => ctx
+                .get(type_idx as usize)
+                .ok_or_else(|| anyhow::anyhow!("Unknown type, this should not happen."))?
+                .clone(),
+        };
         let m = &import.mod_name;
         let tag = if m.name == "concordium_metering" {
             match import.item_name.name.as_ref() {
@@ -584,65 +1467,12 @@ impl TryFromImport for ProcessedImports {
                 name => bail!("Unsupported import {}.", name),
             }
         } else if m.name == "concordium" {
-            match import.item_name.name.as_ref() {
-                "write_output" => ImportFunc::Common(CommonFunc::WriteOutput),
-                "invoke" => ImportFunc::ReceiveOnly(ReceiveOnlyFunc::Invoke),
-                "get_parameter_size" => ImportFunc::Common(CommonFunc::GetParameterSize),
-                "get_parameter_section" => ImportFunc::Common(CommonFunc::GetParameterSection),
-                "get_policy_section" => ImportFunc::Common(CommonFunc::GetPolicySection),
-                "log_event" => ImportFunc::Common(CommonFunc::LogEvent),
-                "get_init_origin" => ImportFunc::InitOnly(InitOnlyFunc::GetInitOrigin),
-                "get_receive_invoker" => {
-                    ImportFunc::ReceiveOnly(ReceiveOnlyFunc::GetReceiveInvoker)
-                }
-                "get_receive_self_address" => {
-                    ImportFunc::ReceiveOnly(ReceiveOnlyFunc::GetReceiveSelfAddress)
-                }
-                "get_receive_self_balance" => {
-                    ImportFunc::ReceiveOnly(ReceiveOnlyFunc::GetReceiveSelfBalance)
-                }
-                "get_receive_sender" => ImportFunc::ReceiveOnly(ReceiveOnlyFunc::GetReceiveSender),
-                "get_receive_owner" => ImportFunc::ReceiveOnly(ReceiveOnlyFunc::GetReceiveOwner),
-                "get_receive_entrypoint_size" => {
-                    ImportFunc::ReceiveOnly(ReceiveOnlyFunc::GetReceiveEntrypointSize)
-                }
-                "get_receive_entrypoint" => {
-                    ImportFunc::ReceiveOnly(ReceiveOnlyFunc::GetReceiveEntryPoint)
-                }
-                "get_slot_time" => ImportFunc::Common(CommonFunc::GetSlotTime),
-                "state_lookup_entry" => ImportFunc::Common(CommonFunc::StateLookupEntry),
-                "state_create_entry" => ImportFunc::Common(CommonFunc::StateCreateEntry),
-                "state_delete_entry" => ImportFunc::Common(CommonFunc::StateDeleteEntry),
-                "state_delete_prefix" => ImportFunc::Common(CommonFunc::StateDeletePrefix),
-                "state_iterate_prefix" => ImportFunc::Common(CommonFunc::StateIteratePrefix),
-                "state_iterator_next" => ImportFunc::Common(CommonFunc::StateIteratorNext),
-                "state_iterator_delete" => ImportFunc::Common(CommonFunc::StateIteratorDelete),
-                "state_iterator_key_size" => ImportFunc::Common(CommonFunc::StateIteratorKeySize),
-                "state_iterator_key_read" => ImportFunc::Common(CommonFunc::StateIteratorKeyRead),
-                "state_entry_read" => ImportFunc::Common(CommonFunc::StateEntryRead),
-                "state_entry_write" => ImportFunc::Common(CommonFunc::StateEntryWrite),
-                "state_entry_size" => ImportFunc::Common(CommonFunc::StateEntrySize),
-                "state_entry_resize" => ImportFunc::Common(CommonFunc::StateEntryResize),
-                "verify_ed25519_signature" => ImportFunc::Common(CommonFunc::VerifyEd25519),
-                "verify_ecdsa_secp256k1_signature" => {
-                    ImportFunc::Common(CommonFunc::VerifySecp256k1)
-                }
-                "hash_sha2_256" => ImportFunc::Common(CommonFunc::HashSHA2_256),
-                "hash_sha3_256" => ImportFunc::Common(CommonFunc::HashSHA3_256),
-                "hash_keccak_256" => ImportFunc::Common(CommonFunc::HashKeccak256),
-                name => bail!("Unsupported import {}.", name),
-            }
+            let name = import.item_name.name.as_ref();
+            ImportFunc::from_concordium_import(name, &ty)
+                .ok_or_else(|| anyhow::anyhow!("Unsupported import {}.", name))?
         } else {
             bail!("Unsupported import module {}.", m)
         };
-        let ty = match import.description {
-            wasm_transform::types::ImportDescription::Func {
-                type_idx,
-            } => ctx
-                .get(type_idx as usize)
-                .ok_or_else(|| anyhow::anyhow!("Unknown type, this should not happen."))?
-                .clone(),
-        };
         Ok(Self {
             tag,
             ty,
@@ -657,8 +1487,8 @@ impl TryFromImport for ProcessedImports {
 #[derive(Debug)]
 pub struct InstanceState<'a, BackingStore> {
     /// The backing store that allows accessing any contract state that is not
-    /// in-memory yet.
-    backing_store:                 BackingStore,
+    /// in-memory yet. Loads are counted to distinguish cold and warm accesses.
+    backing_store:                 trie::LoadCounter<BackingStore>,
     /// A flag indicating whether any of the state change functions have been
     /// called.
     pub(crate) changed:            bool,
@@ -669,6 +1499,218 @@ pub struct InstanceState<'a, BackingStore> {
     /// Opaque pointer to the state of the instance in consensus. Note that this
     /// is in effect a mutable reference.
     state_trie:                    trie::StateTrie<'a>,
+    /// Additional costs charged for accessing the state, depending on whether
+    /// the accessed nodes were already in memory or not.
+    pub(crate) access_costs:       StateAccessCosts,
+    /// Counts of state accesses since the state was created.
+    pub(crate) access_counts:      StateAccessCounts,
+    /// The costs of host functions. See [InstanceState::with_cost_table].
+    pub(crate) costs:              CostTable,
+    /// Whether modifications of the state are forbidden. See
+    /// [InstanceState::with_read_only].
+    pub(crate) read_only:          bool,
+    /// Separate energy budget for state operations, if enabled. See
+    /// [InstanceState::with_state_energy].
+    pub(crate) state_energy:       Option<InterpreterEnergy>,
+    /// Whether the energy of the host is currently swapped with
+    /// [state_energy](Self::state_energy), i.e., a state operation is in
+    /// progress.
+    in_state_budget:               bool,
+    /// Depth of the execution in a chain of nested contract calls. See
+    /// [InstanceState::with_call_depth].
+    pub(crate) call_depth:         CallDepth,
+    /// Behaviour of the host that depends on the protocol version. See
+    /// [InstanceState::with_host_features].
+    pub(crate) host_features:      HostFeatures,
+    /// The seed of the randomness returned by `get_random`, if any. See
+    /// [InstanceState::with_random_seed].
+    pub(crate) random_seed:        Option<v0::RandomSeed>,
+}
+
+/// Additional energy charged by state host functions, on top of their normal
+/// cost, depending on whether the state they access is already in memory.
+/// Loading nodes from the backing store is much more expensive than accessing
+/// nodes that are already in memory, and this allows the costs to reflect
+/// that. The default is to charge nothing extra.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StateAccessCosts {
+    /// Cost for each node that has to be loaded from the backing store.
+    pub cold: u64,
+    /// Cost of a state operation that does not load any nodes.
+    pub warm: u64,
+}
+
+/// Counts of the accesses to the contract state, categorized in the same way
+/// as [StateAccessCosts]. These are reported in execution results to allow
+/// for calibration of the costs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StateAccessCounts {
+    /// Number of nodes loaded from the backing store.
+    pub cold: u64,
+    /// Number of state operations that did not load any nodes.
+    pub warm: u64,
+}
+
+/// Error raised when a contract attempts to modify its state while the state
+/// is read-only, see [InstanceState::with_read_only].
+#[derive(Debug)]
+pub struct StateModificationInReadOnly;
+
+impl std::fmt::Display for StateModificationInReadOnly {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        "Attempt to modify the state in a read-only entrypoint.".fmt(f)
+    }
+}
+
+/// Error raised when a contract attempts to transfer CCD, call a contract, or
+/// upgrade while its state is read-only, see [InstanceState::with_read_only].
+/// Any of these could modify the contract, directly or by a call back into it.
+#[derive(Debug)]
+pub struct OperationInReadOnly;
+
+impl std::fmt::Display for OperationInReadOnly {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        "Attempt to transfer, call a contract, or upgrade in a read-only entrypoint.".fmt(f)
+    }
+}
+
+/// Behaviour of the host that changed after the initial release of V1
+/// contracts, and thus depends on the protocol version. The node selects the
+/// features by protocol version, so that executions of deployed contracts do
+/// not change within a protocol version. The default has all features
+/// disabled, which is the behaviour of the initial release.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HostFeatures {
+    /// Whether memory is charged for when the memory is actually grown, and
+    /// limited to [MAX_MEMORY_PAGES](crate::constants::MAX_MEMORY_PAGES)
+    /// pages, see
+    /// [charge_memory_grow](crate::InterpreterEnergy::charge_memory_grow).
+    /// Otherwise every page requested by `memory.grow` is charged for, even if
+    /// the memory is not grown.
+    pub memory_accounting:       bool,
+    /// Whether the return value of an `invoke` is charged for by its size when
+    /// it is made available to the caller, see
+    /// [receive_return_value_cost](crate::constants::receive_return_value_cost),
+    /// and whether the total size of the return values an execution retains is
+    /// limited to
+    /// [MAX_RETAINED_RETURN_VALUES_SIZE](crate::constants::MAX_RETAINED_RETURN_VALUES_SIZE).
+    /// Otherwise only reading the return value is charged for.
+    pub return_value_accounting: bool,
+    /// Whether init functions may make transfers, call and query other
+    /// contracts, and query the chain, which interrupts their execution as it
+    /// does for receive functions. Otherwise these functions trap in init
+    /// functions.
+    pub init_interrupts:         bool,
+    /// Whether receive functions whose names follow the naming convention of
+    /// view entrypoints, see [is_view_entrypoint], are executed with a
+    /// read-only state, see [InstanceState::with_read_only]. Otherwise they
+    /// are executed like any other receive function.
+    pub read_only_views:         bool,
+}
+
+impl HostFeatures {
+    /// All features enabled.
+    pub const ALL: Self = Self {
+        memory_accounting:       true,
+        return_value_accounting: true,
+        init_interrupts:         true,
+        read_only_views:         true,
+    };
+
+    /// Features from their encoding as a bit set, as used in the FFI. Bit 0
+    /// (the least significant) enables
+    /// [memory_accounting](Self::memory_accounting), bit 1 enables
+    /// [return_value_accounting](Self::return_value_accounting), bit 2
+    /// enables [init_interrupts](Self::init_interrupts), and bit 3 enables
+    /// [read_only_views](Self::read_only_views). Bits that are not assigned to
+    /// a feature are ignored.
+    pub fn from_bits(bits: u64) -> Self {
+        Self {
+            memory_accounting:       bits & 1 != 0,
+            return_value_accounting: bits & 2 != 0,
+            init_interrupts:         bits & 4 != 0,
+            read_only_views:         bits & 8 != 0,
+        }
+    }
+}
+
+/// Depth of an execution in a chain of nested contract calls, together with
+/// the maximum depth that is allowed. The top-level call of a transaction has
+/// depth 0, and a contract called by an execution of depth `n` is executed
+/// with depth `n + 1`. See [InstanceState::with_call_depth].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CallDepth {
+    /// Depth of the execution.
+    pub depth: u32,
+    /// Maximum depth of an execution, if limited.
+    pub limit: Option<u32>,
+}
+
+impl CallDepth {
+    /// The call depth of a contract called by this execution, failing if it
+    /// would exceed the limit.
+    pub fn nested(self) -> Result<CallDepth, CallDepthExceeded> {
+        let depth = self.depth.saturating_add(1);
+        match self.limit {
+            Some(limit) if depth > limit => Err(CallDepthExceeded {
+                depth,
+            }),
+            _ => Ok(CallDepth {
+                depth,
+                limit: self.limit,
+            }),
+        }
+    }
+}
+
+/// Error raised when a contract attempts to call a contract, and the call
+/// would exceed the maximum call depth, see [InstanceState::with_call_depth].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallDepthExceeded {
+    /// The depth the call would have been executed at.
+    pub depth: u32,
+}
+
+impl std::fmt::Display for CallDepthExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Calling a contract would exceed the maximum call depth at depth {}.", self.depth)
+    }
+}
+
+/// Error raised when the return value of an `invoke` would exceed the total
+/// size of return values an execution can retain, see
+/// [constants::MAX_RETAINED_RETURN_VALUES_SIZE]. This is only checked with
+/// [return_value_accounting](HostFeatures::return_value_accounting).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReturnValuesTooLarge {
+    /// The total size of the return values including the one received.
+    pub size: usize,
+}
+
+impl std::fmt::Display for ReturnValuesTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Receiving the return value would exceed the maximum size of retained return values \
+             with a total of {} bytes.",
+            self.size
+        )
+    }
+}
+
+/// Whether the entrypoint of the given receive name, e.g., `contract.view`,
+/// follows the naming convention of view entrypoints, i.e., its name is `view`,
+/// or starts with the word `view` followed by `_` or an uppercase letter, as in
+/// `view_balance` or `viewBalance`. Names such as `viewer` do not follow it.
+pub fn is_view_entrypoint(receive_name: &str) -> bool {
+    let entrypoint = match receive_name.find('.') {
+        Some(idx) => &receive_name[idx + 1..],
+        None => return false,
+    };
+    match entrypoint.strip_prefix("view") {
+        Some(rest) => rest.chars().next().map_or(true, |c| c == '_' || c.is_ascii_uppercase()),
+        None => false,
+    }
 }
 
 /// first bit is ignored, the next 31 indicate a generation,
@@ -856,6 +1898,31 @@ impl trie::AllocCounter<trie::Value> for InterpreterEnergy {
     }
 }
 
+/// Energy together with the costs to charge it for traversing the state trie
+/// and allocating in it, according to the [CostTable] of the state.
+struct StateEnergy<'a> {
+    energy: &'a mut InterpreterEnergy,
+    costs:  &'a CostTable,
+}
+
+impl<'a> trie::TraversalCounter for StateEnergy<'a> {
+    type Err = anyhow::Error;
+
+    #[inline(always)]
+    fn count_key_traverse_part(&mut self, num: u64) -> Result<(), Self::Err> {
+        self.energy.tick_energy(self.costs.tree_traversal_step.saturating_mul(num))
+    }
+}
+
+impl<'a> trie::AllocCounter<trie::Value> for StateEnergy<'a> {
+    type Err = anyhow::Error;
+
+    #[inline(always)]
+    fn allocate(&mut self, data: &trie::Value) -> Result<(), Self::Err> {
+        self.energy.tick_energy(self.costs.additional_entry_byte.saturating_mul(data.len() as u64))
+    }
+}
+
 impl<'a, BackingStore: trie::BackingStoreLoad> InstanceState<'a, BackingStore> {
     pub fn new(
         current_generation: u32,
@@ -864,11 +1931,18 @@ impl<'a, BackingStore: trie::BackingStoreLoad> InstanceState<'a, BackingStore> {
     ) -> InstanceState<'a, BackingStore> {
         Self {
             current_generation,
-            backing_store,
+            backing_store: trie::LoadCounter::new(backing_store),
             changed: false,
             state_trie: state.lock(),
             iterators: Vec::new(),
             entry_mapping: Vec::new(),
+            access_costs: StateAccessCosts::default(),
+            access_counts: StateAccessCounts::default(),
+            costs: CostTable::V1,
+            read_only: false,
+            state_energy: None,
+            in_state_budget: false,
+            call_depth: CallDepth::default(),
         }
     }
 
@@ -885,24 +1959,219 @@ impl<'a, BackingStore: trie::BackingStoreLoad> InstanceState<'a, BackingStore> {
         if state_updated {
             Self {
                 current_generation: current_generation + 1,
-                backing_store,
-                changed: false,
-                state_trie: state.lock(),
-                iterators: Vec::new(),
-                entry_mapping: Vec::new(),
+                backing_store:      trie::LoadCounter::new(backing_store),
+                changed:            false,
+                state_trie:         state.lock(),
+                iterators:          Vec::new(),
+                entry_mapping:      Vec::new(),
+                access_costs:       StateAccessCosts::default(),
+                access_counts:      StateAccessCounts::default(),
+                costs:              CostTable::V1,
+                read_only:          false,
+                state_energy:       None,
+                in_state_budget:    false,
+                call_depth:         CallDepth::default(),
+                host_features:      HostFeatures::default(),
+                random_seed:        None,
             }
         } else {
             Self {
                 current_generation,
-                backing_store,
+                backing_store: trie::LoadCounter::new(backing_store),
                 changed: false,
                 state_trie: state.lock(),
                 iterators,
                 entry_mapping,
+                access_costs: StateAccessCosts::default(),
+                access_counts: StateAccessCounts::default(),
+                costs: CostTable::V1,
+                read_only: false,
+                state_energy: None,
+                in_state_budget: false,
+                call_depth: CallDepth::default(),
+                host_features: HostFeatures::default(),
+                random_seed: None,
             }
         }
     }
 
+    /// The work that freezing the current generation of the state would do,
+    /// see [trie::MutableState::freeze_cost].
+    pub(crate) fn freeze_cost(&self) -> trie::FreezeCost {
+        let mut cost = trie::FreezeCost::default();
+        self.state_trie.freeze_cost(&mut cost);
+        cost
+    }
+
+    /// Set the additional costs charged for state accesses. See
+    /// [StateAccessCosts] for details.
+    pub fn with_access_costs(mut self, access_costs: StateAccessCosts) -> Self {
+        self.access_costs = access_costs;
+        self
+    }
+
+    /// Set the costs of the host functions of executions with this state, see
+    /// [InvokeOptions::costs](super::InvokeOptions::costs).
+    pub(crate) fn with_cost_table(mut self, costs: CostTable) -> Self {
+        self.costs = costs;
+        self
+    }
+
+    /// The costs of the host functions of executions with this state.
+    pub fn cost_table(&self) -> &CostTable { &self.costs }
+
+    /// Set the behaviour of the host that depends on the protocol version, see
+    /// [InvokeOptions::host_features](super::InvokeOptions::host_features).
+    pub(crate) fn with_host_features(mut self, host_features: HostFeatures) -> Self {
+        self.host_features = host_features;
+        self
+    }
+
+    /// The behaviour of the host of executions with this state.
+    pub fn host_features(&self) -> HostFeatures { self.host_features }
+
+    /// Set the seed of the randomness returned by the `get_random` host
+    /// function, see
+    /// [InvokeOptions::random_seed](super::InvokeOptions::random_seed).
+    pub(crate) fn with_random_seed(mut self, random_seed: Option<v0::RandomSeed>) -> Self {
+        self.random_seed = random_seed;
+        self
+    }
+
+    /// Make the state read-only. Any attempt to modify it then fails with
+    /// [StateModificationInReadOnly], which terminates execution. Transfers,
+    /// calls to contracts, and upgrades fail with [OperationInReadOnly]. This
+    /// is intended for entrypoints that are advertised as not modifying the
+    /// state, e.g., those following the [is_view_entrypoint] convention.
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Check that the operation may be performed, i.e., that the state is not
+    /// read-only.
+    pub(crate) fn check_read_only(&self, interrupt: &Interrupt) -> anyhow::Result<()> {
+        if self.read_only {
+            match interrupt {
+                Interrupt::Transfer {
+                    ..
+                }
+                | Interrupt::Call {
+                    ..
+                }
+                | Interrupt::Upgrade {
+                    ..
+                } => bail!(OperationInReadOnly),
+                // Queries do not modify anything.
+                Interrupt::QueryContract {
+                    ..
+                }
+                | Interrupt::QueryAccountBalance {
+                    ..
+                }
+                | Interrupt::QueryExchangeRates {
+                    ..
+                } => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Set the depth of the execution in a chain of nested contract calls, and
+    /// the maximum depth. Calls to contracts that would exceed the maximum
+    /// fail with [CallDepthExceeded], which terminates execution. The depth is
+    /// retained when execution is resumed after an interrupt, and contracts
+    /// called to handle the interrupt should be executed with the depth
+    /// [ReceiveInterruptedState::nested_call_depth].
+    pub fn with_call_depth(mut self, call_depth: CallDepth) -> Self {
+        self.call_depth = call_depth;
+        self
+    }
+
+    /// Check that a contract may be called by the execution, i.e., that the
+    /// call does not exceed the maximum call depth.
+    pub(crate) fn check_call_depth(&self, interrupt: &Interrupt) -> anyhow::Result<()> {
+        if let Interrupt::Call {
+            ..
+        } = interrupt
+        {
+            if let Err(e) = self.call_depth.nested() {
+                bail!(e)
+            }
+        }
+        Ok(())
+    }
+
+    /// Charge state operations to a separate energy budget instead of the
+    /// energy of the execution. This includes the costs of state host
+    /// functions as well as the [StateAccessCosts]. If either budget is
+    /// exhausted execution ends with running out of energy.
+    pub fn with_state_energy(mut self, state_energy: Option<InterpreterEnergy>) -> Self {
+        self.state_energy = state_energy;
+        self
+    }
+
+    /// Start charging the separate budget for state operations, if there is
+    /// one. This swaps the given energy, which is the energy of the host, with
+    /// the state budget until [leave_state_budget](Self::leave_state_budget) is
+    /// called.
+    #[inline(always)]
+    pub(crate) fn enter_state_budget(&mut self, energy: &mut InterpreterEnergy) {
+        if let Some(state_energy) = self.state_energy.as_mut() {
+            if !self.in_state_budget {
+                std::mem::swap(state_energy, energy);
+                self.in_state_budget = true;
+            }
+        }
+    }
+
+    /// Restore the energy of the host after a state operation. This must be
+    /// called before the energy of the host is inspected, also if the state
+    /// operation failed. It does nothing if there is no state operation in
+    /// progress.
+    #[inline(always)]
+    pub(crate) fn leave_state_budget(&mut self, energy: &mut InterpreterEnergy) {
+        if let Some(state_energy) = self.state_energy.as_mut() {
+            if self.in_state_budget {
+                std::mem::swap(state_energy, energy);
+                self.in_state_budget = false;
+            }
+        }
+    }
+
+    /// Record that the state is about to be modified, failing if the state is
+    /// read-only.
+    #[inline(always)]
+    fn mark_changed(&mut self) -> StateResult<()> {
+        if self.read_only {
+            bail!(StateModificationInReadOnly)
+        }
+        self.changed = true;
+        Ok(())
+    }
+
+    /// The number of nodes loaded from the backing store so far.
+    #[inline(always)]
+    pub(crate) fn cold_loads(&self) -> u64 { self.backing_store.loads }
+
+    /// Record a state access and charge for it according to the configured
+    /// [StateAccessCosts]. The argument is the value of
+    /// [cold_loads](Self::cold_loads) before the access.
+    pub(crate) fn charge_access(
+        &mut self,
+        energy: &mut InterpreterEnergy,
+        cold_loads_before: u64,
+    ) -> anyhow::Result<()> {
+        let cold = self.backing_store.loads - cold_loads_before;
+        if cold > 0 {
+            self.access_counts.cold += cold;
+            energy.tick_energy(self.access_costs.cold.saturating_mul(cold))
+        } else {
+            self.access_counts.warm += 1;
+            energy.tick_energy(self.access_costs.warm)
+        }
+    }
+
     /// Lookup an entry and return an entry id if it exists,
     /// and (an encoding of) [None] otherwise.
     pub(crate) fn lookup_entry(&mut self, key: &[u8]) -> InstanceStateEntryOption {
@@ -920,7 +2189,7 @@ impl<'a, BackingStore: trie::BackingStoreLoad> InstanceState<'a, BackingStore> {
     /// subtree that is locked due to an iterator. In that case this returns (an
     /// encoding of) [None].
     pub(crate) fn create_entry(&mut self, key: &[u8]) -> StateResult<InstanceStateEntryOption> {
-        self.changed = true;
+        self.mark_changed()?;
         ensure!(key.len() <= constants::MAX_KEY_SIZE, "Maximum key length exceeded.");
         if let Ok(id) = self.state_trie.insert(&mut self.backing_store, key, Vec::new()) {
             let idx = self.entry_mapping.len();
@@ -936,7 +2205,7 @@ impl<'a, BackingStore: trie::BackingStoreLoad> InstanceState<'a, BackingStore> {
     /// - 1 if the entry did not exist, or was already invalidated.
     /// - 2 if an entry was deleted
     pub(crate) fn delete_entry(&mut self, key: &[u8]) -> anyhow::Result<u32> {
-        self.changed = true;
+        self.mark_changed()?;
         // as u32 is safe since keys are limited by MAX_KEY_SIZE which is less than 2^32
         // - 1
         if let Ok(deleted) = self.state_trie.delete(&mut self.backing_store, key) {
@@ -961,8 +2230,12 @@ impl<'a, BackingStore: trie::BackingStoreLoad> InstanceState<'a, BackingStore> {
         energy: &mut InterpreterEnergy,
         key: &[u8],
     ) -> StateResult<u32> {
-        self.changed = true;
-        if let Ok(b) = self.state_trie.delete_prefix(&mut self.backing_store, key, energy)? {
+        self.mark_changed()?;
+        let mut energy = StateEnergy {
+            energy,
+            costs: &self.costs,
+        };
+        if let Ok(b) = self.state_trie.delete_prefix(&mut self.backing_store, key, &mut energy)? {
             if b {
                 Ok(2)
             } else {
@@ -980,7 +2253,18 @@ impl<'a, BackingStore: trie::BackingStoreLoad> InstanceState<'a, BackingStore> {
     /// - Ok(Some(id)) with an iterator id in case an iterator is found. This
     ///   iterator will always yield at least one value.
     pub(crate) fn iterator(&mut self, prefix: &[u8]) -> InstanceStateIteratorResultOption {
-        if let Ok(iter) = self.state_trie.iter(&mut self.backing_store, prefix) {
+        self.iterator_at(prefix, trie::IteratorPosition::Start)
+    }
+
+    /// Like [InstanceState::iterator], but the iterator continues from the
+    /// given position. In contrast to iterators created by
+    /// [InstanceState::iterator] the iterator might not yield any values.
+    fn iterator_at(
+        &mut self,
+        prefix: &[u8],
+        position: trie::IteratorPosition,
+    ) -> InstanceStateIteratorResultOption {
+        if let Ok(iter) = self.state_trie.iter_at(&mut self.backing_store, prefix, position) {
             if let Some(iter) = iter {
                 let iter_id = self.iterators.len();
                 self.iterators.push(Some(iter));
@@ -1002,13 +2286,17 @@ impl<'a, BackingStore: trie::BackingStoreLoad> InstanceState<'a, BackingStore> {
         energy: &mut InterpreterEnergy,
         iter: InstanceStateIterator,
     ) -> StateResult<InstanceStateEntryResultOption> {
-        energy.tick_energy(constants::ITERATOR_NEXT_COST)?;
+        energy.tick_energy(self.costs.iterator_next)?;
         let (gen, idx) = iter.split();
         if gen != self.current_generation {
             return Ok(InstanceStateEntryResultOption::NEW_ERR);
         }
         if let Some(iter) = self.iterators.get_mut(idx).and_then(Option::as_mut) {
-            if let Some(id) = self.state_trie.next(&mut self.backing_store, iter, energy)? {
+            let mut energy = StateEnergy {
+                energy,
+                costs: &self.costs,
+            };
+            if let Some(id) = self.state_trie.next(&mut self.backing_store, iter, &mut energy)? {
                 let idx = self.entry_mapping.len();
                 self.entry_mapping.push(id);
                 Ok(InstanceStateEntryResultOption::new_ok_some(self.current_generation, idx))
@@ -1030,7 +2318,7 @@ impl<'a, BackingStore: trie::BackingStoreLoad> InstanceState<'a, BackingStore> {
         energy: &mut InterpreterEnergy,
         iter: InstanceStateIterator,
     ) -> anyhow::Result<u32> {
-        energy.tick_energy(constants::DELETE_ITERATOR_BASE_COST)?;
+        energy.tick_energy(self.costs.delete_iterator_base)?;
         let (gen, idx) = iter.split();
         if gen != self.current_generation {
             return Ok(u32::MAX);
@@ -1038,9 +2326,9 @@ impl<'a, BackingStore: trie::BackingStoreLoad> InstanceState<'a, BackingStore> {
         match self.iterators.get_mut(idx) {
             Some(iter) => match iter {
                 Some(existing_iter) => {
-                    energy.tick_energy(constants::delete_iterator_cost(
-                        existing_iter.get_key().len() as u32,
-                    ))?;
+                    energy.tick_energy(
+                        self.costs.delete_iterator.cost(existing_iter.get_key().len() as u32),
+                    )?;
                     // Unlock the nodes associated with this iterator.
                     self.state_trie.delete_iter(existing_iter);
                     // Finally we remove the iterator in the instance by setting it to `None`.
@@ -1092,6 +2380,122 @@ impl<'a, BackingStore: trie::BackingStoreLoad> InstanceState<'a, BackingStore> {
         }
     }
 
+    /// Return the resumption token of the iterator, or [None] if an invalid
+    /// iterator id was supplied. An iterator continuing from the current
+    /// position of the iterator can be created from the token with
+    /// [InstanceState::iterator_resume], also in a later invocation, so that
+    /// long iterations can span several invocations without keeping the
+    /// prefix locked in between. Contracts should treat the token as opaque.
+    /// It consists of
+    /// - a tag, 0 if the iterator has not returned any entries, 1 if it has,
+    ///   and 2 if it is exhausted
+    /// - the length of the prefix of the iterator as a big endian u32
+    /// - the prefix
+    /// - the key of the last returned entry if the tag is 1.
+    fn iterator_token(&self, iter: InstanceStateIterator) -> Option<Vec<u8>> {
+        let (gen, idx) = iter.split();
+        if gen != self.current_generation {
+            return None;
+        }
+        let iter = self.iterators.get(idx).and_then(Option::as_ref)?;
+        let root = iter.get_root();
+        let (tag, key): (u8, &[u8]) = match iter.position() {
+            trie::IteratorPosition::Start => (0, &[]),
+            trie::IteratorPosition::After(key) => (1, key),
+            trie::IteratorPosition::End => (2, &[]),
+        };
+        let mut token = Vec::with_capacity(5 + root.len() + key.len());
+        token.push(tag);
+        token.extend_from_slice(&(root.len() as u32).to_be_bytes());
+        token.extend_from_slice(root);
+        token.extend_from_slice(key);
+        Some(token)
+    }
+
+    /// Return the size (in bytes) of the resumption token of the iterator, see
+    /// [InstanceState::iterator_token]. Returns u32::MAX if an invalid iterator
+    /// id was supplied.
+    pub(crate) fn iterator_token_size(&self, iter: InstanceStateIterator) -> u32 {
+        self.iterator_token(iter).map_or(u32::MAX, |token| token.len() as u32)
+    }
+
+    /// Read a section of the resumption token of the iterator, and return how
+    /// much was read. Returns u32::MAX if an invalid iterator id was supplied.
+    pub(crate) fn iterator_token_read(
+        &self,
+        iter: InstanceStateIterator,
+        dest: &mut [u8],
+        offset: u32,
+    ) -> u32 {
+        if let Some(token) = self.iterator_token(iter) {
+            let offset = std::cmp::min(token.len(), offset as usize);
+            let num_copied = std::cmp::min(token.len().saturating_sub(offset), dest.len());
+            dest[0..num_copied].copy_from_slice(&token[offset..offset + num_copied]);
+            num_copied as u32
+        } else {
+            u32::MAX
+        }
+    }
+
+    /// Create an iterator from a resumption token produced by
+    /// [InstanceState::iterator_token]. The iterator continues after the last
+    /// entry returned by the iterator the token was produced from, as the state
+    /// is now. The result is encoded as for [InstanceState::iterator], except
+    /// that the iterator might not yield any values, and a malformed token is
+    /// an error.
+    pub(crate) fn iterator_resume(
+        &mut self,
+        token: &[u8],
+    ) -> anyhow::Result<InstanceStateIteratorResultOption> {
+        ensure!(token.len() >= 5, "Malformed iterator token.");
+        let prefix_len = u32::from_be_bytes([token[1], token[2], token[3], token[4]]) as usize;
+        ensure!(token.len() - 5 >= prefix_len, "Malformed iterator token.");
+        let (prefix, key) = token[5..].split_at(prefix_len);
+        let position = match token[0] {
+            0 if key.is_empty() => trie::IteratorPosition::Start,
+            1 => trie::IteratorPosition::After(key),
+            2 if key.is_empty() => trie::IteratorPosition::End,
+            _ => bail!("Malformed iterator token."),
+        };
+        Ok(self.iterator_at(prefix, position))
+    }
+
+    /// Count the entries the iterator has yet to return, up to the given limit,
+    /// without advancing it. Returns u32::MAX if an invalid iterator id was
+    /// supplied. This charges energy for traversing the tree as
+    /// [InstanceState::iterator_next] does, so the cost is bounded by the
+    /// limit.
+    pub(crate) fn iterator_remaining(
+        &mut self,
+        energy: &mut InterpreterEnergy,
+        iter: InstanceStateIterator,
+        limit: u32,
+    ) -> StateResult<u32> {
+        energy.tick_energy(self.costs.iterator_next)?;
+        let (gen, idx) = iter.split();
+        if gen != self.current_generation {
+            return Ok(u32::MAX);
+        }
+        let mut copy = if let Some(iter) = self.iterators.get(idx).and_then(Option::as_ref) {
+            iter.clone()
+        } else {
+            return Ok(u32::MAX);
+        };
+        // u32::MAX is reserved for invalid iterators.
+        let limit = std::cmp::min(limit, u32::MAX - 1);
+        let mut count = 0;
+        let mut energy = StateEnergy {
+            energy,
+            costs: &self.costs,
+        };
+        while count < limit
+            && self.state_trie.next(&mut self.backing_store, &mut copy, &mut energy)?.is_some()
+        {
+            count += 1;
+        }
+        Ok(count)
+    }
+
     /// Read a section of the entry, and return how much was read, or u32::MAX,
     /// in case the entry has already been invalidated.
     pub(crate) fn entry_read(
@@ -1131,13 +2535,19 @@ impl<'a, BackingStore: trie::BackingStoreLoad> InstanceState<'a, BackingStore> {
         src: &[u8],
         offset: u32,
     ) -> StateResult<u32> {
-        self.changed = true;
+        self.mark_changed()?;
         let (gen, idx) = entry.split();
         if gen != self.current_generation {
             return Ok(u32::MAX);
         }
         if let Some(entry) = self.entry_mapping.get(idx) {
-            if let Some(v) = self.state_trie.get_mut(*entry, &mut self.backing_store, energy)? {
+            let costs = &self.costs;
+            if let Some(v) =
+                self.state_trie.get_mut(*entry, &mut self.backing_store, &mut StateEnergy {
+                    energy: &mut *energy,
+                    costs,
+                })?
+            {
                 let offset = offset as usize;
                 if offset <= v.len() {
                     // by state invariants, v.len() <= MAX_ENTRY_SIZE.
@@ -1148,9 +2558,9 @@ impl<'a, BackingStore: trie::BackingStoreLoad> InstanceState<'a, BackingStore> {
                         offset.checked_add(src.len()).context("Too much data.")?,
                     );
                     if v.len() < end {
-                        energy.tick_energy(constants::additional_entry_size_cost(
-                            (end - v.len()) as u64,
-                        ))?;
+                        energy.tick_energy(
+                            costs.additional_entry_byte.saturating_mul((end - v.len()) as u64),
+                        )?;
                         v.resize(end, 0u8);
                     }
                     let num_bytes_to_write = end - offset;
@@ -1192,6 +2602,29 @@ impl<'a, BackingStore: trie::BackingStoreLoad> InstanceState<'a, BackingStore> {
         }
     }
 
+    /// Hash the value of the entry with the given algorithm. Returns [None] in
+    /// case the entry has already been invalidated. The cost of hashing, which
+    /// depends on the size of the entry, is charged before the digest is
+    /// computed.
+    pub(crate) fn entry_hash(
+        &mut self,
+        energy: &mut InterpreterEnergy,
+        entry: InstanceStateEntry,
+        algorithm: HashAlgorithm,
+    ) -> StateResult<Option<[u8; 32]>> {
+        let size = self.entry_size(entry);
+        if size == u32::MAX {
+            return Ok(None);
+        }
+        energy.tick_energy(algorithm.cost_with(&self.costs, size))?;
+        let (_, idx) = entry.split();
+        if let Some(entry) = self.entry_mapping.get(idx) {
+            Ok(self.state_trie.with_entry(*entry, &mut self.backing_store, |v| algorithm.digest(v)))
+        } else {
+            Ok(None)
+        }
+    }
+
     /// Resize the entry to the new size. Returns
     /// - 0 if this was unsuccessful because the new state is too big
     /// - u32::MAX if entry was already invalidated
@@ -1202,7 +2635,7 @@ impl<'a, BackingStore: trie::BackingStoreLoad> InstanceState<'a, BackingStore> {
         entry: InstanceStateEntry,
         new_size: u32,
     ) -> StateResult<u32> {
-        self.changed = true;
+        self.mark_changed()?;
         let (gen, idx) = entry.split();
         if gen != self.current_generation {
             return Ok(u32::MAX);
@@ -1212,11 +2645,13 @@ impl<'a, BackingStore: trie::BackingStoreLoad> InstanceState<'a, BackingStore> {
                 return Ok(0);
             }
             let new_size = u64::from(new_size);
+            let additional_entry_byte = self.costs.additional_entry_byte;
             if let Some(v) = self.state_trie.get_mut(
                 entry,
                 &mut self.backing_store,
                 &mut ResizeAllocateCounter {
                     new_size,
+                    additional_entry_byte,
                     energy,
                 },
             )? {
@@ -1225,9 +2660,7 @@ impl<'a, BackingStore: trie::BackingStoreLoad> InstanceState<'a, BackingStore> {
                     // `get_mut` above charged only for the energy in case the entry
                     // was borrowed. If we are increasing the size we also must charge
                     // if the entry is owned already, to prevent excessive state growth.
-                    energy.tick_energy(constants::additional_entry_size_cost(
-                        new_size - existing_len as u64,
-                    ))?;
+                    energy.tick_energy(additional_entry_byte * (new_size - existing_len as u64))?;
                 }
                 v.resize(new_size as usize, 0u8);
                 v.shrink_to_fit();
@@ -1250,8 +2683,10 @@ impl<'a, BackingStore: trie::BackingStoreLoad> InstanceState<'a, BackingStore> {
 /// [Vec::shrink_to_fit] inside [InstanceState::entry_resize]. We must not
 /// retain excess memory.
 struct ResizeAllocateCounter<'a> {
-    new_size: u64,
-    energy:   &'a mut InterpreterEnergy,
+    new_size:              u64,
+    /// See [CostTable::additional_entry_byte].
+    additional_entry_byte: u64,
+    energy:                &'a mut InterpreterEnergy,
 }
 
 impl<'a> trie::AllocCounter<trie::Value> for ResizeAllocateCounter<'a> {
@@ -1264,9 +2699,9 @@ impl<'a> trie::AllocCounter<trie::Value> for ResizeAllocateCounter<'a> {
     fn allocate(&mut self, data: &trie::Value) -> Result<(), Self::Err> {
         let existing_size = data.len() as u64;
         if self.new_size > existing_size {
-            self.energy.tick_energy(constants::additional_entry_size_cost(existing_size))
+            self.energy.tick_energy(self.additional_entry_byte * existing_size)
         } else {
-            self.energy.tick_energy(constants::additional_entry_size_cost(self.new_size))
+            self.energy.tick_energy(self.additional_entry_byte * self.new_size)
         }
     }
 }
@@ -6,8 +6,10 @@
  */
 
 use wasm_transform::{
-    artifact::{Artifact, CompiledFunction},
+    artifact::{artifact_stats, Artifact, CompiledFunction},
+    parse::{parse_skeleton, ParseError},
     utils::instantiate,
+    validate::{validate_module, ValidationConfig, ValidationError},
 };
 
 use crate::v0::ProcessedImports;
@@ -40,3 +42,164 @@ fn init_global_with_ref_test() {
         instantiate(&crate::v0::ConcordiumAllowedImports, &contract);
     assert!(res.is_err(), "Globals cannot be initialized with references to other globals.");
 }
+
+#[test]
+fn artifact_stats_test() {
+    let contract = std::fs::read("../testdata/contracts/global-offset-test.wasm").unwrap();
+    let artifact: Artifact<ProcessedImports, CompiledFunction> =
+        instantiate(&crate::v0::ConcordiumAllowedImports, &contract).unwrap();
+    let stats = artifact_stats(&artifact);
+    assert_eq!(stats.num_functions, 6, "The module defines 6 functions.");
+    assert_eq!(stats.num_imports, 1, "The module imports 1 function.");
+    assert_eq!(stats.max_locals, 2, "The assertion functions have 2 parameters.");
+    assert_eq!(stats.data_size, 13, "The data segments contain 13 bytes.");
+    assert!(stats.code_size > 0, "The artifact contains code.");
+}
+
+/// Instantiate a module that is expected to be rejected, and return the error.
+fn instantiate_error(bytes: &[u8]) -> anyhow::Error {
+    match instantiate::<ProcessedImports, _>(&crate::v0::ConcordiumAllowedImports, bytes) {
+        Ok(_) => panic!("The module should be rejected."),
+        Err(e) => e,
+    }
+}
+
+#[test]
+fn floating_point_hint_test() {
+    const HINT: &str = "Floating point numbers are not supported by smart contracts. Consider \
+                        using integer arithmetic instead";
+    // A module with a single type, of a function returning an f32.
+    let float_type =
+        [0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7D];
+    let e = instantiate_error(&float_type);
+    assert!(e.to_string().contains(HINT), "Floating point types are hinted at: {}", e);
+    // A module with a function that executes `f32.const 0; drop`.
+    let float_instruction = [
+        0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00, 0x03,
+        0x02, 0x01, 0x00, 0x0A, 0x0A, 0x01, 0x08, 0x00, 0x43, 0x00, 0x00, 0x00, 0x00, 0x1A, 0x0B,
+    ];
+    let e = instantiate_error(&float_instruction);
+    assert!(
+        matches!(
+            e.downcast_ref::<ParseError>(),
+            Some(ParseError::UnsupportedInstruction {
+                opcode: 0x43,
+            })
+        ),
+        "f32.const is not supported: {}",
+        e
+    );
+    assert!(e.to_string().contains(HINT), "Floating point instructions are hinted at: {}", e);
+}
+
+#[test]
+fn structured_errors_test() {
+    // A module with a single type, of a function returning an f32.
+    let float_module =
+        [0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7D];
+    let e = instantiate_error(&float_module);
+    assert!(
+        matches!(
+            e.downcast_ref::<ParseError>(),
+            Some(ParseError::UnsupportedValueType {
+                byte: 0x7D,
+            })
+        ),
+        "Floating point types are not supported: {}",
+        e
+    );
+    // The same module, truncated in the middle of the type section.
+    let e = instantiate_error(&float_module[..12]);
+    assert!(
+        matches!(e.downcast_ref::<ParseError>(), Some(ParseError::UnexpectedEnd)),
+        "The module is truncated: {}",
+        e
+    );
+    // A module that imports the function env.foo.
+    let import_module = [
+        0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00, 0x02,
+        0x0B, 0x01, 0x03, b'e', b'n', b'v', 0x03, b'f', b'o', b'o', 0x00, 0x00,
+    ];
+    let e = instantiate_error(&import_module);
+    assert!(
+        matches!(
+            e.downcast_ref::<ValidationError>(),
+            Some(ValidationError::DisallowedImport { .. })
+        ),
+        "Only imports from concordium are allowed: {}",
+        e
+    );
+}
+
+#[test]
+fn validation_config_test() {
+    let validate = |config: &ValidationConfig, bytes: &[u8]| {
+        validate_module(config, &crate::v0::ConcordiumAllowedImports, &parse_skeleton(bytes)?)
+    };
+    let not_enabled = |e: &anyhow::Error| {
+        matches!(
+            e.downcast_ref::<ValidationError>(),
+            Some(ValidationError::ProposalNotEnabled { .. })
+        )
+    };
+    // A module with a function that sign extends the constant 128 from 8 bits.
+    let sign_extension_module = [
+        0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7F,
+        0x03, 0x02, 0x01, 0x00, 0x0A, 0x08, 0x01, 0x06, 0x00, 0x41, 0x80, 0x01, 0xC0, 0x0B,
+    ];
+    let res = validate(&ValidationConfig::LEGACY, &sign_extension_module);
+    assert!(
+        matches!(res, Err(e) if not_enabled(&e)),
+        "Sign extension instructions are not enabled by the legacy rules."
+    );
+    assert!(
+        validate(&ValidationConfig::ALL, &sign_extension_module).is_ok(),
+        "Sign extension instructions can be enabled."
+    );
+    // A module that exports a mutable global.
+    let global_module = [
+        0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 0x06, 0x06, 0x01, 0x7F, 0x01, 0x41, 0x00,
+        0x0B, 0x07, 0x05, 0x01, 0x01, b'g', 0x03, 0x00,
+    ];
+    assert!(
+        validate(&ValidationConfig::LEGACY, &global_module).is_ok(),
+        "Exports of mutable globals are allowed by the legacy rules."
+    );
+    let config = ValidationConfig {
+        allow_mutable_global_exports: false,
+        ..ValidationConfig::ALL
+    };
+    assert!(
+        matches!(validate(&config, &global_module), Err(e) if not_enabled(&e)),
+        "Exports of mutable globals can be disabled."
+    );
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn wat_source_test() {
+    // The modules assembled from the text format are accepted or rejected just
+    // like the binary modules compiled from the same sources.
+    for name in ["global-offset-test", "mut-global-offset-test", "init-global-with-ref-test"].iter()
+    {
+        let source = std::fs::read(format!("../testdata/contracts/{}.wat", name)).unwrap();
+        let assembled =
+            crate::utils::module_from_source(&source).expect("Assembling should succeed.");
+        let binary = std::fs::read(format!("../testdata/contracts/{}.wasm", name)).unwrap();
+        assert_eq!(
+            crate::utils::module_from_source(&binary).unwrap().as_ref(),
+            binary.as_slice(),
+            "Binary modules are used as they are."
+        );
+        let from_text: anyhow::Result<Artifact<ProcessedImports, CompiledFunction>> =
+            instantiate(&crate::v0::ConcordiumAllowedImports, &assembled);
+        let from_binary: anyhow::Result<Artifact<ProcessedImports, CompiledFunction>> =
+            instantiate(&crate::v0::ConcordiumAllowedImports, &binary);
+        assert_eq!(
+            from_text.is_ok(),
+            from_binary.is_ok(),
+            "Module {} is handled differently.",
+            name
+        );
+    }
+}
@@ -0,0 +1,265 @@
+//! Golden tests of the binary encodings that are passed to the node over FFI,
+//! i.e., interrupts, results of V0 and V1 executions, and V0 actions.
+//!
+//! Each encoding is compared to a fixture in [FIXTURE_DIR]. The fixtures are
+//! also consumed by the tests of the Haskell side, so that both sides agree on
+//! the format. The values encoded by each fixture are listed in the README of
+//! the directory. If an encoding is changed deliberately the fixtures can be
+//! regenerated by running the tests with the environment variable
+//! `UPDATE_WIRE_FORMAT_FIXTURES` set, after which the Haskell side must be
+//! updated accordingly.
+use crate::{v0, v1};
+use anyhow::{ensure, Context};
+use concordium_contracts_common::{AccountAddress, Amount, ContractAddress, OwnedEntrypointName};
+use wasm_transform::artifact::CompiledFunction;
+
+/// Directory, relative to the crate root, that contains the fixtures.
+const FIXTURE_DIR: &str = "test-data/wire-format";
+
+/// The logs used in results that contain logs.
+fn logs() -> v0::Logs {
+    v0::Logs {
+        logs: vec![vec![1], vec![2, 3]],
+        tags: Default::default(),
+    }
+}
+
+fn interrupt_fixtures() -> anyhow::Result<Vec<(&'static str, Vec<u8>)>> {
+    let interrupts = [
+        ("interrupt-transfer", v1::Interrupt::Transfer {
+            to:     AccountAddress([1; 32]),
+            amount: Amount::from_micro_ccd(1_000_000),
+        }),
+        ("interrupt-call", v1::Interrupt::Call {
+            address:   ContractAddress {
+                index:    10,
+                subindex: 5,
+            },
+            parameter: vec![1, 2, 3],
+            name:      OwnedEntrypointName::new_unchecked("receive".into()),
+            amount:    Amount::from_micro_ccd(17),
+        }),
+        ("interrupt-query-contract", v1::Interrupt::QueryContract {
+            address: ContractAddress {
+                index:    3,
+                subindex: 0,
+            },
+            query:   v1::ContractQuery::StateSize,
+        }),
+        ("interrupt-query-account-balance", v1::Interrupt::QueryAccountBalance {
+            address: AccountAddress([2; 32]),
+        }),
+        ("interrupt-query-exchange-rates", v1::Interrupt::QueryExchangeRates {
+            conversion: v1::Conversion::EnergyToMicroCcd(1000),
+        }),
+        ("interrupt-upgrade", v1::Interrupt::Upgrade {
+            module_ref: [3; v1::MODULE_REFERENCE_SIZE],
+        }),
+    ];
+    let mut out = Vec::with_capacity(interrupts.len());
+    for (name, interrupt) in interrupts.iter() {
+        let mut bytes = Vec::new();
+        interrupt.to_bytes(&mut bytes)?;
+        out.push((*name, bytes));
+    }
+    Ok(out)
+}
+
+fn v0_fixtures() -> Vec<(&'static str, Vec<u8>)> {
+    use std::rc::Rc;
+    let send = v0::Action::Send {
+        data: Rc::new(v0::SendAction {
+            to_addr:   ContractAddress {
+                index:    10,
+                subindex: 5,
+            },
+            name:      b"c.receive".to_vec(),
+            amount:    17,
+            parameter: vec![1, 2, 3],
+        }),
+    };
+    let simple_transfer = v0::Action::SimpleTransfer {
+        data: Rc::new(v0::SimpleTransferAction {
+            to_addr: AccountAddress([1; 32]),
+            amount:  100,
+        }),
+    };
+    let or = v0::Action::Or {
+        l: 0,
+        r: 1,
+    };
+    let and = v0::Action::And {
+        l: 1,
+        r: 2,
+    };
+    vec![
+        ("v0-action-send", send.to_bytes()),
+        ("v0-action-simple-transfer", simple_transfer.to_bytes()),
+        ("v0-action-or", or.to_bytes()),
+        ("v0-action-and", and.to_bytes()),
+        ("v0-action-accept", v0::Action::Accept.to_bytes()),
+        ("v0-init-result-out-of-energy", v0::InitResult::OutOfEnergy.to_bytes()),
+        (
+            "v0-init-result-reject",
+            v0::InitResult::Reject {
+                reason:           -3,
+                remaining_energy: 1000,
+            }
+            .to_bytes(),
+        ),
+        (
+            "v0-init-result-success",
+            v0::InitResult::Success {
+                state:            v0::State::new(Some(&[1u8, 2, 3][..])),
+                logs:             logs(),
+                remaining_energy: 1000,
+            }
+            .to_bytes(),
+        ),
+        ("v0-receive-result-out-of-energy", v0::ReceiveResult::OutOfEnergy.to_bytes()),
+        (
+            "v0-receive-result-reject",
+            v0::ReceiveResult::Reject {
+                reason:           -3,
+                remaining_energy: 1000,
+            }
+            .to_bytes(),
+        ),
+        (
+            "v0-receive-result-success",
+            v0::ReceiveResult::Success {
+                state:            v0::State::new(Some(&[1u8, 2, 3][..])),
+                logs:             logs(),
+                actions:          vec![v0::Action::Accept, simple_transfer, or],
+                remaining_energy: 1000,
+            }
+            .to_bytes(),
+        ),
+    ]
+}
+
+/// The status encodings of V1 results. The return value and the state are
+/// passed separately, so they are not part of the fixtures.
+fn v1_fixtures() -> Vec<(&'static str, Vec<u8>)> {
+    type InitResult = v1::InitResult<CompiledFunction>;
+    type ReceiveResult = v1::ReceiveResult<CompiledFunction>;
+    vec![
+        ("v1-init-result-out-of-energy", InitResult::OutOfEnergy.extract().status),
+        (
+            "v1-init-result-trap",
+            InitResult::Trap {
+                error:            anyhow::anyhow!("Trap."),
+                remaining_energy: 1000,
+            }
+            .extract()
+            .status,
+        ),
+        (
+            "v1-init-result-reject",
+            InitResult::Reject {
+                reason:                 -3,
+                return_value:           vec![4, 5],
+                remaining_energy:       1000,
+                remaining_state_energy: None,
+            }
+            .extract()
+            .status,
+        ),
+        (
+            "v1-init-result-success",
+            InitResult::Success {
+                logs:                   logs(),
+                return_value:           vec![4, 5],
+                remaining_energy:       1000,
+                state:                  v1::trie::MutableState::initial_state(),
+                state_accesses:         Default::default(),
+                remaining_state_energy: None,
+            }
+            .extract()
+            .status,
+        ),
+        ("v1-receive-result-out-of-energy", ReceiveResult::OutOfEnergy.extract().status),
+        (
+            "v1-receive-result-trap",
+            ReceiveResult::Trap {
+                error:            anyhow::anyhow!("Trap."),
+                remaining_energy: 1000,
+            }
+            .extract()
+            .status,
+        ),
+        (
+            "v1-receive-result-reject",
+            ReceiveResult::Reject {
+                reason:                 -3,
+                return_value:           vec![4, 5],
+                remaining_energy:       1000,
+                remaining_state_energy: None,
+            }
+            .extract()
+            .status,
+        ),
+        (
+            "v1-receive-result-success",
+            ReceiveResult::Success {
+                logs:                   logs(),
+                state_changed:          true,
+                return_value:           vec![4, 5],
+                remaining_energy:       1000,
+                state_accesses:         Default::default(),
+                remaining_state_energy: None,
+                freeze_cost:            Default::default(),
+            }
+            .extract()
+            .status,
+        ),
+    ]
+}
+
+/// All the fixtures, as pairs of the name of the fixture file without the
+/// `.bin` extension, and the bytes it must contain.
+fn fixtures() -> anyhow::Result<Vec<(&'static str, Vec<u8>)>> {
+    let mut out = interrupt_fixtures()?;
+    out.extend(v0_fixtures());
+    out.extend(v1_fixtures());
+    Ok(out)
+}
+
+#[test]
+/// Check that the encodings match the fixtures, or regenerate the fixtures if
+/// requested.
+fn test_wire_format_golden() -> anyhow::Result<()> {
+    let update = std::env::var_os("UPDATE_WIRE_FORMAT_FIXTURES").is_some();
+    for (name, bytes) in fixtures()? {
+        let path = format!("{}/{}.bin", FIXTURE_DIR, name);
+        if update {
+            std::fs::write(&path, &bytes)?;
+        } else {
+            let golden = std::fs::read(&path).with_context(|| format!("Cannot read {}.", path))?;
+            ensure!(
+                golden == bytes,
+                "The encoding does not match {}: expected {:?}, got {:?}.",
+                path,
+                golden,
+                bytes
+            );
+        }
+    }
+    Ok(())
+}
+
+#[test]
+/// Check that every fixture in the directory is checked, so that fixtures of
+/// removed encodings do not linger.
+fn test_wire_format_fixtures_used() -> anyhow::Result<()> {
+    let names: Vec<_> = fixtures()?.into_iter().map(|(name, _)| format!("{}.bin", name)).collect();
+    for entry in std::fs::read_dir(FIXTURE_DIR)? {
+        let file_name = entry?.file_name().to_string_lossy().into_owned();
+        ensure!(
+            !file_name.ends_with(".bin") || names.contains(&file_name),
+            "The fixture {} is not checked by any test.",
+            file_name
+        );
+    }
+    Ok(())
+}
@@ -42,9 +42,16 @@
   ;; cryptographic primitives
   (import "concordium" "verify_ed25519_signature" (func $verify_ed25519_signature (param $public_key i32) (param $signature i32) (param $message i32) (param $message_len i32) (result i32)))
   (import "concordium" "verify_ecdsa_secp256k1_signature" (func $verify_ecdsa_secp256k1_signature (param $public_key i32) (param $signature i32) (param $message i32) (result i32)))
+  (import "concordium" "verify_ecdsa_secp256r1_signature" (func $verify_ecdsa_secp256r1_signature (param $public_key i32) (param $signature i32) (param $message i32) (result i32)))
   (import "concordium" "hash_sha2_256" (func $hash_sha2_256 (param $data i32) (param $data_len i32) (param $output i32)))
   (import "concordium" "hash_sha3_256" (func $hash_sha3_256 (param $data i32) (param $data_len i32) (param $output i32)))
   (import "concordium" "hash_keccak_256" (func $hash_keccak_256 (param $data i32) (param $data_len i32) (param $output i32)))
+  (import "concordium" "bls_verify" (func $bls_verify (param $public_key i32) (param $signature i32) (param $message i32) (param $message_len i32) (result i32)))
+  (import "concordium" "bls_aggregate_verify" (func $bls_aggregate_verify (param $public_keys i32) (param $num_keys i32) (param $signature i32) (param $message i32) (param $message_len i32) (result i32)))
+  (import "concordium" "bls_g1_add" (func $bls_g1_add (param $left i32) (param $right i32) (param $output i32) (result i32)))
+  (import "concordium" "bls_g1_mul" (func $bls_g1_mul (param $point i32) (param $scalar i32) (param $output i32) (result i32)))
+  (import "concordium" "bls_g2_add" (func $bls_g2_add (param $left i32) (param $right i32) (param $output i32) (result i32)))
+  (import "concordium" "bls_g2_mul" (func $bls_g2_mul (param $point i32) (param $scalar i32) (param $output i32) (result i32)))
 
   (func (export "hostfn.verify_ed25519_signature") (param i64) (result i32)
       (local $len i32)
@@ -62,6 +69,13 @@
       (return (i32.const 0))
   )
 
+  (func (export "hostfn.verify_ecdsa_secp256r1_signature") (param i64) (result i32)
+      (call $get_parameter_section (i32.const 0) (i32.const 0) (i32.const 129) (i32.const 0))
+      (i32.store (i32.const 0) (call $verify_ecdsa_secp256r1_signature (i32.const 0) (i32.const 33) (i32.const 97)))
+      (call $write_output (i32.const 0) (i32.const 4) (i32.const 0))
+      (return (i32.const 0))
+  )
+
   (func (export "hostfn.hash_sha2_256") (param i64) (result i32)
       (local $len i32)
       (call $get_parameter_section (i32.const 0) (i32.const 0) (i32.const 4) (i32.const 0))
@@ -88,6 +102,54 @@
       (call $write_output (i32.const 0) (i32.const 32) (i32.const 0))
       (return (i32.const 0))
   )
+  ;; The parameter is the public key, the signature, and the length of the message, which consists of zeros.
+  (func (export "hostfn.bls_verify") (param i64) (result i32)
+      (local $len i32)
+      (call $get_parameter_section (i32.const 0) (i32.const 0) (i32.const 148) (i32.const 0))
+      (local.set $len (i32.load (i32.const 144)))
+      (i32.store (i32.const 0) (call $bls_verify (i32.const 0) (i32.const 96) (i32.const 148) (local.get $len)))
+      (call $write_output (i32.const 0) (i32.const 4) (i32.const 0))
+      (return (i32.const 0))
+  )
+
+  ;; The parameter is the signature, the number of keys, the length of the message, which consists of zeros, and the public keys.
+  ;; The message is placed after the first page.
+  (func (export "hostfn.bls_aggregate_verify") (param i64) (result i32)
+      (call $get_parameter_section (i32.const 0) (i32.const 0) (call $get_parameter_size (i32.const 0)) (i32.const 0))
+      (i32.store (i32.const 0)
+                 (call $bls_aggregate_verify (i32.const 56) (i32.load (i32.const 48)) (i32.const 0) (i32.const 65536) (i32.load (i32.const 52))))
+      (call $write_output (i32.const 0) (i32.const 4) (i32.const 0))
+      (return (i32.const 0))
+  )
+
+  ;; The group operations write the result of the host function followed by the resulting point.
+  (func (export "hostfn.bls_g1_add") (param i64) (result i32)
+      (call $get_parameter_section (i32.const 0) (i32.const 4) (i32.const 96) (i32.const 0))
+      (i32.store (i32.const 0) (call $bls_g1_add (i32.const 4) (i32.const 52) (i32.const 4)))
+      (call $write_output (i32.const 0) (i32.const 52) (i32.const 0))
+      (return (i32.const 0))
+  )
+
+  (func (export "hostfn.bls_g1_mul") (param i64) (result i32)
+      (call $get_parameter_section (i32.const 0) (i32.const 4) (i32.const 80) (i32.const 0))
+      (i32.store (i32.const 0) (call $bls_g1_mul (i32.const 4) (i32.const 52) (i32.const 4)))
+      (call $write_output (i32.const 0) (i32.const 52) (i32.const 0))
+      (return (i32.const 0))
+  )
+
+  (func (export "hostfn.bls_g2_add") (param i64) (result i32)
+      (call $get_parameter_section (i32.const 0) (i32.const 4) (i32.const 192) (i32.const 0))
+      (i32.store (i32.const 0) (call $bls_g2_add (i32.const 4) (i32.const 100) (i32.const 4)))
+      (call $write_output (i32.const 0) (i32.const 100) (i32.const 0))
+      (return (i32.const 0))
+  )
+
+  (func (export "hostfn.bls_g2_mul") (param i64) (result i32)
+      (call $get_parameter_section (i32.const 0) (i32.const 4) (i32.const 128) (i32.const 0))
+      (i32.store (i32.const 0) (call $bls_g2_mul (i32.const 4) (i32.const 100) (i32.const 4)))
+      (call $write_output (i32.const 0) (i32.const 100) (i32.const 0))
+      (return (i32.const 0))
+  )
   (memory 2)
 )
 
@@ -0,0 +1,76 @@
+(module
+
+  ;; Init and receive functions used for integration testing of the invoke host function and
+  ;; of resuming execution after an interrupt.
+  ;; A general precondition is that at least one page of linear memory is allocated.
+
+  ;; Function parameter
+  (import "concordium" "get_parameter_section" (func $get_parameter_section (param $index i32) (param $write_location i32) (param $length i32) (param $offset i32) (result i32)))
+
+  ;; Function return value
+  (import "concordium" "write_output" (func $write_output (param $start i32) (param $length i32) (param $offset i32) (result i32)))
+
+  ;; Invoke another contract or a transfer.
+  (import "concordium" "invoke" (func $invoke (param $tag i32) (param $start i32) (param $length i32) (result i64)))
+
+  ;; Chain metadata
+  (import "concordium" "get_slot_time" (func $get_slot_time (result i64)))
+
+  ;; Helper functions
+
+  ;; Invoke the operation with the given tag, whose payload is in memory at the given start.
+  ;; The response of the invoke is written to the return value, followed by the
+  ;; first 8 bytes of the return value of the operation, if there is one.
+  (func $invoke_and_report (param $tag i32) (param $start i32) (param $length i32) (result i32)
+    (local $response i64)
+    (local.set $response (call $invoke (local.get $tag) (local.get $start) (local.get $length)))
+    (i64.store (i32.const 64) (local.get $response))
+    (call $write_output (i32.const 64) (i32.const 8) (i32.const 0))
+    (drop)
+    ;; The upper 24 bits of the response, without the flag for state changes, are the
+    ;; index of the return value, if there is one.
+    (if (i64.ne (i64.const 0) (i64.and (i64.shr_u (local.get $response) (i64.const 40)) (i64.const 0x7fffff)))
+      (then
+        (call $get_parameter_section
+              (i32.wrap_i64 (i64.and (i64.shr_u (local.get $response) (i64.const 40)) (i64.const 0x7fffff)))
+              (i32.const 72)
+              (i32.const 8)
+              (i32.const 0))
+        (drop)
+        (call $write_output (i32.const 72) (i32.const 8) (i32.const 8))
+        (drop)))
+    (i32.const 0))
+
+  ;; Transfer 1 microCCD to the account with address [2u8; 32].
+  (func $transfer (export "test.transfer") (param i64) (result i32)
+    (i64.store (i32.const 0) (i64.const 0x0202020202020202))
+    (i64.store (i32.const 8) (i64.const 0x0202020202020202))
+    (i64.store (i32.const 16) (i64.const 0x0202020202020202))
+    (i64.store (i32.const 24) (i64.const 0x0202020202020202))
+    (i64.store (i32.const 32) (i64.const 1))
+    (call $invoke_and_report (i32.const 0) (i32.const 0) (i32.const 40)))
+
+  ;; Call the entrypoint "receive" of the contract with index 1 and subindex 2, with an empty
+  ;; parameter and no CCD. The payload is in the data segment below.
+  (func $call (export "test.call") (param i64) (result i32)
+    (call $invoke_and_report (i32.const 1) (i32.const 256) (i32.const 35)))
+
+  ;; Make the same call as "test.call" from an init function.
+  (func $init (export "init_test") (param i64) (result i32)
+    (call $invoke_and_report (i32.const 1) (i32.const 256) (i32.const 35)))
+
+  ;; Read the slot time, which is denied by the import policy of pure contracts.
+  (func $slot_time (export "test.slot_time") (param i64) (result i32)
+    (drop (call $get_slot_time))
+    (i32.const 0))
+
+  ;; Make the same transfer as "test.transfer" from entrypoints whose names follow, and almost
+  ;; follow, the naming convention of view entrypoints.
+  (export "test.view" (func $transfer))
+  (export "test.viewer" (func $transfer))
+
+  (memory 1)
+  ;; The address of the contract, the length of the parameter, the name of the entrypoint
+  ;; prefixed by its length, and the amount.
+  (data (i32.const 256) "\01\00\00\00\00\00\00\00\02\00\00\00\00\00\00\00" "\00\00" "\07\00receive" "\00\00\00\00\00\00\00\00")
+)
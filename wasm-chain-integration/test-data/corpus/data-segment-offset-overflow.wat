@@ -0,0 +1,8 @@
+(module
+
+  ;; The end of the data segment overflows 32 bits, and must not wrap around
+  ;; into the memory.
+
+  (memory 1)
+
+  (data (i32.const -1) "ab"))
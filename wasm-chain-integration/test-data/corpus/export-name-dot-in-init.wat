@@ -0,0 +1,7 @@
+(module
+
+  ;; Init functions must not contain a '.', since it separates the contract
+  ;; name from the function name in receive functions.
+
+  (func (export "init_a.b") (param i64) (result i32)
+    (i32.const 0)))
@@ -0,0 +1,7 @@
+(module
+
+  ;; The name of the init function is exactly 100 bytes, which is the longest
+  ;; allowed name of a function.
+
+  (func (export "init_aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa") (param i64) (result i32)
+    (i32.const 0)))
@@ -0,0 +1,7 @@
+(module
+
+  ;; Names must be ASCII. This is checked when parsing, before exports are
+  ;; validated.
+
+  (func (export "init_\c3\a9") (param i64) (result i32)
+    (i32.const 0)))
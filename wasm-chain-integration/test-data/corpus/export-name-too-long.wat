@@ -0,0 +1,6 @@
+(module
+
+  ;; The name of the init function is 101 bytes, one more than allowed.
+
+  (func (export "init_aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa") (param i64) (result i32)
+    (i32.const 0)))
@@ -0,0 +1,7 @@
+(module
+
+  ;; Names of exported functions may only contain ASCII alphanumeric and
+  ;; punctuation characters. Spaces are neither.
+
+  (func (export "init_a b") (param i64) (result i32)
+    (i32.const 0)))
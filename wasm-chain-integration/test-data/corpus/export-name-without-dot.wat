@@ -0,0 +1,7 @@
+(module
+
+  ;; Functions that are not init functions must be receive functions, whose
+  ;; names are of the form "<contract>.<function>".
+
+  (func (export "receive") (param i64) (result i32)
+    (i32.const 0)))
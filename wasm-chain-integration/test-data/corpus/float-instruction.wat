@@ -0,0 +1,8 @@
+(module
+
+  ;; Floating point instructions are rejected when parsing, even if their
+  ;; result is immediately dropped.
+
+  (func (export "init_float") (param i64) (result i32)
+    (drop (f32.const 0))
+    (i32.const 0)))
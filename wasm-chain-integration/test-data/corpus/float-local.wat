@@ -0,0 +1,8 @@
+(module
+
+  ;; Floating point locals are rejected when parsing, even though the
+  ;; function type only uses integers.
+
+  (func (export "init_float") (param i64) (result i32)
+    (local f64)
+    (i32.const 0)))
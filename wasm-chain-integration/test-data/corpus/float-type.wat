@@ -0,0 +1,6 @@
+(module
+
+  ;; Floating point types are not supported. A module that mentions one in a
+  ;; type is rejected when parsing, even if the type is never used.
+
+  (type (func (result f32))))
@@ -0,0 +1,9 @@
+(module
+
+  ;; Loops are charged on every iteration, so an infinite loop runs out of
+  ;; energy.
+
+  (func (export "init_loop") (param i64) (result i32)
+    (loop $l
+      (br $l))
+    (i32.const 0)))
@@ -0,0 +1,10 @@
+(module
+
+  ;; The effective address of a load is the sum of the 32-bit address and the
+  ;; 32-bit offset. The sum overflows 32 bits, and must not wrap around.
+
+  (memory 1)
+
+  (func (export "init_load") (param i64) (result i32)
+    (drop (i32.load offset=4294967295 (i32.const -1)))
+    (i32.const 0)))
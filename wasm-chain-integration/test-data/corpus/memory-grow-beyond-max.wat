@@ -0,0 +1,9 @@
+(module
+
+  ;; Growing memory beyond the maximum of 512 pages returns -1, which is
+  ;; returned as the reject reason.
+
+  (memory 1)
+
+  (func (export "init_grow") (param i64) (result i32)
+    (memory.grow (i32.const 512))))
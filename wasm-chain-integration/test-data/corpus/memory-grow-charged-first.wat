@@ -0,0 +1,11 @@
+(module
+
+  ;; Growing memory is charged for the requested number of pages before it is
+  ;; checked against the maximum size of memory. Requesting u32::MAX pages
+  ;; thus runs out of energy instead of failing to grow.
+
+  (memory 1)
+
+  (func (export "init_grow") (param i64) (result i32)
+    (drop (memory.grow (i32.const -1)))
+    (i32.const 0)))
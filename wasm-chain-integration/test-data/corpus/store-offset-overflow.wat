@@ -0,0 +1,10 @@
+(module
+
+  ;; The effective address of a store is the sum of the 32-bit address and the
+  ;; 32-bit offset. The sum overflows 32 bits, and must not wrap around.
+
+  (memory 1)
+
+  (func (export "init_store") (param i64) (result i32)
+    (i64.store offset=4294967295 (i32.const 2) (i64.const 0))
+    (i32.const 0)))
@@ -102,7 +102,11 @@ fn validate(source: &[u8]) -> anyhow::Result<Module> {
     }
 
     let skel = wasm_transform::parse::parse_skeleton(source)?;
-    wasm_transform::validate::validate_module(&AllowAll, &skel)
+    wasm_transform::validate::validate_module(
+        &wasm_transform::validate::ValidationConfig::LEGACY,
+        &AllowAll,
+        &skel,
+    )
 }
 
 macro_rules! fail_test {
@@ -167,7 +171,7 @@ fn invoke_update(
     name: &str,
     args: &[Value],
 ) -> anyhow::Result<Option<Value>> {
-    match artifact.run(&mut TrapHost, name, args)? {
+    match artifact.run(&mut TrapHost, name, args).map_err(RuntimeError::into_anyhow)? {
         ExecutionOutcome::Success {
             result,
             ..
@@ -322,6 +326,13 @@ fn main() -> anyhow::Result<()> {
                                                     ParseError::NameTooLong => {}
                                                     ParseError::FuncNameTooLong => {}
                                                     ParseError::StartFunctionsNotSupported => {}
+                                                    // Other errors indicate a malformed module,
+                                                    // and are not expected for valid ones.
+                                                    _ => bail!(
+                                                        "Module {:?} not valid due to {}.",
+                                                        m.id,
+                                                        e
+                                                    ),
                                                 }
                                             } else if let Some(e) =
                                                 e.downcast_ref::<ValidationError>()
@@ -330,6 +341,12 @@ fn main() -> anyhow::Result<()> {
                                                     ValidationError::TooManyLocals {
                                                         ..
                                                     } => {}
+                                                    // As above, other errors are not expected.
+                                                    _ => bail!(
+                                                        "Module {:?} not valid due to {}.",
+                                                        m.id,
+                                                        e
+                                                    ),
                                                 }
                                             } else {
                                                 bail!("Module {:?} not valid due to {}.", m.id, e)
@@ -488,14 +505,11 @@ fn main() -> anyhow::Result<()> {
                                                                 )
                                                             ),
                                                             Err(e) => {
-                                                                if let Some(x) =
-                                                                    e.downcast_ref::<RuntimeError>()
+                                                                if let Some(
+                                                                    RuntimeError::DirectlyCallImport,
+                                                                ) = e.downcast_ref::<RuntimeError>()
                                                                 {
-                                                                    match x {
-                                                                RuntimeError::DirectlyCallImport => {
                                                                     // OK, this is our own restriction.
-                                                                }
-                                                            }
                                                                 } else if e
                                                                     .downcast_ref::<HostCallError>()
                                                                     .is_some()
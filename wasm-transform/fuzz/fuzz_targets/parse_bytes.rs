@@ -0,0 +1,12 @@
+#![no_main]
+
+/// Fuzz target that runs arbitrary bytes through parsing, validation,
+/// metering injection, compilation, and execution. Most inputs are rejected
+/// by the parser, so this mostly tests that parsing and validation never
+/// panic.
+use libfuzzer_sys::fuzz_target;
+use wasm_transform_fuzz::run_pipeline;
+
+fuzz_target!(|bytes: &[u8]| {
+    run_pipeline(bytes, &[]);
+});
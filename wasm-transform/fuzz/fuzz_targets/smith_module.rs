@@ -0,0 +1,19 @@
+#![no_main]
+
+/// Fuzz target that runs structurally valid modules generated by wasm-smith
+/// through validation, metering injection, compilation, and execution of all
+/// exported functions with arbitrary arguments.
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use wasm_smith::{ConfiguredModule, InterpreterConfig};
+use wasm_transform_fuzz::run_pipeline;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    module: ConfiguredModule<InterpreterConfig>,
+    args:   Vec<i64>,
+}
+
+fuzz_target!(|input: Input| {
+    run_pipeline(&input.module.to_bytes(), &input.args);
+});
@@ -0,0 +1,153 @@
+//! Shared parts of the fuzz targets of wasm-transform. Each target runs a
+//! module through the whole pipeline of parsing, validation, metering
+//! injection, compilation, serialization of the artifact, and execution of all
+//! exported functions. Failures at any stage are expected, but panics are not,
+//! and the fuzzer reports them as crashes.
+
+use anyhow::{bail, ensure};
+use wasm_transform::{
+    artifact::{Artifact, ArtifactNamedImport, CompiledFunctionBytes, RunnableCode},
+    machine::{Host, NoInterrupt, RunResult, RuntimeStack, Value},
+    output::Output,
+    parse::parse_skeleton,
+    types::{FunctionType, Name, ValueType},
+    utils::parse_artifact,
+    validate::{validate_module, ValidateImportExport, ValidationConfig},
+};
+
+/// Energy available to each execution of an exported function. Metered code
+/// charges energy at least once per loop iteration and function call, so this
+/// bounds the time spent executing a module.
+pub const ENERGY: u64 = 1_000_000;
+
+/// Maximum number of nested function calls, the same as for contracts.
+pub const MAX_ACTIVATION_FRAMES: u32 = 1024;
+
+/// Energy charged for each page of memory, the same as for contracts.
+pub const MEMORY_COST_FACTOR: u64 = 100;
+
+/// Allow all imports and exports. Only the metering functions are implemented
+/// by [MeteringHost], and calls to any other import trap.
+pub struct AllowAll;
+
+impl ValidateImportExport for AllowAll {
+    fn validate_import_function(
+        &self,
+        duplicate: bool,
+        _mod_name: &Name,
+        _item_name: &Name,
+        _ty: &FunctionType,
+    ) -> bool {
+        !duplicate
+    }
+
+    fn validate_export_function(&self, _item_name: &Name, _ty: &FunctionType) -> bool { true }
+}
+
+/// A host that implements the functions added by the metering transformation,
+/// and traps on calls to all other imports.
+pub struct MeteringHost {
+    /// Remaining energy.
+    pub energy:            u64,
+    /// Remaining number of nested calls.
+    pub activation_frames: u32,
+}
+
+impl MeteringHost {
+    pub fn new() -> Self {
+        Self {
+            energy:            ENERGY,
+            activation_frames: MAX_ACTIVATION_FRAMES,
+        }
+    }
+
+    fn charge(&mut self, amount: u64) -> RunResult<()> {
+        if let Some(energy) = self.energy.checked_sub(amount) {
+            self.energy = energy;
+            Ok(())
+        } else {
+            self.energy = 0;
+            bail!("Out of energy.")
+        }
+    }
+}
+
+impl Default for MeteringHost {
+    fn default() -> Self { Self::new() }
+}
+
+impl Host<ArtifactNamedImport> for MeteringHost {
+    type Interrupt = NoInterrupt;
+
+    fn tick_initial_memory(&mut self, num_pages: u32) -> RunResult<()> {
+        self.charge(u64::from(num_pages) * MEMORY_COST_FACTOR)
+    }
+
+    fn call(
+        &mut self,
+        f: &ArtifactNamedImport,
+        _memory: &mut Vec<u8>,
+        stack: &mut RuntimeStack,
+    ) -> RunResult<Option<NoInterrupt>> {
+        ensure!(f.get_mod_name().as_ref() == "concordium_metering", "Call to import {}.", f);
+        match f.get_item_name().as_ref() {
+            "account_energy" => self.charge(unsafe { stack.pop_u64() })?,
+            "track_call" => {
+                ensure!(self.activation_frames > 0, "Too many nested functions.");
+                self.activation_frames -= 1;
+            }
+            "track_return" => self.activation_frames += 1,
+            "account_memory" => {
+                self.charge(u64::from(unsafe { stack.peek_u32() }) * MEMORY_COST_FACTOR)?
+            }
+            _ => bail!("Call to import {}.", f),
+        }
+        Ok(None)
+    }
+}
+
+/// Run the module through the pipeline. The arguments of the exported
+/// functions are taken from `args` in order, and are 0 if there are not
+/// enough of them.
+pub fn run_pipeline(bytes: &[u8], args: &[i64]) {
+    let skeleton = match parse_skeleton(bytes) {
+        Ok(skeleton) => skeleton,
+        Err(_) => return,
+    };
+    let mut module = match validate_module(&ValidationConfig::ALL, &AllowAll, &skeleton) {
+        Ok(module) => module,
+        Err(_) => return,
+    };
+    module.inject_metering().expect("Metering a validated module should succeed.");
+    let artifact = match module.compile::<ArtifactNamedImport>() {
+        Ok(artifact) => artifact,
+        // Compilation rejects modules that exceed the limits of the interpreter.
+        Err(_) => return,
+    };
+    let mut out = Vec::new();
+    artifact.output(&mut out).expect("Serializing an artifact should succeed.");
+    let parsed: Artifact<ArtifactNamedImport, CompiledFunctionBytes> =
+        parse_artifact(&out).expect("A serialized artifact should parse.");
+    let mut reserialized = Vec::new();
+    parsed.output(&mut reserialized).expect("Serializing an artifact should succeed.");
+    assert_eq!(out, reserialized, "Serialization of artifacts should round-trip.");
+
+    let mut args = args.iter().copied().chain(std::iter::repeat(0));
+    for (name, &index) in parsed.export.iter() {
+        let code = &parsed.code[index as usize - parsed.imports.len()];
+        let values: Vec<Value> = code
+            .params()
+            .iter()
+            .zip(&mut args)
+            .map(|(ty, arg)| match ty {
+                ValueType::I32 => Value::I32(arg as i32),
+                ValueType::I64 => Value::I64(arg),
+            })
+            .collect();
+        let mut host = MeteringHost::new();
+        // Traps, including running out of energy, are expected. The energy
+        // bounds the execution, so it always terminates.
+        let _ = parsed.run(&mut host, name.as_ref(), &values);
+        assert!(host.activation_frames <= MAX_ACTIVATION_FRAMES, "Unbalanced call tracking.");
+    }
+}
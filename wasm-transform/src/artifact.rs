@@ -18,6 +18,8 @@ use std::{
     io::Write,
 };
 
+pub mod disassemble;
+
 #[derive(Copy, Clone)]
 /// Either a short or long integer.
 pub union StackValue {
@@ -213,6 +215,50 @@ pub trait TryFromImport: Sized {
     fn ty(&self) -> &FunctionType;
 }
 
+/// The interface of host functions that processed imports refer to. Imports
+/// are serialized in artifacts, e.g., as tags, so a host can only run the
+/// artifacts whose imports it knows. The version of the interface is recorded
+/// in the [ArtifactHeader] of serialized artifacts, and artifacts of a newer
+/// version than the host's are refused when they are parsed.
+pub trait HostInterface {
+    /// The version of the interface. It must be increased whenever imports
+    /// are added to it, or when artifacts rely on other changes to the host
+    /// that older hosts do not have.
+    const HOST_INTERFACE_VERSION: u32;
+}
+
+/// The current version of the serialization format of artifacts.
+pub const ARTIFACT_FORMAT_VERSION: u32 = 1;
+
+/// Bytes that serialized artifacts with an [ArtifactHeader] start with.
+/// Artifacts of format version 0 have no header, and start with the number of
+/// imports. The prefix is a non-minimal LEB128 encoding of 0, which is never
+/// written for the number of imports, so the two are distinguished.
+pub const ARTIFACT_HEADER_PREFIX: [u8; 2] = [0x80, 0x00];
+
+/// The versions that a serialized artifact starts with. It is written after
+/// the [ARTIFACT_HEADER_PREFIX] as two LEB128 encoded numbers. Artifacts
+/// without a header have format version 0 and host interface version 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArtifactHeader {
+    /// Version of the serialization format, at most [ARTIFACT_FORMAT_VERSION]
+    /// for artifacts that can be parsed.
+    pub format_version:         u32,
+    /// The [HOST_INTERFACE_VERSION](HostInterface::HOST_INTERFACE_VERSION) of
+    /// the imports of the artifact.
+    pub host_interface_version: u32,
+}
+
+impl ArtifactHeader {
+    /// The header of artifacts that are written with imports of type `I`.
+    pub fn current<I: HostInterface>() -> Self {
+        Self {
+            format_version:         ARTIFACT_FORMAT_VERSION,
+            host_interface_version: I::HOST_INTERFACE_VERSION,
+        }
+    }
+}
+
 /// An example of a processed import with minimal processing. Useful for testing
 /// and experimenting, but not for efficient execution.
 #[derive(Debug, Clone, Display)]
@@ -227,6 +273,12 @@ impl ArtifactNamedImport {
     pub fn matches(&self, mod_name: &str, item_name: &str) -> bool {
         self.mod_name.as_ref() == mod_name && self.item_name.as_ref() == item_name
     }
+
+    pub fn get_mod_name(&self) -> &Name { &self.mod_name }
+
+    pub fn get_item_name(&self) -> &Name { &self.item_name }
+
+    pub fn get_type(&self) -> &FunctionType { &self.ty }
 }
 
 impl TryFromImport for ArtifactNamedImport {
@@ -251,6 +303,12 @@ impl TryFromImport for ArtifactNamedImport {
     fn ty(&self) -> &FunctionType { &self.ty }
 }
 
+/// Named imports are resolved by name when they are run, so the serialization
+/// does not depend on the host.
+impl HostInterface for ArtifactNamedImport {
+    const HOST_INTERFACE_VERSION: u32 = 0;
+}
+
 pub struct LocalsIterator<'a> {
     /// Number of locals that are still going to be yielded from the iterator.
     remaining_items:      u32,
@@ -367,6 +425,11 @@ impl<'a> RunnableCode for CompiledFunctionBytes<'a> {
 /// "owned", in the sense of it being a vector of instructions. For efficient
 /// execution, and to avoid deserialization, the code is represented as a byte
 /// array (i.e., as as slice of bytes `&[u8]`) when we execute it on the node.
+///
+/// Running an artifact does not modify it. Each execution gets its own memory,
+/// globals, and stack when it starts, see [Artifact::run]. An artifact can thus
+/// be shared between threads, e.g., in an [Arc](std::sync::Arc), and run
+/// concurrently, as long as the import and code representations are [Sync].
 #[derive(Debug, Clone)]
 pub struct Artifact<ImportFunc, CompiledCode> {
     /// Imports by (module name, item name).
@@ -419,11 +482,48 @@ impl<'a, ImportFunc> From<BorrowedArtifact<'a, ImportFunc>> for OwnedArtifact<Im
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Summary of the size of an artifact. This is intended for tooling, so that
+/// the effect of changes to the source of a contract on the compiled artifact
+/// can be tracked, not just the effect on the size of the Wasm module.
+pub struct ArtifactStats {
+    /// Combined size, in bytes, of the compiled code of all functions.
+    pub code_size:     usize,
+    /// Number of functions defined in the artifact. This does not include
+    /// imported functions.
+    pub num_functions: usize,
+    /// Maximum number of locals of any function, including its parameters.
+    pub max_locals:    u32,
+    /// Number of imported functions.
+    pub num_imports:   usize,
+    /// Combined size, in bytes, of the data segments used to initialize
+    /// memory.
+    pub data_size:     usize,
+}
+
+/// Compute the [ArtifactStats] of the given artifact.
+pub fn artifact_stats<ImportFunc, CompiledCode: RunnableCode>(
+    artifact: &Artifact<ImportFunc, CompiledCode>,
+) -> ArtifactStats {
+    let code_size = artifact.code.iter().map(|c| c.code().len()).sum();
+    let max_locals =
+        artifact.code.iter().map(|c| c.num_params() + c.num_locals()).max().unwrap_or(0);
+    let data_size =
+        artifact.memory.as_ref().map_or(0, |m| m.init.iter().map(|d| d.init.len()).sum());
+    ArtifactStats {
+        code_size,
+        num_functions: artifact.code.len(),
+        max_locals,
+        num_imports: artifact.imports.len(),
+        data_size,
+    }
+}
+
 /// Internal opcode. This is mostly the same as OpCode, but with control
 /// instructions resolved to jumps in the instruction sequence, and function
 /// calls processed.
 #[repr(u8)]
-#[derive(Debug, num_enum::TryFromPrimitive)]
+#[derive(Debug, Copy, Clone, num_enum::TryFromPrimitive)]
 pub enum InternalOpcode {
     // Control instructions
     Unreachable = 0u8,
@@ -539,6 +639,17 @@ pub enum InternalOpcode {
     I32WrapI64,
     I64ExtendI32S,
     I64ExtendI32U,
+
+    // Instructions of proposals that must be enabled by the validation
+    // configuration. These are at the end so that the encoding of the other
+    // instructions in existing artifacts is unchanged.
+    I32Extend8S,
+    I32Extend16S,
+    I64Extend8S,
+    I64Extend16S,
+    I64Extend32S,
+    MemoryCopy,
+    MemoryFill,
 }
 
 /// Result of compilation. Either Ok(_) or an error indicating the reason.
@@ -919,6 +1030,8 @@ impl Handler<&OpCode> for BackPatch {
             }
             OpCode::MemorySize => self.out.push(MemorySize),
             OpCode::MemoryGrow => self.out.push(MemoryGrow),
+            OpCode::MemoryCopy => self.out.push(MemoryCopy),
+            OpCode::MemoryFill => self.out.push(MemoryFill),
             OpCode::I32Const(c) => {
                 self.out.push(I32Const);
                 self.out.push_i32(*c);
@@ -1110,6 +1223,21 @@ impl Handler<&OpCode> for BackPatch {
             OpCode::I64ExtendI32U => {
                 self.out.push(I64ExtendI32U);
             }
+            OpCode::I32Extend8S => {
+                self.out.push(I32Extend8S);
+            }
+            OpCode::I32Extend16S => {
+                self.out.push(I32Extend16S);
+            }
+            OpCode::I64Extend8S => {
+                self.out.push(I64Extend8S);
+            }
+            OpCode::I64Extend16S => {
+                self.out.push(I64Extend16S);
+            }
+            OpCode::I64Extend32S => {
+                self.out.push(I64Extend32S);
+            }
         }
         Ok(())
     }
@@ -0,0 +1,352 @@
+//! A textual rendering of compiled artifacts. This is intended for debugging
+//! compilation and the metering transformation, and the format is not stable.
+//!
+//! The rendering lists the imports, types, table, memory, globals, and exports
+//! of the artifact, followed by the compiled functions. For each function it
+//! lists the layout of its locals, and its instructions, one per line,
+//! prefixed by their offset in the code of the function. Jump targets are
+//! offsets in the same code. Calls to imported functions are annotated with
+//! the import, which makes the instructions injected by the metering
+//! transformation, i.e., calls to the accounting host functions, stand out.
+use super::{Artifact, InternalOpcode, RunnableCode, TryFromImport};
+use crate::types::{BlockType, FunctionType, GlobalInit, ValueType};
+use anyhow::{anyhow, ensure};
+use std::{
+    convert::{TryFrom, TryInto},
+    fmt::{self, Write},
+};
+
+/// Render the artifact as text. This fails if the code of a function is
+/// malformed, which can only happen for artifacts that were not produced by
+/// compilation, e.g., ones deserialized from corrupted bytes.
+pub fn disassemble<ImportFunc: TryFromImport + fmt::Display, CompiledCode: RunnableCode>(
+    artifact: &Artifact<ImportFunc, CompiledCode>,
+) -> anyhow::Result<String> {
+    let mut out = String::new();
+    writeln!(out, "imports:")?;
+    for (idx, import) in artifact.imports.iter().enumerate() {
+        writeln!(out, "  {}: {} {}", idx, import, DisplayFunctionType(import.ty()))?;
+    }
+    writeln!(out, "types:")?;
+    for (idx, ty) in artifact.ty.iter().enumerate() {
+        writeln!(out, "  {}: {}", idx, DisplayFunctionType(ty))?;
+    }
+    writeln!(out, "table:")?;
+    for (idx, f) in artifact.table.functions.iter().enumerate() {
+        if let Some(f) = f {
+            writeln!(out, "  {}: function {}", idx, f)?;
+        }
+    }
+    match &artifact.memory {
+        Some(memory) => {
+            writeln!(
+                out,
+                "memory: {} initial pages, {} maximum pages",
+                memory.init_size, memory.max_size
+            )?;
+            for data in memory.init.iter() {
+                writeln!(out, "  data at {}: {} bytes", data.offset, data.init.len())?;
+            }
+        }
+        None => writeln!(out, "memory: none")?,
+    }
+    writeln!(out, "globals:")?;
+    for (idx, init) in artifact.global.inits.iter().enumerate() {
+        match init {
+            GlobalInit::I32(x) => writeln!(out, "  {}: i32 {}", idx, x)?,
+            GlobalInit::I64(x) => writeln!(out, "  {}: i64 {}", idx, x)?,
+        }
+    }
+    writeln!(out, "exports:")?;
+    for (name, idx) in artifact.export.iter() {
+        writeln!(out, "  {}: function {}", name, idx)?;
+    }
+    for (i, code) in artifact.code.iter().enumerate() {
+        let idx = artifact.imports.len() + i;
+        disassemble_function(&mut out, idx, code, &artifact.imports)
+            .map_err(|e| anyhow!("Function {}: {}", idx, e))?;
+    }
+    Ok(out)
+}
+
+fn disassemble_function<ImportFunc: fmt::Display>(
+    out: &mut String,
+    idx: usize,
+    code: &impl RunnableCode,
+    imports: &[ImportFunc],
+) -> anyhow::Result<()> {
+    write!(out, "function {}: type {}, (", idx, code.type_idx())?;
+    write_value_types(out, code.params())?;
+    match code.return_type() {
+        BlockType::EmptyType => writeln!(out, ") -> ()")?,
+        BlockType::ValueType(ty) => writeln!(out, ") -> {}", value_type_name(ty))?,
+    }
+    // Locals are numbered after the parameters. Consecutive locals of the same
+    // type are grouped as a range of indices.
+    write!(out, "  locals:")?;
+    let mut start = code.num_params();
+    let mut current: Option<(ValueType, u32)> = None;
+    for ty in code.locals() {
+        match current {
+            Some((cur, n)) if cur == ty => current = Some((cur, n + 1)),
+            _ => {
+                if let Some((cur, n)) = current {
+                    write_locals_range(out, start, n, cur)?;
+                    start += n;
+                }
+                current = Some((ty, 1));
+            }
+        }
+    }
+    if let Some((cur, n)) = current {
+        write_locals_range(out, start, n, cur)?;
+    }
+    writeln!(out)?;
+    let mut reader = Reader {
+        code: code.code(),
+        pc:   0,
+    };
+    while reader.pc < reader.code.len() {
+        let pos = reader.pc;
+        let byte = reader.u8()?;
+        let opcode = InternalOpcode::try_from(byte)
+            .map_err(|_| anyhow!("Unknown opcode {:#04x} at {:04x}.", byte, pos))?;
+        write!(out, "  {:04x}: {}", pos, mnemonic(opcode))?;
+        write_immediates(out, &mut reader, opcode, imports)?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn write_immediates<ImportFunc: fmt::Display>(
+    out: &mut String,
+    reader: &mut Reader,
+    opcode: InternalOpcode,
+    imports: &[ImportFunc],
+) -> anyhow::Result<()> {
+    use InternalOpcode::*;
+    match opcode {
+        If => write!(out, " else @{:04x}", reader.u32()?)?,
+        Br | BrCarry | BrIf | BrIfCarry => write_jump(out, reader)?,
+        BrTable | BrTableCarry => {
+            let num_labels = reader.u16()?;
+            write!(out, " default")?;
+            write_jump(out, reader)?;
+            for label in 0..num_labels {
+                write!(out, ", {}", label)?;
+                write_jump(out, reader)?;
+            }
+        }
+        Call => {
+            let idx = reader.u32()?;
+            write!(out, " {}", idx)?;
+            if let Some(import) = imports.get(idx as usize) {
+                write!(out, " <{}>", import)?;
+            }
+        }
+        CallIndirect => write!(out, " type {}", reader.u32()?)?,
+        LocalGet | LocalSet | LocalTee | GlobalGet | GlobalSet => {
+            write!(out, " {}", reader.u16()?)?
+        }
+        I32Load | I64Load | I32Load8S | I32Load8U | I32Load16S | I32Load16U | I64Load8S
+        | I64Load8U | I64Load16S | I64Load16U | I64Load32S | I64Load32U | I32Store | I64Store
+        | I32Store8 | I32Store16 | I64Store8 | I64Store16 | I64Store32 => {
+            write!(out, " offset={}", reader.u32()?)?
+        }
+        I32Const => write!(out, " {}", reader.i32()?)?,
+        I64Const => write!(out, " {}", reader.i64()?)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Write a jump, i.e., the number of stack values to drop, and the target.
+fn write_jump(out: &mut String, reader: &mut Reader) -> anyhow::Result<()> {
+    let diff = reader.u32()?;
+    let target = reader.u32()?;
+    write!(out, " drop {} @{:04x}", diff, target)?;
+    Ok(())
+}
+
+fn write_locals_range(out: &mut String, start: u32, n: u32, ty: ValueType) -> fmt::Result {
+    if !out.ends_with(':') {
+        out.push(',');
+    }
+    if n == 1 {
+        write!(out, " {} {}", start, value_type_name(ty))
+    } else {
+        write!(out, " {}-{} {}", start, start + n - 1, value_type_name(ty))
+    }
+}
+
+fn write_value_types(out: &mut String, tys: &[ValueType]) -> fmt::Result {
+    for (i, ty) in tys.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        out.push_str(value_type_name(*ty));
+    }
+    Ok(())
+}
+
+fn value_type_name(ty: ValueType) -> &'static str {
+    match ty {
+        ValueType::I32 => "i32",
+        ValueType::I64 => "i64",
+    }
+}
+
+struct DisplayFunctionType<'a>(&'a FunctionType);
+
+impl<'a> fmt::Display for DisplayFunctionType<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut params = String::new();
+        write_value_types(&mut params, &self.0.parameters)?;
+        match self.0.result {
+            Some(ty) => write!(f, "({}) -> {}", params, value_type_name(ty)),
+            None => write!(f, "({}) -> ()", params),
+        }
+    }
+}
+
+/// A bounds checked reader of the immediate arguments of instructions.
+struct Reader<'a> {
+    code: &'a [u8],
+    pc:   usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> anyhow::Result<&'a [u8]> {
+        ensure!(self.code.len() - self.pc >= n, "Truncated instruction at {:04x}.", self.pc);
+        let bytes = &self.code[self.pc..self.pc + n];
+        self.pc += n;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> anyhow::Result<u8> { Ok(self.take(1)?[0]) }
+
+    fn u16(&mut self) -> anyhow::Result<u16> { Ok(u16::from_le_bytes(self.take(2)?.try_into()?)) }
+
+    fn u32(&mut self) -> anyhow::Result<u32> { Ok(u32::from_le_bytes(self.take(4)?.try_into()?)) }
+
+    fn i32(&mut self) -> anyhow::Result<i32> { Ok(i32::from_le_bytes(self.take(4)?.try_into()?)) }
+
+    fn i64(&mut self) -> anyhow::Result<i64> { Ok(i64::from_le_bytes(self.take(8)?.try_into()?)) }
+}
+
+/// The name of the instruction. These are the names of the corresponding Wasm
+/// instructions, except for the instructions that only exist in artifacts.
+fn mnemonic(opcode: InternalOpcode) -> &'static str {
+    use InternalOpcode::*;
+    match opcode {
+        Unreachable => "unreachable",
+        If => "if",
+        Br => "br",
+        BrCarry => "br_carry",
+        BrIf => "br_if",
+        BrIfCarry => "br_if_carry",
+        BrTable => "br_table",
+        BrTableCarry => "br_table_carry",
+        Return => "return",
+        Call => "call",
+        CallIndirect => "call_indirect",
+        Drop => "drop",
+        Select => "select",
+        LocalGet => "local.get",
+        LocalSet => "local.set",
+        LocalTee => "local.tee",
+        GlobalGet => "global.get",
+        GlobalSet => "global.set",
+        I32Load => "i32.load",
+        I64Load => "i64.load",
+        I32Load8S => "i32.load8_s",
+        I32Load8U => "i32.load8_u",
+        I32Load16S => "i32.load16_s",
+        I32Load16U => "i32.load16_u",
+        I64Load8S => "i64.load8_s",
+        I64Load8U => "i64.load8_u",
+        I64Load16S => "i64.load16_s",
+        I64Load16U => "i64.load16_u",
+        I64Load32S => "i64.load32_s",
+        I64Load32U => "i64.load32_u",
+        I32Store => "i32.store",
+        I64Store => "i64.store",
+        I32Store8 => "i32.store8",
+        I32Store16 => "i32.store16",
+        I64Store8 => "i64.store8",
+        I64Store16 => "i64.store16",
+        I64Store32 => "i64.store32",
+        MemorySize => "memory.size",
+        MemoryGrow => "memory.grow",
+        I32Const => "i32.const",
+        I64Const => "i64.const",
+        I32Eqz => "i32.eqz",
+        I32Eq => "i32.eq",
+        I32Ne => "i32.ne",
+        I32LtS => "i32.lt_s",
+        I32LtU => "i32.lt_u",
+        I32GtS => "i32.gt_s",
+        I32GtU => "i32.gt_u",
+        I32LeS => "i32.le_s",
+        I32LeU => "i32.le_u",
+        I32GeS => "i32.ge_s",
+        I32GeU => "i32.ge_u",
+        I64Eqz => "i64.eqz",
+        I64Eq => "i64.eq",
+        I64Ne => "i64.ne",
+        I64LtS => "i64.lt_s",
+        I64LtU => "i64.lt_u",
+        I64GtS => "i64.gt_s",
+        I64GtU => "i64.gt_u",
+        I64LeS => "i64.le_s",
+        I64LeU => "i64.le_u",
+        I64GeS => "i64.ge_s",
+        I64GeU => "i64.ge_u",
+        I32Clz => "i32.clz",
+        I32Ctz => "i32.ctz",
+        I32Popcnt => "i32.popcnt",
+        I32Add => "i32.add",
+        I32Sub => "i32.sub",
+        I32Mul => "i32.mul",
+        I32DivS => "i32.div_s",
+        I32DivU => "i32.div_u",
+        I32RemS => "i32.rem_s",
+        I32RemU => "i32.rem_u",
+        I32And => "i32.and",
+        I32Or => "i32.or",
+        I32Xor => "i32.xor",
+        I32Shl => "i32.shl",
+        I32ShrS => "i32.shr_s",
+        I32ShrU => "i32.shr_u",
+        I32Rotl => "i32.rotl",
+        I32Rotr => "i32.rotr",
+        I64Clz => "i64.clz",
+        I64Ctz => "i64.ctz",
+        I64Popcnt => "i64.popcnt",
+        I64Add => "i64.add",
+        I64Sub => "i64.sub",
+        I64Mul => "i64.mul",
+        I64DivS => "i64.div_s",
+        I64DivU => "i64.div_u",
+        I64RemS => "i64.rem_s",
+        I64RemU => "i64.rem_u",
+        I64And => "i64.and",
+        I64Or => "i64.or",
+        I64Xor => "i64.xor",
+        I64Shl => "i64.shl",
+        I64ShrS => "i64.shr_s",
+        I64ShrU => "i64.shr_u",
+        I64Rotl => "i64.rotl",
+        I64Rotr => "i64.rotr",
+        I32WrapI64 => "i32.wrap_i64",
+        I64ExtendI32S => "i64.extend_i32_s",
+        I64ExtendI32U => "i64.extend_i32_u",
+        I32Extend8S => "i32.extend8_s",
+        I32Extend16S => "i32.extend16_s",
+        I64Extend8S => "i64.extend8_s",
+        I64Extend16S => "i64.extend16_s",
+        I64Extend32S => "i64.extend32_s",
+        MemoryCopy => "memory.copy",
+        MemoryFill => "memory.fill",
+    }
+}
@@ -2,13 +2,14 @@
 
 use crate::{
     artifact::{
-        Artifact, ArtifactData, ArtifactLocal, ArtifactMemory, ArtifactNamedImport,
-        CompiledFunctionBytes, InstantiatedGlobals, InstantiatedTable,
+        Artifact, ArtifactData, ArtifactHeader, ArtifactLocal, ArtifactMemory, ArtifactNamedImport,
+        CompiledFunctionBytes, HostInterface, InstantiatedGlobals, InstantiatedTable,
+        ARTIFACT_FORMAT_VERSION, ARTIFACT_HEADER_PREFIX,
     },
     parse::*,
     types::{BlockType, FuncIndex, FunctionType, GlobalInit, Name, TypeIndex, ValueType},
 };
-use anyhow::bail;
+use anyhow::{bail, ensure};
 use std::{collections::BTreeMap, io::Cursor};
 
 impl<'a, Ctx: Copy> Parseable<'a, Ctx> for ArtifactLocal {
@@ -108,13 +109,53 @@ impl<'a, Ctx: Copy> Parseable<'a, Ctx> for ArtifactData {
     }
 }
 
+/// Artifacts without a header, i.e., that do not start with the
+/// [ARTIFACT_HEADER_PREFIX], have the header of format version 0, and nothing
+/// is consumed.
+impl<'a, Ctx: Copy> Parseable<'a, Ctx> for ArtifactHeader {
+    fn parse(ctx: Ctx, cursor: &mut Cursor<&'a [u8]>) -> ParseResult<Self> {
+        let rest = &cursor.get_ref()[cursor.position() as usize..];
+        if !rest.starts_with(&ARTIFACT_HEADER_PREFIX) {
+            return Ok(ArtifactHeader {
+                format_version:         0,
+                host_interface_version: 0,
+            });
+        }
+        cursor.set_position(cursor.position() + ARTIFACT_HEADER_PREFIX.len() as u64);
+        let format_version = cursor.next(ctx)?;
+        let host_interface_version = cursor.next(ctx)?;
+        Ok(ArtifactHeader {
+            format_version,
+            host_interface_version,
+        })
+    }
+}
+
 /// NB: This implementation is only meant to be used on trusted sources.
 /// It optimistically allocates memory, which could lead to problems if the
 /// input is untrusted.
-impl<'a, Ctx: Copy, I: Parseable<'a, Ctx>> Parseable<'a, Ctx>
+///
+/// Artifacts of a newer format version, or built for a newer version of the
+/// host interface `I`, are refused with a [ParseError].
+impl<'a, Ctx: Copy, I: Parseable<'a, Ctx> + HostInterface> Parseable<'a, Ctx>
     for Artifact<I, CompiledFunctionBytes<'a>>
 {
     fn parse(ctx: Ctx, cursor: &mut Cursor<&'a [u8]>) -> ParseResult<Self> {
+        let header = ArtifactHeader::parse(ctx, cursor)?;
+        ensure!(
+            header.format_version <= ARTIFACT_FORMAT_VERSION,
+            ParseError::UnsupportedArtifactFormat {
+                version:   header.format_version,
+                supported: ARTIFACT_FORMAT_VERSION,
+            }
+        );
+        ensure!(
+            header.host_interface_version <= I::HOST_INTERFACE_VERSION,
+            ParseError::NewerHostInterface {
+                version:   header.host_interface_version,
+                supported: I::HOST_INTERFACE_VERSION,
+            }
+        );
         let imports: Vec<I> = Vec::parse(ctx, cursor)?;
         let ty: Vec<FunctionType> = Vec::parse(ctx, cursor)?;
         let table = InstantiatedTable::parse(ctx, cursor)?;
@@ -67,8 +67,21 @@ impl Output for InstantiatedGlobals {
     fn output(&self, out: &mut impl Write) -> OutResult<()> { self.inits.output(out) }
 }
 
-impl<ImportFunc: Output, CompiledCode: RunnableCode> Output for Artifact<ImportFunc, CompiledCode> {
+impl Output for ArtifactHeader {
     fn output(&self, out: &mut impl Write) -> OutResult<()> {
+        out.write_all(&ARTIFACT_HEADER_PREFIX)?;
+        self.format_version.output(out)?;
+        self.host_interface_version.output(out)
+    }
+}
+
+/// Artifacts are written in the current format, with the
+/// [ArtifactHeader::current] of their imports.
+impl<ImportFunc: Output + HostInterface, CompiledCode: RunnableCode> Output
+    for Artifact<ImportFunc, CompiledCode>
+{
+    fn output(&self, out: &mut impl Write) -> OutResult<()> {
+        ArtifactHeader::current::<ImportFunc>().output(out)?;
         self.imports.output(out)?;
         self.ty.output(out)?;
         self.table.functions.output(out)?;
@@ -0,0 +1,80 @@
+//! Tests of the versioning of serialized artifacts, using the modules of the
+//! metering corpus.
+use crate::{
+    artifact::*,
+    metering_compatibility_test::{AllowAll, CORPUS},
+    output::Output,
+    parse::ParseError,
+    utils::{instantiate_with_metering, parse_artifact},
+};
+
+type Parsed<'a> = anyhow::Result<Artifact<ArtifactNamedImport, CompiledFunctionBytes<'a>>>;
+
+/// The header that artifacts with the given versions start with.
+fn header(format_version: u32, host_interface_version: u32) -> Vec<u8> {
+    let mut out = Vec::new();
+    ArtifactHeader {
+        format_version,
+        host_interface_version,
+    }
+    .output(&mut out)
+    .expect("Writing to a vector does not fail.");
+    out
+}
+
+#[test]
+/// Check that artifacts are written with the current header, and that
+/// artifacts without a header, as written before artifacts were versioned,
+/// are still parsed.
+fn artifact_header_backward_compatible() -> anyhow::Result<()> {
+    let current = header(ARTIFACT_FORMAT_VERSION, 0);
+    for path in CORPUS {
+        let artifact =
+            instantiate_with_metering::<ArtifactNamedImport, _>(&AllowAll, &std::fs::read(path)?)?;
+        let mut bytes = Vec::new();
+        artifact.output(&mut bytes)?;
+        anyhow::ensure!(bytes.starts_with(&current), "{} is written with the header.", path);
+        let legacy = &bytes[current.len()..];
+        let parsed: Parsed = parse_artifact(legacy);
+        let mut reserialized = Vec::new();
+        parsed?.output(&mut reserialized)?;
+        anyhow::ensure!(reserialized == bytes, "{} is parsed without a header.", path);
+    }
+    Ok(())
+}
+
+#[test]
+/// Check that artifacts of a newer format, or built for a newer host
+/// interface, are refused with a distinct error.
+fn artifact_header_refuses_newer_versions() -> anyhow::Result<()> {
+    let artifact =
+        instantiate_with_metering::<ArtifactNamedImport, _>(&AllowAll, &std::fs::read(CORPUS[0])?)?;
+    let mut bytes = Vec::new();
+    artifact.output(&mut bytes)?;
+    let body = &bytes[header(ARTIFACT_FORMAT_VERSION, 0).len()..];
+
+    let newer_host = [header(ARTIFACT_FORMAT_VERSION, 1).as_slice(), body].concat();
+    let parsed: Parsed = parse_artifact(&newer_host);
+    let err = parsed.err().expect("Artifacts for a newer host interface are refused.");
+    assert!(matches!(
+        err.downcast_ref::<ParseError>(),
+        Some(ParseError::NewerHostInterface {
+            version:   1,
+            supported: 0,
+        })
+    ));
+    assert!(err.to_string().starts_with("Artifact built for newer host interface"));
+
+    let newer_format = [header(ARTIFACT_FORMAT_VERSION + 1, 0).as_slice(), body].concat();
+    let err = parse_artifact::<ArtifactNamedImport>(&newer_format)
+        .err()
+        .expect("Artifacts of a newer format are refused.");
+    assert!(matches!(
+        err.downcast_ref::<ParseError>(),
+        Some(ParseError::UnsupportedArtifactFormat {
+            version,
+            supported: ARTIFACT_FORMAT_VERSION,
+        }) if *version == ARTIFACT_FORMAT_VERSION + 1
+    ));
+    Ok(())
+}
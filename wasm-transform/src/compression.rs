@@ -0,0 +1,68 @@
+//! Optional zstd compression of serialized artifacts and other persisted data,
+//! such as contract state files.
+//!
+//! Compressed data is recognized by the zstd [MAGIC] bytes at its start, so
+//! loading is transparent: data that does not start with them is returned as
+//! it is, which keeps uncompressed data written by earlier versions readable.
+//! Serialized artifacts of V0 and V1 contracts never start with the magic
+//! bytes, since they start with the number of imports followed by the tag of
+//! the first import, and `0xB5` is not a valid tag. Other formats must make
+//! the same guarantee, e.g., by starting with magic bytes of their own.
+//!
+//! Compressing and decompressing requires the `compression` feature. Without
+//! it compressed data is rejected with [CompressionError::NotSupported].
+use std::{borrow::Cow, io::Read};
+
+/// The magic bytes at the start of every zstd frame.
+pub const MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
+/// Compression level used by [compress]. This is the default level of zstd.
+pub const LEVEL: i32 = 3;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CompressionError {
+    #[error("The data is compressed, but support for compression is not enabled.")]
+    NotSupported,
+    #[error("Could not compress or decompress the data: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Whether the data is compressed, i.e., starts with the [MAGIC] bytes.
+pub fn is_compressed(bytes: &[u8]) -> bool { bytes.starts_with(&MAGIC) }
+
+/// Compress the data at the default [LEVEL].
+#[cfg(feature = "compression")]
+pub fn compress(bytes: &[u8]) -> Result<Vec<u8>, CompressionError> {
+    Ok(zstd::stream::encode_all(bytes, LEVEL)?)
+}
+
+/// Decompress the data if it is compressed, and otherwise return it unchanged.
+///
+/// This function is designed to only be used on trusted sources, since the
+/// size of the decompressed data is not bounded.
+pub fn decompress(bytes: &[u8]) -> Result<Cow<'_, [u8]>, CompressionError> {
+    if !is_compressed(bytes) {
+        return Ok(Cow::Borrowed(bytes));
+    }
+    let mut out = Vec::new();
+    decompressing_reader(bytes)?.read_to_end(&mut out)?;
+    Ok(Cow::Owned(out))
+}
+
+/// A reader that decompresses the given source, which must be compressed,
+/// i.e., start with the [MAGIC] bytes.
+#[cfg(feature = "compression")]
+pub fn decompressing_reader<'a>(
+    source: impl Read + 'a,
+) -> Result<Box<dyn Read + 'a>, CompressionError> {
+    Ok(Box::new(zstd::stream::Decoder::new(source)?))
+}
+
+/// A reader that decompresses the given source, which must be compressed,
+/// i.e., start with the [MAGIC] bytes.
+#[cfg(not(feature = "compression"))]
+pub fn decompressing_reader<'a>(
+    _source: impl Read + 'a,
+) -> Result<Box<dyn Read + 'a>, CompressionError> {
+    Err(CompressionError::NotSupported)
+}
@@ -0,0 +1,64 @@
+//! Tests of the compression of serialized artifacts, using the modules of the
+//! metering corpus.
+use crate::{
+    artifact::ArtifactNamedImport,
+    compression::*,
+    metering_compatibility_test::{AllowAll, CORPUS},
+    output::Output,
+    utils::instantiate_with_metering,
+};
+use std::borrow::Cow;
+
+/// Serialize the artifact of each module of the corpus.
+fn serialized_artifacts() -> anyhow::Result<Vec<Vec<u8>>> {
+    let mut out = Vec::new();
+    for path in CORPUS {
+        let artifact =
+            instantiate_with_metering::<ArtifactNamedImport, _>(&AllowAll, &std::fs::read(path)?)?;
+        let mut bytes = Vec::new();
+        artifact.output(&mut bytes)?;
+        out.push(bytes);
+    }
+    Ok(out)
+}
+
+#[test]
+/// Check that uncompressed artifacts are loaded as they are.
+fn uncompressed_artifacts_unchanged() -> anyhow::Result<()> {
+    for bytes in serialized_artifacts()? {
+        anyhow::ensure!(!is_compressed(&bytes), "Artifacts are not compressed.");
+        anyhow::ensure!(
+            matches!(decompress(&bytes)?, Cow::Borrowed(b) if b == bytes.as_slice()),
+            "Uncompressed data is returned unchanged."
+        );
+    }
+    Ok(())
+}
+
+#[cfg(feature = "compression")]
+#[test]
+/// Check that compressed artifacts are smaller, and decompress to the
+/// original artifact.
+fn compressed_artifacts_roundtrip() -> anyhow::Result<()> {
+    use crate::{
+        artifact::{Artifact, CompiledFunctionBytes},
+        utils::parse_artifact,
+    };
+    for bytes in serialized_artifacts()? {
+        let compressed = compress(&bytes)?;
+        anyhow::ensure!(is_compressed(&compressed), "Compressed data starts with the magic bytes.");
+        anyhow::ensure!(compressed.len() < bytes.len(), "Artifacts compress well.");
+        let decompressed = decompress(&compressed)?;
+        anyhow::ensure!(decompressed.as_ref() == bytes.as_slice(), "Decompression is the inverse.");
+        let _: Artifact<ArtifactNamedImport, CompiledFunctionBytes> =
+            parse_artifact(&decompressed)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "compression"))]
+#[test]
+/// Check that compressed data is rejected if compression is not supported.
+fn compressed_data_not_supported() {
+    assert!(matches!(decompress(&MAGIC), Err(CompressionError::NotSupported)));
+}
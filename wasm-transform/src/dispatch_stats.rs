@@ -0,0 +1,236 @@
+//! Statistics of the interpreter's dispatch loop. This is only available with
+//! the `dispatch-stats` feature, and is intended for analysing the interpreter
+//! on representative contracts, e.g., to find out which instructions are worth
+//! optimizing.
+//!
+//! While the feature is enabled every instruction executed by
+//! [Artifact::run](crate::artifact::Artifact::run) and
+//! [Artifact::run_config](crate::artifact::Artifact::run_config) is counted.
+//! The counts are accumulated per thread across all runs until they are
+//! retrieved with [take_dispatch_stats].
+//!
+//! The energy reported for each instruction is the count multiplied by the
+//! cost the metering transformation assigns to the instruction. For
+//! instructions whose cost depends on the context, i.e., `return` and the call
+//! instructions, only the part that does not depend on the context is
+//! included, so their energy is a lower bound. Energy charged by host
+//! functions, and for allocating memory, is not included.
+use crate::{
+    artifact::InternalOpcode,
+    metering_transformation::cost::{self, Energy},
+};
+use std::{cell::RefCell, convert::TryFrom};
+
+/// Number of distinct instructions of the interpreter. This must be kept in
+/// sync with the last variant of [InternalOpcode].
+const NUM_OPCODES: usize = InternalOpcode::MemoryFill as usize + 1;
+
+thread_local! {
+    static STATS: RefCell<DispatchStats> = RefCell::new(DispatchStats::new());
+}
+
+/// Retrieve the statistics accumulated by the current thread since the last
+/// call to this function, and reset them.
+pub fn take_dispatch_stats() -> DispatchStats {
+    STATS.with(|stats| std::mem::replace(&mut *stats.borrow_mut(), DispatchStats::new()))
+}
+
+/// Counts of executed instructions, indexed by their opcode.
+#[derive(Clone, Debug)]
+pub struct DispatchStats {
+    counts: [u64; NUM_OPCODES],
+}
+
+/// Statistics of a single instruction.
+#[derive(Copy, Clone, Debug)]
+pub struct OpcodeStats {
+    pub opcode: InternalOpcode,
+    /// Number of times the instruction was executed.
+    pub count:  u64,
+    /// Cumulative energy of all the executions of the instruction.
+    pub energy: Energy,
+}
+
+impl Default for DispatchStats {
+    fn default() -> Self { Self::new() }
+}
+
+impl DispatchStats {
+    pub fn new() -> Self {
+        Self {
+            counts: [0; NUM_OPCODES],
+        }
+    }
+
+    /// Add the counts of the given statistics to this one.
+    pub fn merge(&mut self, other: &DispatchStats) {
+        for (c, o) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *c += o;
+        }
+    }
+
+    /// Statistics of all instructions that were executed at least once, in
+    /// order of opcodes.
+    pub fn iter(&self) -> impl Iterator<Item = OpcodeStats> + '_ {
+        self.counts.iter().enumerate().filter(|(_, &count)| count > 0).map(|(i, &count)| {
+            // The index is always a valid opcode since the array has exactly one entry
+            // for each opcode.
+            let opcode = InternalOpcode::try_from(i as u8).expect("Index is a valid opcode.");
+            OpcodeStats {
+                opcode,
+                count,
+                energy: count * opcode_cost(opcode),
+            }
+        })
+    }
+
+    /// Total number of executed instructions.
+    pub fn total_count(&self) -> u64 { self.counts.iter().sum() }
+
+    /// Total energy of the executed instructions.
+    pub fn total_energy(&self) -> Energy { self.iter().map(|s| s.energy).sum() }
+
+    /// Return the `n` most frequently executed instructions, most frequent
+    /// first.
+    pub fn hottest(&self, n: usize) -> Vec<OpcodeStats> {
+        let mut stats = self.iter().collect::<Vec<_>>();
+        stats.sort_by(|l, r| r.count.cmp(&l.count).then(r.energy.cmp(&l.energy)));
+        stats.truncate(n);
+        stats
+    }
+
+    /// Produce a human readable report of the `n` most frequently executed
+    /// instructions, with their share of the total count and energy.
+    pub fn report(&self, n: usize) -> String {
+        use std::fmt::Write;
+        let total_count = self.total_count();
+        let total_energy = self.total_energy();
+        let percent = |x: u64, total: u64| {
+            if total == 0 {
+                0.0
+            } else {
+                100.0 * x as f64 / total as f64
+            }
+        };
+        let mut out = String::new();
+        // Writing to a string cannot fail, so the results are ignored.
+        let _ = writeln!(
+            out,
+            "{:<16} {:>14} {:>7} {:>14} {:>7}",
+            "opcode", "count", "%", "energy", "%"
+        );
+        for s in self.hottest(n) {
+            let _ = writeln!(
+                out,
+                "{:<16} {:>14} {:>6.2}% {:>14} {:>6.2}%",
+                format!("{:?}", s.opcode),
+                s.count,
+                percent(s.count, total_count),
+                s.energy,
+                percent(s.energy, total_energy)
+            );
+        }
+        let _ = writeln!(out, "{:<16} {:>14} {:>7} {:>14}", "total", total_count, "", total_energy);
+        out
+    }
+}
+
+/// Recorder used by the interpreter for a single invocation of `run_config`.
+/// Counts are recorded locally to keep the overhead in the dispatch loop low,
+/// and added to the thread's statistics when the recorder is dropped. This
+/// ensures that they are recorded regardless of how execution terminates.
+pub(crate) struct Recorder {
+    counts: [u64; NUM_OPCODES],
+}
+
+impl Recorder {
+    pub(crate) fn new() -> Self {
+        Self {
+            counts: [0; NUM_OPCODES],
+        }
+    }
+
+    #[cfg_attr(not(feature = "fuzz-coverage"), inline(always))]
+    pub(crate) fn record(&mut self, opcode: u8) {
+        if let Some(c) = self.counts.get_mut(opcode as usize) {
+            *c += 1;
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        // The thread local might already be destroyed if this runs during thread
+        // teardown. In that case the statistics are lost, which is fine.
+        let _ = STATS.try_with(|stats| {
+            for (c, o) in stats.borrow_mut().counts.iter_mut().zip(self.counts.iter()) {
+                *c += o;
+            }
+        });
+    }
+}
+
+/// The cost of the instruction, as assigned by the metering transformation.
+/// See the module documentation for the treatment of instructions whose cost
+/// depends on the context.
+fn opcode_cost(opcode: InternalOpcode) -> Energy {
+    use cost::*;
+    use InternalOpcode::*;
+    match opcode {
+        Unreachable => 0,
+        If => IF_STATEMENT,
+        Br => branch(0),
+        BrCarry => branch(1),
+        BrIf => BR_IF,
+        BrIfCarry => BR_IF,
+        BrTable => br_table(0),
+        BrTableCarry => br_table(1),
+        Return => branch(0),
+        Call => invoke_before(0, 0),
+        CallIndirect => call_indirect(0, 0),
+
+        Drop => DROP,
+        Select => SELECT,
+
+        LocalGet => GET_LOCAL,
+        LocalSet => SET_LOCAL,
+        LocalTee => TEE_LOCAL,
+        GlobalGet => GET_GLOBAL,
+        GlobalSet => SET_GLOBAL,
+
+        I32Load | I64Load | I32Load8S | I32Load8U | I32Load16S | I32Load16U | I64Load8S
+        | I64Load8U | I64Load16S | I64Load16U | I64Load32S | I64Load32U => LOAD_WORD,
+        I32Store => BOUNDS + 2 + 4,
+        I64Store => BOUNDS + 2 + 6,
+        I32Store8 => BOUNDS + 2 + 1,
+        I32Store16 => BOUNDS + 2 + 4,
+        I64Store8 => BOUNDS + 2 + 1 + 2,
+        I64Store16 => BOUNDS + 2 + 2 + 3,
+        I64Store32 => BOUNDS + 2 + 2 + 4,
+        MemorySize => MEMSIZE,
+        MemoryGrow => MEMGROW,
+        MemoryCopy => MEMCOPY,
+        MemoryFill => MEMFILL,
+
+        I32Const | I64Const => CONST,
+
+        I32Eqz | I64Eqz => SIMPLE_UNOP,
+        I32Eq | I32Ne | I32LtS | I32LtU | I32GtS | I32GtU | I32LeS | I32LeU | I32GeS | I32GeU => {
+            SIMPLE_BINOP
+        }
+        I64Eq | I64Ne | I64LtS | I64LtU | I64GtS | I64GtU | I64LeS | I64LeU | I64GeS | I64GeU => {
+            SIMPLE_BINOP
+        }
+
+        I32Clz | I32Ctz | I32Popcnt | I64Clz | I64Ctz | I64Popcnt => SIMPLE_UNOP,
+        I32Add | I32Sub | I32And | I32Or | I32Xor | I32Shl | I32ShrS | I32ShrU | I32Rotl
+        | I32Rotr | I64Add | I64Sub | I64And | I64Or | I64Xor | I64Shl | I64ShrS | I64ShrU
+        | I64Rotl | I64Rotr => SIMPLE_BINOP,
+        I32Mul | I64Mul => MUL,
+        I32DivS | I32DivU | I64DivS | I64DivU => DIV,
+        I32RemS | I32RemU | I64RemS | I64RemU => REM,
+
+        I32WrapI64 | I64ExtendI32S | I64ExtendI32U => SIMPLE_UNOP,
+        I32Extend8S | I32Extend16S | I64Extend8S | I64Extend16S | I64Extend32S => SIMPLE_UNOP,
+    }
+}
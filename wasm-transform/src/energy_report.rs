@@ -0,0 +1,77 @@
+//! A static report of the energy charged by the exported functions of a
+//! module after the metering transformation.
+//!
+//! The report is intended to let contract authors see how changes to their
+//! code affect its cost without running it. It is computed from the metered
+//! module alone, so it describes the code of each function, not the cost of
+//! any particular execution.
+use crate::{
+    metering_transformation::{FN_IDX_ACCOUNT_ENERGY, NUM_ADDED_FUNCTIONS},
+    resource_analysis::{function_types, max_operand_stack},
+    types::*,
+};
+use anyhow::{anyhow, bail};
+
+/// Energy report of a single exported function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionEnergyReport {
+    /// Name under which the function is exported.
+    pub name: Name,
+    /// Number of calls to the functions added by the metering transformation
+    /// in the body of the function.
+    pub num_accounting_instructions: usize,
+    /// Sum of the constant amounts of energy charged in the body of the
+    /// function, each counted once. Energy charged per byte by the bulk memory
+    /// instructions depends on their arguments and is not included.
+    pub static_energy: u64,
+    /// Worst-case height of the stack of the function, i.e., the number of its
+    /// locals, including parameters, and the maximum height of its operand
+    /// stack. This does not include the stacks of functions it calls.
+    pub max_stack_height: usize,
+}
+
+/// Compute the [FunctionEnergyReport] of each exported function of the given
+/// module, in the order of the exports. The module must be the result of
+/// [Module::inject_metering]. Exported imports are not supported since their
+/// cost is determined by the host.
+pub fn energy_report(module: &Module) -> anyhow::Result<Vec<FunctionEnergyReport>> {
+    let funcs = function_types(module);
+    let num_imports = module.import.imports.len();
+    let mut out = Vec::new();
+    for export in module.export.exports.iter() {
+        let index = match export.description {
+            ExportDescription::Func {
+                index,
+            } => index as usize,
+            _ => continue,
+        };
+        if index < num_imports {
+            bail!("Exported function {} is an import.", export.name);
+        }
+        let code = module
+            .code
+            .impls
+            .get(index - num_imports)
+            .ok_or_else(|| anyhow!("Exported function {} does not exist.", export.name))?;
+        let instrs = &code.expr.instrs;
+        let max_height = max_operand_stack(module, &funcs, code)?;
+        let num_accounting_instructions = instrs
+            .iter()
+            .filter(|i| matches!(i, OpCode::Call(idx) if *idx < NUM_ADDED_FUNCTIONS))
+            .count();
+        let static_energy = instrs
+            .windows(2)
+            .map(|w| match (&w[0], &w[1]) {
+                (OpCode::I64Const(e), OpCode::Call(FN_IDX_ACCOUNT_ENERGY)) => *e as u64,
+                _ => 0,
+            })
+            .sum();
+        out.push(FunctionEnergyReport {
+            name: export.name.clone(),
+            num_accounting_instructions,
+            static_energy,
+            max_stack_height: code.num_locals as usize + max_height,
+        });
+    }
+    Ok(out)
+}
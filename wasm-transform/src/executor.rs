@@ -0,0 +1,47 @@
+//! Abstraction over the ways of executing a compiled module. The [Artifact]
+//! produced by compilation is executed by the interpreter of the
+//! [machine](crate::machine) module, which is what the chain uses. With the
+//! `jit` feature a module can instead be compiled to native code by wasmtime,
+//! see [jit::JitModule]. This is much faster for long running functions, and is
+//! intended to shorten test cycles when testing contracts locally.
+//!
+//! Both executors run the metered module and call the host for the metering
+//! imports in the same way, so they charge the same energy and fail at the
+//! same point when running out of it. This is checked by differential tests,
+//! which run the example contracts with both.
+use crate::{
+    artifact::{Artifact, RunnableCode, TryFromImport},
+    machine::{ExecutionOutcome, ExecutionResult, Host, Value},
+};
+
+#[cfg(feature = "jit")]
+pub mod jit;
+
+/// A module that is ready to be executed with a host that handles the imports
+/// `I`.
+pub trait Executor<I> {
+    /// Run the exported function with the given arguments, see
+    /// [Artifact::run].
+    fn run<H: Host<I>>(
+        &self,
+        host: &mut H,
+        name: &str,
+        args: &[Value],
+    ) -> ExecutionResult<ExecutionOutcome<H::Interrupt>>;
+
+    /// Whether the module exports a function with the given name.
+    fn has_entrypoint(&self, name: &str) -> bool;
+}
+
+impl<I: TryFromImport, R: RunnableCode> Executor<I> for Artifact<I, R> {
+    fn run<H: Host<I>>(
+        &self,
+        host: &mut H,
+        name: &str,
+        args: &[Value],
+    ) -> ExecutionResult<ExecutionOutcome<H::Interrupt>> {
+        Artifact::run(self, host, name, args)
+    }
+
+    fn has_entrypoint(&self, name: &str) -> bool { Artifact::has_entrypoint(self, name) }
+}
@@ -0,0 +1,441 @@
+//! Execution of modules by compiling them to native code with wasmtime.
+//!
+//! The module is written out in the binary format after it has been validated
+//! and metered, see [Output for Module](crate::output), and compiled by
+//! wasmtime. Its imports, including the metering imports, are implemented by
+//! calling the [Host] with the same stack of arguments as the interpreter
+//! does, so hosts work unchanged. There are two differences to the
+//! interpreter.
+//! - Host functions get a copy of the memory of the instance, which is written
+//!   back after the call. The metering imports do not access memory, so they
+//!   are called without a copy, but other host functions are slower than with
+//!   the interpreter.
+//! - Execution cannot be interrupted and resumed, since the state of native
+//!   code cannot be captured. A host function that returns an interrupt fails
+//!   with [JitError::InterruptNotSupported].
+use super::Executor;
+use crate::{
+    artifact::{CompileResult, TryFromImport},
+    constants::{MAX_NUM_PAGES, PAGE_SIZE},
+    machine::*,
+    output::Output,
+    types::*,
+};
+use anyhow::{anyhow, ensure};
+use std::{collections::BTreeMap, convert::TryInto};
+use wasmtime::{
+    Caller, Engine, Extern, Func, FuncType, Instance, Memory, Store, Trap, Val, ValType,
+};
+
+/// Name of the module of the metering imports. These do not access memory.
+const METERING_MODULE: &str = "concordium_metering";
+
+/// Name under which the memory is exported, so that the host functions can
+/// access it, if the module does not export it itself.
+const MEMORY_EXPORT: &str = "concordium_jit_memory";
+
+/// wasmtime does not report the index of an undefined function that is called
+/// indirectly, so it is reported as this in [TrapReason::UndefinedFunction].
+pub const UNKNOWN_TABLE_INDEX: u32 = u32::MAX;
+
+/// Errors specific to execution with wasmtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum JitError {
+    #[error("Host functions that interrupt execution are not supported by the JIT executor.")]
+    InterruptNotSupported,
+    #[error("A host function changed the size of the memory.")]
+    MemoryResized,
+    /// The host failed, and the error is reported separately.
+    #[error("Host function failed.")]
+    HostFailed,
+}
+
+/// A module compiled to native code, together with the processed imports that
+/// are passed to the host when they are called.
+pub struct JitModule<I> {
+    engine:         Engine,
+    module:         wasmtime::Module,
+    imports:        Vec<I>,
+    /// The type of each import, and whether it may access memory.
+    import_types:   Vec<(FunctionType, bool)>,
+    /// The types of the functions defined by the module.
+    function_types: Vec<FunctionType>,
+    /// The exported functions.
+    exports:        BTreeMap<Name, FuncIndex>,
+    /// The name under which the memory is exported, if there is one.
+    memory_export:  Option<String>,
+    /// The initial number of pages of the memory, if there is one.
+    initial_memory: Option<u32>,
+}
+
+impl<I: TryFromImport> JitModule<I> {
+    /// Compile a validated module. It should be metered, otherwise execution
+    /// is not limited by energy.
+    pub fn new(mut module: Module) -> CompileResult<Self> {
+        let import_types = module
+            .import
+            .imports
+            .iter()
+            .map(|import| {
+                let ImportDescription::Func {
+                    type_idx,
+                } = import.description;
+                let ty = module.ty.get(type_idx).ok_or_else(|| anyhow!("Non-existent type."))?;
+                Ok((ty.as_ref().clone(), import.mod_name.as_ref() != METERING_MODULE))
+            })
+            .collect::<CompileResult<Vec<_>>>()?;
+        let function_types =
+            module.code.impls.iter().map(|code| code.ty.as_ref().clone()).collect();
+        let mut exports = BTreeMap::new();
+        let mut memory_export = None;
+        for export in module.export.exports.iter() {
+            match export.description {
+                ExportDescription::Func {
+                    index,
+                } => {
+                    exports.insert(export.name.clone(), index);
+                }
+                ExportDescription::Memory => memory_export = Some(export.name.name.clone()),
+                _ => (),
+            }
+        }
+        if module.memory.memory_type.is_some() && memory_export.is_none() {
+            ensure!(
+                module.export.exports.iter().all(|export| export.name.as_ref() != MEMORY_EXPORT),
+                "The export {} is reserved.",
+                MEMORY_EXPORT
+            );
+            module.export.exports.push(Export {
+                name:        Name::from(MEMORY_EXPORT),
+                description: ExportDescription::Memory,
+            });
+            memory_export = Some(MEMORY_EXPORT.into());
+        }
+        let initial_memory = module.memory.memory_type.map(|ty| ty.limits.min);
+        let mut bytes = Vec::new();
+        module.output(&mut bytes)?;
+        let engine = Engine::default();
+        let compiled = wasmtime::Module::new(&engine, &bytes)?;
+        let ty = module.ty.types.iter().map(|ty| ty.as_ref().clone()).collect::<Vec<_>>();
+        let imports = module
+            .import
+            .imports
+            .into_iter()
+            .map(|import| I::try_from_import(&ty, import))
+            .collect::<CompileResult<_>>()?;
+        Ok(Self {
+            engine,
+            module: compiled,
+            imports,
+            import_types,
+            function_types,
+            exports,
+            memory_export,
+            initial_memory,
+        })
+    }
+}
+
+/// The operations of the [Host] that are needed by the imports, without the
+/// type of the host and its imports, so that they can be stored in the
+/// [Store].
+trait HostCalls {
+    fn call(
+        &mut self,
+        import: usize,
+        memory: &mut Vec<u8>,
+        stack: &mut RuntimeStack,
+    ) -> RunResult<()>;
+
+    fn tick_memory_grow(&mut self, current_pages: u32, num_pages: u32) -> RunResult<()>;
+}
+
+struct Calls<'a, I, H> {
+    host:    &'a mut H,
+    imports: &'a [I],
+}
+
+impl<'a, I, H: Host<I>> HostCalls for Calls<'a, I, H> {
+    fn call(
+        &mut self,
+        import: usize,
+        memory: &mut Vec<u8>,
+        stack: &mut RuntimeStack,
+    ) -> RunResult<()> {
+        match self.host.call(&self.imports[import], memory, stack)? {
+            None => Ok(()),
+            Some(_) => Err(JitError::InterruptNotSupported.into()),
+        }
+    }
+
+    fn tick_memory_grow(&mut self, current_pages: u32, num_pages: u32) -> RunResult<()> {
+        self.host.tick_memory_grow(current_pages, num_pages)
+    }
+}
+
+/// The data of the [Store] of an execution.
+struct StoreData {
+    /// The host, with its lifetime erased. It is only used while
+    /// [Executor::run] borrows the host, since the store is dropped before
+    /// that returns.
+    calls:        *mut (dyn HostCalls + 'static),
+    /// The memory of the instance, once it is instantiated.
+    memory:       Option<Memory>,
+    /// The copy of the memory that is passed to host functions.
+    buffer:       Vec<u8>,
+    /// The stack of arguments and results of host functions.
+    stack:        RuntimeStack,
+    /// The error of the host, if a host function or memory accounting failed.
+    error:        Option<RuntimeError>,
+    /// Whether the instance has been instantiated. Before that the memory is
+    /// allocated, which the host is charged for separately.
+    instantiated: bool,
+}
+
+impl StoreData {
+    /// Record an error of the host, and return the error that makes wasmtime
+    /// stop execution.
+    fn fail(&mut self, e: anyhow::Error) -> anyhow::Error {
+        self.error = Some(RuntimeError::from_host(e));
+        JitError::HostFailed.into()
+    }
+}
+
+impl wasmtime::ResourceLimiter for StoreData {
+    fn memory_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> anyhow::Result<bool> {
+        if maximum.map_or(false, |max| desired > max)
+            || desired > (MAX_NUM_PAGES * PAGE_SIZE) as usize
+        {
+            return Ok(false);
+        }
+        if !self.instantiated || desired == current {
+            return Ok(true);
+        }
+        let current_pages = (current / PAGE_SIZE as usize) as u32;
+        let num_pages = ((desired - current) / PAGE_SIZE as usize) as u32;
+        // Safe since the host outlives the store, see [StoreData::calls].
+        match unsafe { (*self.calls).tick_memory_grow(current_pages, num_pages) } {
+            Ok(()) => Ok(true),
+            Err(e) => Err(self.fail(e)),
+        }
+    }
+
+    fn table_growing(
+        &mut self,
+        _current: u32,
+        desired: u32,
+        maximum: Option<u32>,
+    ) -> anyhow::Result<bool> {
+        Ok(maximum.map_or(true, |max| desired <= max))
+    }
+}
+
+fn val_type(ty: ValueType) -> ValType {
+    match ty {
+        ValueType::I32 => ValType::I32,
+        ValueType::I64 => ValType::I64,
+    }
+}
+
+fn to_val(value: Value) -> Val {
+    match value {
+        Value::I32(x) => Val::I32(x),
+        Value::I64(x) => Val::I64(x),
+    }
+}
+
+/// Call the import with the given index, as the interpreter does.
+fn call_host(
+    mut caller: Caller<'_, StoreData>,
+    import: usize,
+    uses_memory: bool,
+    result: Option<ValueType>,
+    params: &[Val],
+    results: &mut [Val],
+) -> anyhow::Result<()> {
+    let memory = if uses_memory {
+        caller.data().memory
+    } else {
+        None
+    };
+    let mut buffer = std::mem::take(&mut caller.data_mut().buffer);
+    buffer.clear();
+    if let Some(memory) = memory {
+        buffer.extend_from_slice(memory.data(&caller));
+    }
+    let data = caller.data_mut();
+    data.stack.set_pos(0);
+    for param in params {
+        match param {
+            Val::I32(x) => data.stack.push_value(*x),
+            Val::I64(x) => data.stack.push_value(*x),
+            _ => return Err(anyhow!("Only i32 and i64 values are supported.")),
+        }
+    }
+    let calls = data.calls;
+    // Safe since the host outlives the store, see [StoreData::calls].
+    if let Err(e) = unsafe { (*calls).call(import, &mut buffer, &mut data.stack) } {
+        data.buffer = buffer;
+        return Err(data.fail(e));
+    }
+    if let Some(ty) = result {
+        let value = data.stack.pop();
+        results[0] = match ty {
+            ValueType::I32 => Val::I32(unsafe { value.short }),
+            ValueType::I64 => Val::I64(unsafe { value.long }),
+        };
+    }
+    if let Some(memory) = memory {
+        let data = memory.data_mut(&mut caller);
+        if data.len() != buffer.len() {
+            return Err(caller.data_mut().fail(JitError::MemoryResized.into()));
+        }
+        data.copy_from_slice(&buffer);
+    }
+    caller.data_mut().buffer = buffer;
+    Ok(())
+}
+
+/// Convert an error of wasmtime that is not caused by the host.
+fn from_wasmtime(e: anyhow::Error) -> RuntimeError {
+    match e.downcast_ref::<Trap>() {
+        Some(Trap::UnreachableCodeReached) => TrapReason::Unreachable.into(),
+        Some(Trap::MemoryOutOfBounds) => TrapReason::MemoryOutOfBounds.into(),
+        Some(Trap::IntegerDivisionByZero) | Some(Trap::IntegerOverflow) => {
+            TrapReason::IntegerDivision.into()
+        }
+        Some(Trap::IndirectCallToNull) | Some(Trap::TableOutOfBounds) => {
+            TrapReason::UndefinedFunction(UNKNOWN_TABLE_INDEX).into()
+        }
+        Some(Trap::BadSignature) => TrapReason::IndirectCallTypeMismatch.into(),
+        _ => RuntimeError::HostCallError(e),
+    }
+}
+
+impl<I> JitModule<I> {
+    /// Check the arguments of the exported function against its type, in the
+    /// same way as [Artifact::run](crate::artifact::Artifact::run).
+    fn check_entrypoint(&self, name: &str, args: &[Value]) -> ExecutionResult<&FunctionType> {
+        let index = *self
+            .exports
+            .get(name)
+            .ok_or_else(|| RuntimeError::MissingEntrypoint(name.to_string()))?;
+        if (index as usize) < self.imports.len() {
+            return Err(RuntimeError::DirectlyCallImport);
+        }
+        let ty = self
+            .function_types
+            .get(index as usize - self.imports.len())
+            .ok_or(RuntimeError::MalformedArtifact("Accessing non-existent code."))?;
+        let num_args: u32 = args
+            .len()
+            .try_into()
+            .map_err(|_| RuntimeError::InvalidArguments("Too many arguments.".into()))?;
+        if ty.parameters.len() != num_args as usize {
+            return Err(RuntimeError::InvalidArguments(format!(
+                "The number of arguments does not match the number of parameters {} != {}.",
+                num_args,
+                ty.parameters.len(),
+            )));
+        }
+        for (p, actual) in ty.parameters.iter().zip(args.iter()) {
+            let actual_ty = ValueType::from(*actual);
+            if *p != actual_ty {
+                return Err(RuntimeError::InvalidArguments(format!(
+                    "Argument of incorrect type: actual {:#?}, expected {:#?}.",
+                    actual_ty, *p
+                )));
+            }
+        }
+        Ok(ty)
+    }
+
+    /// Instantiate the module in the store and call the exported function.
+    fn instantiate_and_call(
+        &self,
+        store: &mut Store<StoreData>,
+        name: &str,
+        args: &[Value],
+        ty: &FunctionType,
+    ) -> anyhow::Result<(Option<Value>, Vec<u8>)> {
+        let mut imports = Vec::with_capacity(self.import_types.len());
+        for (import, (import_ty, uses_memory)) in self.import_types.iter().enumerate() {
+            let func_type = FuncType::new(
+                import_ty.parameters.iter().copied().map(val_type),
+                import_ty.result.map(val_type),
+            );
+            let uses_memory = *uses_memory;
+            let result = import_ty.result;
+            let func = Func::new(&mut *store, func_type, move |caller, params, results| {
+                call_host(caller, import, uses_memory, result, params, results)
+            });
+            imports.push(Extern::from(func));
+        }
+        let instance = Instance::new(&mut *store, &self.module, &imports)?;
+        let memory = match self.memory_export.as_ref() {
+            Some(export) => instance.get_memory(&mut *store, export),
+            None => None,
+        };
+        store.data_mut().memory = memory;
+        store.data_mut().instantiated = true;
+        let func = instance
+            .get_func(&mut *store, name)
+            .ok_or_else(|| anyhow!("The export {} is not a function.", name))?;
+        let params = args.iter().copied().map(to_val).collect::<Vec<_>>();
+        let mut results = ty.result.iter().map(|_| Val::I64(0)).collect::<Vec<_>>();
+        func.call(&mut *store, &params, &mut results)?;
+        let result = match results.first() {
+            Some(Val::I32(x)) => Some(Value::I32(*x)),
+            Some(Val::I64(x)) => Some(Value::I64(*x)),
+            Some(_) => return Err(anyhow!("Only i32 and i64 values are supported.")),
+            None => None,
+        };
+        let memory = memory.map(|memory| memory.data(&*store).to_vec()).unwrap_or_default();
+        Ok((result, memory))
+    }
+}
+
+impl<I> Executor<I> for JitModule<I> {
+    fn run<H: Host<I>>(
+        &self,
+        host: &mut H,
+        name: &str,
+        args: &[Value],
+    ) -> ExecutionResult<ExecutionOutcome<H::Interrupt>> {
+        let ty = self.check_entrypoint(name, args)?;
+        if let Some(pages) = self.initial_memory {
+            host.tick_initial_memory(pages).map_err(RuntimeError::from_host)?;
+        }
+        let mut calls = Calls {
+            host,
+            imports: &self.imports,
+        };
+        let calls: *mut (dyn HostCalls + '_) = &mut calls;
+        // The store, which is the only place the pointer is kept, is dropped
+        // at the end of this function, while the host is still borrowed.
+        let calls: *mut (dyn HostCalls + 'static) = unsafe { std::mem::transmute(calls) };
+        let mut store = Store::new(&self.engine, StoreData {
+            calls,
+            memory: None,
+            buffer: Vec::new(),
+            stack: RuntimeStack::with_capacity(16),
+            error: None,
+            instantiated: false,
+        });
+        store.limiter(|data| data as &mut dyn wasmtime::ResourceLimiter);
+        match self.instantiate_and_call(&mut store, name, args, ty) {
+            Ok((result, memory)) => Ok(ExecutionOutcome::Success {
+                result,
+                memory,
+            }),
+            Err(e) => Err(store.data_mut().error.take().unwrap_or_else(|| from_wasmtime(e))),
+        }
+    }
+
+    fn has_entrypoint(&self, name: &str) -> bool { self.exports.contains_key(name) }
+}
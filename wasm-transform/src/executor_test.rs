@@ -0,0 +1,143 @@
+//! Tests of the executors.
+//!
+//! The JIT executor compiles modules after they are written out in the binary
+//! format, so the encoding of modules is checked on the metering corpus. It
+//! must preserve the module exactly, in particular the metering instructions,
+//! since otherwise the executors would charge different amounts of energy.
+//!
+//! With the `jit` feature the loops of `loop-energy.wasm` are additionally run
+//! with both executors, which must fail at exactly the same point when they
+//! run out of energy.
+use crate::{
+    artifact::ArtifactNamedImport,
+    metering_compatibility_test::{AllowAll, CORPUS},
+    output::Output,
+    parse::parse_skeleton,
+    types::Module,
+    validate::{validate_module, ValidationConfig},
+};
+
+fn validate(bytes: &[u8]) -> anyhow::Result<Module> {
+    validate_module(&ValidationConfig::LEGACY, &AllowAll, &parse_skeleton(bytes)?)
+}
+
+#[test]
+/// Check that encoding a metered module and parsing it again results in the
+/// same module, by comparing the encodings and the compiled artifacts.
+fn module_output_roundtrip() -> anyhow::Result<()> {
+    for path in CORPUS {
+        let mut module = validate(&std::fs::read(path)?)?;
+        module.inject_metering()?;
+        let mut bytes = Vec::new();
+        module.output(&mut bytes)?;
+        let reparsed = validate(&bytes)?;
+        let mut reencoded = Vec::new();
+        reparsed.output(&mut reencoded)?;
+        anyhow::ensure!(bytes == reencoded, "Encoding of {} is not stable.", path);
+        let mut artifact = Vec::new();
+        module.compile::<ArtifactNamedImport>()?.output(&mut artifact)?;
+        let mut reparsed_artifact = Vec::new();
+        reparsed.compile::<ArtifactNamedImport>()?.output(&mut reparsed_artifact)?;
+        anyhow::ensure!(
+            artifact == reparsed_artifact,
+            "Encoding of {} changes its artifact.",
+            path
+        );
+    }
+    Ok(())
+}
+
+#[cfg(feature = "jit")]
+mod jit {
+    use super::*;
+    use crate::{
+        executor::{jit::JitModule, Executor},
+        machine::{ExecutionOutcome, Host, NoInterrupt, RunResult, RuntimeStack, Value},
+    };
+
+    /// A host that only implements the metering imports, and charges energy
+    /// like the hosts of the chain.
+    struct EnergyHost {
+        energy: u64,
+    }
+
+    impl Host<ArtifactNamedImport> for EnergyHost {
+        type Interrupt = NoInterrupt;
+
+        fn tick_initial_memory(&mut self, _num_pages: u32) -> RunResult<()> { Ok(()) }
+
+        fn call(
+            &mut self,
+            f: &ArtifactNamedImport,
+            _memory: &mut Vec<u8>,
+            stack: &mut RuntimeStack,
+        ) -> RunResult<Option<Self::Interrupt>> {
+            if f.matches("concordium_metering", "account_energy") {
+                let amount = unsafe { stack.pop_u64() };
+                if self.energy < amount {
+                    self.energy = 0;
+                    anyhow::bail!("Out of energy.");
+                }
+                self.energy -= amount;
+            }
+            Ok(None)
+        }
+    }
+
+    /// The result of running the function, and the remaining energy.
+    fn run(
+        executor: &impl Executor<ArtifactNamedImport>,
+        name: &str,
+        args: &[Value],
+        energy: u64,
+    ) -> (Option<Option<Value>>, u64) {
+        let mut host = EnergyHost {
+            energy,
+        };
+        let result = match executor.run(&mut host, name, args) {
+            Ok(ExecutionOutcome::Success {
+                result,
+                ..
+            }) => Some(result),
+            Ok(ExecutionOutcome::Interrupted {
+                ..
+            }) => panic!("The host does not interrupt."),
+            Err(_) => None,
+        };
+        (result, host.energy)
+    }
+
+    #[test]
+    /// Run the loops with both executors with various amounts of energy, and
+    /// check that they fail with the same remaining energy.
+    fn loop_energy_differential() -> anyhow::Result<()> {
+        let bytes = std::fs::read("../wasm-chain-integration/benches/code/loop-energy.wasm")?;
+        let mut module = validate(&bytes)?;
+        module.inject_metering()?;
+        let artifact = module.compile::<ArtifactNamedImport>()?;
+        let mut module = validate(&bytes)?;
+        module.inject_metering()?;
+        let jit = JitModule::<ArtifactNamedImport>::new(module)?;
+        for (name, _) in artifact.export.iter() {
+            let name = name.as_ref();
+            let args: &[Value] = if name == "loop" {
+                &[Value::I32(3)]
+            } else {
+                &[]
+            };
+            for &energy in &[0, 1, 17, 1000, 123_457] {
+                let expected = run(&artifact, name, args, energy);
+                let actual = run(&jit, name, args, energy);
+                anyhow::ensure!(
+                    expected == actual,
+                    "The executors differ on {} with energy {}: {:?} != {:?}.",
+                    name,
+                    energy,
+                    expected,
+                    actual
+                );
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,14 +1,37 @@
 pub mod artifact;
 mod artifact_input;
 mod artifact_output;
+pub mod compression;
 pub mod constants;
+#[cfg(feature = "dispatch-stats")]
+pub mod dispatch_stats;
+pub mod energy_report;
+pub mod executor;
+pub mod linking;
 pub mod machine;
 pub mod metering_transformation;
 pub mod output;
 pub mod parse;
+pub mod resource_analysis;
 pub mod types;
 pub mod utils;
 pub mod validate;
 
+#[cfg(test)]
+mod artifact_test;
+#[cfg(test)]
+mod compression_test;
+#[cfg(test)]
+mod executor_test;
+#[cfg(test)]
+mod linking_test;
+#[cfg(test)]
+mod machine_test;
+#[cfg(test)]
+mod metering_compatibility_test;
 #[cfg(test)]
 mod metering_transformation_test;
+#[cfg(test)]
+mod metering_transformation_v0;
+#[cfg(test)]
+mod resource_analysis_test;
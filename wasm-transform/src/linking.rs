@@ -0,0 +1,280 @@
+//! Linking of modules against library modules. A module imports a function of
+//! a library by using the name of the library, prefixed with
+//! [LIBRARY_PREFIX], as the module name of the import. For example, the
+//! function `add` of the library `math` is imported as `lib:math.add`.
+//!
+//! Such imports are only accepted by validation if the embedder allows them by
+//! wrapping its import validation in [AllowLibraryImports]. They are resolved
+//! by [link_libraries], which replaces each of them by a copy of the library
+//! function, before the module is metered and compiled. The artifact of the
+//! linked module is thus self-contained, and is metered and executed like any
+//! other.
+//!
+//! A library does not share memory, globals or tables with the modules that
+//! import from it. Hence the library functions that are linked, i.e., the
+//! imported functions and all the functions they call, must not use memory,
+//! globals, indirect calls, or imports of the library. They may use locals and
+//! call other such functions of the library.
+
+use crate::{
+    types::*,
+    validate::{ValidateImportExport, ValidateResult},
+};
+use std::{collections::BTreeMap, rc::Rc};
+
+/// Prefix of the module names of imports from libraries.
+pub const LIBRARY_PREFIX: &str = "lib:";
+
+/// The name of the library the import is from, if it is a library import.
+pub fn library_name(mod_name: &Name) -> Option<&str> {
+    mod_name.as_ref().strip_prefix(LIBRARY_PREFIX)
+}
+
+/// Import validation that accepts imports of functions of any type from
+/// libraries, and otherwise defers to the wrapped validation. Modules
+/// validated with this must be linked by [link_libraries] before they are
+/// compiled.
+pub struct AllowLibraryImports<'a, V>(pub &'a V);
+
+impl<'a, V: ValidateImportExport> ValidateImportExport for AllowLibraryImports<'a, V> {
+    fn validate_import_function(
+        &self,
+        duplicate: bool,
+        mod_name: &Name,
+        item_name: &Name,
+        ty: &FunctionType,
+    ) -> bool {
+        if library_name(mod_name).is_some() {
+            !duplicate
+        } else {
+            self.0.validate_import_function(duplicate, mod_name, item_name, ty)
+        }
+    }
+
+    fn validate_export_function(&self, item_name: &Name, ty: &FunctionType) -> bool {
+        self.0.validate_export_function(item_name, ty)
+    }
+}
+
+/// Reasons why a module cannot be linked. They are returned as
+/// [anyhow::Error], from which they can be recovered with `downcast_ref`.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum LinkError {
+    #[error("Unknown library {0}.")]
+    UnknownLibrary(String),
+    #[error("The library {library} does not export a function {name}.")]
+    MissingExport {
+        library: String,
+        name:    String,
+    },
+    #[error("The function {name} of the library {library} does not have the type of the import.")]
+    TypeMismatch {
+        library: String,
+        name:    String,
+    },
+    #[error("Function {index} of the library {library} cannot be linked since it {reason}.")]
+    NotLinkable {
+        library: String,
+        index:   FuncIndex,
+        reason:  &'static str,
+    },
+}
+
+/// The reason why a function with the given body cannot be linked, if any.
+fn check_linkable(code: &Code) -> Option<&'static str> {
+    use OpCode::*;
+    code.expr.instrs.iter().find_map(|instr| match instr {
+        GlobalGet(_) | GlobalSet(_) => Some("uses globals"),
+        CallIndirect(_) => Some("calls functions indirectly"),
+        I32Load(_) | I64Load(_) | I32Load8S(_) | I32Load8U(_) | I32Load16S(_) | I32Load16U(_)
+        | I64Load8S(_) | I64Load8U(_) | I64Load16S(_) | I64Load16U(_) | I64Load32S(_)
+        | I64Load32U(_) | I32Store(_) | I64Store(_) | I32Store8(_) | I32Store16(_)
+        | I64Store8(_) | I64Store16(_) | I64Store32(_) | MemorySize | MemoryGrow | MemoryCopy
+        | MemoryFill => Some("uses memory"),
+        _ => None,
+    })
+}
+
+/// The code of the function of the library with the given index, if it is
+/// defined by the library and not imported.
+fn library_code(lib: &Module, index: FuncIndex) -> Option<&Code> {
+    (index as usize).checked_sub(lib.import.imports.len()).and_then(|idx| lib.code.impls.get(idx))
+}
+
+/// Replace the imports of the module from libraries by copies of the library
+/// functions, together with the library functions they call. The libraries are
+/// looked up by name with `resolve`, and must be validated modules that are
+/// not metered. Imports that are not from libraries are kept, and the
+/// functions of the module are renumbered accordingly.
+///
+/// This fails with a [LinkError] if a library or function does not exist, if
+/// the type of a library function differs from the type of the import, or if
+/// a function that would be linked is not allowed to be, see the module
+/// documentation.
+pub fn link_libraries<'a>(
+    module: &mut Module,
+    resolve: impl Fn(&str) -> Option<&'a Module>,
+) -> ValidateResult<()> {
+    let num_imports = module.import.imports.len() as FuncIndex;
+    let num_remaining_imports = module
+        .import
+        .imports
+        .iter()
+        .filter(|import| library_name(&import.mod_name).is_none())
+        .count() as FuncIndex;
+    let num_defined = module.code.impls.len() as FuncIndex;
+    // The resolved libraries, and the position of each in this list.
+    let mut libraries: Vec<&'a Module> = Vec::new();
+    let mut library_ids: BTreeMap<String, usize> = BTreeMap::new();
+    // The linked library functions, as pairs of the library and the index of
+    // the function in the library, in the order in which they are added to the
+    // module.
+    let mut linked: Vec<(usize, FuncIndex)> = Vec::new();
+    // The new index of each linked library function.
+    let mut linked_index: BTreeMap<(usize, FuncIndex), FuncIndex> = BTreeMap::new();
+    // The new index of each import.
+    let mut import_index = Vec::with_capacity(num_imports as usize);
+    let mut next_import = 0;
+    for import in module.import.imports.iter() {
+        let library = match library_name(&import.mod_name) {
+            Some(library) => library,
+            None => {
+                import_index.push(next_import);
+                next_import += 1;
+                continue;
+            }
+        };
+        let lib_id = match library_ids.get(library) {
+            Some(&lib_id) => lib_id,
+            None => {
+                let lib =
+                    resolve(library).ok_or_else(|| LinkError::UnknownLibrary(library.into()))?;
+                libraries.push(lib);
+                library_ids.insert(library.into(), libraries.len() - 1);
+                libraries.len() - 1
+            }
+        };
+        let lib = libraries[lib_id];
+        let name = import.item_name.as_ref();
+        let index = lib
+            .export
+            .exports
+            .iter()
+            .find_map(|export| match export.description {
+                ExportDescription::Func {
+                    index,
+                } if export.name.as_ref() == name => Some(index),
+                _ => None,
+            })
+            .ok_or_else(|| LinkError::MissingExport {
+                library: library.into(),
+                name:    name.into(),
+            })?;
+        let ImportDescription::Func {
+            type_idx,
+        } = import.description;
+        // If the function is imported by the library this is reported below.
+        if let Some(code) = library_code(lib, index) {
+            if Some(&code.ty) != module.ty.get(type_idx) {
+                return Err(LinkError::TypeMismatch {
+                    library: library.into(),
+                    name:    name.into(),
+                }
+                .into());
+            }
+        }
+        // Add the function, and those it calls, to the linked functions.
+        let mut todo = vec![index];
+        while let Some(index) = todo.pop() {
+            if linked_index.contains_key(&(lib_id, index)) {
+                continue;
+            }
+            let not_linkable = |reason| LinkError::NotLinkable {
+                library: library.into(),
+                index,
+                reason,
+            };
+            let code = library_code(lib, index).ok_or_else(|| not_linkable("is imported"))?;
+            if let Some(reason) = check_linkable(code) {
+                return Err(not_linkable(reason).into());
+            }
+            linked_index.insert(
+                (lib_id, index),
+                num_remaining_imports + num_defined + linked.len() as FuncIndex,
+            );
+            linked.push((lib_id, index));
+            for instr in code.expr.instrs.iter() {
+                if let OpCode::Call(callee) = instr {
+                    todo.push(*callee);
+                }
+            }
+        }
+        import_index.push(linked_index[&(lib_id, index)]);
+    }
+    if linked.is_empty() {
+        return Ok(());
+    }
+
+    // Renumber the functions of the module.
+    let renumber = |idx: FuncIndex| {
+        if idx < num_imports {
+            import_index[idx as usize]
+        } else {
+            idx - num_imports + num_remaining_imports
+        }
+    };
+    for code in module.code.impls.iter_mut() {
+        for instr in code.expr.instrs.iter_mut() {
+            if let OpCode::Call(idx) = instr {
+                *idx = renumber(*idx);
+            }
+        }
+    }
+    for elem in module.element.elements.iter_mut() {
+        for init in elem.inits.iter_mut() {
+            *init = renumber(*init);
+        }
+    }
+    for export in module.export.exports.iter_mut() {
+        if let ExportDescription::Func {
+            index,
+        } = &mut export.description
+        {
+            *index = renumber(*index);
+        }
+    }
+
+    // Add the library functions.
+    for (lib_id, index) in linked {
+        let code = library_code(libraries[lib_id], index)
+            .expect("Only functions defined by the library are linked.");
+        let ty_idx = match module.ty.types.iter().position(|ty| ty == &code.ty) {
+            Some(ty_idx) => ty_idx as TypeIndex,
+            None => {
+                module.ty.types.push(Rc::new(code.ty.as_ref().clone()));
+                (module.ty.types.len() - 1) as TypeIndex
+            }
+        };
+        let instrs = code
+            .expr
+            .instrs
+            .iter()
+            .map(|instr| match instr {
+                OpCode::Call(callee) => OpCode::Call(linked_index[&(lib_id, *callee)]),
+                instr => instr.clone(),
+            })
+            .collect::<Vec<_>>();
+        module.func.types.push(ty_idx);
+        module.code.impls.push(Code {
+            ty: module.ty.types[ty_idx as usize].clone(),
+            ty_idx,
+            num_locals: code.num_locals,
+            locals: code.locals.clone(),
+            expr: Expression {
+                instrs,
+            },
+        });
+    }
+    module.import.imports.retain(|import| library_name(&import.mod_name).is_none());
+    Ok(())
+}
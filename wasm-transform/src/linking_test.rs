@@ -0,0 +1,171 @@
+//! Tests of linking modules against libraries.
+//!
+//! The library `math` exports `add`, which adds two i64 values by calling an
+//! internal helper function, and `uses_global`, which reads a global and thus
+//! cannot be linked. The tested modules import one of them and export `f`.
+use crate::{
+    artifact::ArtifactNamedImport,
+    linking::{link_libraries, AllowLibraryImports, LinkError},
+    machine::{ExecutionOutcome, Host, NoInterrupt, RunResult, RuntimeStack, Value},
+    parse::parse_skeleton,
+    types::{FunctionType, Module, Name},
+    validate::{validate_module, ValidateImportExport, ValidationConfig},
+};
+
+const I32: u8 = 0x7F;
+const I64: u8 = 0x7E;
+
+/// Apart from library imports, modules in the tests do not have imports.
+struct NoImports;
+
+impl ValidateImportExport for NoImports {
+    fn validate_import_function(
+        &self,
+        _duplicate: bool,
+        _mod_name: &Name,
+        _item_name: &Name,
+        _ty: &FunctionType,
+    ) -> bool {
+        false
+    }
+
+    fn validate_export_function(&self, _item_name: &Name, _ty: &FunctionType) -> bool { true }
+}
+
+struct NoHost;
+
+impl<I> Host<I> for NoHost {
+    type Interrupt = NoInterrupt;
+
+    fn tick_initial_memory(&mut self, _num_pages: u32) -> RunResult<()> { Ok(()) }
+
+    fn call(
+        &mut self,
+        _f: &I,
+        _memory: &mut Vec<u8>,
+        _stack: &mut RuntimeStack,
+    ) -> RunResult<Option<Self::Interrupt>> {
+        anyhow::bail!("Linked modules have no imports.")
+    }
+}
+
+fn section(out: &mut Vec<u8>, id: u8, contents: &[u8]) {
+    out.push(id);
+    leb128::write::unsigned(out, contents.len() as u64).unwrap();
+    out.extend_from_slice(contents);
+}
+
+fn validate(bytes: &[u8]) -> Module {
+    let skeleton = parse_skeleton(bytes).expect("Module should parse.");
+    validate_module(&ValidationConfig::ALL, &AllowLibraryImports(&NoImports), &skeleton)
+        .expect("Module should be valid.")
+}
+
+fn library() -> Module {
+    let mut out = b"\0asm\x01\0\0\0".to_vec();
+    section(&mut out, 1, &[2, 0x60, 2, I64, I64, 1, I64, 0x60, 0, 1, I32]);
+    section(&mut out, 3, &[3, 0, 0, 1]);
+    section(&mut out, 6, &[1, I32, 0, 0x41, 0, 0x0B]);
+    let mut exports = vec![2, 3];
+    exports.extend_from_slice(b"add");
+    exports.extend_from_slice(&[0, 0, 11]);
+    exports.extend_from_slice(b"uses_global");
+    exports.extend_from_slice(&[0, 2]);
+    section(&mut out, 7, &exports);
+    section(&mut out, 10, &[
+        3, 8, 0, 0x20, 0, 0x20, 1, 0x10, 1, 0x0B, 7, 0, 0x20, 0, 0x20, 1, 0x7C, 0x0B, 4, 0, 0x23,
+        0, 0x0B,
+    ]);
+    validate(&out)
+}
+
+/// A module that imports the function `item` from the library `lib`, and
+/// exports `f` which applies it to two copies of its argument.
+fn module(lib: &str, item: &str) -> Module {
+    let mut out = b"\0asm\x01\0\0\0".to_vec();
+    section(&mut out, 1, &[2, 0x60, 2, I64, I64, 1, I64, 0x60, 1, I64, 1, I64]);
+    let mut imports = vec![1, lib.len() as u8];
+    imports.extend_from_slice(lib.as_bytes());
+    imports.push(item.len() as u8);
+    imports.extend_from_slice(item.as_bytes());
+    imports.extend_from_slice(&[0, 0]);
+    section(&mut out, 2, &imports);
+    section(&mut out, 3, &[1, 1]);
+    section(&mut out, 7, &[1, 1, b'f', 0, 1]);
+    section(&mut out, 10, &[1, 8, 0, 0x20, 0, 0x20, 0, 0x10, 0, 0x0B]);
+    validate(&out)
+}
+
+#[test]
+fn test_link_library() {
+    let math = library();
+    let resolve = |name: &str| {
+        if name == "math" {
+            Some(&math)
+        } else {
+            None
+        }
+    };
+    let mut module = module("lib:math", "add");
+    link_libraries(&mut module, resolve).expect("Linking should succeed.");
+    assert!(module.import.imports.is_empty(), "The library import is replaced.");
+    assert_eq!(module.code.impls.len(), 3, "The helper of add is linked as well.");
+    let artifact = module.compile::<ArtifactNamedImport>().expect("Module should compile.");
+    match artifact.run(&mut NoHost, "f", &[Value::I64(21)]) {
+        Ok(ExecutionOutcome::Success {
+            result: Some(Value::I64(42)),
+            ..
+        }) => (),
+        r => panic!("Unexpected result {:?}.", r.map(|_| ())),
+    }
+}
+
+#[test]
+fn test_link_errors() {
+    let math = library();
+    let link = |lib: &str, item: &str| {
+        link_libraries(&mut module(lib, item), |name| {
+            if name == "math" {
+                Some(&math)
+            } else {
+                None
+            }
+        })
+        .expect_err("Linking should fail.")
+        .downcast::<LinkError>()
+        .expect("Linking should fail with a LinkError.")
+    };
+    assert_eq!(link("lib:other", "add"), LinkError::UnknownLibrary("other".into()));
+    assert_eq!(link("lib:math", "sub"), LinkError::MissingExport {
+        library: "math".into(),
+        name:    "sub".into(),
+    });
+    assert_eq!(link("lib:math", "uses_global"), LinkError::TypeMismatch {
+        library: "math".into(),
+        name:    "uses_global".into(),
+    });
+}
+
+#[test]
+fn test_link_not_linkable() {
+    let math = library();
+    // A module importing `uses_global` with its type.
+    let mut out = b"\0asm\x01\0\0\0".to_vec();
+    section(&mut out, 1, &[1, 0x60, 0, 1, I32]);
+    let mut imports = vec![1, 8];
+    imports.extend_from_slice(b"lib:math");
+    imports.push(11);
+    imports.extend_from_slice(b"uses_global");
+    imports.extend_from_slice(&[0, 0]);
+    section(&mut out, 2, &imports);
+    let mut module = validate(&out);
+    let err = link_libraries(&mut module, |_| Some(&math))
+        .expect_err("Linking should fail.")
+        .downcast::<LinkError>()
+        .expect("Linking should fail with a LinkError.");
+    assert_eq!(err, LinkError::NotLinkable {
+        library: "math".into(),
+        index:   2,
+        reason:  "uses globals",
+    });
+}
@@ -5,8 +5,10 @@ use crate::{
     constants::{MAX_NUM_PAGES, PAGE_SIZE},
     types::*,
 };
-use anyhow::{anyhow, bail, ensure};
-use std::{convert::TryInto, io::Write};
+use std::convert::TryInto;
+
+#[cfg(feature = "table-dispatch")]
+mod table_dispatch;
 
 /// An empty type used when no interrupt is possible by a host function call.
 #[derive(Debug, Copy, Clone)]
@@ -18,6 +20,14 @@ pub trait Host<I> {
     /// Charge the given amount of energy for the initial memory.
     /// The argument is the number of pages.
     fn tick_initial_memory(&mut self, num_pages: u32) -> RunResult<()>;
+    /// Charge for growing the memory, which currently has `current_pages`
+    /// pages, by `num_pages` pages. This is only called if the memory is
+    /// actually grown, i.e., if `num_pages` is non-zero and the new size does
+    /// not exceed the maximum of the module. A host that limits the memory of
+    /// an instance to fewer pages should return [OutOfMemory]. The default
+    /// implementation does nothing.
+    #[cfg_attr(not(feature = "fuzz-coverage"), inline(always))]
+    fn tick_memory_grow(&mut self, _current_pages: u32, _num_pages: u32) -> RunResult<()> { Ok(()) }
     /// Call the specified host function, giving it access to the current memory
     /// and stack. The return value of `Ok(())` signifies that execution
     /// succeeded and the machine should proceeed, the return value of
@@ -28,12 +38,33 @@ pub trait Host<I> {
         memory: &mut Vec<u8>,
         stack: &mut RuntimeStack,
     ) -> RunResult<Option<Self::Interrupt>>;
+
+    /// Called before each instruction is executed if the `execution-trace`
+    /// feature is enabled, with the index of the current function in the list
+    /// of defined functions, the position of the instruction in the function's
+    /// code, its opcode, and the value at the top of the stack, if any. Note
+    /// that the stack also contains the locals of the current function, so the
+    /// top of the stack is a local if the function's operand stack is empty.
+    /// The default implementation does nothing.
+    #[cfg_attr(not(feature = "fuzz-coverage"), inline(always))]
+    fn trace(
+        &mut self,
+        _function: usize,
+        _offset: usize,
+        _opcode: InternalOpcode,
+        _stack_top: Option<StackValue>,
+    ) {
+    }
 }
 
-/// Result of execution. Runtime exceptions are returned as `Err(_)`.
-/// This includes traps, illegal memory accesses, etc.
+/// Result of host functions. Errors are returned as `Err(_)` and terminate
+/// execution. A host that runs out of energy should return [OutOfEnergy].
 pub type RunResult<A> = anyhow::Result<A>;
 
+/// Result of running an artifact. Runtime exceptions are returned as `Err(_)`.
+/// This includes traps, illegal memory accesses, etc.
+pub type ExecutionResult<A> = Result<A, RuntimeError>;
+
 /// Configuration that can be run.
 #[derive(Debug)]
 pub struct RunConfig {
@@ -151,22 +182,132 @@ pub struct RuntimeStack {
     pos:   usize,
 }
 
+/// Error signalled by a host when there is not enough energy to proceed.
+/// The machine reports it as [RuntimeError::OutOfEnergy] instead of as a host
+/// error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("Out of energy")]
+pub struct OutOfEnergy;
+
+/// Error signalled by a host when the memory of an instance would exceed the
+/// limit of the host. The machine reports it as [RuntimeError::OutOfMemory]
+/// instead of as a host error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("Out of memory: {requested} pages requested, but the limit is {limit} pages.")]
+pub struct OutOfMemory {
+    /// The total number of pages the memory would have.
+    pub requested: u32,
+    /// The maximum number of pages allowed by the host.
+    pub limit:     u32,
+}
+
+/// Reasons for a trap during execution of a function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum TrapReason {
+    #[error("Unreachable.")]
+    Unreachable,
+    #[error("Memory access out of bounds.")]
+    MemoryOutOfBounds,
+    /// Division or remainder by zero, or signed division overflow.
+    #[error("Integer division by zero or overflow.")]
+    IntegerDivision,
+    /// An indirect call to a table entry that is not defined.
+    #[error("Calling undefined function {0}.")]
+    UndefinedFunction(u32),
+    #[error("Actual type different from expected.")]
+    IndirectCallTypeMismatch,
+}
+
+/// Errors that can occur when running an artifact. Execution either fails
+/// before it starts, e.g., because the entrypoint does not exist, or it fails
+/// at runtime, in which case the host can be queried for the remaining energy.
 #[derive(Debug)]
 pub enum RuntimeError {
+    /// The entrypoint does not exist.
+    MissingEntrypoint(String),
     DirectlyCallImport,
+    /// The arguments do not match the parameters of the entrypoint.
+    InvalidArguments(String),
+    /// A host function or the host accounting signalled [OutOfEnergy].
+    OutOfEnergy,
+    /// The host accounting signalled [OutOfMemory].
+    OutOfMemory(OutOfMemory),
+    Trap(TrapReason),
+    /// A host function failed with an error other than [OutOfEnergy] or
+    /// [OutOfMemory].
+    HostCallError(anyhow::Error),
+    /// The artifact refers to code or types that do not exist. This does not
+    /// happen for artifacts produced by compilation.
+    MalformedArtifact(&'static str),
 }
 
 impl std::fmt::Display for RuntimeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            RuntimeError::MissingEntrypoint(name) => {
+                write!(f, "Trying to invoke a method that does not exist: {}.", name)
+            }
             RuntimeError::DirectlyCallImport => {
                 write!(f, "Calling an imported function directly is not supported.")
             }
+            RuntimeError::InvalidArguments(msg) => msg.fmt(f),
+            RuntimeError::OutOfEnergy => OutOfEnergy.fmt(f),
+            RuntimeError::OutOfMemory(e) => e.fmt(f),
+            RuntimeError::Trap(reason) => reason.fmt(f),
+            RuntimeError::HostCallError(e) => e.fmt(f),
+            RuntimeError::MalformedArtifact(msg) => msg.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RuntimeError::HostCallError(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<TrapReason> for RuntimeError {
+    fn from(reason: TrapReason) -> Self { RuntimeError::Trap(reason) }
+}
+
+impl RuntimeError {
+    /// Classify an error returned by the host. [OutOfEnergy] is reported as
+    /// [RuntimeError::OutOfEnergy], [OutOfMemory] as
+    /// [RuntimeError::OutOfMemory], and anything else as a host call error.
+    pub fn from_host(e: anyhow::Error) -> Self {
+        if e.downcast_ref::<OutOfEnergy>().is_some() {
+            RuntimeError::OutOfEnergy
+        } else if let Some(oom) = e.downcast_ref::<OutOfMemory>() {
+            RuntimeError::OutOfMemory(*oom)
+        } else {
+            RuntimeError::HostCallError(e)
+        }
+    }
+
+    /// Convert to an [anyhow::Error]. Errors of host functions are returned
+    /// as they are, and [OutOfMemory] is returned on its own, so that they can
+    /// still be downcast to their original type.
+    pub fn into_anyhow(self) -> anyhow::Error {
+        match self {
+            RuntimeError::HostCallError(e) => e,
+            RuntimeError::OutOfMemory(e) => anyhow::Error::new(e),
+            e => anyhow::Error::new(e),
         }
     }
 }
 
 impl RuntimeStack {
+    /// An empty stack with space for the given number of values.
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        Self {
+            stack: Vec::with_capacity(capacity),
+            pos:   0,
+        }
+    }
+
     #[cfg_attr(not(feature = "fuzz-coverage"), inline(always))]
     pub fn size(&self) -> usize { self.pos }
 
@@ -264,50 +405,60 @@ fn get_u64(bytes: &[u8], pc: &mut usize) -> u64 {
 }
 
 #[cfg_attr(not(feature = "fuzz-coverage"), inline(always))]
-fn read_u8(bytes: &[u8], pos: usize) -> RunResult<u8> {
-    bytes.get(pos).copied().ok_or_else(|| anyhow!("Memory access out of bounds."))
+fn read_u8(bytes: &[u8], pos: usize) -> Result<u8, TrapReason> {
+    bytes.get(pos).copied().ok_or(TrapReason::MemoryOutOfBounds)
 }
 
 #[cfg_attr(not(feature = "fuzz-coverage"), inline(always))]
-fn read_u16(bytes: &[u8], pos: usize) -> RunResult<u16> {
-    ensure!(pos + 2 <= bytes.len(), "Memory access out of bounds.");
+fn read_u16(bytes: &[u8], pos: usize) -> Result<u16, TrapReason> {
+    if pos + 2 > bytes.len() {
+        return Err(TrapReason::MemoryOutOfBounds);
+    }
     let mut dst = [0u8; 2];
     dst.copy_from_slice(&bytes[pos..pos + 2]);
     Ok(u16::from_le_bytes(dst))
 }
 
 #[cfg_attr(not(feature = "fuzz-coverage"), inline(always))]
-fn read_u32(bytes: &[u8], pos: usize) -> RunResult<u32> {
-    ensure!(pos + 4 <= bytes.len(), "Memory access out of bounds.");
+fn read_u32(bytes: &[u8], pos: usize) -> Result<u32, TrapReason> {
+    if pos + 4 > bytes.len() {
+        return Err(TrapReason::MemoryOutOfBounds);
+    }
     let mut dst = [0u8; 4];
     dst.copy_from_slice(&bytes[pos..pos + 4]);
     Ok(u32::from_le_bytes(dst))
 }
 
 #[cfg_attr(not(feature = "fuzz-coverage"), inline(always))]
-fn read_i8(bytes: &[u8], pos: usize) -> RunResult<i8> {
-    bytes.get(pos).map(|&x| x as i8).ok_or_else(|| anyhow!("Memory access out of bounds."))
+fn read_i8(bytes: &[u8], pos: usize) -> Result<i8, TrapReason> {
+    bytes.get(pos).map(|&x| x as i8).ok_or(TrapReason::MemoryOutOfBounds)
 }
 
 #[cfg_attr(not(feature = "fuzz-coverage"), inline(always))]
-fn read_i16(bytes: &[u8], pos: usize) -> RunResult<i16> {
-    ensure!(pos + 2 <= bytes.len(), "Memory access out of bounds.");
+fn read_i16(bytes: &[u8], pos: usize) -> Result<i16, TrapReason> {
+    if pos + 2 > bytes.len() {
+        return Err(TrapReason::MemoryOutOfBounds);
+    }
     let mut dst = [0u8; 2];
     dst.copy_from_slice(&bytes[pos..pos + 2]);
     Ok(i16::from_le_bytes(dst))
 }
 
 #[cfg_attr(not(feature = "fuzz-coverage"), inline(always))]
-fn read_i32(bytes: &[u8], pos: usize) -> RunResult<i32> {
-    ensure!(pos + 4 <= bytes.len(), "Memory access out of bounds.");
+fn read_i32(bytes: &[u8], pos: usize) -> Result<i32, TrapReason> {
+    if pos + 4 > bytes.len() {
+        return Err(TrapReason::MemoryOutOfBounds);
+    }
     let mut dst = [0u8; 4];
     dst.copy_from_slice(&bytes[pos..pos + 4]);
     Ok(i32::from_le_bytes(dst))
 }
 
 #[cfg_attr(not(feature = "fuzz-coverage"), inline(always))]
-fn read_i64(bytes: &[u8], pos: usize) -> RunResult<i64> {
-    ensure!(pos + 8 <= bytes.len(), "Memory access out of bounds.");
+fn read_i64(bytes: &[u8], pos: usize) -> Result<i64, TrapReason> {
+    if pos + 8 > bytes.len() {
+        return Err(TrapReason::MemoryOutOfBounds);
+    }
     let mut dst = [0u8; 8];
     dst.copy_from_slice(&bytes[pos..pos + 8]);
     Ok(i64::from_le_bytes(dst))
@@ -318,7 +469,7 @@ fn get_memory_pos(
     instructions: &[u8],
     stack: &mut RuntimeStack,
     pc: &mut usize,
-) -> RunResult<usize> {
+) -> Result<usize, TrapReason> {
     let offset = get_u32(instructions, pc);
     let top = stack.pop();
     let top = unsafe { top.short } as u32;
@@ -327,9 +478,12 @@ fn get_memory_pos(
 }
 
 #[cfg_attr(not(feature = "fuzz-coverage"), inline(always))]
-fn write_memory_at(memory: &mut [u8], pos: usize, bytes: &[u8]) -> RunResult<()> {
-    ensure!(pos < memory.len(), "Illegal memory access.");
-    (&mut memory[pos..]).write_all(bytes)?;
+fn write_memory_at(memory: &mut [u8], pos: usize, bytes: &[u8]) -> Result<(), TrapReason> {
+    let end = pos + bytes.len();
+    if end > memory.len() {
+        return Err(TrapReason::MemoryOutOfBounds);
+    }
+    memory[pos..end].copy_from_slice(bytes);
     Ok(())
 }
 
@@ -356,11 +510,11 @@ fn binary_i32(stack: &mut RuntimeStack, f: impl Fn(i32, i32) -> i32) {
 fn binary_i32_partial(
     stack: &mut RuntimeStack,
     f: impl Fn(i32, i32) -> Option<i32>,
-) -> RunResult<()> {
+) -> Result<(), TrapReason> {
     let right = stack.pop();
     let left = stack.peek_mut();
-    left.short = f(unsafe { left.short }, unsafe { right.short })
-        .ok_or_else(|| anyhow!("Runtime exception in i32 binary."))?;
+    left.short =
+        f(unsafe { left.short }, unsafe { right.short }).ok_or(TrapReason::IntegerDivision)?;
     Ok(())
 }
 
@@ -375,11 +529,11 @@ fn binary_i64(stack: &mut RuntimeStack, f: impl Fn(i64, i64) -> i64) {
 fn binary_i64_partial(
     stack: &mut RuntimeStack,
     f: impl Fn(i64, i64) -> Option<i64>,
-) -> RunResult<()> {
+) -> Result<(), TrapReason> {
     let right = stack.pop();
     let left = stack.peek_mut();
-    left.long = f(unsafe { left.long }, unsafe { right.long })
-        .ok_or_else(|| anyhow!("Runtime exception in i64 binary"))?;
+    left.long =
+        f(unsafe { left.long }, unsafe { right.long }).ok_or(TrapReason::IntegerDivision)?;
     Ok(())
 }
 
@@ -396,38 +550,41 @@ impl<I: TryFromImport, R: RunnableCode> Artifact<I, R> {
         host: &mut H,
         name: &Q,
         args: &[Value],
-    ) -> RunResult<ExecutionOutcome<H::Interrupt>>
+    ) -> ExecutionResult<ExecutionOutcome<H::Interrupt>>
     where
         Name: std::borrow::Borrow<Q>, {
         let start = *self.get_entrypoint_index(name)?;
         // FIXME: The next restriction could easily be lifted, but it is not a problem
         // for now.
-        ensure!(start as usize >= self.imports.len(), RuntimeError::DirectlyCallImport);
+        if (start as usize) < self.imports.len() {
+            return Err(RuntimeError::DirectlyCallImport);
+        }
         let instructions_idx = start as usize - self.imports.len();
         let outer_function = &self.code[instructions_idx]; // safe because the artifact should be well-formed.
-        let num_args: u32 = args.len().try_into()?;
-        ensure!(
-            outer_function.num_params() == num_args,
-            "The number of arguments does not match the number of parameters {} != {}.",
-            num_args,
-            outer_function.num_params(),
-        );
+        let num_args: u32 = args
+            .len()
+            .try_into()
+            .map_err(|_| RuntimeError::InvalidArguments("Too many arguments.".into()))?;
+        if outer_function.num_params() != num_args {
+            return Err(RuntimeError::InvalidArguments(format!(
+                "The number of arguments does not match the number of parameters {} != {}.",
+                num_args,
+                outer_function.num_params(),
+            )));
+        }
         for (p, actual) in outer_function.params().iter().zip(args.iter()) {
             // the first num_params locals are arguments
             let actual_ty = ValueType::from(*actual);
-            ensure!(
-                *p == actual_ty,
-                "Argument of incorrect type: actual {:#?}, expected {:#?}.",
-                actual_ty,
-                *p
-            )
+            if *p != actual_ty {
+                return Err(RuntimeError::InvalidArguments(format!(
+                    "Argument of incorrect type: actual {:#?}, expected {:#?}.",
+                    actual_ty, *p
+                )));
+            }
         }
 
         let globals = self.global.inits.iter().copied().map(StackValue::from).collect::<Vec<_>>();
-        let mut stack: RuntimeStack = RuntimeStack {
-            stack: Vec::with_capacity(1000),
-            pos:   0,
-        };
+        let mut stack = RuntimeStack::with_capacity(1000);
         for &arg in args.iter() {
             match arg {
                 Value::I32(short) => stack.push(StackValue::from(short)),
@@ -442,14 +599,14 @@ impl<I: TryFromImport, R: RunnableCode> Artifact<I, R> {
         }
         let memory = {
             if let Some(m) = self.memory.as_ref() {
-                host.tick_initial_memory(m.init_size)?;
+                host.tick_initial_memory(m.init_size).map_err(RuntimeError::from_host)?;
                 // This is safe since maximum initial memory is limited to 32 pages.
                 let mut memory = vec![0u8; (MAX_NUM_PAGES * PAGE_SIZE) as usize];
                 unsafe {
                     memory.set_len((m.init_size * PAGE_SIZE) as usize);
                 }
                 for data in m.init.iter() {
-                    (&mut memory[data.offset as usize..]).write_all(&data.init)?;
+                    write_memory_at(&mut memory, data.offset as usize, &data.init)?;
                 }
                 memory
             } else {
@@ -480,13 +637,11 @@ impl<I: TryFromImport, R: RunnableCode> Artifact<I, R> {
     }
 
     /// Returns the index of the given entrypoint if it exists.
-    fn get_entrypoint_index<Q>(&self, name: &Q) -> RunResult<&FuncIndex>
+    fn get_entrypoint_index<Q>(&self, name: &Q) -> ExecutionResult<&FuncIndex>
     where
         Q: std::fmt::Display + Ord + ?Sized,
         Name: std::borrow::Borrow<Q>, {
-        self.export
-            .get(name)
-            .ok_or_else(|| anyhow!("Trying to invoke a method that does not exist: {}.", name))
+        self.export.get(name).ok_or_else(|| RuntimeError::MissingEntrypoint(name.to_string()))
     }
 
     /// Returns `true` if the given entrypoint name exists, `false` otherwise.
@@ -501,7 +656,7 @@ impl<I: TryFromImport, R: RunnableCode> Artifact<I, R> {
         &self,
         host: &mut H,
         config: RunConfig,
-    ) -> RunResult<ExecutionOutcome<H::Interrupt>> {
+    ) -> ExecutionResult<ExecutionOutcome<H::Interrupt>> {
         // we deliberately deconstruct the struct here instead of having mutable
         // references to fields here to improve performance. On some benchmarks
         // instruction execution is 30% slower if we keep references to the config
@@ -524,9 +679,33 @@ impl<I: TryFromImport, R: RunnableCode> Artifact<I, R> {
         // are private), and the only place it is constructed is in the `run`
         // method above, where the precondition is checked.
         let mut instructions = unsafe { self.code.get_unchecked(instructions_idx).code() };
+        #[cfg(feature = "dispatch-stats")]
+        let mut dispatch_stats = crate::dispatch_stats::Recorder::new();
         'outer: loop {
             let instr = instructions[pc];
+            #[cfg(feature = "execution-trace")]
+            {
+                let stack_top = if stack.size() > 0 {
+                    Some(stack.peek())
+                } else {
+                    None
+                };
+                // The transmute is safe for the same reason as in the match below.
+                host.trace(
+                    instructions_idx,
+                    pc,
+                    unsafe { std::mem::transmute::<u8, InternalOpcode>(instr) },
+                    stack_top,
+                );
+            }
             pc += 1;
+            #[cfg(feature = "dispatch-stats")]
+            dispatch_stats.record(instr);
+            #[cfg(feature = "table-dispatch")]
+            if let Some(handler) = table_dispatch::HANDLERS[usize::from(instr)] {
+                handler(&mut stack, instructions, &mut pc, locals_base)?;
+                continue 'outer;
+            }
             // FIXME: The unsafe here is a bit wrong, but it is much faster than using
             // InternalOpcode::try_from(instr). About 25% faster on a fibonacci test.
             // The ensure here guarantees that the transmute is safe, provided that
@@ -535,7 +714,7 @@ impl<I: TryFromImport, R: RunnableCode> Artifact<I, R> {
             // println!("{:#?}", unsafe { std::mem::transmute::<_,InternalOpcode>(instr) });
             match unsafe { std::mem::transmute(instr) } {
                 // InternalOpcode::try_from(instr)? {
-                InternalOpcode::Unreachable => bail!("Unreachable."),
+                InternalOpcode::Unreachable => return Err(TrapReason::Unreachable.into()),
                 InternalOpcode::If => {
                     let else_target = get_u32(instructions, &mut pc);
                     let top = stack.pop();
@@ -654,7 +833,10 @@ impl<I: TryFromImport, R: RunnableCode> Artifact<I, R> {
                     let idx = get_u32(instructions, &mut pc);
                     if let Some(f) = self.imports.get(idx as usize) {
                         // we are calling an imported function, handle the call directly.
-                        if let Some(reason) = host.call(f, &mut memory, &mut stack)? {
+                        if let Some(reason) = host
+                            .call(f, &mut memory, &mut stack)
+                            .map_err(RuntimeError::from_host)?
+                        {
                             return Ok(ExecutionOutcome::Interrupted {
                                 reason,
                                 config: RunConfig {
@@ -672,10 +854,9 @@ impl<I: TryFromImport, R: RunnableCode> Artifact<I, R> {
                         }
                     } else {
                         let local_idx = idx as usize - self.imports.len();
-                        let f = self
-                            .code
-                            .get(local_idx)
-                            .ok_or_else(|| anyhow!("Accessing non-existent code."))?;
+                        let f = self.code.get(local_idx).ok_or(RuntimeError::MalformedArtifact(
+                            "Accessing non-existent code.",
+                        ))?;
                         let current_frame = FunctionState {
                             pc,
                             instructions_idx,
@@ -702,15 +883,20 @@ impl<I: TryFromImport, R: RunnableCode> Artifact<I, R> {
                     let ty = self
                         .ty
                         .get(ty_idx as usize)
-                        .ok_or_else(|| anyhow!("Non-existent type."))?;
+                        .ok_or(RuntimeError::MalformedArtifact("Non-existent type."))?;
                     let idx = stack.pop();
                     let idx = unsafe { idx.short } as u32;
                     if let Some(Some(f_idx)) = self.table.functions.get(idx as usize) {
                         if let Some(f) = self.imports.get(*f_idx as usize) {
                             let ty_actual = f.ty();
                             // call imported function.
-                            ensure!(ty_actual == ty, "Actual type different from expected.");
-                            if let Some(reason) = host.call(f, &mut memory, &mut stack)? {
+                            if ty_actual != ty {
+                                return Err(TrapReason::IndirectCallTypeMismatch.into());
+                            }
+                            if let Some(reason) = host
+                                .call(f, &mut memory, &mut stack)
+                                .map_err(RuntimeError::from_host)?
+                            {
                                 return Ok(ExecutionOutcome::Interrupted {
                                     reason,
                                     config: RunConfig {
@@ -727,18 +913,16 @@ impl<I: TryFromImport, R: RunnableCode> Artifact<I, R> {
                                 });
                             }
                         } else {
-                            let f = self
-                                .code
-                                .get(*f_idx as usize - self.imports.len())
-                                .ok_or_else(|| anyhow!("Accessing non-existent code."))?;
-                            let ty_actual =
-                                self.ty.get(f.type_idx() as usize).ok_or_else(|| {
-                                    anyhow!("Non-existent type. This should not happen.")
-                                })?;
-                            ensure!(
-                                f.type_idx() == ty_idx || ty_actual == ty,
-                                "Actual type different from expected."
-                            );
+                            let f = self.code.get(*f_idx as usize - self.imports.len()).ok_or(
+                                RuntimeError::MalformedArtifact("Accessing non-existent code."),
+                            )?;
+                            let ty_actual = self
+                                .ty
+                                .get(f.type_idx() as usize)
+                                .ok_or(RuntimeError::MalformedArtifact("Non-existent type."))?;
+                            if f.type_idx() != ty_idx && ty_actual != ty {
+                                return Err(TrapReason::IndirectCallTypeMismatch.into());
+                            }
                             // FIXME: Remove duplication.
                             let current_frame = FunctionState {
                                 pc,
@@ -765,7 +949,7 @@ impl<I: TryFromImport, R: RunnableCode> Artifact<I, R> {
                             return_type = f.return_type();
                         }
                     } else {
-                        bail!("Calling undefined function {}.", idx) // trap
+                        return Err(TrapReason::UndefinedFunction(idx).into()); // trap
                     }
                 }
                 InternalOpcode::Drop => {
@@ -916,11 +1100,30 @@ impl<I: TryFromImport, R: RunnableCode> Artifact<I, R> {
                         val.short = -1i32;
                     } else {
                         if n != 0 {
+                            host.tick_memory_grow(sz as u32, n).map_err(RuntimeError::from_host)?;
                             unsafe { memory.set_len((sz + n as usize) * PAGE_SIZE as usize) }
                         }
                         val.short = sz as i32;
                     }
                 }
+                InternalOpcode::MemoryCopy => {
+                    let n = unsafe { stack.pop().short } as u32 as usize;
+                    let src = unsafe { stack.pop().short } as u32 as usize;
+                    let dst = unsafe { stack.pop().short } as u32 as usize;
+                    if src + n > memory.len() || dst + n > memory.len() {
+                        return Err(TrapReason::MemoryOutOfBounds.into());
+                    }
+                    memory.copy_within(src..src + n, dst);
+                }
+                InternalOpcode::MemoryFill => {
+                    let n = unsafe { stack.pop().short } as u32 as usize;
+                    let val = unsafe { stack.pop().short } as u8;
+                    let dst = unsafe { stack.pop().short } as u32 as usize;
+                    if dst + n > memory.len() {
+                        return Err(TrapReason::MemoryOutOfBounds.into());
+                    }
+                    memory[dst..dst + n].iter_mut().for_each(|b| *b = val);
+                }
                 InternalOpcode::I32Const => {
                     let val = get_i32(instructions, &mut pc);
                     stack.push(StackValue::from(val));
@@ -1146,6 +1349,21 @@ impl<I: TryFromImport, R: RunnableCode> Artifact<I, R> {
                     // and then extend, making it so that it is extended with 0's.
                     top.long = unsafe { top.short } as u32 as i64;
                 }
+                InternalOpcode::I32Extend8S => {
+                    unary_i32(&mut stack, |x| x as i8 as i32);
+                }
+                InternalOpcode::I32Extend16S => {
+                    unary_i32(&mut stack, |x| x as i16 as i32);
+                }
+                InternalOpcode::I64Extend8S => {
+                    unary_i64(&mut stack, |x| x as i8 as i64);
+                }
+                InternalOpcode::I64Extend16S => {
+                    unary_i64(&mut stack, |x| x as i16 as i64);
+                }
+                InternalOpcode::I64Extend32S => {
+                    unary_i64(&mut stack, |x| x as i32 as i64);
+                }
             }
         }
 
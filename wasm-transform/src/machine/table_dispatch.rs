@@ -0,0 +1,347 @@
+//! Table-based dispatch for the interpreter, enabled by the `table-dispatch`
+//! feature.
+//!
+//! Instructions that only operate on the stack, the locals of the current
+//! frame, and their immediate operands are executed by a handler looked up in
+//! [HANDLERS], indexed by the opcode byte, instead of by the `match` in
+//! [Artifact::run_config](crate::artifact::Artifact::run_config). The table
+//! has an entry for every byte, so the lookup is not bounds checked. All
+//! other instructions, i.e., control flow, calls, and instructions accessing
+//! memory or globals, have no handler and are executed by the `match` as
+//! before.
+//!
+//! The semantics of the handlers must match the corresponding arms of the
+//! `match` exactly. This is checked by the tests in `machine_test`, which are
+//! run both with and without this feature.
+//!
+//! This is an experiment, and it is not faster than the `match`. On a loop of
+//! 64-bit arithmetic it is about 40-50% slower, and on a recursive fibonacci
+//! function about 10% slower. The `match` compiles to a jump table whose
+//! targets keep the program counter, the stack, and the instructions in
+//! registers, whereas each handler is an indirect call which has to load them
+//! from memory and store them back.
+use super::*;
+
+/// A handler of a single instruction. The arguments are the stack, the
+/// instructions of the current function, the program counter pointing just
+/// after the opcode, and the position where the locals of the current frame
+/// start.
+pub(super) type Handler = fn(&mut RuntimeStack, &[u8], &mut usize, usize) -> Result<(), TrapReason>;
+
+/// Handlers of instructions, indexed by their opcode. Opcodes that are not
+/// handled by a table entry are `None`.
+pub(super) static HANDLERS: [Option<Handler>; 256] = build_handlers();
+
+/// Define a handler of a unary or binary instruction in terms of one of the
+/// helpers of the interpreter.
+macro_rules! define_handler {
+    ($name:ident, $helper:ident, $f:expr) => {
+        fn $name(
+            stack: &mut RuntimeStack,
+            _: &[u8],
+            _: &mut usize,
+            _: usize,
+        ) -> Result<(), TrapReason> {
+            $helper(stack, $f);
+            Ok(())
+        }
+    };
+    ($name:ident, $helper:ident, $f:expr,partial) => {
+        fn $name(
+            stack: &mut RuntimeStack,
+            _: &[u8],
+            _: &mut usize,
+            _: usize,
+        ) -> Result<(), TrapReason> {
+            $helper(stack, $f)
+        }
+    };
+}
+
+fn drop(stack: &mut RuntimeStack, _: &[u8], _: &mut usize, _: usize) -> Result<(), TrapReason> {
+    stack.pop();
+    Ok(())
+}
+
+fn select(stack: &mut RuntimeStack, _: &[u8], _: &mut usize, _: usize) -> Result<(), TrapReason> {
+    let top = stack.pop();
+    let t2 = stack.pop();
+    if unsafe { top.short } == 0 {
+        *stack.peek_mut() = t2;
+    } // else t1 remains on the top of the stack.
+    Ok(())
+}
+
+fn local_get(
+    stack: &mut RuntimeStack,
+    instructions: &[u8],
+    pc: &mut usize,
+    locals_base: usize,
+) -> Result<(), TrapReason> {
+    let idx = get_u16(instructions, pc);
+    let val = stack.stack[locals_base + idx as usize];
+    stack.push(val);
+    Ok(())
+}
+
+fn local_set(
+    stack: &mut RuntimeStack,
+    instructions: &[u8],
+    pc: &mut usize,
+    locals_base: usize,
+) -> Result<(), TrapReason> {
+    let idx = get_u16(instructions, pc);
+    let top = stack.pop();
+    stack.stack[locals_base + idx as usize] = top;
+    Ok(())
+}
+
+fn local_tee(
+    stack: &mut RuntimeStack,
+    instructions: &[u8],
+    pc: &mut usize,
+    locals_base: usize,
+) -> Result<(), TrapReason> {
+    let idx = get_u16(instructions, pc);
+    let top = stack.peek();
+    stack.stack[locals_base + idx as usize] = top;
+    Ok(())
+}
+
+fn i32_const(
+    stack: &mut RuntimeStack,
+    instructions: &[u8],
+    pc: &mut usize,
+    _: usize,
+) -> Result<(), TrapReason> {
+    let val = get_i32(instructions, pc);
+    stack.push(StackValue::from(val));
+    Ok(())
+}
+
+fn i64_const(
+    stack: &mut RuntimeStack,
+    instructions: &[u8],
+    pc: &mut usize,
+    _: usize,
+) -> Result<(), TrapReason> {
+    let val = get_u64(instructions, pc);
+    stack.push(StackValue::from(val as i64));
+    Ok(())
+}
+
+fn i32_eqz(stack: &mut RuntimeStack, _: &[u8], _: &mut usize, _: usize) -> Result<(), TrapReason> {
+    let top = stack.peek_mut();
+    top.short = (unsafe { top.short } == 0) as i32;
+    Ok(())
+}
+
+fn i64_eqz(stack: &mut RuntimeStack, _: &[u8], _: &mut usize, _: usize) -> Result<(), TrapReason> {
+    let top = stack.peek_mut();
+    top.short = (unsafe { top.long } == 0) as i32;
+    Ok(())
+}
+
+fn i32_wrap_i64(
+    stack: &mut RuntimeStack,
+    _: &[u8],
+    _: &mut usize,
+    _: usize,
+) -> Result<(), TrapReason> {
+    let top = stack.peek_mut();
+    top.short = unsafe { top.long } as i32;
+    Ok(())
+}
+
+fn i64_extend_i32_s(
+    stack: &mut RuntimeStack,
+    _: &[u8],
+    _: &mut usize,
+    _: usize,
+) -> Result<(), TrapReason> {
+    let top = stack.peek_mut();
+    top.long = unsafe { top.short } as i64;
+    Ok(())
+}
+
+fn i64_extend_i32_u(
+    stack: &mut RuntimeStack,
+    _: &[u8],
+    _: &mut usize,
+    _: usize,
+) -> Result<(), TrapReason> {
+    let top = stack.peek_mut();
+    top.long = unsafe { top.short } as u32 as i64;
+    Ok(())
+}
+
+define_handler!(i32_eq, binary_i32, |left, right| (left == right) as i32);
+define_handler!(i32_ne, binary_i32, |left, right| (left != right) as i32);
+define_handler!(i32_lt_s, binary_i32, |left, right| (left < right) as i32);
+define_handler!(i32_lt_u, binary_i32, |left, right| ((left as u32) < (right as u32)) as i32);
+define_handler!(i32_gt_s, binary_i32, |left, right| (left > right) as i32);
+define_handler!(i32_gt_u, binary_i32, |left, right| ((left as u32) > (right as u32)) as i32);
+define_handler!(i32_le_s, binary_i32, |left, right| (left <= right) as i32);
+define_handler!(i32_le_u, binary_i32, |left, right| ((left as u32) <= (right as u32)) as i32);
+define_handler!(i32_ge_s, binary_i32, |left, right| (left >= right) as i32);
+define_handler!(i32_ge_u, binary_i32, |left, right| ((left as u32) >= (right as u32)) as i32);
+
+define_handler!(i64_eq, binary_i64_test, |left, right| (left == right) as i32);
+define_handler!(i64_ne, binary_i64_test, |left, right| (left != right) as i32);
+define_handler!(i64_lt_s, binary_i64_test, |left, right| (left < right) as i32);
+define_handler!(i64_lt_u, binary_i64_test, |left, right| ((left as u64) < (right as u64)) as i32);
+define_handler!(i64_gt_s, binary_i64_test, |left, right| (left > right) as i32);
+define_handler!(i64_gt_u, binary_i64_test, |left, right| ((left as u64) > (right as u64)) as i32);
+define_handler!(i64_le_s, binary_i64_test, |left, right| (left <= right) as i32);
+define_handler!(i64_le_u, binary_i64_test, |left, right| ((left as u64) <= (right as u64)) as i32);
+define_handler!(i64_ge_s, binary_i64_test, |left, right| (left >= right) as i32);
+define_handler!(i64_ge_u, binary_i64_test, |left, right| ((left as u64) >= (right as u64)) as i32);
+
+define_handler!(i32_clz, unary_i32, |x| x.leading_zeros() as i32);
+define_handler!(i32_ctz, unary_i32, |x| x.trailing_zeros() as i32);
+define_handler!(i32_popcnt, unary_i32, |x| x.count_ones() as i32);
+define_handler!(i32_add, binary_i32, |x, y| x.wrapping_add(y));
+define_handler!(i32_sub, binary_i32, |x, y| x.wrapping_sub(y));
+define_handler!(i32_mul, binary_i32, |x, y| x.wrapping_mul(y));
+define_handler!(i32_div_s, binary_i32_partial, |x, y| x.checked_div(y), partial);
+define_handler!(
+    i32_div_u,
+    binary_i32_partial,
+    |x, y| (x as u32).checked_div(y as u32).map(|x| x as i32),
+    partial
+);
+define_handler!(i32_rem_s, binary_i32_partial, |x, y| x.checked_rem(y), partial);
+define_handler!(
+    i32_rem_u,
+    binary_i32_partial,
+    |x, y| (x as u32).checked_rem(y as u32).map(|x| x as i32),
+    partial
+);
+define_handler!(i32_and, binary_i32, |x, y| x & y);
+define_handler!(i32_or, binary_i32, |x, y| x | y);
+define_handler!(i32_xor, binary_i32, |x, y| x ^ y);
+define_handler!(i32_shl, binary_i32, |x, y| x << (y as u32 % 32));
+define_handler!(i32_shr_s, binary_i32, |x, y| x >> (y as u32 % 32));
+define_handler!(i32_shr_u, binary_i32, |x, y| ((x as u32) >> (y as u32 % 32)) as i32);
+define_handler!(i32_rotl, binary_i32, |x, y| x.rotate_left(y as u32 % 32));
+define_handler!(i32_rotr, binary_i32, |x, y| x.rotate_right(y as u32 % 32));
+
+define_handler!(i64_clz, unary_i64, |x| x.leading_zeros() as i64);
+define_handler!(i64_ctz, unary_i64, |x| x.trailing_zeros() as i64);
+define_handler!(i64_popcnt, unary_i64, |x| x.count_ones() as i64);
+define_handler!(i64_add, binary_i64, |x, y| x.wrapping_add(y));
+define_handler!(i64_sub, binary_i64, |x, y| x.wrapping_sub(y));
+define_handler!(i64_mul, binary_i64, |x, y| x.wrapping_mul(y));
+define_handler!(i64_div_s, binary_i64_partial, |x, y| x.checked_div(y), partial);
+define_handler!(
+    i64_div_u,
+    binary_i64_partial,
+    |x, y| (x as u64).checked_div(y as u64).map(|x| x as i64),
+    partial
+);
+define_handler!(i64_rem_s, binary_i64_partial, |x, y| x.checked_rem(y), partial);
+define_handler!(
+    i64_rem_u,
+    binary_i64_partial,
+    |x, y| (x as u64).checked_rem(y as u64).map(|x| x as i64),
+    partial
+);
+define_handler!(i64_and, binary_i64, |x, y| x & y);
+define_handler!(i64_or, binary_i64, |x, y| x | y);
+define_handler!(i64_xor, binary_i64, |x, y| x ^ y);
+define_handler!(i64_shl, binary_i64, |x, y| x << (y as u64 % 64));
+define_handler!(i64_shr_s, binary_i64, |x, y| x >> (y as u64 % 64));
+define_handler!(i64_shr_u, binary_i64, |x, y| ((x as u64) >> (y as u64 % 64)) as i64);
+define_handler!(i64_rotl, binary_i64, |x, y| x.rotate_left((y as u64 % 64) as u32));
+define_handler!(i64_rotr, binary_i64, |x, y| x.rotate_right((y as u64 % 64) as u32));
+
+define_handler!(i32_extend8_s, unary_i32, |x| x as i8 as i32);
+define_handler!(i32_extend16_s, unary_i32, |x| x as i16 as i32);
+define_handler!(i64_extend8_s, unary_i64, |x| x as i8 as i64);
+define_handler!(i64_extend16_s, unary_i64, |x| x as i16 as i64);
+define_handler!(i64_extend32_s, unary_i64, |x| x as i32 as i64);
+
+const fn build_handlers() -> [Option<Handler>; 256] {
+    let mut table: [Option<Handler>; 256] = [None; 256];
+    macro_rules! register {
+        ($($opcode:ident => $handler:ident,)*) => {
+            $(table[InternalOpcode::$opcode as usize] = Some($handler);)*
+        };
+    }
+    register! {
+        Drop => drop,
+        Select => select,
+        LocalGet => local_get,
+        LocalSet => local_set,
+        LocalTee => local_tee,
+        I32Const => i32_const,
+        I64Const => i64_const,
+        I32Eqz => i32_eqz,
+        I32Eq => i32_eq,
+        I32Ne => i32_ne,
+        I32LtS => i32_lt_s,
+        I32LtU => i32_lt_u,
+        I32GtS => i32_gt_s,
+        I32GtU => i32_gt_u,
+        I32LeS => i32_le_s,
+        I32LeU => i32_le_u,
+        I32GeS => i32_ge_s,
+        I32GeU => i32_ge_u,
+        I64Eqz => i64_eqz,
+        I64Eq => i64_eq,
+        I64Ne => i64_ne,
+        I64LtS => i64_lt_s,
+        I64LtU => i64_lt_u,
+        I64GtS => i64_gt_s,
+        I64GtU => i64_gt_u,
+        I64LeS => i64_le_s,
+        I64LeU => i64_le_u,
+        I64GeS => i64_ge_s,
+        I64GeU => i64_ge_u,
+        I32Clz => i32_clz,
+        I32Ctz => i32_ctz,
+        I32Popcnt => i32_popcnt,
+        I32Add => i32_add,
+        I32Sub => i32_sub,
+        I32Mul => i32_mul,
+        I32DivS => i32_div_s,
+        I32DivU => i32_div_u,
+        I32RemS => i32_rem_s,
+        I32RemU => i32_rem_u,
+        I32And => i32_and,
+        I32Or => i32_or,
+        I32Xor => i32_xor,
+        I32Shl => i32_shl,
+        I32ShrS => i32_shr_s,
+        I32ShrU => i32_shr_u,
+        I32Rotl => i32_rotl,
+        I32Rotr => i32_rotr,
+        I64Clz => i64_clz,
+        I64Ctz => i64_ctz,
+        I64Popcnt => i64_popcnt,
+        I64Add => i64_add,
+        I64Sub => i64_sub,
+        I64Mul => i64_mul,
+        I64DivS => i64_div_s,
+        I64DivU => i64_div_u,
+        I64RemS => i64_rem_s,
+        I64RemU => i64_rem_u,
+        I64And => i64_and,
+        I64Or => i64_or,
+        I64Xor => i64_xor,
+        I64Shl => i64_shl,
+        I64ShrS => i64_shr_s,
+        I64ShrU => i64_shr_u,
+        I64Rotl => i64_rotl,
+        I64Rotr => i64_rotr,
+        I32WrapI64 => i32_wrap_i64,
+        I64ExtendI32S => i64_extend_i32_s,
+        I64ExtendI32U => i64_extend_i32_u,
+        I32Extend8S => i32_extend8_s,
+        I32Extend16S => i32_extend16_s,
+        I64Extend8S => i64_extend8_s,
+        I64Extend16S => i64_extend16_s,
+        I64Extend32S => i64_extend32_s,
+    }
+    table
+}
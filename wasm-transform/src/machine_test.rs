@@ -0,0 +1,605 @@
+//! Tests of the instructions executed by the interpreter without accessing
+//! memory or globals, or affecting control flow.
+//!
+//! Each test compiles a function that applies an instruction to constant
+//! operands and compares the result of running it with a reference
+//! implementation. The tests are run both with and without the
+//! `table-dispatch` feature, which executes exactly these instructions via a
+//! table of handlers instead of the interpreter's `match`, to check that the
+//! two dispatch strategies agree. With the `execution-trace` feature the
+//! instructions reported to the host are checked as well. Growing the memory
+//! is tested separately since it involves the host.
+use crate::{
+    artifact::ArtifactNamedImport,
+    machine::{
+        ExecutionOutcome, Host, NoInterrupt, OutOfMemory, RunResult, RuntimeError, RuntimeStack,
+        TrapReason, Value,
+    },
+    parse::parse_skeleton,
+    types::{FunctionType, Name},
+    validate::{validate_module, ValidateImportExport, ValidationConfig},
+};
+
+/// Operands of the tests of 32-bit instructions.
+const I32_OPERANDS: &[i32] =
+    &[0, 1, 2, -1, -2, 7, -13, 31, 32, 33, 0x1234_5678, i32::MIN, i32::MAX];
+
+/// Operands of the tests of 64-bit instructions.
+const I64_OPERANDS: &[i64] = &[
+    0,
+    1,
+    2,
+    -1,
+    -2,
+    7,
+    -13,
+    63,
+    64,
+    65,
+    0x1234_5678_9abc_def0,
+    i32::MIN as i64,
+    u32::MAX as i64,
+    i64::MIN,
+    i64::MAX,
+];
+
+/// An opcode of a binary instruction together with its reference
+/// implementation.
+type Binary<A, B> = (u8, fn(A, A) -> B);
+
+/// An opcode of a unary instruction together with its reference
+/// implementation.
+type Unary<A, B> = (u8, fn(A) -> B);
+
+const I32: u8 = 0x7F;
+const I64: u8 = 0x7E;
+
+/// Modules in the tests do not have imports.
+struct NoImports;
+
+impl ValidateImportExport for NoImports {
+    fn validate_import_function(
+        &self,
+        _duplicate: bool,
+        _mod_name: &Name,
+        _item_name: &Name,
+        _ty: &FunctionType,
+    ) -> bool {
+        false
+    }
+
+    fn validate_export_function(&self, _item_name: &Name, _ty: &FunctionType) -> bool { true }
+}
+
+struct NoHost;
+
+impl<I> Host<I> for NoHost {
+    type Interrupt = NoInterrupt;
+
+    fn tick_initial_memory(&mut self, _num_pages: u32) -> RunResult<()> { Ok(()) }
+
+    fn call(
+        &mut self,
+        _f: &I,
+        _memory: &mut Vec<u8>,
+        _stack: &mut RuntimeStack,
+    ) -> RunResult<Option<Self::Interrupt>> {
+        anyhow::bail!("Modules in tests have no imports.")
+    }
+}
+
+fn section(out: &mut Vec<u8>, id: u8, contents: &[u8]) {
+    out.push(id);
+    leb128::write::unsigned(out, contents.len() as u64).unwrap();
+    out.extend_from_slice(contents);
+}
+
+/// A module exporting a single function `f` with no parameters, the given
+/// result type, one local of type i32 followed by one of type i64, and the
+/// given body. The body is terminated by `end`.
+fn module_bytes(result: u8, body: &[u8]) -> Vec<u8> {
+    let mut out = b"\0asm\x01\0\0\0".to_vec();
+    section(&mut out, 1, &[1, 0x60, 0, 1, result]);
+    section(&mut out, 3, &[1, 0]);
+    section(&mut out, 7, &[1, 1, b'f', 0, 0]);
+    let mut code = vec![2, 1, I32, 1, I64];
+    code.extend_from_slice(body);
+    code.push(0x0B);
+    let mut code_section = vec![1];
+    leb128::write::unsigned(&mut code_section, code.len() as u64).unwrap();
+    code_section.extend_from_slice(&code);
+    section(&mut out, 10, &code_section);
+    out
+}
+
+/// Compile and run a function with the given result type and body.
+fn run(result: u8, body: &[u8]) -> RunResult<Value> {
+    let bytes = module_bytes(result, body);
+    let skeleton = parse_skeleton(&bytes)?;
+    let artifact = validate_module(&ValidationConfig::ALL, &NoImports, &skeleton)?
+        .compile::<ArtifactNamedImport>()?;
+    match artifact.run(&mut NoHost, "f", &[])? {
+        ExecutionOutcome::Success {
+            result: Some(v),
+            ..
+        } => Ok(v),
+        _ => anyhow::bail!("Function did not return a value."),
+    }
+}
+
+/// Compile and run a function with the given result type and body, and return
+/// the reason it trapped, if it did.
+fn trap(result: u8, body: &[u8]) -> Option<TrapReason> {
+    let bytes = module_bytes(result, body);
+    let skeleton = parse_skeleton(&bytes).expect("Module should parse.");
+    let artifact = validate_module(&ValidationConfig::ALL, &NoImports, &skeleton)
+        .expect("Module should be valid.")
+        .compile::<ArtifactNamedImport>()
+        .expect("Module should compile.");
+    match artifact.run(&mut NoHost, "f", &[]) {
+        Ok(_) => None,
+        Err(RuntimeError::Trap(reason)) => Some(reason),
+        Err(e) => panic!("Unexpected error: {}", e),
+    }
+}
+
+fn i32_const(out: &mut Vec<u8>, x: i32) {
+    out.push(0x41);
+    leb128::write::signed(out, x.into()).unwrap();
+}
+
+fn i64_const(out: &mut Vec<u8>, x: i64) {
+    out.push(0x42);
+    leb128::write::signed(out, x).unwrap();
+}
+
+/// Check the result of running `body` against the expected result, where
+/// `None` means that execution traps.
+fn check(body: &[u8], result: u8, expected: Option<Value>) {
+    let actual = run(result, body).ok();
+    assert_eq!(actual, expected, "Unexpected result of {:x?}.", body);
+}
+
+#[test]
+fn test_i32_binary() {
+    let ops: &[Binary<i32, Option<i32>>] = &[
+        (0x46, |x, y| Some((x == y) as i32)),
+        (0x47, |x, y| Some((x != y) as i32)),
+        (0x48, |x, y| Some((x < y) as i32)),
+        (0x49, |x, y| Some(((x as u32) < (y as u32)) as i32)),
+        (0x4A, |x, y| Some((x > y) as i32)),
+        (0x4B, |x, y| Some(((x as u32) > (y as u32)) as i32)),
+        (0x4C, |x, y| Some((x <= y) as i32)),
+        (0x4D, |x, y| Some(((x as u32) <= (y as u32)) as i32)),
+        (0x4E, |x, y| Some((x >= y) as i32)),
+        (0x4F, |x, y| Some(((x as u32) >= (y as u32)) as i32)),
+        (0x6A, |x, y| Some(x.wrapping_add(y))),
+        (0x6B, |x, y| Some(x.wrapping_sub(y))),
+        (0x6C, |x, y| Some(x.wrapping_mul(y))),
+        (0x6D, |x, y| x.checked_div(y)),
+        (0x6E, |x, y| (x as u32).checked_div(y as u32).map(|r| r as i32)),
+        // The interpreter traps on `i32::MIN rem -1`, unlike the Wasm
+        // specification where the result is 0.
+        (0x6F, |x, y| x.checked_rem(y)),
+        (0x70, |x, y| (x as u32).checked_rem(y as u32).map(|r| r as i32)),
+        (0x71, |x, y| Some(x & y)),
+        (0x72, |x, y| Some(x | y)),
+        (0x73, |x, y| Some(x ^ y)),
+        (0x74, |x, y| Some(x.wrapping_shl(y as u32))),
+        (0x75, |x, y| Some(x.wrapping_shr(y as u32))),
+        (0x76, |x, y| Some((x as u32).wrapping_shr(y as u32) as i32)),
+        (0x77, |x, y| Some(x.rotate_left(y as u32 % 32))),
+        (0x78, |x, y| Some(x.rotate_right(y as u32 % 32))),
+    ];
+    for &(opcode, f) in ops {
+        for &x in I32_OPERANDS {
+            for &y in I32_OPERANDS {
+                let mut body = Vec::new();
+                i32_const(&mut body, x);
+                i32_const(&mut body, y);
+                body.push(opcode);
+                check(&body, I32, f(x, y).map(Value::I32));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_i64_binary() {
+    let tests: &[Binary<i64, i32>] = &[
+        (0x51, |x, y| (x == y) as i32),
+        (0x52, |x, y| (x != y) as i32),
+        (0x53, |x, y| (x < y) as i32),
+        (0x54, |x, y| ((x as u64) < (y as u64)) as i32),
+        (0x55, |x, y| (x > y) as i32),
+        (0x56, |x, y| ((x as u64) > (y as u64)) as i32),
+        (0x57, |x, y| (x <= y) as i32),
+        (0x58, |x, y| ((x as u64) <= (y as u64)) as i32),
+        (0x59, |x, y| (x >= y) as i32),
+        (0x5A, |x, y| ((x as u64) >= (y as u64)) as i32),
+    ];
+    let ops: &[Binary<i64, Option<i64>>] = &[
+        (0x7C, |x, y| Some(x.wrapping_add(y))),
+        (0x7D, |x, y| Some(x.wrapping_sub(y))),
+        (0x7E, |x, y| Some(x.wrapping_mul(y))),
+        (0x7F, |x, y| x.checked_div(y)),
+        (0x80, |x, y| (x as u64).checked_div(y as u64).map(|r| r as i64)),
+        // The interpreter traps on `i64::MIN rem -1`, unlike the Wasm
+        // specification where the result is 0.
+        (0x81, |x, y| x.checked_rem(y)),
+        (0x82, |x, y| (x as u64).checked_rem(y as u64).map(|r| r as i64)),
+        (0x83, |x, y| Some(x & y)),
+        (0x84, |x, y| Some(x | y)),
+        (0x85, |x, y| Some(x ^ y)),
+        (0x86, |x, y| Some(x.wrapping_shl(y as u32))),
+        (0x87, |x, y| Some(x.wrapping_shr(y as u32))),
+        (0x88, |x, y| Some((x as u64).wrapping_shr(y as u32) as i64)),
+        (0x89, |x, y| Some(x.rotate_left((y as u64 % 64) as u32))),
+        (0x8A, |x, y| Some(x.rotate_right((y as u64 % 64) as u32))),
+    ];
+    for &x in I64_OPERANDS {
+        for &y in I64_OPERANDS {
+            let mut operands = Vec::new();
+            i64_const(&mut operands, x);
+            i64_const(&mut operands, y);
+            for &(opcode, f) in tests {
+                let mut body = operands.clone();
+                body.push(opcode);
+                check(&body, I32, Some(Value::I32(f(x, y))));
+            }
+            for &(opcode, f) in ops {
+                let mut body = operands.clone();
+                body.push(opcode);
+                check(&body, I64, f(x, y).map(Value::I64));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_unary() {
+    let i32_ops: &[Unary<i32, i32>] = &[
+        (0x45, |x| (x == 0) as i32),
+        (0x67, |x| x.leading_zeros() as i32),
+        (0x68, |x| x.trailing_zeros() as i32),
+        (0x69, |x| x.count_ones() as i32),
+        (0xC0, |x| x as i8 as i32),
+        (0xC1, |x| x as i16 as i32),
+    ];
+    for &(opcode, f) in i32_ops {
+        for &x in I32_OPERANDS {
+            let mut body = Vec::new();
+            i32_const(&mut body, x);
+            body.push(opcode);
+            check(&body, I32, Some(Value::I32(f(x))));
+        }
+    }
+    let i32_to_i64_ops: &[Unary<i32, i64>] = &[(0xAC, |x| x as i64), (0xAD, |x| x as u32 as i64)];
+    for &(opcode, f) in i32_to_i64_ops {
+        for &x in I32_OPERANDS {
+            let mut body = Vec::new();
+            i32_const(&mut body, x);
+            body.push(opcode);
+            check(&body, I64, Some(Value::I64(f(x))));
+        }
+    }
+    let i64_to_i32_ops: &[Unary<i64, i32>] = &[(0x50, |x| (x == 0) as i32), (0xA7, |x| x as i32)];
+    let i64_ops: &[Unary<i64, i64>] = &[
+        (0x79, |x| x.leading_zeros() as i64),
+        (0x7A, |x| x.trailing_zeros() as i64),
+        (0x7B, |x| x.count_ones() as i64),
+        (0xC2, |x| x as i8 as i64),
+        (0xC3, |x| x as i16 as i64),
+        (0xC4, |x| x as i32 as i64),
+    ];
+    for &x in I64_OPERANDS {
+        for &(opcode, f) in i64_to_i32_ops {
+            let mut body = Vec::new();
+            i64_const(&mut body, x);
+            body.push(opcode);
+            check(&body, I32, Some(Value::I32(f(x))));
+        }
+        for &(opcode, f) in i64_ops {
+            let mut body = Vec::new();
+            i64_const(&mut body, x);
+            body.push(opcode);
+            check(&body, I64, Some(Value::I64(f(x))));
+        }
+    }
+}
+
+#[test]
+fn test_locals_drop_select() {
+    let mut body = Vec::new();
+    // local 0 := 5
+    i32_const(&mut body, 5);
+    body.extend_from_slice(&[0x21, 0]);
+    // local 0 := 3, leaving 3 on the stack.
+    i32_const(&mut body, 3);
+    body.extend_from_slice(&[0x22, 0]);
+    // local 1 is still 0.
+    body.extend_from_slice(&[0x20, 1, 0x50]);
+    // 3 + 3 + 1
+    body.extend_from_slice(&[0x20, 0, 0x6A, 0x6A]);
+    check(&body, I32, Some(Value::I32(7)));
+
+    for &(condition, expected) in &[(0, 20), (1, 10), (-1, 10)] {
+        let mut body = Vec::new();
+        i64_const(&mut body, 10);
+        i64_const(&mut body, 20);
+        i64_const(&mut body, 30);
+        // Drop the 30.
+        body.push(0x1A);
+        i32_const(&mut body, condition);
+        body.push(0x1B);
+        check(&body, I64, Some(Value::I64(expected)));
+    }
+}
+
+#[test]
+fn test_consts() {
+    for &x in I32_OPERANDS {
+        let mut body = Vec::new();
+        i32_const(&mut body, x);
+        check(&body, I32, Some(Value::I32(x)));
+    }
+    for &x in I64_OPERANDS {
+        let mut body = Vec::new();
+        i64_const(&mut body, x);
+        check(&body, I64, Some(Value::I64(x)));
+    }
+}
+
+#[test]
+fn test_trap_reasons() {
+    assert_eq!(trap(I32, &[0x00]), Some(TrapReason::Unreachable));
+    let mut body = Vec::new();
+    i32_const(&mut body, 1);
+    i32_const(&mut body, 0);
+    body.push(0x6D);
+    assert_eq!(trap(I32, &body), Some(TrapReason::IntegerDivision));
+    let mut body = Vec::new();
+    i64_const(&mut body, i64::MIN);
+    i64_const(&mut body, -1);
+    body.push(0x7F);
+    assert_eq!(trap(I64, &body), Some(TrapReason::IntegerDivision));
+    let mut body = Vec::new();
+    i32_const(&mut body, 7);
+    assert_eq!(trap(I32, &body), None);
+}
+
+#[test]
+fn test_disassemble() {
+    // Jump out of a block if local 0 is non-zero, otherwise return 7.
+    let mut body = vec![0x02, 0x40, 0x20, 0, 0x0D, 0, 0x0B];
+    i32_const(&mut body, 7);
+    let bytes = module_bytes(I32, &body);
+    let skeleton = parse_skeleton(&bytes).unwrap();
+    let artifact = validate_module(&ValidationConfig::ALL, &NoImports, &skeleton)
+        .unwrap()
+        .compile::<ArtifactNamedImport>()
+        .unwrap();
+    let text = crate::artifact::disassemble::disassemble(&artifact).unwrap();
+    let expected = "function 0: type 0, () -> i32
+  locals: 0 i32, 1 i64
+  0000: local.get 0
+  0003: br_if drop 1 @000c
+  000c: i32.const 7
+  0011: return
+";
+    assert!(text.ends_with(expected), "Unexpected disassembly:\n{}", text);
+    assert!(text.contains("exports:\n  f: function 0\n"));
+}
+
+#[test]
+fn test_memory_grow() {
+    /// Records the calls to `tick_memory_grow`, and limits the memory to
+    /// `limit` pages.
+    struct MemoryHost {
+        limit: u32,
+        grown: Vec<(u32, u32)>,
+    }
+    impl<I> Host<I> for MemoryHost {
+        type Interrupt = NoInterrupt;
+
+        fn tick_initial_memory(&mut self, _num_pages: u32) -> RunResult<()> { Ok(()) }
+
+        fn tick_memory_grow(&mut self, current_pages: u32, num_pages: u32) -> RunResult<()> {
+            self.grown.push((current_pages, num_pages));
+            let requested = current_pages + num_pages;
+            if requested > self.limit {
+                anyhow::bail!(OutOfMemory {
+                    requested,
+                    limit: self.limit,
+                })
+            }
+            Ok(())
+        }
+
+        fn call(
+            &mut self,
+            _f: &I,
+            _memory: &mut Vec<u8>,
+            _stack: &mut RuntimeStack,
+        ) -> RunResult<Option<Self::Interrupt>> {
+            anyhow::bail!("Modules in tests have no imports.")
+        }
+    }
+    // A module with a memory of initially 1 and at most 4 pages, whose
+    // function grows the memory by the given number of pages.
+    let grow = |n: i32, limit: u32| {
+        let mut out = b"\0asm\x01\0\0\0".to_vec();
+        section(&mut out, 1, &[1, 0x60, 0, 1, I32]);
+        section(&mut out, 3, &[1, 0]);
+        section(&mut out, 5, &[1, 1, 1, 4]);
+        section(&mut out, 7, &[1, 1, b'f', 0, 0]);
+        let mut code = vec![0];
+        i32_const(&mut code, n);
+        code.extend_from_slice(&[0x40, 0, 0x0B]);
+        let mut code_section = vec![1, code.len() as u8];
+        code_section.extend_from_slice(&code);
+        section(&mut out, 10, &code_section);
+        let skeleton = parse_skeleton(&out).unwrap();
+        let artifact = validate_module(&ValidationConfig::ALL, &NoImports, &skeleton)
+            .unwrap()
+            .compile::<ArtifactNamedImport>()
+            .unwrap();
+        let mut host = MemoryHost {
+            limit,
+            grown: Vec::new(),
+        };
+        let result = match artifact.run(&mut host, "f", &[]) {
+            Ok(ExecutionOutcome::Success {
+                result: Some(Value::I32(v)),
+                ..
+            }) => Ok(v),
+            Ok(_) => panic!("Function did not return an i32."),
+            Err(e) => Err(e),
+        };
+        (result, host.grown)
+    };
+    let (result, grown) = grow(2, 3);
+    assert_eq!(result.unwrap(), 1);
+    assert_eq!(grown, [(1, 2)]);
+    // The host is not involved if the memory does not change.
+    let (result, grown) = grow(0, 3);
+    assert_eq!(result.unwrap(), 1);
+    assert!(grown.is_empty());
+    // Exceeding the maximum of the module fails without charging.
+    let (result, grown) = grow(4, 8);
+    assert_eq!(result.unwrap(), -1);
+    assert!(grown.is_empty());
+    // Exceeding the limit of the host stops execution.
+    let (result, grown) = grow(3, 3);
+    match result {
+        Err(RuntimeError::OutOfMemory(e)) => assert_eq!(e, OutOfMemory {
+            requested: 4,
+            limit:     3,
+        }),
+        r => panic!("Unexpected result {:?}.", r),
+    }
+    assert_eq!(grown, [(1, 3)]);
+}
+
+#[test]
+fn test_inline_call_depth() {
+    use crate::metering_transformation::MeteringConfig;
+    /// Implements the metering imports, except for tracking calls, which is
+    /// done by the module itself.
+    struct MeteringHost;
+    impl Host<ArtifactNamedImport> for MeteringHost {
+        type Interrupt = NoInterrupt;
+
+        fn tick_initial_memory(&mut self, _num_pages: u32) -> RunResult<()> { Ok(()) }
+
+        fn call(
+            &mut self,
+            f: &ArtifactNamedImport,
+            _memory: &mut Vec<u8>,
+            stack: &mut RuntimeStack,
+        ) -> RunResult<Option<Self::Interrupt>> {
+            match f.get_item_name().as_ref() {
+                "account_energy" => {
+                    stack.pop();
+                }
+                name => anyhow::bail!("Unexpected call of {}.", name),
+            }
+            Ok(None)
+        }
+    }
+    // A function that calls itself recursively `n` times for argument `n`, and
+    // returns `n`.
+    let mut out = b"\0asm\x01\0\0\0".to_vec();
+    section(&mut out, 1, &[1, 0x60, 1, I32, 1, I32]);
+    section(&mut out, 3, &[1, 0]);
+    section(&mut out, 7, &[1, 1, b'f', 0, 0]);
+    let code = [
+        0, 0x20, 0, 0x45, 0x04, I32, 0x41, 0, 0x05, 0x20, 0, 0x41, 1, 0x6B, 0x10, 0, 0x41, 1, 0x6A,
+        0x0B, 0x0B,
+    ];
+    let mut code_section = vec![1, code.len() as u8];
+    code_section.extend_from_slice(&code);
+    section(&mut out, 10, &code_section);
+    let skeleton = parse_skeleton(&out).unwrap();
+    let mut module = validate_module(&ValidationConfig::ALL, &NoImports, &skeleton).unwrap();
+    module
+        .inject_metering_with_config(&MeteringConfig {
+            inline_call_depth: Some(5),
+        })
+        .unwrap();
+    let artifact = module.compile::<ArtifactNamedImport>().unwrap();
+    match artifact.run(&mut MeteringHost, "f", &[Value::I32(5)]) {
+        Ok(ExecutionOutcome::Success {
+            result: Some(Value::I32(5)),
+            ..
+        }) => (),
+        r => panic!("Unexpected result {:?}.", r),
+    }
+    match artifact.run(&mut MeteringHost, "f", &[Value::I32(6)]) {
+        Err(RuntimeError::Trap(TrapReason::Unreachable)) => (),
+        r => panic!("Unexpected result {:?}.", r),
+    }
+}
+
+#[cfg(feature = "execution-trace")]
+#[test]
+fn test_trace() {
+    use crate::artifact::{InternalOpcode, StackValue};
+    /// Records the position and opcode of the executed instructions, and the
+    /// low 32 bits of the top of the stack.
+    #[derive(Default)]
+    struct TraceHost(Vec<(usize, String, Option<i32>)>);
+    impl<I> Host<I> for TraceHost {
+        type Interrupt = NoInterrupt;
+
+        fn tick_initial_memory(&mut self, _num_pages: u32) -> RunResult<()> { Ok(()) }
+
+        fn call(
+            &mut self,
+            _f: &I,
+            _memory: &mut Vec<u8>,
+            _stack: &mut RuntimeStack,
+        ) -> RunResult<Option<Self::Interrupt>> {
+            anyhow::bail!("Modules in tests have no imports.")
+        }
+
+        fn trace(
+            &mut self,
+            function: usize,
+            offset: usize,
+            opcode: InternalOpcode,
+            stack_top: Option<StackValue>,
+        ) {
+            assert_eq!(function, 0);
+            self.0.push((offset, format!("{:?}", opcode), stack_top.map(|v| unsafe { v.short })))
+        }
+    }
+    // Set local 0 to 3, and then jump out of a block if it is non-zero,
+    // otherwise return 7. The top of the stack is local 1 when the operand
+    // stack is empty.
+    let mut body = Vec::new();
+    i32_const(&mut body, 3);
+    body.extend_from_slice(&[0x21, 0, 0x02, 0x40, 0x20, 0, 0x0D, 0, 0x0B]);
+    i32_const(&mut body, 7);
+    let bytes = module_bytes(I32, &body);
+    let skeleton = parse_skeleton(&bytes).unwrap();
+    let artifact = validate_module(&ValidationConfig::ALL, &NoImports, &skeleton)
+        .unwrap()
+        .compile::<ArtifactNamedImport>()
+        .unwrap();
+    let mut host = TraceHost::default();
+    artifact.run(&mut host, "f", &[]).unwrap();
+    let expected = [
+        (0x00, "I32Const", Some(0)),
+        (0x05, "LocalSet", Some(3)),
+        (0x08, "LocalGet", Some(0)),
+        (0x0b, "BrIf", Some(3)),
+        (0x14, "I32Const", Some(0)),
+        (0x19, "Return", Some(7)),
+    ];
+    assert!(
+        host.0.iter().map(|(o, n, t)| (*o, n.as_str(), *t)).eq(expected.iter().copied()),
+        "Unexpected trace {:?}.",
+        host.0
+    );
+}
@@ -0,0 +1,209 @@
+//! Differential tests of the energy charged by the metering transformation.
+//!
+//! A corpus of representative modules is instrumented by both the frozen
+//! transformation in [`metering_transformation_v0`] and the current one, and
+//! the total energy charged by each is compared. Since changes to metering
+//! change the on-chain cost of executing contracts, the results are recorded
+//! in [`EXPECTATIONS_FILE`], and the test fails if they differ from it. An
+//! intentional change is recorded by running the tests with the
+//! `UPDATE_METERING_EXPECTATIONS` environment variable set, which rewrites
+//! the file, and committing the result.
+//!
+//! The energy of a module is measured statically. It is the sum of all the
+//! amounts charged by accounting instructions in the module, each counted
+//! once. This is not the cost of any particular execution, but any change to
+//! the cost of instructions or to the placement of accounting instructions
+//! changes it.
+//!
+//! The same corpus is used to check the consistency of the
+//! [energy_report](crate::energy_report::energy_report) of the metered
+//! modules.
+use crate::{
+    energy_report::energy_report, metering_transformation::FN_IDX_ACCOUNT_ENERGY,
+    metering_transformation_v0, parse::*, types::*, validate::*,
+};
+use std::{collections::BTreeMap, fmt::Write};
+
+/// File with the expected energy of each module in the corpus. Each line is
+/// of the form `<module> <energy with v0> <energy with current>`.
+const EXPECTATIONS_FILE: &str = "../testdata/metering-expectations.txt";
+
+/// The modules on which metering is compared, relative to the root of this
+/// crate.
+pub(crate) const CORPUS: &[&str] = &[
+    "../testdata/contracts/global-offset-test.wasm",
+    "../testdata/schemas/cis1-wccd-embedded-schema-v0-unversioned.wasm",
+    "../wasm-chain-integration/benches/counter.wasm",
+    "../wasm-chain-integration/benches/simple_game.wasm",
+    "../wasm-chain-integration/benches/code/host-functions.wasm",
+    "../wasm-chain-integration/benches/code/instruction.wasm",
+    "../wasm-chain-integration/benches/code/loop-energy.wasm",
+    "../wasm-chain-integration/benches/code/memory-instruction.wasm",
+    "../wasm-chain-integration/benches/code/minimal.wasm",
+    "../wasm-chain-integration/benches/code/v1/host-functions.wasm",
+    "../wasm-chain-integration/test-data/code/v1/crypto-primitives-tests.wasm",
+    "../wasm-chain-integration/test-data/code/v1/invoke-tests.wasm",
+];
+
+/// Allow all imports and exports. The corpus contains both V0 and V1 modules,
+/// and which host functions they use does not affect metering.
+pub(crate) struct AllowAll;
+
+impl ValidateImportExport for AllowAll {
+    fn validate_import_function(
+        &self,
+        _duplicate: bool,
+        _mod_name: &Name,
+        _item_name: &Name,
+        _ty: &FunctionType,
+    ) -> bool {
+        true
+    }
+
+    fn validate_export_function(&self, _item_name: &Name, _ty: &FunctionType) -> bool { true }
+}
+
+/// Energy charged by the two versions of the transformation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Energies {
+    v0:      u64,
+    current: u64,
+}
+
+/// The sum of the amounts charged by accounting instructions in the module.
+fn static_energy(module: &Module) -> u64 {
+    fn go(instrs: &[OpCode]) -> u64 {
+        instrs
+            .windows(2)
+            .map(|w| match (&w[0], &w[1]) {
+                (OpCode::I64Const(e), OpCode::Call(FN_IDX_ACCOUNT_ENERGY)) => *e as u64,
+                _ => 0,
+            })
+            .sum()
+    }
+    module.code.impls.iter().map(|code| go(&code.expr.instrs)).sum()
+}
+
+/// Whether the instruction existed when the frozen transformation in
+/// [`metering_transformation_v0`] was deployed, so that it can be passed to it.
+fn is_legacy_instruction(instr: &OpCode) -> bool {
+    !matches!(
+        instr,
+        OpCode::MemoryCopy
+            | OpCode::MemoryFill
+            | OpCode::I32Extend8S
+            | OpCode::I32Extend16S
+            | OpCode::I64Extend8S
+            | OpCode::I64Extend16S
+            | OpCode::I64Extend32S
+    )
+}
+
+fn measure(path: &str) -> anyhow::Result<Energies> {
+    let bytes = std::fs::read(path)?;
+    let validate =
+        || validate_module(&ValidationConfig::LEGACY, &AllowAll, &parse_skeleton(&bytes)?);
+    let mut old = validate()?;
+    anyhow::ensure!(
+        old.code.impls.iter().all(|code| code.expr.instrs.iter().all(is_legacy_instruction)),
+        "The module uses instructions that the v0 transformation does not support."
+    );
+    metering_transformation_v0::inject_metering(&mut old)?;
+    let mut new = validate()?;
+    new.inject_metering()?;
+    Ok(Energies {
+        v0:      static_energy(&old),
+        current: static_energy(&new),
+    })
+}
+
+fn parse_expectations(contents: &str) -> anyhow::Result<BTreeMap<String, Energies>> {
+    let mut out = BTreeMap::new();
+    for line in contents.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        let parts = line.split_whitespace().collect::<Vec<_>>();
+        anyhow::ensure!(parts.len() == 3, "Malformed expectation line: {}", line);
+        out.insert(parts[0].to_owned(), Energies {
+            v0:      parts[1].parse()?,
+            current: parts[2].parse()?,
+        });
+    }
+    Ok(out)
+}
+
+fn render_expectations(results: &BTreeMap<String, Energies>) -> String {
+    let mut out = String::from(
+        "# Static energy charged by metering for each module in the corpus of\n# \
+         wasm-transform/src/metering_compatibility_test.rs.\n# Columns: module, energy with \
+         metering v0, energy with current metering.\n",
+    );
+    for (path, e) in results {
+        writeln!(out, "{} {} {}", path, e.v0, e.current).expect("Writing to a string succeeds.");
+    }
+    out
+}
+
+#[test]
+fn metering_energy_matches_expectations() -> anyhow::Result<()> {
+    let mut results = BTreeMap::new();
+    for path in CORPUS {
+        let energies = measure(path).map_err(|e| anyhow::anyhow!("{}: {}", path, e))?;
+        results.insert(path.to_string(), energies);
+    }
+    let mut report = String::new();
+    for (path, e) in results.iter() {
+        writeln!(
+            report,
+            "{}: v0 = {}, current = {}, delta = {}",
+            path,
+            e.v0,
+            e.current,
+            e.current as i128 - e.v0 as i128
+        )?;
+    }
+    if std::env::var_os("UPDATE_METERING_EXPECTATIONS").is_some() {
+        std::fs::write(EXPECTATIONS_FILE, render_expectations(&results))?;
+        return Ok(());
+    }
+    let expected = parse_expectations(&std::fs::read_to_string(EXPECTATIONS_FILE)?)?;
+    anyhow::ensure!(
+        expected == results,
+        "Metering energy differs from {}. If the change is intentional rerun the tests with \
+         UPDATE_METERING_EXPECTATIONS set and commit the result.\n{}",
+        EXPECTATIONS_FILE,
+        report
+    );
+    Ok(())
+}
+
+#[test]
+fn energy_report_consistent() -> anyhow::Result<()> {
+    for path in CORPUS {
+        let bytes = std::fs::read(path)?;
+        let mut module =
+            validate_module(&ValidationConfig::LEGACY, &AllowAll, &parse_skeleton(&bytes)?)?;
+        module.inject_metering()?;
+        let report = energy_report(&module)?;
+        let num_exported_functions = module
+            .export
+            .exports
+            .iter()
+            .filter(|e| matches!(e.description, ExportDescription::Func { .. }))
+            .count();
+        anyhow::ensure!(report.len() == num_exported_functions, "{}: missing functions", path);
+        let module_energy = static_energy(&module);
+        for r in report {
+            // Every function is charged at least for its entry, and at most as
+            // much as the whole module.
+            anyhow::ensure!(
+                r.static_energy > 0
+                    && r.static_energy <= module_energy
+                    && r.num_accounting_instructions > 0,
+                "{}: unexpected energy of {}: {:?}",
+                path,
+                r.name,
+                r
+            );
+        }
+    }
+    Ok(())
+}
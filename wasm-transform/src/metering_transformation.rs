@@ -1,7 +1,7 @@
 //! A program transformation that inserts metering instructions into
 //! a Wasm module.
 
-use crate::types::*;
+use crate::{constants::MAX_NUM_GLOBALS, types::*};
 use anyhow::{anyhow, bail};
 use std::{convert::TryInto, rc::Rc};
 
@@ -26,6 +26,26 @@ pub const FN_IDX_MEMORY_ALLOC: FuncIndex = 3;
 /// this constant.
 pub const NUM_ADDED_FUNCTIONS: FuncIndex = 4;
 
+/// Configuration of the metering transformation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeteringConfig {
+    /// Limit the number of nested calls to the given number with a counter
+    /// in a mutable global that is added to the module, instead of by calls
+    /// to the `track_call` and `track_return` host functions around each
+    /// call. A call that would exceed the limit traps as if it executed
+    /// `unreachable`. The imports of the host functions are still added, so
+    /// that functions have the same indices in both cases.
+    pub inline_call_depth: Option<u32>,
+}
+
+impl MeteringConfig {
+    /// Track calls with the host functions. This is the transformation
+    /// applied to modules on the chain before the counter was introduced.
+    pub const LEGACY: Self = Self {
+        inline_call_depth: None,
+    };
+}
+
 /// Result of a transformation. The transformation should generally not fail on
 /// a well-formed module, i.e., one that has been validated. But we might want
 /// to put additional restrictions on a module, in which case we have the
@@ -125,6 +145,13 @@ pub mod cost {
     /// Constant part for the memory grow instruction. The variable part is
     /// charged for by the host function.
     pub const MEMGROW: Energy = read_stack(1) + write_stack(1) + 8;
+    /// Constant part for the memory copy instruction, which checks the bounds
+    /// of both the source and the destination. The variable part, one unit
+    /// per byte like [store], is charged before the instruction.
+    pub const MEMCOPY: Energy = read_stack(3) + 2 * BOUNDS + 2;
+    /// Constant part for the memory fill instruction. The variable part is
+    /// charged in the same way as for [MEMCOPY].
+    pub const MEMFILL: Energy = read_stack(3) + BOUNDS + 2;
 
     /// Control instructions
     ///
@@ -257,6 +284,8 @@ pub mod cost {
             I64Store32(_) => BOUNDS + 2 + 2 + 4,
             MemorySize => MEMSIZE,
             MemoryGrow => MEMGROW,
+            MemoryCopy => MEMCOPY,
+            MemoryFill => MEMFILL,
 
             // Numeric instructions
             I32Const(_) => CONST,
@@ -325,6 +354,12 @@ pub mod cost {
             I32WrapI64 => SIMPLE_UNOP,
             I64ExtendI32S => SIMPLE_UNOP,
             I64ExtendI32U => SIMPLE_UNOP,
+
+            I32Extend8S => SIMPLE_UNOP,
+            I32Extend16S => SIMPLE_UNOP,
+            I64Extend8S => SIMPLE_UNOP,
+            I64Extend16S => SIMPLE_UNOP,
+            I64Extend32S => SIMPLE_UNOP,
         };
         Ok(res)
     }
@@ -353,6 +388,15 @@ struct InstrSeqTransformer<'a, C> {
     /// Pending instructions that are going to be inserted after the energy
     /// charging instruction. This is a temporary cache.
     pending_instructions: InstrSeq,
+    /// Index of an additional local of type I32, used to charge for the
+    /// number of bytes processed by bulk memory instructions.
+    scratch_local:        LocalIndex,
+    /// Whether [scratch_local](Self::scratch_local) is used, and must be
+    /// added to the locals of the function.
+    scratch_used:         bool,
+    /// The global that counts the number of calls that may still be made, if
+    /// calls are tracked inline instead of by host functions.
+    call_depth_global:    Option<GlobalIndex>,
 }
 
 impl<'b, C: HasTransformationContext> InstrSeqTransformer<'b, C> {
@@ -396,6 +440,44 @@ impl<'b, C: HasTransformationContext> InstrSeqTransformer<'b, C> {
         self.add_to_new(instr);
     }
 
+    /// Add the instructions that track the start of a call to the pending
+    /// sequence.
+    fn track_call(&mut self) {
+        if let Some(g) = self.call_depth_global {
+            // Trap if no more calls may be made, and otherwise decrement the counter.
+            self.pending_instructions.extend_from_slice(&[
+                OpCode::GlobalGet(g),
+                OpCode::I32Eqz,
+                OpCode::If {
+                    ty: BlockType::EmptyType,
+                },
+                OpCode::Unreachable,
+                OpCode::End,
+                OpCode::GlobalGet(g),
+                OpCode::I32Const(1),
+                OpCode::I32Sub,
+                OpCode::GlobalSet(g),
+            ])
+        } else {
+            self.add_to_pending(&OpCode::Call(FN_IDX_TRACK_CALL))
+        }
+    }
+
+    /// Add the instructions that track the return from a call to the output
+    /// sequence.
+    fn track_return(&mut self) {
+        if let Some(g) = self.call_depth_global {
+            self.new_seq.extend_from_slice(&[
+                OpCode::GlobalGet(g),
+                OpCode::I32Const(1),
+                OpCode::I32Add,
+                OpCode::GlobalSet(g),
+            ])
+        } else {
+            self.add_to_new(&OpCode::Call(FN_IDX_TRACK_RETURN))
+        }
+    }
+
     /// Add the OpCode to the pending sequence.
     fn add_to_pending(&mut self, instr: &OpCode) { self.pending_instructions.push(instr.clone()); }
 
@@ -481,6 +563,16 @@ impl<'b, C: HasTransformationContext> InstrSeqTransformer<'b, C> {
                     self.add_to_pending(&Call(FN_IDX_MEMORY_ALLOC));
                     self.add_to_pending(instr);
                 }
+                MemoryCopy | MemoryFill => {
+                    // The number of bytes is on top of the stack. Charge one unit of energy per
+                    // byte, keeping the number in the scratch local to restore it afterwards.
+                    self.scratch_used = true;
+                    self.add_to_pending(&LocalTee(self.scratch_local));
+                    self.add_to_pending(&I64ExtendI32U);
+                    self.add_to_pending(&Call(FN_IDX_ACCOUNT_ENERGY));
+                    self.add_to_pending(&LocalGet(self.scratch_local));
+                    self.add_to_pending(instr);
+                }
                 Unreachable => self.add_instr_account_energy(instr),
                 Br(_) => {
                     self.add_instr_account_energy(instr);
@@ -574,15 +666,15 @@ impl<'b, C: HasTransformationContext> InstrSeqTransformer<'b, C> {
                 // We need to change which function we call since we've inserted NUM_ADDED_FUNCTIONS
                 // functions at the beginning of the module, for cost accounting.
                 Call(idx) => {
-                    self.add_to_pending(&Call(FN_IDX_TRACK_CALL));
+                    self.track_call();
                     self.add_instr_account_energy(&Call(idx + NUM_ADDED_FUNCTIONS));
-                    self.add_to_new(&Call(FN_IDX_TRACK_RETURN));
+                    self.track_return();
                 }
                 // The call indirect function does not have to be reindexed since the table is.
                 CallIndirect(_) => {
-                    self.add_to_pending(&Call(FN_IDX_TRACK_CALL));
+                    self.track_call();
                     self.add_instr_account_energy(instr);
-                    self.add_to_new(&Call(FN_IDX_TRACK_RETURN));
+                    self.track_return();
                 }
                 _ => {
                     // In all other cases, just add the instruction to the pending instructions.
@@ -639,6 +731,25 @@ impl HasTransformationContext for Module {
 pub fn inject_accounting<C: HasTransformationContext>(
     function: &Code,
     module: &C,
+) -> TransformationResult<Code> {
+    inject_accounting_worker(function, module, None)
+}
+
+/// Inject cost accounting into the function like [inject_accounting], but
+/// track calls with the counter in the given global instead of with host
+/// functions, see [MeteringConfig::inline_call_depth].
+pub fn inject_accounting_with_call_depth_global<C: HasTransformationContext>(
+    function: &Code,
+    module: &C,
+    call_depth_global: GlobalIndex,
+) -> TransformationResult<Code> {
+    inject_accounting_worker(function, module, Some(call_depth_global))
+}
+
+fn inject_accounting_worker<C: HasTransformationContext>(
+    function: &Code,
+    module: &C,
+    call_depth_global: Option<GlobalIndex>,
 ) -> TransformationResult<Code> {
     // At the beginning of a function, we charge for its invocation and the first
     // unconditionally executed instructions of the body and account for its maximum
@@ -658,14 +769,27 @@ pub fn inject_accounting<C: HasTransformationContext>(
         new_seq: InstrSeq::new(),
         energy,
         pending_instructions: Vec::new(),
+        scratch_local: function.num_locals,
+        scratch_used: false,
+        call_depth_global,
     };
 
     transformer.run(function.expr.instrs.iter())?;
 
+    let mut locals = function.locals.clone();
+    let mut num_locals = function.num_locals;
+    if transformer.scratch_used {
+        locals.push(Local {
+            multiplicity: 1,
+            ty:           ValueType::I32,
+        });
+        num_locals += 1;
+    }
     Ok(Code {
         ty: function.ty.clone(),
         expr: Expression::from(transformer.new_seq),
-        locals: function.locals.clone(),
+        locals,
+        num_locals,
         ..*function
     })
 }
@@ -710,8 +834,37 @@ impl<'a> HasTransformationContext for ModuleContext<'a> {
 }
 
 impl Module {
-    /// Add metering instructions to the module.
+    /// Add metering instructions to the module, as configured by
+    /// [MeteringConfig::LEGACY].
     pub fn inject_metering(&mut self) -> TransformationResult<()> {
+        self.inject_metering_with_config(&MeteringConfig::LEGACY)
+    }
+
+    /// Add metering instructions to the module, as determined by the given
+    /// configuration.
+    pub fn inject_metering_with_config(
+        &mut self,
+        config: &MeteringConfig,
+    ) -> TransformationResult<()> {
+        let call_depth_global = match config.inline_call_depth {
+            Some(limit) => {
+                let idx = self.global.globals.len();
+                if idx >= MAX_NUM_GLOBALS {
+                    bail!(
+                        "Cannot add a global for tracking calls to a module with {} globals.",
+                        idx
+                    )
+                }
+                // The counter is compared with zero only, so the limit can be the
+                // full range of u32.
+                self.global.globals.push(Global {
+                    init:    GlobalInit::I32(limit as i32),
+                    mutable: true,
+                });
+                Some(idx as GlobalIndex)
+            }
+            None => None,
+        };
         // Update the elements to account for the inserted imports.
         for elem in self.element.elements.iter_mut() {
             for init in elem.inits.iter_mut() {
@@ -724,7 +877,7 @@ impl Module {
             imported: &self.import.imports,
         };
         for code in self.code.impls.iter_mut() {
-            let injected_code = inject_accounting(code, &ctx)?;
+            let injected_code = inject_accounting_worker(code, &ctx, call_depth_global)?;
             *code = injected_code;
         }
 
@@ -311,6 +311,41 @@ fn test_call() {
     )
 }
 
+#[test]
+fn test_call_inline_depth() {
+    let ctx = TransformationContext {
+        types: vec![],
+        funcs: vec![FunctionType {
+            parameters: vec![I32],
+            result:     None,
+        }],
+    };
+    let f = Code {
+        locals:     mk_locals(&[I32, I64]),
+        ty_idx:     0,
+        expr:       Expression {
+            instrs: vec![I32Const(10), Call(0), End],
+        },
+        ty:         Rc::new(FunctionType::empty()),
+        num_locals: 2,
+    };
+    let expected = flatten![
+        energy!(ENTRY + CONST + invoke_before(1, 0)),
+        [I32Const(10)],
+        [GlobalGet(3), I32Eqz, If {
+            ty: EmptyType,
+        }],
+        [Unreachable, End, GlobalGet(3), I32Const(1), I32Sub, GlobalSet(3)],
+        [Call(NUM_ADDED_FUNCTIONS)],
+        [GlobalGet(3), I32Const(1), I32Add, GlobalSet(3)],
+        [End]
+    ];
+    assert_eq!(
+        inject_accounting_with_call_depth_global(&f, &ctx, 3).unwrap().expr.instrs,
+        expected
+    );
+}
+
 #[test]
 fn test_call_indirect() {
     test_body_ctx(
@@ -1121,3 +1156,39 @@ fn test_memory_grow() {
         ],
     )
 }
+
+#[test]
+fn test_memory_copy() {
+    // The number of bytes is charged for dynamically, using an additional local
+    // to keep it.
+    test_body(
+        FunctionType::empty(),
+        vec![I32Const(0), I32Const(16), I32Const(8), MemoryCopy],
+        flatten![
+            energy!(ENTRY + 3 * CONST + MEMCOPY),
+            stack!(S),
+            [I32Const(0), I32Const(16), I32Const(8)],
+            [LocalTee(2), I64ExtendI32U, Call(FN_IDX_ACCOUNT_ENERGY), LocalGet(2)],
+            [MemoryCopy],
+            stack!(-S)
+        ],
+    )
+}
+
+#[test]
+fn test_memory_fill_adds_local() {
+    let ctx = TransformationContext::empty();
+    let f = Code {
+        locals:     mk_locals(&[I64]),
+        ty:         Rc::new(FunctionType::empty()),
+        ty_idx:     0,
+        num_locals: 1,
+        expr:       Expression {
+            instrs: vec![I32Const(0), I32Const(255), I32Const(8), MemoryFill],
+        },
+    };
+    let transformed = inject_accounting(&f, &ctx).unwrap();
+    assert_eq!(transformed.num_locals, 2);
+    assert_eq!(transformed.locals.len(), 2);
+    assert_eq!(transformed.locals[1].ty, I32);
+}
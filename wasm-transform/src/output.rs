@@ -3,7 +3,7 @@
 use crate::{
     constants::{MAGIC_HASH, VERSION},
     parse::{Byte, SectionId, Skeleton, UnparsedSection},
-    types::{BlockType, CustomSection, FunctionType, Name, ValueType},
+    types::*,
 };
 use std::{
     convert::{TryFrom, TryInto},
@@ -198,3 +198,362 @@ pub fn write_custom_section(out: &mut impl Write, cs: &CustomSection) -> OutResu
     out.write_all(cs.contents)?;
     Ok(())
 }
+
+/// Write a section with the given id, whose contents are written by `f`.
+fn write_section(
+    out: &mut impl Write,
+    id: SectionId,
+    f: impl FnOnce(&mut Vec<u8>) -> OutResult<()>,
+) -> OutResult<()> {
+    let mut contents = Vec::new();
+    f(&mut contents)?;
+    UnparsedSection {
+        section_id: id,
+        bytes:      &contents,
+    }
+    .output(out)
+}
+
+/// Write the limits of a table or a memory.
+fn write_limits(out: &mut impl Write, limits: &Limits) -> OutResult<()> {
+    match limits.max {
+        None => {
+            0x00u8.output(out)?;
+            limits.min.output(out)
+        }
+        Some(max) => {
+            0x01u8.output(out)?;
+            limits.min.output(out)?;
+            max.output(out)
+        }
+    }
+}
+
+/// Write a constant expression with the given value.
+fn write_constant_expr(out: &mut impl Write, value: GlobalInit) -> OutResult<()> {
+    match value {
+        GlobalInit::I32(x) => {
+            0x41u8.output(out)?;
+            x.output(out)?;
+        }
+        GlobalInit::I64(x) => {
+            0x42u8.output(out)?;
+            x.output(out)?;
+        }
+    }
+    0x0Bu8.output(out)
+}
+
+fn write_memarg(out: &mut impl Write, opcode: Byte, memarg: &MemArg) -> OutResult<()> {
+    opcode.output(out)?;
+    memarg.align.output(out)?;
+    memarg.offset.output(out)
+}
+
+/// Write the instruction in the binary format it is parsed from, see
+/// [decode_opcode](crate::parse::decode_opcode).
+pub fn write_opcode(out: &mut impl Write, opcode: &OpCode) -> OutResult<()> {
+    use OpCode::*;
+    let byte: Byte = match opcode {
+        End => 0x0B,
+        Nop => 0x01,
+        Unreachable => 0x00,
+        Block(ty) => {
+            0x02u8.output(out)?;
+            return ty.output(out);
+        }
+        Loop(ty) => {
+            0x03u8.output(out)?;
+            return ty.output(out);
+        }
+        If {
+            ty,
+        } => {
+            0x04u8.output(out)?;
+            return ty.output(out);
+        }
+        Else => 0x05,
+        Br(l) => {
+            0x0Cu8.output(out)?;
+            return l.output(out);
+        }
+        BrIf(l) => {
+            0x0Du8.output(out)?;
+            return l.output(out);
+        }
+        BrTable {
+            labels,
+            default,
+        } => {
+            0x0Eu8.output(out)?;
+            labels.output(out)?;
+            return default.output(out);
+        }
+        Return => 0x0F,
+        Call(idx) => {
+            0x10u8.output(out)?;
+            return idx.output(out);
+        }
+        CallIndirect(ty) => {
+            0x11u8.output(out)?;
+            ty.output(out)?;
+            return 0x00u8.output(out);
+        }
+        Drop => 0x1A,
+        Select => 0x1B,
+        LocalGet(idx) => {
+            0x20u8.output(out)?;
+            return idx.output(out);
+        }
+        LocalSet(idx) => {
+            0x21u8.output(out)?;
+            return idx.output(out);
+        }
+        LocalTee(idx) => {
+            0x22u8.output(out)?;
+            return idx.output(out);
+        }
+        GlobalGet(idx) => {
+            0x23u8.output(out)?;
+            return idx.output(out);
+        }
+        GlobalSet(idx) => {
+            0x24u8.output(out)?;
+            return idx.output(out);
+        }
+        I32Load(memarg) => return write_memarg(out, 0x28, memarg),
+        I64Load(memarg) => return write_memarg(out, 0x29, memarg),
+        I32Load8S(memarg) => return write_memarg(out, 0x2C, memarg),
+        I32Load8U(memarg) => return write_memarg(out, 0x2D, memarg),
+        I32Load16S(memarg) => return write_memarg(out, 0x2E, memarg),
+        I32Load16U(memarg) => return write_memarg(out, 0x2F, memarg),
+        I64Load8S(memarg) => return write_memarg(out, 0x30, memarg),
+        I64Load8U(memarg) => return write_memarg(out, 0x31, memarg),
+        I64Load16S(memarg) => return write_memarg(out, 0x32, memarg),
+        I64Load16U(memarg) => return write_memarg(out, 0x33, memarg),
+        I64Load32S(memarg) => return write_memarg(out, 0x34, memarg),
+        I64Load32U(memarg) => return write_memarg(out, 0x35, memarg),
+        I32Store(memarg) => return write_memarg(out, 0x36, memarg),
+        I64Store(memarg) => return write_memarg(out, 0x37, memarg),
+        I32Store8(memarg) => return write_memarg(out, 0x3A, memarg),
+        I32Store16(memarg) => return write_memarg(out, 0x3B, memarg),
+        I64Store8(memarg) => return write_memarg(out, 0x3C, memarg),
+        I64Store16(memarg) => return write_memarg(out, 0x3D, memarg),
+        I64Store32(memarg) => return write_memarg(out, 0x3E, memarg),
+        MemorySize => {
+            0x3Fu8.output(out)?;
+            return 0x00u8.output(out);
+        }
+        MemoryGrow => {
+            0x40u8.output(out)?;
+            return 0x00u8.output(out);
+        }
+        MemoryCopy => {
+            0xFCu8.output(out)?;
+            10u32.output(out)?;
+            0x00u8.output(out)?;
+            return 0x00u8.output(out);
+        }
+        MemoryFill => {
+            0xFCu8.output(out)?;
+            11u32.output(out)?;
+            return 0x00u8.output(out);
+        }
+        I32Const(n) => {
+            0x41u8.output(out)?;
+            return n.output(out);
+        }
+        I64Const(n) => {
+            0x42u8.output(out)?;
+            return n.output(out);
+        }
+        I32Eqz => 0x45,
+        I32Eq => 0x46,
+        I32Ne => 0x47,
+        I32LtS => 0x48,
+        I32LtU => 0x49,
+        I32GtS => 0x4A,
+        I32GtU => 0x4B,
+        I32LeS => 0x4C,
+        I32LeU => 0x4D,
+        I32GeS => 0x4E,
+        I32GeU => 0x4F,
+        I64Eqz => 0x50,
+        I64Eq => 0x51,
+        I64Ne => 0x52,
+        I64LtS => 0x53,
+        I64LtU => 0x54,
+        I64GtS => 0x55,
+        I64GtU => 0x56,
+        I64LeS => 0x57,
+        I64LeU => 0x58,
+        I64GeS => 0x59,
+        I64GeU => 0x5A,
+        I32Clz => 0x67,
+        I32Ctz => 0x68,
+        I32Popcnt => 0x69,
+        I32Add => 0x6A,
+        I32Sub => 0x6B,
+        I32Mul => 0x6C,
+        I32DivS => 0x6D,
+        I32DivU => 0x6E,
+        I32RemS => 0x6F,
+        I32RemU => 0x70,
+        I32And => 0x71,
+        I32Or => 0x72,
+        I32Xor => 0x73,
+        I32Shl => 0x74,
+        I32ShrS => 0x75,
+        I32ShrU => 0x76,
+        I32Rotl => 0x77,
+        I32Rotr => 0x78,
+        I64Clz => 0x79,
+        I64Ctz => 0x7A,
+        I64Popcnt => 0x7B,
+        I64Add => 0x7C,
+        I64Sub => 0x7D,
+        I64Mul => 0x7E,
+        I64DivS => 0x7F,
+        I64DivU => 0x80,
+        I64RemS => 0x81,
+        I64RemU => 0x82,
+        I64And => 0x83,
+        I64Or => 0x84,
+        I64Xor => 0x85,
+        I64Shl => 0x86,
+        I64ShrS => 0x87,
+        I64ShrU => 0x88,
+        I64Rotl => 0x89,
+        I64Rotr => 0x8A,
+        I32WrapI64 => 0xA7,
+        I64ExtendI32S => 0xAC,
+        I64ExtendI32U => 0xAD,
+        I32Extend8S => 0xC0,
+        I32Extend16S => 0xC1,
+        I64Extend8S => 0xC2,
+        I64Extend16S => 0xC3,
+        I64Extend32S => 0xC4,
+    };
+    byte.output(out)
+}
+
+/// Write out a validated, and possibly transformed, module in the binary
+/// format. Custom sections are not part of a [Module], so they are not
+/// written. This makes it possible to run a module that was, e.g., metered or
+/// linked, by a different Wasm implementation.
+impl Output for Module {
+    fn output(&self, out: &mut impl Write) -> OutResult<()> {
+        out.write_all(&MAGIC_HASH)?;
+        out.write_all(&VERSION)?;
+        write_section(out, SectionId::Type, |out| {
+            u32::try_from(self.ty.types.len())?.output(out)?;
+            for ty in self.ty.types.iter() {
+                ty.output(out)?;
+            }
+            Ok(())
+        })?;
+        write_section(out, SectionId::Import, |out| {
+            u32::try_from(self.import.imports.len())?.output(out)?;
+            for import in self.import.imports.iter() {
+                import.mod_name.output(out)?;
+                import.item_name.output(out)?;
+                match import.description {
+                    ImportDescription::Func {
+                        type_idx,
+                    } => {
+                        0x00u8.output(out)?;
+                        type_idx.output(out)?;
+                    }
+                }
+            }
+            Ok(())
+        })?;
+        write_section(out, SectionId::Function, |out| self.func.types.output(out))?;
+        if let Some(table_type) = self.table.table_type.as_ref() {
+            write_section(out, SectionId::Table, |out| {
+                1u32.output(out)?;
+                0x70u8.output(out)?;
+                write_limits(out, &table_type.limits)
+            })?;
+        }
+        if let Some(memory_type) = self.memory.memory_type.as_ref() {
+            write_section(out, SectionId::Memory, |out| {
+                1u32.output(out)?;
+                write_limits(out, &memory_type.limits)
+            })?;
+        }
+        write_section(out, SectionId::Global, |out| {
+            u32::try_from(self.global.globals.len())?.output(out)?;
+            for global in self.global.globals.iter() {
+                global.init.ty().output(out)?;
+                u8::from(global.mutable).output(out)?;
+                write_constant_expr(out, global.init)?;
+            }
+            Ok(())
+        })?;
+        write_section(out, SectionId::Export, |out| {
+            u32::try_from(self.export.exports.len())?.output(out)?;
+            for export in self.export.exports.iter() {
+                export.name.output(out)?;
+                match export.description {
+                    ExportDescription::Func {
+                        index,
+                    } => {
+                        0x00u8.output(out)?;
+                        index.output(out)?;
+                    }
+                    ExportDescription::Table => {
+                        0x01u8.output(out)?;
+                        0u32.output(out)?;
+                    }
+                    ExportDescription::Memory => {
+                        0x02u8.output(out)?;
+                        0u32.output(out)?;
+                    }
+                    ExportDescription::Global {
+                        index,
+                    } => {
+                        0x03u8.output(out)?;
+                        index.output(out)?;
+                    }
+                }
+            }
+            Ok(())
+        })?;
+        write_section(out, SectionId::Element, |out| {
+            u32::try_from(self.element.elements.len())?.output(out)?;
+            for element in self.element.elements.iter() {
+                0u32.output(out)?;
+                write_constant_expr(out, GlobalInit::I32(element.offset))?;
+                element.inits.output(out)?;
+            }
+            Ok(())
+        })?;
+        write_section(out, SectionId::Code, |out| {
+            u32::try_from(self.code.impls.len())?.output(out)?;
+            let mut body = Vec::new();
+            for code in self.code.impls.iter() {
+                body.clear();
+                u32::try_from(code.locals.len())?.output(&mut body)?;
+                for local in code.locals.iter() {
+                    local.multiplicity.output(&mut body)?;
+                    local.ty.output(&mut body)?;
+                }
+                for instr in code.expr.instrs.iter() {
+                    write_opcode(&mut body, instr)?;
+                }
+                body.as_slice().output(out)?;
+            }
+            Ok(())
+        })?;
+        write_section(out, SectionId::Data, |out| {
+            u32::try_from(self.data.sections.len())?.output(out)?;
+            for data in self.data.sections.iter() {
+                0u32.output(out)?;
+                write_constant_expr(out, GlobalInit::I32(data.offset))?;
+                data.init.output(out)?;
+            }
+            Ok(())
+        })
+    }
+}
@@ -131,17 +131,41 @@ impl<'a, Ctx, A: Parseable<'a, Ctx>> GetParseable<A, Ctx> for &'a [u8] {
     fn next(self, ctx: Ctx) -> ParseResult<A> {
         let mut cursor = Cursor::new(self);
         let res = A::parse(ctx, &mut cursor)?;
-        ensure!(cursor.position() == self.len() as u64, "Not all of the contents was consumed.");
+        ensure!(cursor.position() == self.len() as u64, ParseError::LeftoverBytes);
         Ok(res)
     }
 }
 
+/// Map an error from reading a LEB128 encoded integer to a [ParseError]. The
+/// reader is limited to the maximum length of the encoding, so running out of
+/// input before the end of the cursor means that the encoding is too long.
+fn leb128_error(cursor: &Cursor<&[u8]>, e: leb128::read::Error) -> ParseError {
+    match e {
+        leb128::read::Error::IoError(_) if cursor.position() >= cursor.get_ref().len() as u64 => {
+            ParseError::UnexpectedEnd
+        }
+        _ => ParseError::IntegerOverflow,
+    }
+}
+
+/// Read an unsigned LEB128 encoded integer of at most `max_len` bytes.
+fn read_unsigned(cursor: &mut Cursor<&[u8]>, max_len: u64) -> ParseResult<u64> {
+    let res = leb128::read::unsigned(&mut cursor.take(max_len));
+    Ok(res.map_err(|e| leb128_error(cursor, e))?)
+}
+
+/// Read a signed LEB128 encoded integer of at most `max_len` bytes.
+fn read_signed(cursor: &mut Cursor<&[u8]>, max_len: u64) -> ParseResult<i64> {
+    let res = leb128::read::signed(&mut cursor.take(max_len));
+    Ok(res.map_err(|e| leb128_error(cursor, e))?)
+}
+
 /// Implementation for u16 according to the Wasm specification.
 impl<'a, Ctx> Parseable<'a, Ctx> for u16 {
     fn parse(_ctx: Ctx, cursor: &mut Cursor<&'a [u8]>) -> ParseResult<Self> {
         // 3 is ceil(16 / 7)
-        let res = leb128::read::unsigned(&mut cursor.take(3))?;
-        Ok(u16::try_from(res)?)
+        let res = read_unsigned(cursor, 3)?;
+        Ok(u16::try_from(res).map_err(|_| ParseError::IntegerOverflow)?)
     }
 }
 
@@ -149,8 +173,8 @@ impl<'a, Ctx> Parseable<'a, Ctx> for u16 {
 impl<'a, Ctx> Parseable<'a, Ctx> for u32 {
     fn parse(_ctx: Ctx, cursor: &mut Cursor<&'a [u8]>) -> ParseResult<Self> {
         // 5 is ceil(32 / 7)
-        let res = leb128::read::unsigned(&mut cursor.take(5))?;
-        Ok(u32::try_from(res)?)
+        let res = read_unsigned(cursor, 5)?;
+        Ok(u32::try_from(res).map_err(|_| ParseError::IntegerOverflow)?)
     }
 }
 
@@ -158,8 +182,7 @@ impl<'a, Ctx> Parseable<'a, Ctx> for u32 {
 impl<'a, Ctx> Parseable<'a, Ctx> for u64 {
     fn parse(_ctx: Ctx, cursor: &mut Cursor<&'a [u8]>) -> ParseResult<Self> {
         // 10 is ceil(64 / 7)
-        let res = leb128::read::unsigned(&mut cursor.take(10))?;
-        Ok(res)
+        read_unsigned(cursor, 10)
     }
 }
 
@@ -167,16 +190,15 @@ impl<'a, Ctx> Parseable<'a, Ctx> for u64 {
 impl<'a, Ctx> Parseable<'a, Ctx> for i32 {
     fn parse(_ctx: Ctx, cursor: &mut Cursor<&'a [u8]>) -> ParseResult<Self> {
         // 5 is ceil(32 / 7)
-        let res = leb128::read::signed(&mut cursor.take(5))?;
-        Ok(i32::try_from(res)?)
+        let res = read_signed(cursor, 5)?;
+        Ok(i32::try_from(res).map_err(|_| ParseError::IntegerOverflow)?)
     }
 }
 
 /// Implementation for i64 according to the Wasm specification.
 impl<'a, Ctx> Parseable<'a, Ctx> for i64 {
     fn parse(_ctx: Ctx, cursor: &mut Cursor<&'a [u8]>) -> ParseResult<Self> {
-        let res = leb128::read::signed(&mut cursor.take(10))?;
-        Ok(res)
+        read_signed(cursor, 10)
     }
 }
 
@@ -184,7 +206,7 @@ impl<'a, Ctx> Parseable<'a, Ctx> for i64 {
 impl<'a, Ctx> Parseable<'a, Ctx> for SectionId {
     fn parse(_ctx: Ctx, cursor: &mut Cursor<&'a [u8]>) -> ParseResult<Self> {
         let mut buf = [0u8; 1];
-        cursor.read_exact(&mut buf)?;
+        cursor.read_exact(&mut buf).map_err(|_| ParseError::UnexpectedEnd)?;
         use SectionId::*;
         match buf[0] {
             0 => Ok(Custom),
@@ -199,7 +221,9 @@ impl<'a, Ctx> Parseable<'a, Ctx> for SectionId {
             9 => Ok(Element),
             10 => Ok(Code),
             11 => Ok(Data),
-            id => bail!("Unknown section id {}", id),
+            id => bail!(ParseError::UnknownSection {
+                id
+            }),
         }
     }
 }
@@ -227,7 +251,7 @@ impl<'a, Ctx> Parseable<'a, Ctx> for &'a [u8] {
         let len = u32::parse(ctx, cursor)?;
         let pos = cursor.position() as usize;
         let end = pos + len as usize;
-        ensure!(end <= cursor.get_ref().len(), "Malformed byte array");
+        ensure!(end <= cursor.get_ref().len(), ParseError::UnexpectedEnd);
         cursor.seek(SeekFrom::Current(i64::from(len)))?;
         Ok(&cursor.get_ref()[pos..end])
     }
@@ -239,7 +263,10 @@ impl<'a, Ctx: Copy, A: Parseable<'a, Ctx>> Parseable<'a, Ctx> for Option<A> {
         match Byte::parse(ctx, cursor)? {
             0u8 => Ok(None),
             1u8 => Ok(Some(cursor.next(ctx)?)),
-            tag => bail!("Unsupported option tag: {:#04x}", tag),
+            tag => bail!(ParseError::UnsupportedTag {
+                kind: "option",
+                tag
+            }),
         }
     }
 }
@@ -251,7 +278,7 @@ impl<'a, Ctx> Parseable<'a, Ctx> for &'a [ValueType] {
         let len = u32::parse(ctx, cursor)?;
         let pos = cursor.position() as usize;
         let end = pos + len as usize;
-        ensure!(end <= cursor.get_ref().len(), "Malformed byte array");
+        ensure!(end <= cursor.get_ref().len(), ParseError::UnexpectedEnd);
         cursor.seek(SeekFrom::Current(i64::from(len)))?;
         let bytes = &cursor.get_ref()[pos..end];
         for &byte in bytes {
@@ -290,12 +317,12 @@ pub fn parse_skeleton(input: &[u8]) -> ParseResult<Skeleton<'_>> {
     {
         // check magic hash and version
         let mut buf = [0u8; 4];
-        cursor.read_exact(&mut buf)?;
+        cursor.read_exact(&mut buf).map_err(|_| ParseError::UnexpectedEnd)?;
         // ensure magic hash
-        ensure!(buf == MAGIC_HASH, "Unknown magic hash");
-        cursor.read_exact(&mut buf)?;
+        ensure!(buf == MAGIC_HASH, ParseError::UnknownMagicHash);
+        cursor.read_exact(&mut buf).map_err(|_| ParseError::UnexpectedEnd)?;
         // ensure module version.
-        ensure!(buf == VERSION, "Unsupported version.");
+        ensure!(buf == VERSION, ParseError::UnsupportedVersion);
     }
     let mut last_section = SectionId::Custom;
 
@@ -318,7 +345,7 @@ pub fn parse_skeleton(input: &[u8]) -> ParseResult<Skeleton<'_>> {
         let section = UnparsedSection::parse(EMPTY_CTX, cursor)?;
         ensure!(
             section.section_id == SectionId::Custom || section.section_id > last_section,
-            "Section out of place."
+            ParseError::SectionOutOfPlace
         );
         if section.section_id != SectionId::Custom {
             last_section = section.section_id
@@ -339,7 +366,7 @@ pub fn parse_skeleton(input: &[u8]) -> ParseResult<Skeleton<'_>> {
         }
     }
     // make sure we've read all the input
-    ensure!(cursor.position() as usize == input.len(), "Leftover bytes.");
+    ensure!(cursor.position() as usize == input.len(), ParseError::LeftoverBytes);
     Ok(Skeleton {
         ty,
         import,
@@ -363,7 +390,8 @@ impl<'a, Ctx> Parseable<'a, Ctx> for Name {
     fn parse(ctx: Ctx, cursor: &mut Cursor<&'a [u8]>) -> ParseResult<Self> {
         let name_bytes: &[u8] = cursor.next(ctx)?;
         ensure!(name_bytes.len() <= MAX_NAME_SIZE, ParseError::NameTooLong);
-        let name = std::str::from_utf8(name_bytes)?.to_string();
+        let name =
+            std::str::from_utf8(name_bytes).map_err(|_| ParseError::OnlyASCIINames)?.to_string();
         ensure!(name.is_ascii(), ParseError::OnlyASCIINames);
         Ok(Name {
             name,
@@ -386,7 +414,7 @@ pub fn parse_custom<'a>(sec: &UnparsedSection<'a>) -> ParseResult<CustomSection<
 impl<'a, Ctx> Parseable<'a, Ctx> for Byte {
     fn parse(_ctx: Ctx, cursor: &mut Cursor<&'a [u8]>) -> ParseResult<Self> {
         let mut buf = [0u8; 1];
-        cursor.read_exact(&mut buf)?;
+        cursor.read_exact(&mut buf).map_err(|_| ParseError::UnexpectedEnd)?;
         Ok(buf[0])
     }
 }
@@ -421,13 +449,16 @@ impl<'a, Ctx: Copy> Parseable<'a, Ctx> for Limits {
             0x01 => {
                 let min = cursor.next(ctx)?;
                 let mmax = cursor.next(ctx)?;
-                ensure!(min <= mmax, "Lower limit must be no greater than the upper limit.");
+                ensure!(min <= mmax, ParseError::InvalidLimits);
                 Ok(Limits {
                     min,
                     max: Some(mmax),
                 })
             }
-            tag => bail!("Incorrect limits tag {:#04x}.", tag),
+            tag => bail!(ParseError::UnsupportedTag {
+                kind: "limits",
+                tag
+            }),
         }
     }
 }
@@ -436,7 +467,10 @@ impl<'a, Ctx: Copy> Parseable<'a, Ctx> for Limits {
 /// match.
 fn expect_byte(cursor: &mut Cursor<&[u8]>, byte: Byte) -> ParseResult<()> {
     let b = Byte::parse(EMPTY_CTX, cursor)?;
-    ensure!(b == byte, "Unexpected byte {:#04x}. Expected {:#04x}", b, byte);
+    ensure!(b == byte, ParseError::UnexpectedByte {
+        expected: byte,
+        actual:   b,
+    });
     Ok(())
 }
 
@@ -464,7 +498,10 @@ impl<'a, Ctx: Copy> Parseable<'a, Ctx> for TableType {
     fn parse(ctx: Ctx, cursor: &mut Cursor<&'a [u8]>) -> ParseResult<Self> {
         expect_byte(cursor, 0x70)?;
         let limits = Limits::parse(ctx, cursor)?;
-        ensure!(limits.min <= MAX_INIT_TABLE_SIZE, "Initial table size exceeds allowed limits.");
+        ensure!(limits.min <= MAX_INIT_TABLE_SIZE, ParseError::TableTooLarge {
+            size: limits.min,
+            max:  MAX_INIT_TABLE_SIZE,
+        });
         Ok(TableType {
             limits,
         })
@@ -476,15 +513,13 @@ impl<'a, Ctx: Copy> Parseable<'a, Ctx> for TableType {
 impl<'a, Ctx: Copy> Parseable<'a, Ctx> for MemoryType {
     fn parse(ctx: Ctx, cursor: &mut Cursor<&'a [u8]>) -> ParseResult<Self> {
         let limits = Limits::parse(ctx, cursor)?;
-        ensure!(
-            limits.min <= MAX_INIT_MEMORY_SIZE,
-            "Initial memory allocation of {} pages exceeds maximum of {}.",
-            limits.min,
-            MAX_INIT_MEMORY_SIZE
-        );
+        ensure!(limits.min <= MAX_INIT_MEMORY_SIZE, ParseError::MemoryTooLarge {
+            pages: limits.min,
+            max:   MAX_INIT_MEMORY_SIZE,
+        });
         match limits.max {
-            Some(x) => ensure!(x <= 1 << 16, "Memory limits must be in range 2^16."),
-            None => ensure!(limits.min <= 1 << 16, "Memory limits must be in range 2^16."),
+            Some(x) => ensure!(x <= 1 << 16, ParseError::InvalidLimits),
+            None => ensure!(limits.min <= 1 << 16, ParseError::InvalidLimits),
         }
         Ok(MemoryType {
             limits,
@@ -578,33 +613,38 @@ fn read_constant_expr<'a>(
     let instr = decode_opcode(cursor)?;
     let res = match instr {
         OpCode::I32Const(n) => {
-            ensure!(ty == ValueType::I32, "Constant instruction of type I64, but I32 expected.");
+            ensure!(ty == ValueType::I32, ParseError::InvalidConstantExpression {
+                reason: "Constant instruction of type I64, but I32 expected.",
+            });
             GlobalInit::I32(n)
         }
         OpCode::I64Const(n) => {
-            ensure!(ty == ValueType::I64, "Constant instruction of type I32, but I64 expected.");
+            ensure!(ty == ValueType::I64, ParseError::InvalidConstantExpression {
+                reason: "Constant instruction of type I32, but I64 expected.",
+            });
             GlobalInit::I64(n)
         }
         OpCode::GlobalGet(idx) => match globals_allowed {
-            None => bail!("GlobalGet not allowed in this constant expression."),
+            None => bail!(ParseError::InvalidConstantExpression {
+                reason: "GlobalGet not allowed in this constant expression.",
+            }),
             Some(globals) => {
-                let global = globals.get(idx).ok_or_else(|| {
-                    anyhow::anyhow!("Reference to non-existent global in constant expression.")
+                let global = globals.get(idx).ok_or(ParseError::InvalidConstantExpression {
+                    reason: "Reference to non-existent global in constant expression.",
                 })?;
-                ensure!(
-                    global.init.ty() == ty,
-                    "Global in constant expression of incorrect type: {:?} != {:?}",
-                    global.init.ty(),
-                    ty
-                );
-                ensure!(
-                    !global.mutable,
-                    "Only references to constant globals can appear in constant expressions."
-                );
+                ensure!(global.init.ty() == ty, ParseError::InvalidConstantExpression {
+                    reason: "Global in constant expression of incorrect type.",
+                });
+                ensure!(!global.mutable, ParseError::InvalidConstantExpression {
+                    reason: "Only references to constant globals can appear in constant \
+                             expressions.",
+                });
                 global.init
             }
         },
-        _ => bail!("Not a constant instruction {:?}.", instr),
+        _ => bail!(ParseError::InvalidConstantExpression {
+            reason: "Not a constant instruction.",
+        }),
     };
     // end parsing the expression
     expect_byte(cursor, END)?;
@@ -614,7 +654,9 @@ fn read_constant_expr<'a>(
 impl<'a, Ctx: Copy> Parseable<'a, Ctx> for TableSection {
     fn parse(ctx: Ctx, cursor: &mut Cursor<&'a [u8]>) -> ParseResult<Self> {
         let table_type_vec: Vec<TableType> = cursor.next(ctx)?;
-        ensure!(table_type_vec.len() <= 1, "Only table with index 0 is supported.");
+        ensure!(table_type_vec.len() <= 1, ParseError::UnsupportedIndex {
+            kind: "table",
+        });
         Ok(TableSection {
             table_type: table_type_vec.first().copied(),
         })
@@ -624,7 +666,9 @@ impl<'a, Ctx: Copy> Parseable<'a, Ctx> for TableSection {
 impl<'a, Ctx: Copy> Parseable<'a, Ctx> for MemorySection {
     fn parse(ctx: Ctx, cursor: &mut Cursor<&'a [u8]>) -> ParseResult<Self> {
         let memory_types_vec: Vec<MemoryType> = cursor.next(ctx)?;
-        ensure!(memory_types_vec.len() <= 1, "Only memory with index 1 is supported.");
+        ensure!(memory_types_vec.len() <= 1, ParseError::UnsupportedIndex {
+            kind: "memory",
+        });
         Ok(MemorySection {
             memory_type: memory_types_vec.first().copied(),
         })
@@ -642,12 +686,16 @@ impl<'a, Ctx: Copy> Parseable<'a, Ctx> for ExportDescription {
             }
             0x01 => {
                 let index = TableIndex::parse(ctx, cursor)?;
-                ensure!(index == 0, "Only table with index 0 is supported.");
+                ensure!(index == 0, ParseError::UnsupportedIndex {
+                    kind: "table",
+                });
                 Ok(ExportDescription::Table)
             }
             0x02 => {
                 let index = MemIndex::parse(ctx, cursor)?;
-                ensure!(index == 0, "Only memory with index 0 is supported.");
+                ensure!(index == 0, ParseError::UnsupportedIndex {
+                    kind: "memory",
+                });
                 Ok(ExportDescription::Memory)
             }
             0x03 => {
@@ -656,7 +704,10 @@ impl<'a, Ctx: Copy> Parseable<'a, Ctx> for ExportDescription {
                     index,
                 })
             }
-            byte => bail!("Unsupported export tag {:#04x}.", byte),
+            tag => bail!(ParseError::UnsupportedTag {
+                kind: "export",
+                tag
+            }),
         }
     }
 }
@@ -704,7 +755,9 @@ impl<'a, Ctx> Parseable<'a, Ctx> for StartSection {
 impl<'a> Parseable<'a, &GlobalSection> for Element {
     fn parse(ctx: &GlobalSection, cursor: &mut Cursor<&'a [u8]>) -> ParseResult<Self> {
         let table_index = TableIndex::parse(ctx, cursor)?;
-        ensure!(table_index == 0, "Only table index 0 is supported.");
+        ensure!(table_index == 0, ParseError::UnsupportedIndex {
+            kind: "table",
+        });
         let offset = read_constant_expr(cursor, ValueType::I32, Some(ctx))?;
         let inits = cursor.next(ctx)?;
         if let GlobalInit::I32(offset) = offset {
@@ -733,7 +786,10 @@ impl<'a, Ctx: Copy> Parseable<'a, Ctx> for Global {
         let mutable = match Byte::parse(ctx, cursor)? {
             0x00 => false,
             0x01 => true,
-            flag => bail!("Unsupported mutability flag {:#04x}", flag),
+            tag => bail!(ParseError::UnsupportedTag {
+                kind: "mutability",
+                tag
+            }),
         };
         // Globals initialization expressions cannot refer to other (in-module) globals.
         let init = read_constant_expr(cursor, ty, None)?;
@@ -764,7 +820,10 @@ impl<'a, Ctx> Parseable<'a, Ctx> for BlockType {
             0x40 => Ok(BlockType::EmptyType),
             0x7F => Ok(BlockType::ValueType(ValueType::I32)),
             0x7E => Ok(BlockType::ValueType(ValueType::I64)),
-            x => bail!("Unsupported block type {}", x),
+            tag => bail!(ParseError::UnsupportedTag {
+                kind: "block type",
+                tag
+            }),
         }
     }
 }
@@ -794,7 +853,9 @@ impl<'a, Ctx: Copy> Parseable<'a, Ctx> for Local {
 impl<'a> Parseable<'a, &GlobalSection> for Data {
     fn parse(ctx: &GlobalSection, cursor: &mut Cursor<&'a [u8]>) -> ParseResult<Self> {
         let index = u32::parse(ctx, cursor)?;
-        ensure!(index == 0, "Only memory index 0 is supported.");
+        ensure!(index == 0, ParseError::UnsupportedIndex {
+            kind: "memory",
+        });
         let offset = read_constant_expr(cursor, ValueType::I32, Some(ctx))?;
         let init = cursor.next(ctx)?;
         if let GlobalInit::I32(offset) = offset {
@@ -827,51 +888,163 @@ pub fn parse_sec_with_default<'a, Ctx, A: Parseable<'a, Ctx> + Default>(
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
+/// Errors that can occur when parsing a module or an artifact. They are
+/// returned wrapped in an [anyhow::Error], from which they can be recovered
+/// with [`downcast_ref`](anyhow::Error::downcast_ref).
 pub enum ParseError {
+    #[error("Unsupported instruction {opcode:#04x}{}", instruction_hint(.opcode))]
     UnsupportedInstruction {
         opcode: Byte,
     },
+    #[error(
+        "Unsupported instruction {prefix:#04x} {opcode}{}",
+        prefixed_instruction_hint(.prefix, .opcode)
+    )]
+    UnsupportedPrefixedInstruction {
+        prefix: Byte,
+        opcode: u32,
+    },
+    #[error("Unknown value type byte {byte:#04x}{}", value_type_hint(.byte))]
     UnsupportedValueType {
         byte: Byte,
     },
+    #[error("Unsupported import type {tag:#04x}. Only functions can be imported.")]
     UnsupportedImportType {
         tag: Byte,
     },
+    #[error("Only single return value is supported.")]
     OnlySingleReturn,
+    #[error("Only ASCII names are allowed.")]
     OnlyASCIINames,
+    #[error("Names are limited to {} bytes.", MAX_NAME_SIZE)]
     NameTooLong,
+    #[error(
+        "Names of functions are limited to {} bytes.",
+        concordium_contracts_common::constants::MAX_FUNC_NAME_SIZE
+    )]
     FuncNameTooLong,
+    #[error("Start functions are not supported.")]
     StartFunctionsNotSupported,
+    #[error("The input ended unexpectedly.")]
+    UnexpectedEnd,
+    #[error("An integer is out of range of its type, or its encoding is too long.")]
+    IntegerOverflow,
+    #[error("Unknown magic hash.")]
+    UnknownMagicHash,
+    #[error("Unsupported version.")]
+    UnsupportedVersion,
+    #[error("Unknown section id {id}.")]
+    UnknownSection {
+        id: Byte,
+    },
+    #[error("Section out of place.")]
+    SectionOutOfPlace,
+    #[error("Not all of the input was consumed.")]
+    LeftoverBytes,
+    #[error("Unexpected byte {actual:#04x}. Expected {expected:#04x}.")]
+    UnexpectedByte {
+        expected: Byte,
+        actual:   Byte,
+    },
+    #[error("Unsupported {kind} tag {tag:#04x}.")]
+    UnsupportedTag {
+        /// What the tag is for.
+        kind: &'static str,
+        tag:  Byte,
+    },
+    /// Only a single table and a single memory, with index 0, are supported.
+    #[error("Only {kind} with index 0 is supported.")]
+    UnsupportedIndex {
+        kind: &'static str,
+    },
+    #[error("Limits are out of range, or the lower limit is greater than the upper limit.")]
+    InvalidLimits,
+    #[error("Initial table size {size} exceeds the maximum of {max}.")]
+    TableTooLarge {
+        size: u32,
+        max:  u32,
+    },
+    #[error("Initial memory allocation of {pages} pages exceeds maximum of {max}.")]
+    MemoryTooLarge {
+        pages: u32,
+        max:   u32,
+    },
+    #[error("Invalid constant expression. {reason}")]
+    InvalidConstantExpression {
+        reason: &'static str,
+    },
+    #[error("The size of a function body is smaller than its declaration of locals.")]
+    IncorrectFunctionSize,
+    #[error(
+        "Unsupported artifact format version {version}. The latest supported version is \
+         {supported}."
+    )]
+    UnsupportedArtifactFormat {
+        version:   u32,
+        supported: u32,
+    },
+    #[error(
+        "Artifact built for newer host interface version {version}. The host supports versions up \
+         to {supported}."
+    )]
+    NewerHostInterface {
+        version:   u32,
+        supported: u32,
+    },
 }
 
-impl std::fmt::Display for ParseError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ParseError::UnsupportedInstruction {
-                opcode,
-            } => write!(f, "Unsupported instruction {:#04x}", opcode),
-            ParseError::UnsupportedValueType {
-                byte,
-            } => write!(f, "Unknown value type byte {:#04x}", byte),
-            ParseError::UnsupportedImportType {
-                tag,
-            } => write!(f, "Unsupported import type {:#04x}. Only functions can be imported.", tag),
-            ParseError::OnlySingleReturn => write!(f, "Only single return value is supported."),
-            ParseError::OnlyASCIINames => write!(f, "Only ASCII names are allowed."),
-            ParseError::NameTooLong => write!(f, "Names are limited to {} bytes.", MAX_NAME_SIZE),
-            ParseError::FuncNameTooLong => write!(
-                f,
-                "Names of functions are limited to {} bytes.",
-                concordium_contracts_common::constants::MAX_FUNC_NAME_SIZE
-            ),
-            ParseError::StartFunctionsNotSupported => {
-                write!(f, "Start functions are not supported.")
-            }
-        }
+fn instruction_hint(opcode: &Byte) -> &'static str {
+    if is_floating_point_opcode(*opcode) {
+        FLOATING_POINT_HINT
+    } else {
+        ""
     }
 }
 
+/// The instructions `0xFC 0` to `0xFC 7` are the saturating truncations of
+/// floating point numbers.
+fn prefixed_instruction_hint(prefix: &Byte, opcode: &u32) -> &'static str {
+    if *prefix == 0xFC && *opcode <= 7 {
+        FLOATING_POINT_HINT
+    } else {
+        ""
+    }
+}
+
+fn value_type_hint(byte: &Byte) -> &'static str {
+    if *byte == 0x7D || *byte == 0x7C {
+        FLOATING_POINT_HINT
+    } else {
+        ""
+    }
+}
+
+/// Suggestion appended to errors caused by the use of floating point types or
+/// instructions, which are not supported.
+const FLOATING_POINT_HINT: &str = ". Floating point numbers are not supported by smart contracts. \
+                                   Consider using integer arithmetic instead, e.g., with values \
+                                   scaled by a fixed power of ten.";
+
+/// Whether the given byte is the opcode of an instruction that operates on
+/// floating point numbers. None of these are supported, but we detect them so
+/// that we can give a more informative error message.
+pub fn is_floating_point_opcode(opcode: Byte) -> bool {
+    matches!(
+        opcode,
+        0x2A // f32.load
+        | 0x2B // f64.load
+        | 0x38 // f32.store
+        | 0x39 // f64.store
+        | 0x43 // f32.const
+        | 0x44 // f64.const
+        | 0x5B..=0x66 // comparisons
+        | 0x8B..=0xA6 // arithmetic
+        | 0xA8..=0xAB // i32.trunc_*
+        | 0xAE..=0xBF // i64.trunc_*, conversions, and reinterpretations
+    )
+}
+
 /// Decode the next opcode directly from the cursor.
 pub fn decode_opcode(cursor: &mut Cursor<&[u8]>) -> ParseResult<OpCode> {
     match Byte::parse(EMPTY_CTX, cursor)? {
@@ -1104,6 +1277,31 @@ pub fn decode_opcode(cursor: &mut Cursor<&[u8]>) -> ParseResult<OpCode> {
 
         0xAC => Ok(OpCode::I64ExtendI32S),
         0xAD => Ok(OpCode::I64ExtendI32U),
+
+        0xC0 => Ok(OpCode::I32Extend8S),
+        0xC1 => Ok(OpCode::I32Extend16S),
+        0xC2 => Ok(OpCode::I64Extend8S),
+        0xC3 => Ok(OpCode::I64Extend16S),
+        0xC4 => Ok(OpCode::I64Extend32S),
+
+        0xFC => {
+            let opcode: u32 = cursor.next(EMPTY_CTX)?;
+            match opcode {
+                10 => {
+                    expect_byte(cursor, 0x00)?;
+                    expect_byte(cursor, 0x00)?;
+                    Ok(OpCode::MemoryCopy)
+                }
+                11 => {
+                    expect_byte(cursor, 0x00)?;
+                    Ok(OpCode::MemoryFill)
+                }
+                opcode => bail!(ParseError::UnsupportedPrefixedInstruction {
+                    prefix: 0xFC,
+                    opcode,
+                }),
+            }
+        }
         byte => bail!(ParseError::UnsupportedInstruction {
             opcode: byte,
         }),
@@ -1155,14 +1353,11 @@ impl<'a, Ctx: Copy> Parseable<'a, Ctx> for CodeSkeleton<'a> {
         let cur_pos = cursor.position();
         let locals = cursor.next(ctx)?;
         let end_pos = cursor.position();
-        ensure!(
-            u64::from(size) >= end_pos - cur_pos,
-            "We've already read too many bytes, module is malformed."
-        );
+        ensure!(u64::from(size) >= end_pos - cur_pos, ParseError::IncorrectFunctionSize);
         let remaining = u64::from(size) - (end_pos - cur_pos);
         ensure!(
             ((end_pos + remaining) as usize) <= cursor.get_ref().len(),
-            "We would need to read beyond the end of the input."
+            ParseError::UnexpectedEnd
         );
         let expr_bytes = &cursor.get_ref()[end_pos as usize..(end_pos + remaining) as usize];
         cursor.set_position(end_pos + remaining);
@@ -0,0 +1,173 @@
+//! Static analysis of the resources used by the functions of a module, i.e.,
+//! the number of their locals and the maximum height of their operand stack.
+//!
+//! [validate_module](crate::validate::validate_module) rejects functions that
+//! exceed the fixed limits of [ALLOWED_LOCALS] and
+//! [MAX_ALLOWED_STACK_HEIGHT]. This module computes the same quantities for
+//! each function of an already validated module, so that they can be reported,
+//! or checked against stricter [ResourceLimits] before the module is executed.
+//! Since the bounds hold for every execution of a function, an embedder that
+//! checks them does not need to track the stack height of a function at
+//! runtime.
+use crate::{
+    constants::{ALLOWED_LOCALS, MAX_ALLOWED_STACK_HEIGHT},
+    types::*,
+    validate::{make_locals, validate, FunctionContext, Handler, ValidationState},
+};
+use anyhow::ensure;
+
+/// Static bounds on the resources used by a single function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FunctionResources {
+    /// Index of the function in the function index space of the module, i.e.,
+    /// counting imported functions.
+    pub index:             FuncIndex,
+    /// Number of locals of the function, including parameters.
+    pub num_locals:        u32,
+    /// Maximum height of the operand stack of the function. This does not
+    /// include the stacks of functions it calls.
+    pub max_operand_stack: usize,
+}
+
+impl FunctionResources {
+    /// The maximum height of the stack of the function, i.e., its locals and
+    /// its operand stack.
+    pub fn max_stack_height(&self) -> usize { self.num_locals as usize + self.max_operand_stack }
+}
+
+/// Limits on the resources of each function of a module that are checked by
+/// [check_module].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// Maximum number of locals, including parameters.
+    pub max_locals:       u32,
+    /// Maximum height of the stack, i.e., locals and operand stack together.
+    pub max_stack_height: usize,
+}
+
+impl ResourceLimits {
+    /// The limits enforced by
+    /// [validate_module](crate::validate::validate_module). Every validated
+    /// module satisfies them.
+    pub const VALIDATION: Self = Self {
+        max_locals:       ALLOWED_LOCALS,
+        max_stack_height: MAX_ALLOWED_STACK_HEIGHT,
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+/// A function that exceeds the [ResourceLimits]. It is returned by
+/// [check_module] wrapped in an [anyhow::Error].
+pub enum ResourceLimitError {
+    #[error("Function {index} has {actual} locals, which is more than allowed ({max}).")]
+    TooManyLocals {
+        index:  FuncIndex,
+        actual: u32,
+        max:    u32,
+    },
+    #[error("Function {index} has stack height {actual}, which is more than allowed ({max}).")]
+    StackHeightExceeded {
+        index:  FuncIndex,
+        actual: usize,
+        max:    usize,
+    },
+}
+
+/// Handler that only records the maximum reachable stack height.
+struct MaxHeight;
+
+impl<'a> Handler<&'a OpCode> for MaxHeight {
+    type Outcome = usize;
+
+    fn handle_opcode(
+        &mut self,
+        _state: &ValidationState,
+        _stack_height: usize,
+        _opcode: &'a OpCode,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn finish(self, state: &ValidationState) -> anyhow::Result<Self::Outcome> {
+        Ok(state.max_reachable_height)
+    }
+}
+
+/// The types of all the functions of the module, imported ones first.
+pub(crate) fn function_types(module: &Module) -> Vec<TypeIndex> {
+    module
+        .import
+        .imports
+        .iter()
+        .map(|i| match i.description {
+            ImportDescription::Func {
+                type_idx,
+            } => type_idx,
+        })
+        .chain(module.func.types.iter().copied())
+        .collect()
+}
+
+/// The maximum height of the operand stack of the given function of the
+/// module. The `funcs` are the [function_types] of the module.
+pub(crate) fn max_operand_stack(
+    module: &Module,
+    funcs: &[TypeIndex],
+    code: &Code,
+) -> anyhow::Result<usize> {
+    let (locals, _) = make_locals(&code.ty, &code.locals)?;
+    let ctx = FunctionContext {
+        return_type: BlockType::from(code.ty.result),
+        globals: &module.global.globals,
+        funcs,
+        types: &module.ty.types,
+        locals,
+        memory: module.memory.memory_type.is_some(),
+        table: module.table.table_type.is_some(),
+    };
+    validate(&ctx, code.expr.instrs.iter().map(Ok), MaxHeight)
+}
+
+/// Compute the [FunctionResources] of each function defined in the module, in
+/// the order of the code section. The module must be valid, and may have been
+/// transformed, e.g., by [Module::inject_metering], in which case the
+/// resources of the transformed functions are computed.
+pub fn analyze_module(module: &Module) -> anyhow::Result<Vec<FunctionResources>> {
+    let funcs = function_types(module);
+    let num_imports = module.import.imports.len();
+    let mut out = Vec::with_capacity(module.code.impls.len());
+    for (i, code) in module.code.impls.iter().enumerate() {
+        out.push(FunctionResources {
+            index:             (num_imports + i) as FuncIndex,
+            num_locals:        code.num_locals,
+            max_operand_stack: max_operand_stack(module, &funcs, code)?,
+        });
+    }
+    Ok(out)
+}
+
+/// Compute the [FunctionResources] of each function defined in the module, as
+/// [analyze_module], and check that they are within the given limits. The
+/// first function that exceeds them is reported as a [ResourceLimitError].
+pub fn check_module(
+    module: &Module,
+    limits: &ResourceLimits,
+) -> anyhow::Result<Vec<FunctionResources>> {
+    let resources = analyze_module(module)?;
+    for r in resources.iter() {
+        ensure!(r.num_locals <= limits.max_locals, ResourceLimitError::TooManyLocals {
+            index:  r.index,
+            actual: r.num_locals,
+            max:    limits.max_locals,
+        });
+        ensure!(
+            r.max_stack_height() <= limits.max_stack_height,
+            ResourceLimitError::StackHeightExceeded {
+                index:  r.index,
+                actual: r.max_stack_height(),
+                max:    limits.max_stack_height,
+            }
+        );
+    }
+    Ok(resources)
+}
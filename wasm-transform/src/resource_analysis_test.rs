@@ -0,0 +1,103 @@
+//! Tests of the [resource_analysis](crate::resource_analysis) of the modules
+//! in the corpus of the metering compatibility tests.
+use crate::{
+    energy_report::energy_report,
+    metering_compatibility_test::{AllowAll, CORPUS},
+    parse::parse_skeleton,
+    resource_analysis::*,
+    types::*,
+    validate::{validate_module, ValidationConfig},
+};
+use anyhow::{anyhow, ensure};
+
+fn load(path: &str) -> anyhow::Result<Module> {
+    let bytes = std::fs::read(path)?;
+    validate_module(&ValidationConfig::LEGACY, &AllowAll, &parse_skeleton(&bytes)?)
+}
+
+#[test]
+/// Every validated module satisfies the limits of validation, and the stack
+/// heights agree with the energy report of the metered module.
+fn resource_analysis_corpus() -> anyhow::Result<()> {
+    for path in CORPUS {
+        let mut module = load(path)?;
+        let resources = check_module(&module, &ResourceLimits::VALIDATION)?;
+        ensure!(resources.len() == module.code.impls.len(), "{}: missing functions", path);
+        module.inject_metering()?;
+        let metered = analyze_module(&module)?;
+        for report in energy_report(&module)? {
+            let index = module
+                .export
+                .exports
+                .iter()
+                .find_map(|e| match e.description {
+                    ExportDescription::Func {
+                        index,
+                    } if e.name == report.name => Some(index),
+                    _ => None,
+                })
+                .ok_or_else(|| anyhow!("{}: unknown export {}", path, report.name))?;
+            let r = metered
+                .iter()
+                .find(|r| r.index == index)
+                .ok_or_else(|| anyhow!("{}: function {} not analyzed", path, index))?;
+            ensure!(
+                r.max_stack_height() == report.max_stack_height,
+                "{}: stack height of {} differs from the energy report",
+                path,
+                report.name
+            );
+        }
+    }
+    Ok(())
+}
+
+#[test]
+/// Lowering a limit below the maximum of a module rejects the first function
+/// that attains the maximum.
+fn resource_analysis_limits() -> anyhow::Result<()> {
+    let module = load(CORPUS[0])?;
+    let resources = analyze_module(&module)?;
+    let max_height = resources.iter().map(FunctionResources::max_stack_height).max().unwrap_or(0);
+    let max_locals = resources.iter().map(|r| r.num_locals).max().unwrap_or(0);
+    ensure!(max_height > 0 && max_locals > 0, "The module should use the stack and locals.");
+    check_module(&module, &ResourceLimits {
+        max_locals,
+        max_stack_height: max_height,
+    })?;
+
+    let err = check_module(&module, &ResourceLimits {
+        max_locals,
+        max_stack_height: max_height - 1,
+    })
+    .expect_err("The stack height limit should be exceeded.");
+    let first = resources.iter().find(|r| r.max_stack_height() == max_height).unwrap();
+    ensure!(
+        err.downcast_ref::<ResourceLimitError>()
+            == Some(&ResourceLimitError::StackHeightExceeded {
+                index:  first.index,
+                actual: max_height,
+                max:    max_height - 1,
+            }),
+        "Unexpected error {}",
+        err
+    );
+
+    let err = check_module(&module, &ResourceLimits {
+        max_locals:       max_locals - 1,
+        max_stack_height: max_height,
+    })
+    .expect_err("The locals limit should be exceeded.");
+    let first = resources.iter().find(|r| r.num_locals == max_locals).unwrap();
+    ensure!(
+        err.downcast_ref::<ResourceLimitError>()
+            == Some(&ResourceLimitError::TooManyLocals {
+                index:  first.index,
+                actual: max_locals,
+                max:    max_locals - 1,
+            }),
+        "Unexpected error {}",
+        err
+    );
+    Ok(())
+}
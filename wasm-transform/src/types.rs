@@ -491,6 +491,10 @@ pub enum OpCode {
     I64Store32(MemArg),
     MemorySize,
     MemoryGrow,
+    /// `memory.copy` of the bulk memory proposal.
+    MemoryCopy,
+    /// `memory.fill` of the bulk memory proposal.
+    MemoryFill,
 
     // Numeric instructions
     I32Const(i32),
@@ -559,4 +563,11 @@ pub enum OpCode {
     I32WrapI64,
     I64ExtendI32S,
     I64ExtendI32U,
+
+    // Instructions of the sign extension proposal.
+    I32Extend8S,
+    I32Extend16S,
+    I64Extend8S,
+    I64Extend16S,
+    I64Extend32S,
 }
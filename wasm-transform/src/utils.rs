@@ -2,40 +2,89 @@
 //! basic functionality exposed by other modules.
 
 use crate::{
-    artifact::{Artifact, CompiledFunction, CompiledFunctionBytes, TryFromImport},
+    artifact::{Artifact, CompiledFunction, CompiledFunctionBytes, HostInterface, TryFromImport},
+    linking::{link_libraries, AllowLibraryImports},
+    metering_transformation::MeteringConfig,
     parse::{parse_skeleton, GetParseable, Parseable, Skeleton},
-    validate::{validate_module, ValidateImportExport},
+    types::Module,
+    validate::{validate_module, ValidateImportExport, ValidationConfig},
 };
 
 /// Strip the custom sections from the module.
 pub fn strip(skeleton: &mut Skeleton<'_>) { skeleton.custom = Vec::new(); }
 
-/// Parse, validate, and compile to a runnable artifact.
+/// Parse, validate, and compile to a runnable artifact. The module is validated
+/// under the [ValidationConfig::LEGACY] rules.
 pub fn instantiate<I: TryFromImport, VI: ValidateImportExport>(
     imp: &VI,
     bytes: &[u8],
 ) -> anyhow::Result<Artifact<I, CompiledFunction>> {
-    validate_module(imp, &parse_skeleton(bytes)?)?.compile()
+    validate_module(&ValidationConfig::LEGACY, imp, &parse_skeleton(bytes)?)?.compile()
 }
 
-/// Parse, validate, inject metering, and compile to a runnable artifact.
+/// Parse, validate, inject metering, and compile to a runnable artifact. The
+/// module is validated under the [ValidationConfig::LEGACY] rules.
 pub fn instantiate_with_metering<I: TryFromImport, VI: ValidateImportExport>(
     imp: &VI,
     bytes: &[u8],
 ) -> anyhow::Result<Artifact<I, CompiledFunction>> {
-    let mut module = validate_module(imp, &parse_skeleton(bytes)?)?;
+    instantiate_with_metering_config(imp, &MeteringConfig::LEGACY, bytes)
+}
+
+/// Like [instantiate_with_metering], but metering is injected as determined by
+/// the given configuration.
+pub fn instantiate_with_metering_config<I: TryFromImport, VI: ValidateImportExport>(
+    imp: &VI,
+    config: &MeteringConfig,
+    bytes: &[u8],
+) -> anyhow::Result<Artifact<I, CompiledFunction>> {
+    let mut module = validate_module(&ValidationConfig::LEGACY, imp, &parse_skeleton(bytes)?)?;
+    module.inject_metering_with_config(config)?;
+    module.compile()
+}
+
+/// Like [instantiate_with_metering], but the module may import functions from
+/// libraries, which are looked up with `resolve` and linked into the module
+/// before it is metered. See the [linking](crate::linking) module.
+pub fn instantiate_linked_with_metering<'a, I: TryFromImport, VI: ValidateImportExport>(
+    imp: &VI,
+    bytes: &[u8],
+    resolve: impl Fn(&str) -> Option<&'a Module>,
+) -> anyhow::Result<Artifact<I, CompiledFunction>> {
+    let mut module = validate_module(
+        &ValidationConfig::LEGACY,
+        &AllowLibraryImports(imp),
+        &parse_skeleton(bytes)?,
+    )?;
+    link_libraries(&mut module, resolve)?;
     module.inject_metering()?;
     module.compile()
 }
 
+/// Like [instantiate_with_metering], but the metered module is compiled to
+/// native code by wasmtime instead. See the [executor](crate::executor) module.
+#[cfg(feature = "jit")]
+pub fn instantiate_jit_with_metering<I: TryFromImport, VI: ValidateImportExport>(
+    imp: &VI,
+    bytes: &[u8],
+) -> anyhow::Result<crate::executor::jit::JitModule<I>> {
+    let mut module = validate_module(&ValidationConfig::LEGACY, imp, &parse_skeleton(bytes)?)?;
+    module.inject_metering()?;
+    crate::executor::jit::JitModule::new(module)
+}
+
 #[cfg_attr(not(feature = "fuzz-coverage"), inline)]
 /// Parse an artifact from an array of bytes. This does as much zero-copy
 /// deserialization as possible. In particular the function bodies are not
 /// deserialized and are simply retained as references into the original array.
 ///
+/// Artifacts without an [ArtifactHeader](crate::artifact::ArtifactHeader) are
+/// parsed as artifacts of format version 0. Artifacts of a newer format, or
+/// that were built for a newer version of the host interface `I`, are refused.
+///
 /// This function is designed to only be used on trusted sources and is not
 /// guaranteed to not use excessive resources if used on untrusted ones.
-pub fn parse_artifact<'a, I: Parseable<'a, ()>>(
+pub fn parse_artifact<'a, I: Parseable<'a, ()> + HostInterface>(
     bytes: &'a [u8],
 ) -> anyhow::Result<Artifact<I, CompiledFunctionBytes<'a>>> {
     (&mut std::io::Cursor::new(bytes)).next(())
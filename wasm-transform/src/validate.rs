@@ -20,22 +20,150 @@ use crate::{
 use anyhow::{anyhow, bail, ensure};
 use std::{borrow::Borrow, collections::BTreeSet, convert::TryInto, rc::Rc};
 
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
+/// Errors that can occur when validating a module. Like [ParseError] they are
+/// returned wrapped in an [anyhow::Error], from which they can be recovered
+/// with [`downcast_ref`](anyhow::Error::downcast_ref). Since sections are
+/// parsed during validation, validation can also fail with a [ParseError].
+///
+/// [ParseError]: crate::parse::ParseError
 pub enum ValidationError {
+    #[error("The number of locals ({actual}) is more than allowed ({max}).")]
     TooManyLocals {
         actual: u32,
         max:    u32,
     },
+    #[error("Actual type different from expected {actual:?} /= {expected:?}.")]
+    TypeMismatch {
+        expected: ValueType,
+        actual:   ValueType,
+    },
+    #[error("Operand stack exhausted for the current block.")]
+    OperandStackExhausted,
+    #[error("Operand stack not exhausted at the end of the block.")]
+    OperandStackNotExhausted,
+    #[error("Control stack exhausted.")]
+    ControlStackExhausted,
+    #[error("Improperly terminated instruction sequence.")]
+    ImproperlyTerminated,
+    #[error("If without an else must have empty return type.")]
+    IfWithoutElseHasResult,
+    #[error("Else can only come after an if.")]
+    ElseWithoutIf,
+    #[error("Jump to a non-existent label {label}.")]
+    UnknownLabel {
+        label: LabelIndex,
+    },
+    #[error("Different targets have different label types.")]
+    InconsistentLabelTypes,
+    #[error("Index {index} is out of range for {kind}.")]
+    IndexOutOfRange {
+        /// The kind of items the index refers to, e.g., `"locals"`.
+        kind:  &'static str,
+        index: u32,
+    },
+    #[error("Memory should exist.")]
+    MissingMemory,
+    #[error("Table with index 0 must exist.")]
+    MissingTable,
+    #[error("Alignment {align} exceeds the maximum of {max} for the type.")]
+    InvalidAlignment {
+        align: u32,
+        max:   u32,
+    },
+    #[error("Trying to set a const global.")]
+    ImmutableGlobal,
+    #[error("Size of switch statement exceeds maximum.")]
+    SwitchTooLarge,
+    #[error("The number of globals must not exceed {max}.")]
+    TooManyGlobals {
+        max: usize,
+    },
+    #[error("Stack height would exceed allowed limits.")]
+    StackHeightExceeded,
+    #[error("Module exceeds maximum number of exports.")]
+    TooManyExports,
+    #[error("Disallowed import {mod_name}.{item_name}.")]
+    DisallowedImport {
+        mod_name:  Name,
+        item_name: Name,
+    },
+    #[error("Export function {name} not valid.")]
+    DisallowedExport {
+        name: Name,
+    },
+    #[error("Duplicate exports {name}.")]
+    DuplicateExport {
+        name: Name,
+    },
+    #[error("The number of functions in the function and code sections must match.")]
+    FunctionCountMismatch,
+    #[error("Initialization segment exceeds the initial size of the {kind}.")]
+    SegmentOutOfBounds {
+        /// Either `"table"` or `"memory"`.
+        kind: &'static str,
+    },
+    #[error("The {proposal} proposal is not enabled.")]
+    ProposalNotEnabled {
+        /// Name of the proposal, e.g., `"sign extension"`.
+        proposal: &'static str,
+    },
 }
 
-impl std::fmt::Display for ValidationError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ValidationError::TooManyLocals {
-                actual,
-                max,
-            } => write!(f, "The number of locals ({}) is more than allowed ({}).", actual, max),
+/// Configuration of the Wasm proposals, beyond the specification this module
+/// is based on, that are accepted by [validate_module]. This allows new
+/// instructions to be enabled by a protocol update while modules deployed
+/// before it are still validated under the rules that applied to them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValidationConfig {
+    /// Allow the instructions of the sign extension proposal,
+    /// `i32.extend8_s`, `i32.extend16_s`, `i64.extend8_s`, `i64.extend16_s`,
+    /// and `i64.extend32_s`.
+    pub allow_sign_extension_instr:   bool,
+    /// Allow the `memory.copy` and `memory.fill` instructions of the bulk
+    /// memory proposal. Passive data segments and the table instructions of
+    /// the proposal are not supported.
+    pub allow_bulk_memory:            bool,
+    /// Allow exports of mutable globals.
+    pub allow_mutable_global_exports: bool,
+}
+
+impl ValidationConfig {
+    /// Accept all supported proposals.
+    pub const ALL: Self = Self {
+        allow_sign_extension_instr:   true,
+        allow_bulk_memory:            true,
+        allow_mutable_global_exports: true,
+    };
+    /// The rules modules have been validated under before any proposals
+    /// could be enabled. Exports of mutable globals have always been
+    /// accepted.
+    pub const LEGACY: Self = Self {
+        allow_sign_extension_instr:   false,
+        allow_bulk_memory:            false,
+        allow_mutable_global_exports: true,
+    };
+
+    /// Ensure that the instruction is allowed by the configuration.
+    fn check_opcode(&self, opcode: &OpCode) -> ValidateResult<()> {
+        match opcode {
+            OpCode::I32Extend8S
+            | OpCode::I32Extend16S
+            | OpCode::I64Extend8S
+            | OpCode::I64Extend16S
+            | OpCode::I64Extend32S => {
+                ensure!(self.allow_sign_extension_instr, ValidationError::ProposalNotEnabled {
+                    proposal: "sign extension",
+                })
+            }
+            OpCode::MemoryCopy | OpCode::MemoryFill => {
+                ensure!(self.allow_bulk_memory, ValidationError::ProposalNotEnabled {
+                    proposal: "bulk memory",
+                })
+            }
+            _ => (),
         }
+        Ok(())
     }
 }
 
@@ -150,13 +278,13 @@ impl ValidationState {
     /// Pop a type from the stack and, if successful, return it.
     fn pop_opd(&mut self) -> ValidateResult<MaybeKnown> {
         match self.ctrls.stack.last() {
-            None => bail!("Control frame exhausted."),
+            None => bail!(ValidationError::ControlStackExhausted),
             Some(frame) => {
                 if self.opds.stack.len() == frame.height {
                     if frame.unreachable {
                         Ok(Unknown)
                     } else {
-                        bail!("Operand stack exhausted for the current block.")
+                        bail!(ValidationError::OperandStackExhausted)
                     }
                 } else {
                     self.opds
@@ -181,12 +309,12 @@ impl ValidationState {
         if expect.is_unknown() {
             return Ok(actual);
         }
-        ensure!(
-            actual == expect,
-            "Actual type different from expected {:#?} /= {:#?}.",
-            actual,
-            expect
-        );
+        if let (Known(actual), Known(expected)) = (actual, expect) {
+            ensure!(actual == expected, ValidationError::TypeMismatch {
+                expected,
+                actual,
+            });
+        }
         Ok(actual)
     }
 
@@ -233,12 +361,12 @@ impl ValidationState {
         // This is so that pop_expect_opd, which pops elements from the stack, can see
         // whether we are in the unreachable state for the stack or not.
         match self.ctrls.stack.last().map(|frame| (frame.end_type, frame.height, frame.is_if)) {
-            None => bail!("Control stack exhausted."),
+            None => bail!(ValidationError::ControlStackExhausted),
             Some((end_type, height, opcode)) => {
                 if let BlockType::ValueType(ty) = end_type {
                     self.pop_expect_opd(Known(ty))?;
                 }
-                ensure!(self.opds.stack.len() == height, "Operand stack not exhausted.");
+                ensure!(self.opds.stack.len() == height, ValidationError::OperandStackNotExhausted);
                 // Finally pop after we've made sure the stack is properly cleared.
                 self.ctrls.stack.pop();
                 Ok((end_type, opcode))
@@ -248,7 +376,7 @@ impl ValidationState {
 
     fn mark_unreachable(&mut self) -> ValidateResult<()> {
         match self.ctrls.stack.last_mut() {
-            None => bail!("Control stack exhausted."),
+            None => bail!(ValidationError::ControlStackExhausted),
             Some(frame) => {
                 self.opds.stack.truncate(frame.height);
                 frame.unreachable = true;
@@ -282,7 +410,10 @@ pub(crate) struct FunctionContext<'a> {
 /// This function additionally ensures that there are no more than
 /// ALLOWED_LOCALS local variables. Note that function parameters are included
 /// in locals.
-fn make_locals(ty: &FunctionType, locals: &[Local]) -> ValidateResult<(Vec<LocalsRange>, u32)> {
+pub(crate) fn make_locals(
+    ty: &FunctionType,
+    locals: &[Local],
+) -> ValidateResult<(Vec<LocalsRange>, u32)> {
     let mut out = Vec::with_capacity(ty.parameters.len() + locals.len());
     let mut start = 0;
     for &ty in ty.parameters.iter() {
@@ -295,8 +426,10 @@ fn make_locals(ty: &FunctionType, locals: &[Local]) -> ValidateResult<(Vec<Local
         start = end;
     }
     for local in locals.iter() {
-        let end =
-            start.checked_add(local.multiplicity).ok_or_else(|| anyhow!("Too many locals"))?;
+        let end = start.checked_add(local.multiplicity).ok_or(ValidationError::TooManyLocals {
+            actual: u32::MAX,
+            max:    ALLOWED_LOCALS,
+        })?;
         out.push(LocalsRange {
             start,
             end,
@@ -354,7 +487,10 @@ impl<'a> HasValidationContext for FunctionContext<'a> {
         });
         match res {
             Ok(idx) => Ok(self.locals[idx].ty),
-            Err(_) => bail!("Local index out of range."),
+            Err(_) => bail!(ValidationError::IndexOutOfRange {
+                kind:  "locals",
+                index: idx,
+            }),
         }
     }
 
@@ -363,7 +499,10 @@ impl<'a> HasValidationContext for FunctionContext<'a> {
         if let Some(global) = self.globals.get(idx as usize) {
             Ok((global.init.ty(), global.mutable))
         } else {
-            bail!("Global index out of range.")
+            bail!(ValidationError::IndexOutOfRange {
+                kind:  "globals",
+                index: idx,
+            })
         }
     }
 
@@ -375,12 +514,18 @@ impl<'a> HasValidationContext for FunctionContext<'a> {
         if let Some(&type_idx) = self.funcs.get(idx as usize) {
             self.get_type(type_idx)
         } else {
-            bail!("Function index out of range.")
+            bail!(ValidationError::IndexOutOfRange {
+                kind:  "functions",
+                index: idx,
+            })
         }
     }
 
     fn get_type(&self, idx: TypeIndex) -> ValidateResult<&Rc<FunctionType>> {
-        self.types.get(idx as usize).ok_or_else(|| anyhow!("Type index out of range."))
+        Ok(self.types.get(idx as usize).ok_or(ValidationError::IndexOutOfRange {
+            kind:  "types",
+            index: idx,
+        })?)
     }
 
     fn return_type(&self) -> BlockType { self.return_type }
@@ -396,20 +541,16 @@ enum Type {
 
 /// Ensure that the alignment is valid for the given type.
 fn ensure_alignment(num: u32, align: Type) -> ValidateResult<()> {
-    match align {
-        Type::I8 => {
-            ensure!(num == 0, "Type I8 alignment must be less than 0, but is {}.", num);
-        }
-        Type::I16 => {
-            ensure!(num <= 1, "Type I16 alignment must be less than 1, but is {}.", num);
-        }
-        Type::I32 => {
-            ensure!(num <= 2, "Type I32 alignment must be less than 2, but is {}", num);
-        }
-        Type::I64 => {
-            ensure!(num <= 3, "Type I64 alignment must be less than 3, but is {}.", num);
-        }
-    }
+    let max = match align {
+        Type::I8 => 0,
+        Type::I16 => 1,
+        Type::I32 => 2,
+        Type::I64 => 3,
+    };
+    ensure!(num <= max, ValidationError::InvalidAlignment {
+        align: num,
+        max,
+    });
     Ok(())
 }
 
@@ -480,10 +621,7 @@ pub fn validate<O: Borrow<OpCode>, H: Handler<O>>(
             OpCode::End => {
                 let (res, is_if) = state.pop_ctrl()?;
                 if is_if {
-                    ensure!(
-                        res == BlockType::EmptyType,
-                        "If without an else must have empty return type"
-                    )
+                    ensure!(res == BlockType::EmptyType, ValidationError::IfWithoutElseHasResult)
                 }
                 state.push_opds(res);
             }
@@ -507,7 +645,7 @@ pub fn validate<O: Borrow<OpCode>, H: Handler<O>>(
             }
             OpCode::Else => {
                 let (res, is_if) = state.pop_ctrl()?;
-                ensure!(is_if, "Else can only come after an if");
+                ensure!(is_if, ValidationError::ElseWithoutIf);
                 state.push_ctrl(false, res, res);
             }
             OpCode::Br(label) => {
@@ -515,7 +653,9 @@ pub fn validate<O: Borrow<OpCode>, H: Handler<O>>(
                     state.pop_opds(label_type)?;
                     state.mark_unreachable()?;
                 } else {
-                    bail!("Jump to a non-existent label.")
+                    bail!(ValidationError::UnknownLabel {
+                        label: *label,
+                    })
                 }
             }
             OpCode::BrIf(label) => {
@@ -524,33 +664,36 @@ pub fn validate<O: Borrow<OpCode>, H: Handler<O>>(
                     state.pop_opds(label_type)?;
                     state.push_opds(label_type);
                 } else {
-                    bail!("Conditional jump to non-existent label.")
+                    bail!(ValidationError::UnknownLabel {
+                        label: *label,
+                    })
                 }
             }
             OpCode::BrTable {
                 labels,
                 default,
             } => {
-                ensure!(
-                    labels.len() <= MAX_SWITCH_SIZE,
-                    "Size of switch statement exceeds maximum."
-                );
+                ensure!(labels.len() <= MAX_SWITCH_SIZE, ValidationError::SwitchTooLarge);
                 if let Some(default_label_type) = state.ctrls.get_label(*default) {
                     for &label in labels.iter() {
                         if let Some(target_frame) = state.ctrls.get(label) {
                             ensure!(
                                 default_label_type == target_frame.label_type,
-                                "Different targets have different label types."
+                                ValidationError::InconsistentLabelTypes
                             );
                         } else {
-                            bail!("Table jump to non-existent label.")
+                            bail!(ValidationError::UnknownLabel {
+                                label,
+                            })
                         }
                     }
                     state.pop_expect_opd(Known(ValueType::I32))?;
                     state.pop_opds(default_label_type)?;
                     state.mark_unreachable()?;
                 } else {
-                    bail!("Table jump to non-existent label.")
+                    bail!(ValidationError::UnknownLabel {
+                        label: *default,
+                    })
                 }
             }
             OpCode::Return => {
@@ -569,7 +712,7 @@ pub fn validate<O: Borrow<OpCode>, H: Handler<O>>(
                 }
             }
             OpCode::CallIndirect(idx) => {
-                ensure!(context.table_exists(), "Table with index 0 must exist.");
+                ensure!(context.table_exists(), ValidationError::MissingTable);
                 // the table type is valid by construction, there is only one.
                 let func = context.get_type(*idx)?;
                 state.pop_expect_opd(Known(ValueType::I32))?;
@@ -608,133 +751,138 @@ pub fn validate<O: Borrow<OpCode>, H: Handler<O>>(
             }
             OpCode::GlobalSet(idx) => {
                 let (ty, mutable) = context.get_global(*idx)?;
-                ensure!(mutable, "Trying to set a const global.");
+                ensure!(mutable, ValidationError::ImmutableGlobal);
                 state.pop_expect_opd(Known(ty))?;
             }
             OpCode::I32Load(memarg) => {
-                ensure!(context.memory_exists(), "Memory should exist.");
+                ensure!(context.memory_exists(), ValidationError::MissingMemory);
                 ensure_alignment(memarg.align, Type::I32)?;
                 state.pop_expect_opd(Known(ValueType::I32))?;
                 state.push_opd(Known(ValueType::I32));
             }
             OpCode::I64Load(memarg) => {
-                ensure!(context.memory_exists(), "Memory should exist.");
+                ensure!(context.memory_exists(), ValidationError::MissingMemory);
                 ensure_alignment(memarg.align, Type::I64)?;
                 state.pop_expect_opd(Known(ValueType::I32))?;
                 state.push_opd(Known(ValueType::I64));
             }
             OpCode::I32Load8S(memarg) => {
-                ensure!(context.memory_exists(), "Memory should exist.");
+                ensure!(context.memory_exists(), ValidationError::MissingMemory);
                 ensure_alignment(memarg.align, Type::I8)?;
                 state.pop_expect_opd(Known(ValueType::I32))?;
                 state.push_opd(Known(ValueType::I32));
             }
             OpCode::I32Load8U(memarg) => {
-                ensure!(context.memory_exists(), "Memory should exist.");
+                ensure!(context.memory_exists(), ValidationError::MissingMemory);
                 ensure_alignment(memarg.align, Type::I8)?;
                 state.pop_expect_opd(Known(ValueType::I32))?;
                 state.push_opd(Known(ValueType::I32));
             }
             OpCode::I32Load16S(memarg) => {
-                ensure!(context.memory_exists(), "Memory should exist.");
+                ensure!(context.memory_exists(), ValidationError::MissingMemory);
                 ensure_alignment(memarg.align, Type::I16)?;
                 state.pop_expect_opd(Known(ValueType::I32))?;
                 state.push_opd(Known(ValueType::I32));
             }
             OpCode::I32Load16U(memarg) => {
-                ensure!(context.memory_exists(), "Memory should exist.");
+                ensure!(context.memory_exists(), ValidationError::MissingMemory);
                 ensure_alignment(memarg.align, Type::I16)?;
                 state.pop_expect_opd(Known(ValueType::I32))?;
                 state.push_opd(Known(ValueType::I32));
             }
             OpCode::I64Load8S(memarg) => {
-                ensure!(context.memory_exists(), "Memory should exist.");
+                ensure!(context.memory_exists(), ValidationError::MissingMemory);
                 ensure_alignment(memarg.align, Type::I8)?;
                 state.pop_expect_opd(Known(ValueType::I32))?;
                 state.push_opd(Known(ValueType::I64));
             }
             OpCode::I64Load8U(memarg) => {
-                ensure!(context.memory_exists(), "Memory should exist.");
+                ensure!(context.memory_exists(), ValidationError::MissingMemory);
                 ensure_alignment(memarg.align, Type::I8)?;
-                ensure!(memarg.align == 0, "Alignment out of range");
                 state.pop_expect_opd(Known(ValueType::I32))?;
                 state.push_opd(Known(ValueType::I64));
             }
             OpCode::I64Load16S(memarg) => {
-                ensure!(context.memory_exists(), "Memory should exist.");
+                ensure!(context.memory_exists(), ValidationError::MissingMemory);
                 ensure_alignment(memarg.align, Type::I16)?;
                 state.pop_expect_opd(Known(ValueType::I32))?;
                 state.push_opd(Known(ValueType::I64));
             }
             OpCode::I64Load16U(memarg) => {
-                ensure!(context.memory_exists(), "Memory should exist.");
+                ensure!(context.memory_exists(), ValidationError::MissingMemory);
                 ensure_alignment(memarg.align, Type::I16)?;
                 state.pop_expect_opd(Known(ValueType::I32))?;
                 state.push_opd(Known(ValueType::I64));
             }
             OpCode::I64Load32S(memarg) => {
-                ensure!(context.memory_exists(), "Memory should exist.");
+                ensure!(context.memory_exists(), ValidationError::MissingMemory);
                 ensure_alignment(memarg.align, Type::I32)?;
                 state.pop_expect_opd(Known(ValueType::I32))?;
                 state.push_opd(Known(ValueType::I64));
             }
             OpCode::I64Load32U(memarg) => {
-                ensure!(context.memory_exists(), "Memory should exist.");
+                ensure!(context.memory_exists(), ValidationError::MissingMemory);
                 ensure_alignment(memarg.align, Type::I32)?;
                 state.pop_expect_opd(Known(ValueType::I32))?;
                 state.push_opd(Known(ValueType::I64));
             }
             OpCode::I32Store(memarg) => {
-                ensure!(context.memory_exists(), "Memory should exist.");
+                ensure!(context.memory_exists(), ValidationError::MissingMemory);
                 ensure_alignment(memarg.align, Type::I32)?;
                 state.pop_expect_opd(Known(ValueType::I32))?;
                 state.pop_expect_opd(Known(ValueType::I32))?;
             }
             OpCode::I64Store(memarg) => {
-                ensure!(context.memory_exists(), "Memory should exist.");
+                ensure!(context.memory_exists(), ValidationError::MissingMemory);
                 ensure_alignment(memarg.align, Type::I64)?;
                 state.pop_expect_opd(Known(ValueType::I64))?;
                 state.pop_expect_opd(Known(ValueType::I32))?;
             }
             OpCode::I32Store8(memarg) => {
-                ensure!(context.memory_exists(), "Memory should exist.");
+                ensure!(context.memory_exists(), ValidationError::MissingMemory);
                 ensure_alignment(memarg.align, Type::I8)?;
                 state.pop_expect_opd(Known(ValueType::I32))?;
                 state.pop_expect_opd(Known(ValueType::I32))?;
             }
             OpCode::I32Store16(memarg) => {
-                ensure!(context.memory_exists(), "Memory should exist.");
+                ensure!(context.memory_exists(), ValidationError::MissingMemory);
                 ensure_alignment(memarg.align, Type::I16)?;
                 state.pop_expect_opd(Known(ValueType::I32))?;
                 state.pop_expect_opd(Known(ValueType::I32))?;
             }
             OpCode::I64Store8(memarg) => {
-                ensure!(context.memory_exists(), "Memory should exist.");
+                ensure!(context.memory_exists(), ValidationError::MissingMemory);
                 ensure_alignment(memarg.align, Type::I8)?;
                 state.pop_expect_opd(Known(ValueType::I64))?;
                 state.pop_expect_opd(Known(ValueType::I32))?;
             }
             OpCode::I64Store16(memarg) => {
-                ensure!(context.memory_exists(), "Memory should exist.");
+                ensure!(context.memory_exists(), ValidationError::MissingMemory);
                 ensure_alignment(memarg.align, Type::I16)?;
                 state.pop_expect_opd(Known(ValueType::I64))?;
                 state.pop_expect_opd(Known(ValueType::I32))?;
             }
             OpCode::I64Store32(memarg) => {
-                ensure!(context.memory_exists(), "Memory should exist.");
+                ensure!(context.memory_exists(), ValidationError::MissingMemory);
                 ensure_alignment(memarg.align, Type::I32)?;
                 state.pop_expect_opd(Known(ValueType::I64))?;
                 state.pop_expect_opd(Known(ValueType::I32))?;
             }
             OpCode::MemorySize => {
-                ensure!(context.memory_exists(), "Memory should exist.");
+                ensure!(context.memory_exists(), ValidationError::MissingMemory);
                 state.push_opd(Known(ValueType::I32))
             }
             OpCode::MemoryGrow => {
-                ensure!(context.memory_exists(), "Memory should exist.");
+                ensure!(context.memory_exists(), ValidationError::MissingMemory);
                 state.pop_expect_opd(Known(ValueType::I32))?;
                 state.push_opd(Known(ValueType::I32))
             }
+            OpCode::MemoryCopy | OpCode::MemoryFill => {
+                ensure!(context.memory_exists(), ValidationError::MissingMemory);
+                state.pop_expect_opd(Known(ValueType::I32))?;
+                state.pop_expect_opd(Known(ValueType::I32))?;
+                state.pop_expect_opd(Known(ValueType::I32))?;
+            }
             OpCode::I32Const(_) => {
                 state.push_opd(Known(ValueType::I32));
             }
@@ -831,13 +979,21 @@ pub fn validate<O: Borrow<OpCode>, H: Handler<O>>(
                 state.pop_expect_opd(Known(ValueType::I32))?;
                 state.push_opd(Known(ValueType::I64));
             }
+            OpCode::I32Extend8S | OpCode::I32Extend16S => {
+                state.pop_expect_opd(Known(ValueType::I32))?;
+                state.push_opd(Known(ValueType::I32));
+            }
+            OpCode::I64Extend8S | OpCode::I64Extend16S | OpCode::I64Extend32S => {
+                state.pop_expect_opd(Known(ValueType::I64))?;
+                state.push_opd(Known(ValueType::I64));
+            }
         }
         handler.handle_opcode(&state, old_stack_height, next_opcode)?;
     }
     if state.done() {
         handler.finish(&state)
     } else {
-        bail!("Improperly terminated instruction sequence.")
+        bail!(ValidationError::ImproperlyTerminated)
     }
 }
 
@@ -859,8 +1015,10 @@ pub trait ValidateImportExport {
 }
 
 /// Validate the module. This function parses and validates the module at the
-/// same time, failing at the first encountered error.
+/// same time, failing at the first encountered error. Instructions and exports
+/// of the proposals that are not enabled by the `config` are rejected.
 pub fn validate_module<'a>(
+    config: &ValidationConfig,
     imp: &impl ValidateImportExport,
     skeleton: &Skeleton<'a>,
 ) -> ValidateResult<Module> {
@@ -886,10 +1044,16 @@ pub fn validate_module<'a>(
                         let is_new = seen_imports.insert((&i.mod_name, &i.item_name));
                         ensure!(
                             imp.validate_import_function(!is_new, &i.mod_name, &i.item_name, ty),
-                            "Disallowed import."
+                            ValidationError::DisallowedImport {
+                                mod_name:  i.mod_name.clone(),
+                                item_name: i.item_name.clone(),
+                            }
                         );
                     } else {
-                        bail!("Import refers to a non-existent type.");
+                        bail!(ValidationError::IndexOutOfRange {
+                            kind:  "types",
+                            index: type_idx,
+                        });
                     }
                 }
             }
@@ -907,11 +1071,9 @@ pub fn validate_module<'a>(
     // We already check that all the globals are initialized with
     // correct expressions.
     let global: GlobalSection = parse_sec_with_default(EMPTY_CTX, &skeleton.global)?;
-    ensure!(
-        global.globals.len() <= MAX_NUM_GLOBALS,
-        "The number of globals must not exceed {}.",
-        MAX_NUM_GLOBALS
-    );
+    ensure!(global.globals.len() <= MAX_NUM_GLOBALS, ValidationError::TooManyGlobals {
+        max: MAX_NUM_GLOBALS,
+    });
 
     // The start section is valid as long as it parses correctly.
     // We make sure that there is no content in the start section during parsing.
@@ -922,7 +1084,10 @@ pub fn validate_module<'a>(
     // The code section then needs to match.
     let func: FunctionSection = parse_sec_with_default(EMPTY_CTX, &skeleton.func)?;
     for &type_idx in func.types.iter() {
-        ensure!(ty.get(type_idx).is_some(), "Function refers to a type that does not exist.")
+        ensure!(ty.get(type_idx).is_some(), ValidationError::IndexOutOfRange {
+            kind:  "types",
+            index: type_idx,
+        })
     }
 
     // Number of functions that can be referred to.
@@ -933,10 +1098,7 @@ pub fn validate_module<'a>(
         import.imports.iter().filter(|&x| Import::is_func(x)).count() + func.types.len();
 
     let code: CodeSkeletonSection = parse_sec_with_default(EMPTY_CTX, &skeleton.code)?;
-    ensure!(
-        func.types.len() == code.impls.len(),
-        "The number of functions in the function and code sections must match."
-    );
+    ensure!(func.types.len() == code.impls.len(), ValidationError::FunctionCountMismatch);
     // an index of function types, merging imported and declared functions.
     let funcs = import
         .imports
@@ -963,11 +1125,12 @@ pub fn validate_module<'a>(
                     memory: memory.memory_type.is_some(),
                     table: table.table_type.is_some(),
                 };
-                let (opcodes, max_height) =
-                    validate(&ctx, &mut OpCodeIterator::new(c.expr_bytes), Vec::new())?;
+                let opcodes = OpCodeIterator::new(c.expr_bytes)
+                    .map(|opcode| opcode.and_then(|op| config.check_opcode(&op).map(|_| op)));
+                let (opcodes, max_height) = validate(&ctx, opcodes, Vec::new())?;
                 ensure!(
                     num_locals as usize + max_height <= MAX_ALLOWED_STACK_HEIGHT,
-                    "Stack height would exceed allowed limits."
+                    ValidationError::StackHeightExceeded
                 );
 
                 let code = Code {
@@ -981,46 +1144,59 @@ pub fn validate_module<'a>(
                 };
                 parsed_code.push(code)
             }
-            None => bail!("Function has a type that does not exist."),
+            None => bail!(ValidationError::IndexOutOfRange {
+                kind:  "types",
+                index: f,
+            }),
         }
     }
     // Exports are mostly valid by parsing, but we need to make sure that
     // they are all distinct.
     let export: ExportSection = parse_sec_with_default(EMPTY_CTX, &skeleton.export)?;
     let mut export_names = BTreeSet::new();
-    ensure!(export.exports.len() <= MAX_NUM_EXPORTS, "Module exceeds maximum number of exports.");
+    ensure!(export.exports.len() <= MAX_NUM_EXPORTS, ValidationError::TooManyExports);
     for e in export.exports.iter() {
         // ensure the name is unique.
-        ensure!(export_names.insert(&e.name), "Duplicate exports {}.", e.name);
+        ensure!(export_names.insert(&e.name), ValidationError::DuplicateExport {
+            name: e.name.clone(),
+        });
 
         match e.description {
             ExportDescription::Func {
                 index,
             } => {
                 if let Some(ty) = funcs.get(index as usize).and_then(|ty_idx| ty.get(*ty_idx)) {
-                    ensure!(imp.validate_export_function(&e.name, ty), "Export function not valid.")
+                    ensure!(
+                        imp.validate_export_function(&e.name, ty),
+                        ValidationError::DisallowedExport {
+                            name: e.name.clone(),
+                        }
+                    )
                 } else {
-                    bail!("Trying to export a function that does not exist.")
+                    bail!(ValidationError::IndexOutOfRange {
+                        kind: "functions",
+                        index,
+                    })
                 }
             }
             ExportDescription::Table => {
-                ensure!(
-                    table.table_type.is_some(),
-                    "Trying to export a table, but no table is declared."
-                );
+                ensure!(table.table_type.is_some(), ValidationError::MissingTable);
             }
             ExportDescription::Memory => {
-                ensure!(
-                    memory.memory_type.is_some(),
-                    "Trying to export a memory, but no memory is declared."
-                );
+                ensure!(memory.memory_type.is_some(), ValidationError::MissingMemory);
             }
             ExportDescription::Global {
                 index,
             } => {
+                let g = global.get(index).ok_or(ValidationError::IndexOutOfRange {
+                    kind: "globals",
+                    index,
+                })?;
                 ensure!(
-                    global.get(index).is_some(),
-                    "Trying to export a global that does not exist."
+                    !g.mutable || config.allow_mutable_global_exports,
+                    ValidationError::ProposalNotEnabled {
+                        proposal: "mutable globals export",
+                    }
                 );
             }
         }
@@ -1034,35 +1210,28 @@ pub fn validate_module<'a>(
     let element: ElementSection = parse_sec_with_default(&global, &skeleton.element)?;
     ensure!(
         element.elements.is_empty() || table.table_type.is_some(),
-        "There is an elements section, but no table."
+        ValidationError::MissingTable
     );
+    let table_out_of_bounds = || ValidationError::SegmentOutOfBounds {
+        kind: "table",
+    };
     for elem in element.elements.iter() {
-        let inits_len: u32 = elem.inits.len().try_into()?;
-        ensure!(
-            inits_len <= MAX_INIT_TABLE_SIZE,
-            "Number of initial elements is more than the table size."
-        );
+        let inits_len: u32 = elem.inits.len().try_into().map_err(|_| table_out_of_bounds())?;
+        ensure!(inits_len <= MAX_INIT_TABLE_SIZE, table_out_of_bounds());
         if let Some(table_type) = table.table_type.as_ref() {
             let offset = elem.offset as u32;
             // since we provide no way to grow the table the initial minimum size
             // is the size of the table, as specified in the allocation section of the
             // Wasm semantics.
             // The as u32 is safe beca
-            let end = offset
-                .checked_add(inits_len)
-                .ok_or_else(|| anyhow!("The end of the table exceeds u32 max bound."))?;
-            ensure!(
-                end <= table_type.limits.min,
-                "Initialization expression for the table exceeds table size {} > {}.",
-                end,
-                table_type.limits.min
-            );
+            let end = offset.checked_add(inits_len).ok_or_else(table_out_of_bounds)?;
+            ensure!(end <= table_type.limits.min, table_out_of_bounds());
         }
         for &init in elem.inits.iter() {
-            ensure!(
-                (init as usize) < total_funcs,
-                "Index in the element segment refers to a non-existent function."
-            );
+            ensure!((init as usize) < total_funcs, ValidationError::IndexOutOfRange {
+                kind:  "functions",
+                index: init,
+            });
         }
     }
 
@@ -1076,31 +1245,29 @@ pub fn validate_module<'a>(
     // By parsing we already ensure that all the references are to a single memory
     // and that the initial memory is limited by MAX_INIT_MEMORY_SIZE.
     if let Some(memory_type) = memory.memory_type.as_ref() {
+        let memory_out_of_bounds = || ValidationError::SegmentOutOfBounds {
+            kind: "memory",
+        };
         for data in data.sections.iter() {
-            let inits_len: u32 = data.init.len().try_into()?;
+            let inits_len: u32 = data.init.len().try_into().map_err(|_| memory_out_of_bounds())?;
             ensure!(
                 // this cannot overflow because we've already ensured limits.min <
                 // MAX_INIT_MEMORY_SIZE
                 inits_len <= memory_type.limits.min * PAGE_SIZE,
-                "Number of initial elements is more than the initial memory size."
+                memory_out_of_bounds()
             );
-            let offset: u32 = data.offset.try_into()?;
-            let end = offset
-                .checked_add(inits_len)
-                .ok_or_else(|| anyhow!("The end of the memory exceeds u32 max bound."))?;
+            let offset: u32 = data.offset.try_into().map_err(|_| memory_out_of_bounds())?;
+            let end = offset.checked_add(inits_len).ok_or_else(memory_out_of_bounds)?;
             ensure!(
                 // by validation we have that memory_type.limits.min <= MAX_INIT_MEMORY_SIZE <
                 // 2^16, so this cannot overflow but we're still being safe
                 memory_type.limits.min.checked_mul(PAGE_SIZE).map_or(false, |l| end <= l),
-                "Initialization expression for the data segment exceeds initial memory size {} > \
-                 {}.",
-                end,
-                memory_type.limits.min * PAGE_SIZE
+                memory_out_of_bounds()
             );
         }
     } else {
         // There is no memory, so there should be no data section.
-        ensure!(data.sections.is_empty(), "There are data sections, but no declared memory.");
+        ensure!(data.sections.is_empty(), ValidationError::MissingMemory);
     }
     Ok(Module {
         ty,